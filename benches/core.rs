@@ -0,0 +1,89 @@
+//! Times serialization, hashing and simulation over synthetic workloads,
+//! see `command_networking_rs::bench`. Run with
+//! `cargo bench --features bench`, optionally followed by a filter on the
+//! benchmark names.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use command_networking_rs::bench::{game_with_players, simulate, synthetic_script, synthetic_tick};
+use command_networking_rs::hash::Hasher;
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Tick};
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use PlayerIntent::*;
+
+fn serialization(c: &mut Criterion) {
+	let mut group = c.benchmark_group("tick");
+	for players in [2, 8, 32] {
+		let tick = synthetic_tick(1000, players);
+		let mut writer = ByteWriter::new();
+		group.bench_function(BenchmarkId::new("to_bytes", players), |b| b.iter(|| {
+			writer.clear();
+			black_box(&tick).to_bytes(&mut writer).unwrap();
+			writer.len()
+		}));
+
+		writer.clear();
+		tick.to_bytes(&mut writer).unwrap();
+		let bytes = writer.as_slice();
+		group.bench_function(BenchmarkId::new("from_bytes", players), |b| b.iter(|| {
+			Tick::<PlayerIntent>::from_bytes(&mut ByteReader::new(black_box(bytes))).unwrap()
+		}));
+	}
+	group.finish();
+}
+
+/// Writing an 8-player tick with three intents per player, into a buffer
+/// growing as it goes, and into one reserved up front from `byte_len_hint`.
+fn reservation(c: &mut Criterion) {
+	let command_frames = (0..8)
+		.map(|owner| CommandFrame::new(owner, match owner % 2 {
+			0 => vec![MoveLeft, JumpStart, JumpHold],
+			_ => vec![MoveRight, JumpHold, MoveHorizontal(-64)],
+		}))
+		.collect();
+	let tick: Tick<PlayerIntent> = Tick::new(1000, command_frames);
+	let mut writer = ByteWriter::new();
+	tick.to_bytes(&mut writer).unwrap();
+	assert!(tick.byte_len_hint() >= writer.len(), "the hint falls short of the {} bytes written", writer.len());
+
+	let mut group = c.benchmark_group("to_bytes/8p_3i");
+	group.bench_function("growing", |b| b.iter(|| {
+		let mut writer = ByteWriter::new();
+		black_box(&tick).to_bytes(&mut writer).unwrap();
+		writer
+	}));
+	group.bench_function("reserved", |b| b.iter(|| {
+		let tick = black_box(&tick);
+		let mut writer = ByteWriter::with_capacity(tick.byte_len_hint());
+		tick.to_bytes(&mut writer).unwrap();
+		writer
+	}));
+	group.finish();
+}
+
+fn hashing(c: &mut Criterion) {
+	let mut group = c.benchmark_group("tick");
+	for players in [2, 8, 32] {
+		let tick = synthetic_tick(1000, players);
+		group.bench_function(BenchmarkId::new("hash", players), |b| b.iter(|| black_box(&tick).hash_with::<Hasher>()));
+	}
+	group.finish();
+}
+
+fn simulation(c: &mut Criterion) {
+	const PLAYERS: u16 = 8;
+	const TICKS: u64 = 100;
+	let script = synthetic_script(TICKS, PLAYERS);
+	c.bench_function(&format!("game/simulate/{}x{}", PLAYERS, TICKS), |b| b.iter(|| {
+		let mut game = game_with_players(PLAYERS);
+		for tick in &script {
+			simulate(&mut game, tick);
+		}
+		game
+	}));
+}
+
+criterion_group!(benches, serialization, reservation, hashing, simulation);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! Times hashing a tick with every player there's room for on screen,
+//! with SHA-256 and with CRC-32. Run with `cargo bench --bench hashers`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use command_networking_rs::hash::{Crc32Hasher, Sha256Hasher, TickHasher};
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Tick};
+use PlayerIntent::*;
+
+const PLAYERS: u16 = 16;
+
+fn hashers(c: &mut Criterion) {
+	// The standard check value, for the checksum of "123456789".
+	let mut crc = Crc32Hasher::default();
+	crc.update(b"123456789");
+	assert_eq!(u32::from_le_bytes(crc.finish()), 0xcbf43926);
+
+	let command_frames = (0..PLAYERS)
+		.map(|owner| CommandFrame::new(owner, match owner % 3 {
+			0 => vec![MoveLeft, JumpStart],
+			1 => vec![MoveRight, JumpHold],
+			_ => vec![MoveHorizontal(-64)],
+		}))
+		.collect();
+	let tick: Tick<PlayerIntent> = Tick::new(1000, command_frames);
+
+	let mut group = c.benchmark_group(format!("hash/{}p", PLAYERS));
+	group.bench_function("sha256", |b| b.iter(|| black_box(&tick).hash_with::<Sha256Hasher>()));
+	group.bench_function("crc32", |b| b.iter(|| black_box(&tick).hash_with::<Crc32Hasher>()));
+	group.finish();
+}
+
+criterion_group!(benches, hashers);
+criterion_main!(benches);
@@ -0,0 +1,333 @@
+//! `#[derive(NetType)]` for the command networking demo.
+//!
+//! Structs are serialized field by field, in declaration order. Fieldless
+//! `#[repr(u8)]` enums are serialized as a single tag byte holding the
+//! variant's discriminant.
+//!
+//! Generic structs get a `NetType` bound on each of their type parameters.
+//! Lifetimes, const generics and `where` clauses aren't supported.
+//!
+//! The generated code refers to `crate::NetType`, `crate::ByteWriter`,
+//! `crate::ByteReader`, `crate::EncodeError` and `crate::DecodeError`, so all of them need to be reachable from the root
+//! of the crate using the derive.
+
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+#[proc_macro_derive(NetType)]
+pub fn derive_net_type(input: TokenStream) -> TokenStream {
+	let output = match parse_item(input) {
+		Ok(item) => expand(&item),
+		Err(message) => format!("compile_error!({:?});", message),
+	};
+	output.parse().unwrap()
+}
+
+/// The parts of a type definition the derive cares about.
+struct Item {
+	name: String,
+	generics: Vec<Generic>,
+	shape: Shape,
+}
+
+/// A type parameter, and the bounds it was declared with.
+struct Generic {
+	name: String,
+	bounds: String,
+}
+
+enum Shape {
+	NamedStruct(Vec<String>),
+	TupleStruct(usize),
+	UnitStruct,
+	/// Variant names alongside their discriminants.
+	Enum(Vec<(String, u8)>),
+}
+
+fn parse_item(input: TokenStream) -> Result<Item, String> {
+	let mut tokens = input.into_iter().peekable();
+	let mut repr_u8 = false;
+
+	// Outer attributes and visibility come before the item itself.
+	loop {
+		match tokens.peek() {
+			Some(TokenTree::Punct(punct)) if punct.as_char() == '#' => {
+				tokens.next();
+				if let Some(TokenTree::Group(group)) = tokens.next() {
+					repr_u8 |= is_repr_u8(&group);
+				}
+			},
+			Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+				tokens.next();
+				if let Some(TokenTree::Group(group)) = tokens.peek() {
+					if group.delimiter() == Delimiter::Parenthesis {
+						tokens.next();
+					}
+				}
+			},
+			_ => break,
+		}
+	}
+
+	let keyword = match tokens.next() {
+		Some(TokenTree::Ident(ident)) => ident.to_string(),
+		_ => return Err("expected a struct or an enum".into()),
+	};
+
+	let name = match tokens.next() {
+		Some(TokenTree::Ident(ident)) => ident.to_string(),
+		_ => return Err("expected a type name".into()),
+	};
+
+	let mut generics = vec![];
+	if matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == '<') {
+		tokens.next();
+		let mut parameters = vec![];
+		let mut depth = 1usize;
+		loop {
+			let token = tokens.next().ok_or("unterminated generic parameters")?;
+			if let TokenTree::Punct(punct) = &token {
+				match punct.as_char() {
+					'<' => depth += 1,
+					'>' => {
+						depth -= 1;
+						if depth == 0 {
+							break;
+						}
+					},
+					_ => {},
+				}
+			}
+			parameters.push(token);
+		}
+		generics = parse_generics(parameters.into_iter().collect())?;
+	}
+
+	let body = tokens.next();
+	if let Some(TokenTree::Ident(ident)) = &body {
+		if ident.to_string() == "where" {
+			return Err("NetType can't be derived for types with where clauses yet".into());
+		}
+	}
+
+	let shape = match (keyword.as_str(), body) {
+		("struct", Some(TokenTree::Group(group))) if group.delimiter() == Delimiter::Brace => {
+			Shape::NamedStruct(parse_named_fields(&group)?)
+		},
+		("struct", Some(TokenTree::Group(group))) if group.delimiter() == Delimiter::Parenthesis => {
+			Shape::TupleStruct(split_top_level(group.stream()).len())
+		},
+		("struct", Some(TokenTree::Punct(punct))) if punct.as_char() == ';' => {
+			Shape::UnitStruct
+		},
+		("enum", Some(TokenTree::Group(group))) if group.delimiter() == Delimiter::Brace => {
+			if !generics.is_empty() {
+				return Err("NetType can't be derived for generic enums".into());
+			}
+			if !repr_u8 {
+				return Err("NetType can only be derived for enums marked #[repr(u8)]".into());
+			}
+			Shape::Enum(parse_variants(&group)?)
+		},
+		("union", _) => return Err("NetType can't be derived for unions".into()),
+		_ => return Err("expected a struct or an enum".into()),
+	};
+
+	Ok(Item { name, generics, shape })
+}
+
+fn parse_generics(stream: TokenStream) -> Result<Vec<Generic>, String> {
+	split_top_level(stream)
+		.iter()
+		.map(|segment| match segment.as_slice() {
+			[TokenTree::Punct(punct), ..] if punct.as_char() == '\'' => {
+				Err("NetType can't be derived for types with lifetime parameters yet".to_string())
+			},
+			[TokenTree::Ident(ident), ..] if ident.to_string() == "const" => {
+				Err("NetType can't be derived for types with const parameters yet".to_string())
+			},
+			[TokenTree::Ident(name), rest @ ..] => {
+				// Defaults only belong on the type definition, not on the impl.
+				let bounds: String = match rest {
+					[TokenTree::Punct(colon), bounds @ ..] if colon.as_char() == ':' => bounds.iter()
+						.take_while(|x| !matches!(x, TokenTree::Punct(eq) if eq.as_char() == '='))
+						.map(|x| x.to_string() + " ")
+						.collect(),
+					_ => String::new(),
+				};
+				Ok(Generic { name: name.to_string(), bounds })
+			},
+			_ => Err("couldn't parse generic parameter".to_string()),
+		})
+		.collect()
+}
+
+fn is_repr_u8(attribute: &Group) -> bool {
+	let tokens: Vec<TokenTree> = attribute.stream().into_iter().collect();
+	match tokens.as_slice() {
+		[TokenTree::Ident(ident), TokenTree::Group(args)] if ident.to_string() == "repr" => {
+			args.stream().into_iter().any(|x| x.to_string() == "u8")
+		},
+		_ => false,
+	}
+}
+
+/// Splits a comma separated list, ignoring commas nested in generic arguments.
+fn split_top_level(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+	let mut segments = vec![];
+	let mut current = vec![];
+	let mut depth = 0usize;
+	let mut previous_char = None;
+
+	for token in stream {
+		if let TokenTree::Punct(punct) = &token {
+			match punct.as_char() {
+				'<' => depth += 1,
+				// `->` in function pointer types isn't a closing bracket.
+				'>' if previous_char != Some('-') => depth = depth.saturating_sub(1),
+				',' if depth == 0 => {
+					segments.push(std::mem::take(&mut current));
+					previous_char = None;
+					continue;
+				},
+				_ => {},
+			}
+			previous_char = Some(punct.as_char());
+		} else {
+			previous_char = None;
+		}
+		current.push(token);
+	}
+
+	if !current.is_empty() {
+		segments.push(current);
+	}
+	segments
+}
+
+/// Drops leading attributes and visibility modifiers from a field or variant.
+fn strip_prefix(segment: &[TokenTree]) -> &[TokenTree] {
+	let mut rest = segment;
+	loop {
+		match rest {
+			[TokenTree::Punct(punct), TokenTree::Group(_), tail @ ..] if punct.as_char() == '#' => rest = tail,
+			[TokenTree::Ident(ident), TokenTree::Group(group), tail @ ..]
+				if ident.to_string() == "pub" && group.delimiter() == Delimiter::Parenthesis => rest = tail,
+			[TokenTree::Ident(ident), tail @ ..] if ident.to_string() == "pub" => rest = tail,
+			_ => return rest,
+		}
+	}
+}
+
+fn parse_named_fields(body: &Group) -> Result<Vec<String>, String> {
+	split_top_level(body.stream())
+		.iter()
+		.map(|segment| match strip_prefix(segment) {
+			[TokenTree::Ident(name), TokenTree::Punct(colon), ..] if colon.as_char() == ':' => Ok(name.to_string()),
+			_ => Err("couldn't parse struct field".to_string()),
+		})
+		.collect()
+}
+
+fn parse_variants(body: &Group) -> Result<Vec<(String, u8)>, String> {
+	let mut variants = vec![];
+	let mut next_discriminant = 0u16;
+
+	for segment in split_top_level(body.stream()) {
+		let (name, discriminant) = match strip_prefix(&segment) {
+			[TokenTree::Ident(name)] => (name.to_string(), next_discriminant),
+			[TokenTree::Ident(name), TokenTree::Punct(eq), TokenTree::Literal(value)] if eq.as_char() == '=' => {
+				let digits = value.to_string();
+				let digits = digits.trim_end_matches("u8");
+				let discriminant = digits.parse::<u16>()
+					.map_err(|_| format!("discriminant of {} must be an integer literal", name))?;
+				(name.to_string(), discriminant)
+			},
+			[TokenTree::Ident(name), TokenTree::Group(_), ..] => {
+				return Err(format!("NetType can't be derived for enums with payloads yet (variant {})", name));
+			},
+			_ => return Err("couldn't parse enum variant".into()),
+		};
+
+		let discriminant = u8::try_from(discriminant)
+			.map_err(|_| format!("discriminant of {} doesn't fit in a u8", name))?;
+		variants.push((name, discriminant));
+		next_discriminant = discriminant as u16 + 1;
+	}
+
+	if variants.is_empty() {
+		return Err("NetType can't be derived for enums without variants".into());
+	}
+	Ok(variants)
+}
+
+fn expand(item: &Item) -> String {
+	let (to_bytes, from_bytes, len_hint) = match &item.shape {
+		Shape::NamedStruct(fields) => {
+			let writes: String = fields.iter()
+				.map(|x| format!("crate::NetType::to_bytes(&self.{}, writer)?;", x))
+				.collect();
+			let reads: String = fields.iter()
+				.map(|x| format!("{}: crate::NetType::from_bytes(reader)?,", x))
+				.collect();
+			let hints: String = fields.iter()
+				.map(|x| format!(" + crate::NetType::byte_len_hint(&self.{})", x))
+				.collect();
+			(writes, format!("Ok(Self {{ {} }})", reads), format!("0{}", hints))
+		},
+		Shape::TupleStruct(len) => {
+			let writes: String = (0..*len)
+				.map(|x| format!("crate::NetType::to_bytes(&self.{}, writer)?;", x))
+				.collect();
+			let reads: String = (0..*len)
+				.map(|_| "crate::NetType::from_bytes(reader)?,")
+				.collect();
+			let hints: String = (0..*len)
+				.map(|x| format!(" + crate::NetType::byte_len_hint(&self.{})", x))
+				.collect();
+			(writes, format!("Ok(Self({}))", reads), format!("0{}", hints))
+		},
+		Shape::UnitStruct => {
+			("let _ = writer;".to_string(), "let _ = reader; Ok(Self)".to_string(), "0".to_string())
+		},
+		Shape::Enum(variants) => {
+			let tags: String = variants.iter()
+				.map(|(name, tag)| format!("Self::{} => {}u8,", name, tag))
+				.collect();
+			let matches: String = variants.iter()
+				.map(|(name, tag)| format!("{}u8 => Ok(Self::{}),", tag, name))
+				.collect();
+			(
+				format!("let tag = match self {{ {} }}; crate::NetType::to_bytes(&tag, writer)?;", tags),
+				format!(
+					"match <u8 as crate::NetType>::from_bytes(reader)? {{ {} tag => Err(crate::DecodeError::InvalidTag {{ ty: {:?}, tag }}) }}",
+					matches,
+					item.name
+				),
+				"1".to_string(),
+			)
+		},
+	};
+
+	let parameters: Vec<&str> = item.generics.iter().map(|x| x.name.as_str()).collect();
+	let (impl_generics, type_generics) = if parameters.is_empty() {
+		(String::new(), String::new())
+	} else {
+		let declarations: Vec<String> = item.generics.iter()
+			.map(|x| if x.bounds.is_empty() {
+				format!("{}: crate::NetType", x.name)
+			} else {
+				format!("{}: crate::NetType + {}", x.name, x.bounds)
+			})
+			.collect();
+		(format!("<{}>", declarations.join(", ")), format!("<{}>", parameters.join(", ")))
+	};
+
+	format!(
+		"impl{impl_generics} crate::NetType for {name}{type_generics} {{
+			fn to_bytes(&self, writer: &mut crate::ByteWriter) -> Result<(), crate::EncodeError> {{ {to_bytes} Ok(()) }}
+			fn from_bytes(reader: &mut crate::ByteReader) -> Result<Self, crate::DecodeError> {{ {from_bytes} }}
+			fn byte_len_hint(&self) -> usize {{ {len_hint} }}
+		}}",
+		name = item.name,
+	)
+}
\ No newline at end of file
@@ -0,0 +1,129 @@
+//! A small mutation fuzzer for the decoders, for when cargo-fuzz isn't
+//! around; see `fuzz/`. Mutates the seed inputs from
+//! `command_networking_rs::fuzzing`, checking that decoding never panics
+//! and never allocates more than `ALLOCATION_BOUND` at once.
+//!
+//! Runs `ROUNDS` inputs by default, or as many as given:
+//! `cargo run --release --example decode_fuzz --features fuzzing -- <inputs> <seed>`.
+//! With `--write-corpus <dir>` it writes out the seeds for cargo-fuzz instead.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use command_networking_rs::fuzzing::{check, decode, seeds, ALLOCATION_BOUND};
+use command_networking_rs::protocol::MAX_PACKET_SIZE;
+
+/// Keeps track of the largest single allocation made.
+struct TrackingAllocator;
+
+static LARGEST_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		LARGEST_ALLOCATION.fetch_max(layout.size(), Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+const ROUNDS: u64 = 20_000;
+
+/// Inputs which once broke decoding, kept so that they never do again.
+const REGRESSIONS: &[&[u8]] = &[];
+
+/// Xorshift; mutations don't need anything better.
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn below(&mut self, max: usize) -> usize {
+		(self.next() % max.max(1) as u64) as usize
+	}
+}
+
+/// Values which tend to sit right on a length check.
+const INTERESTING: &[u8] = &[0x00, 0x01, 0x7f, 0x80, 0xff, 0x20, 0x21];
+
+/// Changes `input` in a handful of random places.
+fn mutate(rng: &mut Rng, input: &mut Vec<u8>, seeds: &[Vec<u8>]) {
+	for _ in 0..1 + rng.below(4) {
+		let at = rng.below(input.len() + 1);
+		match rng.below(7) {
+			0 if at < input.len() => input[at] ^= 1 << rng.below(8),
+			1 if at < input.len() => input[at] = rng.next() as u8,
+			2 if at < input.len() => input[at] = INTERESTING[rng.below(INTERESTING.len())],
+			3 => input.insert(at, rng.next() as u8),
+			4 if at < input.len() => drop(input.remove(at)),
+			5 => input.truncate(at),
+			// Splices in part of another seed.
+			_ => {
+				let other = &seeds[rng.below(seeds.len())];
+				let from = rng.below(other.len());
+				let len = rng.below(other.len() - from + 1);
+				input.splice(at..at, other[from..from + len].iter().copied());
+			},
+		}
+	}
+	input.truncate(MAX_PACKET_SIZE);
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// Decodes `input`, which has to neither panic nor allocate too much.
+fn run(input: &[u8]) {
+	LARGEST_ALLOCATION.store(0, Ordering::Relaxed);
+	let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+		let decoded = decode(input);
+		let largest = LARGEST_ALLOCATION.load(Ordering::Relaxed);
+		assert!(largest <= ALLOCATION_BOUND, "decoding allocated {} bytes at once", largest);
+		check(decoded);
+	}));
+	if outcome.is_err() {
+		panic!("decoding {} failed", hex(input));
+	}
+}
+
+fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let seeds = seeds();
+
+	if let [flag, dir] = args.as_slice() {
+		if flag == "--write-corpus" {
+			std::fs::create_dir_all(dir).expect("couldn't create the corpus directory");
+			for (i, seed) in seeds.iter().enumerate() {
+				std::fs::write(format!("{}/seed-{:02}", dir, i), seed).expect("couldn't write a seed");
+			}
+			return println!("wrote {} seeds to {}", seeds.len(), dir);
+		}
+	}
+
+	let rounds = args.first().map_or(ROUNDS, |x| x.parse().expect("the input count has to be a number"));
+	let seed = args.get(1).map_or(0x2545_f491_4f6c_dd1d, |x| x.parse().expect("the seed has to be a number"));
+
+	for input in REGRESSIONS.iter().copied().chain(seeds.iter().map(Vec::as_slice)) {
+		run(input);
+	}
+
+	let mut rng = Rng(seed | 1);
+	for _ in 0..rounds {
+		let mut input = seeds[rng.below(seeds.len())].clone();
+		mutate(&mut rng, &mut input, &seeds);
+		run(&input);
+	}
+	println!("{} mutated inputs decoded cleanly, with seed {}", rounds, seed);
+}
\ No newline at end of file
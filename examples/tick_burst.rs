@@ -0,0 +1,77 @@
+//! Times a burst of 100 ticks arriving at once: decoding them, which checks
+//! each against its transmitted hash, then reading the hashes again, which
+//! costs nothing now that they're cached. Ticks which never get compared
+//! against anything skip hashing altogether; the difference is the work
+//! that saves. Best run with `--release`.
+
+use std::time::{Duration, Instant};
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use PlayerIntent::*;
+
+const BURST: u64 = 100;
+const PLAYERS: u16 = 16;
+const ROUNDS: u32 = 1000;
+
+fn tick(index: u64) -> Tick<PlayerIntent> {
+	let command_frames = (0..PLAYERS)
+		.map(|owner| CommandFrame::new(owner, match (owner as u64 + index) % 3 {
+			0 => vec![MoveLeft, JumpStart],
+			1 => vec![MoveRight, JumpHold],
+			_ => vec![MoveHorizontal(-64)],
+		}))
+		.collect();
+	Tick::new(index, command_frames)
+}
+
+/// How long `f` takes on average, per burst.
+fn time(mut f: impl FnMut()) -> Duration {
+	let start = Instant::now();
+	for _ in 0..ROUNDS {
+		f();
+	}
+	start.elapsed() / ROUNDS
+}
+
+fn main() {
+	let packets: Vec<Vec<u8>> = (0..BURST)
+		.map(|index| {
+			let mut writer = ByteWriter::new();
+			Packet::AuthoritativeTick(tick(index)).to_bytes(&mut writer).unwrap();
+			writer.as_slice().to_vec()
+		})
+		.collect();
+	let decode = || -> Vec<Tick<PlayerIntent>> {
+		packets.iter()
+			.map(|x| match Packet::<PlayerIntent>::from_bytes(&mut ByteReader::new(x)) {
+				Ok(Packet::AuthoritativeTick(tick)) => tick,
+				_ => panic!("the tick didn't survive the round trip"),
+			})
+			.collect()
+	};
+
+	let decoding = time(|| {
+		std::hint::black_box(decode());
+	});
+	let ticks = decode();
+	let rereading = time(|| {
+		for tick in &ticks {
+			std::hint::black_box(tick.hash());
+		}
+	});
+	let unhashed = time(|| {
+		for index in 0..BURST {
+			std::hint::black_box(tick(index));
+		}
+	});
+	let hashed = time(|| {
+		for index in 0..BURST {
+			std::hint::black_box(*tick(index).hash());
+		}
+	});
+
+	println!("{} received {}-player ticks:", BURST, PLAYERS);
+	println!("  decoding and verifying: {:?}", decoding);
+	println!("  reading the hashes again: {:?}", rereading);
+	println!("  building without hashing: {:?}, rather than {:?}", unhashed, hashed);
+}
\ No newline at end of file
@@ -29,6 +29,135 @@ let register_ffi = function(guest) {
 		// ... and include them in the tick
 		tick_data[tick_index][guest.wasm_exports.memory.id] = intents;
 	}
+
+	guest.env.replay_exported = function(
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		// the memory is only lent to us, so it has to be copied right away
+		guest.last_replay = new Uint8Array(
+			guest.wasm_memory.buffer,
+			data_ptr,
+			data_len
+		).slice();
+	}
+
+	// only called by builds with the `serde` feature, see `debug_dump_tick`
+	guest.env.tick_dumped = function(
+		data_ptr,	// *const u8, JSON
+		data_len,	// usize
+	) {
+		guest.last_tick_dump = JSON.parse(new TextDecoder().decode(new Uint8Array(
+			guest.wasm_memory.buffer,
+			data_ptr,
+			data_len
+		)));
+		console.log(guest.last_tick_dump);
+	}
+
+	guest.env.state_exported = function(
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		// lent, same as a replay; kept to hand back through `import_state`
+		guest.last_state = new Uint8Array(
+			guest.wasm_memory.buffer,
+			data_ptr,
+			data_len
+		).slice();
+	}
+
+	guest.env.diagnostics_exported = function(
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		// lent, same as a replay; attach it to the bug report
+		guest.last_diagnostics = new Uint8Array(
+			guest.wasm_memory.buffer,
+			data_ptr,
+			data_len
+		).slice();
+	}
+
+	// every `set_observer_interval` confirmed ticks, a `StateSummary`
+	guest.env.on_state_changed = function(
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		let view = new DataView(guest.wasm_memory.buffer, data_ptr, data_len);
+		let offset = 8;
+		// the player count is a varint, see `ByteWriter::write_varint`
+		let count = 0;
+		for(let shift = 0; ; shift += 7) {
+			let byte = view.getUint8(offset++);
+			count |= (byte & 0x7f) << shift;
+			if(byte < 0x80) {
+				break;
+			}
+		}
+		let players = [];
+		for(let i = 0; i < count; i++, offset += 14) {
+			players.push({
+				owner: view.getUint16(offset, true),
+				x: view.getFloat32(offset + 2, true),
+				y: view.getFloat32(offset + 6, true),
+				score: view.getUint32(offset + 10, true),
+			});
+		}
+		guest.last_state_summary = { tick: view.getBigUint64(0, true), players };
+	}
+
+	// once a frame with any, after `forward_sim_events(true)`, the
+	// `TickEvent`s of the ticks simulated over it
+	guest.env.on_sim_events = function(
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		let view = new DataView(guest.wasm_memory.buffer, data_ptr, data_len);
+		let offset = 0;
+		// the event count is a varint too
+		let count = 0;
+		for(let shift = 0; ; shift += 7) {
+			let byte = view.getUint8(offset++);
+			count |= (byte & 0x7f) << shift;
+			if(byte < 0x80) {
+				break;
+			}
+		}
+		for(let i = 0; i < count; i++) {
+			let event = {
+				tick: view.getBigUint64(offset, true),
+				kind: ["landed", "hit_wall", "jumped"][view.getUint8(offset + 8)],
+				id: view.getUint16(offset + 9, true),
+			};
+			offset += 11;
+			if(event.kind == "landed") {
+				event.impact_velocity = view.getFloat32(offset, true);
+				offset += 4;
+			} else if(event.kind == "hit_wall") {
+				event.side = ["left", "right"][view.getUint8(offset)];
+				offset += 1;
+			}
+			// for sounds, screen shake and the like
+			if(guest.on_sim_event) {
+				guest.on_sim_event(event);
+			}
+		}
+	}
+
+	guest.env.host_log = function(
+		level,		// u8, 1 = error ... 5 = trace
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		let message = new TextDecoder().decode(new Uint8Array(
+			guest.wasm_memory.buffer,
+			data_ptr,
+			data_len
+		));
+		let log = [console.error, console.warn, console.info, console.debug, console.debug][level - 1];
+		(log || console.log)(message);
+	}
 }
 
 let start_round = function(guests) {
@@ -38,9 +167,18 @@ let start_round = function(guests) {
 	for(let i = 0; i < guests.length; i++) {
 		let guest_id = next_guest_id++;
 		guests[i].wasm_exports.memory.id = guest_id;
-		guests[i].wasm_exports.start_game(guest_id);
+		// about once a second, into `last_state_summary`
+		guests[i].wasm_exports.set_observer_interval(20);
+		guests[i].wasm_exports.start_game(false);
 	}
 
+	// Hidden tabs get few frames, if any; the guests pause until they're back.
+	document.addEventListener("visibilitychange", () => {
+		for(let i = 0; i < guests.length; i++) {
+			guests[i].wasm_exports.set_focused(!document.hidden);
+		}
+	});
+
 	let server_loop = setInterval(() => {
 		if(current_tick_index in tick_data) {
 			let tick = tick_data[current_tick_index];
@@ -0,0 +1,38 @@
+//! Feeds arbitrary bytes into every decoder which sees bytes from the wire;
+//! see `command_networking_rs::fuzzing`. Run from the repository root with
+//! `make fuzz`, which needs a nightly toolchain and cargo-fuzz.
+
+#![no_main]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use command_networking_rs::fuzzing::{check, decode, ALLOCATION_BOUND};
+use libfuzzer_sys::fuzz_target;
+
+/// Keeps track of the largest single allocation made.
+struct TrackingAllocator;
+
+static LARGEST_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		LARGEST_ALLOCATION.fetch_max(layout.size(), Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+fuzz_target!(|bytes: &[u8]| {
+	LARGEST_ALLOCATION.store(0, Ordering::Relaxed);
+	let decoded = decode(bytes);
+	let largest = LARGEST_ALLOCATION.load(Ordering::Relaxed);
+	assert!(largest <= ALLOCATION_BOUND, "decoding allocated {} bytes at once", largest);
+	check(decoded);
+});
\ No newline at end of file
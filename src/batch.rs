@@ -0,0 +1,183 @@
+//! Several packets travelling as one message, so that a frame's worth of
+//! them takes a single crossing of the host FFI rather than one each.
+//!
+//! A batch starts with `BATCH_MAGIC` and the protocol version, followed by
+//! the packets it carries, laid out the same way as a `Vec<Vec<u8>>`: a
+//! count, then every packet prefixed with its length. Each packet is a
+//! complete one, header included, so splitting a batch gets back exactly
+//! the bytes that went into it.
+
+use crate::net::{ByteReader, ByteWriter, DecodeError};
+use crate::protocol::{MAX_PACKET_SIZE, PROTOCOL_VERSION};
+
+/// Batches start with these, in place of a packet's `PROTOCOL_MAGIC`, so
+/// the two can be told apart by their first bytes.
+pub const BATCH_MAGIC: [u8; 2] = *b"CB";
+/// The first protocol version batches were sent in. Hosts speaking an
+/// older one get every packet on its own.
+pub const BATCHING_VERSION: u8 = 22;
+/// Largest batch, in bytes, that's sent or even looked at. Packets which
+/// don't fit into one together spill over into another.
+pub const MAX_BATCH_SIZE: usize = MAX_PACKET_SIZE;
+/// Most packets a single batch may carry.
+pub const MAX_BATCH_PACKETS: usize = 256;
+
+/// Magic and version.
+const HEADER_SIZE: usize = BATCH_MAGIC.len() + 1;
+
+/// Collects the packets sent over a frame, to send them all at once with
+/// `flush`.
+pub struct Coalescer {
+	/// Most bytes a batch is allowed to take up.
+	max_size: usize,
+	packets: Vec<Vec<u8>>,
+	/// Reused for every batch, rather than allocating one per flush.
+	buffer: ByteWriter,
+}
+
+impl Default for Coalescer {
+	fn default() -> Self {
+		Self::new(MAX_BATCH_SIZE)
+	}
+}
+
+impl Coalescer {
+	/// Keeps every batch within `max_size` bytes, which is capped at
+	/// `MAX_BATCH_SIZE`, as receivers won't take anything bigger.
+	pub fn new(max_size: usize) -> Self {
+		Self {
+			max_size: max_size.min(MAX_BATCH_SIZE),
+			packets: Vec::new(),
+			buffer: ByteWriter::new()
+		}
+	}
+
+	/// Holds on to a packet until the next `flush`.
+	pub fn push(&mut self, bytes: &[u8]) {
+		self.packets.push(bytes.to_vec());
+	}
+
+	/// How many packets are waiting to be flushed.
+	pub fn len(&self) -> usize {
+		self.packets.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.packets.is_empty()
+	}
+
+	/// Hands every packet pushed since the last flush to `send`, in order,
+	/// batched into as few messages as they fit into. A message holding a
+	/// single packet is sent as that packet, without a batch around it;
+	/// so is a packet too big to be batched at all. Returns how many
+	/// messages there were.
+	pub fn flush(&mut self, mut send: impl FnMut(&[u8])) -> usize {
+		let sent = self.try_flush(|x| {
+			send(x);
+			Ok::<(), std::convert::Infallible>(())
+		});
+		match sent {
+			Ok(messages) => messages,
+			Err(never) => match never {},
+		}
+	}
+
+	/// Flushes the same way as `flush`, for as long as `send` succeeds.
+	/// The packets of the message it fails on, and those after it, are
+	/// held on to for the next flush, ahead of any pushed meanwhile.
+	pub fn try_flush<E>(&mut self, mut send: impl FnMut(&[u8]) -> Result<(), E>) -> Result<usize, E> {
+		let mut packets = std::mem::take(&mut self.packets);
+		let mut messages = 0;
+		let mut start = 0;
+		while start < packets.len() {
+			// Everything but the header and the count.
+			let mut body = entry_size(&packets[start]);
+			let mut end = start + 1;
+			while end < packets.len() && end - start < MAX_BATCH_PACKETS {
+				let grown = body + entry_size(&packets[end]);
+				if HEADER_SIZE + ByteWriter::varint_len((end - start + 1) as u64) + grown > self.max_size {
+					break;
+				}
+				body = grown;
+				end += 1;
+			}
+
+			let sent = match end - start {
+				1 => send(&packets[start]),
+				_ => {
+					self.buffer.clear();
+					self.buffer.reserve(HEADER_SIZE + ByteWriter::varint_len((end - start) as u64) + body);
+					encode_batch(&packets[start..end], &mut self.buffer);
+					send(self.buffer.as_slice())
+				},
+			};
+			if let Err(error) = sent {
+				packets.drain(..start);
+				self.packets = packets;
+				return Err(error);
+			}
+			messages += 1;
+			start = end;
+		}
+		Ok(messages)
+	}
+}
+
+/// How many bytes a batch of `packets` takes up.
+pub fn batch_size(packets: &[Vec<u8>]) -> usize {
+	HEADER_SIZE + ByteWriter::varint_len(packets.len() as u64) + packets.iter().map(|x| entry_size(x)).sum::<usize>()
+}
+
+/// How many bytes a packet takes up within a batch, length included.
+fn entry_size(packet: &[u8]) -> usize {
+	ByteWriter::varint_len(packet.len() as u64) + packet.len()
+}
+
+/// Writes `packets` out as a single batch, however big that gets.
+pub fn encode_batch(packets: &[Vec<u8>], writer: &mut ByteWriter) {
+	writer.write_bytes(&BATCH_MAGIC);
+	writer.write_u8(PROTOCOL_VERSION);
+	writer.write_varint(packets.len() as u64);
+	for packet in packets {
+		// The same as `Vec<u8>::to_bytes`, minus going byte by byte.
+		writer.write_varint(packet.len() as u64);
+		writer.write_bytes(packet);
+	}
+}
+
+/// Whether `bytes` are a batch, rather than a single packet.
+pub fn is_batch(bytes: &[u8]) -> bool {
+	bytes.starts_with(&BATCH_MAGIC)
+}
+
+/// The packets in a message, which is either a batch or a single packet;
+/// the latter comes back as it is, for `Packet::decode` to make sense of.
+/// Fails on a batch which is malformed anywhere, rather than returning
+/// whatever packets came before the damage.
+pub fn split_batch(bytes: &[u8]) -> Result<Vec<&[u8]>, DecodeError> {
+	if !is_batch(bytes) {
+		return Ok(vec![bytes]);
+	}
+	if bytes.len() > MAX_BATCH_SIZE {
+		return Err(DecodeError::PacketTooLarge { size: bytes.len(), max: MAX_BATCH_SIZE });
+	}
+
+	let mut reader = ByteReader::new(&bytes[BATCH_MAGIC.len()..]);
+	let version = reader.read_u8()?;
+	if version != PROTOCOL_VERSION {
+		return Err(DecodeError::UnsupportedVersion {
+			got: version,
+			expected: PROTOCOL_VERSION
+		});
+	}
+	let count = reader.read_len(MAX_BATCH_PACKETS, "batched packets")?;
+	let mut packets = Vec::with_capacity(count);
+	for _ in 0..count {
+		let len = reader.read_len(MAX_PACKET_SIZE, "batched packet")?;
+		packets.push(reader.read_exact(len)?);
+	}
+	match reader.remaining() {
+		0 => Ok(packets),
+		count => Err(DecodeError::TrailingBytes { count }),
+	}
+}
\ No newline at end of file
@@ -0,0 +1,62 @@
+//! Deterministic workloads for the benchmarks in `benches/`, along with
+//! access to the parts of the simulation they time. Only built with the
+//! `bench` feature.
+//!
+//! Everything here is derived from a fixed seed, so the same tick comes out
+//! on every machine, and results stay comparable from one change to the next.
+
+use crate::game::Game;
+use crate::protocol::{ClientId, CommandFrame, PlayerIntent, Tick};
+use PlayerIntent::*;
+
+/// The frames a player sends, spread out the way real input tends to be;
+/// mostly running, with the odd jump and some analog movement.
+const PATTERNS: &[&[PlayerIntent]] = &[
+	&[],
+	&[MoveLeft],
+	&[MoveRight],
+	&[MoveRight, JumpStart],
+	&[MoveRight, JumpHold],
+	&[MoveLeft, JumpHold],
+	&[MoveHorizontal(-40)],
+	&[MoveHorizontal(90), JumpStart],
+];
+
+/// A cheap hash of the tick and player, which picks their frame.
+fn mix(index: u64, owner: ClientId) -> u64 {
+	let mut x = index.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (owner as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	x ^= x >> 31;
+	x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+	x ^ (x >> 29)
+}
+
+/// Tick `index`, with a frame from each of `players` players.
+pub fn synthetic_tick(index: u64, players: ClientId) -> Tick<PlayerIntent> {
+	let command_frames = (0..players)
+		.map(|owner| {
+			let pattern = PATTERNS[mix(index, owner) as usize % PATTERNS.len()];
+			CommandFrame::new(owner, pattern.to_vec())
+		})
+		.collect();
+	Tick::new(index, command_frames)
+}
+
+/// The first `ticks` ticks of a game between `players` players.
+pub fn synthetic_script(ticks: u64, players: ClientId) -> Vec<Tick<PlayerIntent>> {
+	(0..ticks).map(|index| synthetic_tick(index, players)).collect()
+}
+
+/// A game every one of `players` players has been in from the start.
+pub fn game_with_players(players: ClientId) -> Game {
+	let mut game = Game::new(0);
+	for client_id in 1..players {
+		game.add_member(client_id);
+	}
+	game
+}
+
+/// Runs the simulation for a tick, without any of the bookkeeping
+/// `Game::advance` does around it.
+pub fn simulate(game: &mut Game, tick: &Tick<PlayerIntent>) {
+	game.simulate(tick);
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+//! Writes the conformance vectors out, to the path given or to
+//! `VECTORS_PATH`; see `conformance`.
+//!
+//!     cargo run --features serde --bin gen_vectors
+
+use command_networking_rs::conformance::{generate, VECTORS_PATH};
+
+fn main() {
+	let path = std::env::args().nth(1).unwrap_or_else(|| VECTORS_PATH.into());
+	let json = serde_json::to_string_pretty(&generate()).expect("vectors are always JSON");
+	if let Err(error) = std::fs::write(&path, json + "\n") {
+		eprintln!("couldn't write {}: {}", path, error);
+		std::process::exit(1);
+	}
+	println!("wrote {}", path);
+}
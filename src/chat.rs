@@ -0,0 +1,133 @@
+//! A tiny chat. Messages go through the server like everything else, but
+//! stay out of the simulation altogether: nothing about them gets hashed,
+//! predicted or rolled back, and a message lost is simply never shown.
+
+use std::collections::VecDeque;
+
+#[cfg(feature = "client")]
+use macroquad::prelude::*;
+
+use crate::protocol::{truncate, ClientId, MAX_CHAT_LEN};
+#[cfg(feature = "client")]
+use crate::game::color_for_client;
+#[cfg(feature = "client")]
+use crate::SCREEN_SIZE;
+
+/// A message as it's shown, with whoever sent it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChatMessage {
+	pub sender: ClientId,
+	pub text: String,
+}
+
+/// Makes `text` fit to be shown: control characters are dropped, along with
+/// whitespace at either end, and anything past `MAX_CHAT_LEN` bytes.
+pub fn sanitize(text: &str) -> String {
+	let text: String = text.chars().filter(|x| !x.is_control()).collect();
+	truncate(text.trim(), MAX_CHAT_LEN).trim_end().into()
+}
+
+/// The last few messages, oldest first.
+#[derive(Default)]
+pub struct ChatLog {
+	messages: VecDeque<ChatMessage>,
+}
+
+impl ChatLog {
+	/// How many messages are kept, and shown.
+	pub const CAPACITY: usize = 5;
+	#[cfg(feature = "client")]
+	const FONT_SIZE: f32 = 14.0;
+	#[cfg(feature = "client")]
+	const LINE_HEIGHT: f32 = 13.0;
+	#[cfg(feature = "client")]
+	const MARGIN: f32 = 5.0;
+
+	/// Adds a message, pushing out the oldest one once there are too many.
+	/// Messages with nothing left to show once sanitized are dropped.
+	pub fn push(&mut self, sender: ClientId, text: &str) {
+		let text = sanitize(text);
+		if text.is_empty() {
+			return;
+		}
+		if self.messages.len() == Self::CAPACITY {
+			self.messages.pop_front();
+		}
+		self.messages.push_back(ChatMessage { sender, text });
+	}
+
+	pub fn messages(&self) -> impl Iterator<Item = &ChatMessage> {
+		self.messages.iter()
+	}
+
+	/// Draws the messages in the bottom left corner, in screen space, with
+	/// the newest one ending just above `bottom`. Each is written in the
+	/// color its sender is drawn with.
+	#[cfg(feature = "client")]
+	pub fn draw(&self, bottom: f32) {
+		let mut y = bottom - Self::MARGIN;
+		for message in self.messages.iter().rev() {
+			draw_text(&message.text, Self::MARGIN, y, Self::FONT_SIZE, color_for_client(message.sender));
+			y -= Self::LINE_HEIGHT;
+		}
+	}
+}
+
+/// Typing a message with the keyboard. `OPEN_KEY` starts a message, Enter
+/// sends it, and Escape throws it away. The players' keys are meant to be
+/// suspended for as long as a message is being typed; see
+/// `InputState::suspend`.
+#[cfg(feature = "client")]
+#[derive(Default)]
+pub struct ChatInput {
+	/// What's been typed so far, while typing.
+	draft: Option<String>,
+}
+
+#[cfg(feature = "client")]
+impl ChatInput {
+	pub const OPEN_KEY: KeyCode = KeyCode::T;
+
+	pub fn is_composing(&self) -> bool {
+		self.draft.is_some()
+	}
+
+	/// Takes in the keys pressed this frame. Returns the message once it's
+	/// sent, sanitized, unless there's nothing left of it.
+	pub fn update(&mut self) -> Option<String> {
+		let Some(draft) = &mut self.draft else {
+			if is_key_pressed(Self::OPEN_KEY) {
+				self.draft = Some(String::new());
+				// The key that opened the chat isn't part of the message.
+				while get_char_pressed().is_some() {}
+			}
+			return None;
+		};
+
+		while let Some(character) = get_char_pressed() {
+			if !character.is_control() && draft.len() + character.len_utf8() <= MAX_CHAT_LEN {
+				draft.push(character);
+			}
+		}
+		if is_key_pressed(KeyCode::Backspace) {
+			draft.pop();
+		}
+		if is_key_pressed(KeyCode::Escape) {
+			self.draft = None;
+		} else if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+			let text = sanitize(&self.draft.take().unwrap_or_default());
+			return (!text.is_empty()).then_some(text);
+		}
+		None
+	}
+
+	/// Draws the message being typed along the bottom of the screen, in
+	/// screen space.
+	pub fn draw(&self) {
+		let Some(draft) = &self.draft else { return };
+		let height = ChatLog::LINE_HEIGHT + ChatLog::MARGIN;
+		let top = SCREEN_SIZE as f32 - height;
+		draw_rectangle(0.0, top, SCREEN_SIZE as f32, height, Color::new(0.0, 0.0, 0.0, 0.6));
+		draw_text(&format!("> {}_", draft), ChatLog::MARGIN, SCREEN_SIZE as f32 - ChatLog::MARGIN, ChatLog::FONT_SIZE, WHITE);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,254 @@
+//! Command line arguments for running the client natively. In the browser,
+//! the host passes the same things to `start_game` instead.
+
+use crate::game::{DEFAULT_INTERP_MARGIN, DEFAULT_REDUNDANT_FRAMES};
+use crate::protocol::{ClientId, MAX_NAME_LEN, MAX_PAST_FRAMES};
+use crate::reliable::{ReliableConfig, DEFAULT_RETRANSMIT_TIMEOUT};
+use crate::TICKRATE;
+
+/// Where a native client gets its game from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Mode {
+	/// Plays against an in-process server, with a scripted second player.
+	Loopback,
+	/// Plays back a replay saved from an earlier game.
+	Replay(String),
+	/// Plays through a UDP relay, with the socket bound to `bind`.
+	#[cfg(feature = "transport-udp")]
+	Connect {
+		relay: String,
+		bind: String,
+	},
+}
+
+impl Default for Mode {
+	/// With a UDP transport, the relay on the local machine. Otherwise
+	/// there's nothing to connect to.
+	fn default() -> Self {
+		#[cfg(feature = "transport-udp")]
+		let mode = Mode::Connect {
+			relay: DEFAULT_RELAY.into(),
+			bind: DEFAULT_BIND.into()
+		};
+		#[cfg(not(feature = "transport-udp"))]
+		let mode = Mode::Loopback;
+		mode
+	}
+}
+
+#[cfg(feature = "transport-udp")]
+const DEFAULT_RELAY: &str = "127.0.0.1:7777";
+#[cfg(feature = "transport-udp")]
+const DEFAULT_BIND: &str = "0.0.0.0:0";
+
+/// Largest `--scale`; anything more wouldn't fit on any screen.
+pub const MAX_SCALE: u8 = 16;
+
+/// How to run the client, as parsed from the command line.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Options {
+	/// The ClientId to ask the server for, rather than taking whichever it
+	/// hands out. Meant for debugging.
+	pub client_id: Option<ClientId>,
+	/// Ticks per second to start out at, until the server says otherwise.
+	pub tick_rate: u8,
+	/// Only watch the game, until joining it by pressing Enter.
+	pub spectator: bool,
+	/// Adds a second player on WASD, sharing the screen.
+	pub shared_screen: bool,
+	/// Has the game play itself from the start, rather than only once
+	/// nobody's pressed a key for a while.
+	pub demo: bool,
+	/// How many times the size of the playfield the window starts out at.
+	pub scale: u8,
+	/// What the others see written above the local player. Left to the
+	/// client to make up when missing.
+	pub name: Option<String>,
+	/// Ticks to predict ahead of the server by, on top of the latency,
+	/// once the clocks are synced.
+	pub interp_margin: u8,
+	/// How many ticks back every tick sent repeats the local player's
+	/// frames; see `Game::set_redundant_frames`.
+	pub redundant_frames: u8,
+	/// How long a packet which has to arrive goes unanswered before it's
+	/// sent again, in milliseconds; see `reliable`.
+	pub retransmit_timeout_ms: u32,
+	/// How long a frame's ticks get to take, in milliseconds, before the
+	/// rest waits for the next frame; see `Game::set_tick_budget`. No limit
+	/// unless given.
+	pub tick_budget_ms: Option<u32>,
+	pub mode: Mode,
+}
+
+impl Options {
+	pub fn reliable_config(&self) -> ReliableConfig {
+		ReliableConfig {
+			retransmit_timeout: self.retransmit_timeout_ms as f64 / 1000.0,
+			..Default::default()
+		}
+	}
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			client_id: None,
+			tick_rate: TICKRATE,
+			spectator: false,
+			shared_screen: false,
+			demo: false,
+			scale: 1,
+			name: None,
+			interp_margin: DEFAULT_INTERP_MARGIN,
+			redundant_frames: DEFAULT_REDUNDANT_FRAMES,
+			retransmit_timeout_ms: (DEFAULT_RETRANSMIT_TIMEOUT * 1000.0) as u32,
+			tick_budget_ms: None,
+			mode: Mode::default()
+		}
+	}
+}
+
+/// What went wrong parsing the command line. `Help` isn't much of an error,
+/// but it's just as much a reason to print the usage and stop.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CliError {
+	Help,
+	UnknownArgument(String),
+	MissingValue(&'static str),
+	InvalidValue {
+		flag: &'static str,
+		value: String,
+	},
+}
+
+impl std::fmt::Display for CliError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			CliError::Help => write!(f, "help requested"),
+			CliError::UnknownArgument(argument) => write!(f, "unknown argument {:?}", argument),
+			CliError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+			CliError::InvalidValue { flag, value } => write!(f, "invalid value {:?} for {}", value, flag),
+		}
+	}
+}
+
+impl std::error::Error for CliError {}
+
+/// Describes every argument `parse` takes, for printing alongside an error.
+pub fn usage() -> String {
+	let mut usage = String::from("usage: command-networking-rs [options]\n\n");
+	usage += "  --client-id <id>   ask the server for this ClientId, from 0 to 65535 (default any)\n";
+	usage += &format!("  --tick-rate <hz>   ticks per second, until the server says otherwise (default {})\n", TICKRATE);
+	usage += &format!("  --margin <n>       predict n ticks ahead of the server, on top of the latency (default {})\n", DEFAULT_INTERP_MARGIN);
+	usage += &format!(
+		"  --redundancy <n>   repeat the last n ticks' input in every tick sent, up to {} (default {})\n",
+		MAX_PAST_FRAMES, DEFAULT_REDUNDANT_FRAMES
+	);
+	usage += &format!(
+		"  --retransmit <ms>  resend chat and the like after ms without an answer (default {})\n",
+		(DEFAULT_RETRANSMIT_TIMEOUT * 1000.0) as u32
+	);
+	usage += "  --tick-budget <ms> spend at most ms a frame on ticks, rollbacks included (default no limit)\n";
+	usage += "  --spectator        only watch, until pressing Enter\n";
+	usage += "  --shared-screen    add a second player on WASD\n";
+	usage += "  --demo             play by itself until a key is pressed\n";
+	usage += "  --scale <n>        open the window n times the size of the playfield (default 1)\n";
+	usage += &format!("  --name <name>      what to show above the player, up to {} bytes (default \"Player <id>\")\n", MAX_NAME_LEN);
+	#[cfg(feature = "transport-udp")]
+	{
+		usage += &format!("  --connect <addr>   play through the UDP relay at addr (default {})\n", DEFAULT_RELAY);
+		usage += &format!("  --bind <addr>      bind the UDP socket to addr (default {})\n", DEFAULT_BIND);
+		usage += "  --loopback         play against an in-process server instead\n";
+	}
+	#[cfg(not(feature = "transport-udp"))]
+	{
+		usage += "  --loopback         play against an in-process server (the default)\n";
+	}
+	usage += "  --replay <path>    play back a replay saved with F2\n";
+	usage += "  --help             print this, and exit\n";
+	usage
+}
+
+/// Parses the arguments following the program name. Later arguments win
+/// over earlier ones, should they conflict.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, CliError> {
+	let mut options = Options::default();
+	let mut args = args.into_iter();
+	while let Some(argument) = args.next() {
+		match argument.as_str() {
+			"--help" | "-h" => return Err(CliError::Help),
+			"--client-id" => options.client_id = Some(parse_value(&mut args, "--client-id")?),
+			"--tick-rate" => {
+				let value = value(&mut args, "--tick-rate")?;
+				match value.parse() {
+					Ok(hz) if hz > 0 => options.tick_rate = hz,
+					_ => return Err(CliError::InvalidValue { flag: "--tick-rate", value }),
+				}
+			},
+			"--scale" => {
+				let value = value(&mut args, "--scale")?;
+				match value.parse::<u8>() {
+					Ok(scale) if (1..=MAX_SCALE).contains(&scale) => options.scale = scale,
+					_ => return Err(CliError::InvalidValue { flag: "--scale", value }),
+				}
+			},
+			"--margin" => options.interp_margin = parse_value(&mut args, "--margin")?,
+			"--redundancy" => {
+				let value = value(&mut args, "--redundancy")?;
+				match value.parse::<u8>() {
+					Ok(frames) if frames as usize <= MAX_PAST_FRAMES => options.redundant_frames = frames,
+					_ => return Err(CliError::InvalidValue { flag: "--redundancy", value }),
+				}
+			},
+			"--retransmit" => {
+				let value = value(&mut args, "--retransmit")?;
+				match value.parse() {
+					Ok(ms) if ms > 0 => options.retransmit_timeout_ms = ms,
+					_ => return Err(CliError::InvalidValue { flag: "--retransmit", value }),
+				}
+			},
+			"--tick-budget" => {
+				let value = value(&mut args, "--tick-budget")?;
+				match value.parse() {
+					Ok(ms) if ms > 0 => options.tick_budget_ms = Some(ms),
+					_ => return Err(CliError::InvalidValue { flag: "--tick-budget", value }),
+				}
+			},
+			"--name" => options.name = Some(value(&mut args, "--name")?),
+			"--spectator" => options.spectator = true,
+			"--shared-screen" => options.shared_screen = true,
+			"--demo" => options.demo = true,
+			"--loopback" => options.mode = Mode::Loopback,
+			"--replay" => options.mode = Mode::Replay(value(&mut args, "--replay")?),
+			#[cfg(feature = "transport-udp")]
+			"--connect" => {
+				let relay = value(&mut args, "--connect")?;
+				let bind = match std::mem::take(&mut options.mode) {
+					Mode::Connect { bind, .. } => bind,
+					_ => DEFAULT_BIND.into(),
+				};
+				options.mode = Mode::Connect { relay, bind };
+			},
+			#[cfg(feature = "transport-udp")]
+			"--bind" => {
+				let bind = value(&mut args, "--bind")?;
+				let relay = match std::mem::take(&mut options.mode) {
+					Mode::Connect { relay, .. } => relay,
+					_ => DEFAULT_RELAY.into(),
+				};
+				options.mode = Mode::Connect { relay, bind };
+			},
+			_ => return Err(CliError::UnknownArgument(argument)),
+		}
+	}
+	Ok(options)
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &'static str) -> Result<String, CliError> {
+	args.next().ok_or(CliError::MissingValue(flag))
+}
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &'static str) -> Result<T, CliError> {
+	let value = value(args, flag)?;
+	value.parse().map_err(|_| CliError::InvalidValue { flag, value })
+}
\ No newline at end of file
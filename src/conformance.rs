@@ -0,0 +1,491 @@
+//! Test vectors for the wire format, for other implementations of it to
+//! check themselves against; the JS host, above all. Every vector names a
+//! type, the values of its fields, and the exact bytes they go over the
+//! wire as, or for bytes which shouldn't make it through, the kind of
+//! `DecodeError` they get turned down with.
+//!
+//! The vectors are written to `VECTORS_PATH` by the `gen_vectors` binary,
+//! and `check` holds that file against the encoder and decoder, so that it
+//! can't go stale without anyone noticing. They're for the default build:
+//! floats, and ticks hashed with SHA-256.
+
+use serde_json::{json, Value};
+use crate::batch::{encode_batch, split_batch, BATCHING_VERSION, BATCH_MAGIC};
+use crate::game::{Coins, Player, PlayerSummary, SimConfig, SimEvent, StateSummary, TickEvent, WallSide};
+use crate::hash::{to_hex, Hash, Hasher, TickHasher};
+use crate::net::{ByteReader, ByteWriter, DecodeError, NetType};
+use crate::protocol::*;
+
+/// Where the vectors are kept, relative to the root of the repository.
+pub const VECTORS_PATH: &str = "conformance/vectors.json";
+
+/// Versions of the protocol the vectors cover. Packets only ever go over
+/// the wire in the current one; older hosts differ in whether they take
+/// batches, see `negotiation`.
+pub const SUPPORTED_VERSIONS: [u8; 1] = [PROTOCOL_VERSION];
+
+/// Every vector, along with what they were made for.
+pub fn generate() -> Value {
+	let mut vectors = vec![];
+	intents(&mut vectors);
+	frames(&mut vectors);
+	ticks(&mut vectors);
+	packets(&mut vectors);
+	batches(&mut vectors);
+	host_types(&mut vectors);
+	json!({
+		"protocol_version": PROTOCOL_VERSION,
+		"supported_versions": SUPPORTED_VERSIONS,
+		"hasher": { "id": Hasher::ID, "name": hasher_name() },
+		"negotiation": negotiation(),
+		"vectors": vectors,
+	})
+}
+
+/// Holds `suite`, as read from `VECTORS_PATH`, against the encoder and the
+/// decoder: every vector has to decode into its fields and encode back
+/// into its bytes, or fail the way it says. Then against `generate`, so
+/// that any vector missing is caught too. Returns how many vectors there
+/// were.
+pub fn check(suite: &Value) -> Result<usize, String> {
+	let expected = generate();
+	if suite["hasher"] != expected["hasher"] {
+		return Err(format!("the vectors are for {}, not {}", suite["hasher"], expected["hasher"]));
+	}
+	for entry in suite["negotiation"].as_array().ok_or("no negotiation")? {
+		let host_version = entry["host_version"].as_u64().ok_or("no host version")?;
+		if entry["batched"] != (host_version >= BATCHING_VERSION as u64) {
+			return Err(format!("a host speaking version {} gets batches the other way around", host_version));
+		}
+	}
+	let vectors = suite["vectors"].as_array().ok_or("no vectors")?;
+	for vector in vectors {
+		check_vector(vector).map_err(|x| format!("{}: {}", vector["name"], x))?;
+	}
+	if *suite != expected {
+		return Err("the vectors are stale; write them again with `cargo run --features serde --bin gen_vectors`".into());
+	}
+	Ok(vectors.len())
+}
+
+fn check_vector(vector: &Value) -> Result<(), String> {
+	let bytes = from_hex(vector["bytes"].as_str().ok_or("no bytes")?)?;
+	let decoded = decode(vector["type"].as_str().ok_or("no type")?, &bytes)?;
+	match (&vector["error"], decoded) {
+		(Value::Null, Ok((fields, encoded))) => {
+			if fields != vector["fields"] {
+				return Err(format!("decodes into {}", fields));
+			}
+			if encoded != bytes {
+				return Err(format!("encodes back into {}", to_hex(&encoded)));
+			}
+			Ok(())
+		},
+		(Value::Null, Err(error)) => Err(format!("doesn't decode: {}", error)),
+		(kind, Err(error)) if *kind == error.kind() => Ok(()),
+		(kind, Err(error)) => Err(format!("fails with {}, not {}", error.kind(), kind)),
+		(kind, Ok((fields, _))) => Err(format!("decodes into {}, rather than failing with {}", fields, kind)),
+	}
+}
+
+/// Decodes `bytes` as `ty`, which has to take up all of them, into its
+/// fields, and the bytes those encode back into.
+fn decode(ty: &str, bytes: &[u8]) -> Result<Result<(Value, Vec<u8>), DecodeError>, String> {
+	Ok(match ty {
+		"PlayerIntent" => whole::<PlayerIntent>(bytes).map(|x| (intent_fields(&x), encode(&x))),
+		"CommandFrame" => whole::<CommandFrame<PlayerIntent>>(bytes).map(|x| (frame_fields(&x), encode(&x))),
+		"Tick" => whole::<Tick<PlayerIntent>>(bytes).map(|x| (tick_fields(&x), encode(&x))),
+		"Packet" => Packet::<PlayerIntent>::decode(bytes).map(|x| (packet_fields(&x), encode(&x))),
+		"Batch" => split_batch(bytes).map(|packets| {
+			let packets: Vec<Vec<u8>> = packets.iter().map(|x| x.to_vec()).collect();
+			let mut writer = ByteWriter::new();
+			encode_batch(&packets, &mut writer);
+			(batch_fields(&packets), writer.as_slice().to_vec())
+		}),
+		"StateSummary" => whole::<StateSummary>(bytes).map(|x| (summary_fields(&x), encode(&x))),
+		"TickEvents" => whole::<Vec<TickEvent>>(bytes).map(|x| (events_fields(&x), encode(&x))),
+		_ => return Err(format!("no such type as {}", ty)),
+	})
+}
+
+/// Protocol versions a host could say it speaks, and whether it gets sent
+/// batches for each; see `HostTransport::new`.
+fn negotiation() -> Value {
+	let versions = [0, BATCHING_VERSION - 1, BATCHING_VERSION, PROTOCOL_VERSION];
+	versions.iter().map(|x| json!({ "host_version": x, "batched": *x >= BATCHING_VERSION })).collect()
+}
+
+fn hasher_name() -> &'static str {
+	match std::mem::size_of::<Hash>() {
+		4 => "crc32",
+		_ => "sha256",
+	}
+}
+
+fn vector(name: &str, ty: &str, fields: Value, bytes: &[u8]) -> Value {
+	json!({ "name": name, "type": ty, "version": PROTOCOL_VERSION, "fields": fields, "bytes": to_hex(bytes) })
+}
+
+fn invalid(name: &str, ty: &str, bytes: &[u8], error: &str) -> Value {
+	json!({ "name": name, "type": ty, "version": PROTOCOL_VERSION, "bytes": to_hex(bytes), "error": error })
+}
+
+/// Like `invalid`, but checks that the bytes really do fail that way, so
+/// that a vector never gets written out wrong.
+fn rejected(name: &str, ty: &str, bytes: &[u8], error: &str) -> Value {
+	match decode(ty, bytes) {
+		Ok(Err(found)) if found.kind() == error => invalid(name, ty, bytes, error),
+		Ok(Err(found)) => panic!("{} fails with {}, not {}", name, found.kind(), error),
+		_ => panic!("{} doesn't fail with {}", name, error),
+	}
+}
+
+fn intents(vectors: &mut Vec<Value>) {
+	use PlayerIntent::*;
+	let all = [
+		("move_left", MoveLeft),
+		("move_right", MoveRight),
+		("jump_start", JumpStart),
+		("jump_hold", JumpHold),
+		("move_horizontal", MoveHorizontal(-64)),
+		("move_horizontal_max", MoveHorizontal(i8::MAX)),
+		("move_horizontal_min", MoveHorizontal(i8::MIN)),
+	];
+	for (name, intent) in all {
+		vectors.push(vector(&format!("intent_{}", name), "PlayerIntent", intent_fields(&intent), &encode(&intent)));
+	}
+	vectors.push(rejected("intent_unknown_tag", "PlayerIntent", &[0xff], "InvalidTag"));
+	vectors.push(rejected("intent_magnitude_missing", "PlayerIntent", &[PlayerIntent::TAG_MOVE_HORIZONTAL], "UnexpectedEof"));
+	vectors.push(rejected("intent_empty", "PlayerIntent", &[], "UnexpectedEof"));
+}
+
+fn frames(vectors: &mut Vec<Value>) {
+	use PlayerIntent::*;
+	let all = [
+		("frame_empty", CommandFrame::new(7, vec![])),
+		// Made of intents which pack into a single byte.
+		("frame_packed", CommandFrame::new(1, vec![MoveRight, JumpStart])),
+		// Has to go as a list, having an analog move in it.
+		("frame_list", CommandFrame::new(2, vec![MoveHorizontal(-64), JumpHold]).with_offset(64)),
+		("frame_max_owner", CommandFrame::new(ClientId::MAX, vec![MoveLeft]).with_offset(255)),
+	];
+	for (name, frame) in all {
+		vectors.push(vector(name, "CommandFrame", frame_fields(&frame), &encode(&frame)));
+	}
+
+	// A list of one more intent than a frame may have.
+	let mut writer = ByteWriter::new();
+	writer.write_bytes(&[0x03, 0x00, 0x80]);
+	writer.write_varint(MAX_INTENTS_PER_FRAME as u64 + 1);
+	writer.write_bytes(&[PlayerIntent::TAG_MOVE_LEFT; MAX_INTENTS_PER_FRAME + 1]);
+	writer.write_u8(0);
+	vectors.push(rejected("frame_too_many_intents", "CommandFrame", writer.as_slice(), "LimitExceeded"));
+	let bytes = encode(&CommandFrame::new(2, vec![MoveHorizontal(-64)]));
+	vectors.push(rejected("frame_truncated", "CommandFrame", &bytes[..bytes.len() - 1], "UnexpectedEof"));
+}
+
+fn two_player_tick() -> Tick<PlayerIntent> {
+	use PlayerIntent::*;
+	Tick::new(300, vec![
+		CommandFrame::new(1, vec![MoveRight, JumpStart]),
+		CommandFrame::new(2, vec![MoveHorizontal(-64), JumpHold]).with_offset(64),
+	])
+}
+
+fn ticks(vectors: &mut Vec<Value>) {
+	let all = [
+		("tick_empty", Tick::new(0, vec![])),
+		("tick_two_players", two_player_tick()),
+		// The index takes up all ten bytes a varint can.
+		("tick_max_index", Tick::new(u64::MAX, vec![CommandFrame::new(0, vec![PlayerIntent::JumpHold])])),
+	];
+	for (name, tick) in all {
+		vectors.push(vector(name, "Tick", tick_fields(&tick), &encode(&tick)));
+	}
+
+	let mut bytes = encode(&two_player_tick());
+	*bytes.last_mut().unwrap() ^= 1;
+	vectors.push(rejected("tick_wrong_hash", "Tick", &bytes, "HashMismatch"));
+	let mut bytes = encode(&two_player_tick());
+	bytes.truncate(bytes.len() - 1);
+	vectors.push(rejected("tick_hash_truncated", "Tick", &bytes, "UnexpectedEof"));
+	// A varint running on past ten bytes.
+	vectors.push(rejected("tick_index_overflow", "Tick", &[0xff; 11], "VarintOverflow"));
+}
+
+fn packets(vectors: &mut Vec<Value>) {
+	let hash = *two_player_tick().hash();
+	let info = PlayerInfo::new(3, "Ada");
+	let all: Vec<(&str, Packet<PlayerIntent>)> = vec![
+		("packet_predicted_tick", Packet::PredictedTick {
+			sender: 2,
+			tick: EncodedTick::Keyframe(two_player_tick()),
+			past_frames: vec![PastFrame { owner: 0, age: 1, intents: vec![PlayerIntent::MoveLeft], offset: 0 }],
+			state_hash: Some(StateHash { index: 280, hash }),
+		}),
+		("packet_authoritative_tick", Packet::AuthoritativeTick(two_player_tick())),
+		("packet_received", Packet::Received { index: 300 }),
+		("packet_ack", Packet::Ack { index: 300, hash }),
+		("packet_snapshot", Packet::Snapshot(Snapshot {
+			index: 300,
+			seed: 7,
+			sim_config: SimConfig::default(),
+			quantized: false,
+			players: vec![SnapshotEntry { owner: 3, player: Player::default() }],
+			platforms: vec![],
+			coins: Coins::default(),
+			infos: vec![info.clone()],
+		})),
+		("packet_snapshot_quantized", Packet::Snapshot(Snapshot {
+			index: 300,
+			seed: 7,
+			sim_config: SimConfig::default(),
+			quantized: true,
+			players: vec![SnapshotEntry { owner: 3, player: Player::default() }],
+			platforms: vec![],
+			coins: Coins::default(),
+			infos: vec![],
+		})),
+		("packet_join", Packet::Join(3)),
+		("packet_leave", Packet::Leave(ClientId::MAX)),
+		("packet_welcome", Packet::Welcome { tick_rate: 20, seed: u64::MAX, sim_config: SimConfig::default() }),
+		("packet_state_hash", Packet::StateHash(StateHash { index: 300, hash })),
+		("packet_player_info", Packet::PlayerInfo(info)),
+		("packet_player_info_longest_name", Packet::PlayerInfo(PlayerInfo::new(3, &"é".repeat(MAX_NAME_LEN)))),
+		("packet_chat", Packet::Chat { sender: 3, text: "gg".into() }),
+		("packet_time_request", Packet::TimeRequest(TimeRequest { sender: 3, client_time: 12.5 })),
+		("packet_time_response", Packet::TimeResponse(TimeResponse { client_time: 12.5, tick: 300, progress: 0.25 })),
+		("packet_connect_request", Packet::ConnectRequest(ConnectRequest { requested_id: Some(3), name: Some("Ada".into()) })),
+		("packet_connect_request_empty", Packet::ConnectRequest(ConnectRequest::default())),
+		("packet_connect_accept", Packet::ConnectAccept(ConnectAccept {
+			client_id: 0x0102,
+			tick_rate: 20,
+			starting_tick: 300,
+			sim_config: SimConfig::default(),
+		})),
+		("packet_connect_reject", Packet::ConnectReject(RejectReason::GameFull)),
+		("packet_snapshot_request", Packet::SnapshotRequest(SnapshotRequest { sender: 3, reason: SnapshotReason::Reconnect })),
+	];
+	for (name, packet) in all {
+		vectors.push(vector(name, "Packet", packet_fields(&packet), &encode(&packet)));
+	}
+
+	// What every packet starts with, wrong in one place or another.
+	let received = encode(&Packet::<PlayerIntent>::Received { index: 300 });
+	let with = |at: usize, value: u8| {
+		let mut bytes = received.clone();
+		bytes[at] = value;
+		bytes
+	};
+	vectors.push(rejected("packet_wrong_magic", "Packet", &with(0, b'X'), "WrongMagic"));
+	vectors.push(rejected("packet_older_version", "Packet", &with(2, PROTOCOL_VERSION - 1), "UnsupportedVersion"));
+	vectors.push(rejected("packet_newer_version", "Packet", &with(2, PROTOCOL_VERSION + 1), "UnsupportedVersion"));
+	vectors.push(rejected("packet_other_hasher", "Packet", &with(3, Hasher::ID + 1), "UnsupportedHasher"));
+	vectors.push(rejected("packet_unknown_tag", "Packet", &with(4, 0xff), "InvalidTag"));
+	let mut trailing = received.clone();
+	trailing.push(0);
+	vectors.push(rejected("packet_trailing_bytes", "Packet", &trailing, "TrailingBytes"));
+	vectors.push(rejected("packet_header_only", "Packet", &received[..4], "UnexpectedEof"));
+
+	// Strings too long, or not UTF-8 at all, written out by hand as the
+	// encoder wouldn't.
+	let header = &received[..4];
+	let mut writer = ByteWriter::new();
+	writer.write_bytes(header);
+	writer.write_u8(Packet::<PlayerIntent>::TAG_CHAT);
+	writer.write_bytes(&3u16.to_le_bytes());
+	writer.write_varint(MAX_CHAT_LEN as u64 + 1);
+	writer.write_bytes(&[b'a'; MAX_CHAT_LEN + 1]);
+	vectors.push(rejected("packet_chat_too_long", "Packet", writer.as_slice(), "LimitExceeded"));
+	let mut writer = ByteWriter::new();
+	writer.write_bytes(header);
+	writer.write_u8(Packet::<PlayerIntent>::TAG_PLAYER_INFO);
+	writer.write_bytes(&3u16.to_le_bytes());
+	writer.write_varint(2);
+	writer.write_bytes(&[0xc3, 0x28]);
+	vectors.push(rejected("packet_name_not_utf8", "Packet", writer.as_slice(), "InvalidUtf8"));
+}
+
+fn batches(vectors: &mut Vec<Value>) {
+	let packets = vec![
+		encode(&Packet::<PlayerIntent>::Received { index: 300 }),
+		encode(&Packet::<PlayerIntent>::Join(3)),
+	];
+	let mut writer = ByteWriter::new();
+	encode_batch(&packets, &mut writer);
+	vectors.push(vector("batch_two_packets", "Batch", batch_fields(&packets), writer.as_slice()));
+	let mut writer = ByteWriter::new();
+	encode_batch(&[], &mut writer);
+	vectors.push(vector("batch_empty", "Batch", batch_fields(&[]), writer.as_slice()));
+
+	let mut writer = ByteWriter::new();
+	encode_batch(&packets, &mut writer);
+	let mut bytes = writer.as_slice().to_vec();
+	bytes[BATCH_MAGIC.len()] = BATCHING_VERSION - 1;
+	vectors.push(rejected("batch_older_version", "Batch", &bytes, "UnsupportedVersion"));
+	bytes[BATCH_MAGIC.len()] = PROTOCOL_VERSION;
+	bytes.truncate(bytes.len() - 1);
+	vectors.push(rejected("batch_truncated", "Batch", &bytes, "LengthOutOfBounds"));
+}
+
+/// What gets handed to the host through the FFI, rather than sent.
+fn host_types(vectors: &mut Vec<Value>) {
+	let summary = StateSummary {
+		tick: 300,
+		players: vec![
+			PlayerSummary { owner: 1, x: 12.5, y: 226.0, score: 3 },
+			PlayerSummary { owner: 0x0102, x: 0.0, y: -8.25, score: 0 },
+		],
+	};
+	vectors.push(vector("state_summary", "StateSummary", summary_fields(&summary), &encode(&summary)));
+	let events = vec![
+		TickEvent { tick: 300, event: SimEvent::Jumped { id: 1 } },
+		TickEvent { tick: 301, event: SimEvent::HitWall { id: 1, side: WallSide::Right } },
+		TickEvent { tick: 320, event: SimEvent::Landed { id: 0x0102, impact_velocity: 312.5 } },
+	];
+	vectors.push(vector("tick_events", "TickEvents", events_fields(&events), &encode(&events)));
+	vectors.push(rejected("tick_events_unknown_kind", "TickEvents", &[0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x01, 0x00], "InvalidTag"));
+}
+
+fn intent_fields(intent: &PlayerIntent) -> Value {
+	match intent {
+		PlayerIntent::MoveHorizontal(magnitude) => json!({ "kind": "MoveHorizontal", "magnitude": magnitude }),
+		_ => json!({ "kind": format!("{:?}", intent) }),
+	}
+}
+
+fn intents_fields(intents: &[PlayerIntent]) -> Value {
+	intents.iter().map(intent_fields).collect()
+}
+
+fn frame_fields(frame: &CommandFrame<PlayerIntent>) -> Value {
+	json!({ "owner": frame.owner(), "intents": intents_fields(frame.intents()), "offset": frame.offset() })
+}
+
+fn tick_fields(tick: &Tick<PlayerIntent>) -> Value {
+	let frames: Vec<Value> = tick.command_frames().iter().map(frame_fields).collect();
+	json!({ "index": tick.index(), "command_frames": frames, hasher_name(): to_hex(tick.hash()) })
+}
+
+fn state_hash_fields(state_hash: &StateHash) -> Value {
+	json!({ "index": state_hash.index, "hash": to_hex(&state_hash.hash) })
+}
+
+fn packet_fields(packet: &Packet<PlayerIntent>) -> Value {
+	let fields = match packet {
+		Packet::PredictedTick { sender, tick, past_frames, state_hash } => {
+			let tick = match tick {
+				EncodedTick::Keyframe(tick) => json!({ "keyframe": tick_fields(tick) }),
+				// Deltas depend on what was sent before, so there's no
+				// making sense of them in a vector on their own.
+				EncodedTick::Delta(_) => json!({ "delta": null }),
+			};
+			let past_frames: Vec<Value> = past_frames
+				.iter()
+				.map(|x| json!({ "age": x.age, "intents": intents_fields(&x.intents), "offset": x.offset }))
+				.collect();
+			json!({ "sender": sender, "tick": tick, "past_frames": past_frames, "state_hash": state_hash.as_ref().map(state_hash_fields) })
+		},
+		Packet::AuthoritativeTick(tick) => json!({ "tick": tick_fields(tick) }),
+		Packet::Received { index } => json!({ "index": index }),
+		Packet::Ack { index, hash } => json!({ "index": index, "hash": to_hex(hash) }),
+		Packet::Snapshot(snapshot) => json!({ "snapshot": serde_json::to_value(snapshot).unwrap() }),
+		Packet::Join(client_id) | Packet::Leave(client_id) => json!({ "client_id": client_id }),
+		Packet::Welcome { tick_rate, seed, sim_config } => {
+			json!({ "tick_rate": tick_rate, "seed": seed, "sim_config": serde_json::to_value(sim_config).unwrap() })
+		},
+		Packet::StateHash(state_hash) => state_hash_fields(state_hash),
+		Packet::PlayerInfo(info) => json!({ "owner": info.owner, "name": info.name }),
+		Packet::Chat { sender, text } => json!({ "sender": sender, "text": text }),
+		Packet::TimeRequest(request) => json!({ "sender": request.sender, "client_time": request.client_time }),
+		Packet::TimeResponse(response) => {
+			json!({ "client_time": response.client_time, "tick": response.tick, "progress": response.progress })
+		},
+		Packet::ConnectRequest(request) => json!({ "requested_id": request.requested_id, "name": request.name }),
+		Packet::ConnectAccept(accept) => json!({
+			"client_id": accept.client_id,
+			"tick_rate": accept.tick_rate,
+			"starting_tick": accept.starting_tick,
+			"sim_config": serde_json::to_value(accept.sim_config).unwrap(),
+		}),
+		Packet::ConnectReject(reason) => json!({ "reason": format!("{:?}", reason) }),
+		Packet::SnapshotRequest(request) => json!({ "sender": request.sender, "reason": format!("{:?}", request.reason) }),
+	};
+	json!({ "tag": packet.tag(), "kind": packet_kind(packet), "fields": fields })
+}
+
+fn packet_kind(packet: &Packet<PlayerIntent>) -> &'static str {
+	match packet {
+		Packet::PredictedTick { .. } => "PredictedTick",
+		Packet::AuthoritativeTick(_) => "AuthoritativeTick",
+		Packet::Received { .. } => "Received",
+		Packet::Ack { .. } => "Ack",
+		Packet::Snapshot(_) => "Snapshot",
+		Packet::Join(_) => "Join",
+		Packet::Leave(_) => "Leave",
+		Packet::Welcome { .. } => "Welcome",
+		Packet::StateHash(_) => "StateHash",
+		Packet::PlayerInfo(_) => "PlayerInfo",
+		Packet::Chat { .. } => "Chat",
+		Packet::TimeRequest(_) => "TimeRequest",
+		Packet::TimeResponse(_) => "TimeResponse",
+		Packet::ConnectRequest(_) => "ConnectRequest",
+		Packet::ConnectAccept(_) => "ConnectAccept",
+		Packet::ConnectReject(_) => "ConnectReject",
+		Packet::SnapshotRequest(_) => "SnapshotRequest",
+	}
+}
+
+fn batch_fields(packets: &[Vec<u8>]) -> Value {
+	let packets: Vec<String> = packets.iter().map(|x| to_hex(x)).collect();
+	json!({ "packets": packets })
+}
+
+fn summary_fields(summary: &StateSummary) -> Value {
+	let players: Vec<Value> = summary.players
+		.iter()
+		.map(|x| json!({ "owner": x.owner, "x": x.x, "y": x.y, "score": x.score }))
+		.collect();
+	json!({ "tick": summary.tick, "players": players })
+}
+
+fn events_fields(events: &[TickEvent]) -> Value {
+	events
+		.iter()
+		.map(|x| {
+			let mut fields = json!({ "tick": x.tick, "tag": x.event.tag(), "id": x.event.id() });
+			match x.event {
+				SimEvent::Landed { impact_velocity, .. } => fields["impact_velocity"] = json!(impact_velocity),
+				SimEvent::HitWall { side, .. } => fields["side"] = json!(format!("{:?}", side)),
+				SimEvent::Jumped { .. } => {},
+			}
+			fields
+		})
+		.collect()
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).expect("vectors have to encode");
+	writer.as_slice().to_vec()
+}
+
+/// Reads a `T` which has to take up all of `bytes`.
+fn whole<T: NetType>(bytes: &[u8]) -> Result<T, DecodeError> {
+	let mut reader = ByteReader::new(bytes);
+	let value = T::from_bytes(&mut reader)?;
+	match reader.remaining() {
+		0 => Ok(value),
+		count => Err(DecodeError::TrailingBytes { count }),
+	}
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+	if !hex.len().is_multiple_of(2) {
+		return Err(format!("{} isn't hex", hex));
+	}
+	(0..hex.len())
+		.step_by(2)
+		.map(|x| u8::from_str_radix(&hex[x..x + 2], 16).map_err(|_| format!("{} isn't hex", hex)))
+		.collect()
+}
@@ -0,0 +1,77 @@
+//! Scripted input, for the game to play itself. A script is nothing but
+//! intents, each held for so many ticks, so whatever plays it goes through
+//! the same prediction, serialization and reconciliation real input does;
+//! and it makes for a canonical input script to check determinism with.
+//!
+//! Driving the local player with one is up to `input::Demo`.
+
+use crate::protocol::PlayerIntent;
+use PlayerIntent::*;
+
+/// Walks right and back left again, jumping along the way and standing
+/// still for a bit at either end. Every entry holds its intents for as many
+/// ticks as it says.
+pub const PATROL: &[(u32, &[PlayerIntent])] = &[
+	(30, &[MoveRight]),
+	(1, &[MoveRight, JumpStart]),
+	(8, &[MoveRight, JumpHold]),
+	(30, &[MoveRight]),
+	(20, &[]),
+	(1, &[JumpStart]),
+	(20, &[]),
+	(30, &[MoveLeft]),
+	(1, &[MoveLeft, JumpStart]),
+	(8, &[MoveLeft, JumpHold]),
+	(30, &[MoveLeft]),
+	(20, &[]),
+];
+
+/// A list of `(duration_ticks, intents)` entries, played in order, and over
+/// again from the top once through.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DemoScript {
+	steps: Vec<(u32, Vec<PlayerIntent>)>,
+}
+
+impl Default for DemoScript {
+	/// `PATROL`.
+	fn default() -> Self {
+		Self::new(PATROL.iter().map(|(ticks, intents)| (*ticks, intents.to_vec())).collect())
+	}
+}
+
+impl DemoScript {
+	/// Entries lasting no ticks at all are left out.
+	pub fn new(mut steps: Vec<(u32, Vec<PlayerIntent>)>) -> Self {
+		steps.retain(|(ticks, _)| *ticks > 0);
+		Self { steps }
+	}
+
+	pub fn steps(&self) -> &[(u32, Vec<PlayerIntent>)] {
+		&self.steps
+	}
+
+	/// How many ticks it takes to get through once.
+	pub fn len(&self) -> u64 {
+		self.steps.iter().map(|(ticks, _)| *ticks as u64).sum()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.steps.is_empty()
+	}
+
+	/// The intents held on the `tick`th tick of playing it, counting from 0.
+	/// An empty script holds nothing, forever.
+	pub fn intents(&self, tick: u64) -> &[PlayerIntent] {
+		let Some(mut tick) = tick.checked_rem(self.len()) else {
+			return &[];
+		};
+		for (ticks, intents) in &self.steps {
+			match tick.checked_sub(*ticks as u64) {
+				Some(rest) => tick = rest,
+				None => return intents,
+			}
+		}
+		&[]
+	}
+}
@@ -0,0 +1,137 @@
+//! Diagnostic bundles, for bug reports: what a client knew at the frame it
+//! got captured at, in a single blob, which a maintainer can load back and
+//! resimulate from; see `Game::export_diagnostics`.
+//!
+//! A bundle starts with `DIAGNOSTICS_MAGIC` and a `DiagnosticsHeader`, then
+//! a `DiagnosticsReport`, and ends with the client's state as
+//! `Game::export_state` writes it, only with no more than
+//! `DIAGNOSTICS_TICKS` of the newest ticks in the history.
+
+use std::fmt;
+use command_networking_derive::NetType;
+
+use crate::game::{ConnectionState, Game};
+use crate::net::{ByteReader, DecodeError, NetType};
+use crate::protocol::SnapshotReason;
+use crate::saved_state::{SavedState, StateError};
+
+/// Every diagnostic bundle starts with these.
+pub const DIAGNOSTICS_MAGIC: [u8; 4] = *b"CNDG";
+/// Bumped whenever what goes into a bundle changes, as opposed to the
+/// protocol version, which the state at its end is checked against.
+pub const DIAGNOSTICS_VERSION: u8 = 1;
+/// How many ticks of the history a bundle holds at most, the newest ones.
+pub const DIAGNOSTICS_TICKS: usize = 64;
+
+/// When a bundle got captured.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct DiagnosticsHeader {
+	/// The `DIAGNOSTICS_VERSION` the bundle was captured with.
+	pub version: u8,
+	/// How many frames the client had run, this one included.
+	pub frame: u64,
+	/// The time of capture, as per `get_time`.
+	pub captured_at: f64,
+}
+
+/// How many of our predictions the server corrected, with one strategy;
+/// see `Game::mispredictions`.
+#[derive(Clone, PartialEq, Debug, NetType)]
+pub struct StrategyCount {
+	pub strategy: String,
+	pub count: u32,
+}
+
+/// Everything in a bundle that the state doesn't already hold.
+#[derive(Clone, PartialEq, Debug, NetType)]
+pub struct DiagnosticsReport {
+	pub connection_state: ConnectionState,
+	pub desync: Option<u64>,
+	/// Why we're waiting on a snapshot, if we are.
+	pub snapshot_reason: Option<SnapshotReason>,
+	/// See `PredictionStats`.
+	pub predicted: u64,
+	pub confirmed: u64,
+	pub corrected: u64,
+	pub remote_intents: u64,
+	pub local_frames_lost: u64,
+	pub mispredictions: Vec<StrategyCount>,
+	pub stale_ticks: u32,
+	pub rejected_frames: u32,
+	pub clamped_frames: u32,
+	pub skipped_ticks: u32,
+	/// The last error decoding a packet from the host, as displayed.
+	pub last_decode_error: Option<String>,
+	/// The last `log::RECENT_LINES` log lines, oldest first; see
+	/// `log::recent`.
+	pub log: Vec<String>,
+}
+
+/// A bundle, read back; see `inspect_diagnostics`.
+pub struct Diagnostics {
+	pub header: DiagnosticsHeader,
+	pub report: DiagnosticsReport,
+	pub state: SavedState,
+}
+
+impl Diagnostics {
+	/// A game restored from the bundle's state, the way `import_state`
+	/// restores one, with as much of the history as the bundle holds; see
+	/// `Game::resimulate`.
+	pub fn into_game(self) -> Game {
+		let mut game = Game::new(self.state.header().client_id);
+		game.restore_state(self.state);
+		game
+	}
+}
+
+/// Why a bundle couldn't be read back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticsError {
+	/// The bytes don't start with `DIAGNOSTICS_MAGIC`; it's not a bundle.
+	NotDiagnostics,
+	/// The bundle was captured by a build putting other things into it.
+	UnsupportedVersion { got: u8, expected: u8 },
+	Malformed(DecodeError),
+	/// The state at the end of the bundle couldn't be read back.
+	State(StateError),
+}
+
+impl fmt::Display for DiagnosticsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DiagnosticsError::NotDiagnostics => write!(f, "not a diagnostic bundle"),
+			DiagnosticsError::UnsupportedVersion { got, expected } => {
+				write!(f, "bundle was captured with version {} (expected {})", got, expected)
+			},
+			DiagnosticsError::Malformed(error) => write!(f, "malformed bundle: {}", error),
+			DiagnosticsError::State(error) => write!(f, "bundle holds a bad state: {}", error),
+		}
+	}
+}
+
+impl From<DecodeError> for DiagnosticsError {
+	fn from(error: DecodeError) -> Self {
+		DiagnosticsError::Malformed(error)
+	}
+}
+
+/// Reads a bundle as written by `Game::export_diagnostics`, say one
+/// attached to a bug report, to poke at in a test.
+pub fn inspect_diagnostics(bytes: &[u8]) -> Result<Diagnostics, DiagnosticsError> {
+	let mut reader = ByteReader::new(bytes);
+	if reader.read_array::<4>() != Ok(DIAGNOSTICS_MAGIC) {
+		return Err(DiagnosticsError::NotDiagnostics);
+	}
+	let header = DiagnosticsHeader::from_bytes(&mut reader)?;
+	if header.version != DIAGNOSTICS_VERSION {
+		return Err(DiagnosticsError::UnsupportedVersion {
+			got: header.version,
+			expected: DIAGNOSTICS_VERSION
+		});
+	}
+	let report = DiagnosticsReport::from_bytes(&mut reader)?;
+	let state = reader.read_exact(reader.remaining())?;
+	let state = SavedState::parse(state).map_err(DiagnosticsError::State)?;
+	Ok(Diagnostics { header, report, state })
+}
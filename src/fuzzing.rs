@@ -0,0 +1,195 @@
+//! What the fuzz targets in `fuzz/` run on every input, along with the
+//! inputs their corpus starts out with. Only built with the `fuzzing`
+//! feature. `examples/decode_fuzz.rs` runs the same checks on mutated
+//! seeds, for machines without cargo-fuzz.
+
+use crate::batch::{encode_batch, is_batch, split_batch};
+use crate::game::{Coin, Platform, SimConfig};
+use crate::net::{ByteReader, ByteWriter, NetType};
+use crate::protocol::{
+	ClientId, CommandFrame, ConnectAccept, ConnectRequest, EncodedTick, Packet, PlayerInfo, PlayerIntent, RejectReason, Snapshot,
+	SnapshotReason, SnapshotRequest, StateHash, Tick, TickDecoder, TickEncoder, TimeRequest, TimeResponse, MAX_FRAMES_PER_TICK
+};
+use PlayerIntent::*;
+
+/// The most any single allocation may take while decoding, whatever the
+/// bytes claim. That's a full tick's worth of frames, the largest
+/// collection the protocol allows for.
+pub const ALLOCATION_BOUND: usize = MAX_FRAMES_PER_TICK * std::mem::size_of::<CommandFrame<PlayerIntent>>();
+
+/// The tick deltas in the corpus are encoded against, which the decoder
+/// starts out holding.
+fn baseline() -> Tick<PlayerIntent> {
+	Tick::new(300, vec![
+		CommandFrame::new(1, vec![MoveRight, JumpStart]),
+		CommandFrame::new(2, vec![MoveHorizontal(-64), JumpHold]),
+	])
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+/// Encodes `value` again and checks that it decodes into the same thing;
+/// whatever made it through once has to make it through every time.
+fn round_trip<T: NetType + PartialEq + std::fmt::Debug>(value: &T) {
+	let bytes = encode(value);
+	match T::from_bytes(&mut ByteReader::new(&bytes)) {
+		Ok(decoded) => assert_eq!(&decoded, value, "decoded differently after being encoded again"),
+		Err(error) => panic!("{:?} didn't decode after being encoded again: {}", value, error),
+	}
+}
+
+/// Everything that made it through decoding one input.
+pub struct Decoded {
+	tick: Option<Tick<PlayerIntent>>,
+	frame: Option<CommandFrame<PlayerIntent>>,
+	packet: Option<Packet<PlayerIntent>>,
+	/// A predicted tick resolved against `baseline`, and the ticks
+	/// recovered from its past frames.
+	resolved: Vec<Tick<PlayerIntent>>,
+	/// The packets in a batch, if it was one.
+	batch: Option<Vec<Vec<u8>>>,
+}
+
+/// Feeds `bytes` into every decoder which sees bytes from the wire, as a
+/// bare tick, as a packet and as a batch. Decoding is allowed to fail, but only with a
+/// `DecodeError`; panics are what the fuzzer is looking for.
+///
+/// Only this part is held to `ALLOCATION_BOUND`, as `check` allocates in
+/// proportion to what actually got decoded.
+pub fn decode(bytes: &[u8]) -> Decoded {
+	let mut decoded = Decoded {
+		tick: Tick::from_bytes(&mut ByteReader::new(bytes)).ok(),
+		frame: CommandFrame::from_bytes(&mut ByteReader::new(bytes)).ok(),
+		packet: None,
+		resolved: vec![],
+		batch: match is_batch(bytes) {
+			true => split_batch(bytes).ok().map(|x| x.into_iter().map(<[u8]>::to_vec).collect()),
+			false => None,
+		},
+	};
+
+	match Packet::decode(bytes) {
+		Ok(Packet::PredictedTick { sender, tick, past_frames, state_hash }) => {
+			let mut decoder = TickDecoder::default();
+			decoder.decode(EncodedTick::Keyframe(baseline())).unwrap();
+			let tick = match decoder.decode(tick) {
+				Ok(tick) => {
+					decoded.resolved = decoder.recover(tick.index(), past_frames.clone());
+					decoded.resolved.push(tick.clone());
+					EncodedTick::Keyframe(tick)
+				},
+				Err(_) => return decoded,
+			};
+			decoded.packet = Some(Packet::PredictedTick { sender, tick, past_frames, state_hash });
+		},
+		Ok(packet) => decoded.packet = Some(packet),
+		Err(_) => {},
+	}
+	decoded
+}
+
+/// Checks that whatever got decoded comes out the same when encoded and
+/// decoded again; anything that made it through once has to every time.
+pub fn check(decoded: Decoded) {
+	if let Some(tick) = &decoded.tick {
+		round_trip(tick);
+	}
+	if let Some(frame) = &decoded.frame {
+		round_trip(frame);
+	}
+	for tick in &decoded.resolved {
+		round_trip(tick);
+	}
+	if let Some(packet) = &decoded.packet {
+		if let Err(error) = Packet::<PlayerIntent>::decode(&encode(packet)) {
+			panic!("packet didn't decode after being encoded again: {}", error);
+		}
+	}
+	if let Some(packets) = &decoded.batch {
+		let mut writer = ByteWriter::new();
+		encode_batch(packets, &mut writer);
+		match split_batch(writer.as_slice()) {
+			Ok(split) => assert_eq!(&split, packets, "batch split differently after being encoded again"),
+			Err(error) => panic!("batch didn't split after being encoded again: {}", error),
+		}
+	}
+}
+
+/// A packet predicting `tick`, with `sender`'s frames for the ticks before it.
+fn predicted(sender: ClientId, tick: EncodedTick<PlayerIntent>, state_hash: Option<StateHash>) -> Packet<PlayerIntent> {
+	Packet::PredictedTick { sender, tick, past_frames: vec![], state_hash }
+}
+
+/// Well-formed inputs for the corpus to grow from: the values pinned in
+/// `tests/wire_goldens.rs`, a packet of every kind, and a batch.
+pub fn seeds() -> Vec<Vec<u8>> {
+	let empty_tick: Tick<PlayerIntent> = Tick::new(0, vec![]);
+	let next = Tick::new(301, vec![
+		CommandFrame::new(1, vec![MoveRight, JumpHold]).with_offset(128),
+		CommandFrame::new(2, vec![MoveHorizontal(-64), JumpHold]),
+	]);
+	let mut encoder = TickEncoder::default();
+	let keyframe = encoder.encode(&baseline());
+	encoder.acknowledge(300);
+	let delta = encoder.encode(&next);
+	let state_hash = StateHash { index: 290, hash: *next.hash() };
+
+	// The default world, with one player, platform and name, as snapshots
+	// can only be built from the bytes up.
+	let mut writer = ByteWriter::new();
+	writer.write_u64_le(300);
+	writer.write_u64_le(0x5eed);
+	SimConfig::default().to_bytes(&mut writer).unwrap();
+	// Positions in full.
+	false.to_bytes(&mut writer).unwrap();
+	writer.write_varint(1);
+	(1 as ClientId).to_bytes(&mut writer).unwrap();
+	writer.write_bytes(&[0; 16]);
+	writer.write_u8(1);
+	writer.write_bytes(&[0; 3]);
+	vec![Platform::new(300.0, 186.0, 64.0, 10.0)].to_bytes(&mut writer).unwrap();
+	// One coin, and one score.
+	vec![Coin::new(320.0, 164.0)].to_bytes(&mut writer).unwrap();
+	writer.write_varint(1);
+	(1 as ClientId).to_bytes(&mut writer).unwrap();
+	writer.write_bytes(&[3, 0, 0, 0]);
+	vec![PlayerInfo::new(1, "Player 1")].to_bytes(&mut writer).unwrap();
+	let snapshot = Snapshot::from_bytes(&mut ByteReader::new(writer.as_slice())).expect("the seed snapshot is malformed");
+
+	let packets = [
+		predicted(1, keyframe, None),
+		predicted(1, delta, Some(state_hash)),
+		Packet::AuthoritativeTick(next.clone()),
+		Packet::Received { index: 300 },
+		Packet::Ack { index: 301, hash: *next.hash() },
+		Packet::Snapshot(snapshot),
+		Packet::Join(3),
+		Packet::Leave(3),
+		Packet::Welcome { tick_rate: 20, seed: 0x5eed, sim_config: SimConfig::default() },
+		Packet::StateHash(state_hash),
+		Packet::PlayerInfo(PlayerInfo::new(3, "Bot 3")),
+		Packet::Chat { sender: 3, text: "gg".into() },
+		Packet::TimeRequest(TimeRequest { sender: 3, client_time: 1.5 }),
+		Packet::TimeResponse(TimeResponse { client_time: 1.5, tick: 300, progress: 0.25 }),
+		Packet::ConnectRequest(ConnectRequest { requested_id: Some(3), name: Some("Bot 3".into()) }),
+		Packet::ConnectAccept(ConnectAccept { client_id: 3, tick_rate: 20, starting_tick: 300, sim_config: SimConfig::default() }),
+		Packet::ConnectReject(RejectReason::IdTaken),
+		Packet::SnapshotRequest(SnapshotRequest { sender: 3, reason: SnapshotReason::Pruned }),
+	];
+
+	let mut seeds = vec![
+		encode(&CommandFrame::<PlayerIntent>::new(7, vec![])),
+		encode(&empty_tick),
+		encode(&baseline()),
+	];
+	seeds.extend(packets.iter().map(encode));
+
+	let mut batch = ByteWriter::new();
+	encode_batch(&[encode(&Packet::<PlayerIntent>::Received { index: 300 }), encode(&packets[1])], &mut batch);
+	seeds.push(batch.as_slice().to_vec());
+	seeds
+}
\ No newline at end of file
@@ -0,0 +1,5112 @@
+//! The simulated world, and the client's view of its prediction history.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+
+#[cfg(feature = "client")]
+use macroquad::prelude::*;
+
+use command_networking_derive::NetType;
+#[cfg(feature = "client")]
+use crate::batch::{encode_batch, is_batch, split_batch};
+use crate::chat::{self, ChatLog};
+use crate::diagnostics::{DiagnosticsHeader, DiagnosticsReport, StrategyCount, DIAGNOSTICS_MAGIC, DIAGNOSTICS_TICKS, DIAGNOSTICS_VERSION};
+use crate::hash::{Hash, Hasher, TickHasher};
+use crate::inbox::*;
+use crate::log::LogLevel;
+use crate::net::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+#[cfg(feature = "client")]
+use crate::overlay::{DebugStats, PlayerStats};
+use crate::prediction::{self, CorrectionCause, InputStats, PredictionContext, PredictionStats, PredictionStrategy, RemoteMotion, RepeatLast};
+#[cfg(feature = "client")]
+use crate::prediction::IntentSummary;
+use crate::protocol::*;
+use crate::replay::{MemberEntry, Playback, Recorder, Replay, ReplayHeader};
+use crate::saved_state::{SavedGame, SavedState, SavedTick, StateError, StateHeader};
+use crate::rng::Rng;
+use crate::scalar::*;
+#[cfg(feature = "client")]
+use crate::transport::{SendError, Transport, TransportEvent};
+#[cfg(feature = "client")]
+use crate::viewport::{view_origin, Viewport};
+use crate::{SCREEN_SIZE, TICKRATE, WORLD_HEIGHT, WORLD_WIDTH};
+#[cfg(feature = "client")]
+use crate::TICK_DELTA;
+
+/// How far the client may predict past the last confirmed tick. Predicting
+/// any further would only mean a bigger rollback, once the server catches up.
+pub const MAX_TICKS_AHEAD: u64 = 8;
+/// Seconds without the accepted head advancing before the connection is
+/// considered lost.
+pub const CONNECTION_TIMEOUT: f64 = 3.0;
+/// Seconds of the transport turning down every packet, before the
+/// connection is considered lost; see `Game::send`.
+pub const SEND_FAILURE_TIMEOUT: f64 = 1.0;
+/// Most packets held on to for sending again, once the transport turned
+/// them down. Past that, the oldest ones are dropped; the ticks sent after
+/// them carry their frames too.
+pub const MAX_RESEND_PACKETS: usize = 32;
+/// How many of the local player's previous command frames are repeated
+/// alongside every predicted tick, so a lost packet doesn't lose its
+/// intents, unless told otherwise; see `Game::set_redundant_frames`.
+pub const DEFAULT_REDUNDANT_FRAMES: u8 = 3;
+/// Ticks in a row nobody has to do anything on, before the client goes
+/// idle and stops sending every tick; see `Game::is_idle`.
+pub const IDLE_TICKS: u64 = 10;
+/// Most authoritative ticks held back while waiting for an earlier one to
+/// arrive. Anything past that is dropped, so a broken host can't make us
+/// buffer forever.
+pub const MAX_PENDING_TICKS: usize = 32;
+/// How many authoritative ticks are buffered before playback starts. The
+/// jitter buffer adapts this at runtime, depending on how bursty arrivals are.
+pub const JITTER_BUFFER_DEPTH: usize = 2;
+/// How many of the server's answers to time requests are averaged, before
+/// picking the tick to start predicting at; see `Game::sync_clock`.
+pub const CLOCK_SYNC_SAMPLES: usize = 3;
+/// Seconds between two time requests, so that their answers get spread
+/// over a bit of jitter, rather than all going through the same.
+pub const CLOCK_SYNC_INTERVAL: f64 = 0.05;
+/// Seconds to wait for answers to time requests, before giving up and
+/// starting with however many arrived.
+pub const CLOCK_SYNC_TIMEOUT: f64 = 1.0;
+/// Seconds between two connect requests, for as long as the server doesn't
+/// answer; see `Game::connecting`.
+pub const CONNECT_RETRY_INTERVAL: f64 = 0.5;
+/// Seconds a snapshot request goes unanswered before it's sent again;
+/// doubles with every try, up to `MAX_SNAPSHOT_RETRY_INTERVAL`. The request
+/// itself is sent reliably, but the snapshot answering it isn't.
+pub const SNAPSHOT_RETRY_INTERVAL: f64 = 1.0;
+/// The longest the wait between two snapshot requests ever gets, in seconds.
+pub const MAX_SNAPSHOT_RETRY_INTERVAL: f64 = 8.0;
+/// Ticks to predict ahead of where the server is expected to be once our
+/// ticks reach it, unless told otherwise; makes room for some jitter.
+pub const DEFAULT_INTERP_MARGIN: u8 = 2;
+/// Ticks behind the newest one remote players are drawn as of, unless told
+/// otherwise; see `Game::set_render_delay`. Anything the server corrects
+/// within that many ticks of its arrival never shows.
+pub const DEFAULT_RENDER_DELAY: u8 = 1;
+/// Most ticks remote players may be drawn behind, which is about as far
+/// back as the history goes.
+pub const MAX_RENDER_DELAY: u8 = TICK_RETENTION as u8;
+/// How many ticks the history may be skipped ahead by, getting in step
+/// with the server. Any further behind, and we'd rather get a snapshot.
+const MAX_CLOCK_CATCH_UP: u64 = TICK_RETENTION;
+/// Most the tick rate gets sped up or slowed down by, steering how far
+/// ahead of the server we are; see `Game::tick_speed`. Small enough not to
+/// be noticed, but it adds up to a tick every couple of seconds.
+pub const MAX_TICK_SPEED_ADJUSTMENT: f32 = 0.02;
+/// How much faster or slower ticks run per tick we're off the target by,
+/// before the limit above.
+const TICK_SPEED_GAIN: f64 = 0.05;
+/// Weight of a frame's ticks ahead in the smoothed count pacing goes by.
+/// Small, since the count jumps around whenever a confirmation arrives.
+const TICK_PACING_SMOOTHING: f64 = 0.05;
+/// How many ticks apart the state hashes we report to the server are. Any
+/// desync sticks around, so there's no need to report every tick.
+pub const STATE_REPORT_INTERVAL: u64 = 10;
+
+/// Every cap on what the client holds on to, all in one place. None of
+/// them should ever be reached in a healthy game; they're there so that a
+/// broken or hostile host, or a session running for hours, can't make the
+/// client's memory grow without bounds. Past a cap, whatever doesn't fit
+/// is dropped, with a warning; see `Game::set_memory_budget`, and
+/// `Game::assert_within_budget` for checking they all hold.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryBudget {
+	/// Ticks the history holds, predicted ones included. It's pruned down
+	/// to `TICK_RETENTION` confirmed ones, but ticks the server has yet to
+	/// settle are kept regardless; past this many, the oldest are dropped
+	/// all the same, and correcting them takes a snapshot.
+	pub history_ticks: usize,
+	/// Packets the host handed over, waiting in the inbox to be decoded;
+	/// see `push_packet`. That inbox is shared by everything in the
+	/// process, and so is its cap.
+	pub inbox_packets: usize,
+	/// Authoritative ticks waiting in the jitter buffer to be played back,
+	/// which they pile up in while paused.
+	pub buffered_ticks: usize,
+	/// Authoritative ticks waiting on an earlier one to arrive.
+	pub pending_ticks: usize,
+	/// The server's corrections waiting to be applied, usually for ticks
+	/// we have yet to predict.
+	pub pending_corrections: usize,
+	/// Packets the transport turned down, waiting to be sent again.
+	pub resend_packets: usize,
+	/// Bytes a replay gets recorded up to; the rest of the session is left
+	/// out of it.
+	pub replay_bytes: usize,
+	/// Clients we keep track of, as members of the game, and by name.
+	pub players: usize,
+	/// Events waiting for the presentation layer to take them; past this
+	/// many, the oldest are dropped.
+	pub sim_events: usize,
+}
+
+impl Default for MemoryBudget {
+	fn default() -> Self {
+		Self {
+			// Buffered ticks, and then some, ahead of however many are
+			// kept behind them.
+			history_ticks: 16 * TICK_RETENTION as usize,
+			inbox_packets: INBOX_CAPACITY,
+			// Ten seconds of being paused, at the default tick rate.
+			buffered_ticks: 10 * TICK_RETENTION as usize,
+			pending_ticks: MAX_PENDING_TICKS,
+			// Every buffered tick could end up a correction at once.
+			pending_corrections: 10 * TICK_RETENTION as usize,
+			resend_packets: MAX_RESEND_PACKETS,
+			// Hours of input, going by the size of a delta encoded tick.
+			replay_bytes: 16 * 1024 * 1024,
+			players: MAX_PLAYERS,
+			// Everyone landing on every tick of a second, and then some.
+			sim_events: 4 * MAX_PLAYERS * TICK_RETENTION as usize,
+		}
+	}
+}
+
+/// How much of one of the caps in a `MemoryBudget` is taken up; see
+/// `Game::memory_usage`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryUsage {
+	/// What's holding on to it, by the name of the field its cap is in.
+	pub subsystem: &'static str,
+	pub used: usize,
+	pub cap: usize,
+}
+
+impl MemoryUsage {
+	pub fn is_within_budget(&self) -> bool {
+		self.used <= self.cap
+	}
+}
+
+/// How long the ticks of a single frame get to take, rollbacks included,
+/// before whatever's left waits for the next frame; see
+/// `Game::set_tick_budget`.
+#[derive(Clone, Copy, Debug)]
+pub struct TickBudget {
+	/// In seconds.
+	pub per_frame: f64,
+	/// Tells the time, the way `get_time` does.
+	pub clock: fn() -> f64,
+}
+
+impl TickBudget {
+	pub fn from_millis(ms: u32, clock: fn() -> f64) -> Self {
+		Self {
+			per_frame: ms as f64 / 1000.0,
+			clock
+		}
+	}
+}
+
+/// Everything gameplay gets tuned with: how players move, fall and jump,
+/// how large they are, and how large the world they're in is, in logical
+/// pixels and seconds, so that it plays out about the same at any tick rate.
+/// Everyone in a game has to simulate with the same one, which the server
+/// hands out along with the tick rate; see `Game::set_sim_config`.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimConfig {
+	/// Downwards acceleration, in pixels per second squared.
+	pub gravity: Scalar,
+	/// Vertical velocity a jump starts out with, in pixels per second.
+	/// Negative, since up is.
+	pub jump_velocity: Scalar,
+	/// The fastest anyone falls, in pixels per second, so that a long fall
+	/// doesn't carry a player through the floor within a single tick.
+	pub terminal_velocity: Scalar,
+	/// Top horizontal speed, in pixels per second.
+	pub move_speed: Scalar,
+	/// How quickly MoveLeft/MoveRight get the player up to speed, in pixels
+	/// per second squared. Analog movement accelerates proportionally slower.
+	pub acceleration: Scalar,
+	pub player_width: Scalar,
+	pub player_height: Scalar,
+	/// The world's edges are walls, and its bottom is the floor.
+	pub world_width: Scalar,
+	pub world_height: Scalar,
+}
+
+impl SimConfig {
+	/// A hundred pixels to the meter.
+	pub const GRAVITY: Scalar = scalar(981.0);
+	/// Reaches the same height at the default tick rate as jumps did
+	/// before the physics were measured in pixels per second, give or take
+	/// a fraction of a pixel.
+	pub const JUMP_VELOCITY: Scalar = scalar(-549.0);
+	/// Twice as fast as falling from the top of a jump gets.
+	pub const TERMINAL_VELOCITY: Scalar = scalar(1000.0);
+	pub const MOVE_SPEED: Scalar = scalar(200.0);
+	pub const ACCELERATION: Scalar = scalar(1600.0);
+	pub const PLAYER_SIZE: Scalar = scalar(30.0);
+
+	/// Whether there's a game to be played with it at all: players of some
+	/// size that can move, in a world they fit into, falling down rather
+	/// than up. Anything else is turned down, wherever it comes from.
+	pub fn is_playable(&self) -> bool {
+		let zero = scalar(0.0);
+		self.gravity >= zero && self.terminal_velocity > zero && self.move_speed > zero && self.acceleration >= zero
+			&& self.jump_velocity <= zero && self.player_width > zero && self.player_height > zero
+			&& self.world_width > self.player_width && self.world_height > self.player_height
+	}
+
+	/// Rightmost position a player can be at without leaving the world.
+	pub fn max_x(&self) -> Scalar {
+		self.world_width - self.player_width
+	}
+
+	/// Vertical position of a player standing on the ground.
+	pub fn floor_y(&self) -> Scalar {
+		self.world_height - self.player_height
+	}
+
+	/// How large players get drawn, in pixels.
+	pub fn player_size(&self) -> (f32, f32) {
+		(scalar_to_f32(self.player_width), scalar_to_f32(self.player_height))
+	}
+
+	/// Feeds every field into `hasher`, so that the state hashes of two
+	/// clients simulating with different configs never match.
+	fn update_hasher(&self, hasher: &mut impl TickHasher) {
+		let fields = [
+			self.gravity,
+			self.jump_velocity,
+			self.terminal_velocity,
+			self.move_speed,
+			self.acceleration,
+			self.player_width,
+			self.player_height,
+			self.world_width,
+			self.world_height
+		];
+		for value in fields {
+			hasher.update(&scalar_bits(value).to_le_bytes());
+		}
+	}
+}
+
+impl Default for SimConfig {
+	fn default() -> Self {
+		Self {
+			gravity: Self::GRAVITY,
+			jump_velocity: Self::JUMP_VELOCITY,
+			terminal_velocity: Self::TERMINAL_VELOCITY,
+			move_speed: Self::MOVE_SPEED,
+			acceleration: Self::ACCELERATION,
+			player_width: Self::PLAYER_SIZE,
+			player_height: Self::PLAYER_SIZE,
+			world_width: scalar(WORLD_WIDTH as f32),
+			world_height: scalar(WORLD_HEIGHT as f32)
+		}
+	}
+}
+
+/// How many ticks the simulation runs per second, along with everything
+/// derived from it. Whatever's meant to happen over a span of time, rather
+/// than on every tick, is scaled to play out the same at any rate.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TickRate {
+	hz: u8,
+	/// The simulation's time step, in seconds.
+	delta: Scalar,
+	ground_friction: Scalar,
+	air_drag: Scalar,
+	jump_hold_boost: Scalar,
+	max_jump_hold_ticks: u8,
+	jump_buffer_ticks: u8,
+	coyote_ticks: u8,
+}
+
+impl TickRate {
+	/// `None` for a rate of 0, which would never get anywhere.
+	pub fn new(hz: u8) -> Option<Self> {
+		if hz == 0 {
+			return None;
+		}
+
+		// Ticks at the default rate one of ours lasts as long as. The
+		// fractions kept every tick compound, so they're raised to it.
+		let scale = TICKRATE as f32 / hz as f32;
+		let mut rate = Self {
+			hz,
+			delta: scalar(1.0 / hz as f32),
+			ground_friction: scalar(Player::GROUND_FRICTION.powf(scale)),
+			air_drag: scalar(Player::AIR_DRAG.powf(scale)),
+			jump_hold_boost: scalar(Player::JUMP_HOLD_BOOST * scale),
+			max_jump_hold_ticks: 0,
+			jump_buffer_ticks: 0,
+			coyote_ticks: 0,
+		};
+		rate.max_jump_hold_ticks = rate.scale_ticks(Player::MAX_JUMP_HOLD_TICKS);
+		rate.jump_buffer_ticks = rate.scale_ticks(Player::JUMP_BUFFER_TICKS);
+		rate.coyote_ticks = rate.scale_ticks(Player::COYOTE_TICKS);
+		Some(rate)
+	}
+
+	pub fn hz(&self) -> u8 {
+		self.hz
+	}
+
+	/// Seconds between two ticks.
+	pub fn delta(&self) -> f32 {
+		1.0 / self.hz as f32
+	}
+
+	/// The number of ticks lasting as long as `ticks` at the default rate,
+	/// rounded to the nearest one, but never less than one.
+	fn scale_ticks(&self, ticks: u8) -> u8 {
+		let scaled = (ticks as u32 * self.hz as u32 + TICKRATE as u32 / 2) / TICKRATE as u32;
+		scaled.clamp(1, u8::MAX as u32) as u8
+	}
+}
+
+impl Default for TickRate {
+	fn default() -> Self {
+		TickRate::new(TICKRATE).unwrap()
+	}
+}
+
+/// Everything simulating a tick has to go on, besides the world itself.
+pub struct SimContext<'a> {
+	seed: u64,
+	tick_index: u64,
+	rate: &'a TickRate,
+	config: &'a SimConfig,
+	rng: Rng,
+}
+
+impl<'a> SimContext<'a> {
+	pub fn new(seed: u64, tick_index: u64, rate: &'a TickRate, config: &'a SimConfig) -> Self {
+		Self {
+			seed,
+			tick_index,
+			rate,
+			config,
+			rng: Rng::for_tick(seed, tick_index)
+		}
+	}
+
+	pub fn tick_index(&self) -> u64 {
+		self.tick_index
+	}
+
+	pub fn rate(&self) -> &TickRate {
+		self.rate
+	}
+
+	pub fn config(&self) -> &SimConfig {
+		self.config
+	}
+
+	/// The tick's random numbers, which every client draws the same way, as
+	/// long as they're drawn in the same order.
+	pub fn rng(&mut self) -> &mut Rng {
+		&mut self.rng
+	}
+
+	/// Random numbers for `owner` alone, which don't depend on anything
+	/// drawn for anyone else during the tick.
+	pub fn player_rng(&self, owner: ClientId) -> Rng {
+		Rng::for_tick(self.seed, self.tick_index).split(owner as u64)
+	}
+}
+
+/// A solid box players stand on, bump their heads on, and run into the
+/// sides of. Positioned by its top left corner, in pixels.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Platform {
+	pub x: Scalar,
+	pub y: Scalar,
+	pub w: Scalar,
+	pub h: Scalar,
+}
+
+impl Platform {
+	pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+		Self {
+			x: scalar(x),
+			y: scalar(y),
+			w: scalar(w),
+			h: scalar(h)
+		}
+	}
+
+	/// What the demo plays on, unless the server sends a level of its own:
+	/// a run of ledges to jump along, and a block on the ground to get over.
+	pub fn demo_level() -> Vec<Self> {
+		vec![
+			Platform::new(300.0, 186.0, 64.0, 10.0),
+			Platform::new(400.0, 146.0, 64.0, 10.0),
+			Platform::new(520.0, 176.0, 96.0, 10.0),
+			Platform::new(680.0, 196.0, 40.0, 60.0),
+			Platform::new(780.0, 156.0, 64.0, 10.0),
+			Platform::new(900.0, 116.0, 64.0, 10.0),
+		]
+	}
+
+	fn right(&self) -> Scalar {
+		self.x + self.w
+	}
+
+	fn bottom(&self) -> Scalar {
+		self.y + self.h
+	}
+
+	/// Whether a player `width` wide at `x` would be above or below the
+	/// platform, or inside it. Merely touching one of its sides doesn't count.
+	fn spans(&self, x: Scalar, width: Scalar) -> bool {
+		x < self.right() && x + width > self.x
+	}
+
+	/// Whether a player `height` high at `y` would be level with the
+	/// platform, or inside it.
+	fn spans_height(&self, y: Scalar, height: Scalar) -> bool {
+		y < self.bottom() && y + height > self.y
+	}
+
+	#[cfg(feature = "client")]
+	fn draw(&self) {
+		let color = Color::new(0.45, 0.4, 0.38, 1.0);
+		draw_rectangle(scalar_to_f32(self.x), scalar_to_f32(self.y), scalar_to_f32(self.w), scalar_to_f32(self.h), color);
+	}
+}
+
+/// A coin lying around the level, waiting to be picked up. Positioned by
+/// its top left corner, in pixels.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coin {
+	pub x: Scalar,
+	pub y: Scalar,
+}
+
+impl Coin {
+	const SIZE: Scalar = scalar(8.0);
+	/// How far above whatever it spawns over a coin floats, so that it's
+	/// picked up by walking through it.
+	const HOVER: f32 = 14.0;
+
+	pub fn new(x: f32, y: f32) -> Self {
+		Self {
+			x: scalar(x),
+			y: scalar(y)
+		}
+	}
+
+	fn touches(&self, player: &Player, config: &SimConfig) -> bool {
+		self.x < player.x + config.player_width && self.x + Self::SIZE > player.x
+			&& self.y < player.y + config.player_height && self.y + Self::SIZE > player.y
+	}
+
+	#[cfg(feature = "client")]
+	fn draw(&self) {
+		let size = scalar_to_f32(Self::SIZE);
+		draw_rectangle(scalar_to_f32(self.x), scalar_to_f32(self.y), size, size, GOLD);
+	}
+}
+
+/// The coins lying around, and how many of them everyone picked up. Part
+/// of the simulated world, so it's rolled back, hashed and snapshotted
+/// along with the players.
+#[derive(Clone, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coins {
+	lying: Vec<Coin>,
+	/// By ClientId; players keep their score after leaving, should they
+	/// come back.
+	scores: BTreeMap<ClientId, u32>,
+}
+
+impl Coins {
+	/// Most coins lying around at once. No more spawn until one is picked up.
+	pub const MAX_LYING: usize = 5;
+	/// Seconds between two coins spawning.
+	const SPAWN_INTERVAL: u64 = 2;
+
+	pub fn lying(&self) -> &[Coin] {
+		&self.lying
+	}
+
+	pub fn score(&self, owner: ClientId) -> u32 {
+		self.scores.get(&owner).copied().unwrap_or(0)
+	}
+
+	pub fn scores(&self) -> &BTreeMap<ClientId, u32> {
+		&self.scores
+	}
+
+	/// Spawns a coin on the first tick of every `SPAWN_INTERVAL`, over the
+	/// floor or one of `platforms`, wherever the tick's random numbers say.
+	fn spawn(&mut self, context: &mut SimContext, platforms: &[Platform]) {
+		let interval = context.rate().hz as u64 * Self::SPAWN_INTERVAL;
+		if !context.tick_index().is_multiple_of(interval) || self.lying.len() >= Self::MAX_LYING {
+			return;
+		}
+		let size = scalar_to_f32(Coin::SIZE) as i32;
+		let config = context.config;
+		let rng = context.rng();
+		let (left, right, surface) = match platforms.get(rng.below(platforms.len() as u32 + 1) as usize) {
+			Some(platform) => (platform.x, platform.right(), platform.y),
+			None => (scalar(0.0), config.world_width, config.world_height),
+		};
+		let x = rng.range(scalar_to_f32(left) as i32, scalar_to_f32(right) as i32 - size);
+		let y = scalar_to_f32(surface) - Coin::HOVER - size as f32;
+		self.lying.push(Coin::new(x as f32, y));
+	}
+
+	/// Hands every coin someone touches to them. Should several players
+	/// touch the same coin on the same tick, the one with the lowest
+	/// ClientId gets it.
+	fn collect(&mut self, players: &BTreeMap<ClientId, Player>, config: &SimConfig) {
+		let scores = &mut self.scores;
+		self.lying.retain(|coin| match players.iter().find(|(_, player)| coin.touches(player, config)) {
+			Some((owner, _)) => {
+				*scores.entry(*owner).or_default() += 1;
+				false
+			},
+			None => true,
+		});
+	}
+
+	fn update_hasher(&self, hasher: &mut impl TickHasher) {
+		hasher.update(&(self.lying.len() as u64).to_le_bytes());
+		for coin in &self.lying {
+			hasher.update(&scalar_bits(coin.x).to_le_bytes());
+			hasher.update(&scalar_bits(coin.y).to_le_bytes());
+		}
+		hasher.update(&(self.scores.len() as u64).to_le_bytes());
+		for (owner, score) in &self.scores {
+			hasher.update(&owner.to_le_bytes());
+			hasher.update(&score.to_le_bytes());
+		}
+	}
+}
+
+/// Coins lying around, then each score as a ClientId and a count.
+impl NetType for Coins {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.lying.to_bytes(writer)?;
+		writer.write_varint(self.scores.len() as u64);
+		for (owner, score) in &self.scores {
+			owner.to_bytes(writer)?;
+			score.to_bytes(writer)?;
+		}
+		Ok(())
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.lying.byte_len_hint() + ByteWriter::varint_len(self.scores.len() as u64) + self.scores.len() * 6
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let lying = reader.read_vec(Self::MAX_LYING, "coins")?;
+		let len = reader.read_len(ClientId::MAX as usize + 1, "scores")?;
+		let mut scores = BTreeMap::new();
+		for _ in 0..len {
+			let owner = ClientId::from_bytes(reader)?;
+			scores.insert(owner, u32::from_bytes(reader)?);
+		}
+		Ok(Self { lying, scores })
+	}
+}
+
+#[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Player {
+	x: Scalar,
+	y: Scalar,
+	last_tick_x: Scalar,
+	last_tick_y: Scalar,
+	horizontal_velocity: Scalar,
+	vertical_velocity: Scalar,
+	grounded: bool,
+	/// How many ticks of the current jump have been held for so far.
+	jump_hold_ticks: u8,
+	/// How many more ticks a pressed jump keeps trying to go off for.
+	jump_buffer_ticks: u8,
+	/// How many more ticks the player can still jump for, after leaving the
+	/// ground without jumping.
+	coyote_ticks: u8,
+	/// How far off the drawn position is from the simulated one, after a
+	/// rollback moved the player. Only affects rendering, and decays over time.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	error_x: f32,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	error_y: f32,
+}
+
+impl Player {
+	// Anything given in ticks or per tick below is at the default TICKRATE,
+	// and gets scaled by `TickRate` for others.
+	/// How many ticks, counting the one it's pressed on, a jump keeps trying
+	/// to go off for, until the player is on the ground.
+	const JUMP_BUFFER_TICKS: u8 = 3;
+	/// How many ticks after walking off an edge the player can still jump.
+	const COYOTE_TICKS: u8 = 2;
+	/// Fraction of horizontal velocity kept every tick while on the ground.
+	const GROUND_FRICTION: f32 = 0.7;
+	/// Fraction of horizontal velocity kept every tick while airborne.
+	const AIR_DRAG: f32 = 0.95;
+	/// Upwards velocity added on every tick the jump is held for, on top of
+	/// the initial impulse, in pixels per second.
+	const JUMP_HOLD_BOOST: f32 = 30.0;
+	/// The most ticks holding a jump makes a difference for.
+	const MAX_JUMP_HOLD_TICKS: u8 = 6;
+	/// Below this speed, horizontal movement just stops.
+	const MIN_SPEED: Scalar = scalar(1.0);
+	/// Fraction of the visual error left after every tick's worth of frames.
+	#[cfg(feature = "client")]
+	const ERROR_DECAY: f32 = 0.6;
+	/// Visual errors smaller than this, in pixels, aren't worth smoothing.
+	const ERROR_EPSILON: f32 = 0.1;
+	/// Corrections which move the player further than this snap instantly,
+	/// instead of gliding across the map.
+	const ERROR_SNAP_DISTANCE: f32 = SCREEN_SIZE as f32 / 2.0;
+	/// Horizontal distance between two neighbouring spawn points.
+	const SPAWN_SPACING: f32 = 45.0;
+	/// How many spawn points fit on the first screen of the world.
+	const SPAWN_SLOTS: ClientId = 6;
+
+	/// A freshly joined player, standing on the ground. Players are spread out
+	/// by ClientId, so that they don't all spawn on top of each other. Once
+	/// the spawn points run out, `rng` picks somewhere on the first screen;
+	/// it's meant to be the `SimContext::player_rng` of the tick they join on.
+	///
+	/// Since a player at rest on the ground stays put, it doesn't matter on
+	/// which tick exactly a client learns about the join.
+	pub fn spawn(owner: ClientId, rng: &mut Rng, config: &SimConfig) -> Self {
+		let x = match owner < Self::SPAWN_SLOTS {
+			true => scalar(Self::SPAWN_SPACING * owner as f32).min(config.max_x()),
+			false => {
+				let first_screen = scalar_to_f32(config.world_width).min(SCREEN_SIZE as f32);
+				scalar(rng.range(0, (first_screen - scalar_to_f32(config.player_width)) as i32) as f32)
+			},
+		};
+		let y = config.floor_y();
+		Self {
+			x,
+			y,
+			last_tick_x: x,
+			last_tick_y: y,
+			grounded: true,
+			..Default::default()
+		}
+	}
+
+	/// Returns where the player got drawn.
+	#[cfg(feature = "client")]
+	pub fn draw(&mut self, smoothing: f32, size: (f32, f32), color: Color) -> (f32, f32) {
+		self.decay_error();
+		let (x, y) = self.interpolated_position(smoothing);
+		Self::draw_at((x, y), size, color);
+		(x, y)
+	}
+
+	/// Lets the visual error decay by a frame's worth.
+	#[cfg(feature = "client")]
+	fn decay_error(&mut self) {
+		let decay = Self::ERROR_DECAY.powf(get_frame_time() / TICK_DELTA);
+		self.error_x *= decay;
+		self.error_y *= decay;
+		if self.error() < Self::ERROR_EPSILON {
+			self.error_x = 0.0;
+			self.error_y = 0.0;
+		}
+	}
+
+	/// Where `draw` puts the player, `smoothing` of the way from the last
+	/// tick's position to the current one, visual error included.
+	pub fn interpolated_position(&self, smoothing: f32) -> (f32, f32) {
+		// Past 1.0, we'd be extrapolating beyond the simulated position.
+		let smoothing = smoothing.clamp(0.0, 1.0);
+		let smooth_x = (1.0 - smoothing) * scalar_to_f32(self.last_tick_x) + scalar_to_f32(self.x) * smoothing;
+		let smooth_y = (1.0 - smoothing) * scalar_to_f32(self.last_tick_y) + scalar_to_f32(self.y) * smoothing;
+		(smooth_x + self.error_x, smooth_y + self.error_y)
+	}
+
+	/// The simulated position, as of the latest tick.
+	pub fn position(&self) -> (Scalar, Scalar) {
+		(self.x, self.y)
+	}
+
+	/// In pixels per second, as of the latest tick.
+	pub fn velocity(&self) -> (Scalar, Scalar) {
+		(self.horizontal_velocity, self.vertical_velocity)
+	}
+
+	pub fn is_grounded(&self) -> bool {
+		self.grounded
+	}
+
+	/// Where the player is currently drawn, ignoring interpolation.
+	pub fn rendered_position(&self) -> (f32, f32) {
+		(scalar_to_f32(self.x) + self.error_x, scalar_to_f32(self.y) + self.error_y)
+	}
+
+	/// Magnitude of the visual error offset.
+	pub fn error(&self) -> f32 {
+		(self.error_x * self.error_x + self.error_y * self.error_y).sqrt()
+	}
+
+	/// The visual error, along either axis.
+	fn error_offset(&self) -> (f32, f32) {
+		(self.error_x, self.error_y)
+	}
+
+	/// Keeps the player drawn at `rendered`, where they were before a rollback
+	/// moved them to `position`, so that the visual error can decay from
+	/// there. That's their simulated position, unless they're drawn as of an
+	/// earlier tick; see `Game::render_tick`.
+	pub fn smooth_correction(&mut self, rendered: (f32, f32), position: (f32, f32)) {
+		self.error_x = rendered.0 - position.0;
+		self.error_y = rendered.1 - position.1;
+		if self.error() < Self::ERROR_EPSILON || self.error() > Self::ERROR_SNAP_DISTANCE {
+			self.error_x = 0.0;
+			self.error_y = 0.0;
+		}
+	}
+
+	/// Somewhere between `from`'s position and its own, `smoothing` of the
+	/// way.
+	pub fn position_from(&self, from: &Player, smoothing: f32) -> (f32, f32) {
+		let smoothing = smoothing.clamp(0.0, 1.0);
+		let smooth_x = (1.0 - smoothing) * scalar_to_f32(from.x) + scalar_to_f32(self.x) * smoothing;
+		let smooth_y = (1.0 - smoothing) * scalar_to_f32(from.y) + scalar_to_f32(self.y) * smoothing;
+		(smooth_x, smooth_y)
+	}
+
+	/// Outlines a player drawn at `(x, y)`, so that whoever's playing as
+	/// them can tell which one's theirs.
+	#[cfg(feature = "client")]
+	pub fn draw_outline((x, y): (f32, f32), (w, h): (f32, f32)) {
+		const MARGIN: f32 = 2.0;
+		draw_rectangle_lines(x - MARGIN, y - MARGIN, w + 2.0 * MARGIN, h + 2.0 * MARGIN, 2.0, Color::new(1.0, 1.0, 1.0, 0.7));
+	}
+
+	#[cfg(feature = "client")]
+	fn draw_at((x, y): (f32, f32), (w, h): (f32, f32), color: Color) {
+		draw_rectangle(x, y, w, h, color);
+	}
+
+	/// Feeds the parts of the player `Game::state_hash` covers into `hasher`.
+	fn update_hasher(&self, hasher: &mut impl TickHasher) {
+		for value in [self.x, self.y, self.horizontal_velocity, self.vertical_velocity] {
+			hasher.update(&scalar_bits(value).to_le_bytes());
+		}
+		hasher.update(&[self.grounded as u8]);
+	}
+
+	pub fn snapshot_position(&mut self) {
+		self.last_tick_x = self.x;
+		self.last_tick_y = self.y;
+	}
+
+	/// Moves the player along by a tick, colliding with the edges of the
+	/// world and with `platforms`. Horizontal movement gets resolved before
+	/// vertical movement, and platforms in the order they're listed in.
+	/// Whether the player is grounded is worked out from scratch every tick.
+	/// Jumps, walls and landings make for `events`, as `owner`'s.
+	pub fn update_physics(&mut self, owner: ClientId, rate: &TickRate, config: &SimConfig, platforms: &[Platform], events: &mut Vec<SimEvent>) {
+		// Whether the player is grounded still holds from the end of the
+		// last tick here, so both timers count whole ticks.
+		let was_grounded = self.grounded;
+		if self.jump_buffer_ticks > 0 {
+			match self.grounded || self.coyote_ticks > 0 {
+				true => {
+					self.jump(config);
+					events.push(SimEvent::Jumped { id: owner });
+				},
+				false => self.jump_buffer_ticks -= 1,
+			}
+		}
+		if self.grounded {
+			self.coyote_ticks = rate.coyote_ticks;
+		} else {
+			self.coyote_ticks = self.coyote_ticks.saturating_sub(1);
+		}
+
+		let previous_x = self.x;
+		self.x += self.horizontal_velocity * rate.delta;
+		self.horizontal_velocity *= match self.grounded {
+			true => rate.ground_friction,
+			false => rate.air_drag,
+		};
+		if self.horizontal_velocity.abs() < Self::MIN_SPEED {
+			self.horizontal_velocity = scalar(0.0);
+		}
+
+		// Running into a wall stops the player dead. Only getting there
+		// makes for an event, rather than every tick spent pushing on it.
+		if self.x <= scalar(0.0) || self.x >= config.max_x() {
+			let (side, arrived) = match self.x <= scalar(0.0) {
+				true => (WallSide::Left, previous_x > scalar(0.0)),
+				false => (WallSide::Right, previous_x < config.max_x()),
+			};
+			self.x = self.x.clamp(scalar(0.0), config.max_x());
+			self.horizontal_velocity = scalar(0.0);
+			if arrived {
+				events.push(SimEvent::HitWall { id: owner, side });
+			}
+		}
+
+		// So does running into the side of a platform. Whichever side the
+		// player came from is the one they end up at.
+		for platform in platforms {
+			if !platform.spans(self.x, config.player_width) || !platform.spans_height(self.y, config.player_height) {
+				continue;
+			}
+			let from_left = match (previous_x + config.player_width <= platform.x, previous_x >= platform.right()) {
+				(true, _) => true,
+				(_, true) => false,
+				// Already inside, say after being pushed by someone else.
+				_ => self.x + config.player_width / scalar(2.0) < platform.x + platform.w / scalar(2.0),
+			};
+			// Though never out of the world, if the platform sticks out
+			// past a wall of a narrower one than it was made for.
+			let x = match from_left {
+				true => platform.x - config.player_width,
+				false => platform.right(),
+			};
+			self.x = x.clamp(scalar(0.0), config.max_x());
+			self.horizontal_velocity = scalar(0.0);
+		}
+
+		// Semi-implicit Euler: the velocity first, then the position with
+		// the new velocity.
+		let previous_y = self.y;
+		self.vertical_velocity += config.gravity * rate.delta;
+		self.vertical_velocity = self.vertical_velocity.min(config.terminal_velocity);
+		self.y += self.vertical_velocity * rate.delta;
+		let impact_velocity = self.vertical_velocity;
+
+		// Whether the player ends up standing on someone else is only known
+		// once collisions are resolved, which may set this again.
+		self.grounded = false;
+		if self.y >= config.floor_y() {
+			self.land(config.floor_y());
+		}
+
+		for platform in platforms {
+			if !platform.spans(self.x, config.player_width) {
+				continue;
+			}
+			if previous_y + config.player_height <= platform.y && self.y + config.player_height >= platform.y {
+				// Landed on top, or still standing there.
+				self.land(platform.y - config.player_height);
+			} else if previous_y >= platform.bottom() && self.y < platform.bottom() {
+				// Bumped into the underside, which ends the jump.
+				self.y = platform.bottom();
+				self.vertical_velocity = self.vertical_velocity.max(scalar(0.0));
+			}
+		}
+
+		if self.grounded && !was_grounded {
+			events.push(SimEvent::Landed { id: owner, impact_velocity: scalar_to_f32(impact_velocity) });
+		}
+	}
+
+	fn land(&mut self, y: Scalar) {
+		self.y = y;
+		self.vertical_velocity = scalar(0.0);
+		self.grounded = true;
+		self.jump_hold_ticks = 0;
+	}
+
+	/// Separates two overlapping players along the axis they overlap the
+	/// least on. A player landing on top of another one can stand on them.
+	pub fn resolve_collision(&mut self, other: &mut Player, config: &SimConfig) {
+		let (width, height) = (config.player_width, config.player_height);
+		let overlap_x = (self.x + width).min(other.x + width) - self.x.max(other.x);
+		let overlap_y = (self.y + height).min(other.y + height) - self.y.max(other.y);
+		if overlap_x <= scalar(0.0) || overlap_y <= scalar(0.0) {
+			return;
+		}
+
+		if overlap_x < overlap_y {
+			let (left, right) = match self.x <= other.x {
+				true => (self, other),
+				false => (other, self),
+			};
+
+			// Both get pushed away from their common center, unless that
+			// would put one of them into a wall.
+			let center = (left.x + right.x + width) / scalar(2.0);
+			let center = center.clamp(width, config.max_x());
+			left.x = center - width;
+			right.x = center;
+
+			left.horizontal_velocity = left.horizontal_velocity.min(scalar(0.0));
+			right.horizontal_velocity = right.horizontal_velocity.max(scalar(0.0));
+		} else {
+			let (upper, lower) = match self.y <= other.y {
+				true => (self, other),
+				false => (other, self),
+			};
+
+			upper.y = lower.y - height;
+			upper.vertical_velocity = upper.vertical_velocity.min(scalar(0.0));
+			upper.grounded = true;
+			upper.jump_hold_ticks = 0;
+			lower.vertical_velocity = lower.vertical_velocity.max(scalar(0.0));
+		}
+	}
+
+	/// Acts on an intent from a frame with the given `offset`; see
+	/// `CommandFrame::offset`. Movement only accelerates for the part of
+	/// the tick that's left after the offset, so a late press moves the
+	/// player proportionally less on the tick it came in on.
+	pub fn execute_intent(&mut self, intent: &PlayerIntent, offset: u8, context: &mut SimContext) {
+		let rate = context.rate();
+		let config = context.config();
+		// Exact in an f32, so the same everywhere.
+		let remaining = scalar((256 - offset as u16) as f32 / 256.0);
+		let acceleration = config.acceleration * rate.delta * remaining;
+		match intent {
+			PlayerIntent::MoveLeft => {
+				self.horizontal_velocity -= acceleration;
+				self.horizontal_velocity = self.horizontal_velocity.max(-config.move_speed);
+			},
+			PlayerIntent::MoveRight => {
+				self.horizontal_velocity += acceleration;
+				self.horizontal_velocity = self.horizontal_velocity.min(config.move_speed);
+			},
+			PlayerIntent::MoveHorizontal(magnitude) => {
+				let magnitude = (*magnitude).max(-PlayerIntent::MAX_MAGNITUDE);
+				let fraction = scalar(magnitude as f32) / scalar(PlayerIntent::MAX_MAGNITUDE as f32);
+				let top_speed = config.move_speed * fraction;
+				self.horizontal_velocity += config.acceleration * fraction * rate.delta * remaining;
+				// Capped the same way as MoveLeft/MoveRight, in the direction of movement.
+				if magnitude > 0 {
+					self.horizontal_velocity = self.horizontal_velocity.min(top_speed);
+				} else if magnitude < 0 {
+					self.horizontal_velocity = self.horizontal_velocity.max(top_speed);
+				}
+			},
+			// Goes off in update_physics, right away if the player can jump.
+			PlayerIntent::JumpStart => self.jump_buffer_ticks = rate.jump_buffer_ticks,
+			PlayerIntent::JumpHold => {
+				// Only ever makes a jump that's still rising go higher.
+				let rising = self.vertical_velocity < scalar(0.0);
+				if !self.grounded && rising && self.jump_hold_ticks < rate.max_jump_hold_ticks {
+					self.vertical_velocity -= rate.jump_hold_boost;
+					self.jump_hold_ticks += 1;
+				}
+			},
+		}
+	}
+
+	fn jump(&mut self, config: &SimConfig) {
+		self.vertical_velocity = config.jump_velocity;
+		self.grounded = false;
+		self.jump_hold_ticks = 0;
+		self.jump_buffer_ticks = 0;
+		self.coyote_ticks = 0;
+	}
+}
+
+// Only the simulated state goes over the wire. The interpolation origin is
+// reset to the received position, so the first frame doesn't lerp in from
+// (0, 0).
+impl NetType for Player {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.write(writer, None)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		// Four scalars and four bytes.
+		4 * self.x.byte_len_hint() + 4
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		Self::read(reader, None)
+	}
+}
+
+impl Player {
+	/// Writes the player as `to_bytes` does, only with the position
+	/// quantized within `extents`, if given; see `Snapshot::quantized`.
+	pub(crate) fn write(&self, writer: &mut ByteWriter, extents: Option<(f32, f32)>) -> Result<(), EncodeError> {
+		match extents {
+			Some((width, height)) => {
+				quantize_pos(scalar_to_f32(self.x), width).to_bytes(writer)?;
+				quantize_pos(scalar_to_f32(self.y), height).to_bytes(writer)?;
+			},
+			None => {
+				self.x.to_bytes(writer)?;
+				self.y.to_bytes(writer)?;
+			},
+		}
+		self.horizontal_velocity.to_bytes(writer)?;
+		self.vertical_velocity.to_bytes(writer)?;
+		self.grounded.to_bytes(writer)?;
+		self.jump_hold_ticks.to_bytes(writer)?;
+		self.jump_buffer_ticks.to_bytes(writer)?;
+		self.coyote_ticks.to_bytes(writer)
+	}
+
+	/// Reads a player written by `write`, with the same `extents`.
+	pub(crate) fn read(reader: &mut ByteReader, extents: Option<(f32, f32)>) -> Result<Self, DecodeError> {
+		let (x, y) = match extents {
+			Some((width, height)) => (
+				scalar(dequantize_pos(u16::from_bytes(reader)?, width)),
+				scalar(dequantize_pos(u16::from_bytes(reader)?, height))
+			),
+			None => (Scalar::from_bytes(reader)?, Scalar::from_bytes(reader)?),
+		};
+		let horizontal_velocity = Scalar::from_bytes(reader)?;
+		let vertical_velocity = Scalar::from_bytes(reader)?;
+		let grounded = bool::from_bytes(reader)?;
+		let jump_hold_ticks = u8::from_bytes(reader)?;
+		let jump_buffer_ticks = u8::from_bytes(reader)?;
+		let coyote_ticks = u8::from_bytes(reader)?;
+
+		Ok(Self {
+			x,
+			y,
+			last_tick_x: x,
+			last_tick_y: y,
+			horizontal_velocity,
+			vertical_velocity,
+			grounded,
+			jump_hold_ticks,
+			jump_buffer_ticks,
+			coyote_ticks,
+			..Default::default()
+		})
+	}
+}
+
+/// Where a player was right after a tick got simulated, and how it was
+/// moving; see `Game::player_state_at`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlayerStateSnapshot {
+	pub x: Scalar,
+	pub y: Scalar,
+	pub horizontal_velocity: Scalar,
+	pub vertical_velocity: Scalar,
+	pub grounded: bool,
+}
+
+impl From<&Player> for PlayerStateSnapshot {
+	fn from(player: &Player) -> Self {
+		Self {
+			x: player.x,
+			y: player.y,
+			horizontal_velocity: player.horizontal_velocity,
+			vertical_velocity: player.vertical_velocity,
+			grounded: player.grounded
+		}
+	}
+}
+
+/// Every player's state right after a tick, by ClientId.
+pub type PlayerStates = BTreeMap<ClientId, PlayerStateSnapshot>;
+
+/// Which edge of the world a player ran into.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, NetType)]
+pub enum WallSide {
+	Left,
+	Right,
+}
+
+/// Something that happened to a player on a tick, for the presentation
+/// layer to play a sound or an effect for; see `Game::take_sim_events`.
+/// Worked out from the simulation, so that every client comes up with the
+/// same ones for the same tick, but never fed back into it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SimEvent {
+	/// Came down onto the floor or a platform, falling at
+	/// `impact_velocity`, in pixels per second. Not onto someone else.
+	Landed { id: ClientId, impact_velocity: f32 },
+	/// Ran into an edge of the world.
+	HitWall { id: ClientId, side: WallSide },
+	/// Jumped, off the ground or in coyote time.
+	Jumped { id: ClientId },
+}
+
+impl SimEvent {
+	pub const TAG_LANDED: u8 = 0;
+	pub const TAG_HIT_WALL: u8 = 1;
+	pub const TAG_JUMPED: u8 = 2;
+
+	/// Whoever it happened to.
+	pub fn id(&self) -> ClientId {
+		match *self {
+			SimEvent::Landed { id, .. } | SimEvent::HitWall { id, .. } | SimEvent::Jumped { id } => id,
+		}
+	}
+
+	pub fn tag(&self) -> u8 {
+		match self {
+			SimEvent::Landed { .. } => Self::TAG_LANDED,
+			SimEvent::HitWall { .. } => Self::TAG_HIT_WALL,
+			SimEvent::Jumped { .. } => Self::TAG_JUMPED,
+		}
+	}
+}
+
+// The tag, then whoever it happened to, then whatever else there is.
+impl NetType for SimEvent {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_u8(self.tag());
+		self.id().to_bytes(writer)?;
+		match self {
+			SimEvent::Landed { impact_velocity, .. } => impact_velocity.to_bytes(writer),
+			SimEvent::HitWall { side, .. } => side.to_bytes(writer),
+			SimEvent::Jumped { .. } => Ok(()),
+		}
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		match self {
+			SimEvent::Landed { .. } => 7,
+			SimEvent::HitWall { .. } => 4,
+			SimEvent::Jumped { .. } => 3,
+		}
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let tag = u8::from_bytes(reader)?;
+		let id = ClientId::from_bytes(reader)?;
+		match tag {
+			Self::TAG_LANDED => Ok(SimEvent::Landed { id, impact_velocity: f32::from_bytes(reader)? }),
+			Self::TAG_HIT_WALL => Ok(SimEvent::HitWall { id, side: WallSide::from_bytes(reader)? }),
+			Self::TAG_JUMPED => Ok(SimEvent::Jumped { id }),
+			tag => Err(DecodeError::InvalidTag { ty: "SimEvent", tag })
+		}
+	}
+}
+
+/// A `SimEvent`, along with the tick it happened on.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct TickEvent {
+	pub tick: u64,
+	pub event: SimEvent,
+}
+
+/// The world as of a confirmed tick, boiled down to what a host needs for a
+/// scoreboard or a minimap; see `Game::state_summary`. Positions are f32
+/// whatever `Scalar` is, so that the host parses a single format.
+#[derive(Clone, PartialEq, Debug, NetType)]
+pub struct StateSummary {
+	pub tick: u64,
+	/// In ClientId order.
+	pub players: Vec<PlayerSummary>,
+}
+
+/// One player in a `StateSummary`.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct PlayerSummary {
+	pub owner: ClientId,
+	pub x: f32,
+	pub y: f32,
+	pub score: u32,
+}
+
+/// Book-keeping stored by the client for every tick in its history.
+#[derive(Clone)]
+struct TickRecord<I: Intent> {
+	tick: Tick<I>,
+	/// The world, as it was right before the tick got simulated.
+	players: BTreeMap<ClientId, Player>,
+	coins: Coins,
+	/// Whether the tick is the server's version, rather than our own prediction.
+	authoritative: bool,
+	/// Set when the server confirmed a different hash than ours; a full
+	/// correction is expected to follow.
+	suspect: bool,
+	/// The tick as we sent it to the server, once predicting it again
+	/// changed it; an ack for that one corrects us back to it.
+	sent: Option<Tick<I>>,
+	/// The state hash of the world right after the tick got simulated.
+	state_hash: Hash,
+	/// The players right after the tick got simulated, for looking them
+	/// up without simulating anything again.
+	after: PlayerStates,
+	/// When we sent the tick to the server, as per `get_time`. Taken once
+	/// the server answers, so every tick makes for one RTT sample at most.
+	sent_at: Option<f64>,
+}
+
+/// How many confirmed ticks are kept around, in case a late correction shows up.
+pub(crate) const TICK_RETENTION: u64 = TICKRATE as u64;
+
+/// The ticks processed by the client, addressed by their absolute index.
+///
+/// Confirmed ticks which fall out of the retention window get pruned,
+/// so the history doesn't grow for the whole length of a session.
+struct TickHistory<I: Intent> {
+	records: VecDeque<TickRecord<I>>,
+	/// How many ticks older than the accepted head are still kept.
+	retention: u64,
+	/// How many ticks are kept at most, pruned or not; see
+	/// `MemoryBudget::history_ticks`.
+	capacity: usize,
+	/// Index of the first tick to be pushed, while the history is empty.
+	start: u64,
+}
+
+impl<I: Intent> TickHistory<I> {
+	fn new(retention: u64, capacity: usize) -> Self {
+		Self {
+			records: VecDeque::new(),
+			retention,
+			capacity,
+			start: 0
+		}
+	}
+
+	/// Forgets every tick, continuing the history from `next_index` instead.
+	fn reset(&mut self, next_index: u64) {
+		self.records.clear();
+		self.start = next_index;
+	}
+
+	/// Maps an absolute tick index to its slot in the buffer.
+	fn slot(&self, tick_index: u64) -> Option<usize> {
+		let first = self.records.front()?.tick.index();
+		let slot = tick_index.checked_sub(first)? as usize;
+		(slot < self.records.len()).then_some(slot)
+	}
+
+	fn get(&self, tick_index: u64) -> Option<&TickRecord<I>> {
+		self.records.get(self.slot(tick_index)?)
+	}
+
+	fn get_mut(&mut self, tick_index: u64) -> Option<&mut TickRecord<I>> {
+		let slot = self.slot(tick_index)?;
+		self.records.get_mut(slot)
+	}
+
+	fn first(&self) -> Option<&TickRecord<I>> {
+		self.records.front()
+	}
+
+	fn last(&self) -> Option<&TickRecord<I>> {
+		self.records.back()
+	}
+
+	fn last_mut(&mut self) -> Option<&mut TickRecord<I>> {
+		self.records.back_mut()
+	}
+
+	/// The index the next predicted tick is going to have.
+	fn next_index(&self) -> u64 {
+		self.last().map(|x| x.tick.index() + 1).unwrap_or(self.start)
+	}
+
+	/// Whether `tick_index` refers to a tick which we've already thrown away.
+	fn is_pruned(&self, tick_index: u64) -> bool {
+		match self.records.front() {
+			Some(first) => tick_index < first.tick.index(),
+			None => tick_index < self.start,
+		}
+	}
+
+	/// Every tick older than `tick_index`, oldest first.
+	fn ticks_before(&self, tick_index: u64) -> Vec<&Tick<I>> {
+		self.records
+			.iter()
+			.map(|x| &x.tick)
+			.take_while(|x| x.index() < tick_index)
+			.collect()
+	}
+
+	/// Adds a tick at the end, dropping the oldest one first if that would
+	/// take the history over its capacity, whether it's settled or not.
+	fn push(&mut self, record: TickRecord<I>) {
+		if self.records.len() >= self.capacity {
+			if let Some(dropped) = self.records.pop_front() {
+				crate::log!(LogLevel::Warn, "history_full capacity={} dropped_tick={}", self.capacity, dropped.tick.index());
+			}
+		}
+		self.records.push_back(record);
+	}
+
+	fn len(&self) -> usize {
+		self.records.len()
+	}
+
+	/// Drops ticks which are older than the retention window allows.
+	fn prune(&mut self, accepted_head: u64) {
+		let oldest_kept = accepted_head.saturating_sub(self.retention);
+		while self.records.front().is_some_and(|x| x.tick.index() < oldest_kept) {
+			self.records.pop_front();
+		}
+	}
+
+	#[cfg(feature = "client")]
+	fn iter(&self) -> impl Iterator<Item = &TickRecord<I>> {
+		self.records.iter()
+	}
+}
+
+/// Turns frame times into whole ticks to simulate, and the fraction of a
+/// tick left over, which is what rendering interpolates by.
+#[derive(Default)]
+pub struct TickAccumulator {
+	elapsed: f32,
+}
+
+impl TickAccumulator {
+	/// Adds a frame's worth of time, returning how many ticks `tick_delta`
+	/// seconds apart are now due. Whatever's left afterwards is always less
+	/// than a single tick.
+	pub fn advance(&mut self, frame_time: f32, tick_delta: f32) -> u32 {
+		self.elapsed += frame_time;
+		let ticks = (self.elapsed / tick_delta).floor();
+		self.elapsed = (self.elapsed - ticks * tick_delta).clamp(0.0, tick_delta);
+		ticks as u32
+	}
+
+	/// Forgets the time accumulated so far, for starting over once ticks
+	/// have stopped being run for a while.
+	pub fn reset(&mut self) {
+		self.elapsed = 0.0;
+	}
+
+	/// How far into the next tick we are, between 0 and 1.
+	pub fn smoothing(&self, tick_delta: f32) -> f32 {
+		(self.elapsed / tick_delta).clamp(0.0, 1.0)
+	}
+}
+
+/// One answer to a time request.
+#[derive(Clone, Copy, Debug)]
+struct ClockSample {
+	/// How long the request took to get answered, in seconds.
+	rtt: f64,
+	/// The server's tick, fractional, as of when the answer arrived. The
+	/// answer took half the round trip to get here, as far as we know.
+	server_tick: f64,
+	/// When the answer arrived, as per `get_time`.
+	received: f64,
+}
+
+/// Finds out which tick the server is on while connecting, so that our
+/// predictions start out where the server expects them, rather than at
+/// whatever tick we happen to be on ourselves.
+#[derive(Clone, Debug)]
+struct ClockSync {
+	/// Ticks to stay ahead of the server by; see `DEFAULT_INTERP_MARGIN`.
+	margin: u8,
+	/// When the first request went out.
+	started: Option<f64>,
+	last_request: Option<f64>,
+	samples: Vec<ClockSample>,
+}
+
+impl ClockSync {
+	fn new(margin: u8) -> Self {
+		Self {
+			margin,
+			started: None,
+			last_request: None,
+			samples: Vec::new()
+		}
+	}
+
+	/// Whether another request should go out at `now`. Only as many go out
+	/// as there are samples still missing, give or take one getting lost.
+	fn request_due(&self, now: f64) -> bool {
+		let missing = CLOCK_SYNC_SAMPLES - self.samples.len().min(CLOCK_SYNC_SAMPLES);
+		missing > 0 && self.last_request.is_none_or(|x| now - x >= CLOCK_SYNC_INTERVAL)
+	}
+
+	/// Takes in an answer, unless it's to a request from before this sync
+	/// started, which says nothing about the round trip by now; it could
+	/// have been held up waiting to be sent again, see `Game::resend`.
+	/// Returns whether it was taken.
+	fn take(&mut self, response: &TimeResponse, now: f64, tick_delta: f64) -> bool {
+		if self.started.is_none_or(|x| response.client_time < x) {
+			return false;
+		}
+		let rtt = (now - response.client_time).max(0.0);
+		let progress = response.progress.clamp(0.0, 1.0) as f64;
+		self.samples.push(ClockSample {
+			rtt,
+			server_tick: response.tick as f64 + progress + rtt / 2.0 / tick_delta,
+			received: now
+		});
+		true
+	}
+
+	fn is_timed_out(&self, now: f64) -> bool {
+		self.started.is_some_and(|x| now - x > CLOCK_SYNC_TIMEOUT)
+	}
+
+	/// The tick the server is on at `now`, averaged over every sample,
+	/// unless there aren't any.
+	fn server_tick(&self, now: f64, tick_delta: f64) -> Option<f64> {
+		if self.samples.is_empty() {
+			return None;
+		}
+		let sum: f64 = self.samples
+			.iter()
+			.map(|x| x.server_tick + (now - x.received) / tick_delta)
+			.sum();
+		Some(sum / self.samples.len() as f64)
+	}
+
+	/// The tick our first prediction should have: where the server is at
+	/// `now`, plus the margin, rounded up.
+	fn start_tick(&self, now: f64, tick_delta: f64) -> Option<u64> {
+		let server_tick = self.server_tick(now, tick_delta)?;
+		Some((server_tick + self.margin as f64).ceil() as u64)
+	}
+}
+
+/// Steers how far ahead of the server we are, by running ticks a little
+/// faster or slower than they're meant to. Only how often they're run
+/// changes; each is still simulated over the same `TICK_DELTA`.
+#[derive(Clone, Copy, Debug)]
+struct TickPacing {
+	/// Ticks ahead, smoothed over frames. `None` until there's a target.
+	smoothed_ahead: Option<f64>,
+	speed: f32,
+}
+
+impl Default for TickPacing {
+	fn default() -> Self {
+		Self {
+			smoothed_ahead: None,
+			speed: 1.0
+		}
+	}
+}
+
+impl TickPacing {
+	/// Takes in how many ticks ahead of the server's latest answer we are;
+	/// behind it, that's negative.
+	fn update(&mut self, ticks_ahead: i64, target: u64) {
+		let ahead = match self.smoothed_ahead {
+			Some(x) => x + (ticks_ahead as f64 - x) * TICK_PACING_SMOOTHING,
+			None => ticks_ahead as f64,
+		};
+		self.smoothed_ahead = Some(ahead);
+		let adjustment = ((ahead - target as f64) * TICK_SPEED_GAIN) as f32;
+		self.speed = 1.0 - adjustment.clamp(-MAX_TICK_SPEED_ADJUSTMENT, MAX_TICK_SPEED_ADJUSTMENT);
+	}
+}
+
+/// Estimates the round trip time to the server from how long our ticks
+/// take to get answered, the way TCP does (RFC 6298): a moving average of
+/// the samples, and of how far they stray from it.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RttEstimator {
+	/// The smoothed round trip time, in seconds. `None` before the first sample.
+	smoothed: Option<f64>,
+	/// The smoothed deviation of samples from `smoothed`, in seconds.
+	jitter: f64,
+}
+
+impl RttEstimator {
+	/// Weight of a new sample in the smoothed round trip time. Small, so
+	/// that a single late answer doesn't throw the estimate off.
+	pub const RTT_GAIN: f64 = 1.0 / 8.0;
+	/// Weight of a new sample's deviation in the jitter. A bit larger, so
+	/// that the jitter picks up on a network getting worse fairly quickly.
+	pub const JITTER_GAIN: f64 = 1.0 / 4.0;
+
+	/// Takes in a round trip time, in seconds. The first one is taken as
+	/// is, with a jitter of half of it.
+	pub fn update(&mut self, sample: f64) {
+		match self.smoothed {
+			Some(smoothed) => {
+				self.jitter += Self::JITTER_GAIN * ((smoothed - sample).abs() - self.jitter);
+				self.smoothed = Some(smoothed + Self::RTT_GAIN * (sample - smoothed));
+			},
+			None => {
+				self.smoothed = Some(sample);
+				self.jitter = sample / 2.0;
+			},
+		}
+	}
+
+	/// In seconds, unless nothing got answered yet.
+	pub fn smoothed(&self) -> Option<f64> {
+		self.smoothed
+	}
+
+	/// In seconds.
+	pub fn jitter(&self) -> f64 {
+		self.jitter
+	}
+}
+
+/// How remote players are put on screen. The local player is always
+/// drawn from its predicted state.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RemotePresentation {
+	/// Drawn from the predicted world, just like the local player.
+	Predicted,
+	/// Drawn from confirmed ticks only, interpolating from the tick before the
+	/// accepted head towards the accepted head itself.
+	Interpolated,
+}
+
+/// Keeps track of when the accepted head last moved, so the interpolation
+/// towards it stops once it's reached instead of repeating every tick.
+#[cfg(feature = "client")]
+#[derive(Default)]
+struct Interpolation {
+	head: u64,
+	since: f64,
+}
+
+/// A rollback's replay, carried on with over the next frames once it ran
+/// past the tick budget; see `Game::set_tick_budget`. The world it's
+/// rebuilding is kept in here, so that the one drawn meanwhile stays the
+/// one the last replay to get all the way through left behind.
+struct PendingReplay {
+	/// The tick the rollback went back to.
+	start: u64,
+	/// The next tick to replay.
+	next_index: u64,
+	/// The world as of right before `next_index`.
+	players: BTreeMap<ClientId, Player>,
+	coins: Coins,
+	/// Where everyone was drawn when the rollback started.
+	rendered: Vec<(ClientId, (f32, f32))>,
+	/// Set once the replay didn't get through within a single frame.
+	split: bool,
+}
+
+/// A client joining or leaving, as reported by the host.
+pub(crate) enum MembershipEvent {
+	Joined(ClientId),
+	Left(ClientId),
+}
+
+/// The ticks during which a client takes part in the game; from `joined`,
+/// up to but excluding `left`.
+#[derive(Clone)]
+struct Membership {
+	joined: u64,
+	left: Option<u64>,
+}
+
+impl Membership {
+	fn active_at(&self, tick_index: u64) -> bool {
+		tick_index >= self.joined && self.left.is_none_or(|x| tick_index < x)
+	}
+
+	/// The membership table, the way replays record it.
+	fn entries(members: &BTreeMap<ClientId, Membership>) -> Vec<MemberEntry> {
+		members
+			.iter()
+			.map(|(client_id, x)| MemberEntry {
+				client_id: *client_id,
+				joined: x.joined,
+				left: x.left
+			})
+			.collect()
+	}
+
+	fn from_entries(entries: &[MemberEntry]) -> BTreeMap<ClientId, Membership> {
+		entries
+			.iter()
+			.map(|x| (x.client_id, Membership {
+				joined: x.joined,
+				left: x.left
+			}))
+			.collect()
+	}
+}
+
+/// Smooths out authoritative ticks arriving in bursts. Ticks are played back
+/// one per local tick, once enough of them have been buffered; the depth
+/// that's considered enough adapts to how irregular the arrivals are.
+struct JitterBuffer {
+	queue: VecDeque<Tick<PlayerIntent>>,
+	/// How many ticks to buffer before playback starts.
+	target_depth: usize,
+	playing: bool,
+	/// For how many ticks in a row the buffer has been deeper than needed.
+	deep_ticks: u32,
+	/// Recent depths, oldest first, for the debug overlay.
+	history: VecDeque<usize>,
+	/// How many ticks the buffer holds at most; see
+	/// `MemoryBudget::buffered_ticks`.
+	capacity: usize,
+}
+
+impl JitterBuffer {
+	const MIN_DEPTH: usize = 1;
+	const MAX_DEPTH: usize = 8;
+	/// Ticks the buffer has to stay deep for before the target shrinks.
+	const SHRINK_AFTER: u32 = 2 * TICKRATE as u32;
+	const HISTORY_LEN: usize = 64;
+
+	fn new(target_depth: usize, capacity: usize) -> Self {
+		Self {
+			queue: VecDeque::new(),
+			target_depth,
+			playing: false,
+			deep_ticks: 0,
+			history: VecDeque::new(),
+			capacity
+		}
+	}
+
+	/// Buffers a tick behind the others, unless the buffer is full already.
+	/// Returns whether it was.
+	fn push(&mut self, tick: Tick<PlayerIntent>) -> bool {
+		if self.queue.len() >= self.capacity {
+			return false;
+		}
+		self.queue.push_back(tick);
+		true
+	}
+
+	/// Takes out every buffered tick at once, to be played back without
+	/// waiting. Buffering starts over after that.
+	fn drain(&mut self) -> Vec<Tick<PlayerIntent>> {
+		self.playing = false;
+		self.queue.drain(..).collect()
+	}
+
+	/// Hands out the next tick to be played back, if there's one due.
+	/// Meant to be called exactly once per local tick.
+	fn pop(&mut self) -> Option<Tick<PlayerIntent>> {
+		self.playing |= self.queue.len() >= self.target_depth;
+		let tick = match self.playing {
+			true => self.queue.pop_front(),
+			false => None,
+		};
+
+		if self.playing {
+			match tick {
+				// We ran dry; buffer more before playing back again.
+				None => {
+					self.target_depth = (self.target_depth + 1).min(Self::MAX_DEPTH);
+					self.playing = false;
+					self.deep_ticks = 0;
+				},
+				Some(_) if self.queue.len() > self.target_depth => {
+					self.deep_ticks += 1;
+					if self.deep_ticks >= Self::SHRINK_AFTER {
+						self.target_depth = (self.target_depth - 1).max(Self::MIN_DEPTH);
+						self.deep_ticks = 0;
+					}
+				},
+				Some(_) => self.deep_ticks = 0,
+			}
+		}
+
+		self.history.push_back(self.queue.len());
+		if self.history.len() > Self::HISTORY_LEN {
+			self.history.pop_front();
+		}
+		tick
+	}
+}
+
+/// Packets the transport turned down, held on to for sending again, in
+/// the order they were meant to go out in.
+#[cfg(feature = "client")]
+#[derive(Default)]
+struct Outbox {
+	queue: VecDeque<Vec<u8>>,
+	/// Sends turned down so far, tries again included.
+	failures: u32,
+	/// When the transport started turning packets down, as per `get_time`,
+	/// for as long as it hasn't taken one since.
+	failing_since: Option<f64>,
+}
+
+#[cfg(feature = "client")]
+impl Outbox {
+	/// Notes that the transport took a packet at `now`.
+	fn sent(&mut self, now: f64) {
+		if let Some(since) = self.failing_since.take() {
+			crate::log!(LogLevel::Info, "send_recovered failures={} queued={} after={:.2}", self.failures, self.queue.len(), now - since);
+		}
+	}
+
+	/// Notes that the transport turned a packet down at `now`, with
+	/// `error`. Only packets which stand a chance of going out later on
+	/// count; the rest are dropped right away.
+	fn failed(&mut self, error: SendError, now: f64) -> bool {
+		if !error.is_retryable() {
+			crate::log!(LogLevel::Warn, "send_dropped error=\"{}\"", error);
+			return false;
+		}
+		self.failures += 1;
+		if self.failing_since.is_none() {
+			crate::log!(LogLevel::Warn, "send_failing error=\"{}\" queued={}", error, self.queue.len());
+			self.failing_since = Some(now);
+		}
+		true
+	}
+
+	/// Holds on to a packet, behind those already waiting, and the oldest
+	/// of those dropped if there are `capacity` of them already.
+	fn hold(&mut self, bytes: Vec<u8>, capacity: usize) {
+		if self.queue.len() >= capacity {
+			crate::log!(LogLevel::Debug, "resend_dropped queued={}", self.queue.len());
+			self.queue.pop_front();
+		}
+		self.queue.push_back(bytes);
+	}
+}
+
+/// Takes the predicted ticks before `received_head` out of a packet held
+/// for sending again, or out of every packet of a batch. `None` if that
+/// leaves nothing to send.
+#[cfg(feature = "client")]
+fn without_received(bytes: Vec<u8>, received_head: u64) -> Option<Vec<u8>> {
+	let received = |packet: &[u8]| matches!(
+		Packet::<PlayerIntent>::decode(packet),
+		Ok(Packet::PredictedTick { tick, .. }) if tick.index() < received_head
+	);
+	if !is_batch(&bytes) {
+		return (!received(&bytes)).then_some(bytes);
+	}
+	let Ok(packets) = split_batch(&bytes) else { return Some(bytes) };
+	let kept: Vec<Vec<u8>> = packets.iter().filter(|x| !received(x)).map(|x| x.to_vec()).collect();
+	match kept.len() {
+		0 => None,
+		len if len == packets.len() => Some(bytes),
+		_ => {
+			let mut writer = ByteWriter::new();
+			encode_batch(&kept, &mut writer);
+			Some(writer.as_slice().to_vec())
+		},
+	}
+}
+
+/// Where we stand with the server. Every change goes through
+/// `Game::set_connection_state`, which only takes the ones `can_become`
+/// allows.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, NetType)]
+pub enum ConnectionState {
+	/// Waiting on the server to answer our `ConnectRequest`. Its ticks get
+	/// played back meanwhile, the same as for a spectator.
+	Connecting,
+	/// Finding out which tick the server is on, before predicting the
+	/// first one; see `Game::sync_clock`.
+	Syncing,
+	Connected,
+	/// Getting back in step with the server, after a pause or after losing
+	/// track of it, by syncing the clock again or through a snapshot.
+	Resyncing,
+	/// The server's state hash differs from ours, though the ticks agree.
+	/// We keep predicting, but the world is off until a snapshot arrives.
+	Desynced,
+	/// The accepted head hasn't moved for `CONNECTION_TIMEOUT` seconds, the
+	/// transport lost the connection, or the server turned us away. The
+	/// simulation is frozen until the server is back.
+	Disconnected,
+}
+
+impl ConnectionState {
+	/// Whether going from this state to `next` makes sense. Staying put
+	/// always does.
+	pub fn can_become(self, next: ConnectionState) -> bool {
+		use ConnectionState::*;
+		self == next || matches!((self, next),
+			(Connecting, Syncing | Connected | Disconnected)
+			| (Syncing, Connected | Resyncing | Disconnected)
+			| (Connected, Syncing | Resyncing | Desynced | Disconnected)
+			| (Resyncing, Connected | Desynced | Disconnected)
+			| (Desynced, Connected | Resyncing | Disconnected)
+			| (Disconnected, Connected | Resyncing)
+		)
+	}
+
+	/// Whether prediction may go on in this state, as far as the
+	/// connection is concerned.
+	pub fn is_playing(self) -> bool {
+		matches!(self, ConnectionState::Connected | ConnectionState::Resyncing | ConnectionState::Desynced)
+	}
+}
+
+/// What's left to do about getting let in by the server.
+#[derive(Clone, Debug)]
+struct Handshake {
+	request: ConnectRequest,
+	/// Whether to only watch once we're in, rather than play.
+	spectator: bool,
+	last_request: Option<f64>,
+}
+
+/// A snapshot we asked for and haven't gotten yet; see
+/// `Game::request_snapshot`.
+#[derive(Clone, Copy, Debug)]
+struct PendingSnapshot {
+	/// Whatever got us to ask first. Asking again meanwhile only counts
+	/// towards `coalesced`.
+	reason: SnapshotReason,
+	/// When the request last went out, if it did yet.
+	last_sent: Option<f64>,
+	/// How many times it went out so far.
+	attempts: u32,
+	/// How many more times it got asked for, while waiting.
+	coalesced: u32,
+}
+
+impl PendingSnapshot {
+	/// How long to wait for an answer before asking again.
+	fn retry_interval(&self) -> f64 {
+		let backoff = 2f64.powi(self.attempts.saturating_sub(1).min(16) as i32);
+		(SNAPSHOT_RETRY_INTERVAL * backoff).min(MAX_SNAPSHOT_RETRY_INTERVAL)
+	}
+}
+
+/// A structure representing the local gamestate.
+pub struct Game {
+	/// ClientId denoting the local player. With several local players, it's
+	/// the one packets are sent as.
+	client_id: ClientId,
+	/// Everyone playing from this client, `client_id` first. Usually just
+	/// `client_id`, unless the screen is shared.
+	local_players: Vec<ClientId>,
+	/// A map of all players and their respective ClientIds. Ordered, so that
+	/// every client iterates over the players in the same (ascending) order.
+	players: BTreeMap<ClientId, Player>,
+	/// All ticks processed by the client locally. Includes predicted ticks.
+	ticks: TickHistory<PlayerIntent>,
+	/// Index into ticks denoting the latest tick confirmed "correct" by the server.
+	accepted_head: u64,
+	/// Index of the latest tick the server acknowledged having received.
+	/// Runs ahead of `accepted_head`, which additionally requires the
+	/// prediction to have been correct.
+	received_head: u64,
+	/// Index of the latest tick the server answered, with an ack or in
+	/// full, whether we had predicted it yet or not.
+	answered_head: u64,
+	/// Authoritative ticks which contradicted our prediction for the same index.
+	/// The game loop is expected to take these out and repair the local state.
+	corrections: Vec<Tick<PlayerIntent>>,
+	/// Time at which the most recent confirmation arrived, as per `get_time`.
+	last_confirmation: Option<f64>,
+	/// Set when we can't repair the local state on our own, say once the
+	/// server corrected a tick we no longer have, and need a fresh one.
+	snapshot_request: Option<PendingSnapshot>,
+	/// Why we last sent a snapshot request, and when, for the overlay.
+	last_snapshot_request: Option<(SnapshotReason, f64)>,
+	/// The tick the server's state hash first differed from ours at, since
+	/// the last snapshot. Our world has drifted off the server's, even
+	/// though the ticks themselves agree.
+	desync: Option<u64>,
+	/// The tick we last reported our state hash for.
+	last_state_report: Option<u64>,
+	/// The last error hit while decoding a packet from the host, if any.
+	last_decode_error: Option<DecodeError>,
+	remote_presentation: RemotePresentation,
+	#[cfg(feature = "client")]
+	interpolation: Interpolation,
+	/// How many ticks behind remote players are drawn; see `render_tick`.
+	render_delay: u8,
+	/// How many confirmed ticks apart state summaries get handed out, or
+	/// zero for none at all; see `take_state_summary`.
+	observer_interval: u32,
+	/// The tick the last state summary handed out was for.
+	last_observed: Option<u64>,
+	/// The level everyone plays on, unless a snapshot brings another one.
+	platforms: Vec<Platform>,
+	coins: Coins,
+	/// Everyone's names, as far as we know them. Nothing the simulation
+	/// looks at, so they're kept apart from the players.
+	player_infos: BTreeMap<ClientId, PlayerInfo>,
+	/// Set when the local players' names have to be sent out again; see
+	/// `take_introductions`.
+	introduction_due: bool,
+	chat: ChatLog,
+	/// What the simulation's random numbers are derived from; see `rng`.
+	/// Comes from the server, along with the tick rate.
+	seed: u64,
+	/// The top left corner of the part of the world in view. Stays put for
+	/// as long as there's no local player to follow.
+	#[cfg(feature = "client")]
+	view_origin: (f32, f32),
+	/// Packets the transport turned down, to be sent again; see `send`.
+	#[cfg(feature = "client")]
+	outbox: Outbox,
+	/// Ticks which were due, but got dropped for exceeding the catch-up budget.
+	skipped_ticks: u32,
+	/// How long a frame's ticks get to take, if there's a limit; see
+	/// `set_tick_budget`.
+	tick_budget: Option<TickBudget>,
+	/// When the current frame started, as per the tick budget's clock.
+	frame_started: f64,
+	/// Set once the current frame ran out of tick budget.
+	over_budget: bool,
+	/// Frames which ran out of tick budget.
+	budget_overruns: u32,
+	/// Rollbacks whose replay got split across frames.
+	split_replays: u32,
+	/// What's left of a rollback which didn't fit into the tick budget.
+	pending_replay: Option<PendingReplay>,
+	/// Set while prediction is paused, because we're too far ahead of the server.
+	throttled: bool,
+	/// Ticks in a row, predicted or received from the server, nobody did
+	/// anything on; see `is_idle`.
+	idle_ticks: u64,
+	connection: ConnectionState,
+	/// Set until the server answers our connect request, see `connecting`.
+	handshake: Option<Handshake>,
+	/// Set from `pause` until `resume`.
+	paused: bool,
+	/// Why the server turned us away, if it did. There's no getting in
+	/// after that, short of starting over.
+	rejection: Option<RejectReason>,
+	tick_rate: TickRate,
+	sim_config: SimConfig,
+	/// The server's tick rate, if it differs from ours, and we found out
+	/// too late to switch. Nothing gets simulated anymore, as it would only
+	/// run too fast or too slow.
+	tick_rate_mismatch: Option<u8>,
+	/// The same for the server's sim config, which we'd only ever drift
+	/// apart from everyone else with.
+	sim_config_mismatch: Option<SimConfig>,
+	/// The accepted head as of when it last moved, and the time at which it did.
+	last_progress: Option<(u64, f64)>,
+	/// Every client taking part in the game, the local one included. Frames
+	/// from anyone else are ignored by the simulation.
+	members: BTreeMap<ClientId, Membership>,
+	/// Authoritative ticks waiting to be played back. Only ticks go through
+	/// here; hash confirmations are applied as soon as they arrive.
+	jitter_buffer: JitterBuffer,
+	/// Index of the authoritative tick we expect to receive next. The server's
+	/// ticks are processed strictly in order, regardless of how they arrive.
+	next_authoritative: u64,
+	/// Authoritative ticks which arrived before the ones preceding them.
+	pending_authoritative: BTreeMap<u64, Tick<PlayerIntent>>,
+	/// Delta encodes the ticks we send to the server.
+	tick_encoder: TickEncoder<PlayerIntent>,
+	/// Authoritative ticks thrown away, for being older than what we've
+	/// already processed, or for not fitting into the pending buffer.
+	stale_ticks: u32,
+	/// Frames dropped from authoritative ticks, for coming from clients
+	/// that aren't in the game.
+	rejected_frames: u32,
+	/// What the frames in authoritative ticks get clamped to, if we don't
+	/// trust the server to have done so; see `set_frame_limits`.
+	frame_limits: Option<FrameLimits>,
+	/// Frames in authoritative ticks which had to be clamped.
+	clamped_frames: u32,
+	/// Guesses the remote players' frames for ticks we predict.
+	prediction: Box<dyn PredictionStrategy<PlayerIntent>>,
+	/// How many of our predictions the server corrected, by the name of the
+	/// strategy that was in use at the time.
+	mispredictions: BTreeMap<&'static str, u32>,
+	prediction_stats: PredictionStats,
+	/// What everyone's frames held, for the last second of confirmed ticks
+	/// and every tick predicted since.
+	input_stats: InputStats,
+	/// Whether `input_stats` gets drawn next to every player.
+	show_inputs: bool,
+	/// Events of the ticks simulated since the presentation layer last
+	/// took them, in tick order; see `take_sim_events`.
+	sim_events: VecDeque<TickEvent>,
+	/// Events the presentation layer took, for the ticks which could still
+	/// get replayed, so that replaying them doesn't bring them up again.
+	presented_events: Vec<TickEvent>,
+	/// How long the server takes to answer our ticks.
+	rtt: RttEstimator,
+	/// Set while finding out which tick the server is on, before the first
+	/// prediction; see `sync_clock`.
+	clock_sync: Option<ClockSync>,
+	/// Ticks to stay ahead of the server by, on top of the latency.
+	interp_margin: u8,
+	/// How many of the ticks before it every tick sent repeats the local
+	/// player's frames of; see `set_redundant_frames`.
+	redundant_frames: u8,
+	pacing: TickPacing,
+	/// Intents queued up from code, for the next tick we predict, by the
+	/// local player they're meant for.
+	queued_intents: BTreeMap<ClientId, Vec<PlayerIntent>>,
+	/// How late into the next tick we predict the local players' input came
+	/// in; see `CommandFrame::offset`.
+	input_offsets: BTreeMap<ClientId, u8>,
+	/// Set while we only watch. Nothing gets predicted, and the world only
+	/// advances through the server's ticks.
+	spectating: bool,
+	/// Collects confirmed ticks for a replay, once `start_recording` is called.
+	recorder: Option<Recorder>,
+	budget: MemoryBudget,
+	/// Packets decoded for us, waiting to be taken in by `receive`.
+	inbox: Inbox,
+}
+
+impl Game {
+	/// A game with only the local player in it, about to predict tick 0.
+	pub fn new(client_id: ClientId) -> Self {
+		let mut game = Game {
+			client_id,
+			local_players: vec![client_id],
+			players: BTreeMap::new(),
+			ticks: TickHistory::new(TICK_RETENTION, MemoryBudget::default().history_ticks),
+			accepted_head: 0,
+			received_head: 0,
+			answered_head: 0,
+			corrections: Vec::new(),
+			last_confirmation: None,
+			snapshot_request: None,
+			last_snapshot_request: None,
+			desync: None,
+			last_state_report: None,
+			last_decode_error: None,
+			remote_presentation: RemotePresentation::Predicted,
+			#[cfg(feature = "client")]
+			interpolation: Interpolation::default(),
+			render_delay: DEFAULT_RENDER_DELAY,
+			observer_interval: 0,
+			last_observed: None,
+			platforms: Platform::demo_level(),
+			coins: Coins::default(),
+			player_infos: BTreeMap::new(),
+			introduction_due: false,
+			chat: ChatLog::default(),
+			seed: 0,
+			#[cfg(feature = "client")]
+			view_origin: (0.0, 0.0),
+			#[cfg(feature = "client")]
+			outbox: Outbox::default(),
+			skipped_ticks: 0,
+			tick_budget: None,
+			frame_started: 0.0,
+			over_budget: false,
+			budget_overruns: 0,
+			split_replays: 0,
+			pending_replay: None,
+			throttled: false,
+			idle_ticks: 0,
+			connection: ConnectionState::Connected,
+			handshake: None,
+			paused: false,
+			rejection: None,
+			tick_rate: TickRate::default(),
+			sim_config: SimConfig::default(),
+			sim_config_mismatch: None,
+			tick_rate_mismatch: None,
+			last_progress: None,
+			members: BTreeMap::new(),
+			jitter_buffer: JitterBuffer::new(JITTER_BUFFER_DEPTH, MemoryBudget::default().buffered_ticks),
+			next_authoritative: 0,
+			pending_authoritative: BTreeMap::new(),
+			tick_encoder: TickEncoder::default(),
+			stale_ticks: 0,
+			rejected_frames: 0,
+			frame_limits: None,
+			clamped_frames: 0,
+			prediction: Box::new(RepeatLast),
+			mispredictions: BTreeMap::new(),
+			prediction_stats: PredictionStats::default(),
+			input_stats: InputStats::default(),
+			show_inputs: false,
+			sim_events: VecDeque::new(),
+			presented_events: vec![],
+			rtt: RttEstimator::default(),
+			clock_sync: None,
+			interp_margin: DEFAULT_INTERP_MARGIN,
+			redundant_frames: DEFAULT_REDUNDANT_FRAMES,
+			pacing: TickPacing::default(),
+			queued_intents: BTreeMap::new(),
+			input_offsets: BTreeMap::new(),
+			spectating: false,
+			recorder: None,
+			budget: MemoryBudget::default(),
+			inbox: Inbox::default()
+		};
+
+		game.players.insert(
+			client_id,
+			game.spawn(client_id, 0)
+		);
+		game.members.insert(client_id, Membership {
+			joined: 0,
+			left: None
+		});
+		game
+	}
+
+	/// A game which only watches the others play, without a player of its
+	/// own. `client_id` is who we're going to play as, should we `join`.
+	pub fn spectator(client_id: ClientId) -> Self {
+		let mut game = Game::new(client_id);
+		game.players.clear();
+		game.members.clear();
+		game.spectating = true;
+		// There's nothing predicted to show, only confirmed ticks.
+		game.remote_presentation = RemotePresentation::Interpolated;
+		game
+	}
+
+	/// A game which has yet to be let in by the server, and doesn't know
+	/// who it plays as before that; `connect_request` asks until it hears
+	/// back. Meanwhile, it watches the others play. Once in, the local player
+	/// joins from `ConnectAccept::starting_tick` on, unless we're only a
+	/// `spectator`.
+	pub fn connecting(request: ConnectRequest, spectator: bool) -> Self {
+		let mut game = Game::spectator(request.requested_id.unwrap_or_default());
+		game.local_players.clear();
+		game.connection = ConnectionState::Connecting;
+		game.handshake = Some(Handshake {
+			request,
+			spectator,
+			last_request: None
+		});
+		game
+	}
+
+	/// Whether we're still waiting on the server to let us in.
+	pub fn is_connecting(&self) -> bool {
+		self.handshake.is_some()
+	}
+
+	/// Why the server turned us away, if it did.
+	pub fn rejection(&self) -> Option<RejectReason> {
+		self.rejection
+	}
+
+	pub fn connection_state(&self) -> ConnectionState {
+		self.connection
+	}
+
+	/// Moves on to `next`, logging the change. Changes `can_become` doesn't
+	/// allow are logged and ignored, and so is any change at all once the
+	/// server turned us away. Returns whether we're in `next` now.
+	fn set_connection_state(&mut self, next: ConnectionState) -> bool {
+		let current = self.connection;
+		if current == next {
+			return true;
+		}
+		if !current.can_become(next) || self.rejection.is_some() {
+			crate::log!(LogLevel::Warn, "connection_state_rejected from={:?} to={:?}", current, next);
+			return false;
+		}
+		crate::log!(LogLevel::Info, "connection_state from={:?} to={:?} tick={}", current, next, self.ticks.next_index());
+		self.connection = next;
+		true
+	}
+
+	/// Asks for a snapshot, which only `Syncing` and `Resyncing` do; from
+	/// anywhere else, that means `Resyncing` first. Prediction waits until
+	/// it arrives. Only one request is ever out at a time: asking again
+	/// while waiting, for whichever reason, doesn't send another. See
+	/// `snapshot_request` for what does.
+	pub fn request_snapshot(&mut self, reason: SnapshotReason) {
+		if self.connection != ConnectionState::Syncing && !self.set_connection_state(ConnectionState::Resyncing) {
+			return;
+		}
+		match &mut self.snapshot_request {
+			Some(pending) => {
+				pending.coalesced += 1;
+				crate::log!(LogLevel::Debug, "snapshot_request_coalesced reason={:?} pending={:?}", reason, pending.reason);
+			},
+			None => {
+				crate::log!(LogLevel::Info, "snapshot_requested reason={:?} tick={}", reason, self.ticks.next_index());
+				self.snapshot_request = Some(PendingSnapshot {
+					reason,
+					last_sent: None,
+					attempts: 0,
+					coalesced: 0
+				});
+			},
+		}
+	}
+
+	/// The snapshot request to send, if one is due at `now`, as per
+	/// `get_time`: right after `request_snapshot`, then again every so
+	/// often, backing off from `SNAPSHOT_RETRY_INTERVAL`, until a snapshot
+	/// arrives. Nothing goes out while disconnected.
+	pub fn snapshot_request(&mut self, now: f64) -> Option<Packet<PlayerIntent>> {
+		if self.handshake.is_some() || !matches!(self.connection, ConnectionState::Syncing | ConnectionState::Resyncing) {
+			return None;
+		}
+		let pending = self.snapshot_request.as_mut()?;
+		match pending.last_sent {
+			Some(last_sent) if now - last_sent < pending.retry_interval() => return None,
+			Some(last_sent) => crate::log!(
+				LogLevel::Warn,
+				"snapshot_request_retry reason={:?} attempts={} waited={:.2}",
+				pending.reason, pending.attempts, now - last_sent
+			),
+			None => self.last_snapshot_request = Some((pending.reason, now)),
+		}
+		pending.last_sent = Some(now);
+		pending.attempts += 1;
+		Some(Packet::SnapshotRequest(SnapshotRequest { sender: self.client_id, reason: pending.reason }))
+	}
+
+	/// Where to go once done getting back in step: wherever the world
+	/// still has us.
+	fn settle(&mut self) {
+		let next = match (self.snapshot_request.is_some(), self.desync) {
+			(true, _) => ConnectionState::Resyncing,
+			(false, Some(_)) => ConnectionState::Desynced,
+			(false, None) => ConnectionState::Connected,
+		};
+		self.set_connection_state(next);
+	}
+
+	/// The next connect request to send, if one is due at `now`, as per
+	/// `get_time`. They keep going out until the server answers one, in
+	/// case any get lost.
+	pub fn connect_request(&mut self, now: f64) -> Option<Packet<PlayerIntent>> {
+		let handshake = self.handshake.as_mut()?;
+		if handshake.last_request.is_some_and(|x| now - x < CONNECT_RETRY_INTERVAL) {
+			return None;
+		}
+		handshake.last_request = Some(now);
+		Some(Packet::ConnectRequest(handshake.request.clone()))
+	}
+
+	pub fn is_spectating(&self) -> bool {
+		self.spectating
+	}
+
+	/// Stops spectating, and puts the local player into the game from the
+	/// next tick on. Prediction picks up right after the last tick we
+	/// played back. While still connecting, we join as soon as we're in.
+	pub fn join(&mut self) {
+		if let Some(handshake) = &mut self.handshake {
+			handshake.spectator = false;
+			return;
+		}
+		if !self.spectating {
+			return;
+		}
+		self.spectating = false;
+		self.remote_presentation = RemotePresentation::Predicted;
+		for client_id in &self.local_players {
+			self.members.insert(*client_id, Membership {
+				joined: self.ticks.next_index(),
+				left: None
+			});
+		}
+	}
+
+	/// Adds another player controlled from this client, who joins from the
+	/// next tick on. Their frames go out alongside those of `client_id`.
+	pub fn add_local_player(&mut self, client_id: ClientId) {
+		if self.local_players.contains(&client_id) {
+			return;
+		}
+		self.local_players.push(client_id);
+		if !self.spectating {
+			self.members.insert(client_id, Membership {
+				joined: self.ticks.next_index(),
+				left: None
+			});
+		}
+	}
+
+	pub fn client_id(&self) -> ClientId {
+		self.client_id
+	}
+
+	/// Everyone playing from this client, `client_id` first.
+	pub fn local_players(&self) -> &[ClientId] {
+		&self.local_players
+	}
+
+	/// Everyone currently in the game, by ClientId.
+	pub fn players(&self) -> &BTreeMap<ClientId, Player> {
+		&self.players
+	}
+
+	/// The name a player goes by, once their client introduced them.
+	pub fn name(&self, client_id: ClientId) -> Option<&str> {
+		self.player_infos.get(&client_id).map(|x| x.name.as_str())
+	}
+
+	/// Names a local player, cutting names that are too long short. Goes
+	/// out with the next `take_introductions`.
+	pub fn set_name(&mut self, client_id: ClientId, name: &str) {
+		if !self.local_players.contains(&client_id) {
+			return;
+		}
+		self.player_infos.insert(client_id, PlayerInfo::new(client_id, name));
+		self.introduction_due = true;
+	}
+
+	/// Takes on what another client said about one of its players, for a
+	/// server, or a test. Whatever others claim about our own players is
+	/// ignored, once we named them ourselves, and so are names for anyone
+	/// new, once we know `MemoryBudget::players` of them.
+	pub fn set_player_info(&mut self, info: PlayerInfo) {
+		let known = self.player_infos.contains_key(&info.owner);
+		if self.local_players.contains(&info.owner) && known {
+			return;
+		}
+		if !known && self.player_infos.len() >= self.budget.players {
+			crate::log!(LogLevel::Warn, "player_info_dropped client={} reason=players_full", info.owner);
+			return;
+		}
+		self.player_infos.insert(info.owner, info);
+	}
+
+	/// The packets introducing the local players to everyone else, should
+	/// they be due to go out: after a name changes, and after reconnecting,
+	/// when the server may have forgotten about us.
+	pub fn take_introductions(&mut self) -> Vec<Packet<PlayerIntent>> {
+		if !std::mem::take(&mut self.introduction_due) {
+			return Vec::new();
+		}
+		self.local_players
+			.iter()
+			.filter_map(|x| self.player_infos.get(x))
+			.map(|x| Packet::PlayerInfo(x.clone()))
+			.collect()
+	}
+
+	/// The most recent tick we simulated, predicted or not.
+	pub fn latest_tick(&self) -> Option<&Tick<PlayerIntent>> {
+		self.ticks.last().map(|x| &x.tick)
+	}
+
+	/// Index of the latest tick the server confirmed our prediction of.
+	pub fn accepted_head(&self) -> u64 {
+		self.accepted_head
+	}
+
+	/// Whether we're waiting on a snapshot, having lost track of the server.
+	pub fn is_resync_requested(&self) -> bool {
+		self.snapshot_request.is_some()
+	}
+
+	/// What got us to ask for the snapshot we're waiting on, if we are.
+	pub fn snapshot_reason(&self) -> Option<SnapshotReason> {
+		self.snapshot_request.map(|x| x.reason)
+	}
+
+	/// Decodes a packet, whichever transport it came in through, for the
+	/// next `receive` to take in. Packets from the host go through the
+	/// shared inbox first; see `drain_inbox`.
+	pub fn dispatch_packet(&mut self, bytes: &[u8]) {
+		self.inbox.dispatch(bytes);
+	}
+
+	/// Takes out whatever clients sent in, should we be running in server
+	/// mode; see `ServerBuffers`.
+	pub fn take_server_buffers(&mut self) -> ServerBuffers {
+		std::mem::take(&mut self.inbox.server)
+	}
+
+	/// Takes in everything that arrived since the last frame, repairing the
+	/// local state where the server disagreed with us. `now` is the current
+	/// time, as per `get_time`.
+	pub fn receive(&mut self, now: f64) {
+		self.receive_acks(now);
+		self.receive_decode_errors();
+		self.receive_welcome();
+		self.receive_connect_response();
+		self.receive_time_responses(now);
+		self.receive_membership();
+		self.receive_player_infos();
+		self.receive_chat();
+		self.receive_snapshots();
+		self.receive_confirmations(now);
+		self.receive_authoritative_ticks(now);
+		self.apply_corrections();
+		self.receive_state_hashes();
+		self.update_connection(now);
+		self.update_pacing();
+	}
+
+	/// Runs a single local tick. The server's ticks keep being played back
+	/// even while we're stalled, as they're what gets us going again, unless
+	/// we're paused; then they're kept for `resume`.
+	///
+	/// Unless stalled, the next tick is then predicted with the intents
+	/// `intents` returns for each local player, and simulated. Returns the
+	/// packet proposing it to the server, or `None` if we're stalled; input
+	/// is only taken when it's actually used, so it keeps accumulating
+	/// while we wait. While idle, only every `idle_send_interval`th tick
+	/// gets sent, and the rest return `None` as well, though they still
+	/// get simulated.
+	pub fn run_tick(&mut self, mut intents: impl FnMut(ClientId) -> Vec<PlayerIntent>) -> Option<Packet<PlayerIntent>> {
+		if self.tick_rate_mismatch.is_some() || self.sim_config_mismatch.is_some() || self.is_paused() {
+			return None;
+		}
+		self.continue_replay(true);
+		self.play_authoritative_tick();
+		if self.spectating {
+			return None;
+		}
+
+		self.throttled = self.ticks_ahead() >= MAX_TICKS_AHEAD;
+		if self.is_stalled() {
+			return None;
+		}
+
+		for owner in self.local_players.clone() {
+			if owner != self.client_id {
+				let owner_intents = intents(owner);
+				self.queue_intents(owner, owner_intents);
+			}
+		}
+		let tick = self.predict_tick(intents(self.client_id));
+		self.prediction_stats.record_predicted();
+		self.idle_ticks = match tick.is_idle() {
+			true => self.idle_ticks + 1,
+			false => 0,
+		};
+		// The ticks left out go along with the next one sent, as its past
+		// frames.
+		let packet = match self.is_idle() && !tick.index().is_multiple_of(self.idle_send_interval()) {
+			true => None,
+			false => Some(Packet::PredictedTick {
+				sender: self.client_id,
+				tick: self.tick_encoder.encode(&tick),
+				past_frames: self.past_frames(tick.index()),
+				state_hash: self.state_report()
+			}),
+		};
+
+		// Execute the proposed tick locally, anticipating that it's a correct prediction,
+		// and add it to the local tick list
+		self.advance(tick);
+		packet
+	}
+
+	/// Whether nobody did anything for the last `IDLE_TICKS` ticks, and
+	/// nobody's doing anything on the one just predicted either. Our ticks
+	/// then only get sent every `idle_send_interval` ticks, until either
+	/// we or the server come up with a tick somebody does something on.
+	pub fn is_idle(&self) -> bool {
+		!self.spectating && self.idle_ticks > IDLE_TICKS
+	}
+
+	/// While idle, only every this many ticks gets sent, to show we're
+	/// still there. No more than `redundant_frames` ticks go unsent in
+	/// between, so that the one sent carries the frames of all of them.
+	pub fn idle_send_interval(&self) -> u64 {
+		self.redundant_frames as u64 + 1
+	}
+
+	/// Has every tick sent repeat each local player's frames of the
+	/// `frames` ticks before it, up to `MAX_PAST_FRAMES`, rather than
+	/// `DEFAULT_REDUNDANT_FRAMES`. More of them make up for more packets
+	/// lost in a row, for a few bytes more a packet.
+	pub fn set_redundant_frames(&mut self, frames: u8) {
+		self.redundant_frames = frames.min(MAX_PAST_FRAMES as u8);
+	}
+
+	pub fn redundant_frames(&self) -> u8 {
+		self.redundant_frames
+	}
+
+	/// How many of our ticks get sent a second, which drops while idle.
+	pub fn send_rate(&self) -> u32 {
+		match self.is_idle() {
+			true => self.tick_rate.hz() as u32 / self.idle_send_interval() as u32,
+			false => self.tick_rate.hz() as u32,
+		}
+	}
+
+	/// Whether prediction is paused, either because we're too far ahead of
+	/// the server, because we aren't in step with it, or waiting on a
+	/// snapshot to get back in step, or on a rollback to get through its
+	/// replay, or because we were told to `pause`.
+	pub fn is_stalled(&self) -> bool {
+		self.throttled || !self.connection.is_playing() || self.tick_rate_mismatch.is_some() || self.sim_config_mismatch.is_some()
+			|| self.clock_sync.is_some() || self.paused || self.snapshot_request.is_some() || self.pending_replay.is_some()
+	}
+
+	/// Stops predicting and sending, for while the window is out of focus
+	/// and frames can't be relied on to keep coming. The server's ticks
+	/// are kept meanwhile, rather than played back, for `resume` to catch
+	/// up through. Only possible once we're in the game.
+	pub fn pause(&mut self) {
+		if self.paused || !self.connection.is_playing() {
+			return;
+		}
+		crate::log!(LogLevel::Info, "paused tick={}", self.ticks.next_index());
+		self.paused = true;
+		self.clock_sync = None;
+	}
+
+	/// Gets back in step with the server after `pause`. The clock gets
+	/// synced again, to find out which tick the server is on by now; then
+	/// the ticks it sent meanwhile are played back all at once, and the
+	/// rest of the way gets predicted without sending, like when catching
+	/// up on connecting. If the server's ticks don't get us most of the
+	/// way there, we start over at its tick instead, with a snapshot to
+	/// catch up on. Either way, prediction only resumes after that.
+	///
+	/// A spectator has no clock to sync, and only plays the ticks back.
+	pub fn resume(&mut self) {
+		if !self.paused {
+			return;
+		}
+		crate::log!(LogLevel::Info, "resumed tick={}", self.ticks.next_index());
+		self.paused = false;
+		// Nothing could have been confirmed while we weren't looking.
+		self.last_progress = None;
+		if self.spectating {
+			self.fast_forward();
+			return;
+		}
+		self.set_connection_state(ConnectionState::Resyncing);
+		self.clock_sync = Some(ClockSync::new(self.interp_margin));
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	/// Whether we're getting back in step with the server, after `resume`
+	/// or after losing track of it.
+	pub fn is_resyncing(&self) -> bool {
+		self.connection == ConnectionState::Resyncing
+	}
+
+	/// Holds off predicting until we know which tick the server is on. Time
+	/// requests go out through `time_request`, and once enough answers
+	/// came back, prediction starts `margin` ticks ahead of where the
+	/// server is by then. Only possible before the first tick is predicted,
+	/// or while connecting; requests only go out once we're in.
+	pub fn sync_clock(&mut self, margin: u8) {
+		let playing = match &self.handshake {
+			Some(handshake) => !handshake.spectator,
+			None => self.ticks.last().is_none() && !self.spectating,
+		};
+		if playing {
+			if self.handshake.is_none() {
+				self.set_connection_state(ConnectionState::Syncing);
+			}
+			self.clock_sync = Some(ClockSync::new(margin));
+			self.interp_margin = margin;
+		}
+	}
+
+	/// How many ticks ahead of the last confirmed one we'd like to be: the
+	/// round trip, plus the margin. Unknown before the first round trip.
+	pub fn target_ticks_ahead(&self) -> Option<u64> {
+		let rtt = self.rtt.smoothed()?;
+		let ticks = (rtt / self.tick_rate.delta() as f64).round() as u64 + self.interp_margin as u64;
+		// Any further, and we'd be throttled instead.
+		Some(ticks.min(MAX_TICKS_AHEAD - 1))
+	}
+
+	/// How fast ticks should be run, compared to the tick rate: a bit above
+	/// 1 while we're behind the target, a bit below while ahead of it, and
+	/// never more than `MAX_TICK_SPEED_ADJUSTMENT` off. Meant to scale the
+	/// frame time going into the `TickAccumulator`, and nothing else.
+	pub fn tick_speed(&self) -> f32 {
+		self.pacing.speed
+	}
+
+	fn update_pacing(&mut self) {
+		match self.target_ticks_ahead() {
+			Some(target) if !self.is_stalled() && !self.spectating && self.ticks.last().is_some() => {
+				// Unlike `ticks_ahead`, this goes negative once the server
+				// answers ticks before we got to predicting them.
+				let ahead = self.ticks.next_index() as i64 - self.answered_head as i64 - 1;
+				self.pacing.update(ahead, target);
+			},
+			_ => self.pacing = TickPacing::default(),
+		}
+	}
+
+	pub fn is_syncing_clock(&self) -> bool {
+		self.clock_sync.is_some()
+	}
+
+	/// The next time request to send, if one is due at `now`, as per
+	/// `get_time`.
+	pub fn time_request(&mut self, now: f64) -> Option<Packet<PlayerIntent>> {
+		if self.handshake.is_some() {
+			return None;
+		}
+		let sync = self.clock_sync.as_mut()?;
+		if !sync.request_due(now) {
+			return None;
+		}
+		sync.started.get_or_insert(now);
+		sync.last_request = Some(now);
+		Some(Packet::TimeRequest(TimeRequest { sender: self.client_id, client_time: now }))
+	}
+
+	/// `now` is the current time, as per `get_time`.
+	fn receive_time_responses(&mut self, now: f64) {
+		let responses: Vec<TimeResponse> = self.inbox.time_responses.drain(..).collect();
+		let tick_delta = self.tick_rate.delta() as f64;
+		let Some(sync) = &mut self.clock_sync else { return };
+		for response in &responses {
+			if sync.take(response, now, tick_delta) {
+				self.rtt.update(sync.samples.last().map_or(0.0, |x| x.rtt));
+			}
+		}
+		if sync.samples.len() < CLOCK_SYNC_SAMPLES && !sync.is_timed_out(now) {
+			return;
+		}
+
+		let sync = self.clock_sync.take().unwrap();
+		let resyncing = self.connection == ConnectionState::Resyncing;
+		match sync.start_tick(now, tick_delta) {
+			Some(start) => {
+				crate::log!(LogLevel::Info, "clock_synced tick={} samples={} rtt_ms={:.0}", start, sync.samples.len(), self.rtt.smoothed().unwrap_or(0.0) * 1000.0);
+				match resyncing {
+					true => self.resync_to(start),
+					false => self.catch_up_to(start),
+				}
+			},
+			None => {
+				crate::log!(LogLevel::Warn, "clock_sync_timeout tick={}", self.ticks.next_index());
+				// Whatever the server sent is still worth playing back.
+				if resyncing {
+					self.fast_forward();
+				}
+			},
+		}
+		self.settle();
+	}
+
+	/// Plays back whatever the server sent while we were paused, and
+	/// predicts the rest of the way to `start`. Unless the server's ticks
+	/// got us most of the way there, some of them never made it, and we
+	/// start over at `start` instead, with a snapshot to catch up on.
+	fn resync_to(&mut self, start: u64) {
+		self.fast_forward();
+		if start > self.next_authoritative + MAX_TICKS_AHEAD {
+			crate::log!(LogLevel::Warn, "resync tick={} expected={} reason=paused", self.next_authoritative, start);
+			self.reset_history(start);
+			self.next_authoritative = start;
+			self.request_snapshot(SnapshotReason::Paused);
+			return;
+		}
+		self.catch_up_to(start);
+		self.apply_corrections();
+	}
+
+	/// Plays back every buffered authoritative tick right away, rather than
+	/// one per local tick. Those past our own history are taken on as they
+	/// are, there being no prediction of ours to compare them against.
+	fn fast_forward(&mut self) {
+		for tick in self.jitter_buffer.drain() {
+			self.reconcile(tick);
+		}
+		self.apply_corrections();
+		while let Some(position) = self.corrections.iter().position(|x| x.index() == self.ticks.next_index()) {
+			let tick = self.corrections.remove(position);
+			self.follow(tick);
+		}
+	}
+
+	/// Gets the history to `start`, by predicting the ticks in between
+	/// without sending them; the server goes on without our frames for
+	/// those, and corrects them like any other. Too far behind, and we
+	/// start over at `start` instead, with a snapshot to catch up on.
+	fn catch_up_to(&mut self, start: u64) {
+		let next_index = self.ticks.next_index();
+		if start <= next_index {
+			return;
+		}
+		if start - next_index > MAX_CLOCK_CATCH_UP {
+			crate::log!(LogLevel::Warn, "resync tick={} expected={} reason=far_behind", next_index, start);
+			self.reset_history(start);
+			self.next_authoritative = start;
+			self.request_snapshot(SnapshotReason::FarBehind);
+			return;
+		}
+		for _ in next_index..start {
+			let tick = self.predict_tick(vec![]);
+			self.prediction_stats.record_predicted();
+			self.advance(tick);
+		}
+	}
+
+	pub fn tick_rate(&self) -> TickRate {
+		self.tick_rate
+	}
+
+	/// Switches to the server's tick rate. That's only possible before the
+	/// first tick gets predicted; a different rate after that leaves the
+	/// game stalled for good, see `tick_rate_mismatch`.
+	pub fn set_tick_rate(&mut self, hz: u8) {
+		if hz == self.tick_rate.hz {
+			return;
+		}
+		match TickRate::new(hz) {
+			Some(rate) if self.ticks.last().is_none() => self.tick_rate = rate,
+			_ => self.tick_rate_mismatch = Some(hz),
+		}
+	}
+
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	pub fn sim_config(&self) -> &SimConfig {
+		&self.sim_config
+	}
+
+	/// Switches to the server's sim config. Like the tick rate, that's only
+	/// possible before the first tick gets predicted; a different config
+	/// after that leaves the game stalled for good, see
+	/// `sim_config_mismatch`, and so does one that isn't playable.
+	pub fn set_sim_config(&mut self, config: SimConfig) {
+		if config == self.sim_config {
+			return;
+		}
+		if !config.is_playable() || self.ticks.last().is_some() {
+			crate::log!(LogLevel::Warn, "sim_config_rejected tick={} playable={}", self.ticks.next_index(), config.is_playable());
+			self.sim_config_mismatch = Some(config);
+			return;
+		}
+		self.sim_config = config;
+		// Players spawn on the floor, wherever that is now.
+		self.respawn_players();
+	}
+
+	/// The server's sim config, if we're stuck with a different one.
+	pub fn sim_config_mismatch(&self) -> Option<&SimConfig> {
+		self.sim_config_mismatch.as_ref()
+	}
+
+	/// Switches to the server's seed. Like the tick rate, that's only
+	/// possible before the first tick gets predicted; after that, the world
+	/// has already been simulated with the old one, and is left to get
+	/// caught as a desync.
+	pub fn set_seed(&mut self, seed: u64) {
+		if seed == self.seed {
+			return;
+		}
+		if self.ticks.last().is_some() {
+			crate::log!(LogLevel::Warn, "seed_rejected tick={} seed={}", self.ticks.next_index(), seed);
+			return;
+		}
+		self.seed = seed;
+		// Where players spawn may depend on it.
+		self.respawn_players();
+	}
+
+	/// Spawns everyone in the game over again, for before the first tick.
+	fn respawn_players(&mut self) {
+		let spawned: Vec<(ClientId, u64)> = self.members.iter().map(|(id, x)| (*id, x.joined)).collect();
+		for (client_id, joined) in spawned {
+			if self.players.contains_key(&client_id) {
+				let player = self.spawn(client_id, joined);
+				self.players.insert(client_id, player);
+			}
+		}
+	}
+
+	/// A player joining on tick `tick_index`, as `simulate` spawns them.
+	fn spawn(&self, client_id: ClientId, tick_index: u64) -> Player {
+		let context = SimContext::new(self.seed, tick_index, &self.tick_rate, &self.sim_config);
+		Player::spawn(client_id, &mut context.player_rng(client_id), &self.sim_config)
+	}
+
+	/// The server's tick rate, if we're stuck at a different one.
+	pub fn tick_rate_mismatch(&self) -> Option<u8> {
+		self.tick_rate_mismatch
+	}
+
+	/// Records ticks which were due, but got dropped for exceeding the
+	/// catch-up budget.
+	pub fn skip_ticks(&mut self, count: u32) {
+		self.skipped_ticks += count;
+	}
+
+	/// Limits how long the ticks of a frame get to take, from `start_frame`
+	/// on, rollbacks included; `None`, the default, for no limit at all.
+	/// Once it's used up, the game loop is expected to leave the rest of
+	/// the frame's ticks be, see `is_over_budget`, and a rollback carries
+	/// on with its replay over the next frames. The world drawn meanwhile
+	/// is the one from before the rollback, and nothing is predicted until
+	/// the replay is through, there being no world to predict from.
+	pub fn set_tick_budget(&mut self, budget: Option<TickBudget>) {
+		self.tick_budget = budget;
+	}
+
+	pub fn tick_budget(&self) -> Option<TickBudget> {
+		self.tick_budget
+	}
+
+	/// Starts the clock on the frame's tick budget, if there is one. Meant
+	/// to be called before `receive`, which rollbacks start from.
+	pub fn start_frame(&mut self) {
+		self.over_budget = false;
+		if let Some(budget) = &self.tick_budget {
+			self.frame_started = (budget.clock)();
+		}
+	}
+
+	/// Whether the frame's used up its tick budget. The first time it has,
+	/// it's counted in `budget_overruns`.
+	pub fn is_over_budget(&mut self) -> bool {
+		if self.over_budget {
+			return true;
+		}
+		let Some(budget) = &self.tick_budget else { return false };
+		let elapsed = (budget.clock)() - self.frame_started;
+		if elapsed > budget.per_frame {
+			crate::log!(LogLevel::Debug, "tick_budget_exceeded tick={} elapsed_ms={:.1}", self.ticks.next_index(), elapsed * 1000.0);
+			self.over_budget = true;
+			self.budget_overruns += 1;
+		}
+		self.over_budget
+	}
+
+	/// Frames which used up their tick budget; see `set_tick_budget`.
+	pub fn budget_overruns(&self) -> u32 {
+		self.budget_overruns
+	}
+
+	/// Rollbacks whose replay didn't fit into a single frame's tick budget.
+	pub fn split_replays(&self) -> u32 {
+		self.split_replays
+	}
+
+	/// Whether a rollback is still getting through its replay.
+	pub fn is_replaying(&self) -> bool {
+		self.pending_replay.is_some()
+	}
+
+	/// `now` is the current time, as per `get_time`.
+	fn receive_acks(&mut self, now: f64) {
+		let acks: Vec<u64> = self.inbox.acks.drain(..).collect();
+		for tick_index in acks {
+			// Nothing to say about ticks we haven't predicted yet; taking
+			// it in would have us hold back ones the server never got.
+			if tick_index >= self.ticks.next_index() {
+				continue;
+			}
+			self.received_head = self.received_head.max(tick_index);
+			self.tick_encoder.acknowledge(tick_index);
+			// Receipts are sent right away, while confirmations wait for the
+			// tick to be over, so the round trip is best timed by these.
+			self.sample_rtt(tick_index, now);
+		}
+	}
+
+	fn receive_decode_errors(&mut self) {
+		if let Some(error) = self.inbox.last_decode_error.take() {
+			self.last_decode_error = Some(error);
+		}
+	}
+
+	fn receive_welcome(&mut self) {
+		if let Some((tick_rate, seed, sim_config)) = self.inbox.welcome.take() {
+			self.set_tick_rate(tick_rate);
+			self.set_sim_config(sim_config);
+			self.set_seed(seed);
+		}
+	}
+
+	/// Takes on the ClientId the server handed out, and puts the local
+	/// player into the game, if it let us in.
+	fn receive_connect_response(&mut self) {
+		let Some(response) = self.inbox.connect_response.take() else { return };
+		// Answers to the requests that went out again, before the first one
+		// got answered.
+		let Some(handshake) = self.handshake.take() else { return };
+		let accept = match response {
+			Ok(accept) => accept,
+			Err(reason) => {
+				crate::log!(LogLevel::Warn, "connect_rejected reason={:?}", reason);
+				self.set_connection_state(ConnectionState::Disconnected);
+				self.rejection = Some(reason);
+				self.clock_sync = None;
+				return;
+			},
+		};
+
+		crate::log!(LogLevel::Info, "connected client={} tick={}", accept.client_id, accept.starting_tick);
+		self.client_id = accept.client_id;
+		self.local_players = vec![accept.client_id];
+		self.set_connection_state(match self.clock_sync {
+			Some(_) if !handshake.spectator => ConnectionState::Syncing,
+			_ => ConnectionState::Connected,
+		});
+		self.set_tick_rate(accept.tick_rate);
+		self.set_sim_config(accept.sim_config);
+		if let Some(name) = &handshake.request.name {
+			self.set_name(accept.client_id, name);
+		}
+		if handshake.spectator {
+			return;
+		}
+
+		self.spectating = false;
+		self.remote_presentation = RemotePresentation::Predicted;
+		// However far the server's ticks got us while we waited, the server
+		// only has our player in the game from the starting tick on.
+		self.members.insert(accept.client_id, Membership {
+			joined: accept.starting_tick.max(self.ticks.next_index()),
+			left: None
+		});
+		// Syncing the clock gets us there too, and further still.
+		if self.clock_sync.is_none() {
+			self.catch_up_to(accept.starting_tick);
+		}
+	}
+
+	/// `now` is the current time, as per `get_time`.
+	fn receive_authoritative_ticks(&mut self, now: f64) {
+		let ticks: Vec<Tick<PlayerIntent>> = self.inbox.authoritative_ticks.drain(..).collect();
+		if self.connection == ConnectionState::Disconnected {
+			if !ticks.is_empty() {
+				self.reconnect();
+			}
+			return;
+		}
+		for mut tick in ticks {
+			// A correction is just as much of an answer as an ack.
+			self.answered_head = self.answered_head.max(tick.index());
+			self.sample_rtt(tick.index(), now);
+			self.reject_unknown_frames(&mut tick);
+			self.clamp_frames(&mut tick);
+			// Someone's doing something, so we'd better say what we are.
+			if !tick.is_idle() {
+				self.idle_ticks = 0;
+			}
+			// An idle one next in line, turning out the way we predicted it,
+			// is as good as an ack, and has nothing to wait in the jitter
+			// buffer for. That's how the ticks we don't send while idle get
+			// confirmed, without the buffer growing over their arrivals.
+			if tick.is_idle() && self.confirms_in_line(&tick) {
+				self.next_authoritative = tick.index() + 1;
+				continue;
+			}
+			// Sent again, it would only hold up the ticks behind it.
+			if self.is_duplicate(&tick) {
+				continue;
+			}
+			let index = tick.index();
+			if !self.jitter_buffer.push(tick) {
+				crate::log!(LogLevel::Warn, "tick_dropped tick={} expected={} reason=jitter_full", index, self.next_authoritative);
+				self.stale_ticks += 1;
+			}
+		}
+	}
+
+	/// Whether we have `tick` already: buffered, waiting on an earlier one,
+	/// or confirmed as is.
+	fn is_duplicate(&self, tick: &Tick<PlayerIntent>) -> bool {
+		let same = |x: &Tick<PlayerIntent>| x.index() == tick.index() && x.hash() == tick.hash();
+		self.jitter_buffer.queue.iter().any(same)
+			|| self.pending_authoritative.get(&tick.index()).is_some_and(same)
+			|| self.ticks.get(tick.index()).is_some_and(|x| x.authoritative && same(&x.tick))
+	}
+
+	/// Whether `tick` is the next authoritative one, with nothing ahead of
+	/// it still waiting to be played back, and confirms our prediction.
+	fn confirms_in_line(&mut self, tick: &Tick<PlayerIntent>) -> bool {
+		self.skip_settled_ticks();
+		let waiting = !self.jitter_buffer.queue.is_empty()
+			|| !self.pending_authoritative.is_empty()
+			|| !self.corrections.is_empty()
+			|| self.pending_replay.is_some();
+		!self.spectating && !waiting && tick.index() == self.next_authoritative && self.confirm(tick.index(), tick.hash())
+	}
+
+	/// Drops the frames of clients which never joined, or are long gone.
+	/// The simulation would ignore them anyway, but predictions repeating
+	/// them would keep a ghost player's input around. The hash is redone
+	/// to match, so that our own predictions without them still confirm.
+	///
+	/// Frames claiming to be from a local player are fine: on the
+	/// authoritative path, they're the server's view of us.
+	fn reject_unknown_frames(&mut self, tick: &mut Tick<PlayerIntent>) {
+		let mut unknown = vec![];
+		tick.retain_frames(|x| {
+			let known = self.members.contains_key(&x.owner);
+			if !known {
+				unknown.push(x.owner);
+			}
+			known
+		});
+		let Some(owner) = unknown.first() else { return };
+
+		crate::log!(LogLevel::Warn, "frames_rejected tick={} owners={:?}", tick.index(), unknown);
+		self.rejected_frames += unknown.len() as u32;
+		self.last_decode_error = Some(DecodeError::UnknownFrameOwner { owner: *owner, index: tick.index() });
+	}
+
+	/// Frames dropped from authoritative ticks so far, see `reject_unknown_frames`.
+	pub fn rejected_frames(&self) -> u32 {
+		self.rejected_frames
+	}
+
+	/// The last error hit while decoding a packet handed to us, if any.
+	pub fn last_decode_error(&self) -> Option<&DecodeError> {
+		self.last_decode_error.as_ref()
+	}
+
+	/// Clamps the frames of an authoritative tick to the frame limits, if
+	/// there are any. A server that clamps the same way never sends
+	/// anything this changes; one that doesn't gets its tick simulated as
+	/// it should have been, rather than as it was.
+	fn clamp_frames(&mut self, tick: &mut Tick<PlayerIntent>) {
+		let Some(limits) = self.frame_limits else { return };
+		let clamped = tick.clamp_frames(&limits);
+		if !clamped.is_empty() {
+			crate::log!(LogLevel::Warn, "frames_clamped tick={} owners={:?}", tick.index(), clamped);
+			self.clamped_frames += clamped.len() as u32;
+		}
+	}
+
+	/// Has the frames of every authoritative tick clamped to `limits`, see
+	/// `CommandFrame::clamp`, or taken as they come with `None`, the
+	/// default; the server is expected to have clamped them already.
+	pub fn set_frame_limits(&mut self, limits: Option<FrameLimits>) {
+		self.frame_limits = limits;
+	}
+
+	/// Frames clamped in authoritative ticks so far, see `set_frame_limits`.
+	pub fn clamped_frames(&self) -> u32 {
+		self.clamped_frames
+	}
+
+	/// Caps everything the game holds on to at `budget`, from here on;
+	/// whatever's over it already is only dropped as more comes in. The
+	/// inbox's cap goes for every game in the process, as does the inbox.
+	/// A recording under way keeps the cap it started out with.
+	pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+		self.budget = budget;
+		self.ticks.capacity = budget.history_ticks;
+		self.jitter_buffer.capacity = budget.buffered_ticks;
+		set_inbox_capacity(budget.inbox_packets);
+	}
+
+	pub fn memory_budget(&self) -> &MemoryBudget {
+		&self.budget
+	}
+
+	/// How much of every cap in the memory budget is taken up right now.
+	pub fn memory_usage(&self) -> Vec<MemoryUsage> {
+		let budget = &self.budget;
+		let usage = |subsystem, used, cap| MemoryUsage { subsystem, used, cap };
+		let mut usages = vec![
+			usage("history_ticks", self.ticks.len(), budget.history_ticks),
+			usage("inbox_packets", inbox_len(), inbox_capacity()),
+			usage("buffered_ticks", self.jitter_buffer.queue.len(), budget.buffered_ticks),
+			usage("pending_ticks", self.pending_authoritative.len(), budget.pending_ticks),
+			usage("pending_corrections", self.corrections.len(), budget.pending_corrections),
+		];
+		#[cfg(feature = "client")]
+		usages.push(usage("resend_packets", self.outbox.queue.len(), budget.resend_packets));
+		if let Some(recorder) = &self.recorder {
+			usages.push(usage("replay_bytes", recorder.len(), recorder.capacity()));
+		}
+		usages.push(usage("players", self.members.len().max(self.player_infos.len()), budget.players));
+		usages.push(usage("sim_events", self.sim_events.len(), budget.sim_events));
+		usages
+	}
+
+	/// Panics, naming the culprit, if anything the game holds on to is
+	/// over its cap in the memory budget. The caps are enforced as things
+	/// come in, so this only ever fires on a bug; the client checks once a
+	/// frame in debug builds, and tests wherever they like.
+	pub fn assert_within_budget(&self) {
+		for usage in self.memory_usage() {
+			assert!(usage.is_within_budget(), "{} over its memory budget, at {} of {}", usage.subsystem, usage.used, usage.cap);
+		}
+	}
+
+	/// Our state hash for the accepted head, if it's been long enough since
+	/// we last reported one. Only confirmed ticks are worth comparing, as
+	/// anything we merely predicted could still change. So could a tick an
+	/// ack confirmed, while a correction for an earlier one is still waiting
+	/// to be played back.
+	fn state_report(&mut self) -> Option<StateHash> {
+		if self.settled_head() < self.accepted_head {
+			return None;
+		}
+		let due = self.last_state_report.is_none_or(|x| self.accepted_head >= x + STATE_REPORT_INTERVAL);
+		let record = self.ticks.get(self.accepted_head).filter(|x| due && x.authoritative)?;
+		let report = StateHash {
+			index: self.accepted_head,
+			hash: record.state_hash
+		};
+		self.last_state_report = Some(self.accepted_head);
+		Some(report)
+	}
+
+	/// Compares the server's state hashes against ours. Since the ticks
+	/// agreed, there's nothing a rollback could fix; only a snapshot can,
+	/// and we're `Desynced` until one arrives.
+	fn receive_state_hashes(&mut self) {
+		let state_hashes: Vec<StateHash> = self.inbox.state_hashes.drain(..).collect();
+		for state_hash in state_hashes {
+			// A tick the replay has yet to get to still has its old hash.
+			if self.pending_replay.as_ref().is_some_and(|x| state_hash.index >= x.next_index) {
+				self.inbox.state_hashes.push(state_hash);
+				continue;
+			}
+			let Some(record) = self.ticks.get(state_hash.index) else { continue };
+			if record.authoritative && record.state_hash != state_hash.hash {
+				crate::log!(LogLevel::Error, "desync tick={}", state_hash.index);
+				self.desync.get_or_insert(state_hash.index);
+				if self.connection == ConnectionState::Connected {
+					self.set_connection_state(ConnectionState::Desynced);
+				}
+			}
+		}
+	}
+
+	/// Plays back the next buffered authoritative tick, if one is due.
+	fn play_authoritative_tick(&mut self) {
+		if let Some(tick) = self.jitter_buffer.pop() {
+			self.reconcile(tick);
+			self.apply_corrections();
+		}
+	}
+
+	/// Joins and departures take effect from the next tick we predict.
+	fn receive_membership(&mut self) {
+		let events: Vec<MembershipEvent> = self.inbox.membership.drain(..).collect();
+		let next_index = self.ticks.next_index();
+		for event in events {
+			match event {
+				MembershipEvent::Joined(client_id) => self.add_member(client_id),
+				MembershipEvent::Left(client_id) => {
+					crate::log!(LogLevel::Info, "leave client={} tick={}", client_id, next_index);
+					if let Some(membership) = self.members.get_mut(&client_id) {
+						membership.left.get_or_insert(next_index);
+					}
+				},
+			}
+		}
+
+		// Nothing at or before the accepted head gets replayed anymore, so
+		// clients which left by then can be forgotten.
+		self.members.retain(|_, x| x.left.is_none_or(|left| left > self.accepted_head));
+	}
+
+	/// Puts `client_id` into the game from the next tick on, unless there
+	/// are `MemoryBudget::players` members already. The local players
+	/// always get in.
+	pub(crate) fn add_member(&mut self, client_id: ClientId) {
+		let full = self.members.len() >= self.budget.players;
+		if full && !self.members.contains_key(&client_id) && !self.local_players.contains(&client_id) {
+			crate::log!(LogLevel::Warn, "join_dropped client={} reason=players_full", client_id);
+			return;
+		}
+		crate::log!(LogLevel::Info, "join client={} tick={}", client_id, self.ticks.next_index());
+		self.members.insert(client_id, Membership {
+			joined: self.ticks.next_index(),
+			left: None
+		});
+	}
+
+	fn receive_player_infos(&mut self) {
+		let infos: Vec<PlayerInfo> = self.inbox.player_infos.drain(..).collect();
+		for info in infos {
+			crate::log!(LogLevel::Debug, "player_info client={} name={:?}", info.owner, info.name);
+			self.set_player_info(info);
+		}
+	}
+
+	fn receive_chat(&mut self) {
+		let messages: Vec<(ClientId, String)> = self.inbox.chat.drain(..).collect();
+		for (sender, text) in messages {
+			self.chat.push(sender, &text);
+		}
+	}
+
+	pub fn chat(&self) -> &ChatLog {
+		&self.chat
+	}
+
+	/// The packet saying `text` in the chat, from this client, unless
+	/// there's nothing left of it to say once sanitized. It only shows up
+	/// in our own chat once the server relays it back.
+	pub fn say(&self, text: &str) -> Option<Packet<PlayerIntent>> {
+		if self.connection == ConnectionState::Disconnected {
+			return None;
+		}
+		let text = chat::sanitize(text);
+		(!text.is_empty()).then_some(Packet::Chat { sender: self.client_id, text })
+	}
+
+	fn receive_snapshots(&mut self) {
+		let snapshots: Vec<Snapshot> = self.inbox.snapshots.drain(..).collect();
+		if let Some(snapshot) = snapshots.into_iter().max_by_key(|x| x.index) {
+			self.apply_snapshot(snapshot);
+		}
+	}
+
+	/// Captures the world as it is after the latest simulated tick.
+	/// The counterpart of `apply_snapshot`, meant for the serving side.
+	pub fn snapshot(&self) -> Snapshot {
+		let players = self.players
+			.iter()
+			.map(|(owner, player)| SnapshotEntry {
+				owner: *owner,
+				player: player.clone()
+			})
+			.collect();
+
+		Snapshot {
+			index: self.ticks.next_index().saturating_sub(1),
+			seed: self.seed,
+			sim_config: self.sim_config,
+			quantized: false,
+			players,
+			platforms: self.platforms.clone(),
+			coins: self.coins.clone(),
+			infos: self.player_infos.values().cloned().collect()
+		}
+	}
+
+	pub fn platforms(&self) -> &[Platform] {
+		&self.platforms
+	}
+
+	/// Replaces the level, for a server setting up a game, or a test. Every
+	/// client has to play on the same one, which joining clients get along
+	/// with their snapshot.
+	pub fn set_platforms(&mut self, platforms: Vec<Platform>) {
+		self.platforms = platforms;
+	}
+
+	pub fn coins(&self) -> &Coins {
+		&self.coins
+	}
+
+	/// Puts a coin down on top of the ones that spawn by themselves, for a
+	/// test, unless `Coins::MAX_LYING` are lying around already.
+	pub fn place_coin(&mut self, coin: Coin) {
+		if self.coins.lying.len() < Coins::MAX_LYING {
+			self.coins.lying.push(coin);
+		}
+	}
+
+	/// Replaces the local world with a snapshot, and resumes prediction right
+	/// after it. Everything we predicted so far is thrown away.
+	fn apply_snapshot(&mut self, snapshot: Snapshot) {
+		crate::log!(LogLevel::Info, "snapshot tick={} players={} platforms={}", snapshot.index, snapshot.players.len(), snapshot.platforms.len());
+		self.platforms = snapshot.platforms;
+		self.coins = snapshot.coins;
+		self.seed = snapshot.seed;
+		// The history starts over from the snapshot, so there's no tick
+		// left that a different config could have gotten simulated with.
+		self.sim_config = snapshot.sim_config;
+		self.sim_config_mismatch = (!snapshot.sim_config.is_playable()).then_some(snapshot.sim_config);
+		// The server's idea of our own names may be outdated, or missing.
+		let local_infos: Vec<PlayerInfo> = self.local_players
+			.iter()
+			.filter_map(|x| self.player_infos.get(x).cloned())
+			.collect();
+		self.player_infos = snapshot.infos
+			.into_iter()
+			.chain(local_infos)
+			.map(|x| (x.owner, x))
+			.collect();
+		self.players = snapshot.players
+			.into_iter()
+			.map(|x| (x.owner, x.player))
+			.collect();
+		if !self.spectating {
+			for client_id in &self.local_players {
+				if !self.players.contains_key(client_id) {
+					let player = self.spawn(*client_id, snapshot.index + 1);
+					self.players.insert(*client_id, player);
+				}
+			}
+		}
+
+		self.members = self.players
+			.keys()
+			.map(|x| (*x, Membership {
+				joined: snapshot.index + 1,
+				left: None
+			}))
+			.collect();
+
+		self.reset_history(snapshot.index + 1);
+		self.accepted_head = snapshot.index;
+		self.received_head = self.received_head.max(snapshot.index);
+		self.answered_head = self.answered_head.max(snapshot.index);
+		self.corrections.retain(|x| x.index() > snapshot.index);
+		if let Some(pending) = self.snapshot_request.take() {
+			crate::log!(LogLevel::Info, "snapshot_request_answered reason={:?} attempts={} coalesced={}", pending.reason, pending.attempts, pending.coalesced);
+		}
+		self.desync = None;
+		self.last_state_report = None;
+		// Still connecting or syncing the clock, there's more to do first.
+		if self.handshake.is_none() && self.clock_sync.is_none() {
+			self.set_connection_state(ConnectionState::Connected);
+		}
+		self.last_progress = None;
+		self.next_authoritative = snapshot.index + 1;
+		self.pending_authoritative.retain(|index, _| *index > snapshot.index);
+		self.jitter_buffer.queue.retain(|x| x.index() > snapshot.index);
+
+		if self.recorder.is_some() {
+			let world = self.snapshot();
+			if let Some(recorder) = &mut self.recorder {
+				recorder.world(snapshot.index + 1, world.players, world.platforms, world.coins, Membership::entries(&self.members));
+			}
+		}
+	}
+
+	/// Starts recording every tick that gets confirmed from here on, for
+	/// `export_replay`. Meant to be called before the first tick is
+	/// predicted; the replay starts out from the world as it is now.
+	pub fn start_recording(&mut self) {
+		let mut recorder = Recorder::new(self.budget.replay_bytes);
+		let world = self.snapshot();
+		recorder.world(self.ticks.next_index(), world.players, world.platforms, world.coins, Membership::entries(&self.members));
+		self.recorder = Some(recorder);
+	}
+
+	/// The tick at `index` in the history, as pretty-printed JSON, for
+	/// poking at from the console. `None` once it's been pruned, or before
+	/// it exists.
+	#[cfg(feature = "serde")]
+	pub fn debug_dump_tick(&self, index: u64) -> Option<String> {
+		let record = self.ticks.get(index)?;
+		serde_json::to_string_pretty(&record.tick).ok()
+	}
+
+	/// Everything recorded since `start_recording`, as a replay `Replay::parse`
+	/// reads back. `None` if we aren't recording.
+	pub fn export_replay(&self) -> Option<Vec<u8>> {
+		let header = ReplayHeader {
+			protocol_version: PROTOCOL_VERSION,
+			hasher: Hasher::ID,
+			tick_rate: self.tick_rate.hz,
+			seed: self.seed,
+			sim_config: self.sim_config,
+			client_id: self.client_id
+		};
+		self.recorder.as_ref().map(|x| x.export(&header))
+	}
+
+	/// The whole client state, as `import_state` reads back, for the host to
+	/// keep around over a page reload; see `saved_state`. Fails on a
+	/// history holding ticks that couldn't be sent either.
+	pub fn export_state(&self) -> Result<Vec<u8>, EncodeError> {
+		self.saved_state(usize::MAX).to_bytes()
+	}
+
+	/// The client state, with no more than `max_ticks` of the newest ticks
+	/// in the history.
+	fn saved_state(&self, max_ticks: usize) -> SavedState {
+		let header = StateHeader {
+			protocol_version: PROTOCOL_VERSION,
+			hasher: Hasher::ID,
+			tick_rate: self.tick_rate.hz,
+			seed: self.seed,
+			client_id: self.client_id
+		};
+		let entries = |players: &BTreeMap<ClientId, Player>| -> Vec<SnapshotEntry> {
+			players
+				.iter()
+				.map(|(owner, player)| SnapshotEntry {
+					owner: *owner,
+					player: player.clone()
+				})
+				.collect()
+		};
+		let ticks = self.ticks.records
+			.iter()
+			.skip(self.ticks.len().saturating_sub(max_ticks))
+			.map(|x| SavedTick {
+				tick: x.tick.clone(),
+				players: entries(&x.players),
+				coins: x.coins.clone(),
+				authoritative: x.authoritative,
+				suspect: x.suspect,
+				state_hash: x.state_hash
+			})
+			.collect();
+
+		SavedState::new(header, SavedGame {
+			local_players: self.local_players.clone(),
+			spectating: self.spectating,
+			sim_config: self.sim_config,
+			platforms: self.platforms.clone(),
+			coins: self.coins.clone(),
+			players: entries(&self.players),
+			members: Membership::entries(&self.members),
+			infos: self.player_infos.values().cloned().collect(),
+			accepted_head: self.accepted_head,
+			received_head: self.received_head,
+			answered_head: self.answered_head,
+			next_authoritative: self.next_authoritative,
+			next_index: self.ticks.next_index(),
+			ticks,
+			unplayed: self.jitter_buffer.queue
+				.iter()
+				.chain(self.pending_authoritative.values())
+				.cloned()
+				.collect()
+		})
+	}
+
+	/// Writes a diagnostic bundle for a bug report into `writer`: the state,
+	/// with the newest `DIAGNOSTICS_TICKS` ticks of the history, and what
+	/// the state doesn't say, like how our predictions fared and the last
+	/// lines logged; see `diagnostics`. `frame` and `now` are what the
+	/// bundle says it was captured at. `writer` is meant to be reused, so
+	/// that capturing doesn't allocate; nothing's left in it on failure.
+	pub fn export_diagnostics(&self, writer: &mut ByteWriter, frame: u64, now: f64) -> Result<(), EncodeError> {
+		let header = DiagnosticsHeader {
+			version: DIAGNOSTICS_VERSION,
+			frame,
+			captured_at: now
+		};
+		let stats = &self.prediction_stats;
+		let report = DiagnosticsReport {
+			connection_state: self.connection,
+			desync: self.desync,
+			snapshot_reason: self.snapshot_reason(),
+			predicted: stats.predicted,
+			confirmed: stats.confirmed,
+			corrected: stats.corrected,
+			remote_intents: stats.remote_intents,
+			local_frames_lost: stats.local_frames_lost,
+			mispredictions: self.mispredictions
+				.iter()
+				.map(|(strategy, count)| StrategyCount { strategy: strategy.to_string(), count: *count })
+				.collect(),
+			stale_ticks: self.stale_ticks,
+			rejected_frames: self.rejected_frames,
+			clamped_frames: self.clamped_frames,
+			skipped_ticks: self.skipped_ticks,
+			last_decode_error: self.last_decode_error.as_ref().map(|x| x.to_string()),
+			log: crate::log::recent()
+		};
+
+		let start = writer.len();
+		writer.write_bytes(&DIAGNOSTICS_MAGIC);
+		let written = header.to_bytes(writer)
+			.and_then(|()| report.to_bytes(writer))
+			.and_then(|()| self.saved_state(DIAGNOSTICS_TICKS).write(writer));
+		if written.is_err() {
+			writer.truncate(start);
+		}
+		written
+	}
+
+	/// Simulates every tick in the history again, each from the world as
+	/// it was right before it, and returns the first one that comes out
+	/// hashing differently than it did the first time around, if any. Run
+	/// on a game from a diagnostic bundle, it tells whether the simulation
+	/// itself went off, or whatever it got fed; see `Diagnostics::into_game`.
+	pub fn resimulate(&mut self) -> Option<u64> {
+		let (players, coins) = (self.players.clone(), self.coins.clone());
+		let mut diverged = None;
+		for index in self.ticks.records.iter().map(|x| x.tick.index()).collect::<Vec<_>>() {
+			let Some(record) = self.ticks.get(index) else { continue };
+			let (tick, state_hash) = (record.tick.clone(), record.state_hash);
+			self.players = record.players.clone();
+			self.coins = record.coins.clone();
+			self.simulate(&tick);
+			if self.state_hash() != state_hash {
+				crate::log!(LogLevel::Warn, "resimulated_differently tick={}", index);
+				diverged = Some(index);
+				break;
+			}
+		}
+		self.players = players;
+		self.coins = coins;
+		diverged
+	}
+
+	/// Restores a state saved by `export_state`, see `restore_state`.
+	/// Nothing changes if it can't be read back.
+	pub fn import_state(&mut self, bytes: &[u8]) -> Result<(), StateError> {
+		self.restore_state(SavedState::parse(bytes)?);
+		Ok(())
+	}
+
+	/// Replaces the whole game with a saved state, down to who we're playing
+	/// as. The prediction strategy and how remote players are drawn are
+	/// kept, along with the chat; anything in flight, like the jitter buffer
+	/// or the clock sync, starts over, though the server's ticks waiting in
+	/// it are kept.
+	///
+	/// The server has moved on since the state was saved, and may never
+	/// have gotten the last of our ticks, so the state is only a starting
+	/// point: a resync is asked for straight away, and our names sent out
+	/// again, rather than carrying on with the restored predictions.
+	pub fn restore_state(&mut self, state: SavedState) {
+		let header = *state.header();
+		let saved = state.game;
+		crate::log!(LogLevel::Info, "state_restored tick={} ticks={} client={}", saved.accepted_head, saved.ticks.len(), header.client_id);
+
+		let mut game = Game::new(header.client_id);
+		game.set_memory_budget(self.budget);
+		if let Some(rate) = TickRate::new(header.tick_rate) {
+			game.tick_rate = rate;
+		}
+		game.seed = header.seed;
+		game.local_players = saved.local_players;
+		game.spectating = saved.spectating;
+		game.sim_config = saved.sim_config;
+		game.platforms = saved.platforms;
+		game.coins = saved.coins;
+		game.players = saved.players.into_iter().map(|x| (x.owner, x.player)).collect();
+		game.members = Membership::from_entries(&saved.members);
+		game.player_infos = saved.infos.into_iter().map(|x| (x.owner, x)).collect();
+		game.accepted_head = saved.accepted_head;
+		game.received_head = saved.received_head;
+		game.answered_head = saved.answered_head;
+		game.next_authoritative = saved.next_authoritative;
+
+		game.ticks.reset(saved.ticks.first().map_or(saved.next_index, |x| x.tick.index()));
+		// What each tick left behind is what the next one started out from,
+		// and the world as it is now for the last one.
+		let mut afters: Vec<PlayerStates> = saved.ticks
+			.iter()
+			.skip(1)
+			.map(|x| x.players.iter().map(|x| (x.owner, (&x.player).into())).collect())
+			.collect();
+		afters.push(game.player_states());
+		for (saved_tick, after) in saved.ticks.into_iter().zip(afters) {
+			game.ticks.push(TickRecord {
+				tick: saved_tick.tick,
+				players: saved_tick.players.into_iter().map(|x| (x.owner, x.player)).collect(),
+				coins: saved_tick.coins,
+				authoritative: saved_tick.authoritative,
+				suspect: saved_tick.suspect,
+				sent: None,
+				state_hash: saved_tick.state_hash,
+				after,
+				sent_at: None
+			});
+		}
+		for tick in saved.unplayed {
+			// Whatever doesn't fit gets sent again, or snapshotted over.
+			game.jitter_buffer.push(tick);
+		}
+
+		game.prediction = std::mem::replace(&mut self.prediction, Box::new(RepeatLast));
+		game.remote_presentation = match game.spectating {
+			true => RemotePresentation::Interpolated,
+			false => self.remote_presentation,
+		};
+		game.interp_margin = self.interp_margin;
+		game.redundant_frames = self.redundant_frames;
+		game.render_delay = self.render_delay;
+		game.tick_budget = self.tick_budget;
+		game.chat = std::mem::take(&mut self.chat);
+		// Whatever was predicted then might not be what the server got.
+		game.request_snapshot(SnapshotReason::Restored);
+		game.introduction_due = true;
+		let recording = self.recorder.is_some();
+		*self = game;
+		if recording {
+			self.start_recording();
+		}
+	}
+
+	/// Replaces the world with one from a replay, right before tick `next_index`.
+	pub(crate) fn restore_world(&mut self, next_index: u64, players: &[SnapshotEntry], platforms: &[Platform], coins: &Coins, members: &[MemberEntry]) {
+		self.platforms = platforms.to_vec();
+		self.coins = coins.clone();
+		self.players = players
+			.iter()
+			.map(|x| (x.owner, x.player.clone()))
+			.collect();
+		self.restore_members(members);
+		self.reset_history(next_index);
+		self.accepted_head = next_index.saturating_sub(1);
+		self.next_authoritative = next_index;
+	}
+
+	/// Starts the history over at `next_index`, dropping whatever was still
+	/// being replayed from the old one.
+	fn reset_history(&mut self, next_index: u64) {
+		self.ticks.reset(next_index);
+		self.pending_replay = None;
+		self.input_stats.clear();
+		self.presented_events.clear();
+		self.idle_ticks = 0;
+	}
+
+	pub(crate) fn restore_members(&mut self, members: &[MemberEntry]) {
+		self.members = Membership::from_entries(members);
+	}
+
+	/// Declares the connection lost once the accepted head stops advancing,
+	/// or the transport stops taking our packets. Once that happens, whatever the server still sends only tells us it's
+	/// back; our prediction history is too stale to be stitched onto its
+	/// timeline, so we ask for a snapshot instead of applying it.
+	fn update_connection(&mut self, now: f64) {
+		// There's no progress to be made while paused, or syncing the clock.
+		if self.paused || self.clock_sync.is_some() {
+			self.last_progress = None;
+			return;
+		}
+		#[cfg(feature = "client")]
+		if self.outbox.failing_since.is_some_and(|x| now - x > SEND_FAILURE_TIMEOUT) && self.connection.is_playing() {
+			crate::log!(LogLevel::Warn, "connection_lost tick={} reason=send_failed queued={}", self.accepted_head, self.outbox.queue.len());
+			self.set_connection_state(ConnectionState::Disconnected);
+			return;
+		}
+		// Waiting on a snapshot, nothing gets accepted; the server's ticks
+		// still coming in are enough to show it's there.
+		let head = match self.snapshot_request {
+			Some(_) => self.answered_head,
+			None => self.accepted_head,
+		};
+		match self.last_progress {
+			Some((last, _)) if last != head => {
+				self.last_progress = Some((head, now));
+			},
+			Some((_, since)) if now - since > CONNECTION_TIMEOUT && self.connection.is_playing() => {
+				crate::log!(LogLevel::Warn, "connection_lost tick={}", self.accepted_head);
+				self.set_connection_state(ConnectionState::Disconnected);
+			},
+			Some(_) => {},
+			None => self.last_progress = Some((head, now)),
+		}
+	}
+
+	/// Reacts to the transport dropping or reestablishing the connection.
+	#[cfg(feature = "client")]
+	pub fn handle_transport_event(&mut self, event: TransportEvent) {
+		match event {
+			// Still connecting, there's nothing to lose yet; the handshake
+			// goes on until it's answered.
+			TransportEvent::Disconnected if self.handshake.is_some() => {},
+			TransportEvent::Disconnected => {
+				self.set_connection_state(ConnectionState::Disconnected);
+			},
+			TransportEvent::Connected if self.connection == ConnectionState::Disconnected => {
+				crate::log!(LogLevel::Info, "resync tick={} reason=reconnected", self.accepted_head);
+				self.reconnect();
+				self.introduction_due = true;
+			},
+			TransportEvent::Connected => self.introduction_due = true,
+		}
+	}
+
+	/// Sends `bytes` through `transport`, unless packets it turned down
+	/// before are still waiting to go again, as they have to go first. If
+	/// it turns these down too, they're held on to for `resend`. So that we
+	/// don't predict into a black hole, a transport turning everything
+	/// down for `SEND_FAILURE_TIMEOUT` counts as losing the connection.
+	/// `now` is the current time, as per `get_time`.
+	#[cfg(feature = "client")]
+	pub fn send(&mut self, transport: &mut impl Transport, bytes: &[u8], now: f64) {
+		if !self.outbox.queue.is_empty() {
+			return self.outbox.hold(bytes.to_vec(), self.budget.resend_packets);
+		}
+		match transport.send(bytes) {
+			Ok(()) => self.outbox.sent(now),
+			Err(error) => {
+				if self.outbox.failed(error, now) {
+					self.outbox.hold(bytes.to_vec(), self.budget.resend_packets);
+				}
+			},
+		}
+	}
+
+	/// Sends again whatever the transport turned down before, oldest
+	/// first, up to the first one it turns down again. Meant to be called
+	/// once a frame, before anything new gets sent. Ticks the server got
+	/// past meanwhile, say through a snapshot, are left out, so that those
+	/// it's still missing go sooner.
+	#[cfg(feature = "client")]
+	pub fn resend(&mut self, transport: &mut impl Transport, now: f64) {
+		while let Some(bytes) = self.outbox.queue.pop_front() {
+			let Some(bytes) = without_received(bytes, self.received_head) else { continue };
+			match transport.send(&bytes) {
+				Ok(()) => self.outbox.sent(now),
+				Err(error) => {
+					if self.outbox.failed(error, now) {
+						self.outbox.queue.push_front(bytes);
+						break;
+					}
+				},
+			}
+		}
+	}
+
+	/// Flushes `transport`, which holds on to whatever it can't flush yet
+	/// itself; that still counts as it turning packets down, though.
+	#[cfg(feature = "client")]
+	pub fn flush(&mut self, transport: &mut impl Transport, now: f64) {
+		if let Err(error) = transport.flush() {
+			self.outbox.failed(error, now);
+		}
+	}
+
+	/// Packets the transport turned down, still waiting to go again.
+	#[cfg(feature = "client")]
+	pub fn resend_queue_len(&self) -> usize {
+		self.outbox.queue.len()
+	}
+
+	/// How many times the transport turned a packet down so far.
+	#[cfg(feature = "client")]
+	pub fn send_failures(&self) -> u32 {
+		self.outbox.failures
+	}
+
+	/// Whether the transport's been turning packets down since it last
+	/// took one.
+	#[cfg(feature = "client")]
+	fn is_send_failing(&self) -> bool {
+		self.outbox.failing_since.is_some()
+	}
+
+	#[cfg(not(feature = "client"))]
+	fn is_send_failing(&self) -> bool {
+		false
+	}
+
+	/// Gets going again, after `Disconnected`: our history is too stale to
+	/// stitch the server's timeline onto, so the clock gets synced again to
+	/// find out where it's at, and a snapshot gets asked for. Once turned
+	/// away, there's no coming back; nor is there while the transport's
+	/// still turning our packets down, whatever the server sends.
+	fn reconnect(&mut self) {
+		if self.rejection.is_some() || self.is_send_failing() || !self.set_connection_state(ConnectionState::Resyncing) {
+			return;
+		}
+		self.request_snapshot(SnapshotReason::Reconnect);
+		self.last_progress = None;
+		if !self.spectating {
+			self.clock_sync = Some(ClockSync::new(self.interp_margin));
+		}
+	}
+
+	/// `now` is the current time, as per `get_time`.
+	fn receive_confirmations(&mut self, now: f64) {
+		let confirmations: Vec<(u64, Hash)> = self.inbox.confirmations.drain(..).collect();
+		if self.connection == ConnectionState::Disconnected {
+			if !confirmations.is_empty() {
+				self.reconnect();
+			}
+			return;
+		}
+		for (tick_index, hash) in confirmations {
+			self.last_confirmation = Some(now);
+			self.answered_head = self.answered_head.max(tick_index);
+			self.sample_rtt(tick_index, now);
+			if !self.confirm(tick_index, &hash) && !self.confirm_sent(tick_index, &hash) {
+				if let Some(record) = self.ticks.get_mut(tick_index) {
+					record.suspect = true;
+				}
+			}
+		}
+	}
+
+	/// Measures how long the server took to answer the tick `tick_index`,
+	/// which it just did at `now`.
+	fn sample_rtt(&mut self, tick_index: u64, now: f64) {
+		let sent_at = self.ticks.get_mut(tick_index).and_then(|x| x.sent_at.take());
+		if let Some(sent_at) = sent_at {
+			self.rtt.update(now - sent_at);
+		}
+	}
+
+	/// Notes that the packet `run_tick` last returned went out at `now`, as
+	/// per `get_time`, so that the answer to it can be timed; see `rtt`.
+	pub fn tick_sent(&mut self, now: f64) {
+		if let Some(record) = self.ticks.last_mut() {
+			record.sent_at.get_or_insert(now);
+		}
+	}
+
+	/// How long the server takes to answer our ticks: with a receipt if it
+	/// sends those, or with a confirmation or correction otherwise.
+	pub fn rtt(&self) -> &RttEstimator {
+		&self.rtt
+	}
+
+	/// Takes in an authoritative tick, in whatever order it arrived. Ticks
+	/// ahead of the next expected one wait until the gap is filled.
+	fn reconcile(&mut self, tick: Tick<PlayerIntent>) {
+		self.skip_settled_ticks();
+		if tick.index() < self.next_authoritative {
+			// Retransmits of a tick we've already confirmed are harmless.
+			let duplicate = self.ticks
+				.get(tick.index())
+				.is_some_and(|x| x.authoritative && x.tick.hash() == tick.hash());
+			if !duplicate {
+				crate::log!(LogLevel::Debug, "tick_stale tick={} expected={}", tick.index(), self.next_authoritative);
+				self.stale_ticks += 1;
+			}
+			return;
+		}
+
+		if tick.index() > self.next_authoritative {
+			let full = self.pending_authoritative.len() >= self.budget.pending_ticks;
+			if full && !self.pending_authoritative.contains_key(&tick.index()) {
+				crate::log!(LogLevel::Warn, "tick_dropped tick={} expected={} reason=pending_full", tick.index(), self.next_authoritative);
+				self.stale_ticks += 1;
+				return;
+			}
+			self.pending_authoritative.insert(tick.index(), tick);
+			return;
+		}
+
+		self.reconcile_in_order(tick);
+		self.reconcile_pending();
+	}
+
+	/// Reconciles the pending ticks which are next in line.
+	fn reconcile_pending(&mut self) {
+		self.skip_settled_ticks();
+		while let Some(tick) = self.pending_authoritative.remove(&self.next_authoritative) {
+			self.reconcile_in_order(tick);
+			self.skip_settled_ticks();
+		}
+	}
+
+	/// Ticks which an ack already confirmed don't get sent in full, so
+	/// they shouldn't hold up the authoritative ticks coming after them.
+	/// Neither should those confirmed for long enough to be thrown away.
+	fn skip_settled_ticks(&mut self) {
+		while self.ticks.is_pruned(self.next_authoritative) || self.ticks.get(self.next_authoritative).is_some_and(|x| x.authoritative) {
+			self.next_authoritative += 1;
+		}
+	}
+
+	/// Compares an authoritative tick against our prediction for the same index.
+	fn reconcile_in_order(&mut self, tick: Tick<PlayerIntent>) {
+		self.next_authoritative = tick.index() + 1;
+		if self.spectating {
+			self.follow(tick);
+			return;
+		}
+		if !self.confirm(tick.index(), tick.hash()) {
+			// A tick which already got settled was counted back then.
+			if let Some(record) = self.ticks.get(tick.index()).filter(|x| !x.authoritative) {
+				*self.mispredictions.entry(self.prediction.name()).or_default() += 1;
+				let cause = CorrectionCause::between(&record.tick, &tick, &self.local_players);
+				self.prediction_stats.record_corrected(cause);
+			}
+			self.hold_correction(tick);
+		}
+	}
+
+	/// Keeps a correction around for `apply_corrections`. Past the budget,
+	/// the one furthest ahead is dropped instead, being the last one we'd
+	/// get to; should we have predicted that tick wrong, the state hashes
+	/// give it away later on.
+	fn hold_correction(&mut self, tick: Tick<PlayerIntent>) {
+		self.corrections.push(tick);
+		if self.corrections.len() <= self.budget.pending_corrections {
+			return;
+		}
+		let furthest = self.corrections
+			.iter()
+			.enumerate()
+			.max_by_key(|(_, x)| x.index())
+			.map(|(position, _)| position);
+		if let Some(position) = furthest {
+			let dropped = self.corrections.swap_remove(position);
+			crate::log!(LogLevel::Warn, "correction_dropped tick={} reason=corrections_full", dropped.index());
+			self.stale_ticks += 1;
+		}
+	}
+
+	/// Simulates an authoritative tick as is, for a spectator. Ticks have to
+	/// follow on from our history; if they don't, we need a fresh snapshot.
+	pub(crate) fn follow(&mut self, tick: Tick<PlayerIntent>) {
+		let index = tick.index();
+		if index != self.ticks.next_index() {
+			crate::log!(LogLevel::Warn, "resync tick={} expected={} reason=gap", index, self.ticks.next_index());
+			self.request_snapshot(SnapshotReason::Gap);
+			return;
+		}
+		self.advance(tick);
+		if let Some(record) = self.ticks.get_mut(index) {
+			record.authoritative = true;
+		}
+		self.accepted_head = index;
+		self.prune_history();
+	}
+
+	/// Accepts our prediction for `tick_index`, provided it hashes to `hash`.
+	/// Returns false if the tick is unknown to us, or was mispredicted.
+	fn confirm(&mut self, tick_index: u64, hash: &Hash) -> bool {
+		match self.ticks.get_mut(tick_index) {
+			Some(predicted) if predicted.tick.hash() == hash => {
+				// The same tick can be confirmed by both an ack and the
+				// server's copy of it; only the first one counts.
+				if !predicted.authoritative {
+					self.prediction_stats.record_confirmed();
+				}
+				predicted.authoritative = true;
+				self.accepted_head = self.accepted_head.max(tick_index);
+				self.prune_history();
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/// Takes an ack for a tick we predicted again since sending it as a
+	/// correction back to the tick as sent, which is what the server went
+	/// with. Returns false if we never sent anything hashing to `hash`.
+	fn confirm_sent(&mut self, tick_index: u64, hash: &Hash) -> bool {
+		let Some(record) = self.ticks.get_mut(tick_index).filter(|x| !x.authoritative) else { return false };
+		let Some(sent) = record.sent.take_if(|x| x.hash() == hash) else { return false };
+		self.prediction_stats.record_confirmed();
+		self.hold_correction(sent);
+		true
+	}
+
+	/// Drops ticks which fell out of the retention window, recording every
+	/// newly confirmed tick first, if we're recording. Both stop short of
+	/// ticks whose correction is still waiting to be played back, which
+	/// the jitter buffer can hold on to for longer than the window lasts.
+	fn prune_history(&mut self) {
+		let settled_head = self.settled_head();
+		if let Some(recorder) = &mut self.recorder {
+			recorder.members(Membership::entries(&self.members));
+			while recorder.next_index <= settled_head {
+				match self.ticks.get(recorder.next_index) {
+					Some(record) => recorder.tick(&record.tick),
+					None => break,
+				}
+			}
+		}
+		self.ticks.prune(settled_head);
+		self.input_stats.prune((settled_head + 1).saturating_sub(self.tick_rate.hz() as u64));
+		self.presented_events.retain(|x| x.tick > settled_head);
+	}
+
+	/// The accepted head, or the tick before the earliest authoritative
+	/// one we have yet to play back, if that's further behind. An ack can
+	/// confirm a tick while a correction for an earlier one is still on
+	/// its way through the jitter buffer, by which the ticks after it
+	/// could still change. So can those a split replay has yet to get to.
+	fn settled_head(&self) -> u64 {
+		let earliest = self.jitter_buffer.queue
+			.iter()
+			.chain(self.pending_authoritative.values())
+			.chain(&self.corrections)
+			.map(|x| x.index())
+			.chain(self.pending_replay.as_ref().map(|x| x.next_index))
+			.min();
+		match earliest {
+			Some(index) => self.accepted_head.min(index.saturating_sub(1)),
+			None => self.accepted_head,
+		}
+	}
+
+	/// How many ticks the local simulation is ahead of the last confirmed one.
+	pub fn ticks_ahead(&self) -> u64 {
+		self.ticks.next_index().saturating_sub(self.accepted_head + 1)
+	}
+
+	/// Replaces mispredicted ticks with the server's versions, then rolls back
+	/// to the earliest of them so that the world reflects the corrected history.
+	fn apply_corrections(&mut self) {
+		let mut corrections = std::mem::take(&mut self.corrections);
+		corrections.sort_by_key(|x| x.index());
+
+		let mut earliest = None;
+		for correction in corrections {
+			let index = correction.index();
+
+			// We haven't predicted this tick yet; keep it around until we do.
+			if index >= self.ticks.next_index() {
+				self.corrections.push(correction);
+				continue;
+			}
+
+			// We can't rewind past what we've stored, so we need a fresh state.
+			if self.ticks.is_pruned(index) {
+				crate::log!(LogLevel::Warn, "resync tick={} reason=pruned", index);
+				self.request_snapshot(SnapshotReason::Pruned);
+				continue;
+			}
+
+			// The tick has already been confirmed, there's nothing to repair.
+			// An ack for a later tick can come in ahead of a correction still
+			// in the jitter buffer, so it's the tick itself that has to be.
+			if self.ticks.get(index).is_some_and(|x| x.authoritative) {
+				continue;
+			}
+
+			if let Some(record) = self.ticks.get_mut(index) {
+				record.tick = correction;
+				record.authoritative = true;
+				record.suspect = false;
+			}
+			self.accepted_head = self.accepted_head.max(index);
+			earliest = Some(earliest.unwrap_or(index).min(index));
+		}
+
+		if let Some(index) = earliest {
+			self.rollback_to(index);
+			self.prune_history();
+		}
+	}
+
+	/// Restores the world to how it was right before `tick_index`, and replays
+	/// every tick from there on. Ticks we merely predicted get their remote frames
+	/// predicted again, but the local players' own frames are always kept.
+	/// Past the tick budget, the rest of the replay waits for the next frame;
+	/// see `continue_replay`.
+	fn rollback_to(&mut self, tick_index: u64) {
+		// A replay that hasn't gotten this far yet picks the tick up anyway.
+		if self.pending_replay.as_ref().is_some_and(|x| x.next_index <= tick_index) {
+			return;
+		}
+		let Some(record) = self.ticks.get(tick_index) else { return };
+		crate::log!(LogLevel::Debug, "rollback tick={} replayed={}", tick_index, self.ticks.next_index() - tick_index);
+		let (players, coins) = (record.players.clone(), record.coins.clone());
+		self.input_stats.rewind(tick_index);
+
+		// The simulation jumps to the corrected state once the replay's
+		// through, but players keep being drawn where they were and only
+		// glide over to it. A replay going back further than one underway
+		// still has them drawn where they were before that one.
+		let (rendered, split) = match self.pending_replay.take() {
+			Some(replay) => (replay.rendered, replay.split),
+			None => {
+				let rendered = self.players
+					.keys()
+					.filter_map(|client_id| Some((*client_id, self.presented_position(*client_id, true)?)))
+					.collect();
+				(rendered, false)
+			},
+		};
+		self.pending_replay = Some(PendingReplay {
+			start: tick_index,
+			next_index: tick_index,
+			players,
+			coins,
+			rendered,
+			split
+		});
+		self.continue_replay(true);
+	}
+
+	/// Replays what's left of the pending rollback, if there is one, for
+	/// as long as the tick budget lasts, unless `budgeted` is false; a tick
+	/// at least, so that it gets through eventually. Once it's through,
+	/// the world it rebuilt takes over from the one drawn until then.
+	fn continue_replay(&mut self, budgeted: bool) {
+		let Some(mut replay) = self.pending_replay.take() else { return };
+		std::mem::swap(&mut self.players, &mut replay.players);
+		std::mem::swap(&mut self.coins, &mut replay.coins);
+
+		let mut through = true;
+		let mut replayed = 0;
+		while replay.next_index < self.ticks.next_index() {
+			if budgeted && replayed > 0 && self.is_over_budget() {
+				through = false;
+				break;
+			}
+			if !self.replay_tick(replay.start, replay.next_index) {
+				break;
+			}
+			replay.next_index += 1;
+			replayed += 1;
+		}
+
+		std::mem::swap(&mut self.players, &mut replay.players);
+		std::mem::swap(&mut self.coins, &mut replay.coins);
+		if !through {
+			if !replay.split {
+				crate::log!(LogLevel::Debug, "rollback_split tick={} replayed={}", replay.start, replay.next_index - replay.start);
+				self.split_replays += 1;
+			}
+			replay.split = true;
+			self.pending_replay = Some(replay);
+			return;
+		}
+
+		self.players = replay.players;
+		self.coins = replay.coins;
+		for (client_id, rendered) in replay.rendered {
+			let Some(position) = self.presented_position(client_id, false) else { continue };
+			if let Some(player) = self.players.get_mut(&client_id) {
+				player.smooth_correction(rendered, position);
+			}
+		}
+	}
+
+	/// Simulates tick `index` again, as part of a rollback to `start`, on
+	/// the world as it was right before it. A tick we merely predicted keeps
+	/// the local players' frames it was sent with, rather than taking input
+	/// again. Returns false if the tick isn't in the history.
+	fn replay_tick(&mut self, start: u64, index: u64) -> bool {
+		let Some(record) = self.ticks.get(index) else { return false };
+
+		let tick = if index > start && !record.authoritative {
+			let local_frames: Vec<CommandFrame<PlayerIntent>> = record.tick
+				.command_frames()
+				.iter()
+				.filter(|x| self.local_players.contains(&x.owner))
+				.cloned()
+				.collect();
+
+			let mut frames = self.anticipate_frames(index);
+			frames.extend(local_frames);
+			let tick = Tick::canonical(index, frames);
+			// Our own frames went out as they were, and are facts as far as
+			// the server's concerned; only the remote ones are guesses.
+			debug_assert_eq!(
+				self.local_frame_bytes(&tick),
+				self.local_frame_bytes(record.sent.as_ref().unwrap_or(&record.tick)),
+				"tick {} got replayed with other local frames than were sent",
+				index
+			);
+			tick
+		} else {
+			record.tick.clone()
+		};
+
+		let players = self.players.clone();
+		let coins = self.coins.clone();
+		self.simulate(&tick);
+		let state_hash = self.state_hash();
+		let after = self.player_states();
+		if let Some(record) = self.ticks.get_mut(index) {
+			if !record.authoritative && record.sent.is_none() && record.tick.hash() != tick.hash() {
+				record.sent = Some(record.tick.clone());
+			}
+			record.tick = tick;
+			record.players = players;
+			record.coins = coins;
+			record.state_hash = state_hash;
+			record.after = after;
+		}
+		true
+	}
+
+	/// The local players' frames in `tick`, as they go over the wire.
+	fn local_frame_bytes(&self, tick: &Tick<PlayerIntent>) -> Vec<Vec<u8>> {
+		tick.command_frames()
+			.iter()
+			.filter(|x| self.local_players.contains(&x.owner))
+			.map(|x| {
+				let mut writer = ByteWriter::new();
+				let _ = x.to_bytes(&mut writer);
+				writer.as_slice().to_vec()
+			})
+			.collect()
+	}
+
+	/// Simulates a tick, and stores it alongside the state it was applied to.
+	pub fn advance(&mut self, tick: Tick<PlayerIntent>) {
+		// Whatever the tick goes on from has to be there first.
+		self.continue_replay(false);
+		let players = self.players.clone();
+		let coins = self.coins.clone();
+		self.simulate(&tick);
+		self.ticks.push(TickRecord {
+			tick,
+			players,
+			coins,
+			authoritative: false,
+			suspect: false,
+			sent: None,
+			state_hash: self.state_hash(),
+			after: self.player_states(),
+			sent_at: None,
+		});
+	}
+
+	fn player_states(&self) -> PlayerStates {
+		self.players
+			.iter()
+			.map(|(client_id, player)| (*client_id, player.into()))
+			.collect()
+	}
+
+	/// Where `client_id` was right after tick `tick_index`, and how it was
+	/// moving; `None` if it wasn't in the game then. Looked up in the tick
+	/// history, for as long as the tick is kept there; after that, only a
+	/// recording lets `replay_range` work it out again.
+	pub fn player_state_at(&self, client_id: ClientId, tick_index: u64) -> Option<PlayerStateSnapshot> {
+		match self.ticks.get(tick_index) {
+			Some(record) => record.after.get(&client_id).copied(),
+			None => self.replay_range(tick_index, tick_index)
+				.first()
+				.and_then(|(_, states)| states.get(&client_id).copied()),
+		}
+	}
+
+	/// The world as of the accepted head, if the server confirmed that tick,
+	/// and there's no correction for an earlier one still to be played back.
+	/// Only ever made of confirmed ticks, so it never changes once handed
+	/// out, however many rollbacks follow.
+	pub fn state_summary(&self) -> Option<StateSummary> {
+		if self.settled_head() < self.accepted_head {
+			return None;
+		}
+		let record = self.ticks.get(self.accepted_head).filter(|x| x.authoritative)?;
+		let coins = match self.ticks.get(self.accepted_head + 1) {
+			Some(next) => &next.coins,
+			None => &self.coins,
+		};
+		let players = record.after
+			.iter()
+			.map(|(owner, state)| PlayerSummary {
+				owner: *owner,
+				x: scalar_to_f32(state.x),
+				y: scalar_to_f32(state.y),
+				score: coins.score(*owner)
+			})
+			.collect();
+		Some(StateSummary { tick: self.accepted_head, players })
+	}
+
+	/// Hands out a state summary whenever the accepted head got another
+	/// `observer_interval` ticks further, for the host to keep track of
+	/// the game with. Meant to be called between frames, where nothing's
+	/// simulated halfway.
+	pub fn take_state_summary(&mut self) -> Option<StateSummary> {
+		if self.observer_interval == 0 {
+			return None;
+		}
+		let due = self.last_observed.map_or(0, |x| x + self.observer_interval as u64);
+		if self.accepted_head < due {
+			return None;
+		}
+		let summary = self.state_summary()?;
+		self.last_observed = Some(summary.tick);
+		Some(summary)
+	}
+
+	pub fn observer_interval(&self) -> u32 {
+		self.observer_interval
+	}
+
+	/// Hands out a state summary every `ticks` confirmed ticks from now
+	/// on, or none at all for zero; see `take_state_summary`.
+	pub fn set_observer_interval(&mut self, ticks: u32) {
+		self.observer_interval = ticks;
+	}
+
+	/// Every player's state after each tick from `from` up to and including
+	/// `to`, worked out again by simulating the ticks from the nearest world
+	/// we have before them, rather than looked up. Ticks still in the
+	/// history start from the world they were simulated on; older ones are
+	/// played back from the recording, if there is one, and left out if
+	/// there isn't. Ticks that don't exist yet are left out too.
+	///
+	/// Meant for debugging and lag compensation experiments; playing back
+	/// the recording is anything but cheap.
+	pub fn replay_range(&self, from: u64, to: u64) -> Vec<(u64, PlayerStates)> {
+		let mut states = vec![];
+		let first_kept = self.ticks.records.front().map_or(self.ticks.next_index(), |x| x.tick.index());
+		if from < first_kept {
+			let last = to.min(first_kept - 1);
+			if let Some(replay) = self.export_replay().and_then(|x| Replay::parse(&x).ok()) {
+				let mut playback = Playback::new(replay);
+				while playback.step() {
+					let Some(index) = playback.tick_index().filter(|x| *x <= last) else { break };
+					if index >= from {
+						states.push((index, playback.game().player_states()));
+					}
+				}
+			}
+		}
+
+		let mut scratch: Option<Game> = None;
+		for record in self.ticks.records.iter().filter(|x| (from..=to).contains(&x.tick.index())) {
+			let scratch = scratch.get_or_insert_with(|| {
+				let mut scratch = Game::spectator(self.client_id);
+				scratch.tick_rate = self.tick_rate;
+				scratch.seed = self.seed;
+				scratch.sim_config = self.sim_config;
+				scratch.platforms = self.platforms.clone();
+				scratch.members = self.members.clone();
+				scratch.players = record.players.clone();
+				scratch.coins = record.coins.clone();
+				scratch
+			});
+			scratch.simulate(&record.tick);
+			states.push((record.tick.index(), scratch.player_states()));
+		}
+		states
+	}
+
+	/// Hashes the world as it is after the latest simulated tick: the sim
+	/// config first, then, in ClientId order, where every player is, how
+	/// fast it's moving and whether it's on the ground, then the coins lying
+	/// around and everyone's score. Tick hashes only cover the inputs, so
+	/// this is what catches the simulation itself drifting apart between two
+	/// clients.
+	pub fn state_hash(&self) -> Hash {
+		let mut hasher = Hasher::default();
+		self.sim_config.update_hasher(&mut hasher);
+		hasher.update(&(self.players.len() as u64).to_le_bytes());
+		for (client_id, player) in &self.players {
+			hasher.update(&client_id.to_le_bytes());
+			player.update_hasher(&mut hasher);
+		}
+		self.coins.update_hasher(&mut hasher);
+		hasher.finish()
+	}
+
+	/// The state hash right after `tick_index` got simulated, as long as we
+	/// still have the tick.
+	pub fn state_hash_at(&self, tick_index: u64) -> Option<Hash> {
+		self.ticks.get(tick_index).map(|x| x.state_hash)
+	}
+
+	/// The tick our world was first found to differ from the server's at,
+	/// if it did since the last snapshot.
+	pub fn desync(&self) -> Option<u64> {
+		self.desync
+	}
+
+	pub(crate) fn simulate(&mut self, tick: &Tick<PlayerIntent>) {
+		// Players come and go on tick boundaries, so that replaying a tick
+		// after a rollback treats them exactly the same way.
+		self.players.retain(|client_id, _| self.members.get(client_id).is_some_and(|x| x.active_at(tick.index())));
+		let mut context = SimContext::new(self.seed, tick.index(), &self.tick_rate, &self.sim_config);
+		for (client_id, membership) in &self.members {
+			if membership.active_at(tick.index()) {
+				let spawned = || Player::spawn(*client_id, &mut context.player_rng(*client_id), &self.sim_config);
+				self.players.entry(*client_id).or_insert_with(spawned);
+			}
+		}
+
+		for player in self.players.values_mut() {
+			player.snapshot_position();
+		}
+		self.coins.spawn(&mut context, &self.platforms);
+
+		self.input_stats.begin_tick(tick.index());
+		for frame in tick.command_frames() {
+			// Frames from clients that haven't joined, or have already left.
+			let Some(player) = self.players.get_mut(&frame.owner) else { continue };
+			for intent in &frame.intents {
+				player.execute_intent(intent, frame.offset(), &mut context);
+			}
+			self.input_stats.record(tick.index(), frame.owner, &frame.intents);
+		}
+
+		let mut events = vec![];
+		for (client_id, player) in &mut self.players {
+			player.update_physics(*client_id, &self.tick_rate, &self.sim_config, &self.platforms, &mut events);
+		}
+
+		self.resolve_collisions();
+		self.coins.collect(&self.players, &self.sim_config);
+		self.record_sim_events(tick.index(), events);
+	}
+
+	/// Keeps the events of tick `index` for the presentation layer, in place
+	/// of those from simulating it before, which the presentation layer has
+	/// yet to take. Those it took already aren't brought up again.
+	fn record_sim_events(&mut self, index: u64, events: Vec<SimEvent>) {
+		self.sim_events.retain(|x| x.tick != index);
+		let at = self.sim_events.partition_point(|x| x.tick < index);
+		for event in events.into_iter().rev() {
+			let event = TickEvent { tick: index, event };
+			if !self.presented_events.contains(&event) {
+				self.sim_events.insert(at, event);
+			}
+		}
+		while self.sim_events.len() > self.budget.sim_events {
+			self.sim_events.pop_front();
+		}
+	}
+
+	/// The events of every tick simulated since last time, in tick order,
+	/// for playing sounds and effects for. Replaying a tick after a
+	/// rollback only brings up the events it didn't come out with before,
+	/// if those were taken already.
+	pub fn take_sim_events(&mut self) -> Vec<TickEvent> {
+		let events: Vec<TickEvent> = self.sim_events.drain(..).collect();
+		self.presented_events.extend(&events);
+		events
+	}
+
+	/// Collisions depend on the order they're resolved in, so pairs are
+	/// always visited in ascending ClientId order; otherwise every client
+	/// could come up with a different outcome for the same tick.
+	fn resolve_collisions(&mut self) {
+		let ids: Vec<ClientId> = self.players.keys().copied().collect();
+		for id in ids {
+			let Some(mut first) = self.players.remove(&id) else { continue };
+			for second in self.players.range_mut(id..).map(|(_, x)| x) {
+				first.resolve_collision(second, &self.sim_config);
+			}
+			self.players.insert(id, first);
+		}
+	}
+
+	/// Sets how late into the next predicted tick the input of `owner` came
+	/// in, in 256ths of a tick; see `CommandFrame::offset`. Like queued
+	/// intents, it's held while prediction is stalled, and only ever for
+	/// local players.
+	pub fn set_input_offset(&mut self, owner: ClientId, offset: u8) {
+		if self.local_players.contains(&owner) {
+			self.input_offsets.insert(owner, offset);
+		}
+	}
+
+	/// Adds to the intents of `owner` for the next predicted tick, on top of
+	/// whatever input the tick gets predicted with. Lets bots and scripts
+	/// drive a player. The intents are held for as long as prediction is
+	/// stalled, and cleared once a tick has been predicted with them.
+	///
+	/// Only the local players' frames are ours to make up, so intents for
+	/// anyone else are ignored.
+	pub fn queue_intents(&mut self, owner: ClientId, intents: Vec<PlayerIntent>) {
+		if !self.local_players.contains(&owner) {
+			return;
+		}
+		let queued = self.queued_intents.entry(owner).or_default();
+		for intent in intents {
+			if !queued.contains(&intent) {
+				queued.push(intent);
+			}
+		}
+	}
+
+	/// Predicts the next tick, with `intents` and anything queued through
+	/// `queue_intents` as the local player's. Other local players only get
+	/// what's been queued for them. The frames are put in the server's
+	/// order, see `Tick::canonical`, so that a right guess hashes the same.
+	pub fn predict_tick(&mut self, intents: Vec<PlayerIntent>) -> Tick<PlayerIntent> {
+		let mut queued = std::mem::take(&mut self.queued_intents);
+		let mut offsets = std::mem::take(&mut self.input_offsets);
+		let mut intents = Some(intents);
+		let local_frames: Vec<CommandFrame<PlayerIntent>> = self.local_players
+			.iter()
+			.map(|owner| {
+				let mut frame_intents = match *owner == self.client_id {
+					true => intents.take().unwrap_or_default(),
+					false => vec![],
+				};
+				frame_intents.extend(queued.remove(owner).unwrap_or_default());
+				// The server normalizes frames the same way, so doing it here
+				// keeps conflicting input from showing up as a misprediction.
+				let offset = offsets.remove(owner).unwrap_or_default();
+				let mut frame = CommandFrame::new(*owner, frame_intents).with_offset(offset);
+				frame.normalize();
+				frame
+			})
+			.collect();
+
+		// Nothing to go on but ourselves, at the start, or after a snapshot.
+		let Some(previous_tick) = self.ticks.last().map(|x| &x.tick) else {
+			return Tick::canonical(self.ticks.next_index(), local_frames);
+		};
+
+		let index = previous_tick.index() + 1;
+		let mut anticipated_frames = self.anticipate_frames(index);
+		anticipated_frames.extend(local_frames);
+
+		Tick::canonical(index, anticipated_frames)
+	}
+
+	/// Every local player's frames for the `redundant_frames` ticks before
+	/// `tick_index`, newest first, up to `MAX_PAST_FRAMES` of them. Those of
+	/// the ticks before `received_head` are left out, as the server got them
+	/// already, along with that tick if not before.
+	fn past_frames(&self, tick_index: u64) -> Vec<PastFrame<PlayerIntent>> {
+		(1..=self.redundant_frames)
+			.filter_map(|age| {
+				let index = tick_index.checked_sub(age as u64).filter(|x| *x >= self.received_head)?;
+				Some((age, self.ticks.get(index)?))
+			})
+			.flat_map(|(age, record)| {
+				self.local_players.iter().filter_map(move |owner| {
+					let command_frame = record.tick.frame_of(*owner)?;
+					Some(PastFrame {
+						owner: *owner,
+						age,
+						intents: command_frame.intents.clone(),
+						offset: command_frame.offset
+					})
+				})
+			})
+			.take(MAX_PAST_FRAMES)
+			.collect()
+	}
+
+	/// Predicts the remote players' frames for `tick_index`, off the ticks before it.
+	fn anticipate_frames(&self, tick_index: u64) -> Vec<CommandFrame<PlayerIntent>> {
+		let history = self.ticks.ticks_before(tick_index);
+		let context = self.prediction_context(tick_index);
+		self.prediction.predict_with_context(&history, &self.local_players, &context)
+	}
+
+	/// How the remote players moved during the last confirmed tick before
+	/// `tick_index`, and when their frames were last confirmed. Nothing's
+	/// known once that's been pruned.
+	fn prediction_context(&self, tick_index: u64) -> PredictionContext {
+		let confirmed: Vec<&TickRecord<PlayerIntent>> = (0..tick_index)
+			.rev()
+			.take_while(|x| !self.ticks.is_pruned(*x))
+			.filter_map(|x| self.ticks.get(x).filter(|x| x.authoritative))
+			.collect();
+		let Some(latest) = confirmed.first() else {
+			return PredictionContext::default();
+		};
+
+		let motion = latest.after
+			.iter()
+			.filter(|(client_id, _)| !self.local_players.contains(client_id))
+			.map(|(client_id, after)| {
+				let top_speed = scalar_to_f32(self.sim_config.move_speed);
+				let velocity = latest.players
+					.get(client_id)
+					.map_or(0.0, |before| scalar_to_f32(after.x - before.x) / self.tick_rate.delta() / top_speed);
+				// Frames gone missing for longer than we remember count as
+				// missing since the very start.
+				let last_seen = confirmed
+					.iter()
+					.find(|x| x.tick.frame_of(*client_id).is_some())
+					.map_or(0, |x| x.tick.index());
+				(*client_id, RemoteMotion { velocity, last_seen })
+			})
+			.collect();
+		PredictionContext { motion }
+	}
+
+	/// The world as it was right after `tick_index` got simulated.
+	pub fn world_after(&self, tick_index: u64) -> Option<&BTreeMap<ClientId, Player>> {
+		match self.ticks.get(tick_index + 1) {
+			Some(record) => Some(&record.players),
+			None if tick_index + 1 == self.ticks.next_index() => Some(&self.players),
+			None => None,
+		}
+	}
+
+	/// Replaces how remote players' frames are predicted from now on. Ticks
+	/// which were already predicted are left as they are.
+	pub fn set_prediction_strategy(&mut self, strategy: Box<dyn PredictionStrategy<PlayerIntent>>) {
+		self.prediction = strategy;
+	}
+
+	pub fn prediction_strategy(&self) -> &dyn PredictionStrategy<PlayerIntent> {
+		self.prediction.as_ref()
+	}
+
+	/// Switches to the next of the built-in strategies. A custom one is
+	/// followed by the first of them.
+	pub fn cycle_prediction_strategy(&mut self) {
+		let mut strategies = prediction::builtin();
+		let current = strategies.iter().position(|x| x.name() == self.prediction.name());
+		let next = current.map_or(0, |x| (x + 1) % strategies.len());
+		self.prediction = strategies.swap_remove(next);
+	}
+
+	/// How many predictions made with the strategy called `name` turned out wrong.
+	pub fn mispredictions(&self, name: &str) -> u32 {
+		self.mispredictions.get(name).copied().unwrap_or(0)
+	}
+
+	/// How well our predictions held up so far, whichever strategy made them.
+	pub fn prediction_stats(&self) -> &PredictionStats {
+		&self.prediction_stats
+	}
+
+	/// What everyone's frames held, as applied by the simulation, for the
+	/// last second of settled ticks and every tick predicted since.
+	pub fn input_stats(&self) -> &InputStats {
+		&self.input_stats
+	}
+
+	/// Shows or hides the intents every player had applied, next to them;
+	/// see `draw_inputs`.
+	pub fn toggle_input_display(&mut self) {
+		self.show_inputs = !self.show_inputs;
+	}
+
+	/// How many ticks behind remote players are drawn; see `render_tick`.
+	pub fn render_delay(&self) -> u8 {
+		self.render_delay
+	}
+
+	/// Draws remote players `ticks` behind, up to `MAX_RENDER_DELAY`. The
+	/// further behind, the more of the server's corrections come in before
+	/// the tick they're for gets drawn, rather than after.
+	pub fn set_render_delay(&mut self, ticks: u8) {
+		self.render_delay = ticks.min(MAX_RENDER_DELAY);
+	}
+
+	/// The tick remote players are drawn as of: `render_delay` ticks before
+	/// the latest one, or before the accepted head when they're drawn from
+	/// confirmed ticks only. Never before the oldest tick still stored,
+	/// however long the delay.
+	pub fn render_tick(&self) -> Option<u64> {
+		let newest = match self.remote_presentation {
+			RemotePresentation::Predicted => self.ticks.last()?.tick.index(),
+			RemotePresentation::Interpolated => self.accepted_head,
+		};
+		// The world after the tick before the oldest is that tick's own.
+		let oldest = self.ticks.first()?.tick.index().saturating_sub(1);
+		Some(newest.saturating_sub(self.render_delay as u64).max(oldest))
+	}
+
+	/// Where the remote player `client_id` is drawn, `smoothing` of the way
+	/// from the world before the render tick to the world after it, leaving
+	/// out the visual error; see `render_tick`. `None` if they weren't in
+	/// the game then.
+	pub fn remote_position(&self, client_id: ClientId, smoothing: f32) -> Option<(f32, f32)> {
+		let tick = self.render_tick()?;
+		let to = self.world_after(tick)?.get(&client_id)?;
+		let from = tick.checked_sub(1).and_then(|x| self.world_after(x)).and_then(|x| x.get(&client_id));
+		Some(to.position_from(from.unwrap_or(to), smoothing))
+	}
+
+	/// Where `client_id` is drawn at the end of a tick, visual error
+	/// included if `with_error`: as of the latest tick for local players,
+	/// and as of the render tick for remote ones.
+	fn presented_position(&self, client_id: ClientId, with_error: bool) -> Option<(f32, f32)> {
+		let player = self.players.get(&client_id)?;
+		let (x, y) = match self.local_players.contains(&client_id) {
+			true => (scalar_to_f32(player.x), scalar_to_f32(player.y)),
+			false => self.remote_position(client_id, 1.0)?,
+		};
+		let (error_x, error_y) = if with_error { player.error_offset() } else { (0.0, 0.0) };
+		Some((x + error_x, y + error_y))
+	}
+
+	pub fn toggle_remote_presentation(&mut self) {
+		self.remote_presentation = match self.remote_presentation {
+			RemotePresentation::Predicted => RemotePresentation::Interpolated,
+			RemotePresentation::Interpolated => RemotePresentation::Predicted,
+		};
+	}
+
+	/// Fills in the game's part of the debug overlay's stats; see `DebugStats`.
+	/// `now` is the current time, as per `get_time`.
+	#[cfg(feature = "client")]
+	pub fn update_debug_stats(&self, stats: &mut DebugStats, now: f64) {
+		stats.local_players = self.local_players.clone();
+		stats.spectating = self.spectating;
+		stats.connection_state = Some(self.connection);
+		stats.local_tick = self.ticks.last().map(|x| x.tick.index());
+		stats.received_head = self.received_head;
+		stats.accepted_head = self.accepted_head;
+		stats.ticks_ahead = self.ticks_ahead();
+		stats.target_ticks_ahead = self.target_ticks_ahead();
+		stats.tick_speed = self.tick_speed();
+		stats.rtt = self.rtt.smoothed().map(|x| (x * 1000.0, self.rtt.jitter() * 1000.0));
+		stats.stored_ticks = self.ticks.iter().count();
+		stats.retention = TICK_RETENTION;
+		stats.since_confirmation = self.last_confirmation.map(|x| now - x);
+		stats.suspect_ticks = self.ticks.iter().filter(|x| x.suspect).count();
+		stats.stale_ticks = self.stale_ticks;
+		stats.rejected_frames = self.rejected_frames;
+		stats.resend_queue = self.outbox.queue.len();
+		stats.send_failures = self.outbox.failures;
+		stats.desync = self.desync;
+		stats.resync_requested = self.snapshot_request.is_some();
+		stats.snapshot_attempts = self.snapshot_request.map_or(0, |x| x.attempts);
+		stats.last_snapshot_request = self.last_snapshot_request.map(|(reason, at)| (reason, now - at));
+		stats.last_decode_error = self.last_decode_error.clone();
+		stats.remote_presentation = match self.remote_presentation {
+			RemotePresentation::Predicted => "predicted",
+			RemotePresentation::Interpolated => "interpolated",
+		};
+		stats.render_delay = self.render_delay;
+		stats.prediction = self.prediction.name();
+		stats.mispredictions = self.mispredictions(self.prediction.name());
+		stats.misprediction_rate = self.prediction_stats.rolling_misprediction_rate();
+		stats.redundant_frames = self.redundant_frames as usize;
+		stats.skipped_ticks = self.skipped_ticks;
+		stats.budget_overruns = self.budget_overruns;
+		stats.split_replays = self.split_replays;
+		stats.replaying = self.pending_replay.as_ref().map(|x| (x.next_index, self.ticks.next_index()));
+		stats.throttled_at = self.throttled.then_some(MAX_TICKS_AHEAD);
+		stats.send_rate = self.send_rate();
+		stats.idle = self.is_idle();
+		stats.jitter_depth = self.jitter_buffer.queue.len();
+		stats.jitter_target = self.jitter_buffer.target_depth;
+		stats.jitter_history = self.jitter_buffer.history.iter().copied().collect();
+		stats.memory = self.memory_usage();
+		let latest = self.ticks.last().filter(|x| !x.authoritative).map(|x| x.tick.index());
+		let context = latest.map(|x| self.prediction_context(x)).unwrap_or_default();
+		stats.players = self.players
+			.iter()
+			.map(|(client_id, player)| {
+				let (x, y) = player.position();
+				let (horizontal, vertical) = player.velocity();
+				PlayerStats {
+					client_id: *client_id,
+					local: self.local_players.contains(client_id),
+					position: (scalar_to_f32(x), scalar_to_f32(y)),
+					velocity: (scalar_to_f32(horizontal), scalar_to_f32(vertical)),
+					grounded: player.is_grounded(),
+					visual_error: player.error(),
+					intents_per_second: self.input_stats.per_second(*client_id, self.accepted_head, self.tick_rate.hz()),
+					confirmed_intents: self.input_stats
+						.intents_at(self.accepted_head, *client_id)
+						.map(IntentSummary::of)
+						.unwrap_or_default(),
+					extrapolating: latest.is_some_and(|x| self.prediction.is_extrapolating(*client_id, x, &context))
+				}
+			})
+			.collect();
+	}
+
+	/// Draws whatever needs the player's attention, like the simulation being
+	/// stuck, regardless of whether the debug overlay is shown. Anything but
+	/// being `Connected` gets a banner, so that a frozen world never goes
+	/// unexplained.
+	#[cfg(feature = "client")]
+	pub fn draw_status(&self) {
+		let centered = |text: &str, font_size: u16, y: f32| {
+			let size = measure_text(text, None, font_size, 1.0);
+			draw_text(text, (SCREEN_SIZE as f32 - size.width) / 2.0, y, font_size as f32, RED);
+		};
+
+		if self.spectating && self.connection == ConnectionState::Connected {
+			centered("Spectating, press Enter to join", 16, SCREEN_SIZE as f32 - 24.0);
+		}
+
+		let middle = SCREEN_SIZE as f32 / 2.0;
+		if let Some(server) = self.tick_rate_mismatch {
+			centered("TICK RATE MISMATCH", 32, middle);
+			let details = format!("server runs at {} Hz, we run at {} Hz", server, self.tick_rate.hz);
+			centered(&details, 16, middle + 20.0);
+			return;
+		}
+		if self.sim_config_mismatch.is_some() {
+			centered("WORLD MISMATCH", 32, middle);
+			centered("the server simulates a different world", 16, middle + 20.0);
+			return;
+		}
+		if self.paused {
+			centered("PAUSED", 32, middle);
+			return;
+		}
+		match self.connection {
+			ConnectionState::Connecting => centered("Connecting...", 16, middle),
+			ConnectionState::Syncing => centered("Syncing with the server...", 16, middle),
+			ConnectionState::Connected => {},
+			ConnectionState::Resyncing => {
+				centered("RESYNCING", 32, middle);
+				let details = match self.clock_sync {
+					Some(_) => "catching up with the server",
+					None => "waiting for a snapshot",
+				};
+				centered(details, 16, middle + 20.0);
+			},
+			ConnectionState::Desynced => {
+				centered("DESYNCED", 32, middle);
+				centered("our world drifted off the server's", 16, middle + 20.0);
+			},
+			ConnectionState::Disconnected => match self.rejection {
+				Some(reason) => {
+					centered("CONNECTION REJECTED", 32, middle);
+					centered(&reason.to_string(), 16, middle + 20.0);
+				},
+				None => centered("CONNECTION LOST", 32, middle),
+			},
+		}
+	}
+
+	/// Draws the last few chat messages, in screen space, clear of the
+	/// status line and the message being typed.
+	#[cfg(feature = "client")]
+	pub fn draw_chat(&self) {
+		self.chat.draw(SCREEN_SIZE as f32 - 32.0);
+	}
+
+	/// Draws the part of the world around the local player, with everyone
+	/// in it `smoothing` of the way between the last two ticks. Leaves the
+	/// camera in screen space, for whatever's drawn on top.
+	#[cfg(feature = "client")]
+	pub fn present(&mut self, smoothing: f32) {
+		if let Some(player) = self.players.get(&self.client_id) {
+			let (x, y) = player.interpolated_position(smoothing);
+			let (width, height) = self.sim_config.player_size();
+			let world = (scalar_to_f32(self.sim_config.world_width), scalar_to_f32(self.sim_config.world_height));
+			self.view_origin = view_origin((x + width / 2.0, y + height / 2.0), world);
+		}
+
+		let viewport = Viewport::current();
+		set_camera(&viewport.camera_at(self.view_origin));
+		draw_backdrop(&self.sim_config);
+		for platform in &self.platforms {
+			platform.draw();
+		}
+		for coin in &self.coins.lying {
+			coin.draw();
+		}
+		self.draw_players(smoothing);
+		set_camera(&viewport.camera());
+		self.draw_scores();
+	}
+
+	/// Everyone's score in the top right corner, in their color, in
+	/// ClientId order.
+	#[cfg(feature = "client")]
+	fn draw_scores(&self) {
+		const FONT_SIZE: f32 = 16.0;
+		for (row, client_id) in self.players.keys().enumerate() {
+			let text = self.coins.score(*client_id).to_string();
+			let width = measure_text(&text, None, FONT_SIZE as u16, 1.0).width;
+			let y = FONT_SIZE * (row + 1) as f32;
+			draw_text(&text, SCREEN_SIZE as f32 - width - 4.0, y, FONT_SIZE, color_for_client(*client_id));
+		}
+	}
+
+	/// Local players get drawn from the predicted world, `smoothing` of the
+	/// way through the latest tick; remote ones as of the render tick, see
+	/// `remote_position`.
+	#[cfg(feature = "client")]
+	fn draw_players(&mut self, smoothing: f32) {
+		let size = self.sim_config.player_size();
+		for client_id in &self.local_players {
+			if let Some(player) = self.players.get_mut(client_id) {
+				let position = player.draw(smoothing, size, color_for_client(*client_id));
+				Player::draw_outline(position, size);
+				draw_label(*client_id, self.player_infos.get(client_id), position, size.0);
+				if self.show_inputs {
+					self.draw_inputs(*client_id, position, size);
+				}
+			}
+		}
+
+		let progress = match self.remote_presentation {
+			RemotePresentation::Predicted => smoothing,
+			RemotePresentation::Interpolated => {
+				if self.interpolation.head != self.accepted_head {
+					self.interpolation = Interpolation {
+						head: self.accepted_head,
+						since: get_time()
+					};
+				}
+				// A remote player whose ticks stop being confirmed simply
+				// comes to a halt at their last confirmed position.
+				((get_time() - self.interpolation.since) as f32 / self.tick_rate.delta()).clamp(0.0, 1.0)
+			},
+		};
+
+		let remote: Vec<ClientId> = self.players.keys().filter(|x| !self.local_players.contains(x)).copied().collect();
+		for client_id in remote {
+			let Some((x, y)) = self.remote_position(client_id, progress) else { continue };
+			let player = self.players.get_mut(&client_id).unwrap();
+			player.decay_error();
+			let (error_x, error_y) = player.error_offset();
+			let position = (x + error_x, y + error_y);
+			Player::draw_at(position, size, color_for_client(client_id));
+			draw_label(client_id, self.player_infos.get(&client_id), position, size.0);
+			if self.show_inputs {
+				self.draw_inputs(client_id, position, size);
+			}
+		}
+	}
+
+	/// Draws the intents `client_id` had applied during the accepted head
+	/// right under them, given where they got drawn and how large they are.
+	/// Local players get those of the latest tick we predicted drawn below,
+	/// dimmer, until the server confirms it; when the two disagree, the
+	/// server's about to correct us.
+	#[cfg(feature = "client")]
+	fn draw_inputs(&self, client_id: ClientId, (x, y): (f32, f32), (width, height): (f32, f32)) {
+		let center = x + width / 2.0;
+		let top = y + height + 2.0;
+		if let Some(intents) = self.input_stats.intents_at(self.accepted_head, client_id) {
+			draw_intents(IntentSummary::of(intents), center, top, Color::new(1.0, 1.0, 1.0, 0.9));
+		}
+		if !self.local_players.contains(&client_id) {
+			return;
+		}
+		let predicted = self.ticks.last().filter(|x| !x.authoritative).map(|x| x.tick.index());
+		if let Some(intents) = predicted.and_then(|x| self.input_stats.intents_at(x, client_id)) {
+			draw_intents(IntentSummary::of(intents), center, top + 7.0, Color::new(1.0, 1.0, 1.0, 0.35));
+		}
+	}
+}
+
+/// Draws tiny arrows either side of `center` for the ways `summary` moved,
+/// and one pointing up in between for a jump, five pixels tall from `top`.
+#[cfg(feature = "client")]
+fn draw_intents(summary: IntentSummary, center: f32, top: f32, color: Color) {
+	if summary.left {
+		let x = center - 10.0;
+		draw_rectangle(x, top + 2.0, 6.0, 1.0, color);
+		draw_rectangle(x + 1.0, top + 1.0, 1.0, 3.0, color);
+		draw_rectangle(x + 2.0, top, 1.0, 5.0, color);
+	}
+	if summary.right {
+		let x = center + 4.0;
+		draw_rectangle(x, top + 2.0, 6.0, 1.0, color);
+		draw_rectangle(x + 4.0, top + 1.0, 1.0, 3.0, color);
+		draw_rectangle(x + 3.0, top, 1.0, 5.0, color);
+	}
+	if summary.jump {
+		draw_rectangle(center - 0.5, top, 1.0, 5.0, color);
+		draw_rectangle(center - 1.5, top + 1.0, 3.0, 1.0, color);
+		draw_rectangle(center - 2.5, top + 2.0, 5.0, 1.0, color);
+	}
+}
+
+/// The color `client_id` is drawn with. Only ever depends on the ClientId,
+/// so that every client draws every player the same. There are `HUES` of
+/// them, evenly spaced around the color wheel bar the reddish greys around
+/// the background's hue, and any `HUES` consecutive ids get one each, with
+/// neighbouring ids far apart.
+#[cfg(feature = "client")]
+pub fn color_for_client(client_id: ClientId) -> Color {
+	const HUES: u16 = 16;
+	/// Coprime with `HUES`, so that stepping by it makes it to every hue.
+	const STRIDE: u16 = 7;
+	/// Where the hues start, in turns; the background's hue sits right in
+	/// the middle of what's left out.
+	const FIRST_HUE: f32 = 10.0 / 360.0;
+	const HUE_RANGE: f32 = 320.0 / 360.0;
+	let slot = (client_id % HUES) * STRIDE % HUES;
+	macroquad::color::hsl_to_rgb(FIRST_HUE + slot as f32 / HUES as f32 * HUE_RANGE, 0.7, 0.6)
+}
+
+/// Writes a player's name centered above them, given the top left corner
+/// of where they got drawn and how wide they are. Players nobody introduced
+/// go by their ClientId.
+#[cfg(feature = "client")]
+fn draw_label(client_id: ClientId, info: Option<&PlayerInfo>, (x, y): (f32, f32), width: f32) {
+	const FONT_SIZE: f32 = 14.0;
+	let label = info.map_or_else(|| client_id.to_string(), |x| x.name.clone());
+	let center = x + width / 2.0;
+	let width = measure_text(&label, None, FONT_SIZE as u16, 1.0).width;
+	draw_text(&label, center - width / 2.0, y - 4.0, FONT_SIZE, WHITE);
+}
+
+/// Marks out the world with faint lines, so that the camera moving along
+/// with the player is noticeable against the otherwise empty background.
+#[cfg(feature = "client")]
+fn draw_backdrop(config: &SimConfig) {
+	const SPACING: usize = 64;
+	let color = Color::new(1.0, 1.0, 1.0, 0.06);
+	let (width, height) = (scalar_to_f32(config.world_width), scalar_to_f32(config.world_height));
+	for x in (SPACING..width as usize).step_by(SPACING) {
+		draw_line(x as f32, 0.0, x as f32, height, 1.0, color);
+	}
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+	use crate::transport::{LoopbackTransport, Transport};
+	use super::*;
+
+	fn encode(packet: &Packet<PlayerIntent>) -> Vec<u8> {
+		let mut writer = ByteWriter::new();
+		packet.to_bytes(&mut writer).unwrap();
+		writer.as_slice().to_vec()
+	}
+
+	#[test]
+	fn past_frames_leave_out_what_the_server_got() {
+		let mut server = LoopbackTransport::new(0);
+		let mut game = Game::new(0);
+		let mut ages = vec![];
+		for index in 0..9 {
+			server.poll(&mut |bytes| game.dispatch_packet(bytes));
+			game.receive(index as f64 / 20.0);
+			let packet = game.run_tick(|_| vec![PlayerIntent::MoveRight]).unwrap();
+			let Packet::PredictedTick { past_frames, .. } = &packet else { panic!("tick {} sent as {}", index, packet.tag()) };
+			ages.push(past_frames.iter().map(|x| x.age).collect::<Vec<_>>());
+			// Tick 5's packet gets lost, so 6 repeats its frames.
+			if index != 5 {
+				server.send(&encode(&packet)).unwrap();
+			}
+		}
+		assert_eq!(ages, [vec![], vec![1], vec![1], vec![1], vec![1], vec![1], vec![1, 2], vec![1], vec![1]]);
+		// And the server got every tick's frames all the same.
+		assert_eq!(server.snapshot().index, 8);
+	}
+
+	#[test]
+	fn past_frames_cover_every_local_player() {
+		let mut server = LoopbackTransport::new(0).with_local(1);
+		let mut game = Game::new(0);
+		game.add_local_player(1);
+		let mut repeated = vec![];
+		for index in 0..9 {
+			server.poll(&mut |bytes| game.dispatch_packet(bytes));
+			game.receive(index as f64 / 20.0);
+			let packet = game.run_tick(|owner| match owner {
+				0 => vec![PlayerIntent::MoveRight],
+				_ => vec![PlayerIntent::MoveLeft],
+			}).unwrap();
+			let Packet::PredictedTick { past_frames, .. } = &packet else { panic!("tick {} sent as {}", index, packet.tag()) };
+			if index == 6 {
+				repeated = past_frames.iter().map(|x| (x.age, x.owner, x.intents.clone())).collect();
+			}
+			if index != 5 {
+				server.send(&encode(&packet)).unwrap();
+			}
+		}
+		assert_eq!(repeated, [
+			(1, 0, vec![PlayerIntent::MoveRight]),
+			(1, 1, vec![PlayerIntent::MoveLeft]),
+			(2, 0, vec![PlayerIntent::MoveRight]),
+			(2, 1, vec![PlayerIntent::MoveLeft]),
+		]);
+		// Tick 5 couldn't have been completed without the second player's frame.
+		assert_eq!(server.snapshot().index, 8);
+	}
+
+	#[test]
+	fn acks_for_ticks_not_yet_predicted_are_ignored() {
+		let mut game = Game::new(0);
+		for index in 0..4 {
+			game.receive(index as f64 / 20.0);
+			game.run_tick(|_| vec![PlayerIntent::MoveRight]).unwrap();
+		}
+		game.dispatch_packet(&encode(&Packet::Received { index: 1_000 }));
+		game.dispatch_packet(&encode(&Packet::Received { index: 2 }));
+		game.receive(0.2);
+		assert_eq!(game.received_head, 2);
+		let packet = game.run_tick(|_| vec![PlayerIntent::MoveRight]).unwrap();
+		let Packet::PredictedTick { past_frames, .. } = &packet else { panic!("tick sent as {}", packet.tag()) };
+		assert_eq!(past_frames.iter().map(|x| x.age).collect::<Vec<_>>(), [1, 2]);
+	}
+
+	#[test]
+	fn players_come_through_a_snapshot_as_they_left() {
+		let mut server = LoopbackTransport::new(0);
+		let mut game = Game::new(0);
+		for index in 0..33 {
+			server.poll(&mut |bytes| game.dispatch_packet(bytes));
+			game.receive(index as f64 / 20.0);
+			let packet = game.run_tick(|_| match index {
+				0..=29 => vec![PlayerIntent::MoveRight],
+				30 => vec![PlayerIntent::MoveRight, PlayerIntent::JumpStart],
+				_ => vec![PlayerIntent::MoveRight, PlayerIntent::JumpHold],
+			}).unwrap();
+			server.send(&encode(&packet)).unwrap();
+		}
+		let sent = &game.players()[&0];
+		// Mid-jump, so that none of what goes over the wire is left at zero.
+		assert!(!sent.grounded && sent.jump_hold_ticks > 0, "the player isn't jumping");
+
+		let mut writer = ByteWriter::new();
+		game.snapshot().to_bytes(&mut writer).unwrap();
+		let (snapshot, _) = Snapshot::from_slice(writer.as_slice()).unwrap();
+		let received = &snapshot.players[0].player;
+		assert_eq!(received.position(), sent.position());
+		assert_eq!(received.velocity(), sent.velocity());
+		assert_eq!(received.is_grounded(), sent.is_grounded());
+		assert_eq!(
+			(received.jump_hold_ticks, received.jump_buffer_ticks, received.coyote_ticks),
+			(sent.jump_hold_ticks, sent.jump_buffer_ticks, sent.coyote_ticks)
+		);
+		// Drawn right where it is on the first frame, rather than sliding in
+		// from the corner.
+		let (x, y) = received.position();
+		assert_eq!(received.interpolated_position(0.0), (scalar_to_f32(x), scalar_to_f32(y)));
+	}
+
+	#[test]
+	fn resends_leave_out_what_the_server_got() {
+		let tick = |index| encode(&Packet::PredictedTick {
+			sender: 0,
+			tick: EncodedTick::Keyframe(Tick::new(index, vec![CommandFrame::new(0, vec![PlayerIntent::MoveRight])])),
+			past_frames: vec![],
+			state_hash: None
+		});
+		let request = encode(&Packet::TimeRequest(TimeRequest { sender: 0, client_time: 1.5 }));
+		let mut writer = ByteWriter::new();
+		encode_batch(&[tick(3), request.clone(), tick(7)], &mut writer);
+		let batch = writer.as_slice().to_vec();
+
+		assert_eq!(without_received(tick(3), 3), Some(tick(3)));
+		assert_eq!(without_received(tick(3), 4), None);
+		assert_eq!(without_received(batch.clone(), 3), Some(batch.clone()));
+		let kept = without_received(batch, 5).unwrap();
+		assert_eq!(split_batch(&kept).unwrap(), [&request[..], &tick(7)[..]]);
+	}
+}
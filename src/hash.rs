@@ -0,0 +1,101 @@
+//! The hashes ticks and worlds get compared by. They only ever need to
+//! catch two clients accidentally disagreeing, so a checksum does just as
+//! well as a cryptographic hash, for a fraction of the cost.
+
+use std::fmt;
+use sha2::{Digest, Sha256};
+
+use crate::net::NetType;
+
+/// An algorithm ticks and worlds can be hashed with.
+pub trait TickHasher: Default {
+	/// A finished hash, which is always the same length for an algorithm.
+	type Output: NetType + Copy + Default + Eq + fmt::Debug + AsRef<[u8]>;
+	/// Identifies the algorithm in the protocol header. Both ends have to
+	/// hash the same way, or every tick would look mispredicted.
+	const ID: u8;
+
+	fn update(&mut self, bytes: &[u8]);
+	fn finish(self) -> Self::Output;
+}
+
+/// SHA-256, as ticks have always been hashed with.
+#[derive(Default)]
+pub struct Sha256Hasher(Sha256);
+
+impl TickHasher for Sha256Hasher {
+	type Output = [u8; 32];
+	const ID: u8 = 0;
+
+	fn update(&mut self, bytes: &[u8]) {
+		self.0.update(bytes);
+	}
+
+	fn finish(self) -> Self::Output {
+		self.0.finalize().into()
+	}
+}
+
+/// CRC-32, as used by zlib and Ethernet. Far cheaper than SHA-256, and
+/// plenty for telling two ticks apart.
+#[derive(Default)]
+pub struct Crc32Hasher(u32);
+
+impl Crc32Hasher {
+	const POLYNOMIAL: u32 = 0xedb88320;
+	const TABLE: [u32; 256] = Self::table();
+
+	const fn table() -> [u32; 256] {
+		let mut table = [0; 256];
+		let mut i = 0;
+		while i < 256 {
+			let mut crc = i as u32;
+			let mut bit = 0;
+			while bit < 8 {
+				crc = match crc & 1 {
+					1 => (crc >> 1) ^ Self::POLYNOMIAL,
+					_ => crc >> 1,
+				};
+				bit += 1;
+			}
+			table[i] = crc;
+			i += 1;
+		}
+		table
+	}
+}
+
+impl TickHasher for Crc32Hasher {
+	type Output = [u8; 4];
+	const ID: u8 = 1;
+
+	fn update(&mut self, bytes: &[u8]) {
+		// The checksum is kept uninverted in between, so that an empty
+		// hasher can start out as zero.
+		let mut crc = !self.0;
+		for byte in bytes {
+			crc = Self::TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+		}
+		self.0 = !crc;
+	}
+
+	fn finish(self) -> Self::Output {
+		self.0.to_le_bytes()
+	}
+}
+
+/// What ticks and worlds get hashed with; SHA-256 by default, or CRC-32
+/// with the `crc32` feature.
+#[cfg(not(feature = "crc32"))]
+pub type Hasher = Sha256Hasher;
+#[cfg(feature = "crc32")]
+pub type Hasher = Crc32Hasher;
+
+/// A finished hash, as made by `Hasher`.
+pub type Hash = <Hasher as TickHasher>::Output;
+
+/// Writes out a hash, or any other bytes, as lowercase hex.
+#[cfg(feature = "serde")]
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
\ No newline at end of file
@@ -0,0 +1,258 @@
+//! Buffers between whatever receives packets and the game loop draining
+//! them. Packets may arrive from a host callback at any point, so they're
+//! queued up here, and only processed once per frame.
+//!
+//! Raw packets from the host wait in the inbox first, see `push_packet`,
+//! until the game loop decodes them all in one go with `drain_inbox`.
+//! Decoding sorts them into the buffers of the `Inbox` the game owns, by
+//! kind, for it to take out of. Transports polled by the game loop itself
+//! can hand their packets straight to `Game::dispatch_packet`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::batch::split_batch;
+use crate::game::{Game, MembershipEvent, SimConfig};
+use crate::hash::Hash;
+use crate::log::LogLevel;
+use crate::net::DecodeError;
+use crate::protocol::{ClientId, ConnectAccept, ConnectRequest, Packet, PlayerIntent, PlayerInfo, RejectReason, Snapshot, SnapshotRequest, StateHash, Tick, TickDecoder, TimeRequest, TimeResponse};
+
+/// What clients sent in, should the game be running in server mode. It's
+/// the server's job to take these out, see `Game::take_server_buffers`,
+/// and process them.
+#[derive(Default, Debug)]
+pub struct ServerBuffers {
+	/// The proposed ticks sent in by the connected clients, along with any
+	/// recovered from the past frames repeated alongside them.
+	pub ticks: Vec<Tick<PlayerIntent>>,
+	/// State hashes the connected clients reported, for the server to
+	/// compare against its own.
+	pub state_reports: Vec<(ClientId, StateHash)>,
+	/// Clients asking which tick the server is on.
+	pub time_requests: Vec<TimeRequest>,
+	/// Clients asking to be let in, for the server to hand out ClientIds to.
+	pub connect_requests: Vec<ConnectRequest>,
+	/// Clients asking for a snapshot, having lost track of the game.
+	pub snapshot_requests: Vec<SnapshotRequest>,
+}
+
+/// Decoded packets, sorted by kind, waiting for the `Game` owning them to
+/// take them out at the start of its next frame; see `Game::receive`.
+/// Every game has one of its own, so that several of them can run in the
+/// same process without reading each other's packets.
+#[derive(Default)]
+pub(crate) struct Inbox {
+	pub(crate) server: ServerBuffers,
+	/// Clients delta encode their ticks, so the server keeps track of the
+	/// last tick decoded from each of them.
+	pub(crate) tick_decoders: BTreeMap<ClientId, TickDecoder<PlayerIntent>>,
+	/// Tick indices the server has acknowledged *receiving* from this
+	/// client. An acknowledgement says nothing about whether the
+	/// prediction was correct.
+	pub(crate) acks: Vec<u64>,
+	/// Authoritative ticks sent back by the server, waiting to be compared
+	/// against the client's own predictions.
+	pub(crate) authoritative_ticks: Vec<Tick<PlayerIntent>>,
+	/// Full world snapshots sent by the server, used to join a running game.
+	pub(crate) snapshots: Vec<Snapshot>,
+	/// Lightweight confirmations from the server, carrying only a tick index
+	/// and the hash the server computed for it. Used when re-sending the
+	/// whole tick would be wasteful, because our prediction was most likely
+	/// correct.
+	pub(crate) confirmations: Vec<(u64, Hash)>,
+	/// The server's state hashes, for the ticks we reported ours for.
+	pub(crate) state_hashes: Vec<StateHash>,
+	/// The tick rate and seed the server told us it runs with, if it did
+	/// since the last frame.
+	pub(crate) welcome: Option<(u8, u64, SimConfig)>,
+	/// Names of the players on other clients, as they introduce themselves.
+	/// In server mode, these still have to be relayed to everyone else.
+	pub(crate) player_infos: Vec<PlayerInfo>,
+	/// The server's answers to the time requests we sent while connecting.
+	pub(crate) time_responses: Vec<TimeResponse>,
+	/// The server's answer to our connect request, if it came since the
+	/// last frame: in with a ClientId, or turned away.
+	pub(crate) connect_response: Option<Result<ConnectAccept, RejectReason>>,
+	/// Chat messages relayed by the server, ours included.
+	pub(crate) chat: Vec<(ClientId, String)>,
+	/// Other clients joining and leaving the game, as reported by the host.
+	pub(crate) membership: Vec<MembershipEvent>,
+	/// The most recent error hit while decoding bytes handed to us by the
+	/// host. Malformed packets are dropped, but the error is kept for the
+	/// debug overlay.
+	pub(crate) last_decode_error: Option<DecodeError>,
+}
+
+/// How many packets the inbox holds before it starts dropping the oldest
+/// ones, unless the memory budget says otherwise. Well over what a frame's
+/// worth of traffic should ever come to; a full inbox means the game loop
+/// isn't running at all, most likely in a background tab, and anything
+/// that old would be stale anyway.
+pub const INBOX_CAPACITY: usize = 1024;
+
+/// Raw packets, in the order they arrived in, waiting to be decoded.
+static INBOX: Mutex<VecDeque<Vec<u8>>> = Mutex::new(VecDeque::new());
+
+/// How many packets the inbox holds at most; see
+/// `MemoryBudget::inbox_packets`.
+static CAPACITY: AtomicUsize = AtomicUsize::new(INBOX_CAPACITY);
+
+/// There's always room for the packet just pushed, at least.
+pub(crate) fn set_inbox_capacity(capacity: usize) {
+	CAPACITY.store(capacity.max(1), Ordering::Relaxed);
+}
+
+pub(crate) fn inbox_capacity() -> usize {
+	CAPACITY.load(Ordering::Relaxed)
+}
+
+/// How many packets are waiting to be decoded.
+pub(crate) fn inbox_len() -> usize {
+	INBOX.lock().unwrap().len()
+}
+
+/// Queues up a packet, for the next `drain_inbox` to decode. Safe to call
+/// from any thread, and from within `drain_inbox` itself; packets pushed
+/// while draining wait for the next one.
+///
+/// Once the inbox is full, the oldest packet is dropped to make room, with
+/// a warning.
+pub fn push_packet(bytes: Vec<u8>) {
+	let capacity = inbox_capacity();
+	let dropped = {
+		let mut inbox = INBOX.lock().unwrap();
+		let mut dropped = None;
+		while inbox.len() >= capacity {
+			dropped = inbox.pop_front();
+		}
+		inbox.push_back(bytes);
+		dropped
+	};
+	// Logged with the lock released, as the host may well push another
+	// packet from within the logging call.
+	if let Some(dropped) = dropped {
+		crate::log!(LogLevel::Warn, "inbox_full capacity={} dropped_bytes={}", capacity, dropped.len());
+	}
+}
+
+/// Decodes and dispatches every packet in the inbox to `game`, oldest
+/// first, handing each one to `on_packet` beforehand, e.g. for keeping
+/// stats. Meant to be called once a frame, before running any ticks.
+///
+/// The inbox is emptied up front, so that nothing's locked while decoding.
+/// Returns how many packets there were.
+pub fn drain_inbox(game: &mut Game, mut on_packet: impl FnMut(&[u8])) -> usize {
+	let packets = std::mem::take(&mut *INBOX.lock().unwrap());
+	for packet in &packets {
+		on_packet(packet);
+		game.dispatch_packet(packet);
+	}
+	packets.len()
+}
+
+impl Inbox {
+	/// Decodes a packet, and pushes it into the matching buffer depending on
+	/// its kind. A batch is split up first, with every packet in it
+	/// dispatched in turn; see `batch`.
+	pub(crate) fn dispatch(&mut self, bytes: &[u8]) {
+		match split_batch(bytes) {
+			Ok(packets) => packets.into_iter().for_each(|x| self.dispatch_single(x)),
+			Err(error) => {
+				crate::log!(LogLevel::Warn, "batch_rejected bytes={} error=\"{}\"", bytes.len(), error);
+				self.last_decode_error = Some(error);
+			},
+		}
+	}
+
+	fn dispatch_single(&mut self, bytes: &[u8]) {
+		// Malformed packets are dropped, keeping the error around for debugging.
+		let packet = match Packet::decode(bytes) {
+			Ok(packet) => packet,
+			Err(error) => {
+				crate::log!(LogLevel::Warn, "packet_rejected bytes={} error=\"{}\"", bytes.len(), error);
+				self.last_decode_error = Some(error);
+				return;
+			}
+		};
+
+		match packet {
+			Packet::PredictedTick { sender, tick, past_frames, state_hash } => {
+				// We only add the ticks to the queue, they're then processed
+				// as part of the server's update loop. The past frames don't
+				// depend on the tick decoding, and are what's there to make
+				// up for the packets lost before it, which could well have
+				// taken its baseline with them.
+				let decoder = self.tick_decoders.entry(sender).or_default();
+				self.server.ticks.extend(decoder.recover(tick.index(), past_frames));
+				let tick = match decoder.decode(tick) {
+					Ok(tick) => tick,
+					Err(error) => {
+						crate::log!(LogLevel::Warn, "tick_rejected sender={} error=\"{}\"", sender, error);
+						self.last_decode_error = Some(error);
+						return;
+					}
+				};
+				if decoder.first_sighting(tick.index()) {
+					self.server.ticks.push(tick);
+				}
+				if let Some(state_hash) = state_hash {
+					self.server.state_reports.push((sender, state_hash));
+				}
+			},
+			Packet::AuthoritativeTick(tick) => self.authoritative_ticks.push(tick),
+			Packet::Received { index } => self.acks.push(index),
+			Packet::Ack { index, hash } => self.confirmations.push((index, hash)),
+			Packet::Snapshot(snapshot) => self.snapshots.push(snapshot),
+			Packet::Join(client_id) => self.membership.push(MembershipEvent::Joined(client_id)),
+			Packet::Leave(client_id) => self.membership.push(MembershipEvent::Left(client_id)),
+			Packet::Welcome { tick_rate, seed, sim_config } => self.welcome = Some((tick_rate, seed, sim_config)),
+			Packet::StateHash(state_hash) => self.state_hashes.push(state_hash),
+			Packet::PlayerInfo(info) => self.player_infos.push(info),
+			Packet::Chat { sender, text } => self.chat.push((sender, text)),
+			Packet::TimeRequest(request) => self.server.time_requests.push(request),
+			Packet::TimeResponse(response) => self.time_responses.push(response),
+			Packet::ConnectRequest(request) => self.server.connect_requests.push(request),
+			Packet::ConnectAccept(accept) => self.connect_response = Some(Ok(accept)),
+			Packet::ConnectReject(reason) => self.connect_response = Some(Err(reason)),
+			Packet::SnapshotRequest(request) => self.server.snapshot_requests.push(request),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::net::{ByteWriter, NetType};
+	use crate::protocol::{CommandFrame, EncodedTick, PastFrame, PlayerIntent::*, TickDelta};
+	use super::*;
+
+	const SENDER: ClientId = 1;
+
+	fn tick(index: u64, intents: Vec<PlayerIntent>) -> Tick<PlayerIntent> {
+		Tick::new(index, vec![CommandFrame::new(SENDER, intents)])
+	}
+
+	fn predicted(tick: EncodedTick<PlayerIntent>, past_frames: Vec<PastFrame<PlayerIntent>>) -> Vec<u8> {
+		let mut writer = ByteWriter::new();
+		Packet::PredictedTick { sender: SENDER, tick, past_frames, state_hash: None }.to_bytes(&mut writer).unwrap();
+		writer.as_slice().to_vec()
+	}
+
+	#[test]
+	fn frames_of_a_lost_packet_are_recovered_from_the_next_one() {
+		let mut game = Game::new(0);
+		let lost = tick(1, vec![MoveRight, JumpStart]);
+		game.dispatch_packet(&predicted(EncodedTick::Keyframe(tick(0, vec![MoveRight])), vec![]));
+		// Tick 1's packet never arrives, and tick 2's is delta encoded
+		// against it, so only its past frames can be made sense of.
+		let past_frames = vec![PastFrame { owner: SENDER, age: 1, intents: vec![MoveRight, JumpStart], offset: 0 }];
+		game.dispatch_packet(&predicted(EncodedTick::Delta(TickDelta::between(&tick(2, vec![MoveLeft]), &lost)), past_frames));
+
+		let ticks = game.take_server_buffers().ticks;
+		assert_eq!(ticks.iter().map(|x| x.index()).collect::<Vec<_>>(), [0, 1]);
+		assert_eq!(ticks[1], lost);
+		game.receive(0.0);
+		assert!(matches!(game.last_decode_error(), Some(DecodeError::StaleBaseline { expected: 1, found: Some(0) })));
+	}
+}
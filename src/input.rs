@@ -0,0 +1,412 @@
+//! Keyboard input, and the keys each intent is bound to, along with input
+//! injected by the host, and the demo playing when there's neither.
+
+use std::sync::Mutex;
+
+use macroquad::prelude::*;
+
+use crate::demo::DemoScript;
+use crate::log::LogLevel;
+use crate::protocol::{Intent, PlayerIntent};
+use crate::SCREEN_SIZE;
+
+macro_rules! named_keys {
+	($($name:ident),* $(,)?) => {
+		/// Every key a binding can name, by the name it's written as.
+		const NAMED_KEYS: &[(&str, KeyCode)] = &[$((stringify!($name), KeyCode::$name)),*];
+	};
+}
+
+named_keys!(
+	Space, Apostrophe, Comma, Minus, Period, Slash,
+	Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+	Semicolon, Equal,
+	A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+	LeftBracket, Backslash, RightBracket, GraveAccent, World1, World2,
+	Escape, Enter, Tab, Backspace, Insert, Delete, Right, Left, Down, Up,
+	PageUp, PageDown, Home, End, CapsLock, ScrollLock, NumLock, PrintScreen, Pause,
+	F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13,
+	F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25,
+	Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9,
+	KpDecimal, KpDivide, KpMultiply, KpSubtract, KpAdd, KpEnter, KpEqual,
+	LeftShift, LeftControl, LeftAlt, LeftSuper, RightShift, RightControl, RightAlt, RightSuper,
+	Menu,
+);
+
+fn key_name(key: KeyCode) -> Option<&'static str> {
+	NAMED_KEYS.iter().find(|(_, x)| *x == key).map(|(name, _)| *name)
+}
+
+fn key_named(name: &str) -> Option<KeyCode> {
+	NAMED_KEYS.iter().find(|(x, _)| *x == name).map(|(_, key)| *key)
+}
+
+/// Which keys produce which intents. Any of an intent's keys produce it.
+///
+/// Written as text for the host to keep around, one intent per line: its
+/// name (`left`, `right`, `jump`), followed by the names of its keys, as in
+/// `jump Up Space`. Intents left out keep their default keys, and lines
+/// starting with `#` are ignored.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyBindings {
+	pub move_left: Vec<KeyCode>,
+	pub move_right: Vec<KeyCode>,
+	pub jump: Vec<KeyCode>,
+}
+
+impl Default for KeyBindings {
+	/// The arrow keys.
+	fn default() -> Self {
+		Self {
+			move_left: vec![KeyCode::Left],
+			move_right: vec![KeyCode::Right],
+			jump: vec![KeyCode::Up]
+		}
+	}
+}
+
+impl KeyBindings {
+	const INTENT_NAMES: [(&'static str, PlayerIntent); 3] = [
+		("left", PlayerIntent::MoveLeft),
+		("right", PlayerIntent::MoveRight),
+		("jump", PlayerIntent::JumpStart),
+	];
+
+	pub fn wasd() -> Self {
+		Self {
+			move_left: vec![KeyCode::A],
+			move_right: vec![KeyCode::D],
+			jump: vec![KeyCode::W]
+		}
+	}
+
+	/// Analog movement comes from gamepads and touch, never from keys.
+	pub fn keys(&self, intent: PlayerIntent) -> &[KeyCode] {
+		match intent {
+			PlayerIntent::MoveLeft => &self.move_left,
+			PlayerIntent::MoveRight => &self.move_right,
+			PlayerIntent::JumpStart | PlayerIntent::JumpHold => &self.jump,
+			PlayerIntent::MoveHorizontal(_) => &[],
+		}
+	}
+
+	/// Replaces the keys bound to `intent`. Analog movement can't be bound.
+	pub fn bind(&mut self, intent: PlayerIntent, keys: Vec<KeyCode>) {
+		match intent {
+			PlayerIntent::MoveLeft => self.move_left = keys,
+			PlayerIntent::MoveRight => self.move_right = keys,
+			PlayerIntent::JumpStart | PlayerIntent::JumpHold => self.jump = keys,
+			PlayerIntent::MoveHorizontal(_) => {},
+		}
+	}
+
+	fn is_down(&self, intent: PlayerIntent) -> bool {
+		self.keys(intent).iter().any(|x| is_key_down(*x))
+	}
+
+	fn is_pressed(&self, intent: PlayerIntent) -> bool {
+		self.keys(intent).iter().any(|x| is_key_pressed(*x))
+	}
+
+	/// Parses bindings written by `to_text`, or by hand.
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let mut bindings = Self::default();
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut words = line.split_whitespace();
+			let name = words.next().unwrap_or_default();
+			let Some((_, intent)) = Self::INTENT_NAMES.iter().find(|(x, _)| *x == name) else {
+				return Err(format!("unknown intent {:?} on line {}", name, number + 1));
+			};
+
+			let mut keys = vec![];
+			for word in words {
+				let key = key_named(word).ok_or(format!("unknown key {:?} on line {}", word, number + 1))?;
+				if !keys.contains(&key) {
+					keys.push(key);
+				}
+			}
+			bindings.bind(*intent, keys);
+		}
+		Ok(bindings)
+	}
+
+	pub fn to_text(&self) -> String {
+		Self::INTENT_NAMES
+			.iter()
+			.map(|(name, intent)| {
+				let mut line = name.to_string();
+				for key in self.keys(*intent) {
+					if let Some(key_name) = key_name(*key) {
+						line.push(' ');
+						line.push_str(key_name);
+					}
+				}
+				line + "\n"
+			})
+			.collect()
+	}
+}
+
+/// Input handed over by the host rather than read from the keyboard, such
+/// as on-screen buttons or a test script driving the game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Injection {
+	/// An intent for the local player at the given index, in the order they
+	/// were added in, for the next tick only. Buttons being held have to be
+	/// injected again for every tick.
+	Intent(usize, PlayerIntent),
+	/// Drops every injected intent which hasn't made it into a tick yet.
+	Clear,
+	/// Whether the local player at the given index still takes keyboard
+	/// input, or only what's injected.
+	Keyboard(usize, bool),
+}
+
+/// Injections from the host, waiting to be taken at the start of a frame.
+static INJECTIONS: Mutex<Vec<Injection>> = Mutex::new(Vec::new());
+
+pub fn inject(injection: Injection) {
+	INJECTIONS.lock().unwrap().push(injection);
+}
+
+/// Everything injected since the last call, in order.
+pub fn take_injections() -> Vec<Injection> {
+	std::mem::take(&mut *INJECTIONS.lock().unwrap())
+}
+
+/// Keyboard input gathered between two ticks. Updated every rendered frame,
+/// and drained whenever a tick gets predicted.
+///
+/// Keys are remembered if they were down on *any* frame since the last tick,
+/// so a tap shorter than a tick still makes it into the next one.
+pub struct InputState {
+	bindings: KeyBindings,
+	/// Whether the keyboard is read at all; see `Injection::Keyboard`.
+	keyboard: bool,
+	/// Set while the keyboard is busy with something else, like typing a
+	/// chat message, on top of `keyboard`.
+	suspended: bool,
+	/// Whether a jump key went down at some point since the last tick.
+	jump_pressed: bool,
+	jump_held: bool,
+	move_left: bool,
+	move_right: bool,
+	/// Intents injected by the host for the next tick.
+	injected: Vec<PlayerIntent>,
+	/// Whether the last tick drained had the player jumping.
+	jumping: bool,
+	/// How far into the next tick the earliest key went down, if one went
+	/// down at all since the last tick; see `offset`.
+	first_press: Option<f32>,
+}
+
+impl InputState {
+	pub fn new(bindings: KeyBindings) -> Self {
+		Self {
+			bindings,
+			keyboard: true,
+			suspended: false,
+			jump_pressed: false,
+			jump_held: false,
+			move_left: false,
+			move_right: false,
+			injected: vec![],
+			jumping: false,
+			first_press: None
+		}
+	}
+
+	/// Gathers this frame's keys. `progress` is how far we are into the tick
+	/// they'll end up in, between 0 and 1; for a tick that's due this very
+	/// frame, that's all the way through.
+	pub fn update(&mut self, progress: f32) {
+		let pressed = [PlayerIntent::JumpStart, PlayerIntent::MoveLeft, PlayerIntent::MoveRight]
+			.into_iter()
+			.any(|x| self.is_pressed(x));
+		if pressed && self.first_press.is_none() {
+			self.first_press = Some(progress);
+		}
+		self.jump_pressed |= self.is_pressed(PlayerIntent::JumpStart);
+		self.jump_held |= self.is_down(PlayerIntent::JumpStart);
+		self.move_left |= self.is_down(PlayerIntent::MoveLeft);
+		self.move_right |= self.is_down(PlayerIntent::MoveRight);
+	}
+
+	fn is_down(&self, intent: PlayerIntent) -> bool {
+		self.reads_keyboard() && self.bindings.is_down(intent)
+	}
+
+	fn is_pressed(&self, intent: PlayerIntent) -> bool {
+		self.reads_keyboard() && self.bindings.is_pressed(intent)
+	}
+
+	fn reads_keyboard(&self) -> bool {
+		self.keyboard && !self.suspended
+	}
+
+	/// Adds an intent to the next tick, on top of the keyboard's.
+	pub fn inject(&mut self, intent: PlayerIntent) {
+		if !self.injected.contains(&intent) {
+			self.injected.push(intent);
+		}
+	}
+
+	pub fn clear_injected(&mut self) {
+		self.injected.clear();
+	}
+
+	/// Stops or resumes reading the keyboard. Keys already gathered for the
+	/// next tick are dropped when it stops.
+	pub fn set_keyboard(&mut self, enabled: bool) {
+		self.keyboard = enabled;
+		if !enabled {
+			self.release_keys();
+		}
+	}
+
+	/// Stops reading the keyboard for a while, without touching whatever
+	/// `set_keyboard` was told; injected intents keep coming through.
+	/// Keys already gathered are dropped, as with `set_keyboard`.
+	pub fn suspend(&mut self, suspended: bool) {
+		self.suspended = suspended;
+		if suspended {
+			self.release_keys();
+		}
+	}
+
+	fn release_keys(&mut self) {
+		self.first_press = None;
+		self.jump_pressed = false;
+		self.jump_held = false;
+		self.move_left = false;
+		self.move_right = false;
+	}
+
+	/// How late into the next tick the earliest key went down, in 256ths of
+	/// a tick, for `CommandFrame::offset`. Keys held over from an earlier
+	/// tick count from its start. `drain` starts over.
+	pub fn offset(&self) -> u8 {
+		self.first_press.map_or(0, |x| (x * 256.0).clamp(0.0, 255.0) as u8)
+	}
+
+	/// Turns the gathered input into intents, one of each at most, however
+	/// many keys are bound to them. Keys that are still held carry over, in
+	/// case several ticks get predicted within the same frame.
+	///
+	/// Movement is held, so it's applied every tick the key was down for.
+	/// Jumping is edge triggered instead; holding a jump key only jumps
+	/// once, until it's released and pressed again. Every tick after that
+	/// the key stays down for holds the jump instead.
+	///
+	/// Injected intents follow the same rules: a jump injected for several
+	/// ticks in a row only starts on the first, and holds on the rest.
+	/// Conflicting intents are normalized away, the same as the server does.
+	pub fn drain(&mut self) -> Vec<PlayerIntent> {
+		let mut intents = vec![];
+		self.first_press = None;
+
+		let jump_down = self.is_down(PlayerIntent::JumpStart);
+		let held = std::mem::replace(&mut self.jump_held, jump_down);
+		if std::mem::take(&mut self.jump_pressed) {
+			intents.push(PlayerIntent::JumpStart);
+		} else if held {
+			intents.push(PlayerIntent::JumpHold);
+		}
+
+		let left_down = self.is_down(PlayerIntent::MoveLeft);
+		if std::mem::replace(&mut self.move_left, left_down) {
+			intents.push(PlayerIntent::MoveLeft);
+		}
+
+		let right_down = self.is_down(PlayerIntent::MoveRight);
+		if std::mem::replace(&mut self.move_right, right_down) {
+			intents.push(PlayerIntent::MoveRight);
+		}
+
+		for intent in std::mem::take(&mut self.injected) {
+			intents.push(match intent {
+				PlayerIntent::JumpStart if self.jumping => PlayerIntent::JumpHold,
+				intent => intent,
+			});
+		}
+
+		PlayerIntent::normalize(&mut intents);
+		self.jumping = intents.iter().any(|x| matches!(x, PlayerIntent::JumpStart | PlayerIntent::JumpHold));
+		intents
+	}
+}
+
+/// How long nobody has to press a key before the game plays itself, in
+/// seconds.
+pub const DEMO_IDLE_TIMEOUT: f64 = 10.0;
+
+/// Plays a `DemoScript` as the local player once left alone for
+/// `DEMO_IDLE_TIMEOUT`, by injecting its intents a tick at a time; so
+/// they're predicted and sent just like the keyboard's. Any key pressed
+/// hands control right back.
+pub struct Demo {
+	script: DemoScript,
+	/// When a key last went down, or the host last injected something.
+	last_input: f64,
+	/// How many ticks into the script we are, while it's playing.
+	position: Option<u64>,
+}
+
+impl Demo {
+	pub fn new(script: DemoScript, now: f64) -> Self {
+		Self {
+			script,
+			last_input: now,
+			position: None
+		}
+	}
+
+	/// Starts playing right away, rather than waiting to be left alone.
+	pub fn start(&mut self) {
+		self.position.get_or_insert(0);
+	}
+
+	pub fn is_playing(&self) -> bool {
+		self.position.is_some()
+	}
+
+	/// Stops playing the moment a key goes down, or `injected` says the
+	/// host handed over input of its own, and starts once neither happened
+	/// for long enough. Once a frame, before any ticks are predicted.
+	pub fn update(&mut self, injected: bool, now: f64) {
+		if injected || !get_keys_pressed().is_empty() {
+			if self.position.take().is_some() {
+				crate::log!(LogLevel::Info, "demo_stopped");
+			}
+			self.last_input = now;
+		} else if self.position.is_none() && now - self.last_input >= DEMO_IDLE_TIMEOUT {
+			crate::log!(LogLevel::Info, "demo_started idle={:.1}", now - self.last_input);
+			self.start();
+		}
+	}
+
+	/// Injects the script's intents for the tick about to be predicted into
+	/// `input`, while playing, and moves on to the next one.
+	pub fn drive(&mut self, input: &mut InputState) {
+		let Some(position) = &mut self.position else { return };
+		for intent in self.script.intents(*position) {
+			input.inject(*intent);
+		}
+		*position += 1;
+	}
+
+	/// Tells everyone watching that nobody's actually playing.
+	pub fn draw(&self) {
+		if !self.is_playing() {
+			return;
+		}
+		let text = "DEMO, press any key to play";
+		let size = measure_text(text, None, 16, 1.0);
+		draw_text(text, (SCREEN_SIZE as f32 - size.width) / 2.0, SCREEN_SIZE as f32 - 40.0, 16.0, RED);
+	}
+}
\ No newline at end of file
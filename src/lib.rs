@@ -0,0 +1,57 @@
+//! Deterministic lockstep networking with client-side prediction and
+//! rollback, for a tiny platformer.
+//!
+//! The simulation core builds without any window or host; rendering, input
+//! and the transports come with the `client` feature.
+
+pub mod log;
+pub mod net;
+pub mod hash;
+pub mod protocol;
+pub mod scalar;
+pub mod rng;
+pub mod game;
+pub mod prediction;
+pub mod replay;
+pub mod saved_state;
+pub mod chat;
+pub mod batch;
+pub mod reliable;
+pub mod demo;
+pub mod diagnostics;
+mod inbox;
+#[cfg(feature = "client")]
+pub mod transport;
+#[cfg(feature = "client")]
+pub mod input;
+#[cfg(feature = "client")]
+pub mod overlay;
+#[cfg(feature = "client")]
+pub mod cli;
+#[cfg(feature = "client")]
+pub mod viewport;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "serde")]
+pub mod conformance;
+
+// `#[derive(NetType)]` refers to these through the crate root.
+pub use net::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+/// Derives `NetType`, for games bringing their own intents.
+pub use command_networking_derive::NetType;
+pub use inbox::{drain_inbox, push_packet, ServerBuffers, INBOX_CAPACITY};
+
+/// Width and height of the playfield, in logical pixels; the window scales
+/// them up as it sees fit, see `viewport`.
+pub const SCREEN_SIZE: i32 = 256;
+/// Width of the world, in logical pixels. Only a screen's worth of it is in
+/// view at a time, following the local player around.
+pub const WORLD_WIDTH: i32 = 1024;
+/// Height of the world, in logical pixels.
+pub const WORLD_HEIGHT: i32 = 256;
+/// Ticks per second, unless the server says otherwise; see `game::TickRate`.
+pub const TICKRATE: u8 = 20;
+/// Seconds between two ticks, at the default tick rate.
+pub const TICK_DELTA: f32 = 1.0 / TICKRATE as f32;
\ No newline at end of file
@@ -0,0 +1,129 @@
+//! A tiny logging facility. Messages go to the host through `host_log` in
+//! wasm builds, where there's no stderr to speak of, and to stderr natively.
+//!
+//! Messages are structured as an event name, followed by `key=value` pairs
+//! which include the tick index wherever there is one, e.g.
+//! `rollback tick=42 replayed=3`. Use `log!`, which skips formatting the
+//! message altogether unless it's going to be written.
+//!
+//! The last few messages written are kept around as well, for diagnostic
+//! bundles; see `recent`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How important a message is. Lower levels are more important.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+	Error = 1,
+	Warn = 2,
+	Info = 3,
+	Debug = 4,
+	Trace = 5,
+}
+
+impl LogLevel {
+	/// The level for the given number, clamped to the range of levels; 0
+	/// stands for logging nothing at all.
+	pub fn from_u8(level: u8) -> Option<Self> {
+		match level {
+			0 => None,
+			1 => Some(LogLevel::Error),
+			2 => Some(LogLevel::Warn),
+			3 => Some(LogLevel::Info),
+			4 => Some(LogLevel::Debug),
+			_ => Some(LogLevel::Trace),
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			LogLevel::Error => "error",
+			LogLevel::Warn => "warn",
+			LogLevel::Info => "info",
+			LogLevel::Debug => "debug",
+			LogLevel::Trace => "trace",
+		}
+	}
+}
+
+/// The least important level that's compiled in at all. With the
+/// `log-release-max-warn` feature, release builds leave out anything less
+/// important than warnings, formatting included.
+#[cfg(all(feature = "log-release-max-warn", not(debug_assertions)))]
+pub const MAX_LEVEL: LogLevel = LogLevel::Warn;
+#[cfg(not(all(feature = "log-release-max-warn", not(debug_assertions))))]
+pub const MAX_LEVEL: LogLevel = LogLevel::Trace;
+
+/// The least important level written at runtime, as a number; see
+/// `set_level`.
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Only writes messages at `level` or more important ones from now on.
+/// `None` silences logging altogether.
+pub fn set_level(level: Option<LogLevel>) {
+	LEVEL.store(level.map_or(0, |x| x as u8), Ordering::Relaxed);
+}
+
+/// Whether a message at `level` would be written.
+pub fn enabled(level: LogLevel) -> bool {
+	level <= MAX_LEVEL && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// How many of the most recent messages `recent` holds on to.
+pub const RECENT_LINES: usize = 32;
+
+/// The messages last written, oldest first, along with their level.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Writes a message, unless its level is filtered out.
+pub fn log(level: LogLevel, message: &str) {
+	if enabled(level) {
+		write(level, message);
+		let mut recent = RECENT.lock().unwrap();
+		if recent.len() >= RECENT_LINES {
+			recent.pop_front();
+		}
+		recent.push_back(format!("[{}] {}", level.name(), message));
+	}
+}
+
+/// The last `RECENT_LINES` messages written, oldest first, the way they
+/// were written natively.
+pub fn recent() -> Vec<String> {
+	RECENT.lock().unwrap().iter().cloned().collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(level: LogLevel, message: &str) {
+	extern "C" {
+		/// Hands a message over to the host. The memory is only lent for
+		/// the duration of the call.
+		fn host_log(level: u8, data_ptr: *const u8, data_size: usize);
+	}
+	unsafe {
+		host_log(level as u8, message.as_ptr(), message.len());
+	}
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(level: LogLevel, message: &str) {
+	eprintln!("[{}] {}", level.name(), message);
+}
+
+/// Formats and writes a message, as long as its level isn't filtered out;
+/// otherwise, the arguments aren't even evaluated.
+///
+/// ```ignore
+/// log!(LogLevel::Debug, "rollback tick={} replayed={}", index, count);
+/// ```
+#[macro_export]
+macro_rules! log {
+	($level:expr, $($arg:tt)+) => {
+		if $crate::log::enabled($level) {
+			$crate::log::log($level, &format!($($arg)+));
+		}
+	};
+}
\ No newline at end of file
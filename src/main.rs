@@ -1,525 +1,1243 @@
-use std::collections::HashMap;
-use std::collections::VecDeque;
-use std::sync::Mutex;
-use sha2::{Sha256, Digest};
-
+#[cfg(feature = "client")]
 use macroquad::prelude::*;
+#[cfg(feature = "client")]
 use macroquad::Window;
 
+#[cfg(not(feature = "client"))]
+use std::collections::BTreeMap;
+#[cfg(not(feature = "client"))]
+use sha2::{Sha256, Digest};
+
+use command_networking_rs::*;
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::PlayerIntent;
+#[cfg(feature = "client")]
+use command_networking_rs::protocol::{ClientId, ConnectRequest, PROTOCOL_VERSION};
+#[cfg(feature = "client")]
+use command_networking_rs::game::{TickAccumulator, TickBudget};
+#[cfg(feature = "client")]
+use command_networking_rs::replay::{Playback, Replay};
+#[cfg(feature = "client")]
+use command_networking_rs::transport::{SimulatedConditions, Transport};
+#[cfg(feature = "client")]
+use command_networking_rs::input::{self, Demo, InputState, Injection, KeyBindings};
+#[cfg(feature = "client")]
+use command_networking_rs::overlay::{DebugStats, Overlay};
+#[cfg(feature = "client")]
+use command_networking_rs::chat::ChatInput;
+use command_networking_rs::demo::DemoScript;
+#[cfg(feature = "client")]
+use command_networking_rs::cli::Options;
+#[cfg(feature = "client")]
+use command_networking_rs::viewport::Viewport;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use command_networking_rs::cli::{self, CliError, Mode};
+#[cfg(feature = "client")]
+use command_networking_rs::saved_state::SavedState;
+#[cfg(feature = "client")]
+use std::sync::Mutex;
+#[cfg(feature = "client")]
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+#[cfg(all(feature = "client", not(any(feature = "transport-udp", feature = "transport-websocket"))))]
+use std::sync::atomic::AtomicU8;
+
+#[cfg(feature = "client")]
 const BACKGROUND_COLOR: Color = Color::new(0.168, 0.149, 0.152, 1.0);
-const SCREEN_SIZE: i32 = 256;
-const TICKRATE: u8 = 20;
-const TICK_DELTA: f32 = 1.0 / TICKRATE as f32;
+/// Most ticks simulated during a single frame. After a long hitch, running
+/// every missed tick would only make the next frame take even longer.
+#[cfg(feature = "client")]
+const MAX_CATCH_UP_TICKS: u32 = 5;
+/// Frames further apart than this, in seconds, mean the window went out of
+/// focus in between, whether the host told us so or not.
+#[cfg(feature = "client")]
+const MAX_FRAME_GAP: f32 = 1.0;
+/// Bytes set aside for a diagnostic bundle, enough for a few dozen players.
+#[cfg(feature = "client")]
+const DIAGNOSTICS_CAPACITY: usize = 256 * 1024;
+
+// Below, we define the client FFI; these are the methods that the JS host
+// will use to interface with the client. In a real-world scenario you
+// would want some authorization mechanism to ensure data has been issued
+// by an authorized server.
+//
+// For the purposes of this example, this security aspect has been
+// skipped entirely, as auth/validation flows are *not* the subject
+// of this demo.
+
+#[cfg(feature = "client")]
+#[no_mangle]
+extern "C" fn protocol_version() -> u8 {
+	PROTOCOL_VERSION
+}
+
+/// The protocol version the host speaks, as it told us through
+/// `set_host_protocol_version`; 0 for hosts which never did.
+#[cfg(all(feature = "client", not(any(feature = "transport-udp", feature = "transport-websocket"))))]
+static HOST_PROTOCOL_VERSION: AtomicU8 = AtomicU8::new(0);
+
+/// Tells the client which protocol version the host speaks, which decides
+/// whether packets go out batched through `send_packets`, or one by one
+/// through `send_packet` for hosts older than `batch::BATCHING_VERSION`.
+/// Has to be called before `start_game` to make a difference; hosts which
+/// never call it are taken to be old ones.
+#[cfg(all(feature = "client", not(any(feature = "transport-udp", feature = "transport-websocket"))))]
+#[no_mangle]
+extern "C" fn set_host_protocol_version(version: u8) {
+	HOST_PROTOCOL_VERSION.store(version, Ordering::Relaxed);
+}
+
+/// Only logs messages at `level` or more important ones from now on, where
+/// 1 stands for errors and 5 for tracing; 0 turns logging off.
+#[cfg(feature = "client")]
+#[no_mangle]
+extern "C" fn set_log_level(level: u8) {
+	log::set_level(log::LogLevel::from_u8(level));
+}
+
+/// Allocates a buffer the host can write an incoming packet into, before
+/// passing it on to `receive_packet`. Packets over `MAX_PACKET_SIZE` would
+/// only be rejected once decoded, so there's no buffer for them, and null
+/// is returned instead; the host should drop the packet.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn net_alloc(size: usize) -> *mut u8 {
+	if size > protocol::MAX_PACKET_SIZE {
+		return std::ptr::null_mut();
+	}
+	replay_alloc(size)
+}
+
+/// Like `net_alloc`, but for passing a replay to `start_replay`, which
+/// is allowed to be a lot larger than any packet.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn replay_alloc(size: usize) -> *mut u8 {
+	let buffer = vec![0u8; size].into_boxed_slice();
+	Box::into_raw(buffer) as *mut u8
+}
 
-/// If a game instance happens to be running in server mode, this field will
-/// be populated with all of the proposed ticks sent in by the connected clients.
+/// Frees a buffer obtained from `net_alloc`, for when the host ends up not
+/// passing it to `receive_packet` after all.
 ///
-/// It is the server's job to take the ticks out of this buffer and process them.
-static BUFFERED_TICKS: Mutex<Vec<Tick>> = Mutex::new(Vec::new());
-
-//// Here we define the host FFI; because this demo is going to use a dummy
-//// network (embedded & simulated inside a JS environment), all of the
-//// communication is going to happen through mock functions.
-extern "C" {
-	fn send_predicted_tick(
-		data_ptr: *mut u8,
-		data_size: usize
-	);
+/// # Safety
+/// `data_ptr` has to come from `net_alloc`, called with the same `data_size`,
+/// and mustn't have been freed or passed to `receive_packet` already.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+unsafe extern "C" fn net_free(data_ptr: *mut u8, data_size: usize) {
+	if data_ptr.is_null() {
+		return;
+	}
+	drop(take_net_buffer(data_ptr, data_size));
 }
 
-//// Below, we define the client FFI; these are the methods that the JS host
-//// will use to interface with the client. In a real-world scenario you
-//// would want some authorization mechanism to ensure data has been issued
-//// by an authorized server.
-////
-//// For the purposes of this example, this security aspect has been
-//// skipped entirely, as auth/validation flows are *not* the subject
-//// of this demo.
+/// Takes back ownership of a buffer handed out by `net_alloc`.
+///
+/// # Safety
+/// Same as for `net_free`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+unsafe fn take_net_buffer(data_ptr: *mut u8, data_size: usize) -> Option<Box<[u8]>> {
+	if data_ptr.is_null() {
+		return None;
+	}
+	Some(Box::from_raw(std::ptr::slice_from_raw_parts_mut(data_ptr, data_size)))
+}
 
+/// Starts the game, as whichever ClientId the server hands out once it
+/// lets us in. A `spectator` only watches, until it joins the game by
+/// pressing Enter.
+#[cfg(all(feature = "client", not(any(feature = "transport-udp", feature = "transport-websocket"))))]
 #[no_mangle]
-extern "C" fn start_game(client_id: u8) {
-	Window::from_config(Conf {
-		window_width: SCREEN_SIZE,
-		window_height: SCREEN_SIZE,
-		window_resizable: false,
-		..Default::default()
-	}, amain(client_id));
+extern "C" fn start_game(spectator: bool) {
+	let name = PLAYER_NAME.lock().unwrap().take();
+	let transport = transport::HostTransport::new(HOST_PROTOCOL_VERSION.load(Ordering::Relaxed));
+	Window::from_config(window_conf(1), amain(Options { spectator, name, ..Default::default() }, transport));
 }
 
+/// Starts the game, connected to the server at the given URL. The URL is
+/// passed in a buffer from `net_alloc`, which is consumed by the call.
+/// `spectator` works the same as for the JS host.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(feature = "transport-websocket")]
 #[no_mangle]
-unsafe extern "C" fn receive_tick(
+unsafe extern "C" fn start_game(spectator: bool, url_ptr: *mut u8, url_size: usize) {
+	let url = take_net_buffer(url_ptr, url_size).unwrap_or_default();
+	let url = String::from_utf8_lossy(&url).into_owned();
+	let name = PLAYER_NAME.lock().unwrap().take();
+	Window::from_config(window_conf(1), amain(
+		Options { spectator, name, ..Default::default() },
+		transport::WebSocketTransport::connect(url)
+	));
+}
+
+#[cfg(feature = "transport-websocket")]
+#[no_mangle]
+extern "C" fn ws_on_open() {
+	transport::socket_opened();
+}
+
+/// Called both when an open socket closes, and when connecting fails.
+#[cfg(feature = "transport-websocket")]
+#[no_mangle]
+extern "C" fn ws_on_close() {
+	transport::socket_closed();
+}
+
+/// Hands over a binary message from the WebSocket.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(feature = "transport-websocket")]
+#[no_mangle]
+unsafe extern "C" fn ws_on_message(
 	data_ptr: *mut u8,
 	data_size: usize
 ) {
-	let mut buffer: VecDeque<u8> = Vec::from_raw_parts(
-		data_ptr,
-		data_size,
-		data_size
-	).into();
+	if let Some(bytes) = take_net_buffer(data_ptr, data_size) {
+		transport::socket_message(bytes.into_vec());
+	}
+}
 
-	// We expect the server to never send malformed bytes.
-	// In a real world scenario, it would be better to have a
-	// more sophisticated error handling mechanism.
-	let tick = Tick::from_bytes(&mut buffer).unwrap();
-	let mut tick_queue = BUFFERED_TICKS.lock().unwrap();
+/// Every message from the host arrives through here, and waits in the inbox
+/// for the next frame to decode it; see `drain_inbox`.
+///
+/// # Safety
+/// The buffer has to come from `net_alloc`, called with the same `data_size`.
+/// It's consumed by the call, so the host mustn't touch or free it afterwards.
+#[cfg(all(feature = "client", not(any(feature = "transport-udp", feature = "transport-websocket"))))]
+#[no_mangle]
+unsafe extern "C" fn receive_packet(
+	data_ptr: *mut u8,
+	data_size: usize
+) {
+	if let Some(bytes) = take_net_buffer(data_ptr, data_size) {
+		push_packet(bytes.into_vec());
+	}
+}
 
-	// We only add the tick to the queue, it's then processed as part of
-	// the server's update loop.
-	tick_queue.push(tick);
+/// Plays back a replay, passed in a buffer from `replay_alloc`, instead of
+/// starting a game. Returns false, without opening a window, if it isn't a
+/// replay this build can play back; see `Replay::parse`.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+unsafe extern "C" fn start_replay(data_ptr: *mut u8, data_size: usize) -> bool {
+	let Some(bytes) = take_net_buffer(data_ptr, data_size) else {
+		return false;
+	};
+	match Replay::parse(&bytes) {
+		Ok(replay) => {
+			Window::from_config(window_conf(1), amain_replay(replay));
+			true
+		},
+		Err(_) => false,
+	}
 }
 
-/// This trait defines the methods that must be implemented by all types
-/// which will be sent over the wire. It's just byte-format serialization.
-pub trait NetType: Sized {
-	fn to_bytes(&self, buffer: &mut Buffer);
-	fn from_bytes(buffer: &mut Buffer) -> Result<Self, ()>;
+/// Whether the window is in focus, as the host last told us through
+/// `set_focused`. Hosts which never call it are always in focus.
+#[cfg(feature = "client")]
+static WINDOW_FOCUSED: AtomicBool = AtomicBool::new(true);
+
+/// Tells the client whether its window is in focus, or its tab visible.
+/// Out of focus, the game pauses, as frames may stop coming at any time;
+/// back in focus, it catches up with the server before predicting again.
+#[cfg(all(feature = "client", not(any(feature = "transport-udp", feature = "transport-websocket"))))]
+#[no_mangle]
+extern "C" fn set_focused(focused: bool) {
+	WINDOW_FOCUSED.store(focused, Ordering::Relaxed);
 }
 
-/// We'll use a double ended queue for serialization/deserialization, as the
-/// latter will happen by consuming the data from the front.
-pub type Buffer = VecDeque<u8>;
+/// Set by the host through `start_demo`, for the game loop to take.
+#[cfg(feature = "client")]
+static DEMO_REQUESTED: AtomicBool = AtomicBool::new(false);
 
-/// Represents all actions that a player may take.
-#[derive(Clone, Copy)]
-#[repr(u8)]
-enum PlayerIntent {
-	/// Player wants to move to the left.
-	MoveLeft = 0,
-	/// Player wants to move to the right.
-	MoveRight = 1,
-	/// Player wants to jump.
-	Jump = 2,
+/// Has the game play itself right away, as `--demo` does natively, rather
+/// than only once nobody's pressed a key for `DEMO_IDLE_TIMEOUT`. Any key
+/// hands control back, as usual.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn start_demo() {
+	DEMO_REQUESTED.store(true, Ordering::Relaxed);
 }
 
-impl NetType for PlayerIntent {
-	fn to_bytes(&self, buffer: &mut Buffer) {
-		buffer.push_back(*self as u8);
+/// Set by the host through `export_replay`, and taken care of by the game
+/// loop, which owns the recording.
+#[cfg(feature = "client")]
+static REPLAY_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks for everything recorded so far. The replay is handed over through
+/// `replay_exported` on the next frame.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn export_replay() {
+	REPLAY_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Hands the replay over to the host. The memory is only lent for the
+/// duration of the call, same as for `send_packet`.
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn save_replay(bytes: &[u8]) {
+	extern "C" {
+		fn replay_exported(
+			data_ptr: *const u8,
+			data_size: usize
+		);
+	}
+	unsafe {
+		replay_exported(bytes.as_ptr(), bytes.len());
 	}
+}
 
-	fn from_bytes(buffer: &mut Buffer) -> Result<Self, ()> {
-		let Some(tag) = buffer.pop_front() else { return Err(()) };
-		match tag {
-			0 => Ok(PlayerIntent::MoveLeft),
-			1 => Ok(PlayerIntent::MoveRight),
-			2 => Ok(PlayerIntent::Jump),
-			_ => Err(())
-		}
+/// Writes the replay to the file named by the `REPLAY_PATH` environment
+/// variable, `replay.cnr` by default.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn save_replay(bytes: &[u8]) {
+	let path = std::env::var("REPLAY_PATH").unwrap_or("replay.cnr".into());
+	match std::fs::write(&path, bytes) {
+		Ok(()) => eprintln!("replay of {} bytes written to {}", bytes.len(), path),
+		Err(error) => eprintln!("{}: {}", path, error),
 	}
 }
 
-/// Represents the effects after acting on an intent
-enum IntentEffects {
-	/// Player moved to some x, y position.
-	MoveTo((f32, f32)),
-	/// Player velocity was set.
-	SetVerticalVelocity(f32)
+/// The tick the host asked for through `debug_dump_tick`, for the game loop
+/// to dump on the next frame.
+#[cfg(all(feature = "client", feature = "serde", target_arch = "wasm32"))]
+static DUMP_REQUESTED: Mutex<Option<u64>> = Mutex::new(None);
+
+/// Asks for the tick at `index` as JSON, see `Game::debug_dump_tick`. The
+/// JSON is handed over through `tick_dumped` on the next frame, as `null`
+/// if there's no such tick.
+#[cfg(all(feature = "client", feature = "serde", target_arch = "wasm32"))]
+#[no_mangle]
+extern "C" fn debug_dump_tick(index: u64) {
+	*DUMP_REQUESTED.lock().unwrap() = Some(index);
 }
 
-impl IntentEffects {
-	pub const TAG_MOVE_TO: u8 = 0;
-	pub const TAG_SET_VERTICAL_VELOCITY: u8 = 1;
-	pub fn tag(&self) -> u8 {
-		match self {
-			IntentEffects::MoveTo(_) => Self::TAG_MOVE_TO,
-			IntentEffects::SetVerticalVelocity(_) => Self::TAG_SET_VERTICAL_VELOCITY,
-		}
+/// Hands a tick dumped as JSON over to the host. The memory is only lent
+/// for the duration of the call, same as for `send_packet`.
+#[cfg(all(feature = "client", feature = "serde", target_arch = "wasm32"))]
+fn dump_tick(game: &Game) {
+	extern "C" {
+		fn tick_dumped(
+			data_ptr: *const u8,
+			data_size: usize
+		);
+	}
+	let Some(index) = DUMP_REQUESTED.lock().unwrap().take() else {
+		return;
+	};
+	let json = game.debug_dump_tick(index).unwrap_or("null".into());
+	unsafe {
+		tick_dumped(json.as_ptr(), json.len());
 	}
 }
 
-impl NetType for IntentEffects {
-	fn to_bytes(&self, buffer: &mut Buffer) {
-		buffer.push_back(self.tag());
-		match self {
-			IntentEffects::MoveTo((x, y)) => {
-				buffer.extend(x.to_le_bytes());
-				buffer.extend(y.to_le_bytes());
-			},
-			IntentEffects::SetVerticalVelocity(v) => {
-				buffer.extend(v.to_le_bytes());
-			},
-		}
+/// Set by the host through `export_state`, and taken care of by the game
+/// loop, which owns the game.
+#[cfg(feature = "client")]
+static STATE_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// A state handed over through `import_state`, for the game loop to
+/// restore on the next frame.
+#[cfg(feature = "client")]
+static IMPORTED_STATE: Mutex<Option<SavedState>> = Mutex::new(None);
+
+/// Asks for the whole client state, see `Game::export_state`. The state is
+/// handed over through `state_exported` on the next frame.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn export_state() {
+	STATE_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Restores a state from `export_state`, passed in a buffer from
+/// `net_alloc`, on the next frame. Can be called before `start_game` too.
+/// Returns false, and logs why, if it isn't a state this build can
+/// restore, e.g. one saved with another protocol version.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+unsafe extern "C" fn import_state(data_ptr: *mut u8, data_size: usize) -> bool {
+	let Some(bytes) = take_net_buffer(data_ptr, data_size) else {
+		return false;
+	};
+	match SavedState::parse(&bytes) {
+		Ok(state) => {
+			*IMPORTED_STATE.lock().unwrap() = Some(state);
+			true
+		},
+		Err(error) => {
+			log!(log::LogLevel::Warn, "state_rejected bytes={} error=\"{}\"", bytes.len(), error);
+			false
+		},
 	}
+}
 
-	fn from_bytes(buffer: &mut Buffer) -> Result<Self, ()> {
-		let tag = buffer.pop_front().ok_or(())?;
-		match tag {
-			Self::TAG_MOVE_TO => {
-				let x: f32 = f32::from_le_bytes([
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-				]);
-				let y: f32 = f32::from_le_bytes([
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-				]);
-				Ok(IntentEffects::MoveTo((x, y)))
-			},
-			Self::TAG_SET_VERTICAL_VELOCITY => {
-				let v: f32 = f32::from_le_bytes([
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-					buffer.pop_front().ok_or(())?,
-				]);
-				Ok(IntentEffects::SetVerticalVelocity(v))
-			}
-			_ => Err(())
-		}
+/// Hands the state over to the host. The memory is only lent for the
+/// duration of the call, same as for `send_packet`.
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn save_state(bytes: &[u8]) {
+	extern "C" {
+		fn state_exported(
+			data_ptr: *const u8,
+			data_size: usize
+		);
+	}
+	unsafe {
+		state_exported(bytes.as_ptr(), bytes.len());
 	}
 }
 
-#[derive(Default)]
-struct Player {
-	x: f32,
-	y: f32,
-	last_tick_x: f32,
-	last_tick_y: f32,
-	vertical_velocity: f32,
-	grounded: bool,
-	color: Color,
+/// Writes the state to the file named by the `STATE_PATH` environment
+/// variable, `state.cns` by default.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn save_state(bytes: &[u8]) {
+	let path = std::env::var("STATE_PATH").unwrap_or("state.cns".into());
+	match std::fs::write(&path, bytes) {
+		Ok(()) => eprintln!("state of {} bytes written to {}", bytes.len(), path),
+		Err(error) => eprintln!("{}: {}", path, error),
+	}
 }
 
-impl Player {
-	const MOVE_SPEED: f32 = 200.0;
-	const WIDTH: f32 = 30.0;
-	const HEIGHT: f32 = 30.0;
+/// Set by the host through `export_diagnostics`, and taken care of by the
+/// game loop, like `STATE_REQUESTED`.
+#[cfg(feature = "client")]
+static DIAGNOSTICS_REQUESTED: AtomicBool = AtomicBool::new(false);
 
-	pub fn local() -> Self {
-		Self {
-			color: BLUE,
-			..Default::default()
-		}
-	}
+/// Asks for a diagnostic bundle, see `Game::export_diagnostics`, as of the
+/// next frame. It's handed over through `diagnostics_exported`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn export_diagnostics() {
+	DIAGNOSTICS_REQUESTED.store(true, Ordering::Relaxed);
+}
 
-	pub fn enemy() -> Self {
-		Self {
-			color: RED,
-			..Default::default()
-		}
+/// Hands a diagnostic bundle over to the host. The memory is only lent for
+/// the duration of the call, same as for `send_packet`.
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn save_diagnostics(bytes: &[u8]) {
+	extern "C" {
+		fn diagnostics_exported(
+			data_ptr: *const u8,
+			data_size: usize
+		);
+	}
+	unsafe {
+		diagnostics_exported(bytes.as_ptr(), bytes.len());
 	}
+}
+
+/// Writes a diagnostic bundle to the file named by the `DIAGNOSTICS_PATH`
+/// environment variable, `diagnostics.cnd` by default. The writing's left
+/// to a thread of its own, so the frame doesn't wait on the disk.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn save_diagnostics(bytes: &[u8]) {
+	let path = std::env::var("DIAGNOSTICS_PATH").unwrap_or("diagnostics.cnd".into());
+	let bytes = bytes.to_vec();
+	std::thread::spawn(move || match std::fs::write(&path, &bytes) {
+		Ok(()) => eprintln!("diagnostics of {} bytes written to {}", bytes.len(), path),
+		Err(error) => eprintln!("{}: {}", path, error),
+	});
+}
+
+/// How many confirmed ticks apart the host wants to hear about the game,
+/// through `on_state_changed`; zero for never.
+#[cfg(feature = "client")]
+static OBSERVER_INTERVAL: AtomicU32 = AtomicU32::new(0);
+
+/// Has `on_state_changed` called every `ticks` confirmed ticks from the
+/// next frame on, or not at all for zero. Can be called before
+/// `start_game` too.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn set_observer_interval(ticks: u32) {
+	OBSERVER_INTERVAL.store(ticks, Ordering::Relaxed);
+}
+
+/// Milliseconds a frame's ticks get to take, rollbacks included, before the
+/// rest waits for the next frame; zero for no limit. See
+/// `Game::set_tick_budget`.
+#[cfg(feature = "client")]
+static TICK_BUDGET_MS: AtomicU32 = AtomicU32::new(0);
+
+/// Limits how long a frame's ticks get to take to `ms` milliseconds, from
+/// the next frame on, for devices too slow to keep up with a long rollback
+/// in a single frame; zero, the default, lifts the limit. Can be called
+/// before `start_game` too.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn set_tick_budget(ms: u32) {
+	TICK_BUDGET_MS.store(ms, Ordering::Relaxed);
+}
 
-	pub fn draw(&self, smoothing: f32) {
-		let smooth_x = (1.0 - smoothing) * self.last_tick_x + self.x * smoothing;
-		let smooth_y = (1.0 - smoothing) * self.last_tick_y + self.y * smoothing;
+/// Whether the host wants to hear about the `SimEvent`s of every tick,
+/// through `on_sim_events`.
+#[cfg(feature = "client")]
+static SIM_EVENTS_FORWARDED: AtomicBool = AtomicBool::new(false);
+
+/// Has `on_sim_events` called once a frame with the events of the ticks
+/// simulated over it, if there were any, from the next frame on; or not
+/// at all anymore. Can be called before `start_game` too.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+extern "C" fn forward_sim_events(enabled: bool) {
+	SIM_EVENTS_FORWARDED.store(enabled, Ordering::Relaxed);
+}
 
-		draw_rectangle(
-			smooth_x,
-			smooth_y,
-			Self::WIDTH,
-			Self::HEIGHT,
-			self.color
+/// Hands a `StateSummary` over to the host, between frames. The memory is
+/// only lent for the duration of the call, same as for `send_packet`.
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn observe_state(bytes: &[u8]) {
+	extern "C" {
+		fn on_state_changed(
+			data_ptr: *const u8,
+			data_size: usize
 		);
 	}
-
-	pub fn snapshot_position(&mut self) {
-		self.last_tick_x = self.x;
-		self.last_tick_y = self.y;
+	unsafe {
+		on_state_changed(bytes.as_ptr(), bytes.len());
 	}
+}
 
-	pub fn update_physics(&mut self) {
-		self.y += self.vertical_velocity * TICK_DELTA * 10.0;
-		self.vertical_velocity += 9.81 * TICK_DELTA * 10.0;
+/// There's no host to hand the summary to, so it only gets logged.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn observe_state(bytes: &[u8]) {
+	log!(log::LogLevel::Debug, "state_summary bytes={}", bytes.len());
+}
 
-		if self.y >= SCREEN_SIZE as f32 - 30.0 {
-			self.y = SCREEN_SIZE as f32 - 30.0;
-			self.vertical_velocity = 0.0;
-			self.grounded = true;
-		}
+/// Hands a frame's worth of `TickEvent`s over to the host. The memory is
+/// only lent for the duration of the call, same as for `send_packet`.
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn forward_events(bytes: &[u8]) {
+	extern "C" {
+		fn on_sim_events(
+			data_ptr: *const u8,
+			data_size: usize
+		);
 	}
+	unsafe {
+		on_sim_events(bytes.as_ptr(), bytes.len());
+	}
+}
 
-	pub fn execute_intent(&mut self, intent: &PlayerIntent) {
-		match intent {
-			PlayerIntent::MoveLeft => {
-				self.x -= Self::MOVE_SPEED * TICK_DELTA;
-				self.x = self.x.clamp(0.0, SCREEN_SIZE as f32 - Self::WIDTH);
-			},
-			PlayerIntent::MoveRight => {
-				self.x += Self::MOVE_SPEED * TICK_DELTA;
-				self.x = self.x.clamp(0.0, SCREEN_SIZE as f32 - Self::WIDTH);
-			},
-			PlayerIntent::Jump => {
-				if self.grounded {
-					self.vertical_velocity = -50.0;
-					self.grounded = false;
-				}
-			},
-		}
+/// There's no host to hand the events to, so they only get logged.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn forward_events(bytes: &[u8]) {
+	log!(log::LogLevel::Debug, "sim_events bytes={}", bytes.len());
+}
+
+/// Key bindings handed over by the host, for the local player.
+#[cfg(feature = "client")]
+static KEY_BINDINGS: Mutex<Option<KeyBindings>> = Mutex::new(None);
+
+/// Replaces the local player's key bindings with ones written as described
+/// by `KeyBindings`, passed in a buffer from `net_alloc`. Meant to be called
+/// before `start_game`. Returns false, keeping the previous bindings, if
+/// they couldn't be parsed.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+unsafe extern "C" fn set_key_bindings(data_ptr: *mut u8, data_size: usize) -> bool {
+	let Some(bytes) = take_net_buffer(data_ptr, data_size) else {
+		return false;
+	};
+	match KeyBindings::parse(&String::from_utf8_lossy(&bytes)) {
+		Ok(bindings) => {
+			*KEY_BINDINGS.lock().unwrap() = Some(bindings);
+			true
+		},
+		Err(_) => false,
 	}
 }
 
-/// The ClientId is assigned to each player *by the server they connect to*.
-pub type ClientId = u8;
+/// The local player's name, as handed over by the host.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+static PLAYER_NAME: Mutex<Option<String>> = Mutex::new(None);
 
-/// A command frame is a collection of a player's intents, and their unique ClientId.
-#[derive(Clone)]
-struct CommandFrame {
-	owner: ClientId,
-	intents: Vec<PlayerIntent>
+/// Names the local player, with the name passed in a buffer from
+/// `net_alloc` as UTF-8. Meant to be called before `start_game`; names
+/// longer than `MAX_NAME_LEN` bytes get cut short.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+unsafe extern "C" fn set_player_name(data_ptr: *mut u8, data_size: usize) {
+	if let Some(bytes) = take_net_buffer(data_ptr, data_size) {
+		*PLAYER_NAME.lock().unwrap() = Some(String::from_utf8_lossy(&bytes).into_owned());
+	}
 }
 
-impl CommandFrame {
-	pub fn update_hasher(&self, hasher: &mut impl Digest) {
-		hasher.update(&[self.owner, self.intents.len() as u8]);
-		for intent in &self.intents {
-			hasher.update(&[*intent as u8]);
-		}
+/// Chat messages the host asked us to send, waiting for the next frame.
+#[cfg(feature = "client")]
+static OUTGOING_CHAT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Says something in the chat, as the local player. The message is passed
+/// in a buffer from `net_alloc`, as UTF-8, and gets sanitized the same as
+/// one typed in; see `chat::sanitize`.
+///
+/// # Safety
+/// Same as for `receive_packet`.
+#[cfg(all(feature = "client", not(feature = "transport-udp")))]
+#[no_mangle]
+unsafe extern "C" fn send_chat(data_ptr: *mut u8, data_size: usize) {
+	if let Some(bytes) = take_net_buffer(data_ptr, data_size) {
+		OUTGOING_CHAT.lock().unwrap().push(String::from_utf8_lossy(&bytes).into_owned());
 	}
 }
 
-impl NetType for CommandFrame {
-	fn to_bytes(&self, buffer: &mut Buffer) {
-		buffer.push_back(self.owner);
-		buffer.push_back(self.intents.len() as u8);
-		for intent in &self.intents {
-			intent.to_bytes(buffer);
-		}
+/// The local player's key bindings; the host's if it set any, otherwise
+/// read from the file named by the `KEY_BINDINGS` environment variable,
+/// falling back to the arrow keys.
+#[cfg(feature = "client")]
+fn key_bindings() -> KeyBindings {
+	if let Some(bindings) = KEY_BINDINGS.lock().unwrap().clone() {
+		return bindings;
 	}
+	let Some(path) = std::env::var_os("KEY_BINDINGS") else {
+		return KeyBindings::default();
+	};
+	let parsed = std::fs::read_to_string(&path)
+		.map_err(|x| x.to_string())
+		.and_then(|x| KeyBindings::parse(&x));
+	parsed.unwrap_or_else(|error| {
+		eprintln!("{}: {}, using the default key bindings", path.to_string_lossy(), error);
+		KeyBindings::default()
+	})
+}
+
+/// Adds an intent to the next tick predicted for a local player, on top of
+/// the keyboard's: 0 for the player started with `start_game`, 1 for the
+/// second one sharing the screen. The tag is one of `PlayerIntent`'s; ones
+/// needing a payload, like analog movement, are rejected.
+#[cfg(feature = "client")]
+#[no_mangle]
+extern "C" fn inject_intent(client_local_index: u8, intent_tag: u8) {
+	let Some(intent) = PlayerIntent::from_tag(intent_tag) else {
+		log!(log::LogLevel::Error, "intent_rejected local_index={} tag={}", client_local_index, intent_tag);
+		return;
+	};
+	input::inject(Injection::Intent(client_local_index as usize, intent));
+}
+
+/// Drops every injected intent which hasn't made it into a tick yet.
+#[cfg(feature = "client")]
+#[no_mangle]
+extern "C" fn clear_injected_intents() {
+	input::inject(Injection::Clear);
+}
 
-	fn from_bytes(buffer: &mut Buffer) -> Result<Self, ()> {
-		let Some(owner) = buffer.pop_front() else { return Err(()) };
-		let Some(len) = buffer.pop_front() else { return Err(()) };
-		let mut intents = Vec::new();
+/// Whether a local player, numbered as for `inject_intent`, still takes
+/// keyboard input, or only injected intents. Keyboard input is on by default.
+#[cfg(feature = "client")]
+#[no_mangle]
+extern "C" fn set_keyboard_input(client_local_index: u8, enabled: bool) {
+	input::inject(Injection::Keyboard(client_local_index as usize, enabled));
+}
 
-		for _ in 0..len {
-			let intent = PlayerIntent::from_bytes(buffer)?;
-			intents.push(intent);
+/// Hands whatever the host injected over to the local players' input.
+/// Returns whether there was anything.
+#[cfg(feature = "client")]
+fn apply_injections(inputs: &mut [(ClientId, InputState)]) -> bool {
+	let injections = input::take_injections();
+	let injected = !injections.is_empty();
+	for injection in injections {
+		let index = match injection {
+			Injection::Intent(index, _) | Injection::Keyboard(index, _) => index,
+			Injection::Clear => {
+				for (_, input) in inputs.iter_mut() {
+					input.clear_injected();
+				}
+				continue;
+			},
+		};
+		let Some((_, input)) = inputs.get_mut(index) else {
+			log!(log::LogLevel::Error, "injection_rejected local_index={} reason=no_such_player", index);
+			continue;
+		};
+		match injection {
+			Injection::Intent(_, intent) => input.inject(intent),
+			Injection::Keyboard(_, enabled) => input.set_keyboard(enabled),
+			Injection::Clear => {},
 		}
+	}
+	injected
+}
 
-		Ok(CommandFrame {
-			owner,
-			intents
-		})
+/// Offset between the ClientIds of the two players sharing a screen, far
+/// enough apart not to collide with the ids a server hands out first.
+#[cfg(feature = "client")]
+const SECOND_PLAYER_OFFSET: ClientId = 128;
+
+/// The window starts out `scale` times the size of the playfield, which is
+/// then scaled along with it; see `Viewport`.
+#[cfg(feature = "client")]
+fn window_conf(scale: u8) -> Conf {
+	Conf {
+		window_width: SCREEN_SIZE * scale as i32,
+		window_height: SCREEN_SIZE * scale as i32,
+		window_resizable: true,
+		..Default::default()
 	}
 }
 
-/// An ordinally indexed collection of CommandFrames, with a SHA256 checksum.
-#[derive(Clone)]
-struct Tick {
-	index: u64,
-	command_frames: Vec<CommandFrame>,
-	hash: [u8; 32]
+/// Reads the replay at `path`. Exits if it can't be played back, rather than
+/// running a game nobody asked for.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn read_replay(path: &str) -> Replay {
+	let bytes = std::fs::read(path).unwrap_or_else(|error| {
+		eprintln!("{}: {}", path, error);
+		std::process::exit(1);
+	});
+	Replay::parse(&bytes).unwrap_or_else(|error| {
+		eprintln!("{}: {}", path, error);
+		std::process::exit(1);
+	})
 }
 
-impl Tick {
-	fn new(index: u64, command_frames: Vec<CommandFrame>) -> Self {
-		let mut tick = Tick {
-			index,
-			command_frames,
-			hash: [0; 32]
-		};
-		tick.recalculate_hash();
-		tick
+/// The ClientId of a second player on WASD, when asked to share the screen.
+/// The first one keeps the arrow keys.
+/// What players not given a name go by.
+#[cfg(feature = "client")]
+fn default_name(client_id: ClientId) -> String {
+	format!("Player {}", client_id)
+}
+
+#[cfg(feature = "client")]
+fn second_local_player(options: &Options, client_id: ClientId) -> Option<ClientId> {
+	options.shared_screen.then_some(client_id.wrapping_add(SECOND_PLAYER_OFFSET))
+}
+
+/// Runs against an in-process server, with a second player occasionally
+/// changing direction, to cause the odd misprediction. The network starts out
+/// perfect, see `adjust_conditions` for making it worse.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn run_loopback(options: Options) {
+	let schedule = vec![
+		(0, vec![PlayerIntent::MoveRight]),
+		(40, vec![PlayerIntent::MoveLeft, PlayerIntent::JumpStart]),
+		(45, vec![PlayerIntent::MoveLeft]),
+		(80, vec![]),
+		(100, vec![PlayerIntent::JumpStart]),
+		(101, vec![PlayerIntent::MoveRight, PlayerIntent::JumpHold]),
+		(107, vec![PlayerIntent::MoveRight]),
+		(140, vec![PlayerIntent::MoveLeft]),
+	];
+	let client_id = options.client_id.unwrap_or_default();
+	let mut server = transport::LoopbackTransport::new(client_id)
+		.with_tick_rate(options.tick_rate)
+		.with_clock(get_time)
+		.with_reliability(options.reliable_config())
+		.with_remote(client_id.wrapping_add(1), schedule);
+	if let Some(second) = second_local_player(&options, client_id) {
+		server = server.with_local(second);
 	}
+	let transport = transport::SimulatedTransport::new(
+		server,
+		SimulatedConditions::default(),
+		0x5eed
+	);
+	Window::from_config(window_conf(options.scale), amain(options, transport));
+}
 
-	fn recalculate_hash(&mut self) {
-		let mut hasher = Sha256::new();
-		hasher.update(self.index.to_le_bytes());
-		hasher.update(&[self.command_frames.len() as u8]);
-		for command_frame in &self.command_frames {
-			command_frame.update_hasher(&mut hasher);
-		}
-		self.hash = hasher.finalize().into();
+/// Runs a native client, configured through the command line; see
+/// `cli::usage`. Bad arguments print the usage, and exit with 2.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn main() {
+	let options = match cli::parse(std::env::args().skip(1)) {
+		Ok(options) => options,
+		Err(CliError::Help) => {
+			print!("{}", cli::usage());
+			return;
+		},
+		Err(error) => {
+			eprintln!("{}\n\n{}", error, cli::usage());
+			std::process::exit(2);
+		},
+	};
+
+	match &options.mode {
+		Mode::Loopback => run_loopback(options),
+		Mode::Replay(path) => Window::from_config(window_conf(options.scale), amain_replay(read_replay(path))),
+		#[cfg(feature = "transport-udp")]
+		Mode::Connect { relay, bind } => {
+			let transport = transport::UdpTransport::bind(bind, relay).unwrap_or_else(|error| {
+				eprintln!("couldn't set up a UDP socket on {} for {}: {}", bind, relay, error);
+				std::process::exit(1);
+			});
+			Window::from_config(window_conf(options.scale), amain(options, transport));
+		},
 	}
 }
 
-impl NetType for Tick {
-	fn to_bytes(&self, buffer: &mut Buffer) {
-		for byte in self.index.to_le_bytes() {
-			buffer.push_back(byte);
-		}
-		buffer.push_back(self.command_frames.len() as u8);
-		for command_frame in &self.command_frames {
-			command_frame.to_bytes(buffer);
+// The game is started by the host through `start_game` instead.
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn main() { }
+
+/// Advances a game without any window, host or server, driven by a script,
+/// then prints a hash of the resulting world. Running the same script twice,
+/// or on different machines, should always print the same hash.
+///
+/// Takes the path to the script, and optionally how many ticks to run for,
+/// 500 by default. Every line of the script holds a tick index, followed by
+/// the intents (`left`, `right`, `jump`, `hold` to hold the jump, or
+/// `move:<magnitude>` for analog
+/// movement) the player switches to on that tick, and keeps until the next
+/// line. Lines starting with `#` are ignored.
+///
+/// `--demo` in place of the path plays the demo the client plays while
+/// left alone instead, see `DemoScript`.
+#[cfg(not(feature = "client"))]
+fn main() {
+	let mut args = std::env::args().skip(1);
+	let Some(path) = args.next() else {
+		eprintln!("usage: command-networking-rs <script | --demo> [ticks]");
+		std::process::exit(2);
+	};
+	let ticks: u64 = args.next()
+		.map(|x| x.parse().expect("the tick count has to be a number"))
+		.unwrap_or(500);
+
+	let schedule = match path.as_str() {
+		"--demo" => {
+			let demo = DemoScript::default();
+			(0..ticks).map(|x| (x, demo.intents(x).to_vec())).collect()
+		},
+		_ => {
+			let script = std::fs::read_to_string(&path).expect("couldn't read the script");
+			parse_script(&script).unwrap_or_else(|error| {
+				eprintln!("{}: {}", path, error);
+				std::process::exit(1);
+			})
+		},
+	};
+
+	let mut game = Game::new(0);
+	let mut intents = vec![];
+	for tick_index in 0..ticks {
+		if let Some(scheduled) = schedule.get(&tick_index) {
+			intents = scheduled.clone();
 		}
+		let tick = game.predict_tick(intents.clone());
+		game.advance(tick);
 	}
 
-	fn from_bytes(buffer: &mut Buffer) -> Result<Self, ()> {
-		let index: Option<u64> = {Some(u64::from_le_bytes([
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-			buffer.pop_front().ok_or(())?,
-		]))};
-		let Some(index) = index else { return Err(()) };
-		let Some(len) = buffer.pop_front() else { return Err(()) };
+	let mut writer = ByteWriter::new();
+	game.snapshot().to_bytes(&mut writer).expect("the snapshot couldn't be written");
+	let hash: String = Sha256::digest(writer.as_slice())
+		.iter()
+		.map(|x| format!("{:02x}", x))
+		.collect();
+	println!("{}", hash);
+}
 
-		let mut command_frames = Vec::new();
-		for _ in 0..len {
-			let command_frame = CommandFrame::from_bytes(buffer)?;
-			command_frames.push(command_frame);
+/// Parses a script for the headless mode, see `main`.
+#[cfg(not(feature = "client"))]
+fn parse_script(script: &str) -> Result<BTreeMap<u64, Vec<PlayerIntent>>, String> {
+	let mut schedule = BTreeMap::new();
+	for (number, line) in script.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
 		}
 
-		let mut tick = Tick::new(
-			index,
-			command_frames
-		);
-		tick.recalculate_hash();
-
-		Ok(tick)
+		let mut words = line.split_whitespace();
+		let tick_index = words.next()
+			.and_then(|x| x.parse().ok())
+			.ok_or(format!("line {} doesn't start with a tick index", number + 1))?;
+		let intents = words
+			.map(|x| match x {
+				"left" => Ok(PlayerIntent::MoveLeft),
+				"right" => Ok(PlayerIntent::MoveRight),
+				"jump" => Ok(PlayerIntent::JumpStart),
+				"hold" => Ok(PlayerIntent::JumpHold),
+				_ => match x.strip_prefix("move:").map(|x| x.parse::<i8>()) {
+					Some(Ok(magnitude)) => Ok(PlayerIntent::MoveHorizontal(magnitude)),
+					_ => Err(format!("unknown intent {:?} on line {}", x, number + 1)),
+				},
+			})
+			.collect::<Result<Vec<PlayerIntent>, String>>()?;
+		schedule.insert(tick_index, intents);
 	}
+	Ok(schedule)
 }
 
-/// A structure representing the local gamestate.
-struct Game {
-	/// ClientId denoting the local player
-	client_id: ClientId,
-	/// A map of all players and their respective ClientIds.
-	players: HashMap<ClientId, Player>,
-	/// All ticks processed by the client locally. Includes predicted ticks.
-	ticks: Vec<Tick>,
-	/// Index into ticks denoting the latest tick confirmed "correct" by the server.
-	accepted_head: u64,
-}
+#[cfg(feature = "client")]
+async fn amain(options: Options, transport: impl Transport) {
+	// Whichever transport it is, chat and the like have to make it across.
+	let mut transport = transport::ReliableTransport::new(transport, options.reliable_config());
+	let mut accumulator = TickAccumulator::default();
+	// Reused for every outgoing packet, rather than allocating one per tick.
+	let mut send_buffer = ByteWriter::new();
+	// Allocated up front, so that capturing diagnostics doesn't have to.
+	let mut diagnostics = ByteWriter::with_capacity(DIAGNOSTICS_CAPACITY);
+	let mut frame = 0u64;
+	let mut overlay = Overlay::default();
+	let mut stats = DebugStats::default();
+	let mut chat_input = ChatInput::default();
+	let mut demo = Demo::new(DemoScript::default(), get_time());
+	if options.demo {
+		demo.start();
+	}
+	if let Some(ms) = options.tick_budget_ms {
+		TICK_BUDGET_MS.store(ms, Ordering::Relaxed);
+	}
 
-impl Game {
-	fn poll_intents(&self) -> Vec<PlayerIntent> {
-		let mut intents = vec![];
+	let request = ConnectRequest { requested_id: options.client_id, name: options.name.clone() };
+	let mut game = Game::connecting(request, options.spectator);
+	game.set_tick_rate(options.tick_rate);
+	game.sync_clock(options.interp_margin);
+	game.set_redundant_frames(options.redundant_frames);
+	game.start_recording();
+	// Filled in once the server tells us who the local players are.
+	let mut inputs = vec![];
 
-		if is_key_down(KeyCode::Up) {
-			intents.push(PlayerIntent::Jump);
+	loop {
+		frame += 1;
+		let focused = WINDOW_FOCUSED.load(Ordering::Relaxed);
+		if !focused || get_frame_time() > MAX_FRAME_GAP {
+			game.pause();
 		}
-
-		if is_key_down(KeyCode::Left) {
-			intents.push(PlayerIntent::MoveLeft);
+		// Time spent paused isn't owed any ticks; the server's catch us up
+		// instead, and the accumulator starts over from there.
+		let mut frame_time = get_frame_time();
+		if game.is_paused() {
+			frame_time = 0.0;
+			if focused {
+				game.resume();
+				accumulator.reset();
+			}
 		}
+		// Pacing only changes how often ticks run, never how long they are.
+		let due_ticks = accumulator.advance(frame_time * game.tick_speed(), game.tick_rate().delta());
 
-		if is_key_down(KeyCode::Right) {
-			intents.push(PlayerIntent::MoveRight);
+		if let Some(state) = IMPORTED_STATE.lock().unwrap().take() {
+			game.restore_state(state);
 		}
 
-		intents
-	}
-
-	fn simulate(&mut self, tick: &Tick) {
-		for (_, player) in &mut self.players {
-			player.snapshot_position();
+		transport.poll(&mut |packet| push_packet(packet.to_vec()));
+		drain_inbox(&mut game, |packet| stats.received.record(packet.len()));
+		for event in transport.poll_events() {
+			game.handle_transport_event(event);
 		}
 
-		for frame in &tick.command_frames {
-			let player = self.players.entry(frame.owner).or_insert(Player::enemy());
-			for intent in &frame.intents {
-				player.execute_intent(intent);
+		// Rollbacks count towards the budget as much as new ticks do.
+		let tick_budget = TICK_BUDGET_MS.load(Ordering::Relaxed);
+		game.set_tick_budget((tick_budget > 0).then(|| TickBudget::from_millis(tick_budget, get_time)));
+		game.start_frame();
+		game.receive(get_time());
+		if inputs.is_empty() && !game.is_connecting() && game.rejection().is_none() {
+			let client_id = game.client_id();
+			inputs.push((client_id, InputState::new(key_bindings())));
+			if game.name(client_id).is_none() {
+				game.set_name(client_id, &default_name(client_id));
+			}
+			if let Some(second) = second_local_player(&options, client_id) {
+				game.add_local_player(second);
+				game.set_name(second, &default_name(second));
+				inputs.push((second, InputState::new(KeyBindings::wasd())));
 			}
 		}
 
-		for (_, player) in &mut self.players {
-			player.update_physics();
+		// Enter sends the message being typed, rather than joining.
+		let composing = chat_input.is_composing();
+		let mut chat: Vec<String> = OUTGOING_CHAT.lock().unwrap().drain(..).collect();
+		chat.extend(chat_input.update());
+		// Held on to until the chat sent so far gets through.
+		if transport.is_congested() {
+			OUTGOING_CHAT.lock().unwrap().append(&mut chat);
+		}
+		// Whatever the transport turned down last frame goes first.
+		game.resend(&mut transport, get_time());
+		let mut messages: Vec<_> = game.connect_request(get_time()).into_iter().collect();
+		messages.extend(game.take_introductions());
+		messages.extend(game.time_request(get_time()));
+		messages.extend(game.snapshot_request(get_time()));
+		messages.extend(chat.iter().filter_map(|x| game.say(x)));
+		for packet in messages {
+			send_buffer.clear();
+			if let Err(error) = packet.to_bytes(&mut send_buffer) {
+				log!(log::LogLevel::Warn, "encode_failed tag={} error=\"{}\"", packet.tag(), error);
+				continue;
+			}
+			game.send(&mut transport, send_buffer.as_slice(), get_time());
+			stats.sent.record(send_buffer.len());
 		}
-	}
-
-	fn predict_tick(&self) -> Tick {
-		// Poll local intents and construct a command frame
-		let intents = self.poll_intents();
-		let local_frame = CommandFrame {
-			owner: self.client_id,
-			intents
-		};
 
-		// Predict player intents for the upcoming tick.
-		// A good enough heuristic is simply repeating whatever they were doing
-		// last tick.
-		let Some(previous_tick) = self.ticks.last() else {
-			return Tick::new(0, vec![local_frame]);
+		let injected = apply_injections(&mut inputs);
+		demo.update(injected, get_time());
+		if DEMO_REQUESTED.swap(false, Ordering::Relaxed) {
+			demo.start();
+		}
+		// Whatever went down since the last frame makes it into the first
+		// tick due, if there is one, however late into it that is.
+		let progress = match due_ticks {
+			0 => accumulator.smoothing(game.tick_rate().delta()),
+			_ => 1.0,
 		};
+		for (_, input) in &mut inputs {
+			input.suspend(chat_input.is_composing());
+			input.update(progress);
+		}
 
-		let mut anticipated_frames: Vec<CommandFrame> = previous_tick
-			.command_frames
-			.iter()
-			.filter(|x| x.owner != self.client_id)
-			.map(|x| x.clone())
-			.collect();
+		overlay.update();
+		if !composing && is_key_pressed(KeyCode::I) {
+			game.toggle_remote_presentation();
+		}
+		if !composing && is_key_pressed(KeyCode::P) {
+			game.cycle_prediction_strategy();
+		}
+		if !composing && is_key_pressed(KeyCode::K) {
+			game.toggle_input_display();
+		}
+		if !composing && is_key_pressed(KeyCode::LeftBracket) {
+			game.set_render_delay(game.render_delay().saturating_sub(1));
+		}
+		if !composing && is_key_pressed(KeyCode::RightBracket) {
+			game.set_render_delay(game.render_delay().saturating_add(1));
+		}
+		if !composing && is_key_pressed(KeyCode::Enter) {
+			game.join();
+		}
+		if is_key_pressed(KeyCode::F2) || REPLAY_REQUESTED.swap(false, Ordering::Relaxed) {
+			if let Some(bytes) = game.export_replay() {
+				save_replay(&bytes);
+			}
+		}
+		#[cfg(all(feature = "serde", target_arch = "wasm32"))]
+		dump_tick(&game);
+		if is_key_pressed(KeyCode::F4) || DIAGNOSTICS_REQUESTED.swap(false, Ordering::Relaxed) {
+			diagnostics.clear();
+			match game.export_diagnostics(&mut diagnostics, frame, get_time()) {
+				Ok(()) => save_diagnostics(diagnostics.as_slice()),
+				Err(error) => log!(log::LogLevel::Warn, "diagnostics_failed error=\"{}\"", error),
+			}
+		}
+		if STATE_REQUESTED.swap(false, Ordering::Relaxed) {
+			match game.export_state() {
+				Ok(bytes) => save_state(&bytes),
+				Err(error) => log!(log::LogLevel::Warn, "state_export_failed error=\"{}\"", error),
+			}
+		}
+		if let Some(conditions) = transport.simulated_conditions().filter(|_| !composing) {
+			adjust_conditions(conditions);
+		}
 
-		anticipated_frames.push(local_frame);
+		// Anything over the catch-up budget is dropped, rather than simulated.
+		let skipped_ticks = due_ticks.saturating_sub(MAX_CATCH_UP_TICKS);
+		game.skip_ticks(skipped_ticks);
+
+		// Timing is left out altogether while nobody's looking at it.
+		let timing = overlay.is_visible();
+		let mut ticks_run = 0;
+		for tick in 0..due_ticks - skipped_ticks {
+			// Past the tick budget, the rest are dropped as well; the first
+			// one always runs, so that the game keeps going however slow.
+			if tick > 0 && game.is_over_budget() {
+				game.skip_ticks(due_ticks - skipped_ticks - tick);
+				break;
+			}
+			let started = timing.then(get_time);
+			for (owner, input) in &inputs {
+				game.set_input_offset(*owner, input.offset());
+			}
+			let packet = game.run_tick(|owner| {
+				let Some(position) = inputs.iter().position(|(client_id, _)| *client_id == owner) else {
+					return vec![];
+				};
+				let input = &mut inputs[position].1;
+				// The demo only ever plays as the first local player.
+				if position == 0 {
+					demo.drive(input);
+				}
+				input.drain()
+			});
+
+			let simulated = timing.then(get_time);
+			let Some(packet) = packet else {
+				// Idle, the tick still got simulated, and merely not sent.
+				if game.is_idle() && !game.is_stalled() {
+					ticks_run += 1;
+					continue;
+				}
+				break;
+			};
+			ticks_run += 1;
 
-		Tick::new(previous_tick.index + 1, anticipated_frames)
-	}
+			// Send the proposed tick to the server
+			// A tick that can't be sent is still predicted, and gets put
+			// right by the server's version of it.
+			send_buffer.clear();
+			if let Err(error) = packet.to_bytes(&mut send_buffer) {
+				log!(log::LogLevel::Warn, "encode_failed tag={} error=\"{}\"", packet.tag(), error);
+				continue;
+			}
+			if let (Some(started), Some(simulated)) = (started, simulated) {
+				stats.timings.simulation.push(((simulated - started) * 1000.0) as f32);
+				stats.timings.serialization.push(((get_time() - simulated) * 1000.0) as f32);
+			}
+			game.send(&mut transport, send_buffer.as_slice(), get_time());
+			game.tick_sent(get_time());
+			stats.sent.record(send_buffer.len());
+			stats.last_tick_size = send_buffer.len();
+		}
+		// Everything sent over the frame goes out in one go.
+		game.flush(&mut transport, get_time());
+		#[cfg(debug_assertions)]
+		game.assert_within_budget();
+
+		// Only now that the frame's ticks are through, rollbacks and all.
+		game.set_observer_interval(OBSERVER_INTERVAL.load(Ordering::Relaxed));
+		if let Some(summary) = game.take_state_summary() {
+			send_buffer.clear();
+			match summary.to_bytes(&mut send_buffer) {
+				Ok(()) => observe_state(send_buffer.as_slice()),
+				Err(error) => log!(log::LogLevel::Warn, "state_summary_failed error=\"{}\"", error),
+			}
+		}
+		// Taken whether the host wants them or not, so they don't pile up.
+		let events = game.take_sim_events();
+		if SIM_EVENTS_FORWARDED.load(Ordering::Relaxed) && !events.is_empty() {
+			send_buffer.clear();
+			match events.to_bytes(&mut send_buffer) {
+				Ok(()) => forward_events(send_buffer.as_slice()),
+				Err(error) => log!(log::LogLevel::Warn, "sim_events_failed error=\"{}\"", error),
+			}
+		}
+		if timing {
+			stats.timings.ticks_per_frame.push(ticks_run as f32);
+		}
 
-	fn print_debug(&self) {
-		draw_text(&format!("Client ID: {}", self.client_id), 10.0, 20.0, 16.0, RED);
-		if let Some(tick) = self.ticks.last() {
-			draw_text(&format!("Local tick index: {}", tick.index), 10.0, 35.0, 16.0, RED);
-			draw_text(&format!("Confirmed tick index: {}", self.accepted_head), 10.0, 50.0, 16.0, RED);
-			draw_text(&format!("Running {} ticks ahead of server", tick.index - self.accepted_head), 10.0, 65.0, 16.0, RED);
+		Viewport::current().begin_frame(BACKGROUND_COLOR);
+		// While stalled there's no next tick to interpolate towards.
+		let smoothing = match game.is_stalled() {
+			true => 1.0,
+			false => accumulator.smoothing(game.tick_rate().delta()),
+		};
+		game.present(smoothing);
+		game.draw_status();
+
+		if overlay.is_visible() {
+			let now = get_time();
+			stats.sent.update(now);
+			stats.received.update(now);
+			stats.smoothing = smoothing;
+			stats.network = transport.simulated_conditions().map(|x| x.to_string());
+			game.update_debug_stats(&mut stats, now);
+			overlay.draw(&stats);
 		}
-	}
-}
+		game.draw_chat();
+		chat_input.draw();
+		demo.draw();
 
-#[cfg(target_os = "windows")]
-fn main() {
-	Window::from_config(Conf {
-		window_width: SCREEN_SIZE,
-		window_height: SCREEN_SIZE,
-		window_resizable: false,
-		..Default::default()
-	}, amain(0));
+		next_frame().await;
+	}
 }
 
-#[cfg(not(target_os = "windows"))]
-fn main() { }
-
-async fn amain(client_id: u8) {
-	let mut tick_time = 0.0;
-
-	let mut game = Game {
-		client_id,
-		players: HashMap::new(),
-		ticks: Vec::new(),
-		accepted_head: 0
-	};
-
-	game.players.insert(
-		client_id,
-		Player::local()
-	);
+/// Plays a replay back at the tick rate it was recorded at. Space pauses,
+/// the right and left arrows step a tick forward or back, up and down seek
+/// five seconds ahead or back, and Home starts over.
+#[cfg(feature = "client")]
+async fn amain_replay(replay: Replay) {
+	let last = replay.tick_range().map(|(_, last)| last);
+	let mut playback = Playback::new(replay);
+	let tick_rate = playback.game().tick_rate();
+	let seek_distance = 5 * tick_rate.hz() as u64;
+	let mut accumulator = TickAccumulator::default();
+	let mut paused = false;
 
 	loop {
-		tick_time += get_frame_time();
-
-		while tick_time >= TICK_DELTA {
-			let tick_to_propose: Tick = game.predict_tick();
+		let due_ticks = accumulator.advance(get_frame_time(), tick_rate.delta());
+		let current = playback.tick_index();
 
-			// Send the proposed tick to the server
-			unsafe {
-				let mut tick_buffer = Buffer::new();
-				tick_to_propose.to_bytes(&mut tick_buffer);
-				tick_buffer.make_contiguous();
-
-				send_predicted_tick(
-					tick_buffer.as_mut_slices().0.as_mut_ptr(),
-					tick_buffer.len()
-				);
+		if is_key_pressed(KeyCode::Space) {
+			paused = !paused;
+		}
+		if is_key_pressed(KeyCode::Right) {
+			paused = true;
+			playback.step();
+		}
+		if is_key_pressed(KeyCode::Left) {
+			paused = true;
+			if let Some(index) = current {
+				playback.seek(index.saturating_sub(1));
 			}
+		}
+		if is_key_pressed(KeyCode::Up) {
+			playback.seek(current.map_or(0, |x| x + seek_distance));
+		}
+		if is_key_pressed(KeyCode::Down) {
+			if let Some(index) = current {
+				playback.seek(index.saturating_sub(seek_distance));
+			}
+		}
+		if is_key_pressed(KeyCode::Home) {
+			playback.restart();
+		}
 
-			// Execute the proposed tick locally, anticipating that it's a correct prediction
-			game.simulate(&tick_to_propose);
-
-			// Add the tick to the local tick list
-			game.ticks.push(tick_to_propose);
-
-			tick_time -= TICK_DELTA;
+		if !paused {
+			for _ in 0..due_ticks.min(MAX_CATCH_UP_TICKS) {
+				if !playback.step() {
+					break;
+				}
+			}
 		}
 
-		clear_background(BACKGROUND_COLOR);
-		present(&mut game, tick_time);
+		Viewport::current().begin_frame(BACKGROUND_COLOR);
+		let smoothing = match paused || playback.is_finished() {
+			true => 1.0,
+			false => accumulator.smoothing(tick_rate.delta()),
+		};
+		playback.game_mut().present(smoothing);
 
-		game.print_debug();
+		let position = match (playback.tick_index(), last) {
+			(Some(index), Some(last)) => format!("tick {} / {}", index, last),
+			(None, Some(last)) => format!("start / {}", last),
+			(_, None) => "no ticks recorded".to_string(),
+		};
+		let state = match (paused, playback.is_finished()) {
+			(true, _) => " (paused)",
+			(false, true) => " (finished)",
+			(false, false) => "",
+		};
+		draw_text(&format!("Replay: {}{}", position, state), 10.0, 20.0, 16.0, RED);
+		draw_text("Space: pause, arrows: step/seek, Home: restart", 10.0, 35.0, 16.0, RED);
 
 		next_frame().await;
 	}
 }
 
-fn present(game: &mut Game, tick_time: f32) {
-	let smoothing = tick_time / TICK_DELTA;
+/// Cycles the simulated network conditions through a few presets: L for
+/// packet loss, J for latency and jitter, R for reordering and duplication.
+/// B has every send turned down, or taken again.
+#[cfg(feature = "client")]
+fn adjust_conditions(conditions: &mut SimulatedConditions) {
+	fn next<T: Copy + PartialEq>(presets: &[T], current: T) -> T {
+		let position = presets.iter().position(|x| *x == current);
+		presets[position.map_or(0, |x| (x + 1) % presets.len())]
+	}
 
-	for (_, player) in &game.players {
-		player.draw(smoothing);
+	if is_key_pressed(KeyCode::L) {
+		conditions.loss_percent = next(&[0, 10, 25, 50], conditions.loss_percent);
+	}
+	if is_key_pressed(KeyCode::J) {
+		let presets = [(0, 0), (50, 10), (150, 50), (300, 100)];
+		(conditions.latency_ms, conditions.jitter_ms) = next(&presets, (conditions.latency_ms, conditions.jitter_ms));
+	}
+	if is_key_pressed(KeyCode::R) {
+		let presets = [(0, 0), (5, 5), (20, 20)];
+		(conditions.duplicate_percent, conditions.reorder_percent) = next(&presets, (conditions.duplicate_percent, conditions.reorder_percent));
+	}
+	if is_key_pressed(KeyCode::B) {
+		conditions.rejecting = !conditions.rejecting;
 	}
 }
\ No newline at end of file
@@ -0,0 +1,487 @@
+//! Byte-format serialization of everything sent over the wire.
+
+/// This trait defines the methods that must be implemented by all types
+/// which will be sent over the wire. It's just byte-format serialization.
+pub trait NetType: Sized {
+	/// Fails on values the other end would refuse to decode, like frames
+	/// with more intents than the protocol allows for, rather than sending
+	/// them anyway. Whatever got written up to that point is left in
+	/// `writer`.
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError>;
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError>;
+
+	/// Reads a value straight out of `bytes`, along with how many of them it
+	/// took up. Whatever follows is left alone.
+	fn from_slice(bytes: &[u8]) -> Result<(Self, usize), DecodeError> {
+		let mut reader = ByteReader::new(bytes);
+		let value = Self::from_bytes(&mut reader)?;
+		Ok((value, reader.position()))
+	}
+
+	/// About how many bytes `to_bytes` is going to write, so that buffers
+	/// can be reserved up front. Doesn't have to be exact; none by default.
+	fn byte_len_hint(&self) -> usize {
+		0
+	}
+}
+
+/// Serialization target; a contiguous, growable byte buffer.
+#[derive(Default)]
+pub struct ByteWriter {
+	bytes: Vec<u8>,
+}
+
+impl ByteWriter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self {
+			bytes: Vec::with_capacity(capacity)
+		}
+	}
+
+	/// Makes room for at least `additional` more bytes.
+	pub fn reserve(&mut self, additional: usize) {
+		self.bytes.reserve(additional);
+	}
+
+	pub fn write_u8(&mut self, value: u8) {
+		self.bytes.push(value);
+	}
+
+	pub fn write_u64_le(&mut self, value: u64) {
+		self.write_bytes(&value.to_le_bytes());
+	}
+
+	/// Writes an unsigned LEB128 varint; 7 bits per byte, with the high
+	/// bit set on every byte but the last.
+	pub fn write_varint(&mut self, mut value: u64) {
+		while value >= 0x80 {
+			self.write_u8(value as u8 | 0x80);
+			value >>= 7;
+		}
+		self.write_u8(value as u8);
+	}
+
+	/// How many bytes `write_varint` takes for `value`.
+	pub fn varint_len(value: u64) -> usize {
+		(64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+	}
+
+	pub fn write_bytes(&mut self, bytes: &[u8]) {
+		self.bytes.extend_from_slice(bytes);
+	}
+
+	pub fn as_slice(&self) -> &[u8] {
+		&self.bytes
+	}
+
+	pub fn len(&self) -> usize {
+		self.bytes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.bytes.is_empty()
+	}
+
+	/// Drops everything past the first `len` bytes, say to take back a
+	/// value that failed partway through being written.
+	pub fn truncate(&mut self, len: usize) {
+		self.bytes.truncate(len);
+	}
+
+	pub fn clear(&mut self) {
+		self.bytes.clear();
+	}
+}
+
+/// Deserialization source; reads from a borrowed byte slice, front to back.
+pub struct ByteReader<'a> {
+	bytes: &'a [u8],
+	position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+	pub fn new(bytes: &'a [u8]) -> Self {
+		Self {
+			bytes,
+			position: 0
+		}
+	}
+
+	/// How many bytes have been read so far.
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	/// How many bytes are left to be read.
+	pub fn remaining(&self) -> usize {
+		self.bytes.len() - self.position
+	}
+
+	pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+		let [value] = self.read_array()?;
+		Ok(value)
+	}
+
+	pub fn read_u64_le(&mut self) -> Result<u64, DecodeError> {
+		Ok(u64::from_le_bytes(self.read_array()?))
+	}
+
+	pub fn read_varint(&mut self) -> Result<u64, DecodeError> {
+		let mut value = 0u64;
+		for shift in (0..64).step_by(7) {
+			let byte = self.read_u8()?;
+			let bits = (byte & 0x7f) as u64;
+
+			// The tenth byte may only carry the single remaining bit.
+			if shift == 63 && bits > 1 {
+				return Err(DecodeError::VarintOverflow);
+			}
+			value |= bits << shift;
+
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(DecodeError::VarintOverflow)
+	}
+
+	/// Reads a collection's length prefix. Fails if it's over `max`, or
+	/// larger than what's left could hold, as every element takes up at
+	/// least one byte; either way, before anything gets allocated for it.
+	pub fn read_len(&mut self, max: usize, what: &'static str) -> Result<usize, DecodeError> {
+		let len = usize::try_from(self.read_varint()?).unwrap_or(usize::MAX);
+		if len > max {
+			return Err(DecodeError::LimitExceeded { what, len, max });
+		}
+		if len > self.remaining() {
+			return Err(DecodeError::LengthOutOfBounds {
+				len,
+				remaining: self.remaining()
+			});
+		}
+		Ok(len)
+	}
+
+	/// Reads a length prefixed collection of up to `max` elements.
+	pub fn read_vec<T: NetType>(&mut self, max: usize, what: &'static str) -> Result<Vec<T>, DecodeError> {
+		let len = self.read_len(max, what)?;
+		let mut items = Vec::with_capacity(len);
+		for _ in 0..len {
+			items.push(T::from_bytes(self)?);
+		}
+		Ok(items)
+	}
+
+	/// Reads a length prefixed UTF-8 string of up to `max` bytes.
+	pub fn read_string(&mut self, max: usize, what: &'static str) -> Result<String, DecodeError> {
+		let len = self.read_len(max, what)?;
+		let bytes = self.read_exact(len)?;
+		std::str::from_utf8(bytes)
+			.map(String::from)
+			.map_err(|_| DecodeError::InvalidUtf8 { what })
+	}
+
+	pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+		let mut bytes = [0; N];
+		bytes.copy_from_slice(self.read_exact(N)?);
+		Ok(bytes)
+	}
+
+	/// Consumes exactly `len` bytes, failing without consuming anything
+	/// if there aren't enough left.
+	pub fn read_exact(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+		if self.remaining() < len {
+			return Err(DecodeError::UnexpectedEof {
+				needed: len,
+				remaining: self.remaining()
+			});
+		}
+		let bytes = &self.bytes[self.position..self.position + len];
+		self.position += len;
+		Ok(bytes)
+	}
+}
+
+/// Describes why a value couldn't be read back from a buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+	/// The buffer ran out before the value was fully read.
+	UnexpectedEof { needed: usize, remaining: usize },
+	/// A tag byte didn't correspond to any variant of the type.
+	InvalidTag { ty: &'static str, tag: u8 },
+	/// A length prefix claimed more elements than the buffer could hold.
+	LengthOutOfBounds { len: usize, remaining: usize },
+	/// A length prefix claimed more elements than the protocol allows for.
+	LimitExceeded { what: &'static str, len: usize, max: usize },
+	/// A string wasn't valid UTF-8.
+	InvalidUtf8 { what: &'static str },
+	/// The packet was larger than any the protocol allows for.
+	PacketTooLarge { size: usize, max: usize },
+	/// A varint ran on for longer than a u64 can hold.
+	VarintOverflow,
+	/// A delta was encoded against a different tick than the one we hold.
+	StaleBaseline { expected: u64, found: Option<u64> },
+	/// A delta referred to a command frame its baseline doesn't have.
+	MissingBaselineFrame { owner: u16 },
+	/// A received tick carried a frame from a client that isn't in the game.
+	UnknownFrameOwner { owner: u16, index: u64 },
+	/// The packet didn't start with `PROTOCOL_MAGIC`; it's not meant for us.
+	WrongMagic { found: [u8; 2] },
+	/// The packet was written for a different version of the protocol.
+	UnsupportedVersion { got: u8, expected: u8 },
+	/// The packet's ticks were hashed with a different algorithm than ours.
+	UnsupportedHasher { got: u8, expected: u8 },
+	/// A tick's contents didn't hash to the digest it was sent with.
+	HashMismatch { index: u64 },
+	/// Bytes were left over after a whole packet had been read, most likely
+	/// because the host handed us several packets at once.
+	TrailingBytes { count: usize },
+}
+
+impl DecodeError {
+	/// The variant's name, for comparing errors without their details, as
+	/// the conformance vectors do.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			DecodeError::UnexpectedEof { .. } => "UnexpectedEof",
+			DecodeError::InvalidTag { .. } => "InvalidTag",
+			DecodeError::LengthOutOfBounds { .. } => "LengthOutOfBounds",
+			DecodeError::LimitExceeded { .. } => "LimitExceeded",
+			DecodeError::InvalidUtf8 { .. } => "InvalidUtf8",
+			DecodeError::PacketTooLarge { .. } => "PacketTooLarge",
+			DecodeError::VarintOverflow => "VarintOverflow",
+			DecodeError::StaleBaseline { .. } => "StaleBaseline",
+			DecodeError::MissingBaselineFrame { .. } => "MissingBaselineFrame",
+			DecodeError::UnknownFrameOwner { .. } => "UnknownFrameOwner",
+			DecodeError::WrongMagic { .. } => "WrongMagic",
+			DecodeError::UnsupportedVersion { .. } => "UnsupportedVersion",
+			DecodeError::UnsupportedHasher { .. } => "UnsupportedHasher",
+			DecodeError::HashMismatch { .. } => "HashMismatch",
+			DecodeError::TrailingBytes { .. } => "TrailingBytes",
+		}
+	}
+}
+
+impl std::fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			DecodeError::UnexpectedEof { needed, remaining } => {
+				write!(f, "unexpected end of buffer (needed {} bytes, {} remaining)", needed, remaining)
+			},
+			DecodeError::InvalidTag { ty, tag } => {
+				write!(f, "invalid tag {} for {}", tag, ty)
+			},
+			DecodeError::LengthOutOfBounds { len, remaining } => {
+				write!(f, "length {} out of bounds ({} bytes remaining)", len, remaining)
+			},
+			DecodeError::LimitExceeded { what, len, max } => {
+				write!(f, "{} {} exceeds the limit of {}", len, what, max)
+			},
+			DecodeError::InvalidUtf8 { what } => {
+				write!(f, "{} isn't valid UTF-8", what)
+			},
+			DecodeError::PacketTooLarge { size, max } => {
+				write!(f, "packet of {} bytes exceeds the limit of {}", size, max)
+			},
+			DecodeError::VarintOverflow => {
+				write!(f, "varint overflows a u64")
+			},
+			DecodeError::StaleBaseline { expected, found: Some(found) } => {
+				write!(f, "delta against tick {}, but the baseline is tick {}", expected, found)
+			},
+			DecodeError::StaleBaseline { expected, found: None } => {
+				write!(f, "delta against tick {}, but there's no baseline", expected)
+			},
+			DecodeError::MissingBaselineFrame { owner } => {
+				write!(f, "baseline has no command frame for client {}", owner)
+			},
+			DecodeError::UnknownFrameOwner { owner, index } => {
+				write!(f, "tick {} has a frame from unknown client {}", index, owner)
+			},
+			DecodeError::WrongMagic { found } => {
+				write!(f, "wrong magic bytes {:02x?}", found)
+			},
+			DecodeError::UnsupportedVersion { got, expected } => {
+				write!(f, "unsupported protocol version {} (expected {})", got, expected)
+			},
+			DecodeError::UnsupportedHasher { got, expected } => {
+				write!(f, "unsupported hash algorithm {} (expected {})", got, expected)
+			},
+			DecodeError::HashMismatch { index } => {
+				write!(f, "hash mismatch for tick {}", index)
+			},
+			DecodeError::TrailingBytes { count } => {
+				write!(f, "{} trailing bytes after the packet", count)
+			},
+		}
+	}
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Describes why a value couldn't be written out. Each of these has a
+/// `DecodeError` counterpart the other end would have failed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+	/// A command frame had more intents than `MAX_INTENTS_PER_FRAME`.
+	TooManyIntents { len: usize, max: usize },
+	/// A tick had more command frames than `MAX_FRAMES_PER_TICK`.
+	TooManyFrames { len: usize, max: usize },
+	/// The packet came out larger than any the protocol allows for.
+	PacketTooLarge { size: usize, max: usize },
+}
+
+impl std::fmt::Display for EncodeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			EncodeError::TooManyIntents { len, max } => {
+				write!(f, "{} intents in one frame exceed the limit of {}", len, max)
+			},
+			EncodeError::TooManyFrames { len, max } => {
+				write!(f, "{} command frames in one tick exceed the limit of {}", len, max)
+			},
+			EncodeError::PacketTooLarge { size, max } => {
+				write!(f, "packet of {} bytes exceeds the limit of {}", size, max)
+			},
+		}
+	}
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Implements NetType for primitive numeric types.
+/// Everything goes over the wire in little-endian byte order.
+macro_rules! impl_net_type_le {
+	($($ty:ty),*) => {$(
+		impl NetType for $ty {
+			fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+				writer.write_bytes(&self.to_le_bytes());
+				Ok(())
+			}
+
+			fn byte_len_hint(&self) -> usize {
+				std::mem::size_of::<$ty>()
+			}
+
+			fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+				Ok(<$ty>::from_le_bytes(reader.read_array()?))
+			}
+		}
+	)*};
+}
+
+impl_net_type_le!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl NetType for bool {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_u8(*self as u8);
+		Ok(())
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		1
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		match u8::from_bytes(reader)? {
+			0 => Ok(false),
+			1 => Ok(true),
+			tag => Err(DecodeError::InvalidTag { ty: "bool", tag })
+		}
+	}
+}
+
+/// Strings are prefixed with their length in bytes as a varint, followed
+/// by the bytes themselves, in UTF-8.
+impl NetType for String {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_varint(self.len() as u64);
+		writer.write_bytes(self.as_bytes());
+		Ok(())
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		ByteWriter::varint_len(self.len() as u64) + self.len()
+	}
+
+	/// Only bounded by what's left in the buffer; see `ByteReader::read_string`
+	/// for strings the protocol puts a limit on.
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		reader.read_string(usize::MAX, "string")
+	}
+}
+
+/// Collections are prefixed with their length as a varint.
+impl<T: NetType> NetType for Vec<T> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_varint(self.len() as u64);
+		for item in self {
+			item.to_bytes(writer)?;
+		}
+		Ok(())
+	}
+
+	/// Only bounded by what's left in the buffer; see `ByteReader::read_vec`
+	/// for collections the protocol puts a limit on.
+	fn byte_len_hint(&self) -> usize {
+		ByteWriter::varint_len(self.len() as u64) + self.iter().map(|x| x.byte_len_hint()).sum::<usize>()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		reader.read_vec(usize::MAX, "elements")
+	}
+}
+
+/// Optional values are prefixed with a presence byte.
+impl<T: NetType> NetType for Option<T> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.is_some().to_bytes(writer)?;
+		match self {
+			Some(value) => value.to_bytes(writer),
+			None => Ok(()),
+		}
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		1 + self.as_ref().map_or(0, |x| x.byte_len_hint())
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		match bool::from_bytes(reader)? {
+			true => Ok(Some(T::from_bytes(reader)?)),
+			false => Ok(None),
+		}
+	}
+}
+
+/// Arrays have a known length, so unlike `Vec` they're not length prefixed.
+impl<T: NetType, const N: usize> NetType for [T; N] {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		for item in self {
+			item.to_bytes(writer)?;
+		}
+		Ok(())
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.iter().map(|x| x.byte_len_hint()).sum()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		// Built in place rather than through a `Vec`, as hashes are decoded
+		// out of nearly every packet.
+		let mut error = None;
+		let items: [Option<T>; N] = std::array::from_fn(|_| match error {
+			Some(_) => None,
+			None => T::from_bytes(reader).map_err(|x| error = Some(x)).ok(),
+		});
+		match error {
+			Some(error) => Err(error),
+			None => Ok(items.map(|x| x.expect("every item was read"))),
+		}
+	}
+}
\ No newline at end of file
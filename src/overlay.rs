@@ -0,0 +1,481 @@
+//! The debug overlay. It only ever draws what's in a `DebugStats`, which
+//! the game and the game loop keep up to date, so it doesn't need to know
+//! where any of it comes from.
+
+use std::collections::VecDeque;
+use macroquad::prelude::*;
+
+use crate::game::{ConnectionState, MemoryUsage};
+use crate::net::DecodeError;
+use crate::prediction::{IntentSummary, PredictionStats};
+use crate::protocol::{ClientId, SnapshotReason};
+
+/// How many bytes pass through per second, measured over whole seconds.
+#[derive(Default)]
+pub struct ByteRate {
+	/// When the current measurement started, as per `get_time`.
+	since: f64,
+	bytes: usize,
+	per_second: usize,
+}
+
+impl ByteRate {
+	pub fn record(&mut self, bytes: usize) {
+		self.bytes += bytes;
+	}
+
+	/// Finishes the current measurement, once it's been going for a second.
+	/// Meant to be called every frame; `now` is the current time, as per
+	/// `get_time`.
+	pub fn update(&mut self, now: f64) {
+		let elapsed = now - self.since;
+		if elapsed >= 1.0 {
+			self.per_second = (self.bytes as f64 / elapsed) as usize;
+			self.bytes = 0;
+			self.since = now;
+		}
+	}
+
+	/// As of the last whole second.
+	pub fn per_second(&self) -> usize {
+		self.per_second
+	}
+}
+
+/// The most recent samples of something, for percentiles and histograms.
+#[derive(Default)]
+pub struct Samples {
+	values: VecDeque<f32>,
+}
+
+impl Samples {
+	/// How many samples are kept around; older ones are dropped.
+	pub const LEN: usize = 200;
+
+	pub fn push(&mut self, value: f32) {
+		if self.values.len() == Self::LEN {
+			self.values.pop_front();
+		}
+		self.values.push_back(value);
+	}
+
+	/// The smallest sample at least `percent` of them are no larger than.
+	pub fn percentile(&self, percent: f32) -> Option<f32> {
+		let mut sorted: Vec<f32> = self.values.iter().copied().collect();
+		sorted.sort_by(f32::total_cmp);
+		let rank = (percent / 100.0 * sorted.len() as f32).ceil() as usize;
+		sorted.get(rank.clamp(1, sorted.len().max(1)) - 1).copied()
+	}
+
+	pub fn max(&self) -> Option<f32> {
+		self.values.iter().copied().reduce(f32::max)
+	}
+
+	/// How many samples fall into each of `bins` equally wide ranges, from
+	/// 0 up to the largest sample.
+	pub fn histogram(&self, bins: usize) -> Vec<usize> {
+		let mut counts = vec![0; bins];
+		let Some(max) = self.max().filter(|x| *x > 0.0) else {
+			return counts;
+		};
+		for value in &self.values {
+			let bin = (value / max * bins as f32) as usize;
+			counts[bin.min(bins - 1)] += 1;
+		}
+		counts
+	}
+}
+
+/// How long the game loop spends on every tick. Only measured while the
+/// overlay is shown, so that hiding it leaves the loop unobserved.
+#[derive(Default)]
+pub struct TickTimings {
+	/// Predicting and simulating a tick, in milliseconds.
+	pub simulation: Samples,
+	/// Serializing the packet proposing a tick, in milliseconds.
+	pub serialization: Samples,
+	/// How many ticks ran during each rendered frame.
+	pub ticks_per_frame: Samples,
+}
+
+/// One player's readout, for the overlay's second page.
+pub struct PlayerStats {
+	pub client_id: ClientId,
+	pub local: bool,
+	pub position: (f32, f32),
+	/// In pixels per second.
+	pub velocity: (f32, f32),
+	pub grounded: bool,
+	/// How far off the drawn position is from the simulated one, in pixels.
+	pub visual_error: f32,
+	/// Whether the latest tick's frame is made up from how the player was
+	/// moving; see `PredictionStrategy::is_extrapolating`.
+	pub extrapolating: bool,
+	/// Intents applied over the last second of confirmed ticks.
+	pub intents_per_second: usize,
+	/// Which way the intents applied during the accepted head pointed.
+	pub confirmed_intents: IntentSummary,
+}
+
+/// Everything the overlay shows. The game fills in its own part through
+/// `Game::update_debug_stats`; traffic, the smoothing factor and the
+/// network conditions are up to the game loop.
+#[derive(Default)]
+pub struct DebugStats {
+	pub local_players: Vec<ClientId>,
+	pub spectating: bool,
+	/// `None` until there's a game to show the state of.
+	pub connection_state: Option<ConnectionState>,
+	/// The most recent tick simulated, predicted or not.
+	pub local_tick: Option<u64>,
+	pub received_head: u64,
+	pub accepted_head: u64,
+	pub ticks_ahead: u64,
+	/// The ticks ahead pacing steers toward, once there's a round trip.
+	pub target_ticks_ahead: Option<u64>,
+	/// How fast ticks run compared to the tick rate; see `Game::tick_speed`.
+	pub tick_speed: f32,
+	/// The smoothed round trip time and its jitter, in milliseconds.
+	pub rtt: Option<(f64, f64)>,
+	/// Ticks currently held in the history, predicted ones included.
+	pub stored_ticks: usize,
+	/// How many confirmed ticks the history holds on to, at most.
+	pub retention: u64,
+	/// Seconds since the last confirmation arrived, if one ever did.
+	pub since_confirmation: Option<f64>,
+	pub suspect_ticks: usize,
+	pub stale_ticks: u32,
+	pub rejected_frames: u32,
+	/// Packets the transport turned down, waiting to be sent again.
+	pub resend_queue: usize,
+	/// Sends the transport turned down so far, tries again included.
+	pub send_failures: u32,
+	pub desync: Option<u64>,
+	pub resync_requested: bool,
+	/// How many times the snapshot we're waiting on got asked for so far.
+	pub snapshot_attempts: u32,
+	/// Why a snapshot was last asked for, and how many seconds ago.
+	pub last_snapshot_request: Option<(SnapshotReason, f64)>,
+	pub last_decode_error: Option<DecodeError>,
+	pub remote_presentation: &'static str,
+	/// Ticks behind remote players are drawn; see `Game::render_delay`.
+	pub render_delay: u8,
+	pub prediction: &'static str,
+	/// Mispredictions under the current prediction strategy.
+	pub mispredictions: u32,
+	/// See `PredictionStats::rolling_misprediction_rate`.
+	pub misprediction_rate: Option<f32>,
+	pub redundant_frames: usize,
+	pub skipped_ticks: u32,
+	/// Frames which used up their tick budget; see `Game::set_tick_budget`.
+	pub budget_overruns: u32,
+	/// Rollbacks whose replay got split across frames.
+	pub split_replays: u32,
+	/// The tick a split replay is at, and the one it's replaying up to.
+	pub replaying: Option<(u64, u64)>,
+	/// How many ticks ahead the game stopped predicting at, while throttled.
+	pub throttled_at: Option<u64>,
+	/// Ticks sent a second; see `Game::send_rate`.
+	pub send_rate: u32,
+	/// See `Game::is_idle`.
+	pub idle: bool,
+	pub jitter_depth: usize,
+	pub jitter_target: usize,
+	/// Recent jitter buffer depths, oldest first.
+	pub jitter_history: Vec<usize>,
+	pub players: Vec<PlayerStats>,
+	pub sent: ByteRate,
+	pub received: ByteRate,
+	/// Size of the last packet carrying one of our ticks, in bytes.
+	pub last_tick_size: usize,
+	/// How far the latest frame was drawn between the last two ticks.
+	pub smoothing: f32,
+	/// The simulated network conditions, if there are any.
+	pub network: Option<String>,
+	pub timings: TickTimings,
+	/// Every cap in the memory budget, and how much of it is taken up.
+	pub memory: Vec<MemoryUsage>,
+}
+
+/// What the overlay currently shows.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum OverlayPage {
+	/// The connection and the tick pipeline, compactly.
+	#[default]
+	Summary,
+	/// Every player's state, one line each.
+	Players,
+	/// How long ticks take, and how many run per frame.
+	Timings,
+	/// How much of the memory budget everything takes up.
+	Memory,
+	Hidden,
+}
+
+/// The debug overlay. `TOGGLE_KEY` cycles through its pages, then hides it.
+#[derive(Default)]
+pub struct Overlay {
+	page: OverlayPage,
+}
+
+impl Overlay {
+	pub const TOGGLE_KEY: KeyCode = KeyCode::F3;
+	const FONT_SIZE: f32 = 16.0;
+	const LINE_HEIGHT: f32 = 15.0;
+	const MARGIN: f32 = 5.0;
+	const BACKGROUND: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+	/// Height of the jitter buffer graph at the bottom of the summary.
+	const GRAPH_HEIGHT: f32 = 25.0;
+	const HISTOGRAM_BINS: usize = 20;
+
+	pub fn page(&self) -> OverlayPage {
+		self.page
+	}
+
+	pub fn is_visible(&self) -> bool {
+		self.page != OverlayPage::Hidden
+	}
+
+	pub fn cycle(&mut self) {
+		self.page = match self.page {
+			OverlayPage::Summary => OverlayPage::Players,
+			OverlayPage::Players => OverlayPage::Timings,
+			OverlayPage::Timings => OverlayPage::Memory,
+			OverlayPage::Memory => OverlayPage::Hidden,
+			OverlayPage::Hidden => OverlayPage::Summary,
+		};
+	}
+
+	/// Cycles the page if `TOGGLE_KEY` was pressed this frame.
+	pub fn update(&mut self) {
+		if is_key_pressed(Self::TOGGLE_KEY) {
+			self.cycle();
+		}
+	}
+
+	pub fn draw(&self, stats: &DebugStats) {
+		match self.page {
+			OverlayPage::Summary => {
+				let bottom = Self::draw_lines(&Self::summary(stats), Self::GRAPH_HEIGHT);
+				Self::draw_graph(&stats.jitter_history, bottom);
+			},
+			OverlayPage::Players => {
+				Self::draw_lines(&Self::players(stats), 0.0);
+			},
+			OverlayPage::Timings => {
+				let bottom = Self::draw_lines(&Self::timings(&stats.timings), Self::GRAPH_HEIGHT);
+				Self::draw_histogram(&stats.timings.simulation.histogram(Self::HISTOGRAM_BINS), bottom);
+			},
+			OverlayPage::Memory => {
+				Self::draw_lines(&Self::memory(&stats.memory), 0.0);
+			},
+			OverlayPage::Hidden => {},
+		}
+	}
+
+	fn summary(stats: &DebugStats) -> Vec<String> {
+		let mut lines = vec![];
+		let client_ids: Vec<String> = stats.local_players.iter().map(|x| x.to_string()).collect();
+		match (stats.spectating, client_ids.len()) {
+			(true, _) => lines.push(format!("Spectating as client {}", client_ids.join(", "))),
+			(false, 1) => lines.push(format!("Client ID: {}", client_ids.join(", "))),
+			(false, _) => lines.push(format!("Client IDs: {}", client_ids.join(", "))),
+		}
+		if let Some(state) = stats.connection_state {
+			lines.push(format!("Connection: {:?}", state));
+		}
+		if let Some(local_tick) = stats.local_tick {
+			lines.push(format!(
+				"Ticks: {} local, {} received, {} confirmed",
+				local_tick,
+				stats.received_head,
+				stats.accepted_head
+			));
+			let rtt = match stats.rtt {
+				Some((rtt, jitter)) => format!("RTT {:.0} ms, jitter {:.0} ms", rtt, jitter),
+				None => "no RTT yet".to_string(),
+			};
+			lines.push(format!("{} ticks ahead, {}", stats.ticks_ahead, rtt));
+			if let Some(target) = stats.target_ticks_ahead {
+				lines.push(format!("Pacing: {:.1}% speed, aiming for {} ticks ahead", stats.tick_speed * 100.0, target));
+			}
+		}
+		lines.push(format!("Stored ticks: {} (retaining {} confirmed)", stats.stored_ticks, stats.retention));
+
+		let since_confirmation = match stats.since_confirmation {
+			Some(seconds) => format!("{:.1}s ago", seconds),
+			None => "never".to_string(),
+		};
+		lines.push(format!(
+			"Confirmed {}; {} suspect, {} stale, {} rejected",
+			since_confirmation,
+			stats.suspect_ticks,
+			stats.stale_ticks,
+			stats.rejected_frames
+		));
+		lines.push(format!(
+			"Traffic: {} B/s up, {} B/s down",
+			stats.sent.per_second(),
+			stats.received.per_second()
+		));
+		lines.push(format!(
+			"Sends: {} ticks/s{}, {} waiting to go again, {} failed",
+			stats.send_rate,
+			if stats.idle { " (idle)" } else { "" },
+			stats.resend_queue,
+			stats.send_failures
+		));
+		lines.push(format!(
+			"Last tick: {} B, with {} past frames",
+			stats.last_tick_size,
+			stats.redundant_frames
+		));
+
+		if let Some(index) = stats.desync {
+			lines.push(format!("DESYNC at tick {}", index));
+		}
+		if stats.resync_requested {
+			lines.push(format!("Resync requested, asked {} times", stats.snapshot_attempts));
+		}
+		if let Some((reason, age)) = stats.last_snapshot_request {
+			lines.push(format!("Last snapshot request: {}, {:.1}s ago", reason, age));
+		}
+		if let Some(error) = &stats.last_decode_error {
+			lines.push(format!("Decode error: {}", error));
+		}
+
+		lines.push(format!(
+			"Remote: {} (I), {} (P), drawn {} ticks behind ([ ])",
+			stats.remote_presentation, stats.prediction, stats.render_delay
+		));
+		let rate = match stats.misprediction_rate {
+			Some(rate) => format!("{:.0}% of the last {}", rate, PredictionStats::ROLLING_WINDOW),
+			None => "none settled yet".to_string(),
+		};
+		lines.push(format!("Mispredicted: {}, {} total", rate, stats.mispredictions));
+		lines.push(format!("Smoothing: {:.2}", stats.smoothing));
+		if stats.skipped_ticks > 0 {
+			lines.push(format!("Catch-up budget hit, {} ticks skipped", stats.skipped_ticks));
+		}
+		if stats.budget_overruns > 0 {
+			lines.push(format!("Tick budget hit in {} frames, {} replays split", stats.budget_overruns, stats.split_replays));
+		}
+		if let Some((at, to)) = stats.replaying {
+			lines.push(format!("Replaying: tick {} of {}", at, to));
+		}
+		if let Some(ahead) = stats.throttled_at {
+			lines.push(format!("Throttled: {} ticks ahead, waiting for the server", ahead));
+		}
+		if let Some(network) = &stats.network {
+			lines.push(format!("Network: {}", network));
+		}
+		lines.push(format!("Jitter buffer: {} (target {})", stats.jitter_depth, stats.jitter_target));
+		lines
+	}
+
+	fn players(stats: &DebugStats) -> Vec<String> {
+		let intents: usize = stats.players.iter().map(|x| x.intents_per_second).sum();
+		let mut lines = vec![format!("{} players, {} intents/s, drawn by them with K", stats.players.len(), intents)];
+		for player in &stats.players {
+			let held = player.confirmed_intents;
+			let held: String = [(held.left, '<'), (held.jump, '^'), (held.right, '>')]
+				.iter()
+				.filter(|(on, _)| *on)
+				.map(|(_, glyph)| *glyph)
+				.collect();
+			lines.push(format!(
+				"{}{}: ({:.1}, {:.1}) at ({:.0}, {:.0}) px/s{}{}{}, {} intents/s {}",
+				player.client_id,
+				if player.local { "*" } else { "" },
+				player.position.0,
+				player.position.1,
+				player.velocity.0,
+				player.velocity.1,
+				if player.grounded { ", grounded" } else { "" },
+				match player.visual_error > 0.0 {
+					true => format!(", {:.1}px off", player.visual_error),
+					false => String::new(),
+				},
+				if player.extrapolating { ", extrapolating" } else { "" },
+				player.intents_per_second,
+				held
+			));
+		}
+		lines
+	}
+
+	fn timings(timings: &TickTimings) -> Vec<String> {
+		fn summary(samples: &Samples, unit: &str) -> String {
+			match (samples.percentile(50.0), samples.percentile(95.0), samples.max()) {
+				(Some(p50), Some(p95), Some(max)) => format!("p50 {:.2}, p95 {:.2}, max {:.2} {}", p50, p95, max, unit),
+				_ => "no samples yet".to_string(),
+			}
+		}
+
+		vec![
+			format!("Last {} ticks", Samples::LEN),
+			format!("Simulation: {}", summary(&timings.simulation, "ms")),
+			format!("Serialization: {}", summary(&timings.serialization, "ms")),
+			format!("Ticks per frame: {}", summary(&timings.ticks_per_frame, "")),
+			"Simulation time, from 0 to max:".to_string(),
+		]
+	}
+
+	fn memory(usages: &[MemoryUsage]) -> Vec<String> {
+		let mut lines = vec!["Memory budget, used of cap".to_string()];
+		for usage in usages {
+			lines.push(format!(
+				"{}: {} of {}{}",
+				usage.subsystem,
+				usage.used,
+				usage.cap,
+				if usage.is_within_budget() { "" } else { ", OVER" }
+			));
+		}
+		lines
+	}
+
+	/// Draws `lines` over a translucent background with `extra` pixels of
+	/// room left below them, which is where the returned y coordinate is.
+	fn draw_lines(lines: &[String], extra: f32) -> f32 {
+		let width = lines
+			.iter()
+			.map(|x| measure_text(x, None, Self::FONT_SIZE as u16, 1.0).width)
+			.fold(0.0, f32::max);
+		let height = lines.len() as f32 * Self::LINE_HEIGHT + extra;
+		draw_rectangle(
+			Self::MARGIN,
+			Self::MARGIN,
+			width + 2.0 * Self::MARGIN,
+			height + 2.0 * Self::MARGIN,
+			Self::BACKGROUND
+		);
+
+		let mut y = Self::MARGIN;
+		for line in lines {
+			y += Self::LINE_HEIGHT;
+			draw_text(line, 2.0 * Self::MARGIN, y, Self::FONT_SIZE, WHITE);
+		}
+		y + Self::MARGIN
+	}
+
+	/// A bar for every recent jitter buffer depth, growing upwards from `top`.
+	fn draw_graph(history: &[usize], top: f32) {
+		let bottom = top + Self::GRAPH_HEIGHT - Self::MARGIN;
+		for (i, depth) in history.iter().enumerate() {
+			let height = (*depth as f32 * 3.0).min(Self::GRAPH_HEIGHT - Self::MARGIN);
+			draw_rectangle(2.0 * Self::MARGIN + i as f32 * 2.0, bottom - height, 2.0, height, RED);
+		}
+	}
+
+	/// A bar for every bin of a histogram, growing upwards from `top`, scaled
+	/// to the fullest bin.
+	fn draw_histogram(counts: &[usize], top: f32) {
+		let bottom = top + Self::GRAPH_HEIGHT - Self::MARGIN;
+		let fullest = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+		for (i, count) in counts.iter().enumerate() {
+			let height = *count as f32 / fullest * (Self::GRAPH_HEIGHT - Self::MARGIN);
+			draw_rectangle(2.0 * Self::MARGIN + i as f32 * 6.0, bottom - height, 5.0, height, RED);
+		}
+	}
+}
\ No newline at end of file
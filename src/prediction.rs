@@ -0,0 +1,342 @@
+//! How the frames of remote players get guessed, before the server has
+//! told us what they actually did.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::protocol::{ClientId, CommandFrame, Intent, PlayerIntent, Tick};
+
+/// Most ticks a remote player's movement gets extrapolated for, past the
+/// last confirmed frame of theirs; see `Extrapolate`.
+pub const EXTRAPOLATION_TICKS: u64 = 4;
+/// Fraction of a remote player's estimated velocity kept for every tick
+/// it's extrapolated for.
+pub const EXTRAPOLATION_DECAY: f32 = 0.75;
+
+/// Predicts what every remote player is going to do during the next tick.
+pub trait PredictionStrategy<I: Intent> {
+	/// Shown in the debug overlay, and used to tell strategies apart.
+	fn name(&self) -> &'static str;
+
+	/// The remote players' frames for the tick following `history`, which
+	/// holds the ticks we know of, oldest first. Frames of `local_ids` are
+	/// added by the caller, and shouldn't be returned.
+	fn predict_remote_frames(&self, history: &[&Tick<I>], local_ids: &[ClientId]) -> Vec<CommandFrame<I>>;
+
+	/// Like `predict_remote_frames`, for strategies which also go by how the
+	/// remote players were last confirmed to move. Ignores `context` by
+	/// default; this is what the game calls.
+	fn predict_with_context(&self, history: &[&Tick<I>], local_ids: &[ClientId], context: &PredictionContext) -> Vec<CommandFrame<I>> {
+		let _ = context;
+		self.predict_remote_frames(history, local_ids)
+	}
+
+	/// Whether `owner`'s frame for `tick_index` is made up from their
+	/// movement, rather than anything they were seen doing; shown in the
+	/// debug overlay.
+	fn is_extrapolating(&self, owner: ClientId, tick_index: u64, context: &PredictionContext) -> bool {
+		let _ = (owner, tick_index, context);
+		false
+	}
+}
+
+/// How a remote player was last confirmed to move.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RemoteMotion {
+	/// Horizontal velocity as a fraction of the top speed, from the
+	/// positions before and after the last confirmed tick.
+	pub velocity: f32,
+	/// The last confirmed tick holding a frame of theirs.
+	pub last_seen: u64,
+}
+
+/// What's known for sure about the remote players, as of the last tick the
+/// server confirmed before the one being predicted.
+#[derive(Clone, Default, Debug)]
+pub struct PredictionContext {
+	pub motion: BTreeMap<ClientId, RemoteMotion>,
+}
+
+/// Assumes everyone keeps doing whatever they were doing last tick. Usually
+/// right, as players tend to hold keys down for many ticks in a row. Keys
+/// held into the next tick were there from its start, so offsets aren't
+/// repeated.
+pub struct RepeatLast;
+
+impl<I: Intent> PredictionStrategy<I> for RepeatLast {
+	fn name(&self) -> &'static str {
+		"repeat-last"
+	}
+
+	fn predict_remote_frames(&self, history: &[&Tick<I>], local_ids: &[ClientId]) -> Vec<CommandFrame<I>> {
+		let Some(previous) = history.last() else { return vec![] };
+		previous.command_frames()
+			.iter()
+			.filter(|x| !local_ids.contains(&x.owner))
+			.map(|x| CommandFrame::new(x.owner, x.intents.clone()))
+			.collect()
+	}
+}
+
+/// Assumes nobody does anything. Remote players come to a halt until the
+/// server's version of the tick arrives.
+pub struct EmptyFrames;
+
+impl<I: Intent> PredictionStrategy<I> for EmptyFrames {
+	fn name(&self) -> &'static str {
+		"empty"
+	}
+
+	fn predict_remote_frames(&self, history: &[&Tick<I>], local_ids: &[ClientId]) -> Vec<CommandFrame<I>> {
+		let Some(previous) = history.last() else { return vec![] };
+		previous.command_frames()
+			.iter()
+			.filter(|x| !local_ids.contains(&x.owner))
+			.map(|x| CommandFrame::new(x.owner, vec![]))
+			.collect()
+	}
+}
+
+/// Carries on with the remote players' movement once their frames stop
+/// coming in, rather than with their last intents: each player's velocity
+/// is extrapolated, decaying by `EXTRAPOLATION_DECAY` every tick, for up to
+/// `EXTRAPOLATION_TICKS` past their last confirmed frame. After that, they
+/// stand still until the server says otherwise.
+///
+/// Only ever makes up frames of predicted ticks; the server's always win,
+/// and a rollback glides the players over to where they actually went.
+/// Players nothing's known about yet get their last frame repeated.
+pub struct Extrapolate;
+
+impl Extrapolate {
+	/// `owner`'s frame for `tick_index`, keeping them going at their decayed
+	/// velocity, or standing still once that's gone on for too long.
+	fn frame(owner: ClientId, motion: &RemoteMotion, tick_index: u64) -> CommandFrame<PlayerIntent> {
+		let age = tick_index.saturating_sub(motion.last_seen);
+		if age > EXTRAPOLATION_TICKS {
+			return CommandFrame::new(owner, vec![]);
+		}
+		let speed = motion.velocity * EXTRAPOLATION_DECAY.powi(age as i32);
+		let max = PlayerIntent::MAX_MAGNITUDE as f32;
+		let magnitude = (speed * max).round().clamp(-max, max) as i8;
+		match magnitude {
+			0 => CommandFrame::new(owner, vec![]),
+			_ => CommandFrame::new(owner, vec![PlayerIntent::MoveHorizontal(magnitude)]),
+		}
+	}
+}
+
+impl PredictionStrategy<PlayerIntent> for Extrapolate {
+	fn name(&self) -> &'static str {
+		"extrapolate"
+	}
+
+	fn predict_remote_frames(&self, history: &[&Tick<PlayerIntent>], local_ids: &[ClientId]) -> Vec<CommandFrame<PlayerIntent>> {
+		RepeatLast.predict_remote_frames(history, local_ids)
+	}
+
+	fn predict_with_context(&self, history: &[&Tick<PlayerIntent>], local_ids: &[ClientId], context: &PredictionContext) -> Vec<CommandFrame<PlayerIntent>> {
+		let Some(previous) = history.last() else { return vec![] };
+		let tick_index = previous.index() + 1;
+		RepeatLast.predict_remote_frames(history, local_ids)
+			.into_iter()
+			.map(|frame| match context.motion.get(&frame.owner) {
+				Some(motion) => Self::frame(frame.owner, motion, tick_index),
+				None => frame,
+			})
+			.collect()
+	}
+
+	fn is_extrapolating(&self, owner: ClientId, tick_index: u64, context: &PredictionContext) -> bool {
+		context.motion
+			.get(&owner)
+			.is_some_and(|x| (1..=EXTRAPOLATION_TICKS).contains(&tick_index.saturating_sub(x.last_seen)))
+	}
+}
+
+/// Why the server had to correct one of our predictions.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CorrectionCause {
+	/// A remote player did something other than what we guessed.
+	RemoteIntents,
+	/// The server's frame for one of our own players differs from ours,
+	/// most likely because the packet carrying it never made it in time.
+	LocalFrameLost,
+}
+
+impl CorrectionCause {
+	/// Tells what went wrong between our prediction and the server's version
+	/// of the same tick. Our own frames going missing takes precedence, as
+	/// the remote ones were only ever guesses.
+	pub fn between<I: Intent>(predicted: &Tick<I>, authoritative: &Tick<I>, local_ids: &[ClientId]) -> Self {
+		fn local_frame<I: Intent>(tick: &Tick<I>, owner: ClientId) -> Option<&Vec<I>> {
+			tick.command_frames().iter().find(|x| x.owner == owner).map(|x| &x.intents)
+		}
+		let local_changed = local_ids
+			.iter()
+			.any(|owner| local_frame(predicted, *owner) != local_frame(authoritative, *owner));
+		match local_changed {
+			true => CorrectionCause::LocalFrameLost,
+			false => CorrectionCause::RemoteIntents,
+		}
+	}
+}
+
+/// How well our predictions held up against the server's ticks, across
+/// every strategy used so far. Every tick is counted once, however often
+/// a rollback replays it.
+#[derive(Clone, Default, Debug)]
+pub struct PredictionStats {
+	/// Ticks we predicted.
+	pub predicted: u64,
+	/// Predicted ticks the server confirmed as they were.
+	pub confirmed: u64,
+	/// Predicted ticks the server had to correct.
+	pub corrected: u64,
+	/// Corrections caused by a remote player, see `CorrectionCause`.
+	pub remote_intents: u64,
+	/// Corrections caused by our own frames going missing.
+	pub local_frames_lost: u64,
+	/// Whether each of the last `ROLLING_WINDOW` settled ticks got
+	/// corrected, oldest first.
+	recent: VecDeque<bool>,
+}
+
+impl PredictionStats {
+	/// How many of the most recently settled ticks the rolling rate covers.
+	pub const ROLLING_WINDOW: usize = 100;
+
+	pub(crate) fn record_predicted(&mut self) {
+		self.predicted += 1;
+	}
+
+	pub(crate) fn record_confirmed(&mut self) {
+		self.confirmed += 1;
+		self.push_outcome(false);
+	}
+
+	pub(crate) fn record_corrected(&mut self, cause: CorrectionCause) {
+		self.corrected += 1;
+		match cause {
+			CorrectionCause::RemoteIntents => self.remote_intents += 1,
+			CorrectionCause::LocalFrameLost => self.local_frames_lost += 1,
+		}
+		self.push_outcome(true);
+	}
+
+	fn push_outcome(&mut self, corrected: bool) {
+		if self.recent.len() == Self::ROLLING_WINDOW {
+			self.recent.pop_front();
+		}
+		self.recent.push_back(corrected);
+	}
+
+	/// Percentage of the last `ROLLING_WINDOW` settled ticks which got
+	/// corrected, or `None` before any got settled.
+	pub fn rolling_misprediction_rate(&self) -> Option<f32> {
+		if self.recent.is_empty() {
+			return None;
+		}
+		let corrected = self.recent.iter().filter(|x| **x).count();
+		Some(100.0 * corrected as f32 / self.recent.len() as f32)
+	}
+}
+
+/// Which way a player's intents for a tick point, for drawing them.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct IntentSummary {
+	pub left: bool,
+	pub right: bool,
+	pub jump: bool,
+}
+
+impl IntentSummary {
+	pub fn of(intents: &[PlayerIntent]) -> Self {
+		let mut summary = Self::default();
+		for intent in intents {
+			match intent {
+				PlayerIntent::MoveLeft => summary.left = true,
+				PlayerIntent::MoveRight => summary.right = true,
+				PlayerIntent::JumpStart | PlayerIntent::JumpHold => summary.jump = true,
+				PlayerIntent::MoveHorizontal(magnitude) => {
+					summary.left |= *magnitude < 0;
+					summary.right |= *magnitude > 0;
+				},
+			}
+		}
+		summary
+	}
+}
+
+/// The intents every player's frames held, tick by tick, as the simulation
+/// applied them; see `Game::input_stats`. Every tick is counted once,
+/// however often a rollback replays it: simulating a tick again replaces
+/// what it held, and a rollback drops every tick from the one it goes back
+/// to on, before replaying them.
+#[derive(Clone, Default, Debug)]
+pub struct InputStats {
+	/// By tick index, then by the player the frame was from.
+	ticks: BTreeMap<u64, BTreeMap<ClientId, Vec<PlayerIntent>>>,
+}
+
+impl InputStats {
+	/// Starts tick `tick_index` over, as it's about to be simulated.
+	pub(crate) fn begin_tick(&mut self, tick_index: u64) {
+		self.ticks.remove(&tick_index);
+	}
+
+	/// Notes down the intents `owner`'s frame held in tick `tick_index`.
+	pub(crate) fn record(&mut self, tick_index: u64, owner: ClientId, intents: &[PlayerIntent]) {
+		self.ticks
+			.entry(tick_index)
+			.or_default()
+			.entry(owner)
+			.or_default()
+			.extend_from_slice(intents);
+	}
+
+	/// Forgets every tick from `tick_index` on, for a rollback to it.
+	pub(crate) fn rewind(&mut self, tick_index: u64) {
+		self.ticks.split_off(&tick_index);
+	}
+
+	/// Forgets every tick before `tick_index`.
+	pub(crate) fn prune(&mut self, tick_index: u64) {
+		self.ticks = self.ticks.split_off(&tick_index);
+	}
+
+	pub(crate) fn clear(&mut self) {
+		self.ticks.clear();
+	}
+
+	/// The intents `owner` had applied during tick `tick_index`, if they
+	/// were in the game then, and the tick's still known.
+	pub fn intents_at(&self, tick_index: u64, owner: ClientId) -> Option<&[PlayerIntent]> {
+		self.ticks.get(&tick_index)?.get(&owner).map(|x| x.as_slice())
+	}
+
+	/// How many intents `owner` had applied over the `hz` ticks up to and
+	/// including `tick_index`, which is a second's worth at `hz` ticks per
+	/// second.
+	pub fn per_second(&self, owner: ClientId, tick_index: u64, hz: u8) -> usize {
+		let from = (tick_index + 1).saturating_sub(hz as u64);
+		self.ticks
+			.range(from..=tick_index)
+			.filter_map(|(_, x)| x.get(&owner))
+			.map(|x| x.len())
+			.sum()
+	}
+
+	/// How many ticks are kept.
+	pub fn len(&self) -> usize {
+		self.ticks.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.ticks.is_empty()
+	}
+}
+
+/// The strategies that come with the crate, in the order they're cycled through.
+pub fn builtin() -> Vec<Box<dyn PredictionStrategy<PlayerIntent>>> {
+	vec![Box::new(RepeatLast), Box::new(EmptyFrames), Box::new(Extrapolate)]
+}
\ No newline at end of file
@@ -0,0 +1,1616 @@
+//! What goes over the wire: intents, command frames, ticks and the packets
+//! carrying them.
+
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use command_networking_derive::NetType;
+
+use crate::game::{Coins, Platform, Player, SimConfig, TICK_RETENTION};
+use crate::hash::{Hash, Hasher, TickHasher};
+#[cfg(feature = "serde")]
+use crate::hash::to_hex;
+use crate::net::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+use crate::scalar::scalar_to_f32;
+use crate::TICKRATE;
+
+/// Every packet starts with these, followed by the protocol version and
+/// the `TickHasher::ID` of the hash algorithm in use.
+pub const PROTOCOL_MAGIC: [u8; 2] = *b"CN";
+/// Bumped whenever the wire format changes in an incompatible way.
+pub const PROTOCOL_VERSION: u8 = 28;
+
+/// Most platforms a level may be made of.
+pub const MAX_PLATFORMS: usize = 256;
+/// Longest display name, in bytes.
+pub const MAX_NAME_LEN: usize = 24;
+/// Longest chat message, in bytes.
+pub const MAX_CHAT_LEN: usize = 120;
+/// Most intents a single command frame may hold.
+pub const MAX_INTENTS_PER_FRAME: usize = 32;
+/// Most players a game may have at once. Far fewer than there are
+/// ClientIds, which never get handed out twice.
+pub const MAX_PLAYERS: usize = 1024;
+/// Most command frames a single tick may hold; one for every player.
+pub const MAX_FRAMES_PER_TICK: usize = MAX_PLAYERS;
+/// Most past frames a predicted tick may carry, those of all the sender's
+/// players together, however many it's set to repeat; see
+/// `Game::set_redundant_frames`.
+pub const MAX_PAST_FRAMES: usize = 8;
+/// Largest packet, in bytes, that's even looked at. Comfortably fits a
+/// snapshot of every player there can be.
+pub const MAX_PACKET_SIZE: usize = 8 * 1024;
+
+/// An action carried in a command frame. The tick machinery only ever
+/// moves intents around, compares and hashes them; what they mean is up
+/// to the game acting on them.
+pub trait Intent: NetType + Clone + PartialEq {
+	/// Feeds the intent into a tick's hash. Hashes the serialized bytes by
+	/// default, so that every client hashes an intent the same way.
+	fn update_hasher(&self, hasher: &mut impl TickHasher) {
+		let mut writer = ByteWriter::new();
+		// Only collections can be over a limit, never a single intent.
+		self.to_bytes(&mut writer).expect("an intent couldn't be written");
+		hasher.update(writer.as_slice());
+	}
+
+	/// Brings the intents of a single frame into a canonical form, so that
+	/// two frames meaning the same thing simulate and hash the same way.
+	/// Only collapses duplicates by default, keeping the first of each.
+	fn normalize(intents: &mut Vec<Self>) {
+		let mut unique: Vec<Self> = Vec::with_capacity(intents.len());
+		for intent in intents.drain(..) {
+			if !unique.contains(&intent) {
+				unique.push(intent);
+			}
+		}
+		*intents = unique;
+	}
+
+	/// Writes the intents of a single frame. By default that's a length
+	/// prefix, followed by every intent in turn. Frames with more than
+	/// `MAX_INTENTS_PER_FRAME` intents are turned away before this is called.
+	fn write_intents(intents: &[Self], writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_varint(intents.len() as u64);
+		for intent in intents {
+			intent.to_bytes(writer)?;
+		}
+		Ok(())
+	}
+
+	/// Reads back what `write_intents` wrote.
+	fn read_intents(reader: &mut ByteReader) -> Result<Vec<Self>, DecodeError> {
+		reader.read_vec(MAX_INTENTS_PER_FRAME, "intents")
+	}
+
+	/// About how many bytes `write_intents` is going to take, see
+	/// `NetType::byte_len_hint`.
+	fn intents_len_hint(intents: &[Self]) -> usize {
+		ByteWriter::varint_len(intents.len() as u64) + intents.iter().map(|x| x.byte_len_hint()).sum::<usize>()
+	}
+}
+
+/// Represents all actions that a player may take.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayerIntent {
+	/// Player wants to move to the left.
+	MoveLeft,
+	/// Player wants to move to the right.
+	MoveRight,
+	/// Player wants to jump; sent on the tick the jump key goes down.
+	JumpStart,
+	/// Player is still holding the jump key, for a higher jump. Sent on
+	/// every tick after `JumpStart` until the key is released.
+	JumpHold,
+	/// Player wants to move sideways at a fraction of the top speed, for
+	/// analog input. Negative is to the left, and `MAX_MAGNITUDE` either way
+	/// is the same as `MoveLeft` or `MoveRight`. Kept integral, so that every
+	/// client scales movement by exactly the same amount.
+	MoveHorizontal(i8),
+}
+
+impl PlayerIntent {
+	pub const TAG_MOVE_LEFT: u8 = 0;
+	pub const TAG_MOVE_RIGHT: u8 = 1;
+	pub const TAG_JUMP_START: u8 = 2;
+	pub const TAG_MOVE_HORIZONTAL: u8 = 3;
+	pub const TAG_JUMP_HOLD: u8 = 4;
+	/// The largest magnitude `MoveHorizontal` means anything for. Anything
+	/// further out is treated as this.
+	pub const MAX_MAGNITUDE: i8 = 127;
+	/// Bits of a packed frame, see `write_intents`, in the order the
+	/// intents are unpacked in.
+	const PACKED_BITS: [(u8, PlayerIntent); 4] = [
+		(1 << 0, PlayerIntent::MoveLeft),
+		(1 << 1, PlayerIntent::MoveRight),
+		(1 << 2, PlayerIntent::JumpStart),
+		(1 << 3, PlayerIntent::JumpHold),
+	];
+	/// Set instead of any intent bits when a frame is written as a list.
+	const LIST_FORMAT: u8 = 1 << 7;
+
+	pub fn tag(&self) -> u8 {
+		match self {
+			PlayerIntent::MoveLeft => Self::TAG_MOVE_LEFT,
+			PlayerIntent::MoveRight => Self::TAG_MOVE_RIGHT,
+			PlayerIntent::JumpStart => Self::TAG_JUMP_START,
+			PlayerIntent::JumpHold => Self::TAG_JUMP_HOLD,
+			PlayerIntent::MoveHorizontal(_) => Self::TAG_MOVE_HORIZONTAL,
+		}
+	}
+
+	/// The intent `tag` stands for, unless there's no such intent or it needs
+	/// a payload, as `MoveHorizontal` does.
+	pub fn from_tag(tag: u8) -> Option<Self> {
+		match tag {
+			Self::TAG_MOVE_LEFT => Some(PlayerIntent::MoveLeft),
+			Self::TAG_MOVE_RIGHT => Some(PlayerIntent::MoveRight),
+			Self::TAG_JUMP_START => Some(PlayerIntent::JumpStart),
+			Self::TAG_JUMP_HOLD => Some(PlayerIntent::JumpHold),
+			_ => None,
+		}
+	}
+
+	/// The intents a packed frame holds, in canonical order.
+	fn packed(mask: u8) -> impl Iterator<Item = Self> {
+		Self::PACKED_BITS.iter()
+			.filter(move |(bit, _)| mask & bit != 0)
+			.map(|(_, intent)| *intent)
+	}
+
+	fn unpack(mask: u8) -> Vec<Self> {
+		Self::packed(mask).collect()
+	}
+}
+
+// Written as a tag byte, the same as a derived enum would be, followed by
+// the magnitude for `MoveHorizontal`.
+impl NetType for PlayerIntent {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_u8(self.tag());
+		match self {
+			PlayerIntent::MoveHorizontal(magnitude) => magnitude.to_bytes(writer),
+			_ => Ok(()),
+		}
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		match self {
+			PlayerIntent::MoveHorizontal(_) => 2,
+			_ => 1,
+		}
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		match u8::from_bytes(reader)? {
+			Self::TAG_MOVE_LEFT => Ok(PlayerIntent::MoveLeft),
+			Self::TAG_MOVE_RIGHT => Ok(PlayerIntent::MoveRight),
+			Self::TAG_JUMP_START => Ok(PlayerIntent::JumpStart),
+			Self::TAG_JUMP_HOLD => Ok(PlayerIntent::JumpHold),
+			Self::TAG_MOVE_HORIZONTAL => Ok(PlayerIntent::MoveHorizontal(i8::from_bytes(reader)?)),
+			tag => Err(DecodeError::InvalidTag { ty: "PlayerIntent", tag })
+		}
+	}
+}
+
+impl Intent for PlayerIntent {
+	/// Leaves at most one horizontal and one jump intent, in that order:
+	///
+	/// - `MoveLeft` and `MoveRight` together cancel out, leaving neither.
+	/// - Either of them overrides any `MoveHorizontal`, as does a
+	///   `MoveHorizontal(0)`, which does nothing. Of several analog moves,
+	///   only the first is kept.
+	/// - `JumpStart` overrides `JumpHold`.
+	/// - Duplicates collapse into one.
+	fn normalize(intents: &mut Vec<Self>) {
+		let (mut left, mut right, mut analog, mut jump_start, mut jump_hold) = (false, false, None, false, false);
+		for intent in intents.iter() {
+			match intent {
+				PlayerIntent::MoveLeft => left = true,
+				PlayerIntent::MoveRight => right = true,
+				PlayerIntent::MoveHorizontal(magnitude) => analog = analog.or(Some(*magnitude)),
+				PlayerIntent::JumpStart => jump_start = true,
+				PlayerIntent::JumpHold => jump_hold = true,
+			}
+		}
+
+		intents.clear();
+		match (left, right, analog) {
+			(true, true, _) => {},
+			(true, false, _) => intents.push(PlayerIntent::MoveLeft),
+			(false, true, _) => intents.push(PlayerIntent::MoveRight),
+			(false, false, Some(magnitude)) if magnitude != 0 => intents.push(PlayerIntent::MoveHorizontal(magnitude)),
+			(false, false, _) => {},
+		}
+		if jump_start {
+			intents.push(PlayerIntent::JumpStart);
+		} else if jump_hold {
+			intents.push(PlayerIntent::JumpHold);
+		}
+	}
+
+	/// Packs frames of intents without a payload into a single byte, with a
+	/// bit per intent. Anything else, including frames that wouldn't unpack
+	/// in the same order, gets written as `LIST_FORMAT` followed by the
+	/// usual list. Ticks hash the intents themselves, so the format doesn't
+	/// change a tick's hash.
+	fn write_intents(intents: &[Self], writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		let mut mask = 0;
+		for intent in intents {
+			match Self::PACKED_BITS.iter().find(|(_, x)| x == intent) {
+				Some((bit, _)) => mask |= bit,
+				None => mask = Self::LIST_FORMAT,
+			}
+		}
+
+		if mask & Self::LIST_FORMAT == 0 && Self::packed(mask).eq(intents.iter().copied()) {
+			writer.write_u8(mask);
+		} else {
+			writer.write_u8(Self::LIST_FORMAT);
+			writer.write_varint(intents.len() as u64);
+			for intent in intents {
+				intent.to_bytes(writer)?;
+			}
+		}
+		Ok(())
+	}
+
+	fn read_intents(reader: &mut ByteReader) -> Result<Vec<Self>, DecodeError> {
+		let mask = u8::from_bytes(reader)?;
+		let known_bits = Self::PACKED_BITS.iter().fold(0, |bits, (bit, _)| bits | bit);
+		match mask {
+			Self::LIST_FORMAT => reader.read_vec(MAX_INTENTS_PER_FRAME, "intents"),
+			// The remaining bits are reserved for intents to come.
+			mask if mask & !known_bits != 0 => Err(DecodeError::InvalidTag { ty: "packed PlayerIntents", tag: mask }),
+			mask => Ok(Self::unpack(mask)),
+		}
+	}
+}
+
+/// Positions never get further from the world than its size, even jumping
+/// off its top, so when they're sent over the wire a `u16` fixed-point
+/// value within `extent` of the origin, either way, is plenty; see
+/// `Snapshot::quantized`. Whole pixels come back as they were. The
+/// simulation itself always keeps full precision.
+pub(crate) fn quantize_pos(value: f32, extent: f32) -> u16 {
+	let units = (value.max(-extent).min(extent) + extent) / extent * 32768.0;
+	units.round().min(u16::MAX as f32) as u16
+}
+
+pub(crate) fn dequantize_pos(value: u16, extent: f32) -> f32 {
+	value as f32 / 32768.0 * extent - extent
+}
+
+/// The ClientId is assigned to each player *by the server they connect to*,
+/// in its answer to a `Packet::ConnectRequest`.
+pub type ClientId = u16;
+
+/// A command frame is a collection of a player's intents, and their unique ClientId.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandFrame<I: Intent> {
+	pub(crate) owner: ClientId,
+	pub(crate) intents: Vec<I>,
+	/// How far into the tick the earliest of the intents came in, in 256ths
+	/// of a tick; see `offset`.
+	pub(crate) offset: u8
+}
+
+impl<I: Intent> CommandFrame<I> {
+	/// A frame of intents which were all there from the start of the tick.
+	pub fn new(owner: ClientId, intents: Vec<I>) -> Self {
+		Self {
+			owner,
+			intents,
+			offset: 0
+		}
+	}
+
+	/// The same frame, with the earliest intent having come in `offset`
+	/// 256ths of a tick late.
+	pub fn with_offset(mut self, offset: u8) -> Self {
+		self.offset = offset;
+		self
+	}
+
+	pub fn owner(&self) -> ClientId {
+		self.owner
+	}
+
+	pub fn intents(&self) -> &[I] {
+		&self.intents
+	}
+
+	/// How far into the tick the earliest of the intents came in, from 0
+	/// for right at the start, to 255 for right before the end. Intents
+	/// that carried over from the last tick were there from the start.
+	pub fn offset(&self) -> u8 {
+		self.offset
+	}
+
+	/// Brings the frame's intents into their canonical form, see
+	/// `Intent::normalize`.
+	pub fn normalize(&mut self) {
+		I::normalize(&mut self.intents);
+		// Without intents, there's nothing for it to have been late for.
+		if self.intents.is_empty() {
+			self.offset = 0;
+		}
+	}
+
+	/// Cuts the frame down to what a single player may do in a tick: its
+	/// canonical intents, see `normalize`, and no more of them than `limits`
+	/// allow, keeping the first. Returns whether that changed anything,
+	/// which it never does for frames a client normalized before sending,
+	/// as `Game::predict_tick` does. Goes by the frame alone, so everyone
+	/// clamping the same frame ends up with the same one.
+	pub fn clamp(&mut self, limits: &FrameLimits) -> bool {
+		let before = self.clone();
+		self.normalize();
+		self.intents.truncate(limits.max_intents);
+		if self.intents.is_empty() {
+			self.offset = 0;
+		}
+		*self != before
+	}
+
+	pub fn update_hasher(&self, hasher: &mut impl TickHasher) {
+		// Counts are hashed at a fixed width, independent of how they're encoded.
+		hasher.update(&self.owner.to_le_bytes());
+		hasher.update(&(self.intents.len() as u64).to_le_bytes());
+		for intent in &self.intents {
+			intent.update_hasher(hasher);
+		}
+		hasher.update(&[self.offset]);
+	}
+}
+
+/// How much a single command frame may ask for, on top of what normalizing
+/// already rules out; see `CommandFrame::clamp`. For `PlayerIntent`, that's
+/// at most one horizontal move and one jump, whatever the limits.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameLimits {
+	/// Most intents a frame keeps, once normalized.
+	pub max_intents: usize,
+}
+
+impl Default for FrameLimits {
+	fn default() -> Self {
+		Self { max_intents: MAX_INTENTS_PER_FRAME }
+	}
+}
+
+/// Turns away frames the other end would fail to read the intents of.
+fn check_intents<I>(intents: &[I]) -> Result<(), EncodeError> {
+	match intents.len() {
+		len if len > MAX_INTENTS_PER_FRAME => Err(EncodeError::TooManyIntents { len, max: MAX_INTENTS_PER_FRAME }),
+		_ => Ok(()),
+	}
+}
+
+/// Turns away ticks the other end would fail to read the frames of.
+fn check_frames<T>(frames: &[T]) -> Result<(), EncodeError> {
+	match frames.len() {
+		len if len > MAX_FRAMES_PER_TICK => Err(EncodeError::TooManyFrames { len, max: MAX_FRAMES_PER_TICK }),
+		_ => Ok(()),
+	}
+}
+
+// Intents are written however the intent type prefers, see
+// `Intent::write_intents`.
+impl<I: Intent> NetType for CommandFrame<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.owner.to_bytes(writer)?;
+		check_intents(&self.intents)?;
+		I::write_intents(&self.intents, writer)?;
+		self.offset.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.owner.byte_len_hint() + I::intents_len_hint(&self.intents) + 1
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		Ok(Self {
+			owner: ClientId::from_bytes(reader)?,
+			intents: I::read_intents(reader)?,
+			offset: u8::from_bytes(reader)?
+		})
+	}
+}
+
+/// An ordinally indexed collection of CommandFrames, with a checksum;
+/// see `hash::Hasher`.
+///
+/// The server assembles its ticks with a frame from every client in the
+/// game, in ascending ClientId order; see `canonical`. The order goes into
+/// the hash, so a prediction has to be put together the same way for the
+/// server to confirm it, rather than send the same frames back reordered.
+///
+/// The fields are private, so that the contents can't change without the
+/// hash being redone.
+#[derive(Clone, Debug)]
+pub struct Tick<I: Intent> {
+	index: u64,
+	command_frames: Vec<CommandFrame<I>>,
+	/// Only computed once something asks for it, as plenty of ticks are
+	/// thrown away before anyone does.
+	hash: OnceCell<Hash>
+}
+
+// The hash follows from the rest, and either side may not have computed
+// it yet, so it's left out.
+impl<I: Intent> PartialEq for Tick<I> {
+	fn eq(&self, other: &Self) -> bool {
+		self.index == other.index && self.command_frames == other.command_frames
+	}
+}
+
+impl<I: Intent> Tick<I> {
+	pub fn new(index: u64, command_frames: Vec<CommandFrame<I>>) -> Self {
+		Tick {
+			index,
+			command_frames,
+			hash: OnceCell::new()
+		}
+	}
+
+	/// A tick with `command_frames` in canonical order: by ascending
+	/// ClientId of their owner, and those of the same owner in the order
+	/// given. That's how the server assembles its ticks.
+	pub fn canonical(index: u64, mut command_frames: Vec<CommandFrame<I>>) -> Self {
+		command_frames.sort_by_key(|x| x.owner);
+		Tick::new(index, command_frames)
+	}
+
+	/// Drops the command frames `keep` returns false for. The hash gets
+	/// redone if any were, the next time it's asked for.
+	pub(crate) fn retain_frames(&mut self, keep: impl FnMut(&CommandFrame<I>) -> bool) {
+		let len = self.command_frames.len();
+		self.command_frames.retain(keep);
+		if self.command_frames.len() != len {
+			self.hash = OnceCell::new();
+		}
+	}
+
+	/// Clamps every command frame, see `CommandFrame::clamp`, returning the
+	/// owners of those it changed. The hash gets redone if there were any,
+	/// the next time it's asked for.
+	pub(crate) fn clamp_frames(&mut self, limits: &FrameLimits) -> Vec<ClientId> {
+		let clamped: Vec<ClientId> = self.command_frames
+			.iter_mut()
+			.filter_map(|x| x.clamp(limits).then_some(x.owner))
+			.collect();
+		if !clamped.is_empty() {
+			self.hash = OnceCell::new();
+		}
+		clamped
+	}
+
+	/// Hashes the tick's contents with `H`, whichever algorithm the tick
+	/// itself is hashed with.
+	pub fn hash_with<H: TickHasher>(&self) -> H::Output {
+		let mut hasher = H::default();
+		hasher.update(&self.index.to_le_bytes());
+		hasher.update(&(self.command_frames.len() as u64).to_le_bytes());
+		for command_frame in &self.command_frames {
+			command_frame.update_hasher(&mut hasher);
+		}
+		hasher.finish()
+	}
+
+	pub fn index(&self) -> u64 {
+		self.index
+	}
+
+	pub fn command_frames(&self) -> &[CommandFrame<I>] {
+		&self.command_frames
+	}
+
+	/// The hash of the tick's contents, computed on first use.
+	pub fn hash(&self) -> &Hash {
+		self.hash.get_or_init(|| self.hash_with::<Hasher>())
+	}
+
+	pub fn frame_of(&self, owner: ClientId) -> Option<&CommandFrame<I>> {
+		self.command_frames.iter().find(|x| x.owner == owner)
+	}
+
+	/// Whether nobody did anything on this tick, every frame in it being
+	/// empty.
+	pub fn is_idle(&self) -> bool {
+		self.command_frames.iter().all(|x| x.intents.is_empty())
+	}
+}
+
+// Tick isn't derived, as the transmitted hash has to be checked against
+// the one recomputed from the rest of the fields.
+impl<I: Intent> NetType for Tick<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		check_frames(&self.command_frames)?;
+		writer.write_varint(self.index);
+		self.command_frames.to_bytes(writer)?;
+		self.hash().to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		ByteWriter::varint_len(self.index) + self.command_frames.byte_len_hint() + std::mem::size_of::<Hash>()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let index = reader.read_varint()?;
+		let command_frames = reader.read_vec(MAX_FRAMES_PER_TICK, "command frames")?;
+		let transmitted_hash = Hash::from_bytes(reader)?;
+
+		let tick = Tick::new(
+			index,
+			command_frames
+		);
+		if *tick.hash() != transmitted_hash {
+			return Err(DecodeError::HashMismatch { index });
+		}
+
+		Ok(tick)
+	}
+}
+
+// Not derived either, so that the hash comes out as a hex string, and gets
+// checked the same way on the way back in.
+#[cfg(feature = "serde")]
+impl<I: Intent + serde::Serialize> serde::Serialize for Tick<I> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeStruct;
+		let mut tick = serializer.serialize_struct("Tick", 3)?;
+		tick.serialize_field("index", &self.index)?;
+		tick.serialize_field("command_frames", &self.command_frames)?;
+		tick.serialize_field("hash", &to_hex(self.hash().as_ref()))?;
+		tick.end()
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de, I: Intent + serde::Deserialize<'de>> serde::Deserialize<'de> for Tick<I> {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(serde::Deserialize)]
+		struct Fields<I: Intent> {
+			index: u64,
+			command_frames: Vec<CommandFrame<I>>,
+			hash: String,
+		}
+
+		let fields = Fields::<I>::deserialize(deserializer)?;
+		let tick = Tick::new(fields.index, fields.command_frames);
+		let hash = to_hex(tick.hash().as_ref());
+		if !hash.eq_ignore_ascii_case(&fields.hash) {
+			return Err(serde::de::Error::custom(format!("tick {} hashes to {}, not {}", tick.index, hash, fields.hash)));
+		}
+		Ok(tick)
+	}
+}
+
+/// How far back from the tick being sent its baseline can be. Past that,
+/// with no newer tick acknowledged, ticks are sent whole until one is.
+pub(crate) const BASELINE_WINDOW: u64 = TICKRATE as u64;
+
+/// A command frame within a `TickDelta`. The intents and offset are left
+/// out if they're the same as in the baseline's frame of the same owner.
+pub struct FrameDelta<I: Intent> {
+	pub(crate) owner: ClientId,
+	pub(crate) intents: Option<Vec<I>>,
+	/// Only written along with the intents; taken from the baseline
+	/// otherwise.
+	pub(crate) offset: u8
+}
+
+impl<I: Intent> NetType for FrameDelta<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.owner.to_bytes(writer)?;
+		self.intents.is_some().to_bytes(writer)?;
+		match &self.intents {
+			Some(intents) => {
+				check_intents(intents)?;
+				I::write_intents(intents, writer)?;
+				self.offset.to_bytes(writer)
+			},
+			None => Ok(()),
+		}
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		2 + self.intents.as_ref().map_or(0, |x| 1 + I::intents_len_hint(x))
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let owner = ClientId::from_bytes(reader)?;
+		let (intents, offset) = match bool::from_bytes(reader)? {
+			true => (Some(I::read_intents(reader)?), u8::from_bytes(reader)?),
+			false => (None, 0),
+		};
+		Ok(Self { owner, intents, offset })
+	}
+}
+
+/// A tick, written relative to an earlier `baseline` tick.
+pub struct TickDelta<I: Intent> {
+	pub(crate) index: u64,
+	pub(crate) baseline: u64,
+	pub(crate) command_frames: Vec<FrameDelta<I>>,
+	/// The hash of the reconstructed tick, not of the delta.
+	pub(crate) hash: Hash
+}
+
+impl<I: Intent> TickDelta<I> {
+	pub(crate) fn between(tick: &Tick<I>, baseline: &Tick<I>) -> Self {
+		let command_frames = tick.command_frames
+			.iter()
+			.map(|command_frame| {
+				let unchanged = baseline.frame_of(command_frame.owner)
+					.is_some_and(|x| x.intents == command_frame.intents && x.offset == command_frame.offset);
+				FrameDelta {
+					owner: command_frame.owner,
+					intents: (!unchanged).then(|| command_frame.intents.clone()),
+					offset: command_frame.offset
+				}
+			})
+			.collect();
+
+		Self {
+			index: tick.index,
+			baseline: baseline.index,
+			command_frames,
+			hash: *tick.hash()
+		}
+	}
+
+	/// Rebuilds the full tick, which has to hash to what the delta was sent with.
+	pub(crate) fn resolve(self, baseline: Option<&Tick<I>>) -> Result<Tick<I>, DecodeError> {
+		let baseline = match baseline {
+			Some(baseline) if baseline.index == self.baseline => baseline,
+			_ => return Err(DecodeError::StaleBaseline {
+				expected: self.baseline,
+				found: baseline.map(|x| x.index)
+			}),
+		};
+
+		let mut command_frames = Vec::with_capacity(self.command_frames.len());
+		for frame in self.command_frames {
+			let (intents, offset) = match frame.intents {
+				Some(intents) => (intents, frame.offset),
+				None => match baseline.frame_of(frame.owner) {
+					Some(command_frame) => (command_frame.intents.clone(), command_frame.offset),
+					None => return Err(DecodeError::MissingBaselineFrame { owner: frame.owner }),
+				},
+			};
+			command_frames.push(CommandFrame {
+				owner: frame.owner,
+				intents,
+				offset
+			});
+		}
+
+		let tick = Tick::new(
+			self.index,
+			command_frames
+		);
+		if *tick.hash() != self.hash {
+			return Err(DecodeError::HashMismatch { index: self.index });
+		}
+
+		Ok(tick)
+	}
+}
+
+impl<I: Intent> NetType for TickDelta<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		check_frames(&self.command_frames)?;
+		writer.write_varint(self.index);
+		writer.write_varint(self.baseline);
+		self.command_frames.to_bytes(writer)?;
+		self.hash.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		ByteWriter::varint_len(self.index)
+			+ ByteWriter::varint_len(self.baseline)
+			+ self.command_frames.byte_len_hint()
+			+ std::mem::size_of::<Hash>()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		Ok(Self {
+			index: reader.read_varint()?,
+			baseline: reader.read_varint()?,
+			command_frames: reader.read_vec(MAX_FRAMES_PER_TICK, "command frames")?,
+			hash: Hash::from_bytes(reader)?
+		})
+	}
+}
+
+/// A tick as sent by a client; either whole, or relative to an earlier one.
+pub enum EncodedTick<I: Intent> {
+	Keyframe(Tick<I>),
+	Delta(TickDelta<I>),
+}
+
+impl<I: Intent> EncodedTick<I> {
+	pub const TAG_KEYFRAME: u8 = 0;
+	pub const TAG_DELTA: u8 = 1;
+	pub fn tag(&self) -> u8 {
+		match self {
+			EncodedTick::Keyframe(_) => Self::TAG_KEYFRAME,
+			EncodedTick::Delta(_) => Self::TAG_DELTA,
+		}
+	}
+
+	/// The index of the tick, which is known even if it can't be decoded.
+	pub fn index(&self) -> u64 {
+		match self {
+			EncodedTick::Keyframe(tick) => tick.index,
+			EncodedTick::Delta(delta) => delta.index,
+		}
+	}
+}
+
+impl<I: Intent> NetType for EncodedTick<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_u8(self.tag());
+		match self {
+			EncodedTick::Keyframe(tick) => tick.to_bytes(writer),
+			EncodedTick::Delta(delta) => delta.to_bytes(writer),
+		}
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		1 + match self {
+			EncodedTick::Keyframe(tick) => tick.byte_len_hint(),
+			EncodedTick::Delta(delta) => delta.byte_len_hint(),
+		}
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		match u8::from_bytes(reader)? {
+			Self::TAG_KEYFRAME => Ok(EncodedTick::Keyframe(Tick::from_bytes(reader)?)),
+			Self::TAG_DELTA => Ok(EncodedTick::Delta(TickDelta::from_bytes(reader)?)),
+			tag => Err(DecodeError::InvalidTag { ty: "EncodedTick", tag })
+		}
+	}
+}
+
+/// Sending side of the delta encoding. Ticks are written against the
+/// newest one the receiver acknowledged, which it's sure to have, so that
+/// a lost packet doesn't take the ones after it down with it.
+pub struct TickEncoder<I: Intent> {
+	/// The ticks sent within `BASELINE_WINDOW` of the newest one.
+	pub(crate) sent: VecDeque<Tick<I>>,
+	/// The newest tick the receiver got whole; see `acknowledge`.
+	pub(crate) acknowledged: Option<u64>,
+}
+
+// Not derived, as that would require `I: Default`.
+impl<I: Intent> Default for TickEncoder<I> {
+	fn default() -> Self {
+		Self {
+			sent: VecDeque::new(),
+			acknowledged: None
+		}
+	}
+}
+
+impl<I: Intent> TickEncoder<I> {
+	pub fn encode(&mut self, tick: &Tick<I>) -> EncodedTick<I> {
+		let baseline = self.acknowledged
+			.filter(|x| *x < tick.index && tick.index - x <= BASELINE_WINDOW)
+			.and_then(|x| self.sent.iter().find(|y| y.index == x));
+		let encoded = match baseline {
+			Some(baseline) => EncodedTick::Delta(TickDelta::between(tick, baseline)),
+			None => EncodedTick::Keyframe(tick.clone()),
+		};
+		let oldest = tick.index.saturating_sub(BASELINE_WINDOW);
+		self.sent.retain(|x| x.index >= oldest && x.index != tick.index);
+		self.sent.push_back(tick.clone());
+		encoded
+	}
+
+	/// The receiver decoded the tick at `index`, so later ones can be
+	/// written against it. Ticks it only got the past frames of don't
+	/// count, as those don't make a whole tick.
+	pub fn acknowledge(&mut self, index: u64) {
+		if self.acknowledged.is_some_and(|x| x >= index) {
+			return;
+		}
+		if self.sent.iter().any(|x| x.index == index) {
+			self.acknowledged = Some(index);
+		}
+	}
+}
+
+/// Receiving side of the delta encoding. A delta is resolved against
+/// whichever of the recently decoded ticks it names as its baseline, and
+/// rejected if that one never made it here.
+pub struct TickDecoder<I: Intent> {
+	/// The ticks decoded within `BASELINE_WINDOW` of the newest one.
+	pub(crate) baselines: BTreeMap<u64, Tick<I>>,
+	/// Recently received tick indices, so that frames sent redundantly
+	/// aren't handed to the server more than once.
+	pub(crate) seen: BTreeSet<u64>,
+}
+
+impl<I: Intent> Default for TickDecoder<I> {
+	fn default() -> Self {
+		Self {
+			baselines: BTreeMap::new(),
+			seen: BTreeSet::new()
+		}
+	}
+}
+
+impl<I: Intent> TickDecoder<I> {
+	/// How far back received indices are remembered. Anything older is
+	/// assumed to have been seen already.
+	pub(crate) const SEEN_WINDOW: u64 = TICK_RETENTION;
+
+	/// Whether `tick_index` is new to us; remembers it if it is.
+	pub fn first_sighting(&mut self, tick_index: u64) -> bool {
+		let newest = self.seen.last().copied().unwrap_or(0).max(tick_index);
+		let oldest = newest.saturating_sub(Self::SEEN_WINDOW);
+		if tick_index < oldest {
+			return false;
+		}
+		self.seen.retain(|x| *x >= oldest);
+		self.seen.insert(tick_index)
+	}
+
+	/// Turns the past frames repeated in a packet into ticks, one for every
+	/// tick they're from, oldest first, skipping those whose packets did
+	/// arrive the first time around.
+	pub fn recover(&mut self, tick_index: u64, past_frames: Vec<PastFrame<I>>) -> Vec<Tick<I>> {
+		let mut recovered: BTreeMap<u64, Option<Vec<CommandFrame<I>>>> = BTreeMap::new();
+		for past_frame in past_frames {
+			let Some(index) = tick_index.checked_sub(past_frame.age as u64) else { continue };
+			if past_frame.age == 0 {
+				continue;
+			}
+			let frames = recovered
+				.entry(index)
+				.or_insert_with(|| self.first_sighting(index).then(Vec::new));
+			if let Some(frames) = frames {
+				frames.push(CommandFrame {
+					owner: past_frame.owner,
+					intents: past_frame.intents,
+					offset: past_frame.offset
+				});
+			}
+		}
+		recovered
+			.into_iter()
+			.filter_map(|(index, frames)| Some(Tick::canonical(index, frames?)))
+			.collect()
+	}
+
+	pub fn decode(&mut self, encoded: EncodedTick<I>) -> Result<Tick<I>, DecodeError> {
+		let tick = match encoded {
+			EncodedTick::Keyframe(tick) => tick,
+			EncodedTick::Delta(delta) => {
+				// Reported as what was found instead, should it be missing.
+				let baseline = self.baselines.get(&delta.baseline).or_else(|| self.baselines.values().next_back());
+				delta.resolve(baseline)?
+			},
+		};
+		let newest = self.baselines.keys().next_back().copied().unwrap_or(0).max(tick.index);
+		let oldest = newest.saturating_sub(BASELINE_WINDOW);
+		self.baselines.retain(|x, _| *x >= oldest);
+		if tick.index >= oldest {
+			self.baselines.insert(tick.index, tick.clone());
+		}
+		Ok(tick)
+	}
+}
+
+/// A single player's state within a snapshot.
+#[derive(NetType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotEntry {
+	pub(crate) owner: ClientId,
+	pub(crate) player: Player
+}
+
+/// What a client tells everyone else about one of its players when it
+/// joins, which has no bearing on the simulation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerInfo {
+	pub owner: ClientId,
+	/// Shown above the player. Doesn't have to be unique.
+	pub name: String,
+}
+
+impl PlayerInfo {
+	/// Cuts `name` down to `MAX_NAME_LEN` bytes, at a char boundary, should
+	/// it be any longer.
+	pub fn new(owner: ClientId, name: &str) -> Self {
+		Self { owner, name: truncate(name, MAX_NAME_LEN).into() }
+	}
+}
+
+/// The longest start of `text` that's at most `max` bytes long, without
+/// splitting a char.
+pub(crate) fn truncate(text: &str, max: usize) -> &str {
+	let mut len = text.len().min(max);
+	while !text.is_char_boundary(len) {
+		len -= 1;
+	}
+	&text[..len]
+}
+
+impl NetType for PlayerInfo {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.owner.to_bytes(writer)?;
+		self.name.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.owner.byte_len_hint() + self.name.byte_len_hint()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let owner = ClientId::from_bytes(reader)?;
+		let name = reader.read_string(MAX_NAME_LEN, "name")?;
+		Ok(Self { owner, name })
+	}
+}
+
+/// The whole world as it was right after tick `index` got simulated.
+/// Lets a client that connects late pick up where everyone else is.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+	pub(crate) index: u64,
+	/// The game's seed, which joining clients can't have gotten anywhere else
+	/// if it changed since they were welcomed.
+	pub(crate) seed: u64,
+	/// The same goes for the sim config.
+	pub(crate) sim_config: SimConfig,
+	/// Whether the players' positions go over the wire quantized; see
+	/// `quantized`.
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub(crate) quantized: bool,
+	pub(crate) players: Vec<SnapshotEntry>,
+	/// The level, which only ever comes along with a snapshot.
+	pub(crate) platforms: Vec<Platform>,
+	pub(crate) coins: Coins,
+	/// Everyone's names, so that late joiners don't have to wait for them.
+	pub(crate) infos: Vec<PlayerInfo>
+}
+
+impl Snapshot {
+	/// Has the players' positions sent as `u16`s rather than in full, see
+	/// `quantize_pos`, for a snapshot some 4 bytes a player smaller. Off by
+	/// less than a pixel, it's only good for watching: a client predicting
+	/// off of it would desync.
+	pub fn quantized(mut self) -> Self {
+		self.quantized = true;
+		self
+	}
+
+	/// How far positions get quantized within, along either axis; see
+	/// `quantize_pos`.
+	fn extents(&self) -> Option<(f32, f32)> {
+		self.quantized.then(|| (scalar_to_f32(self.sim_config.world_width), scalar_to_f32(self.sim_config.world_height)))
+	}
+}
+
+// Not derived, as the players are written depending on `quantized`.
+impl NetType for Snapshot {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.index.to_bytes(writer)?;
+		self.seed.to_bytes(writer)?;
+		self.sim_config.to_bytes(writer)?;
+		self.quantized.to_bytes(writer)?;
+		let extents = self.extents();
+		writer.write_varint(self.players.len() as u64);
+		for entry in &self.players {
+			entry.owner.to_bytes(writer)?;
+			entry.player.write(writer, extents)?;
+		}
+		self.platforms.to_bytes(writer)?;
+		self.coins.to_bytes(writer)?;
+		self.infos.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.index.byte_len_hint()
+			+ self.seed.byte_len_hint()
+			+ self.sim_config.byte_len_hint()
+			+ 1
+			+ self.players.byte_len_hint()
+			+ self.platforms.byte_len_hint()
+			+ self.coins.byte_len_hint()
+			+ self.infos.byte_len_hint()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let mut snapshot = Self {
+			index: u64::from_bytes(reader)?,
+			seed: u64::from_bytes(reader)?,
+			sim_config: SimConfig::from_bytes(reader)?,
+			quantized: bool::from_bytes(reader)?,
+			players: vec![],
+			platforms: vec![],
+			coins: Coins::default(),
+			infos: vec![]
+		};
+		let extents = snapshot.extents();
+		let len = reader.read_len(MAX_PLAYERS, "players")?;
+		for _ in 0..len {
+			let owner = ClientId::from_bytes(reader)?;
+			snapshot.players.push(SnapshotEntry { owner, player: Player::read(reader, extents)? });
+		}
+		snapshot.platforms = reader.read_vec(MAX_PLATFORMS, "platforms")?;
+		snapshot.coins = Coins::from_bytes(reader)?;
+		snapshot.infos = reader.read_vec(MAX_PLAYERS, "player infos")?;
+		Ok(snapshot)
+	}
+}
+
+/// One of the sender's own command frames from an earlier tick, repeated in
+/// case the packet which first carried it got lost.
+#[derive(Clone)]
+pub struct PastFrame<I: Intent> {
+	/// Which of the sender's players it's from.
+	pub(crate) owner: ClientId,
+	/// How many ticks before the packet's own tick this frame belongs to.
+	pub(crate) age: u8,
+	pub(crate) intents: Vec<I>,
+	/// See `CommandFrame::offset`.
+	pub(crate) offset: u8
+}
+
+impl<I: Intent> NetType for PastFrame<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.owner.to_bytes(writer)?;
+		self.age.to_bytes(writer)?;
+		check_intents(&self.intents)?;
+		I::write_intents(&self.intents, writer)?;
+		self.offset.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		4 + I::intents_len_hint(&self.intents)
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		Ok(Self {
+			owner: ClientId::from_bytes(reader)?,
+			age: u8::from_bytes(reader)?,
+			intents: I::read_intents(reader)?,
+			offset: u8::from_bytes(reader)?
+		})
+	}
+}
+
+/// The hash of the world right after tick `index` got simulated, see
+/// `Game::state_hash`.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct StateHash {
+	pub index: u64,
+	pub hash: Hash
+}
+
+/// Sent by a client while connecting, to find out which tick the server
+/// is on; see `Game::sync_clock`.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct TimeRequest {
+	pub sender: ClientId,
+	/// When the request went out, in seconds, by the client's own clock.
+	/// Only ever compared against that same clock, once it comes back.
+	pub client_time: f64,
+}
+
+/// The server's answer to a `TimeRequest`.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct TimeResponse {
+	/// Echoed back from the request.
+	pub client_time: f64,
+	/// The tick the server is in the middle of, as of answering.
+	pub tick: u64,
+	/// How far into `tick` the server is, from 0 to 1.
+	pub progress: f32,
+}
+
+/// Sent by a client until the server lets it in, before anything else but
+/// time requests; see `Game::connecting`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ConnectRequest {
+	/// A ClientId to get, rather than whichever the server hands out. Only
+	/// meant for debugging, as the server refuses ids already in use.
+	pub requested_id: Option<ClientId>,
+	/// What the player would like to go by, see `PlayerInfo`.
+	pub name: Option<String>,
+}
+
+impl NetType for ConnectRequest {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.requested_id.to_bytes(writer)?;
+		self.name.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.requested_id.byte_len_hint() + self.name.byte_len_hint()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		let requested_id = Option::from_bytes(reader)?;
+		let name = match bool::from_bytes(reader)? {
+			true => Some(reader.read_string(MAX_NAME_LEN, "name")?),
+			false => None,
+		};
+		Ok(Self { requested_id, name })
+	}
+}
+
+/// The server letting a client in.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct ConnectAccept {
+	/// Who the client plays as, from now on.
+	pub client_id: ClientId,
+	/// The server's tick rate, see `TickRate`.
+	pub tick_rate: u8,
+	/// The first tick the client's player is in the game for.
+	pub starting_tick: u64,
+	/// The world the server simulates, which the client has to as well.
+	pub sim_config: SimConfig,
+}
+
+/// Why the server turned a client away.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, NetType)]
+pub enum RejectReason {
+	/// Someone else already plays as the ClientId the client asked for.
+	IdTaken = 0,
+	/// There's no room for another player.
+	GameFull = 1,
+}
+
+impl fmt::Display for RejectReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			RejectReason::IdTaken => write!(f, "the requested ClientId is already taken"),
+			RejectReason::GameFull => write!(f, "the game is full"),
+		}
+	}
+}
+
+/// What got a client to ask for a snapshot; see `Game::request_snapshot`.
+/// Nothing the server has to act on, other than by sending one, but it
+/// makes for telling logs.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, NetType)]
+pub enum SnapshotReason {
+	/// The server corrected a tick we no longer have.
+	Pruned = 0,
+	/// Back from a pause, the server's ticks didn't get us most of the way
+	/// to where it's at.
+	Paused = 1,
+	/// Syncing the clock again, we turned out to be too far behind to
+	/// catch up.
+	FarBehind = 2,
+	/// A spectator got a tick which doesn't follow on from its history.
+	Gap = 3,
+	/// Back after the connection got lost.
+	Reconnect = 4,
+	/// A saved state got restored, whose predictions the server may never
+	/// have gotten.
+	Restored = 5,
+}
+
+impl fmt::Display for SnapshotReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SnapshotReason::Pruned => write!(f, "correction past the history"),
+			SnapshotReason::Paused => write!(f, "resumed too late"),
+			SnapshotReason::FarBehind => write!(f, "too far behind"),
+			SnapshotReason::Gap => write!(f, "gap in the ticks"),
+			SnapshotReason::Reconnect => write!(f, "reconnected"),
+			SnapshotReason::Restored => write!(f, "restored a saved state"),
+		}
+	}
+}
+
+/// A client asking for a `Snapshot`, having lost track of the server.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, NetType)]
+pub struct SnapshotRequest {
+	pub sender: ClientId,
+	pub reason: SnapshotReason,
+}
+
+/// Everything that goes over the wire, in either direction. Written as the
+/// protocol header, then a tag byte, followed by the message itself.
+pub enum Packet<I: Intent> {
+	/// A tick predicted by `sender`, on its way to the server, along with
+	/// the sender's frames for the ticks right before it. Every so often,
+	/// it also carries the sender's state hash for a tick it confirmed.
+	PredictedTick {
+		sender: ClientId,
+		tick: EncodedTick<I>,
+		past_frames: Vec<PastFrame<I>>,
+		state_hash: Option<StateHash>
+	},
+	/// The server's version of a tick.
+	AuthoritativeTick(Tick<I>),
+	/// The server received a tick from us, whole, rather than only our
+	/// frames repeated in a later one. Says nothing about whether our
+	/// prediction was correct.
+	Received { index: u64 },
+	/// The hash the server computed for a tick, which confirms our prediction
+	/// if it matches our own.
+	Ack { index: u64, hash: Hash },
+	/// The whole world, for joining a game that's already running.
+	Snapshot(Snapshot),
+	Join(ClientId),
+	Leave(ClientId),
+	/// Sent by the server to a client joining, before any ticks: how many
+	/// ticks per second it runs at, what the simulation's random numbers
+	/// are seeded with, and the world it simulates.
+	Welcome { tick_rate: u8, seed: u64, sim_config: SimConfig },
+	/// The server's own state hash for a tick a client reported one for.
+	/// The client has desynced if it doesn't match its own.
+	StateHash(StateHash),
+	/// A player's name, sent by its client when joining and relayed by the
+	/// server to everyone else.
+	PlayerInfo(PlayerInfo),
+	/// A chat message, which the server relays to everyone, the sender
+	/// included. Nothing the simulation ever looks at.
+	Chat { sender: ClientId, text: String },
+	TimeRequest(TimeRequest),
+	TimeResponse(TimeResponse),
+	/// A client asking to be let into the game.
+	ConnectRequest(ConnectRequest),
+	ConnectAccept(ConnectAccept),
+	ConnectReject(RejectReason),
+	SnapshotRequest(SnapshotRequest),
+}
+
+impl<I: Intent> Packet<I> {
+	pub const TAG_PREDICTED_TICK: u8 = 0;
+	pub const TAG_AUTHORITATIVE_TICK: u8 = 1;
+	pub const TAG_RECEIVED: u8 = 2;
+	pub const TAG_ACK: u8 = 3;
+	pub const TAG_SNAPSHOT: u8 = 4;
+	pub const TAG_JOIN: u8 = 5;
+	pub const TAG_LEAVE: u8 = 6;
+	pub const TAG_WELCOME: u8 = 7;
+	pub const TAG_STATE_HASH: u8 = 8;
+	pub const TAG_PLAYER_INFO: u8 = 9;
+	pub const TAG_CHAT: u8 = 10;
+	pub const TAG_TIME_REQUEST: u8 = 11;
+	pub const TAG_TIME_RESPONSE: u8 = 12;
+	pub const TAG_CONNECT_REQUEST: u8 = 13;
+	pub const TAG_CONNECT_ACCEPT: u8 = 14;
+	pub const TAG_CONNECT_REJECT: u8 = 15;
+	pub const TAG_SNAPSHOT_REQUEST: u8 = 16;
+	pub fn tag(&self) -> u8 {
+		match self {
+			Packet::PredictedTick { .. } => Self::TAG_PREDICTED_TICK,
+			Packet::AuthoritativeTick(_) => Self::TAG_AUTHORITATIVE_TICK,
+			Packet::Received { .. } => Self::TAG_RECEIVED,
+			Packet::Ack { .. } => Self::TAG_ACK,
+			Packet::Snapshot(_) => Self::TAG_SNAPSHOT,
+			Packet::Join(_) => Self::TAG_JOIN,
+			Packet::Leave(_) => Self::TAG_LEAVE,
+			Packet::Welcome { .. } => Self::TAG_WELCOME,
+			Packet::StateHash(_) => Self::TAG_STATE_HASH,
+			Packet::PlayerInfo(_) => Self::TAG_PLAYER_INFO,
+			Packet::Chat { .. } => Self::TAG_CHAT,
+			Packet::TimeRequest(_) => Self::TAG_TIME_REQUEST,
+			Packet::TimeResponse(_) => Self::TAG_TIME_RESPONSE,
+			Packet::ConnectRequest(_) => Self::TAG_CONNECT_REQUEST,
+			Packet::ConnectAccept(_) => Self::TAG_CONNECT_ACCEPT,
+			Packet::ConnectReject(_) => Self::TAG_CONNECT_REJECT,
+			Packet::SnapshotRequest(_) => Self::TAG_SNAPSHOT_REQUEST,
+		}
+	}
+
+	/// Whether packets tagged `tag` have to arrive, exactly once and in
+	/// order, rather than being made up for by the ones after them; see
+	/// `reliable`. Along with the chat, that's who's in the game and what
+	/// they're called, which no tick repeats, and asking for a snapshot,
+	/// which nothing but a snapshot makes up for.
+	pub fn is_reliable_tag(tag: u8) -> bool {
+		matches!(tag, Self::TAG_JOIN | Self::TAG_LEAVE | Self::TAG_PLAYER_INFO | Self::TAG_CHAT | Self::TAG_SNAPSHOT_REQUEST)
+	}
+
+	/// Reads a packet which has to take up all of `bytes`, as a datagram or
+	/// a message from the host does.
+	pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+		let (packet, len) = Self::from_slice(bytes)?;
+		match bytes.len() - len {
+			0 => Ok(packet),
+			count => Err(DecodeError::TrailingBytes { count }),
+		}
+	}
+}
+
+impl<I: Intent> NetType for Packet<I> {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		// Packets are the outermost thing written into a buffer, so this is
+		// where the room for everything gets made, and where the whole of it
+		// gets measured against what receivers take.
+		let start = writer.len();
+		writer.reserve(self.byte_len_hint());
+		writer.write_bytes(&PROTOCOL_MAGIC);
+		writer.write_u8(PROTOCOL_VERSION);
+		writer.write_u8(Hasher::ID);
+
+		writer.write_u8(self.tag());
+		match self {
+			Packet::PredictedTick { sender, tick, past_frames, state_hash } => {
+				sender.to_bytes(writer)?;
+				tick.to_bytes(writer)?;
+				past_frames.to_bytes(writer)?;
+				state_hash.to_bytes(writer)?;
+			},
+			Packet::AuthoritativeTick(tick) => {
+				tick.to_bytes(writer)?;
+			},
+			Packet::Received { index } => {
+				writer.write_varint(*index);
+			},
+			Packet::Ack { index, hash } => {
+				writer.write_varint(*index);
+				hash.to_bytes(writer)?;
+			},
+			Packet::Snapshot(snapshot) => {
+				snapshot.to_bytes(writer)?;
+			},
+			Packet::Join(client_id) | Packet::Leave(client_id) => {
+				client_id.to_bytes(writer)?;
+			},
+			Packet::Welcome { tick_rate, seed, sim_config } => {
+				tick_rate.to_bytes(writer)?;
+				seed.to_bytes(writer)?;
+				sim_config.to_bytes(writer)?;
+			},
+			Packet::StateHash(state_hash) => {
+				state_hash.to_bytes(writer)?;
+			},
+			Packet::PlayerInfo(info) => {
+				info.to_bytes(writer)?;
+			},
+			Packet::Chat { sender, text } => {
+				sender.to_bytes(writer)?;
+				text.to_bytes(writer)?;
+			},
+			Packet::TimeRequest(request) => {
+				request.to_bytes(writer)?;
+			},
+			Packet::TimeResponse(response) => {
+				response.to_bytes(writer)?;
+			},
+			Packet::ConnectRequest(request) => {
+				request.to_bytes(writer)?;
+			},
+			Packet::ConnectAccept(accept) => {
+				accept.to_bytes(writer)?;
+			},
+			Packet::ConnectReject(reason) => {
+				reason.to_bytes(writer)?;
+			},
+			Packet::SnapshotRequest(request) => {
+				request.to_bytes(writer)?;
+			},
+		}
+
+		let size = writer.len() - start;
+		if size > MAX_PACKET_SIZE {
+			return Err(EncodeError::PacketTooLarge { size, max: MAX_PACKET_SIZE });
+		}
+		Ok(())
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		// Magic, version, hasher and tag.
+		let header = PROTOCOL_MAGIC.len() + 3;
+		header + match self {
+			Packet::PredictedTick { sender, tick, past_frames, state_hash } => {
+				sender.byte_len_hint() + tick.byte_len_hint() + past_frames.byte_len_hint() + state_hash.byte_len_hint()
+			},
+			Packet::AuthoritativeTick(tick) => tick.byte_len_hint(),
+			Packet::Received { index } => ByteWriter::varint_len(*index),
+			Packet::Ack { index, hash } => ByteWriter::varint_len(*index) + hash.byte_len_hint(),
+			Packet::Snapshot(snapshot) => snapshot.byte_len_hint(),
+			Packet::Join(client_id) | Packet::Leave(client_id) => client_id.byte_len_hint(),
+			Packet::Welcome { tick_rate, seed, sim_config } => {
+				tick_rate.byte_len_hint() + seed.byte_len_hint() + sim_config.byte_len_hint()
+			},
+			Packet::StateHash(state_hash) => state_hash.byte_len_hint(),
+			Packet::PlayerInfo(info) => info.byte_len_hint(),
+			Packet::Chat { sender, text } => sender.byte_len_hint() + text.byte_len_hint(),
+			Packet::TimeRequest(request) => request.byte_len_hint(),
+			Packet::TimeResponse(response) => response.byte_len_hint(),
+			Packet::ConnectRequest(request) => request.byte_len_hint(),
+			Packet::ConnectAccept(accept) => accept.byte_len_hint(),
+			Packet::ConnectReject(reason) => reason.byte_len_hint(),
+			Packet::SnapshotRequest(request) => request.byte_len_hint(),
+		}
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		if reader.remaining() > MAX_PACKET_SIZE {
+			return Err(DecodeError::PacketTooLarge { size: reader.remaining(), max: MAX_PACKET_SIZE });
+		}
+		let magic = reader.read_array()?;
+		if magic != PROTOCOL_MAGIC {
+			return Err(DecodeError::WrongMagic { found: magic });
+		}
+		let version = reader.read_u8()?;
+		if version != PROTOCOL_VERSION {
+			return Err(DecodeError::UnsupportedVersion {
+				got: version,
+				expected: PROTOCOL_VERSION
+			});
+		}
+		let hasher = reader.read_u8()?;
+		if hasher != Hasher::ID {
+			return Err(DecodeError::UnsupportedHasher {
+				got: hasher,
+				expected: Hasher::ID
+			});
+		}
+
+		let tag = u8::from_bytes(reader)?;
+		match tag {
+			Self::TAG_PREDICTED_TICK => {
+				let sender = ClientId::from_bytes(reader)?;
+				let tick = EncodedTick::from_bytes(reader)?;
+				let past_frames = reader.read_vec(MAX_PAST_FRAMES, "past frames")?;
+				let state_hash = Option::from_bytes(reader)?;
+				Ok(Packet::PredictedTick { sender, tick, past_frames, state_hash })
+			},
+			Self::TAG_AUTHORITATIVE_TICK => Ok(Packet::AuthoritativeTick(Tick::from_bytes(reader)?)),
+			Self::TAG_RECEIVED => Ok(Packet::Received { index: reader.read_varint()? }),
+			Self::TAG_ACK => {
+				let index = reader.read_varint()?;
+				let hash = Hash::from_bytes(reader)?;
+				Ok(Packet::Ack { index, hash })
+			},
+			Self::TAG_SNAPSHOT => Ok(Packet::Snapshot(Snapshot::from_bytes(reader)?)),
+			Self::TAG_JOIN => Ok(Packet::Join(ClientId::from_bytes(reader)?)),
+			Self::TAG_LEAVE => Ok(Packet::Leave(ClientId::from_bytes(reader)?)),
+			Self::TAG_WELCOME => {
+				let tick_rate = u8::from_bytes(reader)?;
+				let seed = u64::from_bytes(reader)?;
+				let sim_config = SimConfig::from_bytes(reader)?;
+				Ok(Packet::Welcome { tick_rate, seed, sim_config })
+			},
+			Self::TAG_STATE_HASH => Ok(Packet::StateHash(StateHash::from_bytes(reader)?)),
+			Self::TAG_PLAYER_INFO => Ok(Packet::PlayerInfo(PlayerInfo::from_bytes(reader)?)),
+			Self::TAG_CHAT => {
+				let sender = ClientId::from_bytes(reader)?;
+				let text = reader.read_string(MAX_CHAT_LEN, "chat message")?;
+				Ok(Packet::Chat { sender, text })
+			},
+			Self::TAG_TIME_REQUEST => Ok(Packet::TimeRequest(TimeRequest::from_bytes(reader)?)),
+			Self::TAG_TIME_RESPONSE => Ok(Packet::TimeResponse(TimeResponse::from_bytes(reader)?)),
+			Self::TAG_CONNECT_REQUEST => Ok(Packet::ConnectRequest(ConnectRequest::from_bytes(reader)?)),
+			Self::TAG_CONNECT_ACCEPT => Ok(Packet::ConnectAccept(ConnectAccept::from_bytes(reader)?)),
+			Self::TAG_CONNECT_REJECT => Ok(Packet::ConnectReject(RejectReason::from_bytes(reader)?)),
+			Self::TAG_SNAPSHOT_REQUEST => Ok(Packet::SnapshotRequest(SnapshotRequest::from_bytes(reader)?)),
+			tag => Err(DecodeError::InvalidTag { ty: "Packet", tag })
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tick(index: u64) -> Tick<PlayerIntent> {
+		let intents = match index % 3 {
+			0 => vec![PlayerIntent::MoveRight],
+			1 => vec![PlayerIntent::MoveRight, PlayerIntent::JumpStart],
+			_ => vec![],
+		};
+		Tick::new(index, vec![CommandFrame::new(1, intents), CommandFrame::new(2, vec![PlayerIntent::MoveLeft])])
+	}
+
+	#[test]
+	fn a_lost_packet_only_takes_its_own_tick() {
+		let mut encoder = TickEncoder::default();
+		let mut decoder = TickDecoder::default();
+		let mut deltas = 0;
+		let mut received = None;
+		for index in 0..3 * BASELINE_WINDOW {
+			let encoded = encoder.encode(&tick(index));
+			deltas += matches!(encoded, EncodedTick::Delta(_)) as u32;
+			if index == 5 || index == 6 {
+				continue;
+			}
+			let decoded = decoder.decode(encoded).unwrap_or_else(|x| panic!("tick {}: {}", index, x));
+			assert_eq!(decoded.hash(), tick(index).hash());
+			// The receipt takes a tick to come back.
+			if let Some(received) = received.replace(index) {
+				encoder.acknowledge(received);
+			}
+		}
+		assert!(deltas as u64 > 2 * BASELINE_WINDOW, "only {} ticks went out as deltas", deltas);
+	}
+
+	#[test]
+	fn a_delta_against_a_tick_never_received_is_stale() {
+		let mut decoder = TickDecoder::default();
+		decoder.decode(EncodedTick::Keyframe(tick(0))).unwrap();
+		let delta = TickDelta::between(&tick(2), &tick(1));
+		assert!(matches!(decoder.decode(EncodedTick::Delta(delta)), Err(DecodeError::StaleBaseline { expected: 1, found: Some(0) })));
+	}
+
+	#[test]
+	fn nothing_acknowledged_for_too_long_goes_out_whole() {
+		let mut encoder = TickEncoder::default();
+		encoder.encode(&tick(0));
+		encoder.acknowledge(0);
+		assert!(matches!(encoder.encode(&tick(BASELINE_WINDOW)), EncodedTick::Delta(_)));
+		assert!(matches!(encoder.encode(&tick(BASELINE_WINDOW + 1)), EncodedTick::Keyframe(_)));
+		// Nor does a receipt for a tick we never sent change that.
+		encoder.acknowledge(BASELINE_WINDOW + 5);
+		assert!(matches!(encoder.encode(&tick(BASELINE_WINDOW + 6)), EncodedTick::Keyframe(_)));
+	}
+
+	#[test]
+	fn past_frames_are_read_up_to_the_protocol_maximum() {
+		let packet = |frames: usize| {
+			let past_frames = (1..=frames as u8).map(|age| PastFrame { owner: 0, age, intents: vec![PlayerIntent::MoveLeft], offset: 0 }).collect();
+			let packet = Packet::PredictedTick { sender: 1, tick: EncodedTick::Keyframe(tick(20)), past_frames, state_hash: None };
+			let mut writer = ByteWriter::new();
+			packet.to_bytes(&mut writer).unwrap();
+			writer.as_slice().to_vec()
+		};
+		match Packet::<PlayerIntent>::decode(&packet(MAX_PAST_FRAMES)) {
+			Ok(Packet::PredictedTick { past_frames, .. }) => assert_eq!(past_frames.len(), MAX_PAST_FRAMES),
+			other => panic!("expected a predicted tick, got {:?}", other.err()),
+		}
+		assert!(matches!(
+			Packet::<PlayerIntent>::decode(&packet(MAX_PAST_FRAMES + 1)),
+			Err(DecodeError::LimitExceeded { what: "past frames", max: MAX_PAST_FRAMES, .. })
+		));
+	}
+
+	#[test]
+	fn quantized_positions_are_off_by_under_a_pixel() {
+		for extent in [256.0, 1024.0, 4096.0] {
+			let mut value = -extent;
+			while value <= extent {
+				let error = (dequantize_pos(quantize_pos(value, extent), extent) - value).abs();
+				assert!(error < 1.0, "{} came back off by {} within {}", value, error, extent);
+				value += 0.37;
+			}
+			assert_eq!(dequantize_pos(quantize_pos(226.0, extent), extent), 226.0);
+			assert!(extent - dequantize_pos(quantize_pos(2.0 * extent, extent), extent) < 1.0);
+		}
+	}
+
+	#[test]
+	fn quantized_snapshots_only_round_off_positions() {
+		let mut game = crate::game::Game::new(1);
+		for index in 0..30 {
+			game.run_tick(|_| tick(index).frame_of(1).unwrap().intents.clone());
+		}
+		let encode = |snapshot: &Snapshot| {
+			let mut writer = ByteWriter::new();
+			snapshot.to_bytes(&mut writer).unwrap();
+			writer.as_slice().to_vec()
+		};
+		let exact = game.snapshot();
+		assert!(!exact.players.is_empty());
+		let quantized = encode(&game.snapshot().quantized());
+		assert_eq!(encode(&exact).len(), quantized.len() + 4 * exact.players.len());
+
+		let decoded = Snapshot::from_bytes(&mut ByteReader::new(&quantized)).unwrap();
+		assert!(decoded.quantized);
+		for (exact, decoded) in exact.players.iter().zip(&decoded.players) {
+			let (exact, decoded) = (crate::game::PlayerStateSnapshot::from(&exact.player), crate::game::PlayerStateSnapshot::from(&decoded.player));
+			assert!((scalar_to_f32(exact.x) - scalar_to_f32(decoded.x)).abs() < 1.0);
+			assert!((scalar_to_f32(exact.y) - scalar_to_f32(decoded.y)).abs() < 1.0);
+			assert_eq!((exact.horizontal_velocity, exact.vertical_velocity, exact.grounded), (decoded.horizontal_velocity, decoded.vertical_velocity, decoded.grounded));
+		}
+	}
+}
\ No newline at end of file
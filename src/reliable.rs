@@ -0,0 +1,248 @@
+//! Getting the packets which can't be made up for to the other end exactly
+//! once, and in order. Ticks get by without; losing one only means waiting
+//! for the next, or for a correction. Chat, players coming and going, and
+//! their names don't come again, so the packets carrying them, see
+//! `Packet::is_reliable_tag`, take a detour through a `ReliableEndpoint` on
+//! either end.
+//!
+//! A reliable packet travels in an envelope: `RELIABLE_MAGIC`, the protocol
+//! version, `KIND_MESSAGE` and the packet's sequence number as a varint,
+//! followed by the packet itself, header included. The other end answers
+//! every envelope with one of `KIND_ACK`, holding just the sequence number.
+//! Envelopes which go unanswered get sent again, less and less often, until
+//! they are. Everything else goes out as it is.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::log::LogLevel;
+use crate::net::{ByteReader, ByteWriter, DecodeError};
+use crate::protocol::{Packet, PlayerIntent, PROTOCOL_MAGIC, PROTOCOL_VERSION};
+
+/// Envelopes start with these, in place of a packet's `PROTOCOL_MAGIC` or
+/// a batch's `BATCH_MAGIC`.
+pub const RELIABLE_MAGIC: [u8; 2] = *b"CR";
+/// The first protocol version reliable packets were sent in.
+pub const RELIABLE_VERSION: u8 = 24;
+pub const KIND_MESSAGE: u8 = 0;
+pub const KIND_ACK: u8 = 1;
+
+/// How long a reliable packet goes unanswered before it's sent again, in
+/// seconds, by default. Doubles with every time it's sent again.
+pub const DEFAULT_RETRANSMIT_TIMEOUT: f64 = 0.25;
+/// The longest the wait between two sends of a packet ever gets to be, in
+/// seconds, by default.
+pub const DEFAULT_MAX_RETRANSMIT_TIMEOUT: f64 = 2.0;
+/// Most reliable packets in flight at once, by default.
+pub const DEFAULT_WINDOW: usize = 64;
+
+/// How persistent an endpoint is about getting its packets through.
+#[derive(Clone, Copy, Debug)]
+pub struct ReliableConfig {
+	/// How long a packet goes unanswered before it's sent again, the first
+	/// time, in seconds.
+	pub retransmit_timeout: f64,
+	/// Cap on the retransmit timeout, however many times a packet has been
+	/// sent already.
+	pub max_retransmit_timeout: f64,
+	/// Most packets which may be waiting to be answered. Those sent past
+	/// that wait their turn, and make the endpoint congested meanwhile;
+	/// see `ReliableEndpoint::is_congested`. The same number caps how far
+	/// ahead of the next packet due the other end's packets are held on to.
+	pub window: usize,
+}
+
+impl Default for ReliableConfig {
+	fn default() -> Self {
+		Self {
+			retransmit_timeout: DEFAULT_RETRANSMIT_TIMEOUT,
+			max_retransmit_timeout: DEFAULT_MAX_RETRANSMIT_TIMEOUT,
+			window: DEFAULT_WINDOW,
+		}
+	}
+}
+
+/// A packet waiting to be answered.
+struct InFlight {
+	/// The envelope, as it's sent every time.
+	envelope: Vec<u8>,
+	/// When it's sent again, unless answered by then.
+	due: f64,
+	/// How long it waited the last time.
+	timeout: f64,
+}
+
+/// One end of the reliable exchange of packets with the other; what gets
+/// sent through it and what's received through it. Doesn't tell the time
+/// on its own, but gets told.
+pub struct ReliableEndpoint {
+	config: ReliableConfig,
+	next_sequence: u64,
+	in_flight: BTreeMap<u64, InFlight>,
+	/// Packets sent while the window was full, in order.
+	waiting: VecDeque<Vec<u8>>,
+	/// The sequence number of the other end's packet due next.
+	next_expected: u64,
+	/// Packets received ahead of the one due next, by sequence number.
+	early: BTreeMap<u64, Vec<u8>>,
+	retransmissions: u64,
+	/// Every envelope gets written into this first.
+	scratch: ByteWriter,
+}
+
+impl ReliableEndpoint {
+	pub fn new(config: ReliableConfig) -> Self {
+		Self {
+			config,
+			next_sequence: 0,
+			in_flight: BTreeMap::new(),
+			waiting: VecDeque::new(),
+			next_expected: 0,
+			early: BTreeMap::new(),
+			retransmissions: 0,
+			scratch: ByteWriter::new()
+		}
+	}
+
+	/// Hands a packet to `send`; in an envelope if it's a reliable one, in
+	/// which case it may also be held back until the window has room.
+	pub fn send(&mut self, bytes: &[u8], now: f64, mut send: impl FnMut(&[u8])) {
+		if !is_reliable(bytes) {
+			return send(bytes);
+		}
+		if self.in_flight.len() >= self.config.window.max(1) || !self.waiting.is_empty() {
+			self.waiting.push_back(bytes.to_vec());
+			return;
+		}
+		self.transmit(bytes, now, &mut send);
+	}
+
+	fn transmit(&mut self, bytes: &[u8], now: f64, send: &mut impl FnMut(&[u8])) {
+		let sequence = self.next_sequence;
+		self.next_sequence += 1;
+		self.scratch.clear();
+		write_header(&mut self.scratch, KIND_MESSAGE, sequence);
+		self.scratch.write_bytes(bytes);
+		send(self.scratch.as_slice());
+
+		let timeout = self.config.retransmit_timeout;
+		let envelope = self.scratch.as_slice().to_vec();
+		self.in_flight.insert(sequence, InFlight { envelope, due: now + timeout, timeout });
+	}
+
+	/// Sends again whatever went unanswered for too long, and whatever's
+	/// been waiting for room in the window, if there is some now. Meant to
+	/// be called every frame or so.
+	pub fn update(&mut self, now: f64, mut send: impl FnMut(&[u8])) {
+		let max_timeout = self.config.max_retransmit_timeout;
+		for (sequence, packet) in self.in_flight.iter_mut().filter(|(_, x)| x.due <= now) {
+			packet.timeout = (packet.timeout * 2.0).min(max_timeout);
+			packet.due = now + packet.timeout;
+			self.retransmissions += 1;
+			crate::log!(LogLevel::Trace, "reliable_retransmit sequence={} timeout={:.2}", sequence, packet.timeout);
+			send(&packet.envelope);
+		}
+		while self.in_flight.len() < self.config.window.max(1) {
+			let Some(bytes) = self.waiting.pop_front() else { break };
+			self.transmit(&bytes, now, &mut send);
+		}
+	}
+
+	/// Takes in a message from the other end. Packets not in an envelope are
+	/// handed straight to `deliver`; those in one are answered through
+	/// `send`, and handed to `deliver` once every packet before them has
+	/// been, and only the once.
+	pub fn receive(&mut self, bytes: &[u8], mut send: impl FnMut(&[u8]), mut deliver: impl FnMut(&[u8])) {
+		if !is_envelope(bytes) {
+			return deliver(bytes);
+		}
+		let (kind, sequence, packet) = match read_envelope(bytes) {
+			Ok(envelope) => envelope,
+			Err(error) => {
+				crate::log!(LogLevel::Warn, "reliable_rejected bytes={} error=\"{}\"", bytes.len(), error);
+				return;
+			},
+		};
+		if kind == KIND_ACK {
+			self.in_flight.remove(&sequence);
+			return;
+		}
+
+		// Too far ahead to be held on to; it'll come again.
+		if sequence >= self.next_expected + self.config.window.max(1) as u64 {
+			return;
+		}
+		// Answered even if it's been seen already, as the answer to the
+		// first time might have been lost.
+		self.scratch.clear();
+		write_header(&mut self.scratch, KIND_ACK, sequence);
+		send(self.scratch.as_slice());
+		if sequence < self.next_expected {
+			return;
+		}
+		self.early.entry(sequence).or_insert_with(|| packet.to_vec());
+		while let Some(packet) = self.early.remove(&self.next_expected) {
+			self.next_expected += 1;
+			deliver(&packet);
+		}
+	}
+
+	/// Whether packets are being sent faster than the other end answers
+	/// them, so that some are waiting for room in the window. Whoever's
+	/// sending should hold off on more of them for a while.
+	pub fn is_congested(&self) -> bool {
+		!self.waiting.is_empty()
+	}
+
+	/// How many packets have been sent, but not answered yet.
+	pub fn in_flight(&self) -> usize {
+		self.in_flight.len()
+	}
+
+	/// How many packets are waiting for room in the window.
+	pub fn waiting(&self) -> usize {
+		self.waiting.len()
+	}
+
+	/// How many times packets have been sent again, over every packet.
+	pub fn retransmissions(&self) -> u64 {
+		self.retransmissions
+	}
+}
+
+/// Whether `bytes` are a packet which has to be sent reliably.
+pub fn is_reliable(bytes: &[u8]) -> bool {
+	bytes.starts_with(&PROTOCOL_MAGIC)
+		&& bytes.get(PROTOCOL_MAGIC.len() + 2).is_some_and(|x| Packet::<PlayerIntent>::is_reliable_tag(*x))
+}
+
+/// Whether `bytes` are an envelope, rather than a packet or a batch.
+pub fn is_envelope(bytes: &[u8]) -> bool {
+	bytes.starts_with(&RELIABLE_MAGIC)
+}
+
+fn write_header(writer: &mut ByteWriter, kind: u8, sequence: u64) {
+	writer.write_bytes(&RELIABLE_MAGIC);
+	writer.write_u8(PROTOCOL_VERSION);
+	writer.write_u8(kind);
+	writer.write_varint(sequence);
+}
+
+/// The kind of an envelope, its sequence number, and the packet in it.
+/// Acks come with no packet.
+fn read_envelope(bytes: &[u8]) -> Result<(u8, u64, &[u8]), DecodeError> {
+	let mut reader = ByteReader::new(&bytes[RELIABLE_MAGIC.len()..]);
+	let version = reader.read_u8()?;
+	if version != PROTOCOL_VERSION {
+		return Err(DecodeError::UnsupportedVersion { got: version, expected: PROTOCOL_VERSION });
+	}
+	let kind = reader.read_u8()?;
+	let sequence = reader.read_varint()?;
+	match kind {
+		KIND_MESSAGE => Ok((kind, sequence, reader.read_exact(reader.remaining())?)),
+		KIND_ACK => match reader.remaining() {
+			0 => Ok((kind, sequence, &[])),
+			count => Err(DecodeError::TrailingBytes { count }),
+		},
+		tag => Err(DecodeError::InvalidTag { ty: "reliable envelope", tag }),
+	}
+}
\ No newline at end of file
@@ -0,0 +1,435 @@
+//! Recording a session, and playing it back later. A replay holds every
+//! tick the server confirmed, along with the world they started out from;
+//! as the simulation is deterministic, that's all it takes to get every
+//! frame of the session back.
+//!
+//! A replay starts with `REPLAY_MAGIC` and a `ReplayHeader`, followed by a
+//! stream of entries, each of them a tag byte and the entry itself. Ticks
+//! are delta encoded against the one before them, same as on the wire.
+
+use std::fmt;
+use command_networking_derive::NetType;
+
+use crate::game::{Coins, Game, Platform, SimConfig};
+use crate::hash::{Hasher, TickHasher};
+use crate::log::LogLevel;
+use crate::net::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+use crate::protocol::*;
+
+/// Every replay starts with these.
+pub const REPLAY_MAGIC: [u8; 4] = *b"CNRP";
+
+/// What a replay has to agree on with whoever plays it back.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct ReplayHeader {
+	/// The `PROTOCOL_VERSION` the ticks were recorded with. Replays don't
+	/// carry over between versions, as the simulation may have changed too.
+	pub protocol_version: u8,
+	/// The `TickHasher::ID` the ticks were hashed with.
+	pub hasher: u8,
+	/// The tick rate the session ran at, which playback sticks to.
+	pub tick_rate: u8,
+	/// The seed the session's random numbers were drawn from.
+	pub seed: u64,
+	/// The world the session got simulated in.
+	pub sim_config: SimConfig,
+	/// Who recorded the replay.
+	pub client_id: ClientId
+}
+
+/// A client's membership, as recorded in a replay; see `Game::members`.
+#[derive(Clone, PartialEq, NetType)]
+pub(crate) struct MemberEntry {
+	pub(crate) client_id: ClientId,
+	pub(crate) joined: u64,
+	pub(crate) left: Option<u64>
+}
+
+/// A single entry in the stream following the header.
+enum Entry {
+	/// The whole world, right before tick `next_index`. Written once the
+	/// recording starts, and whenever a snapshot replaces the world.
+	World {
+		next_index: u64,
+		players: Vec<SnapshotEntry>,
+		platforms: Vec<Platform>,
+		coins: Coins,
+		members: Vec<MemberEntry>
+	},
+	/// Who takes part in the game from here on; written whenever that changed.
+	Members(Vec<MemberEntry>),
+	Tick(EncodedTick<PlayerIntent>),
+}
+
+impl Entry {
+	const TAG_WORLD: u8 = 0;
+	const TAG_MEMBERS: u8 = 1;
+	const TAG_TICK: u8 = 2;
+}
+
+impl NetType for Entry {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		match self {
+			Entry::World { next_index, players, platforms, coins, members } => {
+				writer.write_u8(Self::TAG_WORLD);
+				writer.write_varint(*next_index);
+				players.to_bytes(writer)?;
+				platforms.to_bytes(writer)?;
+				coins.to_bytes(writer)?;
+				members.to_bytes(writer)
+			},
+			Entry::Members(members) => {
+				writer.write_u8(Self::TAG_MEMBERS);
+				members.to_bytes(writer)
+			},
+			Entry::Tick(tick) => {
+				writer.write_u8(Self::TAG_TICK);
+				tick.to_bytes(writer)
+			},
+		}
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		1 + match self {
+			Entry::World { next_index, players, platforms, coins, members } => {
+				ByteWriter::varint_len(*next_index) + players.byte_len_hint() + platforms.byte_len_hint() + coins.byte_len_hint() + members.byte_len_hint()
+			},
+			Entry::Members(members) => members.byte_len_hint(),
+			Entry::Tick(tick) => tick.byte_len_hint(),
+		}
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		match reader.read_u8()? {
+			Self::TAG_WORLD => Ok(Entry::World {
+				next_index: reader.read_varint()?,
+				players: reader.read_vec(MAX_FRAMES_PER_TICK, "players")?,
+				platforms: reader.read_vec(MAX_PLATFORMS, "platforms")?,
+				coins: Coins::from_bytes(reader)?,
+				members: reader.read_vec(MAX_FRAMES_PER_TICK, "members")?
+			}),
+			Self::TAG_MEMBERS => Ok(Entry::Members(reader.read_vec(MAX_FRAMES_PER_TICK, "members")?)),
+			Self::TAG_TICK => Ok(Entry::Tick(EncodedTick::from_bytes(reader)?)),
+			tag => Err(DecodeError::InvalidTag { ty: "Entry", tag })
+		}
+	}
+}
+
+/// Collects a session's confirmed ticks as they come in; see
+/// `Game::start_recording`.
+pub(crate) struct Recorder {
+	entries: ByteWriter,
+	encoder: TickEncoder<PlayerIntent>,
+	/// Index of the next tick to be recorded.
+	pub(crate) next_index: u64,
+	/// The membership table as of the last `Members` or `World` entry.
+	members: Vec<MemberEntry>,
+	/// How many bytes of entries get recorded; see
+	/// `MemoryBudget::replay_bytes`.
+	capacity: usize,
+	/// Set once an entry didn't fit, after which nothing more gets recorded.
+	full: bool
+}
+
+impl Recorder {
+	pub(crate) fn new(capacity: usize) -> Self {
+		Self {
+			entries: ByteWriter::new(),
+			encoder: TickEncoder::default(),
+			next_index: 0,
+			members: vec![],
+			capacity,
+			full: false
+		}
+	}
+
+	/// How many bytes of entries there are so far.
+	pub(crate) fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub(crate) fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// Starts over from a whole new world, right before tick `next_index`.
+	pub(crate) fn world(&mut self, next_index: u64, players: Vec<SnapshotEntry>, platforms: Vec<Platform>, coins: Coins, members: Vec<MemberEntry>) {
+		self.record(Entry::World { next_index, players, platforms, coins, members: members.clone() });
+		self.members = members;
+		self.next_index = next_index;
+	}
+
+	/// Records who takes part in the ticks recorded from here on, if that
+	/// changed since the last time.
+	pub(crate) fn members(&mut self, members: Vec<MemberEntry>) {
+		if members != self.members {
+			self.record(Entry::Members(members.clone()));
+			self.members = members;
+		}
+	}
+
+	pub(crate) fn tick(&mut self, tick: &Tick<PlayerIntent>) {
+		let entry = Entry::Tick(self.encoder.encode(tick));
+		match self.record(entry) {
+			// Playback decodes every tick that made it in.
+			true => self.encoder.acknowledge(tick.index()),
+			// The next tick can't be a delta against one that isn't there.
+			false => self.encoder = TickEncoder::default(),
+		}
+		self.next_index = tick.index() + 1;
+	}
+
+	/// Appends an entry, or leaves it out altogether if it can't be written,
+	/// returning whether it made it in. Once one takes the recording over
+	/// its capacity, the replay ends right before it; playing it back gets
+	/// as far as the recording went.
+	fn record(&mut self, entry: Entry) -> bool {
+		if self.full {
+			return false;
+		}
+		let len = self.entries.len();
+		match entry.to_bytes(&mut self.entries) {
+			Ok(()) if self.entries.len() > self.capacity => {
+				self.entries.truncate(len);
+				self.full = true;
+				crate::log!(LogLevel::Warn, "replay_full capacity={} next_index={}", self.capacity, self.next_index);
+				false
+			},
+			Ok(()) => true,
+			Err(error) => {
+				self.entries.truncate(len);
+				crate::log!(LogLevel::Warn, "replay_entry_dropped next_index={} error=\"{}\"", self.next_index, error);
+				false
+			},
+		}
+	}
+
+	/// The whole replay, from the header on.
+	pub(crate) fn export(&self, header: &ReplayHeader) -> Vec<u8> {
+		let mut writer = ByteWriter::with_capacity(REPLAY_MAGIC.len() + header.byte_len_hint() + self.entries.len());
+		writer.write_bytes(&REPLAY_MAGIC);
+		// A header is a handful of numbers, with no limits to be over.
+		header.to_bytes(&mut writer).expect("a replay header couldn't be written");
+		writer.write_bytes(self.entries.as_slice());
+		writer.as_slice().to_vec()
+	}
+}
+
+/// Why a replay couldn't be played back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+	/// The bytes don't start with `REPLAY_MAGIC`; it's not a replay at all.
+	NotAReplay,
+	/// The replay was recorded with a different version of the protocol.
+	UnsupportedVersion { got: u8, expected: u8 },
+	/// The replay's ticks were hashed with a different algorithm than ours.
+	UnsupportedHasher { got: u8, expected: u8 },
+	/// The replay claims a tick rate nothing can run at.
+	InvalidTickRate(u8),
+	/// The replay has no world to start playing from.
+	MissingWorld,
+	/// An entry couldn't be read back.
+	Malformed(DecodeError),
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ReplayError::NotAReplay => write!(f, "not a replay"),
+			ReplayError::UnsupportedVersion { got, expected } => {
+				write!(f, "replay was recorded with protocol version {} (expected {})", got, expected)
+			},
+			ReplayError::UnsupportedHasher { got, expected } => {
+				write!(f, "replay was hashed with algorithm {} (expected {})", got, expected)
+			},
+			ReplayError::InvalidTickRate(hz) => write!(f, "replay runs at an invalid tick rate of {} Hz", hz),
+			ReplayError::MissingWorld => write!(f, "replay doesn't start with a world"),
+			ReplayError::Malformed(error) => write!(f, "malformed replay: {}", error),
+		}
+	}
+}
+
+impl From<DecodeError> for ReplayError {
+	fn from(error: DecodeError) -> Self {
+		ReplayError::Malformed(error)
+	}
+}
+
+/// A replay's entry, with its tick decoded.
+enum Event {
+	World {
+		next_index: u64,
+		players: Vec<SnapshotEntry>,
+		platforms: Vec<Platform>,
+		coins: Coins,
+		members: Vec<MemberEntry>
+	},
+	Members(Vec<MemberEntry>),
+	Tick(Tick<PlayerIntent>),
+}
+
+/// A parsed replay, ready to be played back.
+pub struct Replay {
+	header: ReplayHeader,
+	events: Vec<Event>,
+	/// Index of the first and the last recorded tick, if there are any.
+	ticks: Option<(u64, u64)>
+}
+
+impl Replay {
+	/// Reads a replay as written by `Game::export_replay`. Fails on replays
+	/// from a different protocol version, rather than playing back a
+	/// session that never happened.
+	pub fn parse(bytes: &[u8]) -> Result<Self, ReplayError> {
+		let mut reader = ByteReader::new(bytes);
+		if reader.read_array::<4>() != Ok(REPLAY_MAGIC) {
+			return Err(ReplayError::NotAReplay);
+		}
+		let header = ReplayHeader::from_bytes(&mut reader)?;
+		if header.protocol_version != PROTOCOL_VERSION {
+			return Err(ReplayError::UnsupportedVersion {
+				got: header.protocol_version,
+				expected: PROTOCOL_VERSION
+			});
+		}
+		if header.hasher != Hasher::ID {
+			return Err(ReplayError::UnsupportedHasher {
+				got: header.hasher,
+				expected: Hasher::ID
+			});
+		}
+		if header.tick_rate == 0 {
+			return Err(ReplayError::InvalidTickRate(header.tick_rate));
+		}
+
+		let mut decoder = TickDecoder::default();
+		let mut events = vec![];
+		let mut ticks = None;
+		while reader.remaining() > 0 {
+			let event = match Entry::from_bytes(&mut reader)? {
+				Entry::World { next_index, players, platforms, coins, members } => Event::World { next_index, players, platforms, coins, members },
+				Entry::Members(members) => Event::Members(members),
+				Entry::Tick(encoded) => {
+					let tick = decoder.decode(encoded)?;
+					let (first, _) = ticks.unwrap_or((tick.index(), tick.index()));
+					ticks = Some((first, tick.index()));
+					Event::Tick(tick)
+				},
+			};
+			events.push(event);
+		}
+
+		if !matches!(events.first(), Some(Event::World { .. })) {
+			return Err(ReplayError::MissingWorld);
+		}
+		Ok(Self { header, events, ticks })
+	}
+
+	pub fn header(&self) -> &ReplayHeader {
+		&self.header
+	}
+
+	/// Index of the first and the last recorded tick, unless the replay
+	/// ended before any got confirmed.
+	pub fn tick_range(&self) -> Option<(u64, u64)> {
+		self.ticks
+	}
+}
+
+/// Plays a replay back, one tick at a time, in a game of its own.
+pub struct Playback {
+	replay: Replay,
+	game: Game,
+	/// Index of the next event to play.
+	position: usize,
+	/// The last tick played, if any.
+	tick_index: Option<u64>
+}
+
+impl Playback {
+	pub fn new(replay: Replay) -> Self {
+		let game = Self::fresh_game(&replay);
+		let mut playback = Self {
+			replay,
+			game,
+			position: 0,
+			tick_index: None
+		};
+		playback.restart();
+		playback
+	}
+
+	fn fresh_game(replay: &Replay) -> Game {
+		let mut game = Game::spectator(replay.header.client_id);
+		game.set_tick_rate(replay.header.tick_rate);
+		game.set_sim_config(replay.header.sim_config);
+		game.set_seed(replay.header.seed);
+		game
+	}
+
+	pub fn replay(&self) -> &Replay {
+		&self.replay
+	}
+
+	/// The game the replay is played back in, at the tick last played.
+	pub fn game(&self) -> &Game {
+		&self.game
+	}
+
+	pub fn game_mut(&mut self) -> &mut Game {
+		&mut self.game
+	}
+
+	/// The last tick played, or `None` right at the start.
+	pub fn tick_index(&self) -> Option<u64> {
+		self.tick_index
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.position >= self.replay.events.len()
+	}
+
+	/// Goes back to the very beginning, before any tick got played.
+	pub fn restart(&mut self) {
+		self.game = Self::fresh_game(&self.replay);
+		self.position = 0;
+		self.tick_index = None;
+		self.apply_until_tick();
+	}
+
+	/// Plays the next tick. Returns false at the end of the replay.
+	pub fn step(&mut self) -> bool {
+		self.apply_until_tick();
+		let Some(Event::Tick(tick)) = self.replay.events.get(self.position) else {
+			return false;
+		};
+		self.tick_index = Some(tick.index());
+		self.game.follow(tick.clone());
+		self.position += 1;
+		self.apply_until_tick();
+		true
+	}
+
+	/// Plays, or replays, everything up to and including `tick_index`.
+	/// Seeking backwards starts over from the beginning.
+	pub fn seek(&mut self, tick_index: u64) {
+		if self.tick_index.is_some_and(|x| x > tick_index) {
+			self.restart();
+		}
+		while self.tick_index.is_none_or(|x| x < tick_index) && self.step() {}
+	}
+
+	/// Applies everything up to the next tick, or the end of the replay.
+	fn apply_until_tick(&mut self) {
+		while let Some(event) = self.replay.events.get(self.position) {
+			match event {
+				Event::World { next_index, players, platforms, coins, members } => {
+					self.game.restore_world(*next_index, players, platforms, coins, members);
+				},
+				Event::Members(members) => self.game.restore_members(members),
+				Event::Tick(_) => return,
+			}
+			self.position += 1;
+		}
+	}
+}
\ No newline at end of file
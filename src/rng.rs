@@ -0,0 +1,86 @@
+//! Randomness the simulation can use without desyncing anyone. Every draw
+//! comes from a small PCG generator whose state is derived from the game's
+//! seed and the index of the tick being simulated, and nothing else; so
+//! simulating a tick again after a rollback draws the same numbers as the
+//! first time, on every client.
+//!
+//! The seed comes from the server, along with the tick rate, and travels in
+//! snapshots and replays too. Nothing here ever touches the OS for entropy.
+
+/// PCG32 (XSH RR), with a 64 bit state and 32 bit output.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rng {
+	state: u64,
+	/// Picks one of 2^63 independent sequences; always odd.
+	increment: u64,
+}
+
+impl Rng {
+	const MULTIPLIER: u64 = 6364136223846793005;
+
+	/// The generator for simulating tick `tick_index` of a game seeded with
+	/// `seed`. Neighbouring ticks and seeds get unrelated sequences.
+	pub fn for_tick(seed: u64, tick_index: u64) -> Self {
+		Self::new(splitmix64(seed ^ splitmix64(tick_index)), 0)
+	}
+
+	/// A generator of its own for `stream`, alongside this one. Draws from
+	/// either don't affect the other, so whatever only concerns a single
+	/// player, say, doesn't depend on who else drew numbers before it.
+	pub fn split(&self, stream: u64) -> Self {
+		Self::new(self.state, stream.wrapping_add(1))
+	}
+
+	fn new(state: u64, stream: u64) -> Self {
+		let mut rng = Self {
+			state: 0,
+			increment: (stream << 1) | 1
+		};
+		rng.next_u32();
+		rng.state = rng.state.wrapping_add(state);
+		rng.next_u32();
+		rng
+	}
+
+	pub fn next_u32(&mut self) -> u32 {
+		let state = self.state;
+		self.state = state.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+		let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+		xorshifted.rotate_right((state >> 59) as u32)
+	}
+
+	/// A uniformly distributed value in `0..bound`, or 0 if `bound` is.
+	pub fn below(&mut self, bound: u32) -> u32 {
+		if bound == 0 {
+			return 0;
+		}
+		// Rejects the few values that would make lower results more likely.
+		let threshold = bound.wrapping_neg() % bound;
+		loop {
+			let value = self.next_u32();
+			if value >= threshold {
+				return value % bound;
+			}
+		}
+	}
+
+	/// A uniformly distributed value in `min..max`, or `min` if that's empty.
+	pub fn range(&mut self, min: i32, max: i32) -> i32 {
+		let span = (max as i64 - min as i64).max(0) as u32;
+		(min as i64 + self.below(span) as i64) as i32
+	}
+
+	/// True `percent` times out of a hundred.
+	pub fn chance(&mut self, percent: u8) -> bool {
+		self.below(100) < percent as u32
+	}
+}
+
+/// Scrambles a number into one that looks nothing like it, so that seeds
+/// and tick indices which only differ by a bit still end up far apart.
+fn splitmix64(value: u64) -> u64 {
+	let mut x = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+	x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	x ^ (x >> 31)
+}
\ No newline at end of file
@@ -0,0 +1,169 @@
+//! Saving a client's whole state, so that the host can keep it around over
+//! a page reload and pick the session back up where it left off.
+//!
+//! A saved state starts with `STATE_MAGIC` and a `StateHeader`, followed by
+//! everything `Game::export_state` keeps: the world, who's in the game, how
+//! far the server got, the ticks still in the history, along with the world
+//! as it was before each of them, and the server's ticks yet to be played. The simulation's random numbers
+//! only depend on the seed and the tick index, so there's no generator
+//! state to save.
+
+use std::fmt;
+use command_networking_derive::NetType;
+
+use crate::game::{Coins, Platform, SimConfig};
+use crate::hash::{Hash, Hasher, TickHasher};
+use crate::net::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+use crate::protocol::*;
+use crate::replay::MemberEntry;
+
+/// Every saved state starts with these.
+pub const STATE_MAGIC: [u8; 4] = *b"CNST";
+
+/// What a saved state has to agree on with the build restoring it.
+#[derive(Clone, Copy, PartialEq, Debug, NetType)]
+pub struct StateHeader {
+	/// The `PROTOCOL_VERSION` the state was saved with. States don't carry
+	/// over between versions, any more than replays do.
+	pub protocol_version: u8,
+	/// The `TickHasher::ID` the saved hashes were made with.
+	pub hasher: u8,
+	pub tick_rate: u8,
+	pub seed: u64,
+	/// Who we were playing as.
+	pub client_id: ClientId
+}
+
+/// A tick in the history, along with the world it got simulated on.
+#[derive(NetType)]
+pub(crate) struct SavedTick {
+	pub(crate) tick: Tick<PlayerIntent>,
+	/// The world, as it was right before the tick got simulated.
+	pub(crate) players: Vec<SnapshotEntry>,
+	pub(crate) coins: Coins,
+	pub(crate) authoritative: bool,
+	pub(crate) suspect: bool,
+	pub(crate) state_hash: Hash
+}
+
+/// Everything following the header.
+#[derive(NetType)]
+pub(crate) struct SavedGame {
+	pub(crate) local_players: Vec<ClientId>,
+	pub(crate) spectating: bool,
+	pub(crate) sim_config: SimConfig,
+	pub(crate) platforms: Vec<Platform>,
+	pub(crate) coins: Coins,
+	/// The world as it is, right after the last tick in the history.
+	pub(crate) players: Vec<SnapshotEntry>,
+	pub(crate) members: Vec<MemberEntry>,
+	pub(crate) infos: Vec<PlayerInfo>,
+	pub(crate) accepted_head: u64,
+	pub(crate) received_head: u64,
+	pub(crate) answered_head: u64,
+	pub(crate) next_authoritative: u64,
+	/// The index of the next tick, for when the history is empty.
+	pub(crate) next_index: u64,
+	pub(crate) ticks: Vec<SavedTick>,
+	/// Authoritative ticks which arrived, but hadn't been played back yet.
+	pub(crate) unplayed: Vec<Tick<PlayerIntent>>
+}
+
+/// A saved state, read back and checked, ready for `Game::restore_state`.
+pub struct SavedState {
+	header: StateHeader,
+	pub(crate) game: SavedGame
+}
+
+/// Why a saved state couldn't be restored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateError {
+	/// The bytes don't start with `STATE_MAGIC`; it's not a saved state.
+	NotAState,
+	/// The state was saved with a different version of the protocol.
+	UnsupportedVersion { got: u8, expected: u8 },
+	/// The state's hashes were made with a different algorithm than ours.
+	UnsupportedHasher { got: u8, expected: u8 },
+	/// The state claims a tick rate nothing can run at.
+	InvalidTickRate(u8),
+	/// The state couldn't be read back.
+	Malformed(DecodeError),
+}
+
+impl fmt::Display for StateError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			StateError::NotAState => write!(f, "not a saved state"),
+			StateError::UnsupportedVersion { got, expected } => {
+				write!(f, "state was saved with protocol version {} (expected {})", got, expected)
+			},
+			StateError::UnsupportedHasher { got, expected } => {
+				write!(f, "state was hashed with algorithm {} (expected {})", got, expected)
+			},
+			StateError::InvalidTickRate(hz) => write!(f, "state runs at an invalid tick rate of {} Hz", hz),
+			StateError::Malformed(error) => write!(f, "malformed state: {}", error),
+		}
+	}
+}
+
+impl From<DecodeError> for StateError {
+	fn from(error: DecodeError) -> Self {
+		StateError::Malformed(error)
+	}
+}
+
+impl SavedState {
+	pub(crate) fn new(header: StateHeader, game: SavedGame) -> Self {
+		Self { header, game }
+	}
+
+	/// Reads a state as written by `Game::export_state`. Fails on states
+	/// from a different protocol version, or with anything left over.
+	pub fn parse(bytes: &[u8]) -> Result<Self, StateError> {
+		let mut reader = ByteReader::new(bytes);
+		if reader.read_array::<4>() != Ok(STATE_MAGIC) {
+			return Err(StateError::NotAState);
+		}
+		let header = StateHeader::from_bytes(&mut reader)?;
+		if header.protocol_version != PROTOCOL_VERSION {
+			return Err(StateError::UnsupportedVersion {
+				got: header.protocol_version,
+				expected: PROTOCOL_VERSION
+			});
+		}
+		if header.hasher != Hasher::ID {
+			return Err(StateError::UnsupportedHasher {
+				got: header.hasher,
+				expected: Hasher::ID
+			});
+		}
+		if header.tick_rate == 0 {
+			return Err(StateError::InvalidTickRate(header.tick_rate));
+		}
+
+		let game = SavedGame::from_bytes(&mut reader)?;
+		if reader.remaining() > 0 {
+			return Err(StateError::Malformed(DecodeError::TrailingBytes { count: reader.remaining() }));
+		}
+		Ok(Self { header, game })
+	}
+
+	pub fn header(&self) -> &StateHeader {
+		&self.header
+	}
+
+	/// The whole state, from the magic on.
+	pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+		let mut writer = ByteWriter::with_capacity(STATE_MAGIC.len() + self.header.byte_len_hint() + self.game.byte_len_hint());
+		self.write(&mut writer)?;
+		Ok(writer.as_slice().to_vec())
+	}
+
+	/// Writes the whole state, from the magic on, behind whatever `writer`
+	/// holds already.
+	pub fn write(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		writer.write_bytes(&STATE_MAGIC);
+		self.header.to_bytes(writer)?;
+		self.game.to_bytes(writer)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,164 @@
+//! The number type the simulation runs on.
+
+#[cfg(feature = "fixed-point")]
+use crate::net::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+
+/// The number type the simulation runs on; plain `f32` by default.
+/// With the `fixed-point` feature it's a Q16.16 fixed-point number instead,
+/// which produces bit-identical results on every platform. Either way,
+/// values are only turned into `f32`s when drawn.
+#[cfg(not(feature = "fixed-point"))]
+pub type Scalar = f32;
+#[cfg(feature = "fixed-point")]
+pub type Scalar = Fixed;
+
+/// Turns a constant into a `Scalar`.
+#[cfg(not(feature = "fixed-point"))]
+pub(crate) const fn scalar(value: f32) -> Scalar {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+pub(crate) const fn scalar(value: f32) -> Scalar {
+	Fixed::from_f32(value)
+}
+
+#[cfg(not(feature = "fixed-point"))]
+pub(crate) fn scalar_to_f32(value: Scalar) -> f32 {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+pub(crate) fn scalar_to_f32(value: Scalar) -> f32 {
+	value.to_f32()
+}
+
+/// The bits a value gets hashed as. `-0.0` and `0.0` behave the same in
+/// the simulation, so both hash as the latter.
+#[cfg(not(feature = "fixed-point"))]
+pub(crate) fn scalar_bits(value: Scalar) -> u32 {
+	match value == 0.0 {
+		true => 0,
+		false => value.to_bits(),
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+pub(crate) fn scalar_bits(value: Scalar) -> u32 {
+	value.0 as u32
+}
+
+/// A signed Q16.16 fixed-point number. Integer arithmetic behaves the same
+/// everywhere, which floats across wasm and native builds don't quite do.
+#[cfg(feature = "fixed-point")]
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+// Serialized as the raw number, which a float couldn't always hold exactly.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct Fixed(i32);
+
+#[cfg(feature = "fixed-point")]
+impl Fixed {
+	pub(crate) const FRACTION_BITS: u32 = 16;
+	pub(crate) const ONE: i32 = 1 << Self::FRACTION_BITS;
+
+	pub const fn from_f32(value: f32) -> Self {
+		Self((value * Self::ONE as f32) as i32)
+	}
+
+	pub fn to_f32(self) -> f32 {
+		self.0 as f32 / Self::ONE as f32
+	}
+
+	pub fn abs(self) -> Self {
+		Self(self.0.wrapping_abs())
+	}
+}
+
+// Printed as the number it stands for, rather than its raw bits.
+#[cfg(feature = "fixed-point")]
+impl std::fmt::Debug for Fixed {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{:?}", self.to_f32())
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::Add for Fixed {
+	type Output = Self;
+
+	fn add(self, other: Self) -> Self {
+		Self(self.0.wrapping_add(other.0))
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::Sub for Fixed {
+	type Output = Self;
+
+	fn sub(self, other: Self) -> Self {
+		Self(self.0.wrapping_sub(other.0))
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::Mul for Fixed {
+	type Output = Self;
+
+	fn mul(self, other: Self) -> Self {
+		Self(((self.0 as i64 * other.0 as i64) >> Self::FRACTION_BITS) as i32)
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::Div for Fixed {
+	type Output = Self;
+
+	fn div(self, other: Self) -> Self {
+		Self((((self.0 as i64) << Self::FRACTION_BITS) / other.0 as i64) as i32)
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::Neg for Fixed {
+	type Output = Self;
+
+	fn neg(self) -> Self {
+		Self(self.0.wrapping_neg())
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::AddAssign for Fixed {
+	fn add_assign(&mut self, other: Self) {
+		*self = *self + other;
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::SubAssign for Fixed {
+	fn sub_assign(&mut self, other: Self) {
+		*self = *self - other;
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl std::ops::MulAssign for Fixed {
+	fn mul_assign(&mut self, other: Self) {
+		*self = *self * other;
+	}
+}
+
+#[cfg(feature = "fixed-point")]
+impl NetType for Fixed {
+	fn to_bytes(&self, writer: &mut ByteWriter) -> Result<(), EncodeError> {
+		self.0.to_bytes(writer)
+	}
+
+	fn byte_len_hint(&self) -> usize {
+		self.0.byte_len_hint()
+	}
+
+	fn from_bytes(reader: &mut ByteReader) -> Result<Self, DecodeError> {
+		Ok(Self(i32::from_bytes(reader)?))
+	}
+}
\ No newline at end of file
@@ -0,0 +1,1302 @@
+//! How packets get to and from the other end. The game itself only ever sees
+//! raw packet bytes, so it doesn't care which transport is in use.
+
+/// Moves packets between the game and whatever is on the other end.
+pub trait Transport {
+	/// Sends a single packet. There's no guarantee it ever arrives, nor that
+	/// it goes out before the next `flush`; only that it was taken, unless
+	/// this returns an error.
+	fn send(&mut self, bytes: &[u8]) -> Result<(), SendError>;
+
+	/// Sends whatever `send` held back, for transports which batch packets
+	/// up; see `batch`. Called once a frame, after the last packet's sent.
+	/// Whatever couldn't go out is held on to for the next flush.
+	fn flush(&mut self) -> Result<(), SendError> {
+		Ok(())
+	}
+
+	/// Hands every packet received since the last call to `receive`, in
+	/// arrival order. The bytes are only lent for the duration of the call,
+	/// so transports can receive into the same buffer over and over.
+	fn poll(&mut self, receive: &mut dyn FnMut(&[u8]));
+
+	/// Returns the changes in connection state noticed by the last `poll`.
+	/// Transports without a notion of being connected never report any.
+	fn poll_events(&mut self) -> Vec<TransportEvent> {
+		Vec::new()
+	}
+
+	/// Whether packets which have to arrive are being sent faster than
+	/// they get through, see `ReliableTransport`, so that more of them had
+	/// better wait. Transports without any such packets never are.
+	fn is_congested(&self) -> bool {
+		false
+	}
+
+	/// The network conditions being simulated on top of the transport, if
+	/// any, so they can be adjusted while the game is running.
+	fn simulated_conditions(&mut self) -> Option<&mut SimulatedConditions> {
+		None
+	}
+}
+
+/// Why a transport didn't take a packet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SendError {
+	/// There's no room for it right now, the way there isn't in a full
+	/// socket buffer, or in a host applying backpressure.
+	WouldBlock,
+	/// Sending failed outright, though it might not the next time.
+	Failed,
+	/// Too big to ever go out.
+	TooLarge,
+}
+
+impl SendError {
+	/// Whether the same packet stands a chance of going out later on.
+	pub fn is_retryable(self) -> bool {
+		self != SendError::TooLarge
+	}
+}
+
+impl std::fmt::Display for SendError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			SendError::WouldBlock => write!(f, "would block"),
+			SendError::Failed => write!(f, "failed"),
+			SendError::TooLarge => write!(f, "too large"),
+		}
+	}
+}
+
+/// A change in the state of the connection underneath a transport.
+#[cfg_attr(not(feature = "transport-websocket"), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TransportEvent {
+	Connected,
+	Disconnected,
+}
+
+#[cfg(all(feature = "transport-udp", feature = "transport-websocket"))]
+compile_error!("only one of the transport-udp and transport-websocket features can be enabled at a time");
+
+#[cfg(not(any(feature = "transport-udp", feature = "transport-websocket")))]
+pub use host::HostTransport;
+
+#[cfg(feature = "transport-udp")]
+pub use udp::{MAX_DATAGRAM_SIZE, UdpTransport};
+
+#[cfg(feature = "transport-websocket")]
+pub use websocket::{WebSocketTransport, socket_closed, socket_message, socket_opened};
+
+pub use loopback::LoopbackTransport;
+
+pub use simulated::SimulatedConditions;
+
+pub use simulated::SimulatedTransport;
+
+pub use reliable::ReliableTransport;
+
+#[cfg(not(any(feature = "transport-udp", feature = "transport-websocket")))]
+mod host {
+	use crate::batch::{Coalescer, BATCHING_VERSION};
+	use super::{SendError, Transport};
+
+	// Here we define the host FFI; because this demo is going to use a dummy
+	// network (embedded & simulated inside a JS environment), all of the
+	// communication is going to happen through mock functions.
+	extern "C" {
+		/// Hands a packet over to the host. The memory is only lent for the
+		/// duration of the call, and gets reused for the next packet, so the
+		/// host has to copy it out before returning. Only used with hosts
+		/// older than `BATCHING_VERSION`.
+		///
+		/// Returns zero once the host took the packet. Anything else means
+		/// it couldn't, for now, and the packet will be sent again later.
+		fn send_packet(
+			data_ptr: *const u8,
+			data_size: usize
+		) -> i32;
+
+		/// Hands a frame's worth of packets over to the host, either as a
+		/// batch or, if there's just the one, as a single packet; see
+		/// `batch::split_batch`. Lent and answered the same way as for
+		/// `send_packet`.
+		fn send_packets(
+			data_ptr: *const u8,
+			data_size: usize
+		) -> i32;
+	}
+
+	/// Most packets held back for the host at once, flushes it turned
+	/// down included. Past that, sends are turned down in turn.
+	const MAX_HELD_PACKETS: usize = 64;
+
+	fn status(status: i32) -> Result<(), SendError> {
+		match status {
+			0 => Ok(()),
+			_ => Err(SendError::WouldBlock),
+		}
+	}
+
+	/// Talks to the JS host through the FFI.
+	pub struct HostTransport {
+		/// Packets waiting for the end of the frame, unless the host is too
+		/// old to split batches.
+		coalescer: Option<Coalescer>,
+	}
+
+	impl HostTransport {
+		/// Batches packets up, as long as the host speaks `BATCHING_VERSION`
+		/// of the protocol or later.
+		pub fn new(host_version: u8) -> Self {
+			Self {
+				coalescer: (host_version >= BATCHING_VERSION).then(Coalescer::default)
+			}
+		}
+	}
+
+	impl Transport for HostTransport {
+		fn send(&mut self, bytes: &[u8]) -> Result<(), SendError> {
+			match &mut self.coalescer {
+				Some(coalescer) if coalescer.len() >= MAX_HELD_PACKETS => Err(SendError::WouldBlock),
+				Some(coalescer) => {
+					coalescer.push(bytes);
+					Ok(())
+				},
+				None => status(unsafe {
+					send_packet(
+						bytes.as_ptr(),
+						bytes.len()
+					)
+				}),
+			}
+		}
+
+		fn flush(&mut self) -> Result<(), SendError> {
+			let Some(coalescer) = &mut self.coalescer else { return Ok(()) };
+			coalescer.try_flush(|bytes| status(unsafe {
+				send_packets(
+					bytes.as_ptr(),
+					bytes.len()
+				)
+			}))?;
+			Ok(())
+		}
+
+		fn poll(&mut self, _receive: &mut dyn FnMut(&[u8])) {
+			// The host pushes packets in through `receive_packet` on its own.
+		}
+	}
+}
+
+#[cfg(feature = "transport-udp")]
+mod udp {
+	use std::io::{self, ErrorKind};
+	use std::net::{ToSocketAddrs, UdpSocket};
+	use crate::log::LogLevel;
+	use super::{SendError, Transport};
+
+	/// Largest packet sent or accepted, in bytes. Kept under the smallest MTU
+	/// IPv6 guarantees, minus headers, so datagrams never get fragmented on
+	/// the way.
+	///
+	/// Packets aren't fragmented by us either: a client's tick, past frames
+	/// and all, is a fraction of this, and the server's ticks fit the frames
+	/// of over a hundred busy players. Anything bigger means something went
+	/// wrong, so it's dropped rather than sent in pieces.
+	pub const MAX_DATAGRAM_SIZE: usize = 1200;
+
+	/// Sends every packet to a single relay, and accepts packets only from it.
+	pub struct UdpTransport {
+		socket: UdpSocket,
+		/// One byte bigger than the largest datagram, so oversized ones can be
+		/// told apart from ones that fit exactly.
+		buffer: Vec<u8>,
+	}
+
+	impl UdpTransport {
+		pub fn bind(address: impl ToSocketAddrs, relay: impl ToSocketAddrs) -> io::Result<Self> {
+			let socket = UdpSocket::bind(address)?;
+			socket.connect(relay)?;
+			socket.set_nonblocking(true)?;
+
+			Ok(Self {
+				socket,
+				buffer: vec![0; MAX_DATAGRAM_SIZE + 1]
+			})
+		}
+	}
+
+	impl Transport for UdpTransport {
+		fn send(&mut self, bytes: &[u8]) -> Result<(), SendError> {
+			if bytes.len() > MAX_DATAGRAM_SIZE {
+				crate::log!(LogLevel::Warn, "packet_dropped bytes={} max={} reason=too_large", bytes.len(), MAX_DATAGRAM_SIZE);
+				return Err(SendError::TooLarge);
+			}
+
+			match self.socket.send(bytes) {
+				Ok(_) => Ok(()),
+				Err(error) if error.kind() == ErrorKind::WouldBlock => Err(SendError::WouldBlock),
+				Err(error) => {
+					crate::log!(LogLevel::Warn, "send_failed error=\"{}\"", error);
+					Err(SendError::Failed)
+				},
+			}
+		}
+
+		fn poll(&mut self, receive: &mut dyn FnMut(&[u8])) {
+			loop {
+				match self.socket.recv(&mut self.buffer) {
+					Ok(size) if size > MAX_DATAGRAM_SIZE => {
+						crate::log!(LogLevel::Warn, "packet_dropped max={} reason=too_large", MAX_DATAGRAM_SIZE);
+					},
+					Ok(size) => receive(&self.buffer[..size]),
+					Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+					// The relay not listening (yet) shows up as a refused
+					// connection; it's worth trying again next frame.
+					Err(error) if error.kind() == ErrorKind::ConnectionRefused => break,
+					Err(error) => {
+						crate::log!(LogLevel::Error, "receive_failed error=\"{}\"", error);
+						break;
+					},
+				}
+			}
+		}
+	}
+}
+
+#[cfg(feature = "transport-websocket")]
+mod websocket {
+	use std::collections::VecDeque;
+	use std::sync::Mutex;
+	use macroquad::time::get_time;
+	use crate::log::LogLevel;
+	use super::{SendError, Transport, TransportEvent};
+
+	// The browser's WebSocket API, as exposed by the JS glue. Every packet
+	// travels as a single binary message; the glue drops text messages.
+	extern "C" {
+		/// Opens a socket to the given URL, closing the previous one if any.
+		fn ws_connect(
+			url_ptr: *const u8,
+			url_size: usize
+		);
+
+		/// Sends a binary message. Like `send_packet`, the memory is only
+		/// lent for the duration of the call, and zero comes back once the
+		/// socket took the message.
+		fn ws_send(
+			data_ptr: *const u8,
+			data_size: usize
+		) -> i32;
+	}
+
+	/// Most messages held back while the socket is still connecting. Past
+	/// that, the oldest ones are dropped; the server only cares about the
+	/// latest ticks anyway, and redundant frames cover the gap.
+	const MAX_QUEUED_MESSAGES: usize = 16;
+
+	/// Seconds to wait before reconnecting after the socket closes.
+	const RECONNECT_DELAY: f64 = 1.0;
+
+	enum SocketEvent {
+		Opened,
+		Closed,
+		Message(Vec<u8>),
+	}
+
+	/// Everything the JS glue reported since the transport last polled.
+	static SOCKET_EVENTS: Mutex<Vec<SocketEvent>> = Mutex::new(Vec::new());
+
+	// The JS glue reports back through these, by way of the binary's exports.
+
+	pub fn socket_opened() {
+		SOCKET_EVENTS.lock().unwrap().push(SocketEvent::Opened);
+	}
+
+	/// Called both when an open socket closes, and when connecting fails.
+	pub fn socket_closed() {
+		SOCKET_EVENTS.lock().unwrap().push(SocketEvent::Closed);
+	}
+
+	pub fn socket_message(bytes: Vec<u8>) {
+		SOCKET_EVENTS.lock().unwrap().push(SocketEvent::Message(bytes));
+	}
+
+	enum SocketState {
+		Connecting,
+		Open,
+		/// Closed at the given time, as per `get_time`.
+		Closed(f64),
+	}
+
+	/// Talks to a game server over a browser WebSocket, reconnecting whenever
+	/// it drops. After a reconnect the server is expected to send a snapshot,
+	/// the same as for a player joining a running game.
+	pub struct WebSocketTransport {
+		url: String,
+		state: SocketState,
+		/// Messages sent while still connecting, flushed once the socket opens.
+		queue: VecDeque<Vec<u8>>,
+		events: Vec<TransportEvent>,
+	}
+
+	impl WebSocketTransport {
+		pub fn connect(url: String) -> Self {
+			let mut transport = Self {
+				url,
+				state: SocketState::Connecting,
+				queue: VecDeque::new(),
+				events: Vec::new()
+			};
+			transport.open();
+			transport
+		}
+
+		fn open(&mut self) {
+			self.state = SocketState::Connecting;
+			unsafe {
+				ws_connect(
+					self.url.as_ptr(),
+					self.url.len()
+				);
+			}
+		}
+	}
+
+	impl Transport for WebSocketTransport {
+		fn send(&mut self, bytes: &[u8]) -> Result<(), SendError> {
+			match self.state {
+				SocketState::Open => {
+					let status = unsafe {
+						ws_send(
+							bytes.as_ptr(),
+							bytes.len()
+						)
+					};
+					match status {
+						0 => Ok(()),
+						_ => Err(SendError::WouldBlock),
+					}
+				},
+				SocketState::Connecting => {
+					self.queue.push_back(bytes.to_vec());
+					if self.queue.len() > MAX_QUEUED_MESSAGES {
+						self.queue.pop_front();
+					}
+					Ok(())
+				},
+				// Whatever we'd send now is made obsolete by the snapshot
+				// we'll get after reconnecting.
+				SocketState::Closed(_) => Ok(()),
+			}
+		}
+
+		fn poll(&mut self, receive: &mut dyn FnMut(&[u8])) {
+			let socket_events: Vec<SocketEvent> = SOCKET_EVENTS.lock().unwrap().drain(..).collect();
+			for event in socket_events {
+				match event {
+					SocketEvent::Opened => {
+						crate::log!(LogLevel::Info, "socket_opened url={} queued={}", self.url, self.queue.len());
+						self.state = SocketState::Open;
+						// Those the socket turns down this early are as good
+						// as lost, the same as the ones that didn't fit.
+						for message in std::mem::take(&mut self.queue) {
+							if self.send(&message).is_err() {
+								break;
+							}
+						}
+						self.events.push(TransportEvent::Connected);
+					},
+					SocketEvent::Closed => {
+						crate::log!(LogLevel::Info, "socket_closed url={}", self.url);
+						self.state = SocketState::Closed(get_time());
+						self.queue.clear();
+						self.events.push(TransportEvent::Disconnected);
+					},
+					SocketEvent::Message(bytes) => receive(&bytes),
+				}
+			}
+
+			if let SocketState::Closed(since) = self.state {
+				if get_time() - since > RECONNECT_DELAY {
+					self.open();
+				}
+			}
+		}
+
+		fn poll_events(&mut self) -> Vec<TransportEvent> {
+			std::mem::take(&mut self.events)
+		}
+	}
+}
+
+mod loopback {
+	use std::collections::BTreeMap;
+	use std::collections::VecDeque;
+	use crate::batch::split_batch;
+	use crate::chat;
+	use crate::game::{Game, SimConfig, IDLE_TICKS};
+	use crate::hash::Hash;
+	use crate::log::LogLevel;
+	use crate::net::{ByteWriter, NetType};
+	use crate::protocol::{
+		ClientId, CommandFrame, ConnectAccept, ConnectRequest, FrameLimits, Packet, PlayerInfo, PlayerIntent, RejectReason, Snapshot, SnapshotRequest, StateHash,
+		Tick, TickDecoder, TimeResponse
+	};
+	use crate::reliable::{ReliableConfig, ReliableEndpoint};
+	use super::{SendError, Transport};
+
+	/// A fake second client, following a script.
+	struct ScriptedClient {
+		client_id: ClientId,
+		/// Tick indices alongside the intents the client switches to from that
+		/// tick on, sorted by index.
+		schedule: Vec<(u64, Vec<PlayerIntent>)>,
+	}
+
+	impl ScriptedClient {
+		fn frame_for(&self, tick_index: u64) -> CommandFrame<PlayerIntent> {
+			let intents = self.schedule
+				.iter()
+				.take_while(|(index, _)| *index <= tick_index)
+				.last()
+				.map(|(_, intents)| intents.clone())
+				.unwrap_or_default();
+
+			// Normalized already, so that only a script asking for more
+			// than the limits allow gets clamped.
+			let mut frame = CommandFrame::new(self.client_id, intents);
+			frame.normalize();
+			frame
+		}
+	}
+
+	/// Runs a tiny authoritative server inside the process, so the whole
+	/// prediction and reconciliation flow can be exercised without a host.
+	///
+	/// The local players' frames are taken alongside those of an optional
+	/// scripted client, clamped to the frame limits; see `with_frame_limits`.
+	/// Every tick gets confirmed with an `Ack` when the client predicted it
+	/// correctly, or corrected with the full authoritative tick, frames as
+	/// clamped, when it didn't. The server simulates the ticks
+	/// too, and answers the client's state hashes with its own. The
+	/// scripted client introduces itself as a bot; the client's own names
+	/// are kept, with nobody else around to relay them to. Chat comes back
+	/// to the client, the same as it would from any other server, and so do
+	/// answers to time requests.
+	///
+	/// Every tick gets a frame from each client in the game, real or
+	/// scripted, in ascending ClientId order, the way the client predicts
+	/// them; see `Tick::canonical`. A client whose frame didn't make it gets
+	/// an empty one instead, which is to be expected of an idle client;
+	/// see `Game::is_idle`.
+	///
+	/// Ticks go by as the client sends its frames, unless given a clock:
+	/// then they go by as time does, each waiting for the client's frames
+	/// until its deadline, see `with_input_deadline`, and no longer. Frames
+	/// arriving after that are dropped, rather than applied to a tick
+	/// that's over, and counted; see `late_frames`. Told to, it sends the packets which have to
+	/// arrive in envelopes, the way a `ReliableTransport` on the client's
+	/// end expects them to; see `with_reliability`. It can also send each
+	/// tick's confirmation or correction again along with the next few, so
+	/// that losing one doesn't hold the client up; see `with_redundancy`.
+	pub struct LoopbackTransport {
+		/// The players on the client's side, the one it sends as first.
+		local_ids: Vec<ClientId>,
+		remote: Option<ScriptedClient>,
+		decoder: TickDecoder<PlayerIntent>,
+		/// The local players' frames, waiting for the ticks before them to
+		/// be completed.
+		local_frames: BTreeMap<u64, Vec<CommandFrame<PlayerIntent>>>,
+		/// Hashes of the ticks the client predicted, to tell whether it
+		/// predicted them correctly.
+		predicted_hashes: BTreeMap<u64, Hash>,
+		next_index: u64,
+		/// The first tick the client sent its frames for. A client which
+		/// synced its clock starts later than we do; the ticks before go
+		/// on without it.
+		first_local: Option<u64>,
+		/// Tells the time, in seconds, when ticks go by as time does.
+		clock: Option<fn() -> f64>,
+		/// How long after a tick is over its frames are still waited for,
+		/// in seconds, when ticks go by as time does.
+		input_deadline: f64,
+		/// Frames which arrived after their tick's deadline.
+		late_frames: u32,
+		/// Completed ticks in a row nobody did anything on. Past
+		/// `IDLE_TICKS`, the client goes idle, and its frames stop coming
+		/// every tick.
+		idle_ticks: u64,
+		/// When tick 0 started, as per `clock`: the first time it got asked.
+		started: Option<f64>,
+		/// The server's own copy of the world, which every completed tick
+		/// gets simulated on.
+		world: Game,
+		/// What every frame gets clamped to before its tick is completed.
+		frame_limits: FrameLimits,
+		/// Packets on their way to the client, encoded.
+		outgoing: VecDeque<Vec<u8>>,
+		/// Makes sure the packets which have to arrive do, if told to.
+		reliable: Option<ReliableEndpoint>,
+		/// Every packet gets written into this first, rather than into a
+		/// buffer of its own.
+		scratch: ByteWriter,
+		/// How many of the latest ticks' outcomes get sent again along with
+		/// each new one; see `with_redundancy`.
+		redundancy: usize,
+		/// Those outcomes, encoded, oldest first.
+		recent_outcomes: VecDeque<Vec<u8>>,
+	}
+
+	impl LoopbackTransport {
+		/// The client plays as `client_id`, which is also the ClientId it
+		/// gets handed when it connects.
+		pub fn new(client_id: ClientId) -> Self {
+			let mut transport = Self {
+				local_ids: vec![client_id],
+				remote: None,
+				decoder: TickDecoder::default(),
+				local_frames: BTreeMap::new(),
+				predicted_hashes: BTreeMap::new(),
+				next_index: 0,
+				first_local: None,
+				clock: None,
+				input_deadline: 0.0,
+				late_frames: 0,
+				idle_ticks: 0,
+				started: None,
+				world: Game::new(client_id),
+				frame_limits: FrameLimits::default(),
+				outgoing: VecDeque::new(),
+				reliable: None,
+				scratch: ByteWriter::new(),
+				redundancy: 0,
+				recent_outcomes: VecDeque::new()
+			};
+			transport.enqueue(transport.welcome());
+			transport
+		}
+
+		/// What the world's set up as, for the client to take on.
+		fn welcome(&self) -> Packet<PlayerIntent> {
+			Packet::Welcome {
+				tick_rate: self.world.tick_rate().hz(),
+				seed: self.world.seed(),
+				sim_config: *self.world.sim_config()
+			}
+		}
+
+		/// Swaps the welcome queued up by `new`, which is still the first
+		/// packet out, for one saying how the world's set up now.
+		fn requeue_welcome(&mut self) {
+			self.outgoing.pop_front();
+			self.enqueue(self.welcome());
+			self.outgoing.rotate_right(1);
+		}
+
+		/// Runs the server at `hz` rather than the default tick rate. Meant
+		/// to be called while setting up, before the client first polls.
+		pub fn with_tick_rate(mut self, hz: u8) -> Self {
+			self.world.set_tick_rate(hz);
+			self.requeue_welcome();
+			self
+		}
+
+		/// Simulates the world with `config` rather than the default one,
+		/// which the client gets told about in the welcome. Meant to be
+		/// called while setting up, the same as `with_tick_rate`.
+		pub fn with_sim_config(mut self, config: SimConfig) -> Self {
+			self.world.set_sim_config(config);
+			self.requeue_welcome();
+			self
+		}
+
+		/// Has ticks go by as `clock` says time does, rather than as the
+		/// client sends its frames, the way a real server can't be held up
+		/// by its clients. Time starts once the client first sends or polls.
+		pub fn with_clock(mut self, clock: fn() -> f64) -> Self {
+			self.clock = Some(clock);
+			self
+		}
+
+		/// Waits for the client's frames for `seconds` past the end of their
+		/// tick, rather than only until it's over, before going on without
+		/// them. Only makes a difference with a clock. Every tick gets
+		/// answered that much later too, which the client's prediction has
+		/// to make room for.
+		pub fn with_input_deadline(mut self, seconds: f64) -> Self {
+			self.input_deadline = seconds;
+			self
+		}
+
+		/// Frames the client sent after their tick's deadline, which got
+		/// dropped.
+		pub fn late_frames(&self) -> u32 {
+			self.late_frames
+		}
+
+		/// Clamps every frame to `limits` rather than the defaults, however
+		/// many intents the client stuffed into it; see `CommandFrame::clamp`.
+		pub fn with_frame_limits(mut self, limits: FrameLimits) -> Self {
+			self.frame_limits = limits;
+			self
+		}
+
+		/// Makes sure the packets the client can't do without get to it, and
+		/// answers those it makes sure of; see `reliable`. Without a clock,
+		/// nothing ever gets sent again, as no time goes by.
+		pub fn with_reliability(mut self, config: ReliableConfig) -> Self {
+			self.reliable = Some(ReliableEndpoint::new(config));
+			// Whatever's already queued up goes out in envelopes too.
+			let now = self.now();
+			let endpoint = self.reliable.as_mut().unwrap();
+			let mut outgoing = VecDeque::new();
+			for bytes in self.outgoing.drain(..) {
+				endpoint.send(&bytes, now, |x| outgoing.push_back(x.to_vec()));
+			}
+			self.outgoing = outgoing;
+			self
+		}
+
+		/// Sends the `Ack` or correction of each of the last `count` ticks
+		/// again along with that of every new one, each in a packet of its
+		/// own. A correction lost on the way holds up the client until it
+		/// comes again, as the ones after it are off the same wrong start;
+		/// the way past frames do for the client's ticks, this makes up for
+		/// it with the next packet to arrive.
+		pub fn with_redundancy(mut self, count: usize) -> Self {
+			self.redundancy = count;
+			self
+		}
+
+		/// The time as per `clock`, or the very start without one.
+		fn now(&self) -> f64 {
+			self.clock.map_or(0.0, |clock| clock())
+		}
+
+		/// How many ticks have gone by since the first, fractional, if ticks
+		/// go by as time does.
+		fn elapsed_ticks(&mut self) -> Option<f64> {
+			let clock = self.clock?;
+			let now = clock();
+			let started = *self.started.get_or_insert(now);
+			Some(((now - started) / self.world.tick_rate().delta() as f64).max(0.0))
+		}
+
+		/// Adds a fake client, which switches to the scheduled intents on the
+		/// given ticks, holding them until its next scheduled change.
+		pub fn with_remote(mut self, client_id: ClientId, mut schedule: Vec<(u64, Vec<PlayerIntent>)>) -> Self {
+			schedule.sort_by_key(|(index, _)| *index);
+			self.remote = Some(ScriptedClient {
+				client_id,
+				schedule
+			});
+			self.world.add_member(client_id);
+			self.enqueue(Packet::Join(client_id));
+			let info = PlayerInfo::new(client_id, &format!("Bot {}", client_id));
+			self.world.set_player_info(info.clone());
+			self.enqueue(Packet::PlayerInfo(info));
+			self
+		}
+
+		/// Takes the frames of another player sharing the client's screen.
+		pub fn with_local(mut self, client_id: ClientId) -> Self {
+			self.local_ids.push(client_id);
+			self.world.add_member(client_id);
+			self
+		}
+
+		/// The server's world as of the last tick it completed, for a
+		/// client that needs to get back in step.
+		pub fn snapshot(&self) -> Snapshot {
+			self.world.snapshot()
+		}
+
+		fn enqueue(&mut self, packet: Packet<PlayerIntent>) {
+			self.scratch.clear();
+			if let Err(error) = packet.to_bytes(&mut self.scratch) {
+				return crate::log!(LogLevel::Warn, "encode_failed tag={} error=\"{}\"", packet.tag(), error);
+			}
+			let now = self.now();
+			let outgoing = &mut self.outgoing;
+			match &mut self.reliable {
+				Some(endpoint) => endpoint.send(self.scratch.as_slice(), now, |x| outgoing.push_back(x.to_vec())),
+				None => outgoing.push_back(self.scratch.as_slice().to_vec()),
+			}
+		}
+
+		/// Sends a chat message back out to everyone, which only the client
+		/// has any use for; the scripted client doesn't read. Sanitized on
+		/// the way, as the server can't trust its clients to have done so.
+		fn relay_chat(&mut self, sender: ClientId, text: &str) {
+			let text = chat::sanitize(text);
+			if text.is_empty() || !self.local_ids.contains(&sender) {
+				return;
+			}
+			crate::log!(LogLevel::Debug, "chat sender={} len={}", sender, text.len());
+			self.enqueue(Packet::Chat { sender, text });
+		}
+
+		/// Lets the client in as the first of the local players, which is
+		/// who our world has it playing as, whichever ClientId it asks for;
+		/// unless that's the scripted client's, which is taken.
+		fn connect(&mut self, request: ConnectRequest) {
+			let taken = self.remote.as_ref().map(|x| x.client_id);
+			if request.requested_id.is_some() && request.requested_id == taken {
+				crate::log!(LogLevel::Warn, "connect_rejected requested={:?} reason=id_taken", request.requested_id);
+				return self.enqueue(Packet::ConnectReject(RejectReason::IdTaken));
+			}
+			let client_id = self.local_ids[0];
+			if let Some(name) = &request.name {
+				self.world.set_player_info(PlayerInfo::new(client_id, name));
+			}
+			// Whichever ticks are over by now go on without the client.
+			if self.clock.is_some() {
+				self.complete_ticks();
+			}
+			self.enqueue(Packet::ConnectAccept(ConnectAccept {
+				client_id,
+				tick_rate: self.world.tick_rate().hz(),
+				starting_tick: self.next_index,
+				sim_config: *self.world.sim_config()
+			}));
+		}
+
+		/// Sends the world as of the last tick completed, to a client which
+		/// lost track of it.
+		fn send_snapshot(&mut self, request: SnapshotRequest) {
+			if self.clock.is_some() {
+				self.complete_ticks();
+			}
+			let snapshot = self.world.snapshot();
+			crate::log!(LogLevel::Info, "snapshot_sent sender={} reason={:?} tick={}", request.sender, request.reason, snapshot.index);
+			self.enqueue(Packet::Snapshot(snapshot));
+		}
+
+		/// Records the local players' frames from a tick the client sent.
+		fn accept(&mut self, tick: Tick<PlayerIntent>, predicted: bool) {
+			// Past frames get sent again on purpose, and are only ever late
+			// when the ticks they were sent along with were.
+			if tick.index() < self.next_index {
+				if predicted {
+					crate::log!(LogLevel::Debug, "frame_late tick={} expected={}", tick.index(), self.next_index);
+					self.late_frames += 1;
+				}
+				return;
+			}
+			self.first_local.get_or_insert(tick.index());
+			let command_frames: Vec<CommandFrame<PlayerIntent>> = tick.command_frames()
+				.iter()
+				.filter(|x| self.local_ids.contains(&x.owner))
+				.cloned()
+				.collect();
+			if !command_frames.is_empty() {
+				self.local_frames.insert(tick.index(), command_frames);
+			}
+			// Ticks made up of past frames aren't whole, and so can't be
+			// what the client writes the next ones against.
+			if predicted {
+				self.predicted_hashes.insert(tick.index(), *tick.hash());
+				self.enqueue(Packet::Received { index: tick.index() });
+			}
+		}
+
+		/// Completes every tick we have the local players' frames for, in
+		/// order. With a clock, every tick past its deadline gets completed
+		/// instead, with whichever frames arrived for it.
+		fn complete_ticks(&mut self) {
+			let elapsed = self.elapsed_ticks();
+			let deadline = self.input_deadline / self.world.tick_rate().delta() as f64;
+			loop {
+				let index = self.next_index;
+				if elapsed.is_some_and(|x| index as f64 + 1.0 + deadline > x) {
+					break;
+				}
+				let mut command_frames = match (self.local_frames.remove(&index), elapsed) {
+					(Some(local_frames), _) => local_frames,
+					(None, Some(_)) => vec![],
+					(None, None) if self.first_local.is_some_and(|x| index < x) => vec![],
+					(None, None) => break,
+				};
+				command_frames.extend(self.remote.iter().map(|x| x.frame_for(index)));
+
+				// Whatever the client sent, every client gets to simulate the
+				// same canonical intents, and no more of them than allowed.
+				// Clamped before the tick gets hashed, so that a correction
+				// carries the frames as clamped.
+				let mut tick = self.assemble(index, command_frames);
+				let clamped = tick.clamp_frames(&self.frame_limits);
+				if !clamped.is_empty() {
+					crate::log!(LogLevel::Warn, "frames_clamped tick={} owners={:?}", index, clamped);
+				}
+				self.idle_ticks = match tick.is_idle() {
+					true => self.idle_ticks + 1,
+					false => 0,
+				};
+				self.world.follow(tick.clone());
+
+				let outcome = match self.predicted_hashes.remove(&index) {
+					Some(hash) if hash == *tick.hash() => Packet::Ack { index, hash },
+					_ => Packet::AuthoritativeTick(tick),
+				};
+				self.send_outcome(outcome);
+				self.next_index += 1;
+			}
+		}
+
+		/// Sends what became of a tick, after the outcomes of the ticks
+		/// before it that get sent again; see `with_redundancy`.
+		fn send_outcome(&mut self, outcome: Packet<PlayerIntent>) {
+			self.outgoing.extend(self.recent_outcomes.iter().cloned());
+			self.enqueue(outcome);
+			if self.redundancy == 0 {
+				return;
+			}
+			if self.recent_outcomes.len() >= self.redundancy {
+				self.recent_outcomes.pop_front();
+			}
+			self.recent_outcomes.extend(self.outgoing.back().cloned());
+		}
+
+		/// Tick `index` out of `command_frames`, with an empty frame for
+		/// every client in the game without one among them, in canonical
+		/// order.
+		fn assemble(&self, index: u64, mut command_frames: Vec<CommandFrame<PlayerIntent>>) -> Tick<PlayerIntent> {
+			let members = self.local_ids.iter().chain(self.remote.iter().map(|x| &x.client_id));
+			for owner in members {
+				if command_frames.iter().any(|x| x.owner == *owner) {
+					continue;
+				}
+				// Before the client's first tick, it wasn't playing yet, and
+				// an idle client only sends every so often.
+				if self.first_local.is_some_and(|x| index >= x) && self.idle_ticks < IDLE_TICKS {
+					crate::log!(LogLevel::Debug, "frame_missed tick={} owner={}", index, owner);
+				}
+				command_frames.push(CommandFrame::new(*owner, vec![]));
+			}
+			Tick::canonical(index, command_frames)
+		}
+	}
+
+	impl LoopbackTransport {
+		/// Takes in a single packet from the client.
+		fn receive(&mut self, bytes: &[u8]) {
+			let (tick, past_frames, state_hash) = match Packet::decode(bytes) {
+				Ok(Packet::PredictedTick { tick, past_frames, state_hash, .. }) => (tick, past_frames, state_hash),
+				Ok(Packet::PlayerInfo(info)) => return self.world.set_player_info(info),
+				Ok(Packet::Chat { sender, text }) => return self.relay_chat(sender, &text),
+				Ok(Packet::ConnectRequest(request)) => return self.connect(request),
+				Ok(Packet::SnapshotRequest(request)) => return self.send_snapshot(request),
+				// Without a clock, ticks only go by as the client sends its
+				// frames, so we're never in the middle of one.
+				Ok(Packet::TimeRequest(request)) => {
+					let (tick, progress) = match self.elapsed_ticks() {
+						Some(elapsed) => (elapsed.floor() as u64, elapsed.fract() as f32),
+						None => (self.next_index, 0.0),
+					};
+					let response = TimeResponse { client_time: request.client_time, tick, progress };
+					return self.enqueue(Packet::TimeResponse(response));
+				},
+				_ => return,
+			};
+			// The past frames make up for packets lost before this one,
+			// which may have taken the tick's baseline with them, so they're
+			// taken in whether the tick itself decodes or not.
+			for recovered in self.decoder.recover(tick.index(), past_frames) {
+				self.accept(recovered, false);
+			}
+			let Ok(tick) = self.decoder.decode(tick) else {
+				self.complete_ticks();
+				return;
+			};
+			if self.decoder.first_sighting(tick.index()) {
+				self.accept(tick, true);
+			}
+			self.complete_ticks();
+
+			// Reports are for ticks the client had confirmed, so we've
+			// always simulated them already.
+			let Some(report) = state_hash else { return };
+			if let Some(hash) = self.world.state_hash_at(report.index) {
+				self.enqueue(Packet::StateHash(StateHash { index: report.index, hash }));
+			}
+		}
+	}
+
+	impl Transport for LoopbackTransport {
+		/// Takes batches too, splitting them up as a real server would.
+		/// Envelopes only make it in when told to take them.
+		fn send(&mut self, bytes: &[u8]) -> Result<(), SendError> {
+			let packets = match split_batch(bytes) {
+				Ok(packets) => packets,
+				Err(error) => {
+					crate::log!(LogLevel::Warn, "batch_rejected bytes={} error=\"{}\"", bytes.len(), error);
+					return Ok(());
+				},
+			};
+			// Received before any of them gets acted on, as acting on them
+			// can send packets through the endpoint too.
+			let mut delivered = vec![];
+			for packet in packets {
+				match &mut self.reliable {
+					Some(endpoint) => {
+						let outgoing = &mut self.outgoing;
+						endpoint.receive(packet, |x| outgoing.push_back(x.to_vec()), |x| delivered.push(x.to_vec()));
+					},
+					None => delivered.push(packet.to_vec()),
+				}
+			}
+			for packet in delivered {
+				self.receive(&packet);
+			}
+			Ok(())
+		}
+
+		fn poll(&mut self, receive: &mut dyn FnMut(&[u8])) {
+			if self.clock.is_some() {
+				self.complete_ticks();
+			}
+			let now = self.now();
+			if let Some(endpoint) = &mut self.reliable {
+				let outgoing = &mut self.outgoing;
+				endpoint.update(now, |x| outgoing.push_back(x.to_vec()));
+			}
+			for bytes in self.outgoing.drain(..) {
+				receive(&bytes);
+			}
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use crate::protocol::{EncodedTick, PastFrame, PlayerIntent::*, TickDelta};
+		use super::*;
+
+		const LOCAL: ClientId = 3;
+
+		fn tick(index: u64, intents: Vec<PlayerIntent>) -> Tick<PlayerIntent> {
+			Tick::new(index, vec![CommandFrame::new(LOCAL, intents)])
+		}
+
+		fn send(server: &mut LoopbackTransport, tick: EncodedTick<PlayerIntent>, past_frames: Vec<PastFrame<PlayerIntent>>) {
+			let mut writer = ByteWriter::new();
+			Packet::PredictedTick { sender: LOCAL, tick, past_frames, state_hash: None }.to_bytes(&mut writer).unwrap();
+			server.send(writer.as_slice()).unwrap();
+		}
+
+		#[test]
+		fn frames_of_a_lost_packet_are_recovered_from_the_next_one() {
+			let mut server = LoopbackTransport::new(LOCAL);
+			let lost = tick(1, vec![MoveRight, JumpStart]);
+			send(&mut server, EncodedTick::Keyframe(tick(0, vec![MoveRight])), vec![]);
+			// Tick 1's packet never arrives, and tick 2's is delta encoded
+			// against it, so only its past frames can be made sense of.
+			let past_frames = vec![PastFrame { owner: LOCAL, age: 1, intents: vec![MoveRight, JumpStart], offset: 0 }];
+			send(&mut server, EncodedTick::Delta(TickDelta::between(&tick(2, vec![MoveLeft]), &lost)), past_frames);
+
+			let mut corrections = vec![];
+			server.poll(&mut |bytes| {
+				if let Ok(Packet::AuthoritativeTick(tick)) = Packet::<PlayerIntent>::decode(bytes) {
+					corrections.push(tick);
+				}
+			});
+			let recovered = corrections.iter().find(|x| x.index() == 1).expect("tick 1 never got completed");
+			assert_eq!(recovered.frame_of(LOCAL), lost.frame_of(LOCAL));
+		}
+	}
+}
+
+mod simulated {
+	use std::fmt;
+	use macroquad::time::get_time;
+	use super::{SendError, Transport, TransportEvent};
+
+	/// How badly the simulated network behaves. Percentages apply to every
+	/// packet individually, in both directions.
+	#[derive(Clone, Copy, Default)]
+	pub struct SimulatedConditions {
+		/// One-way delay added to every packet.
+		pub latency_ms: u32,
+		/// Most the delay of a single packet deviates from `latency_ms`, either way.
+		pub jitter_ms: u32,
+		pub loss_percent: u8,
+		pub duplicate_percent: u8,
+		/// Share of packets held back long enough for later ones to overtake them.
+		pub reorder_percent: u8,
+		/// Whether sends get turned down outright, the way they are by a
+		/// host applying backpressure. Only goes for the client's end.
+		pub rejecting: bool,
+	}
+
+	impl fmt::Display for SimulatedConditions {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(
+				f,
+				"{}±{} ms, {}% lost, {}% duplicated, {}% reordered",
+				self.latency_ms,
+				self.jitter_ms,
+				self.loss_percent,
+				self.duplicate_percent,
+				self.reorder_percent
+			)?;
+			match self.rejecting {
+				true => write!(f, ", sends rejected"),
+				false => Ok(()),
+			}
+		}
+	}
+
+	/// How long reordered packets are held back on top of their usual delay.
+	const REORDER_DELAY: f64 = 0.1;
+
+	/// A small xorshift generator. Good enough to pick which packets to drop,
+	/// and seeded, so that runs can be reproduced.
+	struct Rng(u64);
+
+	impl Rng {
+		fn next_u64(&mut self) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0
+		}
+
+		fn chance(&mut self, percent: u8) -> bool {
+			self.next_u64() % 100 < percent as u64
+		}
+
+		/// A uniformly distributed value in `-range..=range`.
+		fn spread(&mut self, range: u32) -> i64 {
+			(self.next_u64() % (2 * range as u64 + 1)) as i64 - range as i64
+		}
+	}
+
+	struct InFlight {
+		/// When the packet arrives, as per `get_time`.
+		arrival: f64,
+		/// Breaks ties between packets arriving at the same time.
+		sequence: u64,
+		bytes: Vec<u8>,
+	}
+
+	/// Wraps another transport, delaying, dropping, duplicating and reordering
+	/// the packets going through it.
+	pub struct SimulatedTransport<T: Transport> {
+		inner: T,
+		conditions: SimulatedConditions,
+		rng: Rng,
+		/// Tells the time, in seconds; `get_time` unless told otherwise.
+		clock: fn() -> f64,
+		sequence: u64,
+		outgoing: Vec<InFlight>,
+		incoming: Vec<InFlight>,
+	}
+
+	impl<T: Transport> SimulatedTransport<T> {
+		pub fn new(inner: T, conditions: SimulatedConditions, seed: u64) -> Self {
+			Self {
+				inner,
+				conditions,
+				// Xorshift gets stuck on zero.
+				rng: Rng(seed.max(1)),
+				clock: get_time,
+				sequence: 0,
+				outgoing: Vec::new(),
+				incoming: Vec::new()
+			}
+		}
+
+		/// Tells the time with `clock` rather than `get_time`, for running
+		/// without a window, or faster than real time.
+		pub fn with_clock(mut self, clock: fn() -> f64) -> Self {
+			self.clock = clock;
+			self
+		}
+
+		/// The transport the packets go through, once they got through the
+		/// simulated network.
+		pub fn inner(&self) -> &T {
+			&self.inner
+		}
+
+		/// Puts a packet in flight, as many times as the conditions say.
+		fn schedule(&mut self, direction: fn(&mut Self) -> &mut Vec<InFlight>, bytes: Vec<u8>, now: f64) {
+			if self.rng.chance(self.conditions.loss_percent) {
+				return;
+			}
+			let copies = match self.rng.chance(self.conditions.duplicate_percent) {
+				true => 2,
+				false => 1,
+			};
+
+			for _ in 0..copies {
+				let delay_ms = (self.conditions.latency_ms as i64 + self.rng.spread(self.conditions.jitter_ms)).max(0);
+				let mut arrival = now + delay_ms as f64 / 1000.0;
+				if self.rng.chance(self.conditions.reorder_percent) {
+					arrival += REORDER_DELAY;
+				}
+
+				self.sequence += 1;
+				let in_flight = InFlight {
+					arrival,
+					sequence: self.sequence,
+					bytes: bytes.clone()
+				};
+				direction(self).push(in_flight);
+			}
+		}
+
+		/// Takes out the packets which have arrived by `now`, in arrival order.
+		fn arrived(packets: &mut Vec<InFlight>, now: f64) -> Vec<Vec<u8>> {
+			let mut arrived: Vec<InFlight> = vec![];
+			let mut i = 0;
+			while i < packets.len() {
+				match packets[i].arrival <= now {
+					true => arrived.push(packets.swap_remove(i)),
+					false => i += 1,
+				}
+			}
+			arrived.sort_by(|a, b| a.arrival.total_cmp(&b.arrival).then(a.sequence.cmp(&b.sequence)));
+			arrived.into_iter().map(|x| x.bytes).collect()
+		}
+
+		/// The inner transport turning down a packet that's been on its
+		/// way already is no different from the packet getting lost.
+		fn flush_outgoing(&mut self, now: f64) {
+			for bytes in Self::arrived(&mut self.outgoing, now) {
+				let _ = self.inner.send(&bytes);
+			}
+			let _ = self.inner.flush();
+		}
+	}
+
+	impl<T: Transport> Transport for SimulatedTransport<T> {
+		fn send(&mut self, bytes: &[u8]) -> Result<(), SendError> {
+			let now = (self.clock)();
+			if self.conditions.rejecting {
+				self.flush_outgoing(now);
+				return Err(SendError::WouldBlock);
+			}
+			self.schedule(|x| &mut x.outgoing, bytes.to_vec(), now);
+			self.flush_outgoing(now);
+			Ok(())
+		}
+
+		fn poll(&mut self, receive: &mut dyn FnMut(&[u8])) {
+			let now = (self.clock)();
+			self.flush_outgoing(now);
+			// Packets are held on to for a while, so they need copies of
+			// their own anyway.
+			let mut received = vec![];
+			self.inner.poll(&mut |bytes| received.push(bytes.to_vec()));
+			for bytes in received {
+				self.schedule(|x| &mut x.incoming, bytes, now);
+			}
+			for bytes in Self::arrived(&mut self.incoming, now) {
+				receive(&bytes);
+			}
+		}
+
+		fn poll_events(&mut self) -> Vec<TransportEvent> {
+			self.inner.poll_events()
+		}
+
+		fn is_congested(&self) -> bool {
+			self.inner.is_congested()
+		}
+
+		fn simulated_conditions(&mut self) -> Option<&mut SimulatedConditions> {
+			Some(&mut self.conditions)
+		}
+	}
+}
+
+mod reliable {
+	use macroquad::time::get_time;
+	use crate::batch::split_batch;
+	use crate::log::LogLevel;
+	use crate::reliable::{is_reliable, ReliableConfig, ReliableEndpoint};
+	use super::{SendError, SimulatedConditions, Transport, TransportEvent};
+
+	/// Wraps another transport, making sure the packets which have to get
+	/// to the other end do, exactly once and in order; see `reliable`. The
+	/// other end has to speak `RELIABLE_VERSION` of the protocol or later.
+	pub struct ReliableTransport<T: Transport> {
+		inner: T,
+		endpoint: ReliableEndpoint,
+		/// Tells the time, in seconds; `get_time` unless told otherwise.
+		clock: fn() -> f64,
+	}
+
+	impl<T: Transport> ReliableTransport<T> {
+		pub fn new(inner: T, config: ReliableConfig) -> Self {
+			Self {
+				inner,
+				endpoint: ReliableEndpoint::new(config),
+				clock: get_time
+			}
+		}
+
+		/// Tells the time with `clock` rather than `get_time`, for running
+		/// without a window, or faster than real time.
+		pub fn with_clock(mut self, clock: fn() -> f64) -> Self {
+			self.clock = clock;
+			self
+		}
+
+		pub fn endpoint(&self) -> &ReliableEndpoint {
+			&self.endpoint
+		}
+	}
+
+	impl<T: Transport> Transport for ReliableTransport<T> {
+		/// Packets in an envelope go again until they're answered, so only
+		/// those outside one can get turned down.
+		fn send(&mut self, bytes: &[u8]) -> Result<(), SendError> {
+			let inner = &mut self.inner;
+			let mut sent = Ok(());
+			self.endpoint.send(bytes, (self.clock)(), |x| sent = inner.send(x));
+			match is_reliable(bytes) {
+				true => Ok(()),
+				false => sent,
+			}
+		}
+
+		fn flush(&mut self) -> Result<(), SendError> {
+			self.inner.flush()
+		}
+
+		/// Answers whatever arrived in an envelope, and sends again whatever
+		/// the other end didn't answer in time. Batches come out split up.
+		fn poll(&mut self, receive: &mut dyn FnMut(&[u8])) {
+			let mut received = vec![];
+			self.inner.poll(&mut |bytes| received.push(bytes.to_vec()));
+			let inner = &mut self.inner;
+			for bytes in &received {
+				match split_batch(bytes) {
+					Ok(packets) => {
+						for packet in packets {
+							self.endpoint.receive(packet, |x| { let _ = inner.send(x); }, &mut *receive);
+						}
+					},
+					Err(error) => crate::log!(LogLevel::Warn, "batch_rejected bytes={} error=\"{}\"", bytes.len(), error),
+				}
+			}
+			self.endpoint.update((self.clock)(), |x| { let _ = inner.send(x); });
+		}
+
+		fn poll_events(&mut self) -> Vec<TransportEvent> {
+			self.inner.poll_events()
+		}
+
+		fn is_congested(&self) -> bool {
+			self.endpoint.is_congested() || self.inner.is_congested()
+		}
+
+		fn simulated_conditions(&mut self) -> Option<&mut SimulatedConditions> {
+			self.inner.simulated_conditions()
+		}
+	}
+}
\ No newline at end of file
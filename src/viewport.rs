@@ -0,0 +1,96 @@
+//! Maps the playfield onto the window, however large it is. Everything is
+//! simulated and drawn in logical pixels, `SCREEN_SIZE` across either way,
+//! so the window's size never gets anywhere near the simulation.
+//!
+//! The world itself is larger than the screen. Gameplay is drawn in world
+//! coordinates, through a camera showing the part of it that's in view;
+//! text and the overlay are drawn in screen coordinates on top.
+
+use macroquad::prelude::*;
+
+use crate::SCREEN_SIZE;
+
+/// The top left corner of the part of a `world` sized world in view, with
+/// `focus` in the middle of it, as far as that's possible without looking
+/// past the edges of the world.
+pub fn view_origin(focus: (f32, f32), (width, height): (f32, f32)) -> (f32, f32) {
+	let half = SCREEN_SIZE as f32 / 2.0;
+	let max_x = (width - SCREEN_SIZE as f32).max(0.0);
+	let max_y = (height - SCREEN_SIZE as f32).max(0.0);
+	((focus.0 - half).clamp(0.0, max_x), (focus.1 - half).clamp(0.0, max_y))
+}
+
+/// Where the playfield ends up on screen: scaled up as far as it fits,
+/// keeping it square, and centered with bars along the leftover sides.
+/// Screen coordinates are in points, the same as `screen_width` and the
+/// mouse position.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+	/// Points per logical pixel.
+	scale: f32,
+	/// Where the playfield's top left corner is on screen.
+	offset: (f32, f32),
+}
+
+impl Viewport {
+	/// Fits the playfield into a screen of the given size, in points.
+	pub fn fit(width: f32, height: f32) -> Self {
+		let size = SCREEN_SIZE as f32;
+		let scale = (width / size).min(height / size).max(f32::MIN_POSITIVE);
+		Self {
+			scale,
+			offset: ((width - size * scale) / 2.0, (height - size * scale) / 2.0)
+		}
+	}
+
+	/// Fits the playfield into the window as it is right now.
+	pub fn current() -> Self {
+		Self::fit(screen_width(), screen_height())
+	}
+
+	pub fn scale(&self) -> f32 {
+		self.scale
+	}
+
+	pub fn to_screen(&self, (x, y): (f32, f32)) -> (f32, f32) {
+		(self.offset.0 + x * self.scale, self.offset.1 + y * self.scale)
+	}
+
+	/// The inverse of `to_screen`, for mouse and touch input. Points on the
+	/// bars map to just outside the playfield.
+	pub fn to_logical(&self, (x, y): (f32, f32)) -> (f32, f32) {
+		((x - self.offset.0) / self.scale, (y - self.offset.1) / self.scale)
+	}
+
+	/// A camera drawing logical pixels into the viewport, in screen space.
+	pub fn camera(&self) -> Camera2D {
+		self.camera_at((0.0, 0.0))
+	}
+
+	/// A camera drawing the world into the viewport, with `origin` ending
+	/// up in the top left corner; see `view_origin`. Anything out of view
+	/// gets clipped.
+	pub fn camera_at(&self, origin: (f32, f32)) -> Camera2D {
+		let size = SCREEN_SIZE as f32;
+		// Viewports are in physical pixels, counted from the bottom left,
+		// which the vertical centering makes no difference to.
+		let dpi = screen_dpi_scale();
+		let mut camera = Camera2D::from_display_rect(Rect::new(origin.0, origin.1, size, size));
+		camera.viewport = Some((
+			(self.offset.0 * dpi).round() as i32,
+			(self.offset.1 * dpi).round() as i32,
+			(size * self.scale * dpi).round() as i32,
+			(size * self.scale * dpi).round() as i32
+		));
+		camera
+	}
+
+	/// Starts drawing a frame: the bars are cleared to black, and the
+	/// playfield to `background`, with every draw call after this one in
+	/// logical pixels.
+	pub fn begin_frame(&self, background: Color) {
+		clear_background(BLACK);
+		set_camera(&self.camera());
+		draw_rectangle(0.0, 0.0, SCREEN_SIZE as f32, SCREEN_SIZE as f32, background);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,130 @@
+//! Feeds the decoder packets with hostile length prefixes, checking that
+//! they're rejected up front with the right error, and that decoding never
+//! allocates more than a full tick's worth of frames, whatever the lengths
+//! claim.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::hash::{Hasher, TickHasher};
+use command_networking_rs::protocol::{
+	CommandFrame, Packet, PlayerIntent, MAX_FRAMES_PER_TICK, MAX_INTENTS_PER_FRAME, MAX_PACKET_SIZE, MAX_PLAYERS, PROTOCOL_MAGIC,
+	PROTOCOL_VERSION
+};
+
+/// Keeps track of the largest single allocation made, by the thread
+/// making it, so that tests running alongside don't get counted.
+struct TrackingAllocator;
+
+thread_local! {
+	static LARGEST_ALLOCATION: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		// Gone already while the thread's being torn down.
+		let _ = LARGEST_ALLOCATION.try_with(|x| x.set(x.get().max(layout.size())));
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+/// An authoritative tick packet, up to and including its frame count.
+fn tick_header(index: u64, frames: u64) -> ByteWriter {
+	let mut writer = ByteWriter::new();
+	writer.write_bytes(&PROTOCOL_MAGIC);
+	writer.write_u8(PROTOCOL_VERSION);
+	writer.write_u8(Hasher::ID);
+	writer.write_u8(Packet::<PlayerIntent>::TAG_AUTHORITATIVE_TICK);
+	writer.write_varint(index);
+	writer.write_varint(frames);
+	writer
+}
+
+fn decode(bytes: &[u8]) -> Result<Packet<PlayerIntent>, DecodeError> {
+	Packet::from_bytes(&mut ByteReader::new(bytes))
+}
+
+#[test]
+fn more_frames_than_there_can_be_clients() {
+	let writer = tick_header(0, u64::MAX >> 1);
+	assert!(matches!(decode(writer.as_slice()), Err(DecodeError::LimitExceeded { what: "command frames", .. })));
+}
+
+#[test]
+fn more_frames_than_the_packet_could_hold() {
+	// Within the limit, but more than the rest of the packet could hold.
+	let mut writer = tick_header(0, MAX_FRAMES_PER_TICK as u64);
+	writer.write_bytes(&[0; 16]);
+	assert!(matches!(decode(writer.as_slice()), Err(DecodeError::LengthOutOfBounds { len: MAX_FRAMES_PER_TICK, .. })));
+}
+
+#[test]
+fn a_frame_claiming_too_many_intents() {
+	// A single frame, written as a list.
+	let mut writer = tick_header(0, 1);
+	0u16.to_bytes(&mut writer).unwrap();
+	writer.write_u8(1 << 7);
+	writer.write_varint(MAX_INTENTS_PER_FRAME as u64 + 1);
+	writer.write_bytes(&[PlayerIntent::TAG_MOVE_LEFT; MAX_INTENTS_PER_FRAME + 1]);
+	assert!(matches!(decode(writer.as_slice()), Err(DecodeError::LimitExceeded { what: "intents", .. })));
+}
+
+#[test]
+fn a_packet_too_large_to_be_looked_at() {
+	let mut writer = tick_header(0, 0);
+	writer.write_bytes(&vec![0; MAX_PACKET_SIZE]);
+	assert!(matches!(decode(writer.as_slice()), Err(DecodeError::PacketTooLarge { .. })));
+}
+
+#[test]
+fn a_snapshot_naming_more_players_than_a_game_can_have() {
+	let mut writer = ByteWriter::new();
+	Packet::<PlayerIntent>::Snapshot(Game::new(0).snapshot()).to_bytes(&mut writer).unwrap();
+	let mut bytes = writer.as_slice().to_vec();
+	assert_eq!(bytes.pop(), Some(0), "the snapshot's names don't come last");
+	let mut writer = ByteWriter::new();
+	writer.write_bytes(&bytes);
+	writer.write_varint(MAX_PLAYERS as u64 + 1);
+	writer.write_bytes(&vec![0; 2 * MAX_PLAYERS]);
+	LARGEST_ALLOCATION.set(0);
+	assert!(matches!(decode(writer.as_slice()), Err(DecodeError::LimitExceeded { what: "player infos", .. })));
+	assert!(LARGEST_ALLOCATION.get() < MAX_PLAYERS, "allocated for the names anyway");
+}
+
+#[test]
+fn random_lengths_never_allocate_more_than_a_full_tick() {
+	// Random packets, with every length prefix set to something large.
+	LARGEST_ALLOCATION.set(0);
+	let mut state = 0x2545_f491_4f6c_dd1du64;
+	let mut next = move || {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		state
+	};
+	for _ in 0..10_000 {
+		let mut writer = tick_header(next() % 1000, next() % (MAX_FRAMES_PER_TICK as u64 + 1));
+		for _ in 0..next() % 64 {
+			match next() % 4 {
+				0 => writer.write_varint(next()),
+				1 => writer.write_varint(next() % (MAX_INTENTS_PER_FRAME as u64 + 1)),
+				2 => writer.write_u8(1 << 7),
+				_ => writer.write_u8(next() as u8),
+			}
+		}
+		let _ = decode(writer.as_slice());
+	}
+
+	let largest = LARGEST_ALLOCATION.get();
+	let bound = MAX_FRAMES_PER_TICK * std::mem::size_of::<CommandFrame<PlayerIntent>>();
+	assert!(largest <= bound, "decoding allocated {} bytes at once", largest);
+}
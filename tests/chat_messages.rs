@@ -0,0 +1,85 @@
+//! Chats through the in-process server, to check that messages come back
+//! sanitized and capped, that only the last few are kept, and that a game
+//! chatting away ends up in exactly the same state as one that doesn't.
+
+mod common;
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+use command_networking_rs::chat::{self, ChatLog, ChatMessage};
+use command_networking_rs::game::Game;
+use command_networking_rs::hash::Hash;
+use command_networking_rs::protocol::{Packet, PlayerIntent, MAX_CHAT_LEN};
+use command_networking_rs::transport::{LoopbackTransport, Transport};
+use common::send;
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const TICKS: u64 = 60;
+
+/// Plays against the loopback server, saying each of `lines` on the tick
+/// alongside it. Returns the chat as it ended up, and the final state hash.
+fn play(lines: &[(u64, &str)]) -> (Vec<ChatMessage>, Hash) {
+	let mut transport = LoopbackTransport::new(LOCAL).with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])]);
+	let mut game = Game::new(LOCAL);
+	for index in 0..TICKS {
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(index as f64 / 20.0);
+		for (_, line) in lines.iter().filter(|(at, _)| *at == index) {
+			if let Some(packet) = game.say(line) {
+				send(&mut transport, packet);
+			}
+		}
+		if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+			send(&mut transport, packet);
+		}
+	}
+	transport.poll(&mut |packet| game.dispatch_packet(packet));
+	game.receive(TICKS as f64 / 20.0);
+	(game.chat().messages().cloned().collect(), game.state_hash())
+}
+
+#[test]
+fn control_characters_and_overlong_text_get_cut() {
+	assert_eq!(chat::sanitize("  hi\tthere\u{7}\n "), "hithere");
+	let long = "é".repeat(MAX_CHAT_LEN);
+	assert_eq!(chat::sanitize(&long), "é".repeat(MAX_CHAT_LEN / 2));
+}
+
+#[test]
+fn the_log_keeps_the_latest_messages() {
+	let mut log = ChatLog::default();
+	for i in 0..ChatLog::CAPACITY + 2 {
+		log.push(REMOTE, &format!("message {}", i));
+	}
+	log.push(REMOTE, "\n\r");
+	let texts: Vec<&str> = log.messages().map(|x| x.text.as_str()).collect();
+	assert_eq!(texts, ["message 2", "message 3", "message 4", "message 5", "message 6"]);
+}
+
+#[test]
+fn overlong_messages_are_dropped_while_decoding() {
+	let mut writer = ByteWriter::new();
+	Packet::<PlayerIntent>::Chat { sender: LOCAL, text: "x".repeat(MAX_CHAT_LEN + 1) }.to_bytes(&mut writer).unwrap();
+	assert!(matches!(
+		Packet::<PlayerIntent>::decode(writer.as_slice()),
+		Err(DecodeError::LimitExceeded { what: "chat message", .. })
+	));
+	let mut reader = ByteReader::new(&[0]);
+	assert!(String::from_bytes(&mut reader).unwrap().is_empty());
+}
+
+#[test]
+fn nothing_goes_out_of_a_message_of_whitespace() {
+	assert!(Game::new(LOCAL).say(" \u{1b} ").is_none());
+}
+
+#[test]
+fn chat_leaves_the_simulation_alone() {
+	let (quiet, quiet_hash) = play(&[]);
+	assert!(quiet.is_empty());
+	let lines = [(5, "hello"), (5, "anyone\u{0} there?"), (30, "  \t "), (40, "gg")];
+	let (chatty, chatty_hash) = play(&lines);
+	let expected = ["hello", "anyone there?", "gg"].map(|text| ChatMessage { sender: LOCAL, text: text.into() });
+	assert_eq!(chatty, expected);
+	assert_eq!(chatty_hash, quiet_hash, "chatting changed the simulation");
+}
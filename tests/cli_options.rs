@@ -0,0 +1,76 @@
+//! Parses a few sample command lines into the native client's options, and
+//! checks bad ones come back as errors rather than panics.
+
+use command_networking_rs::cli::{self, CliError, Mode, Options};
+
+fn parse(line: &str) -> Result<Options, CliError> {
+	cli::parse(line.split_whitespace().map(String::from))
+}
+
+#[test]
+fn no_arguments_mean_the_defaults() {
+	assert_eq!(parse(""), Ok(Options::default()));
+}
+
+#[test]
+fn every_flag_parses() {
+	let options = parse("--client-id 7 --tick-rate 60 --spectator --shared-screen --demo --scale 3 --name Ada --margin 4 --redundancy 5 --retransmit 100 --tick-budget 8 --loopback").unwrap();
+	assert_eq!(options, Options {
+		client_id: Some(7),
+		tick_rate: 60,
+		spectator: true,
+		shared_screen: true,
+		demo: true,
+		scale: 3,
+		name: Some("Ada".into()),
+		interp_margin: 4,
+		redundant_frames: 5,
+		retransmit_timeout_ms: 100,
+		tick_budget_ms: Some(8),
+		mode: Mode::Loopback
+	});
+	assert_eq!(options.reliable_config().retransmit_timeout, 0.1);
+
+	let options = parse("--loopback --replay game.cnr").unwrap();
+	assert_eq!(options.mode, Mode::Replay("game.cnr".into()));
+}
+
+#[cfg(feature = "transport-udp")]
+#[test]
+fn connecting_takes_a_relay_and_an_address_to_bind() {
+	let options = parse("--bind 0.0.0.0:9000 --connect 10.0.0.2:7777").unwrap();
+	assert_eq!(options.mode, Mode::Connect {
+		relay: "10.0.0.2:7777".into(),
+		bind: "0.0.0.0:9000".into()
+	});
+}
+
+#[cfg(not(feature = "transport-udp"))]
+#[test]
+fn connecting_needs_the_udp_transport() {
+	assert_eq!(parse("--connect 10.0.0.2:7777"), Err(CliError::UnknownArgument("--connect".into())));
+}
+
+#[test]
+fn bad_arguments_are_turned_down() {
+	assert_eq!(parse("--help"), Err(CliError::Help));
+	assert_eq!(parse("--client-id"), Err(CliError::MissingValue("--client-id")));
+	assert_eq!(parse("--client-id 65536"), Err(CliError::InvalidValue { flag: "--client-id", value: "65536".into() }));
+	assert_eq!(parse("--tick-rate 0"), Err(CliError::InvalidValue { flag: "--tick-rate", value: "0".into() }));
+	assert_eq!(parse("--scale 0"), Err(CliError::InvalidValue { flag: "--scale", value: "0".into() }));
+	assert_eq!(parse("--scale 17"), Err(CliError::InvalidValue { flag: "--scale", value: "17".into() }));
+	assert_eq!(parse("--name"), Err(CliError::MissingValue("--name")));
+	assert_eq!(parse("--margin -1"), Err(CliError::InvalidValue { flag: "--margin", value: "-1".into() }));
+	assert_eq!(parse("--redundancy 9"), Err(CliError::InvalidValue { flag: "--redundancy", value: "9".into() }));
+	assert_eq!(parse("--retransmit 0"), Err(CliError::InvalidValue { flag: "--retransmit", value: "0".into() }));
+	assert_eq!(parse("--tick-budget 0"), Err(CliError::InvalidValue { flag: "--tick-budget", value: "0".into() }));
+	assert_eq!(parse("--fast"), Err(CliError::UnknownArgument("--fast".into())));
+}
+
+#[test]
+fn every_flag_shows_up_in_the_usage() {
+	let usage = cli::usage();
+	for flag in ["--client-id", "--tick-rate", "--spectator", "--shared-screen", "--demo", "--scale", "--name", "--margin", "--redundancy", "--retransmit", "--tick-budget", "--loopback", "--replay", "--help"] {
+		assert!(usage.contains(flag), "{} is missing from the usage", flag);
+	}
+}
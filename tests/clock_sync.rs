@@ -0,0 +1,114 @@
+//! Connects to the in-process server over a simulated network with some
+//! latency, to check that the clock handshake starts predictions as far
+//! ahead as the latency and margin call for, that the game goes on
+//! smoothly from there, and that a server which never answers only holds
+//! things up for a little while.
+
+mod common;
+
+use command_networking_rs::game::{Game, CLOCK_SYNC_TIMEOUT};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SendError, SimulatedConditions, SimulatedTransport, Transport};
+use command_networking_rs::TICK_DELTA;
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const LATENCY_MS: u32 = 100;
+const MARGIN: u8 = 2;
+/// Frames per tick, at 60 frames per second.
+const FRAMES_PER_TICK: u64 = 3;
+
+/// Runs a frame: takes in whatever arrived, sends any time request due,
+/// and predicts a tick on every `FRAMES_PER_TICK`th frame. Returns whether
+/// a tick went out.
+fn frame(game: &mut Game, transport: &mut impl Transport, number: u64) -> bool {
+	NOW_US.set(number * 1_000_000 / 60);
+	transport.poll(&mut |packet| game.dispatch_packet(packet));
+	game.receive(now());
+	if let Some(request) = game.time_request(now()) {
+		send(transport, request);
+	}
+	if !number.is_multiple_of(FRAMES_PER_TICK) {
+		return false;
+	}
+	let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) else {
+		return false;
+	};
+	send(transport, packet);
+	game.tick_sent(now());
+	true
+}
+
+/// Drops everything, like a server that doesn't know about time requests.
+struct Silent;
+
+impl Transport for Silent {
+	fn send(&mut self, _bytes: &[u8]) -> Result<(), SendError> {
+		Ok(())
+	}
+	fn poll(&mut self, _receive: &mut dyn FnMut(&[u8])) {}
+}
+
+/// A game whose clock got synced with the server's over the simulated
+/// network, and the number of the next frame.
+fn synced() -> (Game, SimulatedTransport<LoopbackTransport>, u64) {
+	let conditions = SimulatedConditions { latency_ms: LATENCY_MS, jitter_ms: 10, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL).with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])]);
+	let mut transport = SimulatedTransport::new(server, conditions, 7).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(MARGIN);
+	assert!(game.is_stalled());
+
+	let mut number = 1;
+	while game.is_syncing_clock() {
+		assert!(!frame(&mut game, &mut transport, number), "predicted a tick before the clock got synced");
+		number += 1;
+		assert!(now() < CLOCK_SYNC_TIMEOUT, "the clock never got synced");
+	}
+	(game, transport, number)
+}
+
+#[test]
+fn predictions_start_as_far_ahead_as_the_latency_calls_for() {
+	let (game, _, _) = synced();
+	// Half a round trip to hear from the server, and another for our ticks
+	// to get there, on top of the margin.
+	let target = (LATENCY_MS as f32 / 1000.0 / TICK_DELTA) as i64 + MARGIN as i64;
+	let ticks_ahead = game.ticks_ahead() as i64;
+	assert!((ticks_ahead - target).abs() <= 1, "{} ticks ahead, rather than {}", ticks_ahead, target);
+	let rtt = game.rtt().smoothed().unwrap();
+	assert!((rtt - 2.0 * LATENCY_MS as f64 / 1000.0).abs() < 0.05, "round trip of {} s", rtt);
+}
+
+#[test]
+fn everything_predicted_from_there_gets_settled() {
+	let (mut game, mut transport, mut number) = synced();
+	let mut sent = 0;
+	while sent < 100 {
+		sent += frame(&mut game, &mut transport, number) as usize;
+		number += 1;
+	}
+	for _ in 0..30 {
+		frame(&mut game, &mut transport, number);
+		number += 1;
+	}
+	let stats = game.prediction_stats();
+	assert!(stats.confirmed + stats.corrected <= stats.predicted, "a tick got settled twice: {:?}", stats);
+	assert!(game.accepted_head() > 100, "the server stopped confirming at {}", game.accepted_head());
+	assert_eq!(game.desync(), None);
+}
+
+#[test]
+fn a_server_which_never_answers_only_holds_things_up_for_a_while() {
+	// Without answers, the game gives up waiting, and starts where it is.
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(MARGIN);
+	let mut number = 1;
+	while !frame(&mut game, &mut Silent, number) {
+		number += 1;
+		assert!(now() < CLOCK_SYNC_TIMEOUT + 0.1, "still waiting on time requests");
+	}
+	assert!(now() > CLOCK_SYNC_TIMEOUT);
+	assert_eq!(game.latest_tick().map(|x| x.index()), Some(0));
+}
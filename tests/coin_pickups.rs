@@ -0,0 +1,123 @@
+//! Spawns and collects coins, to check that the same coins spawn on every
+//! game, that ties go to the lowest ClientId, and that a client rolling
+//! back puts back the coins it wrongly thought were picked up, and picks
+//! up the ones it missed.
+
+mod common;
+
+use command_networking_rs::game::{Coin, Coins, Game};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+const TICKS: u64 = 80;
+
+/// Runs `ticks` ticks, with the local player doing nothing.
+fn idle(game: &mut Game, ticks: u64) {
+	for _ in 0..ticks {
+		let tick = game.predict_tick(vec![]);
+		game.advance(tick);
+	}
+}
+
+/// A coin lying on the floor, at `x`.
+fn floor_coin(x: f32) -> Coin {
+	Coin::new(x, 236.0)
+}
+
+/// Tick `index` as the server has it: player 1 runs right, through the
+/// coins `with_remote` puts down, while player 0 stands still.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	Tick::new(index, vec![CommandFrame::new(0, vec![]), CommandFrame::new(1, vec![MoveRight])])
+}
+
+/// A game with player 1 in it, and a row of coins in their way.
+fn with_remote() -> Game {
+	let mut game = Game::new(0);
+	deliver(&mut game, Packet::Join(1));
+	game.receive(0.0);
+	for x in [100.0, 150.0, 200.0] {
+		game.place_coin(floor_coin(x));
+	}
+	game
+}
+
+/// The server's version of the game, played as is.
+fn lockstep() -> Game {
+	let mut game = with_remote();
+	for index in 0..TICKS {
+		game.advance(authoritative(index));
+	}
+	game
+}
+
+#[test]
+fn every_game_spawns_the_same_coins() {
+	let mut first = Game::new(0);
+	let mut second = Game::new(0);
+	idle(&mut first, 400);
+	idle(&mut second, 400);
+	assert_eq!(first.coins().lying().len(), Coins::MAX_LYING, "coins stopped spawning");
+	assert_eq!(first.coins(), second.coins(), "two games spawned different coins");
+	assert_eq!(first.state_hash(), second.state_hash());
+}
+
+#[test]
+fn standing_on_a_coin_picks_it_up() {
+	let mut game = Game::new(0);
+	game.place_coin(floor_coin(10.0));
+	idle(&mut game, 1);
+	assert_eq!(game.coins().score(0), 1, "the coin wasn't picked up");
+	assert!(!game.coins().lying().contains(&floor_coin(10.0)), "the coin is still lying around");
+}
+
+#[test]
+fn ties_go_to_the_lowest_client_id() {
+	// Two players pressed up against each other, with a coin between them.
+	let mut game = Game::new(0);
+	game.add_local_player(1);
+	for _ in 0..20 {
+		game.queue_intents(1, vec![MoveLeft]);
+		idle(&mut game, 1);
+	}
+	idle(&mut game, 10);
+	let (left, right) = (game.players()[&0].rendered_position(), game.players()[&1].rendered_position());
+	assert_eq!(left.0 + 30.0, right.0, "the players aren't touching");
+	let coin = floor_coin(right.0 - 4.0);
+	game.place_coin(coin);
+	idle(&mut game, 1);
+	assert!(!game.coins().lying().contains(&coin), "nobody picked up the coin");
+	assert_eq!((game.coins().score(0), game.coins().score(1)), (1, 0), "the tie didn't go to the lowest ClientId");
+}
+
+#[test]
+fn rolling_back_collects_the_coins_the_client_missed() {
+	let lockstep = lockstep();
+	// Along with whatever spawned in their way.
+	assert!(lockstep.coins().score(1) >= 3, "player 1 missed some of the coins");
+
+	// A client predicting player 1 stands still, then finding out otherwise.
+	let mut client = with_remote();
+	for index in 0..TICKS + LAG {
+		if index >= LAG {
+			deliver(&mut client, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		client.receive(index as f64 * TICK_DELTA as f64);
+		client.run_tick(|_| vec![]);
+	}
+	let strategy = client.prediction_strategy().name();
+	assert!(client.mispredictions(strategy) > 0, "nothing got mispredicted, so nothing was rolled back");
+	let head = client.accepted_head();
+	assert!(head >= TICKS - LAG, "the client never caught up with the server");
+	assert_eq!(client.state_hash_at(head), lockstep.state_hash_at(head), "the client's coins came out differently after rolling back");
+}
+
+#[test]
+fn snapshots_carry_the_coins_and_scores() {
+	let lockstep = lockstep();
+	let mut spectator = Game::spectator(2);
+	deliver(&mut spectator, Packet::Snapshot(lockstep.snapshot()));
+	spectator.receive(0.0);
+	assert_eq!(spectator.coins(), lockstep.coins(), "the snapshot lost the coins");
+}
@@ -0,0 +1,43 @@
+//! What the integration tests share: a clock for transports to read, and
+//! ways of getting packets to a transport or straight into a game.
+//!
+//! Each test brings this in with `mod common;`, and not every one of them
+//! uses all of it.
+#![allow(dead_code)]
+
+use std::cell::Cell;
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{Packet, PlayerIntent};
+#[cfg(feature = "client")]
+use command_networking_rs::transport::Transport;
+
+/// How many ticks the server's answers lag behind the client's predictions.
+pub const LAG: u64 = 4;
+
+thread_local! {
+	/// The simulated time, in microseconds, so that the transports can read
+	/// it. Every test runs on a thread of its own, and so on a clock of its
+	/// own.
+	pub static NOW_US: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn now() -> f64 {
+	NOW_US.get() as f64 / 1e6
+}
+
+/// Sends `packet` out over `transport`, as the game would.
+#[cfg(feature = "client")]
+pub fn send(transport: &mut impl Transport, packet: Packet<PlayerIntent>) {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	transport.send(writer.as_slice()).unwrap();
+}
+
+/// Hands `packet` to `game`, as though it just came in.
+pub fn deliver(game: &mut Game, packet: Packet<PlayerIntent>) {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	game.dispatch_packet(writer.as_slice());
+}
@@ -0,0 +1,72 @@
+//! Checks the conformance vectors in `conformance/vectors.json`, which the
+//! JS host's tests go through as well, against the encoder and decoder:
+//! every valid vector decodes into its fields and encodes back into its
+//! bytes, every invalid one fails the way it says, and none are missing
+//! or out of date. Then makes sure a vector gone wrong doesn't get past.
+//!
+//!     cargo test --features serde --test conformance_vectors
+
+use serde_json::Value;
+use command_networking_rs::conformance::{check, VECTORS_PATH};
+
+fn suite() -> Value {
+	let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), VECTORS_PATH);
+	let json = std::fs::read_to_string(&path).unwrap_or_else(|x| panic!("couldn't read {}: {}", path, x));
+	serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn every_vector_holds() {
+	if let Err(error) = check(&suite()) {
+		panic!("{}", error);
+	}
+}
+
+#[test]
+fn every_type_and_packet_has_a_vector() {
+	let suite = suite();
+	let vectors = suite["vectors"].as_array().unwrap();
+	let kinds = |ty: &str| vectors.iter().filter(|x| x["type"] == ty).count();
+	for ty in ["PlayerIntent", "CommandFrame", "Tick", "Packet", "Batch", "StateSummary", "TickEvents"] {
+		assert!(kinds(ty) > 0, "no vectors for {}", ty);
+	}
+	let tag_count = vectors
+		.iter()
+		.filter_map(|x| x["fields"]["tag"].as_u64())
+		.collect::<std::collections::BTreeSet<_>>()
+		.len();
+	assert_eq!(tag_count, 17, "not every kind of packet has a vector");
+}
+
+#[test]
+fn a_vector_gone_wrong_gets_caught() {
+	// A byte off anywhere gets caught, as does a field, an error, or a
+	// vector gone missing.
+	let suite = suite();
+	let vectors = suite["vectors"].as_array().unwrap();
+	let tampered = |edit: &dyn Fn(&mut Value)| {
+		let mut suite = suite.clone();
+		edit(&mut suite);
+		check(&suite)
+	};
+	let flip = |x: &mut Value| {
+		let bytes = x["bytes"].as_str().unwrap();
+		x["bytes"] = Value::from(format!("{}{}", &bytes[..bytes.len() - 1], if bytes.ends_with('0') { '1' } else { '0' }));
+	};
+	let index = |name: &str| vectors.iter().position(|x| x["name"] == name).unwrap();
+	let errors = [
+		tampered(&|x| flip(&mut x["vectors"][index("packet_received")])),
+		tampered(&|x| flip(&mut x["vectors"][index("tick_two_players")])),
+		tampered(&|x| x["vectors"][index("packet_chat")]["fields"]["fields"]["text"] = Value::from("gl")),
+		tampered(&|x| x["vectors"][index("packet_wrong_magic")]["error"] = Value::from("UnsupportedVersion")),
+		tampered(&|x| x["vectors"][index("packet_trailing_bytes")]["error"] = Value::Null),
+		tampered(&|x| {
+			x["vectors"].as_array_mut().unwrap().pop();
+		}),
+		tampered(&|x| x["negotiation"][1]["batched"] = Value::from(true)),
+		tampered(&|x| x["hasher"]["id"] = Value::from(1)),
+	];
+	for error in errors {
+		error.expect_err("a tampered vector got past");
+	}
+}
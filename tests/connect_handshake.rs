@@ -0,0 +1,173 @@
+//! Connects to the in-process server without picking a ClientId, to check
+//! that nothing gets predicted until the server hands one out, and that the
+//! local player is in the game right after. Asking for a ClientId that's
+//! taken gets us turned away instead, a server that doesn't answer gets
+//! asked again, and a spectator only joins once told to.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::game::{Game, CONNECT_RETRY_INTERVAL};
+use command_networking_rs::protocol::{ConnectRequest, PlayerIntent, RejectReason};
+use command_networking_rs::transport::{LoopbackTransport, SendError, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+/// Who the server hands out to the client.
+const LOCAL: u16 = 4;
+const REMOTE: u16 = 5;
+const MARGIN: u8 = 2;
+/// Frames per tick, at 60 frames per second.
+const FRAMES_PER_TICK: u64 = 3;
+
+thread_local! {
+	/// Frames run so far, over every connection; time only ever goes forward.
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// What went out over a frame.
+#[derive(Default)]
+struct Sent {
+	connect_requests: usize,
+	ticks: usize,
+}
+
+/// Runs a frame the way the client does, predicting a tick on every
+/// `FRAMES_PER_TICK`th one.
+fn frame(game: &mut Game, transport: &mut impl Transport) -> Sent {
+	let number = FRAMES.get() + 1;
+	FRAMES.set(number);
+	NOW_US.set(number * 1_000_000 / 60);
+	transport.poll(&mut |packet| game.dispatch_packet(packet));
+	game.receive(now());
+
+	let mut sent = Sent::default();
+	if let Some(request) = game.connect_request(now()) {
+		send(transport, request);
+		sent.connect_requests += 1;
+	}
+	let mut messages = game.take_introductions();
+	messages.extend(game.time_request(now()));
+	for packet in messages {
+		send(transport, packet);
+	}
+	if !number.is_multiple_of(FRAMES_PER_TICK) {
+		return sent;
+	}
+	if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveLeft]) {
+		send(transport, packet);
+		game.tick_sent(now());
+		sent.ticks += 1;
+	}
+	sent
+}
+
+fn server() -> SimulatedTransport<LoopbackTransport> {
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![])]);
+	let conditions = SimulatedConditions { latency_ms: 50, jitter_ms: 5, ..Default::default() };
+	SimulatedTransport::new(server, conditions, 7).with_clock(now)
+}
+
+fn request(requested_id: Option<u16>) -> ConnectRequest {
+	ConnectRequest { requested_id, name: Some("Ada".into()) }
+}
+
+/// Drops everything, like a server that's yet to come up.
+struct Silent;
+
+impl Transport for Silent {
+	fn send(&mut self, _bytes: &[u8]) -> Result<(), SendError> {
+		Ok(())
+	}
+	fn poll(&mut self, _receive: &mut dyn FnMut(&[u8])) {}
+}
+
+#[test]
+fn the_server_lets_clients_in_with_an_id() {
+	let mut transport = server();
+	let mut game = Game::connecting(request(None), false);
+	game.sync_clock(MARGIN);
+	let mut requests = 0;
+	let mut waited = 0;
+	while game.is_connecting() {
+		assert!(game.local_players().is_empty(), "had a local player before being let in");
+		let sent = frame(&mut game, &mut transport);
+		assert_eq!(sent.ticks, 0, "predicted a tick before being let in");
+		requests += sent.connect_requests;
+		waited += 1;
+		assert!(waited < 60, "the server never let us in");
+	}
+	assert_eq!(requests, 1, "asked to be let in again before the answer could arrive");
+	assert_eq!(game.client_id(), LOCAL);
+	assert_eq!(game.local_players(), [LOCAL]);
+	assert_eq!(game.name(LOCAL), Some("Ada"));
+}
+
+#[test]
+fn clients_let_in_play_along_with_the_server() {
+	let mut transport = server();
+	let mut game = Game::connecting(request(None), false);
+	game.sync_clock(MARGIN);
+	while game.is_connecting() {
+		frame(&mut game, &mut transport);
+	}
+	let mut ticks = 0;
+	let mut spawn = None;
+	for _ in 0..180 {
+		ticks += frame(&mut game, &mut transport).ticks;
+		if let Some(player) = game.players().get(&LOCAL) {
+			spawn.get_or_insert(player.position().0);
+		}
+	}
+	let spawn = spawn.expect("the local player never made it into the game");
+	assert!(ticks > 0 && game.accepted_head() > 0, "the server never confirmed any ticks");
+	assert_eq!(game.desync(), None, "the local player joined on a different tick than the server has them");
+	assert!(game.players()[&LOCAL].position().0 < spawn, "the local player didn't go anywhere");
+}
+
+#[test]
+fn asking_for_a_taken_id_gets_clients_turned_away() {
+	// The server's own scripted client already plays as `REMOTE`.
+	let mut transport = server();
+	let mut game = Game::connecting(request(Some(REMOTE)), false);
+	for _ in 0..60 {
+		assert_eq!(frame(&mut game, &mut transport).ticks, 0, "predicted a tick while turned away");
+	}
+	assert_eq!(game.rejection(), Some(RejectReason::IdTaken));
+	assert!(!game.is_connecting() && game.is_stalled());
+	assert!(game.connect_request(now()).is_none(), "asked again after being turned away");
+	assert!(game.local_players().is_empty());
+}
+
+#[test]
+fn a_server_which_doesnt_answer_gets_asked_again() {
+	let mut game = Game::connecting(request(None), false);
+	let mut requests = 0;
+	let start = now();
+	while now() - start < CONNECT_RETRY_INTERVAL * 2.5 {
+		requests += frame(&mut game, &mut Silent).connect_requests;
+	}
+	assert_eq!(requests, 3, "a server which doesn't answer got asked {} times", requests);
+}
+
+#[test]
+fn spectators_only_play_once_joining() {
+	let mut transport = server();
+	let mut game = Game::connecting(request(None), true);
+	while game.is_connecting() {
+		frame(&mut game, &mut transport);
+	}
+	assert!(game.is_spectating(), "a spectator joined without being told to");
+	let mut ticks = 0;
+	for _ in 0..30 {
+		ticks += frame(&mut game, &mut transport).ticks;
+	}
+	assert_eq!(ticks, 0, "a spectator predicted ticks");
+	game.join();
+	for _ in 0..30 {
+		ticks += frame(&mut game, &mut transport).ticks;
+	}
+	assert!(ticks > 0, "the spectator didn't start playing after joining");
+}
@@ -0,0 +1,196 @@
+//! Walks the connection state table, and takes a client through it against
+//! the in-process server: let in, syncing the clock, playing, desyncing,
+//! losing the server and getting it back, and a snapshot putting things
+//! right. Changes the table doesn't have are ignored, and so is everything
+//! once the server turned us away.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::game::{ConnectionState, Game};
+use command_networking_rs::hash::Hash;
+use command_networking_rs::protocol::{ConnectRequest, Packet, StateHash};
+use command_networking_rs::transport::{LoopbackTransport, Transport, TransportEvent};
+
+use ConnectionState::*;
+use common::{deliver, now, NOW_US, send};
+
+const STATES: [ConnectionState; 6] = [Connecting, Syncing, Connected, Resyncing, Desynced, Disconnected];
+/// Every change that's allowed, on top of staying put.
+const LEGAL: [(ConnectionState, ConnectionState); 18] = [
+	(Connecting, Syncing), (Connecting, Connected), (Connecting, Disconnected),
+	(Syncing, Connected), (Syncing, Resyncing), (Syncing, Disconnected),
+	(Connected, Syncing), (Connected, Resyncing), (Connected, Desynced), (Connected, Disconnected),
+	(Resyncing, Connected), (Resyncing, Desynced), (Resyncing, Disconnected),
+	(Desynced, Connected), (Desynced, Resyncing), (Desynced, Disconnected),
+	(Disconnected, Connected), (Disconnected, Resyncing),
+];
+const LOCAL: u16 = 3;
+const REMOTE: u16 = 4;
+const MARGIN: u8 = 2;
+
+thread_local! {
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Runs a frame the way the client does, predicting a tick on every third
+/// one, at 60 frames per second. Returns whether a tick went out.
+fn frame(game: &mut Game, transport: &mut impl Transport) -> bool {
+	let number = FRAMES.get() + 1;
+	FRAMES.set(number);
+	NOW_US.set(number * 1_000_000 / 60);
+	transport.poll(&mut |packet| game.dispatch_packet(packet));
+	game.receive(now());
+
+	let mut messages: Vec<_> = game.connect_request(now()).into_iter().collect();
+	messages.extend(game.time_request(now()));
+	for packet in messages {
+		send(transport, packet);
+	}
+	if !number.is_multiple_of(3) {
+		return false;
+	}
+	let Some(packet) = game.run_tick(|_| vec![]) else { return false };
+	send(transport, packet);
+	game.tick_sent(now());
+	true
+}
+
+/// Runs frames until the state stops being `state`, returning the one
+/// it changed to.
+fn leave(game: &mut Game, transport: &mut impl Transport, state: ConnectionState) -> ConnectionState {
+	for _ in 0..120 {
+		frame(game, transport);
+		if game.connection_state() != state {
+			return game.connection_state();
+		}
+	}
+	panic!("stuck in {:?}", state);
+}
+
+/// A game that connected to `transport`'s server, and has been playing
+/// for a second since.
+fn connected(transport: &mut LoopbackTransport) -> Game {
+	let mut game = Game::connecting(ConnectRequest { requested_id: None, name: None }, false);
+	game.sync_clock(MARGIN);
+	assert_eq!(leave(&mut game, transport, Connecting), Syncing);
+	assert_eq!(leave(&mut game, transport, Syncing), Connected);
+	for _ in 0..60 {
+		frame(&mut game, transport);
+	}
+	game
+}
+
+/// Has the server disagree with `game` about the last tick it confirmed.
+fn desync(game: &mut Game, transport: &mut LoopbackTransport) {
+	let index = game.accepted_head();
+	deliver(game, Packet::StateHash(StateHash { index, hash: Hash::default() }));
+	frame(game, transport);
+	assert_eq!(game.connection_state(), Desynced);
+}
+
+/// Drops the connection, and runs until the server is back and the clock
+/// synced again.
+fn lose_server(game: &mut Game, transport: &mut LoopbackTransport) {
+	game.handle_transport_event(TransportEvent::Disconnected);
+	assert_eq!(leave(game, transport, Disconnected), Resyncing);
+	while game.is_syncing_clock() {
+		frame(game, transport);
+	}
+}
+
+fn server() -> LoopbackTransport {
+	LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![])])
+}
+
+#[test]
+fn only_the_listed_changes_are_allowed() {
+	for from in STATES {
+		for to in STATES {
+			let legal = from == to || LEGAL.contains(&(from, to));
+			assert_eq!(from.can_become(to), legal, "{:?} to {:?}", from, to);
+		}
+	}
+}
+
+#[test]
+fn connecting_then_syncing_then_connected() {
+	let mut transport = server();
+	let mut game = Game::connecting(ConnectRequest { requested_id: None, name: None }, false);
+	game.sync_clock(MARGIN);
+	assert_eq!(game.connection_state(), Connecting);
+	game.pause();
+	assert!(!game.is_paused(), "paused before being let in");
+	assert_eq!(leave(&mut game, &mut transport, Connecting), Syncing);
+	assert!(game.is_stalled(), "predicted before the clock got synced");
+	assert_eq!(leave(&mut game, &mut transport, Syncing), Connected);
+	let mut ticks = 0;
+	for _ in 0..60 {
+		ticks += frame(&mut game, &mut transport) as u32;
+	}
+	assert!(ticks > 0 && game.accepted_head() > 0, "never got going");
+}
+
+#[test]
+fn desynced_games_keep_predicting() {
+	let mut transport = server();
+	let mut game = connected(&mut transport);
+	// The server's take on a tick we confirmed, had it simulated it some
+	// other way.
+	desync(&mut game, &mut transport);
+	assert!(!game.is_resync_requested(), "desyncing asked for a snapshot");
+	let sent = (0..30).filter(|_| frame(&mut game, &mut transport)).count();
+	assert!(sent > 0, "stopped predicting once desynced");
+}
+
+#[test]
+fn losing_the_server_means_resyncing_once_its_back() {
+	let mut transport = server();
+	let mut game = connected(&mut transport);
+	desync(&mut game, &mut transport);
+	game.handle_transport_event(TransportEvent::Disconnected);
+	assert_eq!(game.connection_state(), Disconnected);
+	assert!(game.is_stalled());
+	assert!(game.say("anyone there?").is_none(), "chat went out while disconnected");
+	// Whatever the server sent while gone only tells us it's back.
+	assert_eq!(leave(&mut game, &mut transport, Disconnected), Resyncing);
+	assert!(game.is_resync_requested(), "came back without asking for a snapshot");
+	assert!(game.is_syncing_clock() && game.is_stalled(), "predicted off a stale history");
+	while game.is_syncing_clock() {
+		frame(&mut game, &mut transport);
+	}
+	assert_eq!(game.connection_state(), Resyncing, "got back in step without a snapshot");
+}
+
+#[test]
+fn a_snapshot_connects_a_resyncing_game() {
+	let mut transport = server();
+	let mut game = connected(&mut transport);
+	desync(&mut game, &mut transport);
+	lose_server(&mut game, &mut transport);
+	// Our own world stands in for the server's.
+	let snapshot = game.snapshot();
+	deliver(&mut game, Packet::Snapshot(snapshot));
+	frame(&mut game, &mut transport);
+	assert_eq!(game.connection_state(), Connected);
+	assert!(!game.is_resync_requested() && game.desync().is_none());
+}
+
+#[test]
+fn being_turned_away_is_for_good() {
+	// Asking for the scripted client's ClientId gets us turned away.
+	let mut transport = server();
+	let mut game = Game::connecting(ConnectRequest { requested_id: Some(REMOTE), name: None }, false);
+	assert_eq!(leave(&mut game, &mut transport, Connecting), Disconnected);
+	assert!(game.rejection().is_some());
+	game.handle_transport_event(TransportEvent::Connected);
+	let snapshot = game.snapshot();
+	deliver(&mut game, Packet::Snapshot(snapshot));
+	for _ in 0..30 {
+		assert!(!frame(&mut game, &mut transport), "predicted once turned away");
+	}
+	assert_eq!(game.connection_state(), Disconnected, "came back after being turned away");
+}
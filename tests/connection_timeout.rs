@@ -0,0 +1,127 @@
+//! Drives a client whose server goes quiet mid-game: once the accepted
+//! head hasn't moved for `CONNECTION_TIMEOUT`, it has to give the
+//! connection up, stop predicting and sending, and when the server's
+//! ticks come back, ask for a snapshot rather than carrying on from the
+//! history it predicted into the void. The snapshot gets it playing again.
+
+mod common;
+
+use command_networking_rs::game::{ConnectionState, Game, CONNECTION_TIMEOUT};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, SnapshotReason, Tick};
+use command_networking_rs::TICK_DELTA;
+use common::{deliver, LAG};
+
+const LOCAL: u16 = 0;
+/// A client and the server it plays against, a tick per frame.
+struct Session {
+	client: Game,
+	server: Game,
+	frames: u64,
+}
+
+impl Session {
+	fn new() -> Self {
+		Self { client: Game::new(LOCAL), server: Game::new(LOCAL), frames: 0 }
+	}
+
+	fn now(&self) -> f64 {
+		self.frames as f64 * TICK_DELTA as f64
+	}
+
+	/// Runs a frame, with the server answering `LAG` ticks late if it's
+	/// `answering`. Returns the packet the client sent, if any.
+	fn frame(&mut self, answering: bool) -> Option<Packet<PlayerIntent>> {
+		self.frames += 1;
+		if self.frames > LAG {
+			let tick = Tick::new(self.frames - LAG - 1, vec![CommandFrame::new(LOCAL, vec![PlayerIntent::MoveRight])]);
+			self.server.advance(tick.clone());
+			if answering {
+				deliver(&mut self.client, Packet::AuthoritativeTick(tick));
+			}
+		}
+		self.client.receive(self.now());
+		// There's no answer to the clock being synced; it goes on what it
+		// knows once that times out.
+		self.client.time_request(self.now());
+		self.client.run_tick(|_| vec![PlayerIntent::MoveRight])
+	}
+}
+
+/// A session that went on for 40 frames, then the server went quiet until
+/// the client gave up on it. Returns the client's accepted head at that
+/// point, and how long it waited after it last moved.
+fn gone_quiet() -> (Session, u64, f64) {
+	let mut session = Session::new();
+	for _ in 0..40 {
+		assert!(session.frame(true).is_some(), "the client stopped sending while connected");
+	}
+	assert_eq!(session.client.connection_state(), ConnectionState::Connected);
+	assert!(session.client.accepted_head() >= 40 - LAG - 4, "only got to tick {}", session.client.accepted_head());
+
+	// What's still buffered of the server gets played back, and the
+	// timeout only starts from there.
+	let (mut head, mut quiet) = (session.client.accepted_head(), session.now());
+	while session.client.connection_state() == ConnectionState::Connected {
+		session.frame(false);
+		if session.client.accepted_head() != head {
+			(head, quiet) = (session.client.accepted_head(), session.now());
+		}
+		assert!(session.now() - quiet < CONNECTION_TIMEOUT + 1.0, "still connected after {:.2}s", session.now() - quiet);
+	}
+	let waited = session.now() - quiet;
+	(session, head, waited)
+}
+
+#[test]
+fn a_quiet_server_disconnects_once_the_timeout_is_up() {
+	// The client keeps predicting until it's waited out the timeout, and
+	// then, not for much longer.
+	let (session, _, waited) = gone_quiet();
+	assert_eq!(session.client.connection_state(), ConnectionState::Disconnected);
+	assert!(waited > CONNECTION_TIMEOUT, "gave up after {:.2}s", waited);
+}
+
+#[test]
+fn disconnected_the_world_stays_put_and_nothing_goes_out() {
+	let (mut session, _, _) = gone_quiet();
+	let latest = session.client.latest_tick().map(|x| x.index());
+	let hash = session.client.state_hash();
+	for _ in 0..20 {
+		assert!(session.frame(false).is_none(), "sent a tick while disconnected");
+		assert!(session.client.snapshot_request(session.now()).is_none());
+	}
+	assert_eq!(session.client.latest_tick().map(|x| x.index()), latest, "kept predicting while disconnected");
+	assert_eq!(session.client.state_hash(), hash);
+}
+
+#[test]
+fn a_server_coming_back_gets_asked_for_a_snapshot() {
+	// Its ticks get the client to ask for a snapshot, rather than being
+	// matched against predictions long gone wrong.
+	let (mut session, _, _) = gone_quiet();
+	session.frame(true);
+	assert_eq!(session.client.connection_state(), ConnectionState::Resyncing);
+	assert!(session.client.is_resync_requested());
+	let Some(Packet::SnapshotRequest(request)) = session.client.snapshot_request(session.now()) else {
+		panic!("no snapshot request went out on reconnecting");
+	};
+	assert_eq!(request.reason, SnapshotReason::Reconnect);
+	assert_eq!(request.sender, LOCAL);
+}
+
+#[test]
+fn the_snapshot_gets_the_client_playing_again() {
+	let (mut session, head, _) = gone_quiet();
+	session.frame(true);
+	deliver(&mut session.client, Packet::Snapshot(session.server.snapshot()));
+	let resumed = session.now();
+	while session.client.connection_state() != ConnectionState::Connected {
+		session.frame(true);
+		assert!(session.now() - resumed < 5.0, "stuck {:?} after the snapshot", session.client.connection_state());
+	}
+	assert!(!session.client.is_resync_requested());
+	for _ in 0..20 {
+		assert!(session.frame(true).is_some(), "the client didn't get back to sending");
+	}
+	assert!(session.client.accepted_head() > head + 20, "stuck at tick {}", session.client.accepted_head());
+}
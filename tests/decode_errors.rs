@@ -0,0 +1,196 @@
+//! Gets every `DecodeError` variant out of the decoder, through the same
+//! entry points the transports use, checking that each malformed input is
+//! reported for what it is rather than as some other failure.
+
+use command_networking_rs::{ByteWriter, DecodeError, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::hash::{Hasher, TickHasher};
+use command_networking_rs::protocol::{
+	CommandFrame, EncodedTick, Packet, PlayerIntent, Tick, TickDecoder, TickEncoder,
+	MAX_FRAMES_PER_TICK, MAX_PACKET_SIZE, PROTOCOL_MAGIC, PROTOCOL_VERSION
+};
+use PlayerIntent::*;
+
+type P = Packet<PlayerIntent>;
+
+/// The start of a packet with `tag`, for the rest to be written by hand.
+fn header(tag: u8) -> ByteWriter {
+	let mut writer = ByteWriter::new();
+	writer.write_bytes(&PROTOCOL_MAGIC);
+	writer.write_u8(PROTOCOL_VERSION);
+	writer.write_u8(Hasher::ID);
+	writer.write_u8(tag);
+	writer
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn decode(bytes: &[u8]) -> DecodeError {
+	match P::decode(bytes) {
+		Ok(packet) => panic!("decoded as {}", packet.tag()),
+		Err(error) => error,
+	}
+}
+
+#[test]
+fn unexpected_eof() {
+	// A receipt without the index it's for.
+	let writer = header(P::TAG_RECEIVED);
+	assert_eq!(decode(writer.as_slice()), DecodeError::UnexpectedEof { needed: 1, remaining: 0 });
+	// Not even a whole header.
+	assert_eq!(decode(&PROTOCOL_MAGIC[..1]), DecodeError::UnexpectedEof { needed: 2, remaining: 1 });
+}
+
+#[test]
+fn invalid_tag() {
+	let writer = header(0xff);
+	assert_eq!(decode(writer.as_slice()), DecodeError::InvalidTag { ty: "Packet", tag: 0xff });
+}
+
+#[test]
+fn length_out_of_bounds() {
+	// Five frames claimed, and nothing after.
+	let mut writer = header(P::TAG_AUTHORITATIVE_TICK);
+	writer.write_varint(0);
+	writer.write_varint(5);
+	assert_eq!(decode(writer.as_slice()), DecodeError::LengthOutOfBounds { len: 5, remaining: 0 });
+}
+
+#[test]
+fn limit_exceeded() {
+	let mut writer = header(P::TAG_AUTHORITATIVE_TICK);
+	writer.write_varint(0);
+	writer.write_varint(MAX_FRAMES_PER_TICK as u64 + 1);
+	assert_eq!(decode(writer.as_slice()), DecodeError::LimitExceeded {
+		what: "command frames",
+		len: MAX_FRAMES_PER_TICK + 1,
+		max: MAX_FRAMES_PER_TICK
+	});
+}
+
+#[test]
+fn invalid_utf8() {
+	let mut writer = header(P::TAG_CHAT);
+	3u16.to_bytes(&mut writer).unwrap();
+	writer.write_varint(2);
+	writer.write_bytes(&[0xc3, 0x28]);
+	assert_eq!(decode(writer.as_slice()), DecodeError::InvalidUtf8 { what: "chat message" });
+}
+
+#[test]
+fn packet_too_large() {
+	let mut bytes = encode(&P::Received { index: 1 });
+	bytes.resize(MAX_PACKET_SIZE + 1, 0);
+	assert_eq!(decode(&bytes), DecodeError::PacketTooLarge { size: MAX_PACKET_SIZE + 1, max: MAX_PACKET_SIZE });
+}
+
+#[test]
+fn varint_overflow() {
+	// The tenth byte carrying more than the one bit left of a u64.
+	let mut writer = header(P::TAG_RECEIVED);
+	writer.write_bytes(&[0xff; 9]);
+	writer.write_u8(0x02);
+	assert_eq!(decode(writer.as_slice()), DecodeError::VarintOverflow);
+	// Or the tenth byte saying there's more to come.
+	let mut writer = header(P::TAG_RECEIVED);
+	writer.write_bytes(&[0x80; 9]);
+	writer.write_bytes(&[0x81, 0x00]);
+	assert_eq!(decode(writer.as_slice()), DecodeError::VarintOverflow);
+	// The largest one there is still makes it.
+	let mut writer = header(P::TAG_RECEIVED);
+	writer.write_varint(u64::MAX);
+	assert!(matches!(P::decode(writer.as_slice()), Ok(Packet::Received { index: u64::MAX })));
+}
+
+/// A delta of tick 1 against tick 0, as the sender has them.
+fn delta(first: &Tick<PlayerIntent>, second: &Tick<PlayerIntent>) -> EncodedTick<PlayerIntent> {
+	let mut encoder = TickEncoder::default();
+	encoder.encode(first);
+	encoder.acknowledge(first.index());
+	let delta = encoder.encode(second);
+	assert!(matches!(delta, EncodedTick::Delta(_)));
+	delta
+}
+
+#[test]
+fn stale_baseline() {
+	let first = Tick::new(0, vec![CommandFrame::new(1, vec![MoveLeft])]);
+	let second = Tick::new(1, vec![CommandFrame::new(1, vec![MoveLeft])]);
+	let mut decoder = TickDecoder::default();
+	assert!(matches!(
+		decoder.decode(delta(&first, &second)),
+		Err(DecodeError::StaleBaseline { expected: 0, found: None })
+	));
+}
+
+#[test]
+fn missing_baseline_frame() {
+	// Player 2's frame is left out as unchanged, but the baseline we hold
+	// doesn't have one.
+	let first = Tick::new(0, vec![CommandFrame::new(1, vec![MoveLeft]), CommandFrame::new(2, vec![JumpStart])]);
+	let second = Tick::new(1, vec![CommandFrame::new(1, vec![MoveRight]), CommandFrame::new(2, vec![JumpStart])]);
+	let mut decoder = TickDecoder::default();
+	decoder.decode(EncodedTick::Keyframe(Tick::new(0, vec![CommandFrame::new(1, vec![MoveLeft])]))).unwrap();
+	assert!(matches!(
+		decoder.decode(delta(&first, &second)),
+		Err(DecodeError::MissingBaselineFrame { owner: 2 })
+	));
+}
+
+#[test]
+fn unknown_frame_owner() {
+	let mut game = Game::new(1);
+	game.run_tick(|_| vec![MoveRight]);
+	let tick = Tick::new(0, vec![CommandFrame::new(1, vec![MoveRight]), CommandFrame::new(9, vec![JumpStart])]);
+	game.dispatch_packet(&encode(&P::AuthoritativeTick(tick)));
+	game.receive(0.0);
+	assert_eq!(game.last_decode_error(), Some(&DecodeError::UnknownFrameOwner { owner: 9, index: 0 }));
+}
+
+#[test]
+fn wrong_magic() {
+	let mut bytes = encode(&P::Received { index: 1 });
+	bytes[..2].copy_from_slice(b"XY");
+	assert_eq!(decode(&bytes), DecodeError::WrongMagic { found: *b"XY" });
+}
+
+#[test]
+fn unsupported_version() {
+	let mut bytes = encode(&P::Received { index: 1 });
+	bytes[2] = PROTOCOL_VERSION + 1;
+	assert_eq!(decode(&bytes), DecodeError::UnsupportedVersion { got: PROTOCOL_VERSION + 1, expected: PROTOCOL_VERSION });
+}
+
+#[test]
+fn unsupported_hasher() {
+	let mut bytes = encode(&P::Received { index: 1 });
+	bytes[3] = Hasher::ID ^ 0x80;
+	assert_eq!(decode(&bytes), DecodeError::UnsupportedHasher { got: Hasher::ID ^ 0x80, expected: Hasher::ID });
+}
+
+#[test]
+fn hash_mismatch() {
+	let tick = Tick::new(4, vec![CommandFrame::new(1, vec![MoveLeft])]);
+	let mut bytes = encode(&P::AuthoritativeTick(tick));
+	// The hash comes last.
+	*bytes.last_mut().unwrap() ^= 1;
+	assert_eq!(decode(&bytes), DecodeError::HashMismatch { index: 4 });
+
+	// A delta resolving to something other than what was sent.
+	let first = Tick::new(0, vec![CommandFrame::new(1, vec![MoveLeft])]);
+	let second = Tick::new(1, vec![CommandFrame::new(1, vec![MoveLeft])]);
+	let mut decoder = TickDecoder::default();
+	decoder.decode(EncodedTick::Keyframe(Tick::new(0, vec![CommandFrame::new(1, vec![MoveRight])]))).unwrap();
+	assert!(matches!(decoder.decode(delta(&first, &second)), Err(DecodeError::HashMismatch { index: 1 })));
+}
+
+#[test]
+fn trailing_bytes() {
+	let mut bytes = encode(&P::Received { index: 1 });
+	bytes.extend([0, 0, 0]);
+	assert_eq!(decode(&bytes), DecodeError::TrailingBytes { count: 3 });
+}
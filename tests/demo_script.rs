@@ -0,0 +1,78 @@
+//! Plays the demo the client falls back on while nobody's at the keys, the
+//! way it does: a tick at a time, predicted, sent over the wire and
+//! simulated. Checks the player actually patrols, and that playing the
+//! same script twice ends up in the very same world.
+
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::demo::{DemoScript, PATROL};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{PlayerIntent, Tick};
+use command_networking_rs::scalar::Scalar;
+
+const LOCAL: u16 = 0;
+
+#[cfg(not(feature = "fixed-point"))]
+fn to_f32(value: Scalar) -> f32 {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+fn to_f32(value: Scalar) -> f32 {
+	value.to_f32()
+}
+
+/// Plays `passes` times through `script`, each tick read back off the
+/// bytes it'd be sent as. Returns the game, and how far left and right,
+/// and how high the player got.
+fn play(script: &DemoScript, passes: u64) -> (Game, (f32, f32, f32)) {
+	let mut game = Game::new(LOCAL);
+	let (spawn_x, spawn_y) = game.players()[&LOCAL].position();
+	let mut reach = (to_f32(spawn_x), to_f32(spawn_x), to_f32(spawn_y));
+	for tick in 0..script.len() * passes {
+		let predicted = game.predict_tick(script.intents(tick).to_vec());
+		let mut writer = ByteWriter::new();
+		predicted.to_bytes(&mut writer).unwrap();
+		let received = Tick::from_bytes(&mut ByteReader::new(writer.as_slice())).unwrap();
+		assert_eq!(received, predicted, "tick {} changed on the wire", tick);
+		game.advance(received);
+
+		let (x, y) = game.players()[&LOCAL].position();
+		let (x, y) = (to_f32(x), to_f32(y));
+		reach = (reach.0.min(x), reach.1.max(x), reach.2.min(y));
+	}
+	(game, reach)
+}
+
+#[test]
+fn entries_are_held_and_start_over_once_through() {
+	let script = DemoScript::new(vec![
+		(2, vec![PlayerIntent::MoveRight]),
+		(0, vec![PlayerIntent::JumpStart]),
+		(1, vec![]),
+	]);
+	assert_eq!(script.len(), 3);
+	assert_eq!(script.steps().len(), 2, "an entry lasting no ticks was kept");
+	let held: Vec<_> = (0..6).map(|x| script.intents(x)).collect();
+	let right: &[PlayerIntent] = &[PlayerIntent::MoveRight];
+	assert_eq!(held, [right, right, &[], right, right, &[]]);
+	assert!(DemoScript::new(vec![]).intents(7).is_empty());
+}
+
+#[test]
+fn the_player_patrols_and_jumps() {
+	let patrol = DemoScript::default();
+	assert_eq!(patrol.len(), PATROL.iter().map(|(ticks, _)| *ticks as u64).sum::<u64>());
+	let (_, (left, right, top)) = play(&patrol, 2);
+	let (spawn_x, spawn_y) = Game::new(LOCAL).players()[&LOCAL].position();
+	let (spawn_x, spawn_y) = (to_f32(spawn_x), to_f32(spawn_y));
+	assert!(right > spawn_x + 100.0 && left < right - 100.0, "barely moved, from {} to {}", left, right);
+	assert!(top < spawn_y - 20.0, "never jumped, got up to {}", top);
+}
+
+#[test]
+fn the_demo_plays_out_the_same_every_time() {
+	let patrol = DemoScript::default();
+	let (game, _) = play(&patrol, 2);
+	let (again, _) = play(&patrol, 2);
+	assert_eq!(again.state_hash(), game.state_hash(), "the same demo played out differently");
+}
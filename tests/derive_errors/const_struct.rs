@@ -0,0 +1,6 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+pub struct Fixed<const N: usize> {
+	pub bytes: [u8; N],
+}
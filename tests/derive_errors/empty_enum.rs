@@ -0,0 +1,5 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+#[repr(u8)]
+pub enum Never {}
@@ -0,0 +1,7 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+pub enum Direction {
+	Left,
+	Right,
+}
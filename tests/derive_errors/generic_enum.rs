@@ -0,0 +1,8 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+#[repr(u8)]
+pub enum Tagged<T> {
+	Nothing,
+	Marker(std::marker::PhantomData<T>),
+}
@@ -0,0 +1,6 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+pub struct Borrowed<'a> {
+	pub name: &'a str,
+}
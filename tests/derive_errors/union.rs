@@ -0,0 +1,8 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+#[repr(C)]
+pub union Bits {
+	pub int: u32,
+	pub float: f32,
+}
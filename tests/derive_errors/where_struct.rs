@@ -0,0 +1,6 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+pub struct Wrapper<T> where T: Clone {
+	pub value: T,
+}
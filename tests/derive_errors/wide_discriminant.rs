@@ -0,0 +1,9 @@
+use command_networking_derive::NetType;
+
+#[derive(NetType)]
+#[repr(u8)]
+pub enum Code {
+	Low = 254,
+	High,
+	Higher,
+}
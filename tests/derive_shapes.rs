@@ -0,0 +1,158 @@
+//! What `#[derive(NetType)]` makes of the shapes it supports, and that it
+//! turns down those it doesn't with a message saying why, rather than
+//! generating code that fails to compile somewhere else.
+//!
+//! Each of `tests/derive_errors/*.rs` is compiled on its own against the
+//! derive, and has to fail with the error in the `.stderr` file next to it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+// The derive refers to these through the crate root, as it does in the
+// crate itself.
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+use command_networking_rs::game::{Coin, Coins};
+
+#[derive(NetType, PartialEq, Debug)]
+struct Named {
+	id: u16,
+	x: f32,
+	y: f32,
+	label: String,
+}
+
+#[derive(NetType, PartialEq, Debug)]
+struct Tuple(u8, i64);
+
+#[derive(NetType, PartialEq, Debug)]
+struct Unit;
+
+#[derive(NetType, PartialEq, Debug)]
+struct Generic<T: Clone> {
+	values: Vec<T>,
+}
+
+#[derive(NetType, PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+enum Tagged {
+	First,
+	Skipped = 5,
+	Next,
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn round_trip<T: NetType + PartialEq + std::fmt::Debug>(value: &T) -> Vec<u8> {
+	let bytes = encode(value);
+	assert_eq!(value.byte_len_hint(), bytes.len(), "{:?}", value);
+	let mut reader = ByteReader::new(&bytes);
+	assert_eq!(&T::from_bytes(&mut reader).unwrap(), value);
+	assert_eq!(reader.remaining(), 0);
+	bytes
+}
+
+#[test]
+fn supported_shapes_round_trip() {
+	let named = Named { id: 0x0102, x: 1.0, y: -2.5, label: "a".into() };
+	let bytes = round_trip(&named);
+	// Field by field, in declaration order.
+	let mut expected = vec![0x02, 0x01];
+	expected.extend(1.0f32.to_le_bytes());
+	expected.extend((-2.5f32).to_le_bytes());
+	expected.extend([1, b'a']);
+	assert_eq!(bytes, expected);
+
+	assert_eq!(round_trip(&Tuple(7, -1)), [&[7][..], &(-1i64).to_le_bytes()].concat());
+	assert!(round_trip(&Unit).is_empty());
+	assert_eq!(round_trip(&Generic { values: vec![3u32] }), [1, 3, 0, 0, 0]);
+
+	// Discriminants carry on from explicit ones, like the language's own.
+	let tags: Vec<Vec<u8>> = [Tagged::First, Tagged::Skipped, Tagged::Next].iter().map(round_trip).collect();
+	assert_eq!(tags, [[0], [5], [6]]);
+	assert!(matches!(
+		Tagged::from_bytes(&mut ByteReader::new(&[1])),
+		Err(DecodeError::InvalidTag { ty: "Tagged", tag: 1 })
+	));
+
+	// Written by hand rather than derived, with a u16 ClientId and a u32
+	// count for every score.
+	let mut bytes = encode(&vec![Coin::new(4.0, 8.0)]);
+	bytes.push(2);
+	for (owner, score) in [(1u16, 3u32), (300, 70_000)] {
+		bytes.extend(encode(&owner));
+		bytes.extend(encode(&score));
+	}
+	let coins = Coins::from_bytes(&mut ByteReader::new(&bytes)).unwrap();
+	assert_eq!(round_trip(&coins), bytes);
+}
+
+/// The derive as built for these tests, which sits among the test's own
+/// dependencies. The newest one is taken, should there be several.
+fn derive_library() -> PathBuf {
+	let deps = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+	let prefix = format!("{}command_networking_derive-", std::env::consts::DLL_PREFIX);
+	std::fs::read_dir(&deps)
+		.unwrap()
+		.filter_map(Result::ok)
+		.filter(|x| {
+			let name = x.file_name().to_string_lossy().into_owned();
+			name.starts_with(&prefix) && name.ends_with(std::env::consts::DLL_SUFFIX)
+		})
+		.max_by_key(|x| x.metadata().and_then(|x| x.modified()).ok())
+		.map(|x| x.path())
+		.expect("the derive wasn't built alongside the tests")
+}
+
+/// Compiles `fixture` on its own, returning what the compiler had to say
+/// if it failed.
+fn compile(fixture: &Path, derive: &Path, out_dir: &Path) -> Option<String> {
+	let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+	let output = Command::new(rustc)
+		.args(["--edition", "2021", "--crate-type", "lib", "--emit", "metadata"])
+		.arg("--extern")
+		.arg(format!("command_networking_derive={}", derive.display()))
+		.arg("--out-dir")
+		.arg(out_dir)
+		.arg(fixture)
+		.output()
+		.expect("couldn't run rustc");
+	match output.status.success() {
+		true => None,
+		false => Some(String::from_utf8_lossy(&output.stderr).into_owned()),
+	}
+}
+
+#[test]
+fn unsupported_shapes_fail_to_compile() {
+	let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/derive_errors");
+	let out_dir = std::env::temp_dir().join(format!("derive_errors-{}", std::process::id()));
+	std::fs::create_dir_all(&out_dir).unwrap();
+	let derive = derive_library();
+
+	let mut paths: Vec<PathBuf> = std::fs::read_dir(&fixtures)
+		.unwrap()
+		.map(|x| x.unwrap().path())
+		.filter(|x| x.extension().is_some_and(|x| x == "rs"))
+		.collect();
+	paths.sort();
+	assert!(!paths.is_empty(), "no fixtures in {}", fixtures.display());
+
+	let mut failures = vec![];
+	for path in &paths {
+		let expected = std::fs::read_to_string(path.with_extension("stderr")).unwrap();
+		let expected = expected.trim_end();
+		let name = path.file_stem().unwrap().to_string_lossy();
+		match compile(path, &derive, &out_dir) {
+			None => failures.push(format!("{} compiled", name)),
+			Some(stderr) if stderr.lines().next() != Some(expected) => {
+				failures.push(format!("{} failed with\n{}\nrather than\n{}", name, stderr, expected));
+			},
+			Some(_) => {},
+		}
+	}
+	let _ = std::fs::remove_dir_all(&out_dir);
+	assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
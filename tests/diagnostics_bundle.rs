@@ -0,0 +1,154 @@
+//! Plays against an in-process server for a bit, then captures a diagnostic
+//! bundle the way F4 does, into a buffer set aside beforehand. Read back,
+//! the bundle says what the client went through, and the game it holds
+//! simulates its ticks again the same way; one that's been tampered with
+//! doesn't.
+
+mod common;
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use command_networking_rs::ByteWriter;
+use command_networking_rs::diagnostics::{inspect_diagnostics, DiagnosticsError, DIAGNOSTICS_TICKS, DIAGNOSTICS_VERSION};
+use command_networking_rs::game::{ConnectionState, Game, TickAccumulator};
+use command_networking_rs::log;
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const FPS: u64 = 60;
+
+/// The log is the whole process's, so the tests filling it take turns.
+static LOG: Mutex<()> = Mutex::new(());
+
+/// Runs a frame the way the client does.
+fn frame(number: u64, game: &mut Game, transport: &mut LoopbackTransport, accumulator: &mut TickAccumulator) {
+	NOW_US.set(number * 1_000_000 / FPS);
+	let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+	transport.poll(&mut |bytes| game.dispatch_packet(bytes));
+	game.receive(now());
+	if let Some(request) = game.time_request(now()) {
+		send(transport, request);
+	}
+	for _ in 0..due_ticks {
+		let input = if (number / 20).is_multiple_of(2) { PlayerIntent::MoveRight } else { PlayerIntent::JumpStart };
+		if let Some(packet) = game.run_tick(|_| vec![input]) {
+			send(transport, packet);
+			game.tick_sent(now());
+		}
+	}
+	transport.flush().unwrap();
+}
+
+/// A game that played against the server for five seconds, and a bundle
+/// captured of it at the end, twice into the same buffer.
+struct Captured {
+	game: Game,
+	frames: u64,
+	/// The first of the two.
+	first: Vec<u8>,
+	bytes: Vec<u8>,
+	_log: MutexGuard<'static, ()>,
+}
+
+fn captured() -> Captured {
+	let log = LOG.lock().unwrap_or_else(PoisonError::into_inner);
+	let mut transport = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft]), (45, vec![PlayerIntent::JumpStart])]);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(2);
+	let mut accumulator = TickAccumulator::default();
+	// Quieter than what gets logged, so that the bundle has a few lines.
+	log::set_level(Some(log::LogLevel::Trace));
+	let frames = 5 * FPS;
+	for number in 1..=frames {
+		frame(number, &mut game, &mut transport, &mut accumulator);
+	}
+	// More than the bundle has room for, so that the oldest get dropped.
+	for line in 0..log::RECENT_LINES {
+		log::log(log::LogLevel::Trace, &format!("filler line={}", line));
+	}
+	log::set_level(Some(log::LogLevel::Info));
+	assert_eq!(game.connection_state(), ConnectionState::Connected);
+
+	let mut buffer = ByteWriter::with_capacity(256 * 1024);
+	game.export_diagnostics(&mut buffer, frames, now()).unwrap();
+	let first = buffer.as_slice().to_vec();
+	buffer.clear();
+	game.export_diagnostics(&mut buffer, frames, now()).unwrap();
+	// Capturing again into the same buffer doesn't allocate.
+	assert!(buffer.len() < 256 * 1024, "a bundle of {} bytes outgrew its buffer", buffer.len());
+	let bytes = buffer.as_slice().to_vec();
+	Captured { game, frames, first, bytes, _log: log }
+}
+
+#[test]
+fn the_bundle_says_what_the_client_went_through() {
+	let Captured { game, frames, first, bytes, .. } = captured();
+	let diagnostics = inspect_diagnostics(&bytes).expect("the bundle didn't read back");
+	assert_eq!(diagnostics.header.version, DIAGNOSTICS_VERSION);
+	assert_eq!(diagnostics.header.frame, frames);
+	assert_eq!(diagnostics.header.captured_at, now());
+	let report = &diagnostics.report;
+	assert_eq!(report.connection_state, ConnectionState::Connected);
+	assert_eq!(report.desync, None);
+	assert_eq!(report.predicted, game.prediction_stats().predicted);
+	assert_eq!(report.confirmed, game.prediction_stats().confirmed);
+	assert!(report.predicted > 100 && report.remote_intents > 0);
+	for count in &report.mispredictions {
+		assert_eq!(count.count, game.mispredictions(&count.strategy));
+	}
+	assert_eq!(report.log.len(), log::RECENT_LINES, "the log had more to say than that");
+	assert_eq!(report.log[0], "[trace] filler line=0");
+	assert_eq!(report.log, log::recent());
+	assert_eq!(
+		inspect_diagnostics(&first).unwrap().report.log.len(),
+		log::RECENT_LINES,
+		"capturing again changed what the first bundle held"
+	);
+}
+
+#[test]
+fn the_game_in_it_simulates_every_tick_the_same_way_again() {
+	let Captured { game, bytes, .. } = captured();
+	let mut restored = inspect_diagnostics(&bytes).unwrap().into_game();
+	assert_eq!(restored.accepted_head(), game.accepted_head());
+	assert_eq!(restored.state_hash(), game.state_hash());
+	let ticks = restored.memory_usage().into_iter().find(|x| x.subsystem == "history_ticks").unwrap().used;
+	assert!(ticks > 0 && ticks <= DIAGNOSTICS_TICKS, "the bundle held {} ticks", ticks);
+	assert_eq!(restored.resimulate(), None);
+	assert_eq!(restored.state_hash(), game.state_hash(), "resimulating changed the world");
+}
+
+#[test]
+fn a_tampered_hash_comes_out_differently() {
+	// The newest tick's hash is the one to look for, being the last thing
+	// in the bundle that matches.
+	let Captured { game, bytes, .. } = captured();
+	let newest = game.accepted_head();
+	let restored = inspect_diagnostics(&bytes).unwrap().into_game();
+	let hash = restored.state_hash_at(newest).expect("the newest tick didn't make it into the bundle");
+	let at = bytes.windows(hash.len()).rposition(|x| x == hash).unwrap();
+	let mut tampered = bytes.to_vec();
+	tampered[at] ^= 1;
+	let mut restored = inspect_diagnostics(&tampered).unwrap().into_game();
+	log::set_level(None);
+	assert_eq!(restored.resimulate(), Some(newest));
+	log::set_level(Some(log::LogLevel::Info));
+}
+
+#[test]
+fn whatever_isnt_a_bundle_of_ours_gets_told_apart() {
+	let Captured { game, bytes, .. } = captured();
+	assert_eq!(inspect_diagnostics(&game.export_state().unwrap()).err(), Some(DiagnosticsError::NotDiagnostics));
+	let mut newer = bytes.to_vec();
+	newer[4] = DIAGNOSTICS_VERSION + 1;
+	assert_eq!(
+		inspect_diagnostics(&newer).err(),
+		Some(DiagnosticsError::UnsupportedVersion { got: DIAGNOSTICS_VERSION + 1, expected: DIAGNOSTICS_VERSION })
+	);
+	assert!(matches!(inspect_diagnostics(&bytes[..bytes.len() / 2]), Err(DiagnosticsError::Malformed(_) | DiagnosticsError::State(_))));
+}
@@ -0,0 +1,75 @@
+//! Writes ticks right at and just past the limits the decoder holds them
+//! to, checking that whatever's past them fails to encode with the matching
+//! error, rather than going out for the other end to refuse, and that
+//! whatever's right at them makes it through and back unchanged.
+
+use command_networking_rs::{ByteReader, ByteWriter, EncodeError, NetType};
+use command_networking_rs::protocol::{
+	ClientId, CommandFrame, EncodedTick, Packet, PlayerIntent, Tick, TickEncoder, MAX_FRAMES_PER_TICK, MAX_INTENTS_PER_FRAME, MAX_PACKET_SIZE
+};
+
+/// `len` intents which can't be collapsed or packed into a single byte.
+fn intents(len: usize) -> Vec<PlayerIntent> {
+	(0..len).map(|x| PlayerIntent::MoveHorizontal(x as i8 + 1)).collect()
+}
+
+/// A tick of `frames` frames, holding `intents_per_frame` intents each.
+fn tick(frames: usize, intents_per_frame: usize) -> Tick<PlayerIntent> {
+	Tick::new(7, (0..frames).map(|x| CommandFrame::new(x as ClientId, intents(intents_per_frame))).collect())
+}
+
+fn encode(packet: &Packet<PlayerIntent>) -> Result<Vec<u8>, EncodeError> {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer)?;
+	Ok(writer.as_slice().to_vec())
+}
+
+/// Sends `tick` through a packet and back.
+fn round_trip(tick: Tick<PlayerIntent>) {
+	let bytes = encode(&Packet::AuthoritativeTick(tick.clone())).expect("a tick within the limits failed to encode");
+	assert!(bytes.len() <= MAX_PACKET_SIZE);
+	match Packet::from_bytes(&mut ByteReader::new(&bytes)) {
+		Ok(Packet::AuthoritativeTick(decoded)) => assert!(decoded == tick, "came back different"),
+		Ok(_) => panic!("came back as some other packet"),
+		Err(error) => panic!("failed to decode: {}", error),
+	}
+}
+
+#[test]
+fn a_frame_of_too_many_intents_fails_to_encode() {
+	let frame = CommandFrame::new(1, intents(MAX_INTENTS_PER_FRAME + 1));
+	let mut writer = ByteWriter::new();
+	assert_eq!(
+		frame.to_bytes(&mut writer),
+		Err(EncodeError::TooManyIntents { len: MAX_INTENTS_PER_FRAME + 1, max: MAX_INTENTS_PER_FRAME })
+	);
+	assert_eq!(
+		encode(&Packet::AuthoritativeTick(tick(1, MAX_INTENTS_PER_FRAME + 1))),
+		Err(EncodeError::TooManyIntents { len: MAX_INTENTS_PER_FRAME + 1, max: MAX_INTENTS_PER_FRAME })
+	);
+	round_trip(tick(1, MAX_INTENTS_PER_FRAME));
+}
+
+#[test]
+fn a_tick_of_too_many_frames_fails_to_encode() {
+	let oversized = tick(MAX_FRAMES_PER_TICK + 1, 0);
+	let too_many_frames = Err(EncodeError::TooManyFrames { len: MAX_FRAMES_PER_TICK + 1, max: MAX_FRAMES_PER_TICK });
+	assert_eq!(encode(&Packet::AuthoritativeTick(oversized.clone())), too_many_frames);
+	// The same goes for a tick sent as a delta.
+	let mut encoder = TickEncoder::default();
+	encoder.encode(&tick(MAX_FRAMES_PER_TICK, 0));
+	encoder.acknowledge(7);
+	let encoded = encoder.encode(&Tick::new(8, oversized.command_frames().to_vec()));
+	assert!(matches!(encoded, EncodedTick::Delta(_)));
+	let delta = Packet::PredictedTick { sender: 0, tick: encoded, past_frames: vec![], state_hash: None };
+	assert_eq!(encode(&delta), too_many_frames);
+	round_trip(tick(MAX_FRAMES_PER_TICK, 0));
+}
+
+#[test]
+fn every_frame_at_its_limit_is_too_large_a_packet() {
+	match encode(&Packet::AuthoritativeTick(tick(MAX_FRAMES_PER_TICK, MAX_INTENTS_PER_FRAME))) {
+		Err(EncodeError::PacketTooLarge { max, .. }) => assert_eq!(max, MAX_PACKET_SIZE),
+		other => panic!("expected the packet to be too large, got {:?}", other.map(|x| x.len())),
+	}
+}
@@ -0,0 +1,140 @@
+//! Pauses a client against the in-process server for five seconds, the way
+//! the game does when its window goes out of focus, to check that nothing
+//! gets predicted or sent meanwhile, and that on resuming it resyncs before
+//! predicting again: through the server's ticks that piled up, if they all
+//! made it, or through a snapshot, if some didn't.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::TICKRATE;
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const MARGIN: u8 = 2;
+/// Frames per tick, at 60 frames per second.
+const FRAMES_PER_TICK: u64 = 3;
+/// How long the window stays out of focus, in frames.
+const STALL: u64 = 5 * 60;
+
+thread_local! {
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Moves time on by a frame.
+fn tick_clock() -> u64 {
+	let number = FRAMES.get() + 1;
+	FRAMES.set(number);
+	NOW_US.set(number * 1_000_000 / 60);
+	number
+}
+
+/// Runs a frame the way the client does, predicting a tick on every
+/// `FRAMES_PER_TICK`th one. Returns how many packets went out.
+fn frame(game: &mut Game, transport: &mut impl Transport) -> usize {
+	let number = tick_clock();
+	transport.poll(&mut |packet| game.dispatch_packet(packet));
+	game.receive(now());
+
+	let mut sent = 0;
+	if let Some(packet) = game.time_request(now()) {
+		send(transport, packet);
+		sent += 1;
+	}
+	if !number.is_multiple_of(FRAMES_PER_TICK) {
+		return sent;
+	}
+	if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveLeft]) {
+		send(transport, packet);
+		game.tick_sent(now());
+		sent += 1;
+	}
+	sent
+}
+
+/// The server's tick, as of now.
+fn server_tick(started: f64) -> u64 {
+	((now() - started) * TICKRATE as f64) as u64
+}
+
+/// Plays for a while, pauses for `STALL` frames, and resumes, until the
+/// game's done resyncing.
+fn stall(transport: &mut SimulatedTransport<LoopbackTransport>, lose_ticks: bool) -> Game {
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(MARGIN);
+	for _ in 0..120 {
+		frame(&mut game, transport);
+	}
+	assert!(!game.is_stalled() && game.accepted_head() > 0, "never got going before the stall");
+
+	game.pause();
+	assert!(game.is_paused() && game.is_stalled());
+	for _ in 0..STALL {
+		let sent = match lose_ticks {
+			// Whatever arrived meanwhile is gone, along with the host's
+			// buffers, say.
+			true => {
+				tick_clock();
+				transport.poll(&mut |_| {});
+				0
+			},
+			false => frame(&mut game, transport),
+		};
+		assert_eq!(sent, 0, "sent something while paused");
+	}
+
+	game.resume();
+	assert!(game.is_resyncing() && game.is_stalled(), "predicted right after resuming");
+	let mut waited = 0;
+	while game.is_syncing_clock() {
+		frame(&mut game, transport);
+		waited += 1;
+		assert!(waited < 60, "never got done resyncing");
+	}
+	game
+}
+
+fn server() -> SimulatedTransport<LoopbackTransport> {
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveRight])]);
+	let conditions = SimulatedConditions { latency_ms: 50, jitter_ms: 5, ..Default::default() };
+	SimulatedTransport::new(server, conditions, 7).with_clock(now)
+}
+
+#[test]
+fn a_stall_the_server_covered_resyncs_without_a_snapshot() {
+	let started = now();
+	let mut transport = server();
+	let game = stall(&mut transport, false);
+	assert!(!game.is_resync_requested(), "asked for a snapshot, though the server's ticks covered the stall");
+	assert!(!game.is_resyncing() && !game.is_stalled(), "didn't get going again after resyncing");
+	let behind = server_tick(started).saturating_sub(game.latest_tick().unwrap().index());
+	assert!(behind <= 2, "resynced {} ticks behind the server", behind);
+}
+
+#[test]
+fn the_server_keeps_confirming_after_the_stall() {
+	let mut transport = server();
+	let mut game = stall(&mut transport, false);
+	let head = game.accepted_head();
+	for _ in 0..180 {
+		frame(&mut game, &mut transport);
+	}
+	assert!(game.accepted_head() > head + 40, "the server stopped confirming after the stall");
+	assert_eq!(game.desync(), None, "ended up in a different world than the server");
+	assert_eq!(game.rejected_frames(), 0);
+}
+
+#[test]
+fn with_the_servers_ticks_lost_a_snapshot_gets_asked_for() {
+	let mut transport = server();
+	let game = stall(&mut transport, true);
+	assert!(game.is_resync_requested(), "the server's ticks went missing, but no snapshot got asked for");
+	assert!(game.is_resyncing(), "got back in step without a snapshot");
+}
@@ -0,0 +1,141 @@
+//! Has a modified client stuff its frames with far more intents than it may
+//! send in a tick, through the in-process server. The server clamps them to
+//! what's allowed before hashing, so the corrections it sends back carry
+//! the clamped frames, and the client ends up exactly where it would have
+//! by playing fair. Then has a client clamp the frames of a server which
+//! doesn't, to the same effect.
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{CommandFrame, FrameLimits, Packet, PlayerIntent, Tick, TickDecoder, TickEncoder};
+use command_networking_rs::transport::{LoopbackTransport, Transport};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const TICKS: u64 = 30;
+/// Idle ticks after the last stuffed one, for every correction to get
+/// played back.
+const SETTLE: u64 = 10;
+
+/// What the modified client sends, in place of what it predicted with.
+fn stuffed_intents() -> Vec<PlayerIntent> {
+	let mut intents = vec![PlayerIntent::MoveRight; 10];
+	intents.extend([PlayerIntent::JumpStart; 3]);
+	intents.push(PlayerIntent::MoveHorizontal(127));
+	intents
+}
+
+struct Outcome {
+	game: Game,
+	/// Every tick the server corrected the client with.
+	corrections: Vec<Tick<PlayerIntent>>,
+	acks: usize,
+}
+
+/// Plays against a server clamping to `limits`, predicting with `predicted`
+/// for `TICKS` ticks, then idling. Sends `sent` rather than what it
+/// predicted with, if given, the way a modified client would.
+fn play(limits: FrameLimits, predicted: &[PlayerIntent], sent: Option<&[PlayerIntent]>) -> Outcome {
+	let mut transport = LoopbackTransport::new(LOCAL).with_frame_limits(limits);
+	let mut game = Game::new(LOCAL);
+	// The modified client's frames go out through an encoder of its own,
+	// as the game's is baselined on what it predicted.
+	let mut decoder = TickDecoder::default();
+	let mut encoder = TickEncoder::default();
+	let mut corrections = vec![];
+	let mut acks = 0;
+	let mut buffer = ByteWriter::new();
+	for index in 0..TICKS + SETTLE {
+		transport.poll(&mut |bytes| {
+			match Packet::<PlayerIntent>::decode(bytes) {
+				Ok(Packet::AuthoritativeTick(tick)) => corrections.push(tick),
+				Ok(Packet::Ack { .. }) => acks += 1,
+				Ok(Packet::Received { index }) => encoder.acknowledge(index),
+				_ => {},
+			}
+			game.dispatch_packet(bytes);
+		});
+		game.receive(index as f64 / 20.0);
+
+		let intents = if index < TICKS { predicted.to_vec() } else { vec![] };
+		let Some(packet) = game.run_tick(|_| intents.clone()) else { continue };
+		let packet = match (packet, sent) {
+			(Packet::PredictedTick { sender, tick, state_hash, .. }, Some(sent)) => {
+				let tick = decoder.decode(tick).unwrap();
+				let frames = match index < TICKS {
+					true => vec![CommandFrame::new(LOCAL, sent.to_vec())],
+					false => tick.command_frames().to_vec(),
+				};
+				let tick = Tick::new(tick.index(), frames);
+				Packet::PredictedTick { sender, tick: encoder.encode(&tick), past_frames: vec![], state_hash }
+			},
+			(packet, _) => packet,
+		};
+		buffer.clear();
+		packet.to_bytes(&mut buffer).unwrap();
+		transport.send(buffer.as_slice()).unwrap();
+	}
+	Outcome { game, corrections, acks }
+}
+
+/// Plays against a server that doesn't clamp frames, with the remote
+/// player sending `intents` on every tick, and the client clamping them to
+/// `limits`.
+fn play_remote(intents: Vec<PlayerIntent>, limits: Option<FrameLimits>) -> Game {
+	let mut game = Game::new(LOCAL);
+	game.set_frame_limits(limits);
+	let mut writer = ByteWriter::new();
+	Packet::<PlayerIntent>::Join(REMOTE).to_bytes(&mut writer).unwrap();
+	game.dispatch_packet(writer.as_slice());
+	for index in 0..TICKS + SETTLE {
+		game.run_tick(|_| vec![]);
+		let remote = if index < TICKS { intents.clone() } else { vec![] };
+		let tick = Tick::new(index, vec![CommandFrame::new(REMOTE, remote), CommandFrame::new(LOCAL, vec![])]);
+		writer.clear();
+		Packet::AuthoritativeTick(tick).to_bytes(&mut writer).unwrap();
+		game.dispatch_packet(writer.as_slice());
+		game.receive(index as f64 / 20.0);
+	}
+	game
+}
+
+#[test]
+fn ten_moves_only_ever_move_as_fast_as_one() {
+	let fair = play(FrameLimits::default(), &[PlayerIntent::MoveRight], None);
+	let spammed = play(FrameLimits::default(), &[PlayerIntent::MoveRight], Some(&[PlayerIntent::MoveRight; 10]));
+	// What got sent isn't what the client predicted, so every tick gets
+	// corrected, to what it predicted after all.
+	assert_eq!(spammed.corrections.len() as u64, TICKS);
+	for tick in &spammed.corrections {
+		assert_eq!(tick.frame_of(LOCAL).unwrap().intents(), &[PlayerIntent::MoveRight], "tick {} kept the spam", tick.index());
+	}
+	assert!(fair.corrections.is_empty() && fair.acks > 0, "playing fair got corrected");
+	assert_eq!(spammed.game.state_hash(), fair.game.state_hash(), "ten moves a tick got further than one");
+}
+
+#[test]
+fn a_stuffed_frame_gets_clamped_by_the_server() {
+	// With a single intent allowed, the jump is the one that goes, however
+	// sure the client is that it jumped.
+	let limits = FrameLimits { max_intents: 1 };
+	let fair = play(limits, &[PlayerIntent::MoveRight], None);
+	let stuffed = play(limits, &[PlayerIntent::MoveRight, PlayerIntent::JumpStart], Some(&stuffed_intents()));
+	assert_eq!(stuffed.corrections.len() as u64, TICKS, "every jump the client predicted should have been corrected");
+	for tick in &stuffed.corrections {
+		let frame = tick.frame_of(LOCAL).unwrap();
+		assert_eq!(frame.intents(), &[PlayerIntent::MoveRight], "tick {} went out with the frame unclamped", tick.index());
+	}
+	assert_eq!(stuffed.game.state_hash(), fair.game.state_hash(), "the corrected client isn't where playing fair gets");
+	assert_eq!(stuffed.game.players()[&LOCAL].position(), fair.game.players()[&LOCAL].position());
+	let jumped = play(FrameLimits::default(), &[PlayerIntent::MoveRight, PlayerIntent::JumpStart], None);
+	assert_ne!(jumped.game.players()[&LOCAL].position(), fair.game.players()[&LOCAL].position(), "jumping made no difference");
+}
+
+#[test]
+fn a_server_that_doesnt_clamp_gets_clamped_after_by_the_client() {
+	let fair = play_remote(vec![PlayerIntent::MoveRight], None);
+	let clamped = play_remote(stuffed_intents(), Some(FrameLimits { max_intents: 1 }));
+	assert_eq!(clamped.clamped_frames() as u64, TICKS);
+	assert_eq!(clamped.players()[&REMOTE].position(), fair.players()[&REMOTE].position(), "the remote frames weren't clamped");
+	assert_eq!(fair.clamped_frames(), 0);
+}
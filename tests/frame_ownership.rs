@@ -0,0 +1,54 @@
+//! Plays back authoritative ticks carrying frames that claim the local
+//! player's id, along with frames from a client that never joined. The
+//! former are the server's view of us and get applied; the latter are
+//! dropped before they can conjure a ghost player.
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::scalar::Scalar;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+
+/// Spawns away from the wall, so there's room to move left.
+const LOCAL: u16 = 1;
+const GHOST: u16 = 9;
+const TICKS: u64 = 20;
+
+/// Plays `TICKS` ticks thinking we're moving right, while the server says
+/// we're moving left and a ghost is jumping. Returns the game, and where the
+/// local player spawned.
+fn play() -> (Game, (Scalar, Scalar)) {
+	let mut game = Game::new(LOCAL);
+	let spawn = game.players()[&LOCAL].position();
+
+	for index in 0..TICKS {
+		// We think we're moving right, the server says otherwise.
+		game.run_tick(|_| vec![PlayerIntent::MoveRight]);
+
+		let tick = Tick::new(index, vec![
+			CommandFrame::new(GHOST, vec![PlayerIntent::JumpStart]),
+			CommandFrame::new(LOCAL, vec![PlayerIntent::MoveLeft]),
+		]);
+		let mut writer = ByteWriter::new();
+		Packet::AuthoritativeTick(tick).to_bytes(&mut writer).unwrap();
+		game.dispatch_packet(writer.as_slice());
+		game.receive(index as f64 / 20.0);
+	}
+	// Play back whatever the jitter buffer still holds.
+	for _ in 0..TICKS {
+		game.run_tick(|_| vec![]);
+	}
+	(game, spawn)
+}
+
+#[test]
+fn frames_from_a_client_that_never_joined_are_dropped() {
+	let (game, _) = play();
+	assert_eq!(game.rejected_frames(), TICKS as u32, "every ghost frame should have been rejected");
+	assert!(!game.players().contains_key(&GHOST), "a ghost player showed up");
+}
+
+#[test]
+fn the_servers_frames_for_the_local_player_are_applied() {
+	let (game, spawn) = play();
+	assert!(game.players()[&LOCAL].position().0 < spawn.0, "the server's frames for the local player weren't applied");
+}
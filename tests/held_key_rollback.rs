@@ -0,0 +1,118 @@
+//! Holds Left for a while against an in-process server, with the remote
+//! player setting off and stopping again meanwhile, so that corrections
+//! for it come in while the key's still held and the ticks after them get
+//! replayed. Checks that every confirmed tick has the local player exactly
+//! where it is in a run the remote player never moves in, and nothing
+//! gets corrected; only the remote player ends up anywhere else.
+
+mod common;
+
+use std::collections::BTreeMap;
+use command_networking_rs::game::{Game, PlayerStateSnapshot, TickAccumulator};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+/// Far enough from the left wall to keep going left for as long as it's
+/// held, with the remote player to its right, walking off the other way;
+/// so that the two never run into each other.
+const LOCAL: u16 = 4;
+const REMOTE: u16 = 5;
+/// Ticks Left is held for.
+const HELD: std::ops::Range<u64> = 40..120;
+/// Ticks the remote player moves for, in the run it moves in.
+const MOVING: std::ops::Range<u64> = 60..100;
+const FPS: u64 = 60;
+const SECONDS: u64 = 10;
+
+struct Run {
+	/// Both players, right after every confirmed tick.
+	confirmed: BTreeMap<u64, (PlayerStateSnapshot, PlayerStateSnapshot)>,
+	corrected: u64,
+}
+
+fn run(schedule: Vec<(u64, Vec<PlayerIntent>)>) -> Run {
+	NOW_US.set(0);
+	let conditions = SimulatedConditions { latency_ms: 60, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL).with_clock(now).with_remote(REMOTE, schedule);
+	let mut transport = SimulatedTransport::new(server, conditions, 5).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(2);
+	let mut accumulator = TickAccumulator::default();
+	let mut confirmed = BTreeMap::new();
+
+	for number in 1..=SECONDS * FPS {
+		NOW_US.set(number * 1_000_000 / FPS);
+		let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(now());
+		if let Some(request) = game.time_request(now()) {
+			send(&mut transport, request);
+		}
+		for _ in 0..due_ticks {
+			// The first tick comes well before the key's held.
+			let index = game.latest_tick().map_or(0, |x| x.index() + 1);
+			let held = HELD.contains(&index);
+			if let Some(packet) = game.run_tick(|_| if held { vec![PlayerIntent::MoveLeft] } else { vec![] }) {
+				send(&mut transport, packet);
+				game.tick_sent(now());
+			}
+		}
+
+		let recorded = confirmed.keys().next_back().map_or(0, |x| x + 1);
+		for index in recorded..=game.accepted_head() {
+			let local = game.player_state_at(LOCAL, index);
+			let remote = game.player_state_at(REMOTE, index);
+			if let (Some(local), Some(remote)) = (local, remote) {
+				confirmed.insert(index, (local, remote));
+			}
+		}
+	}
+	assert_eq!(game.desync(), None, "the client desynced");
+	Run { confirmed, corrected: game.prediction_stats().corrected }
+}
+
+fn still() -> Run {
+	run(vec![(0, vec![])])
+}
+
+fn moving() -> Run {
+	run(vec![(0, vec![]), (MOVING.start, vec![PlayerIntent::MoveRight]), (MOVING.end, vec![])])
+}
+
+#[test]
+fn nothing_gets_corrected_with_the_remote_player_standing_still() {
+	let still = still();
+	assert_eq!(still.corrected, 0, "ticks got corrected with the remote player standing still");
+	let first = *still.confirmed.keys().next().unwrap();
+	assert!(first < HELD.start, "nothing got confirmed until tick {}", first);
+}
+
+#[test]
+fn the_remote_player_moving_gets_ticks_corrected() {
+	let moving = moving();
+	assert!(moving.corrected >= 2, "only {} ticks got corrected", moving.corrected);
+}
+
+#[test]
+fn the_local_player_ends_up_where_it_would_have_anyway() {
+	let still = still();
+	let moving = moving();
+	let mut compared = 0;
+	let mut remote_apart = 0;
+	for (index, (local, remote)) in &moving.confirmed {
+		let Some((still_local, still_remote)) = still.confirmed.get(index) else { continue };
+		assert_eq!(local, still_local, "the local player went elsewhere on tick {}", index);
+		remote_apart += (remote != still_remote) as u32;
+		compared += 1;
+	}
+	assert!(compared as u64 > HELD.end, "only {} ticks got compared", compared);
+	assert!(remote_apart > 0, "the remote player never went anywhere");
+}
+
+#[test]
+fn holding_left_goes_left() {
+	let moving = moving();
+	let held = |index| moving.confirmed[&index].0.x;
+	assert!(held(HELD.end) < held(HELD.start), "the local player didn't move left");
+}
@@ -0,0 +1,112 @@
+//! Walks a scripted session, with the server answering late enough for
+//! rollbacks to happen, and checks that where the client says everyone was
+//! at every tick matches a fresh simulation of the server's ticks; both for
+//! ticks still in the history, and for ones only the recording has left.
+
+mod common;
+
+use command_networking_rs::game::{Game, PlayerStateSnapshot, PlayerStates};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+const TICKS: u64 = 120;
+
+fn local_intents(index: u64) -> Vec<PlayerIntent> {
+	match index % 20 {
+		0 => vec![MoveRight, JumpStart],
+		1..=4 => vec![MoveRight, JumpHold],
+		_ => vec![MoveRight],
+	}
+}
+
+/// Tick `index` as the server has it. Player 1 keeps changing direction,
+/// which the client keeps mispredicting.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	let remote = match (index / 7) % 2 {
+		0 => vec![MoveLeft],
+		_ => vec![MoveRight, JumpStart],
+	};
+	Tick::new(index, vec![CommandFrame::new(0, local_intents(index)), CommandFrame::new(1, remote)])
+}
+
+fn with_remote() -> Game {
+	let mut game = Game::new(0);
+	deliver(&mut game, Packet::Join(1));
+	game.receive(0.0);
+	game
+}
+
+/// The server's version, simulated from scratch, noting down everyone
+/// after every tick.
+fn expected() -> Vec<PlayerStates> {
+	let mut lockstep = with_remote();
+	let mut expected = vec![];
+	for index in 0..TICKS {
+		lockstep.advance(authoritative(index));
+		expected.push(lockstep.players().iter().map(|(id, x)| (*id, PlayerStateSnapshot::from(x))).collect());
+	}
+	expected
+}
+
+/// A recording client, with the server's ticks coming in `LAG` ticks late.
+fn client() -> Game {
+	let mut client = with_remote();
+	client.start_recording();
+	for index in 0..TICKS + LAG {
+		if index >= LAG {
+			deliver(&mut client, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		client.receive(index as f64 * TICK_DELTA as f64);
+		client.run_tick(|_| local_intents(index));
+	}
+	client
+}
+
+#[test]
+fn the_client_rolls_back_and_prunes_its_history() {
+	let client = client();
+	let strategy = client.prediction_strategy().name();
+	assert!(client.mispredictions(strategy) > 0, "nothing got rolled back");
+	let head = client.accepted_head();
+	assert!(head >= TICKS - LAG, "the client only got to tick {}", head);
+	assert!(client.state_hash_at(0).is_none(), "nothing got pruned, so the recording never gets looked at");
+}
+
+#[test]
+fn every_accepted_tick_matches_a_fresh_simulation() {
+	let expected = expected();
+	let client = client();
+	for index in 0..=client.accepted_head() {
+		for id in [0, 1] {
+			let state = client.player_state_at(id, index);
+			assert_eq!(state, expected[index as usize].get(&id).copied(), "player {} at tick {} is off", id, index);
+		}
+	}
+}
+
+#[test]
+fn replaying_agrees_with_the_history() {
+	// Looked up and simulated again, for whatever's still in the history,
+	// and played back for the rest.
+	let client = client();
+	let replayed = client.replay_range(0, TICKS + LAG);
+	assert_eq!(replayed.first().map(|x| x.0), Some(0), "the recording wasn't played back");
+	for (index, states) in &replayed {
+		let stored: PlayerStates = [0, 1]
+			.into_iter()
+			.filter_map(|id| client.player_state_at(id, *index).map(|x| (id, x)))
+			.collect();
+		assert_eq!(&stored, states, "tick {} simulated again came out different", index);
+	}
+}
+
+#[test]
+fn replaying_covers_the_predicted_ticks_too() {
+	let client = client();
+	let replayed = client.replay_range(0, TICKS + LAG);
+	let last = replayed.last().map(|x| x.0);
+	assert!(last.is_some_and(|x| x > client.accepted_head()), "the predicted ticks were left out");
+	assert!(replayed.windows(2).all(|x| x[1].0 == x[0].0 + 1), "ticks went missing");
+}
@@ -0,0 +1,169 @@
+//! Plays against an in-process server keeping time of its own, moving
+//! about for a bit, then jumping and doing nothing at all for 30 seconds,
+//! then moving about again, repeating one more past frame than it would
+//! by default. Checks that while idle, the client only sends every
+//! `idle_send_interval`th tick, one more than that, yet keeps simulating
+//! every one of them, the jump coming down meanwhile; that the server
+//! fills in the ticks in between without calling anything late; that the
+//! client sends every tick again as soon as there's something to say, or
+//! the remote player does something; and that nothing desyncs through any
+//! of it.
+
+mod common;
+
+use command_networking_rs::TICKRATE;
+use command_networking_rs::game::{ConnectionState, Game, TickAccumulator, DEFAULT_REDUNDANT_FRAMES, IDLE_TICKS};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use PlayerIntent::*;
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 2;
+const REMOTE: u16 = 4;
+const FPS: u64 = 60;
+/// The last tick the local player does anything on, jumping.
+const JUMP: u64 = 40;
+/// When the local player moves again, 30 seconds later.
+const BURST: u64 = JUMP + 1 + 30 * TICKRATE as u64;
+/// When the remote player sets off, with the local player idle again.
+const REMOTE_MOVES: u64 = BURST + 10 * TICKRATE as u64;
+const SECONDS: u64 = REMOTE_MOVES / TICKRATE as u64 + 5;
+
+fn local_intents(index: u64) -> Vec<PlayerIntent> {
+	match index {
+		0..JUMP => vec![MoveRight],
+		JUMP => vec![JumpStart],
+		_ if (BURST..BURST + 5 * TICKRATE as u64).contains(&index) => match index % 10 {
+			0 => vec![MoveLeft, JumpStart],
+			1..=4 => vec![MoveLeft, JumpHold],
+			_ => vec![MoveRight],
+		},
+		_ => vec![],
+	}
+}
+
+/// How the session went.
+struct Played {
+	game: Game,
+	late_frames: u32,
+	/// Sending every how many ticks while idle.
+	interval: u64,
+	/// The first tick run idle.
+	went_idle: Option<u64>,
+	/// Ticks run and sent while idle.
+	idle_run: u32,
+	idle_sent: u32,
+	/// Whether the first tick of the burst got sent.
+	burst_sent: Option<bool>,
+	/// The first tick after the remote player sets off that wasn't idle.
+	woken_at: Option<u64>,
+	/// The first tick after the jump that had the local player grounded.
+	landed: Option<u64>,
+}
+
+fn play() -> Played {
+	NOW_US.set(0);
+	let conditions = SimulatedConditions { latency_ms: 40, ..Default::default() };
+	let schedule = vec![(0, vec![]), (REMOTE_MOVES, vec![MoveLeft]), (REMOTE_MOVES + 20, vec![])];
+	let server = LoopbackTransport::new(LOCAL).with_clock(now).with_remote(REMOTE, schedule);
+	let mut transport = SimulatedTransport::new(server, conditions, 9).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(2);
+	game.set_redundant_frames(DEFAULT_REDUNDANT_FRAMES + 1);
+	let interval = game.idle_send_interval();
+	let mut accumulator = TickAccumulator::default();
+
+	let (mut idle_run, mut idle_sent) = (0, 0);
+	let mut went_idle = None;
+	let mut burst_sent = None;
+	let mut woken_at = None;
+	let mut landed = None;
+	for number in 1..=SECONDS * FPS {
+		NOW_US.set(number * 1_000_000 / FPS);
+		let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(now());
+		if let Some(request) = game.time_request(now()) {
+			send(&mut transport, request);
+		}
+		for _ in 0..due_ticks {
+			// The first tick comes well before the jump.
+			let index = game.latest_tick().map_or(0, |x| x.index() + 1);
+			let packet = game.run_tick(|_| local_intents(index));
+			if game.is_idle() {
+				went_idle.get_or_insert(index);
+				assert_eq!(game.send_rate(), TICKRATE as u32 / interval as u32);
+				idle_run += 1;
+				idle_sent += packet.is_some() as u32;
+			}
+			if index > JUMP && game.player_state_at(LOCAL, index).is_some_and(|x| x.grounded) {
+				landed.get_or_insert(index);
+			}
+			if index == BURST {
+				burst_sent = Some(packet.is_some());
+			}
+			if index > REMOTE_MOVES && !game.is_idle() {
+				woken_at.get_or_insert(index);
+			}
+			if let Some(packet) = packet {
+				send(&mut transport, packet);
+				game.tick_sent(now());
+			}
+		}
+		assert_eq!(game.desync(), None, "the client desynced");
+	}
+	let late_frames = transport.inner().late_frames();
+	Played { game, late_frames, interval, went_idle, idle_run, idle_sent, burst_sent, woken_at, landed }
+}
+
+#[test]
+fn idle_sends_go_out_one_tick_less_often_for_every_extra_past_frame() {
+	let mut game = Game::new(LOCAL);
+	let default = game.idle_send_interval();
+	game.set_redundant_frames(DEFAULT_REDUNDANT_FRAMES + 1);
+	assert_eq!(game.idle_send_interval(), DEFAULT_REDUNDANT_FRAMES as u64 + 2);
+	assert_eq!(game.idle_send_interval(), default + 1);
+}
+
+#[test]
+fn goes_idle_once_nobody_has_done_anything_for_long_enough() {
+	let played = play();
+	assert_eq!(played.went_idle, Some(JUMP + IDLE_TICKS + 1));
+	assert!(played.idle_run > 30 * TICKRATE as u32, "only idle for {} ticks", played.idle_run);
+}
+
+#[test]
+fn only_a_keepalive_goes_out_every_so_often_while_idle() {
+	let Played { interval, idle_run, idle_sent, .. } = play();
+	assert!(idle_sent * interval as u32 <= idle_run + interval as u32, "sent {} of {} idle ticks", idle_sent, idle_run);
+	assert!(idle_sent > 0, "no keepalives went out");
+}
+
+#[test]
+fn a_jump_still_comes_down_while_idle() {
+	let played = play();
+	let landed = played.landed.expect("never landed");
+	assert!(landed > played.went_idle.unwrap(), "landed on tick {}, before going idle", landed);
+}
+
+#[test]
+fn every_tick_gets_sent_again_once_theres_something_to_say() {
+	assert_eq!(play().burst_sent, Some(true), "the first tick of the burst didn't get sent");
+}
+
+#[test]
+fn the_remote_player_moving_wakes_the_client_up() {
+	let woken_at = play().woken_at.expect("the remote player moving didn't wake the client up");
+	assert!(woken_at < REMOTE_MOVES + TICKRATE as u64 / 2, "only woke up on tick {}", woken_at);
+}
+
+#[test]
+fn the_ticks_never_sent_get_filled_in_on_time() {
+	// The ticks never sent got empty frames, as predicted, and weren't late.
+	let played = play();
+	assert_eq!(played.game.connection_state(), ConnectionState::Connected);
+	let head = played.game.accepted_head();
+	assert!(head > REMOTE_MOVES + 2 * TICKRATE as u64, "the client only got to tick {}", head);
+	assert_eq!(played.late_frames, 0);
+	assert_eq!(played.game.prediction_stats().local_frames_lost, 0, "the server went with other frames than ours");
+}
@@ -0,0 +1,78 @@
+//! Drives a player's input purely through injection, the way a host's
+//! on-screen buttons or test scripts would, and checks injected intents
+//! get normalized like keyboard ones: opposing moves cancel, duplicates
+//! collapse, and a jump injected on consecutive ticks only starts once.
+//! The keyboard is switched off, so no window is needed.
+
+use command_networking_rs::input::{self, InputState, Injection, KeyBindings};
+use command_networking_rs::protocol::PlayerIntent;
+
+use PlayerIntent::*;
+
+fn tick(input: &mut InputState, intents: &[PlayerIntent]) -> Vec<PlayerIntent> {
+	for intent in intents {
+		input.inject(*intent);
+	}
+	input.drain()
+}
+
+fn injected_only() -> InputState {
+	let mut input = InputState::new(KeyBindings::default());
+	input.set_keyboard(false);
+	input
+}
+
+#[test]
+fn opposing_moves_cancel_and_duplicates_collapse() {
+	let mut input = injected_only();
+	assert_eq!(tick(&mut input, &[]), vec![]);
+	assert_eq!(tick(&mut input, &[MoveLeft, MoveLeft]), vec![MoveLeft]);
+	assert_eq!(tick(&mut input, &[MoveLeft, MoveRight]), vec![]);
+	assert_eq!(tick(&mut input, &[JumpHold, MoveRight, JumpStart]), vec![MoveRight, JumpStart]);
+}
+
+#[test]
+fn a_jump_injected_every_tick_only_starts_once() {
+	let mut input = injected_only();
+	assert_eq!(tick(&mut input, &[JumpStart]), vec![JumpStart]);
+	// Holding an on-screen jump button injects a jump every tick.
+	assert_eq!(tick(&mut input, &[JumpStart]), vec![JumpHold]);
+	assert_eq!(tick(&mut input, &[JumpStart, MoveLeft]), vec![MoveLeft, JumpHold]);
+	// Let go of for a tick, it jumps again.
+	assert_eq!(tick(&mut input, &[]), vec![]);
+	assert_eq!(tick(&mut input, &[JumpStart]), vec![JumpStart]);
+}
+
+#[test]
+fn injected_intents_last_a_single_tick() {
+	let mut input = injected_only();
+	input.inject(MoveRight);
+	assert_eq!(input.drain(), vec![MoveRight]);
+	assert_eq!(input.drain(), vec![]);
+	// Unless cleared first.
+	input.inject(MoveRight);
+	input.clear_injected();
+	assert_eq!(input.drain(), vec![]);
+}
+
+#[test]
+fn only_intents_without_a_payload_can_be_injected_by_tag() {
+	for intent in [MoveLeft, MoveRight, JumpStart, JumpHold] {
+		assert_eq!(PlayerIntent::from_tag(intent.tag()), Some(intent));
+	}
+	assert_eq!(PlayerIntent::from_tag(PlayerIntent::TAG_MOVE_HORIZONTAL), None);
+	assert_eq!(PlayerIntent::from_tag(200), None);
+}
+
+#[test]
+fn what_the_host_injects_waits_in_order() {
+	input::inject(Injection::Intent(1, JumpStart));
+	input::inject(Injection::Clear);
+	input::inject(Injection::Keyboard(0, false));
+	assert_eq!(input::take_injections(), vec![
+		Injection::Intent(1, JumpStart),
+		Injection::Clear,
+		Injection::Keyboard(0, false),
+	]);
+	assert!(input::take_injections().is_empty());
+}
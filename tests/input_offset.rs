@@ -0,0 +1,67 @@
+//! Presses right at different points into a tick, to check that a later
+//! press moves the player less on its first tick, and that the offset it
+//! came in at makes it over the wire, delta encoded or not.
+
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Tick, TickDecoder, TickEncoder};
+
+/// How far the player got one tick after pressing right `offset` 256ths of
+/// the way into it.
+fn first_step(offset: u8) -> f32 {
+	let mut game = Game::new(0);
+	let start = game.players()[&0].rendered_position().0;
+	game.set_input_offset(0, offset);
+	let tick = game.predict_tick(vec![PlayerIntent::MoveRight]);
+	assert_eq!(tick.frame_of(0).map(|x| x.offset()), Some(offset), "the offset didn't make it into the tick");
+	game.advance(tick);
+	game.players()[&0].rendered_position().0 - start
+}
+
+fn round_trip(tick: &Tick<PlayerIntent>) -> Tick<PlayerIntent> {
+	let mut writer = ByteWriter::new();
+	tick.to_bytes(&mut writer).unwrap();
+	Tick::from_bytes(&mut ByteReader::new(writer.as_slice())).expect("the tick didn't decode")
+}
+
+fn frame(offset: u8) -> CommandFrame<PlayerIntent> {
+	CommandFrame::new(1, vec![PlayerIntent::MoveLeft]).with_offset(offset)
+}
+
+#[test]
+fn a_later_press_moves_less_on_its_first_tick() {
+	let early = first_step(0);
+	let halfway = first_step(128);
+	let late = first_step(255);
+	assert!(early > halfway && halfway > late, "moved {} then {} then {} pixels", early, halfway, late);
+	assert!((halfway - early / 2.0).abs() < early * 0.05, "moved {} pixels halfway in, out of {}", halfway, early);
+}
+
+#[test]
+fn the_offset_is_hashed_and_sent() {
+	let on_time = Tick::new(1, vec![frame(0)]);
+	let delayed = Tick::new(1, vec![frame(200)]);
+	assert_ne!(on_time.hash(), delayed.hash(), "the offset isn't hashed");
+	assert_eq!(round_trip(&delayed).frame_of(1).map(|x| x.offset()), Some(200));
+}
+
+#[test]
+fn an_idle_frame_drops_its_offset() {
+	// Offsets don't mean anything without intents to apply them to.
+	let mut idle = CommandFrame::<PlayerIntent>::new(1, vec![]).with_offset(90);
+	idle.normalize();
+	assert_eq!(idle.offset(), 0, "an idle frame kept its offset");
+}
+
+#[test]
+fn offsets_survive_delta_encoding() {
+	// Same intents a tick later, but pressed at a different point.
+	let mut encoder = TickEncoder::default();
+	let mut decoder = TickDecoder::default();
+	for tick in [Tick::new(1, vec![frame(30)]), Tick::new(2, vec![frame(30)]), Tick::new(3, vec![frame(220)])] {
+		let decoded = decoder.decode(encoder.encode(&tick)).expect("the delta didn't resolve");
+		encoder.acknowledge(decoded.index());
+		assert_eq!(decoded.frame_of(1).map(|x| x.offset()), tick.frame_of(1).map(|x| x.offset()));
+		assert_eq!(decoded.hash(), tick.hash());
+	}
+}
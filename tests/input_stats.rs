@@ -0,0 +1,120 @@
+//! Plays a session the server answers late enough for rollbacks to happen,
+//! with a remote player who keeps changing direction, and checks that the
+//! intents the client says everyone had applied are the server's, counted
+//! once per tick however often a rollback replayed it.
+
+mod common;
+
+use command_networking_rs::game::Game;
+use command_networking_rs::prediction::IntentSummary;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::{TICKRATE, TICK_DELTA};
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+const TICKS: u64 = 120;
+
+fn local_intents(index: u64) -> Vec<PlayerIntent> {
+	match index % 20 {
+		0 => vec![MoveRight, JumpStart],
+		1..=4 => vec![MoveRight, JumpHold],
+		_ => vec![MoveRight],
+	}
+}
+
+/// Player 1's frame as the server has it, which the client keeps
+/// mispredicting.
+fn remote_intents(index: u64) -> Vec<PlayerIntent> {
+	match (index / 7) % 3 {
+		0 => vec![MoveLeft],
+		1 => vec![MoveHorizontal(40), JumpStart],
+		_ => vec![],
+	}
+}
+
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	Tick::new(index, vec![CommandFrame::new(0, local_intents(index)), CommandFrame::new(1, remote_intents(index))])
+}
+
+/// Plays the session, checking after every tick that the ticks replayed
+/// hold what they were replayed with. Returns the client, and how many
+/// ticks got rolled back.
+fn play() -> (Game, u32) {
+	let mut client = Game::new(0);
+	deliver(&mut client, Packet::Join(1));
+	client.receive(0.0);
+	let mut rolled_back = 0;
+	for index in 0..TICKS + LAG {
+		if index >= LAG {
+			deliver(&mut client, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		let before = client.mispredictions(client.prediction_strategy().name());
+		client.receive(index as f64 * TICK_DELTA as f64);
+		client.run_tick(|_| local_intents(index));
+		if client.mispredictions(client.prediction_strategy().name()) > before {
+			rolled_back += 1;
+		}
+
+		// Right after a rollback too, the ticks replayed hold what they
+		// were replayed with, and nothing from before.
+		let stats = client.input_stats();
+		let head = client.accepted_head();
+		if index > LAG {
+			assert_eq!(stats.intents_at(head, 1), Some(remote_intents(head).as_slice()), "tick {} holds the wrong intents", head);
+			assert_eq!(stats.intents_at(index, 0), Some(local_intents(index).as_slice()), "tick {} holds the wrong local intents", index);
+		}
+	}
+	let head = client.accepted_head();
+	assert!(head >= TICKS - LAG, "the client only got to tick {}", head);
+	(client, rolled_back)
+}
+
+/// The last second of settled ticks.
+fn last_second(client: &Game) -> std::ops::RangeInclusive<u64> {
+	let head = client.accepted_head();
+	head + 1 - TICKRATE as u64..=head
+}
+
+#[test]
+fn replayed_ticks_hold_what_they_were_replayed_with() {
+	let (_, rolled_back) = play();
+	assert!(rolled_back > 5, "only {} rollbacks happened", rolled_back);
+}
+
+#[test]
+fn settled_ticks_hold_the_servers_intents() {
+	let (client, _) = play();
+	let stats = client.input_stats();
+	for index in last_second(&client) {
+		for (id, intents) in [(0, local_intents(index)), (1, remote_intents(index))] {
+			assert_eq!(stats.intents_at(index, id), Some(intents.as_slice()), "player {} at tick {} is off", id, index);
+		}
+	}
+}
+
+#[test]
+fn intents_get_counted_once_per_tick() {
+	let (client, _) = play();
+	let stats = client.input_stats();
+	let head = client.accepted_head();
+	for (id, intents) in [(0u16, local_intents as fn(u64) -> Vec<PlayerIntent>), (1, remote_intents)] {
+		let expected: usize = last_second(&client).map(|x| intents(x).len()).sum();
+		assert_eq!(stats.per_second(id, head, TICKRATE), expected, "player {}'s intents got counted more than once", id);
+	}
+}
+
+#[test]
+fn only_the_last_second_is_kept() {
+	// Of settled ticks, plus the predicted ones.
+	let (client, _) = play();
+	let stats = client.input_stats();
+	let head = client.accepted_head();
+	assert!(stats.intents_at(head - TICKRATE as u64 - 1, 1).is_none(), "ticks from long ago are still around");
+	assert!(stats.len() <= TICKRATE as usize + LAG as usize + 1, "{} ticks are kept", stats.len());
+}
+
+#[test]
+fn an_analog_move_sums_up_by_its_direction() {
+	assert_eq!(IntentSummary::of(&[MoveHorizontal(-3), JumpHold]), IntentSummary { left: true, right: false, jump: true });
+	assert_eq!(IntentSummary::of(&[MoveHorizontal(0)]), IntentSummary::default());
+}
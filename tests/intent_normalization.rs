@@ -0,0 +1,91 @@
+//! Pins down how conflicting intents within a single command frame get
+//! resolved, for every kind of conflict.
+
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent};
+use PlayerIntent::*;
+
+fn normalized(intents: &[PlayerIntent]) -> Vec<PlayerIntent> {
+	let mut frame = CommandFrame::new(0, intents.to_vec());
+	frame.normalize();
+	frame.intents().to_vec()
+}
+
+type Cases<'a> = &'a [(&'a [PlayerIntent], &'a [PlayerIntent])];
+
+fn check(cases: Cases) {
+	for (input, expected) in cases {
+		let output = normalized(input);
+		assert!(output == *expected, "a frame of {} normalized to {}, rather than {}", describe(input), describe(&output), describe(expected));
+	}
+}
+
+#[test]
+fn duplicates_collapse() {
+	check(&[
+		(&[], &[]),
+		(&[MoveLeft], &[MoveLeft]),
+		(&[MoveLeft, MoveLeft], &[MoveLeft]),
+		(&[JumpStart, JumpStart], &[JumpStart]),
+		(&[JumpHold, JumpHold], &[JumpHold]),
+	]);
+}
+
+#[test]
+fn opposing_moves_cancel() {
+	check(&[
+		(&[MoveLeft, MoveRight], &[]),
+		(&[MoveRight, MoveLeft, MoveRight], &[]),
+		(&[MoveLeft, MoveRight, JumpStart], &[JumpStart]),
+		(&[MoveHorizontal(40), MoveLeft, MoveRight], &[]),
+	]);
+}
+
+#[test]
+fn digital_moves_win_over_analog_ones() {
+	check(&[
+		(&[MoveHorizontal(40), MoveRight], &[MoveRight]),
+		(&[MoveHorizontal(40), MoveHorizontal(-90)], &[MoveHorizontal(40)]),
+		(&[MoveHorizontal(0)], &[]),
+	]);
+}
+
+#[test]
+fn starting_a_jump_wins_over_holding_it() {
+	check(&[(&[JumpHold, JumpStart], &[JumpStart])]);
+}
+
+#[test]
+fn movement_always_goes_first() {
+	check(&[
+		(&[JumpStart, MoveRight], &[MoveRight, JumpStart]),
+		(&[JumpHold, MoveHorizontal(-5), JumpStart, MoveHorizontal(5)], &[MoveHorizontal(-5), JumpStart]),
+	]);
+}
+
+#[test]
+fn normalizing_twice_changes_nothing() {
+	// So the server can normalize frames a client already has.
+	let inputs: &[&[PlayerIntent]] = &[
+		&[MoveRight, MoveLeft, MoveRight],
+		&[MoveHorizontal(40), MoveHorizontal(-90)],
+		&[JumpHold, MoveHorizontal(-5), JumpStart, MoveHorizontal(5)],
+	];
+	for input in inputs {
+		let output = normalized(input);
+		assert!(normalized(&output) == output, "normalizing {} isn't idempotent", describe(input));
+	}
+}
+
+fn describe(intents: &[PlayerIntent]) -> String {
+	let names: Vec<String> = intents
+		.iter()
+		.map(|x| match x {
+			MoveLeft => "MoveLeft".to_string(),
+			MoveRight => "MoveRight".to_string(),
+			JumpStart => "JumpStart".to_string(),
+			JumpHold => "JumpHold".to_string(),
+			MoveHorizontal(magnitude) => format!("MoveHorizontal({})", magnitude),
+		})
+		.collect();
+	format!("[{}]", names.join(", "))
+}
\ No newline at end of file
@@ -0,0 +1,69 @@
+//! Jumps at a few tick rates, to check that the jump still reaches the
+//! height it did before the physics were measured in pixels per second,
+//! that it reaches about the same height at other rates, and that falls
+//! top out at the terminal velocity.
+
+use command_networking_rs::game::{Game, SimConfig};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::scalar::Scalar;
+use command_networking_rs::TICKRATE;
+
+/// How high a jump went at the default tick rate, in pixels, back when
+/// velocities were in tenths of a pixel per second.
+const PREVIOUS_APEX: f32 = 140.1125;
+
+/// Jumps once, without holding it, and returns how high the player got,
+/// the fastest they fell, and whether they made it back to the floor.
+fn jump(hz: u8, config: SimConfig) -> (f32, Scalar, bool) {
+	let mut game = Game::new(0);
+	game.set_tick_rate(hz);
+	game.set_sim_config(config);
+	game.set_platforms(vec![]);
+	let floor = game.players()[&0].rendered_position().1;
+
+	let mut highest = floor;
+	// Standing still, to begin with.
+	let mut fastest = game.players()[&0].velocity().1;
+	let mut intents = vec![PlayerIntent::JumpStart];
+	for _ in 0..hz as u32 * 10 {
+		let tick = game.predict_tick(std::mem::take(&mut intents));
+		game.advance(tick);
+		let player = &game.players()[&0];
+		highest = highest.min(player.rendered_position().1);
+		fastest = fastest.max(player.velocity().1);
+	}
+	let player = &game.players()[&0];
+	let landed = player.is_grounded() && player.rendered_position().1 == floor;
+	(floor - highest, fastest, landed)
+}
+
+#[test]
+fn jumps_reach_the_height_they_always_did() {
+	let (apex, _, landed) = jump(TICKRATE, SimConfig::default());
+	assert!(landed, "the player never landed");
+	assert!((apex - PREVIOUS_APEX).abs() < 1.0, "jumped {} pixels high, rather than {}", apex, PREVIOUS_APEX);
+}
+
+#[test]
+fn jumps_reach_about_as_high_at_other_tick_rates() {
+	let (apex, _, _) = jump(TICKRATE, SimConfig::default());
+	for hz in [TICKRATE / 2, TICKRATE * 2] {
+		let (other, _, landed) = jump(hz, SimConfig::default());
+		assert!(landed, "the player never landed at {} Hz", hz);
+		assert!((other - apex).abs() < apex * 0.1, "jumped {} pixels high at {} Hz, rather than about {}", other, hz, apex);
+	}
+}
+
+#[test]
+fn long_falls_top_out_at_the_terminal_velocity() {
+	// A jump taking the player way out of the world, to fall back from.
+	let mut config = SimConfig::default();
+	for _ in 0..3 {
+		config.jump_velocity += SimConfig::JUMP_VELOCITY;
+	}
+	let (apex, fastest, landed) = jump(TICKRATE, config);
+	let terminal = SimConfig::TERMINAL_VELOCITY;
+	assert!(apex > 1000.0, "only jumped {} pixels high", apex);
+	assert!(fastest == terminal, "fell at {:?} pixels per second, rather than {:?}", fastest, terminal);
+	assert!(landed, "the player fell through the floor");
+}
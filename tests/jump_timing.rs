@@ -0,0 +1,48 @@
+//! Scripts exact tick sequences around a landing, to check that a jump
+//! pressed just before touching the ground still goes off once the player
+//! is back on it, and that one pressed too early doesn't.
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::PlayerIntent;
+
+/// Jumps on tick 0, presses jump again on `press`, and returns on which
+/// ticks the player was airborne.
+fn airborne_ticks(press: u32, ticks: u32) -> Vec<bool> {
+	let mut game = Game::new(0);
+	let floor = game.players()[&0].position().1;
+	(0..ticks)
+		.map(|x| {
+			if x == 0 || x == press {
+				game.queue_intents(0, vec![PlayerIntent::JumpStart]);
+			}
+			let tick = game.predict_tick(vec![]);
+			game.advance(tick);
+			game.players()[&0].position().1 != floor
+		})
+		.collect()
+}
+
+/// The tick a single jump lands on.
+fn landing() -> u32 {
+	let single = airborne_ticks(u32::MAX, 40);
+	single.iter().position(|x| !x).expect("the player never landed") as u32
+}
+
+#[test]
+fn a_jump_pressed_up_to_a_tick_before_landing_goes_off_on_landing() {
+	// The press keeps trying for 3 ticks; pressing on the landing tick itself
+	// or the one before means one of those starts out on the ground.
+	let landing = landing();
+	for early in 0..=1 {
+		let buffered = airborne_ticks(landing - early, 40);
+		assert!(!buffered[landing as usize], "a buffered jump went off in mid-air");
+		assert!(buffered[landing as usize + 1], "a jump pressed {} ticks early got dropped", early);
+	}
+}
+
+#[test]
+fn a_jump_pressed_two_ticks_before_landing_is_dropped() {
+	let landing = landing();
+	let dropped = airborne_ticks(landing - 2, 40);
+	assert!(dropped[landing as usize..].iter().all(|x| !x), "a jump pressed 2 ticks early still went off");
+}
@@ -0,0 +1,63 @@
+//! Writes key bindings out as text and parses them back, with keys of
+//! every kind there are names for, and checks that what's written by hand
+//! gets read the way the format says: intents left out keep their
+//! defaults, comments and repeated keys are skipped, and unknown names are
+//! turned down.
+
+use command_networking_rs::input::KeyBindings;
+use command_networking_rs::protocol::PlayerIntent;
+use macroquad::prelude::KeyCode;
+
+/// A few of every kind of key there's a name for.
+const KEYS: &[KeyCode] = &[
+	KeyCode::Space, KeyCode::Apostrophe, KeyCode::Key0, KeyCode::Key9, KeyCode::Semicolon, KeyCode::A,
+	KeyCode::Z, KeyCode::LeftBracket, KeyCode::GraveAccent, KeyCode::World2, KeyCode::Escape,
+	KeyCode::Up, KeyCode::PageDown, KeyCode::PrintScreen, KeyCode::F1, KeyCode::F25, KeyCode::Kp0,
+	KeyCode::KpEqual, KeyCode::LeftShift, KeyCode::RightSuper, KeyCode::Menu,
+];
+
+#[test]
+fn bindings_survive_a_round_trip() {
+	for bindings in [KeyBindings::default(), KeyBindings::wasd()] {
+		assert_eq!(KeyBindings::parse(&bindings.to_text()), Ok(bindings));
+	}
+
+	// Those keys, spread over the intents, some bound to several of them
+	// and one intent bound to none at all.
+	for (offset, chunk) in [0, 7, 13].into_iter().zip([7, 6, 8]) {
+		let keys = &KEYS[offset..offset + chunk];
+		let mut bindings = KeyBindings::default();
+		bindings.bind(PlayerIntent::MoveLeft, keys.to_vec());
+		bindings.bind(PlayerIntent::MoveRight, keys.iter().rev().copied().collect());
+		bindings.bind(PlayerIntent::JumpHold, vec![]);
+		let text = bindings.to_text();
+		assert_eq!(KeyBindings::parse(&text), Ok(bindings.clone()), "read back from {:?}", text);
+		assert_eq!(KeyBindings::parse(&text).unwrap().to_text(), text);
+	}
+}
+
+#[test]
+fn bindings_written_by_hand_are_read_as_documented() {
+	let text = "# WASD, but jumping with space too\n\n  left A\nright D D\njump W Space\n";
+	let bindings = KeyBindings::parse(text).unwrap();
+	assert_eq!(bindings.keys(PlayerIntent::MoveLeft), [KeyCode::A]);
+	assert_eq!(bindings.keys(PlayerIntent::MoveRight), [KeyCode::D]);
+	assert_eq!(bindings.keys(PlayerIntent::JumpStart), [KeyCode::W, KeyCode::Space]);
+	assert_eq!(bindings.to_text(), "left A\nright D\njump W Space\n");
+
+	// Only jumping rebound; moving stays on the arrow keys.
+	let bindings = KeyBindings::parse("jump Space").unwrap();
+	assert_eq!(bindings.keys(PlayerIntent::MoveLeft), [KeyCode::Left]);
+	assert_eq!(bindings.keys(PlayerIntent::JumpHold), [KeyCode::Space]);
+	assert_eq!(KeyBindings::parse(""), Ok(KeyBindings::default()));
+}
+
+#[test]
+fn unknown_names_are_turned_down() {
+	let error = KeyBindings::parse("left A\ncrouch S").unwrap_err();
+	assert!(error.contains("\"crouch\"") && error.contains("line 2"), "{}", error);
+	let error = KeyBindings::parse("jump Up Spacebar").unwrap_err();
+	assert!(error.contains("\"Spacebar\"") && error.contains("line 1"), "{}", error);
+	// Names are case-sensitive, the way `to_text` writes them.
+	assert!(KeyBindings::parse("Left a").is_err());
+}
@@ -0,0 +1,95 @@
+//! Runs the client against an in-process server keeping time of its own,
+//! with the client's frames held back on their way there for longer than
+//! it takes the server to go on without them. The server fills in empty
+//! frames instead, and the client gets corrected to having done nothing,
+//! rather than desyncing from everyone else. Given a deadline long enough,
+//! the very same frames make it.
+
+mod common;
+
+use std::collections::VecDeque;
+use command_networking_rs::TICKRATE;
+use command_networking_rs::game::{ConnectionState, Game, TickAccumulator};
+use command_networking_rs::prediction::PredictionStats;
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+/// Higher than the remote player's, so that the order the client sends
+/// the frames in isn't the canonical one.
+const LOCAL: u16 = 5;
+const REMOTE: u16 = 1;
+const LATENCY_MS: u32 = 20;
+/// How much longer the client's frames take to get there than anything
+/// else, clock sync included, so that they can't be made up for.
+const HELD_BACK_MS: u64 = 150;
+const DEADLINE_MS: u64 = 150;
+const MARGIN: u8 = 0;
+const FPS: u64 = 60;
+const SECONDS: u64 = 10;
+
+/// Plays for `SECONDS`, with the server waiting `deadline_ms` past the end
+/// of every tick for the client's frames. Returns how many frames it
+/// dropped, and the client's prediction stats.
+fn run(deadline_ms: u64) -> (u32, PredictionStats) {
+	NOW_US.set(0);
+	let conditions = SimulatedConditions { latency_ms: LATENCY_MS, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_input_deadline(deadline_ms as f64 / 1000.0)
+		.with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])]);
+	let mut transport = SimulatedTransport::new(server, conditions, 7).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(MARGIN);
+	let mut accumulator = TickAccumulator::default();
+	// The client's ticks, along with when they're let through.
+	let mut held_back = VecDeque::new();
+
+	for number in 1..=SECONDS * FPS {
+		let now_us = number * 1_000_000 / FPS;
+		NOW_US.set(now_us);
+		// Not paced, which would have the client fall back as far as the
+		// server's answers do, deadline and all, and keep its frames late.
+		let due_ticks = accumulator.advance(1.0 / FPS as f32, game.tick_rate().delta());
+
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(now());
+		if let Some(request) = game.time_request(now()) {
+			send(&mut transport, request);
+		}
+		for _ in 0..due_ticks {
+			if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+				held_back.push_back((now_us + HELD_BACK_MS * 1000, packet));
+				game.tick_sent(now());
+			}
+		}
+		while held_back.front().is_some_and(|(due, _)| *due <= now_us) {
+			let (_, packet) = held_back.pop_front().unwrap();
+			send(&mut transport, packet);
+		}
+	}
+	assert_eq!(game.desync(), None, "the client desynced");
+	assert_eq!(game.connection_state(), ConnectionState::Connected);
+	let head = game.accepted_head();
+	assert!(head > SECONDS * TICKRATE as u64 * 9 / 10, "the client only got to tick {}", head);
+	(transport.inner().late_frames(), game.prediction_stats().clone())
+}
+
+#[test]
+fn frames_past_the_deadline_get_corrected_to_empty_ones() {
+	let (late, stats) = run(0);
+	let ticks = SECONDS * TICKRATE as u64 / 2;
+	assert!(late as u64 > ticks, "only {} frames came late", late);
+	assert!(stats.local_frames_lost > ticks, "only {} ticks had the local frame corrected away", stats.local_frames_lost);
+}
+
+#[test]
+fn a_long_enough_deadline_has_the_same_frames_make_it() {
+	// Waiting for longer than they're held back, nothing comes late, and
+	// the client's predictions, in canonical order, are the server's.
+	let (late, stats) = run(DEADLINE_MS);
+	assert_eq!(late, 0);
+	assert_eq!(stats.local_frames_lost, 0);
+	let ticks = SECONDS * TICKRATE as u64 / 2;
+	assert!(stats.confirmed > ticks, "only {} ticks got confirmed", stats.confirmed);
+}
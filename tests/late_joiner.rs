@@ -0,0 +1,102 @@
+//! Has a second client join halfway through a game, catching up from a
+//! snapshot of the first one's world rather than from tick 0, and checks
+//! that both simulate everything after it the same.
+
+mod common;
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+/// When the second client joins.
+const JOIN: u64 = 40;
+const END: u64 = 120;
+
+/// Player 0 runs right and jumps every so often; player 1 runs left, and
+/// jumps too once it's joined.
+fn intents(index: u64, owner: u16) -> Vec<PlayerIntent> {
+	match (owner, index % 15) {
+		(0, 0) => vec![MoveRight, JumpStart],
+		(0, _) => vec![MoveRight],
+		(_, 7) if index > JOIN => vec![MoveLeft, JumpStart],
+		(_, _) => vec![MoveLeft],
+	}
+}
+
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	Tick::new(index, vec![CommandFrame::new(0, intents(index, 0)), CommandFrame::new(1, intents(index, 1))])
+}
+
+/// Runs ticks `range` on a client playing `client_id`, with the server
+/// answering `LAG` ticks late.
+fn play(game: &mut Game, client_id: u16, range: std::ops::Range<u64>) {
+	for index in range {
+		if index >= LAG {
+			deliver(game, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		game.receive(index as f64 * TICK_DELTA as f64);
+		game.run_tick(|_| intents(index, client_id));
+	}
+}
+
+/// The first client, `JOIN` ticks in, and the second, having just caught
+/// up from its snapshot.
+fn joined() -> (Game, Game) {
+	let mut first = Game::new(0);
+	deliver(&mut first, Packet::Join(1));
+	first.receive(0.0);
+	play(&mut first, 0, 0..JOIN);
+
+	// The second client got a few ticks in on its own before the snapshot
+	// came, which have nothing to do with the game it's joining.
+	let mut second = Game::new(1);
+	for index in 0..5 {
+		second.receive(index as f64 * TICK_DELTA as f64);
+		second.run_tick(|_| vec![MoveRight, JumpStart]);
+	}
+	assert_ne!(second.players()[&1].position(), first.players()[&1].position());
+
+	let snapshot = first.snapshot();
+	deliver(&mut second, Packet::Snapshot(snapshot));
+	second.receive(JOIN as f64 * TICK_DELTA as f64);
+	(first, second)
+}
+
+#[test]
+fn the_snapshot_wins_over_what_the_joiner_had() {
+	// The snapshot's entry for the second client's own player wins over
+	// what it had, and the first client's player is where it should be.
+	let (first, second) = joined();
+	assert_eq!(second.state_hash(), first.state_hash(), "the snapshot came out as a different world");
+	for owner in [0, 1] {
+		assert_eq!(second.players()[&owner].position(), first.players()[&owner].position(), "player {}", owner);
+	}
+}
+
+#[test]
+fn ticks_from_before_the_snapshot_are_dropped() {
+	let (_, second) = joined();
+	assert_eq!(second.accepted_head(), JOIN - 1);
+	assert_eq!(second.latest_tick().map(|x| x.index()), None, "ticks from before the snapshot were kept");
+}
+
+#[test]
+fn both_simulate_everything_after_the_join_the_same() {
+	let (mut first, mut second) = joined();
+	play(&mut first, 0, JOIN..END);
+	play(&mut second, 1, JOIN..END);
+	let head = first.accepted_head();
+	assert!(head >= END - LAG - 2, "the server only got to tick {}", head);
+	assert_eq!(second.accepted_head(), head);
+	let mut compared = 0;
+	for index in JOIN..=head {
+		if let (Some(a), Some(b)) = (first.state_hash_at(index), second.state_hash_at(index)) {
+			assert_eq!(a, b, "tick {} simulated differently", index);
+			compared += 1;
+		}
+	}
+	assert!(compared >= 20, "only {} ticks were still around to compare", compared);
+	assert_eq!(second.players()[&1].position(), first.players()[&1].position());
+}
@@ -0,0 +1,233 @@
+//! Plays the same ticks on two separate games, and checks after every
+//! tick that both ended up with bit-identical worlds. Reconciliation relies
+//! on every client simulating a tick exactly the same way, whoever it's
+//! playing as and whichever order it learned about the players in.
+//!
+//! Each `Script` stands for a stretch of play that's sensitive to order,
+//! and new ones can be added to `SCRIPTS` as the physics grow. Every one of
+//! them gets played in the default world, and in a `tuned` one, where
+//! players have to keep to its walls and speed limit all the same.
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::demo::DemoScript;
+use command_networking_rs::game::{Game, SimConfig};
+use command_networking_rs::protocol::{ClientId, CommandFrame, PlayerIntent, Tick};
+use command_networking_rs::scalar::Scalar;
+use PlayerIntent::*;
+
+const TICKS: u64 = 1000;
+
+/// What every player does on every tick.
+struct Script {
+	name: &'static str,
+	players: ClientId,
+	intents: fn(tick: u64, player: ClientId) -> Vec<PlayerIntent>,
+}
+
+/// Where two games first disagreed.
+struct Divergence {
+	index: u64,
+	first: String,
+	second: String,
+}
+
+/// How far the players got, over a whole script.
+#[derive(Default)]
+struct Reach {
+	rightmost: Scalar,
+	fastest: Scalar,
+}
+
+#[cfg(not(feature = "fixed-point"))]
+fn scalar(value: f32) -> Scalar {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+fn scalar(value: f32) -> Scalar {
+	Scalar::from_f32(value)
+}
+
+/// A narrower world than the default one, with larger and faster players
+/// falling harder.
+fn tuned() -> SimConfig {
+	SimConfig {
+		gravity: scalar(1400.0),
+		move_speed: scalar(320.0),
+		acceleration: scalar(2400.0),
+		player_width: scalar(40.0),
+		player_height: scalar(36.0),
+		world_width: scalar(600.0),
+		..Default::default()
+	}
+}
+
+/// Everyone walks into one of the walls, and keeps jumping against it.
+fn edges(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	let towards = if player.is_multiple_of(2) { MoveLeft } else { MoveRight };
+	match tick % 40 {
+		0 => vec![towards, JumpStart],
+		1..=8 => vec![towards, JumpHold],
+		_ => vec![towards],
+	}
+}
+
+/// Pairs of players run at each other and jump on the same tick, colliding
+/// head on and landing on one another.
+fn opposites(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	let phase = (tick / 60).is_multiple_of(2);
+	let towards = if player.is_multiple_of(2) == phase { MoveRight } else { MoveLeft };
+	match tick % 30 {
+		10 => vec![towards, JumpStart],
+		11..=14 => vec![towards, JumpHold],
+		20..=24 => vec![],
+		_ => vec![towards],
+	}
+}
+
+/// More players than spawn points, so the last few spawn at random, maybe
+/// on top of someone else, then everyone piles into the middle of the screen.
+fn pileup(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	match (tick + player as u64 * 7) % 50 {
+		0 => vec![JumpStart],
+		1..=5 => vec![JumpHold],
+		_ if player < 4 => vec![MoveRight],
+		_ => vec![MoveHorizontal(-(player as i8 * 15))],
+	}
+}
+
+/// Anything goes, from a fixed seed.
+fn noise(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	let mut x = tick.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (player as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	x ^= x >> 29;
+	x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+	let horizontal = match x % 5 {
+		0 => Some(MoveLeft),
+		1 => Some(MoveRight),
+		2 => Some(MoveHorizontal((x >> 8) as i8)),
+		_ => None,
+	};
+	let jump = match (x >> 16) % 6 {
+		0 => Some(JumpStart),
+		1 | 2 => Some(JumpHold),
+		_ => None,
+	};
+	horizontal.into_iter().chain(jump).collect()
+}
+
+/// The demo the client plays while left alone, every player a little
+/// further into it than the last.
+fn demo(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	DemoScript::default().intents(tick + player as u64 * 17).to_vec()
+}
+
+const SCRIPTS: &[Script] = &[
+	Script { name: "jumping at the edges", players: 4, intents: edges },
+	Script { name: "simultaneous opposite moves", players: 6, intents: opposites },
+	Script { name: "colliding players", players: 9, intents: pileup },
+	Script { name: "random input", players: 8, intents: noise },
+	Script { name: "the demo", players: 5, intents: demo },
+];
+
+/// Every player's simulated state, in ClientId order, and the state hash.
+fn dump(game: &Game) -> String {
+	let mut dump = String::new();
+	for (client_id, player) in game.players() {
+		let mut writer = ByteWriter::new();
+		player.to_bytes(&mut writer).unwrap();
+		let bytes: String = writer.as_slice().iter().map(|x| format!("{:02x}", x)).collect();
+		dump += &format!("  player {} at {:?}: {}\n", client_id, player.position(), bytes);
+	}
+	let hash: String = game.state_hash().iter().map(|x| format!("{:02x}", x)).collect();
+	dump + &format!("  state hash {}", hash)
+}
+
+/// A game with every one of `players` in it from tick 0, as seen by
+/// `client_id`, having learned about the others in `order`.
+fn game(client_id: ClientId, order: impl Iterator<Item = ClientId>, config: SimConfig) -> Game {
+	let mut game = Game::new(client_id);
+	game.set_sim_config(config);
+	for player in order {
+		game.add_local_player(player);
+	}
+	game
+}
+
+/// Panics unless every player in `game` is inside its world, moving no
+/// faster than it allows, and keeps track of how far they got.
+fn check_bounds(game: &Game, index: u64, reach: &mut Reach) {
+	let config = game.sim_config();
+	for (client_id, player) in game.players() {
+		let (x, y) = player.position();
+		let speed = player.velocity().0.abs();
+		assert!(x >= scalar(0.0) && x <= config.max_x() && y <= config.floor_y(), "player {} left the world on tick {}, at {:?}", client_id, index, (x, y));
+		assert!(speed <= config.move_speed, "player {} moved at {:?} on tick {}", client_id, speed, index);
+		reach.rightmost = reach.rightmost.max(x);
+		reach.fastest = reach.fastest.max(speed);
+	}
+}
+
+/// Plays `script` on two games in a world set up as `config`, one playing
+/// as the first player and one as the last, comparing them after every
+/// tick.
+fn run(script: &Script, config: SimConfig) -> Result<Reach, Divergence> {
+	let last = script.players - 1;
+	let mut first = game(0, 0..script.players, config);
+	let mut second = game(last, (0..script.players).rev(), config);
+	let mut reach = Reach::default();
+
+	for index in 0..TICKS {
+		let command_frames = (0..script.players)
+			.map(|player| CommandFrame::new(player, (script.intents)(index, player)))
+			.collect();
+		let tick = Tick::new(index, command_frames);
+		first.advance(tick.clone());
+		second.advance(tick);
+
+		let (a, b) = (dump(&first), dump(&second));
+		if a != b {
+			return Err(Divergence { index, first: a, second: b });
+		}
+		check_bounds(&first, index, &mut reach);
+	}
+	Ok(reach)
+}
+
+/// Plays every script in a world set up as `config`, panicking on the
+/// first divergence, and returns how far anyone got over all of them.
+fn play(world: &str, config: SimConfig) -> Reach {
+	let mut reach = Reach::default();
+	for script in SCRIPTS {
+		match run(script, config) {
+			Ok(script) => {
+				reach.rightmost = reach.rightmost.max(script.rightmost);
+				reach.fastest = reach.fastest.max(script.fastest);
+			},
+			Err(divergence) => panic!(
+				"{} in the {} world: the games diverged on tick {}\nplaying as the first player:\n{}\nplaying as the last:\n{}",
+				script.name, world, divergence.index, divergence.first, divergence.second
+			),
+		}
+	}
+	reach
+}
+
+#[test]
+fn every_script_plays_out_the_same_in_the_default_world() {
+	let reach = play("default", SimConfig::default());
+	// Someone made it all the way to the wall.
+	assert_eq!(reach.rightmost, SimConfig::default().max_x(), "nobody reached the right wall");
+}
+
+#[test]
+fn every_script_plays_out_the_same_in_a_tuned_world() {
+	let reach = play("tuned", tuned());
+	// Wherever the config put the wall.
+	assert_eq!(reach.rightmost, tuned().max_x(), "nobody reached the tuned world's right wall");
+}
+
+#[test]
+fn players_in_a_tuned_world_get_faster() {
+	let fastest = play("tuned", tuned()).fastest;
+	assert!(fastest > SimConfig::MOVE_SPEED, "nobody in the tuned world outran the default top speed, at {:?}", fastest);
+}
@@ -0,0 +1,86 @@
+//! Checks the runtime log filter: levels less important than the one set
+//! are skipped without even formatting their arguments, and 0 silences
+//! everything. Decoding a malformed packet logs a warning to stderr.
+
+use std::cell::Cell;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use command_networking_rs::game::Game;
+use command_networking_rs::log::{self, LogLevel};
+
+const LEVELS: [LogLevel; 5] = [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace];
+
+/// The level is the whole process's, so the tests setting it take turns.
+static LOG: Mutex<()> = Mutex::new(());
+
+/// A turn at the log, which puts the level back to the default once done.
+struct Turn {
+	_log: MutexGuard<'static, ()>,
+}
+
+impl Drop for Turn {
+	fn drop(&mut self) {
+		log::set_level(Some(LogLevel::Info));
+	}
+}
+
+fn turn() -> Turn {
+	Turn { _log: LOG.lock().unwrap_or_else(PoisonError::into_inner) }
+}
+
+#[test]
+fn levels_come_back_from_their_numbers() {
+	for level in LEVELS {
+		assert_eq!(LogLevel::from_u8(level as u8), Some(level));
+	}
+	assert_eq!(LogLevel::from_u8(0), None);
+	assert_eq!(LogLevel::from_u8(200), Some(LogLevel::Trace));
+}
+
+#[test]
+fn info_is_the_default() {
+	let _turn = turn();
+	assert!(log::enabled(LogLevel::Info));
+	assert!(!log::enabled(LogLevel::Debug));
+}
+
+#[test]
+fn only_levels_as_important_as_the_one_set_are_enabled() {
+	let _turn = turn();
+	for threshold in LEVELS {
+		log::set_level(Some(threshold));
+		for level in LEVELS {
+			assert_eq!(log::enabled(level), level <= threshold, "{:?} at {:?}", level, threshold);
+		}
+	}
+}
+
+#[test]
+fn none_silences_everything() {
+	let _turn = turn();
+	log::set_level(None);
+	assert!(LEVELS.iter().all(|x| !log::enabled(*x)));
+}
+
+#[test]
+fn skipped_levels_dont_format_their_arguments() {
+	let _turn = turn();
+	let formatted = Cell::new(0);
+	let argument = || {
+		formatted.set(formatted.get() + 1);
+		0
+	};
+	log::set_level(Some(LogLevel::Warn));
+	command_networking_rs::log!(LogLevel::Debug, "skipped value={}", argument());
+	assert_eq!(formatted.get(), 0);
+	command_networking_rs::log!(LogLevel::Warn, "example value={}", argument());
+	assert_eq!(formatted.get(), 1);
+}
+
+#[test]
+fn a_malformed_packet_logs_a_warning() {
+	let _turn = turn();
+	// A truncated packet, which shows up as a `packet_rejected` warning.
+	Game::new(0).dispatch_packet(&[0xff]);
+	let last = log::recent().pop().unwrap_or_default();
+	assert!(last.starts_with("[warn] packet_rejected"), "logged {:?}", last);
+}
@@ -0,0 +1,137 @@
+//! Plays 500 ticks against an in-process server over a network losing a
+//! tenth of the packets either way, with a scripted remote player the
+//! client keeps mispredicting. The client has to end up with the very
+//! world the server has: its confirmed state at the server's latest tick
+//! hashes the same as a world built from the server's snapshot of it.
+
+mod common;
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::{ConnectionState, Game, TickAccumulator};
+use command_networking_rs::protocol::{Packet, PlayerIntent};
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use PlayerIntent::*;
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 2;
+const REMOTE: u16 = 3;
+const TICKS: u64 = 500;
+const FPS: u64 = 60;
+/// How many ticks' outcomes the server sends again along with each one.
+const REDUNDANCY: usize = 3;
+
+/// The local player runs back and forth, jumping every so often.
+fn intents(index: u64) -> Vec<PlayerIntent> {
+	let towards = if (index / 60).is_multiple_of(2) { MoveRight } else { MoveLeft };
+	match index % 25 {
+		0 => vec![towards, JumpStart],
+		1..=4 => vec![towards, JumpHold],
+		_ => vec![towards],
+	}
+}
+
+/// The remote player changes its mind every couple of seconds, which the
+/// client only hears of once it's already predicted otherwise.
+fn schedule() -> Vec<(u64, Vec<PlayerIntent>)> {
+	(0..TICKS / 40)
+		.map(|x| match x % 3 {
+			0 => (x * 40, vec![MoveLeft]),
+			1 => (x * 40, vec![MoveRight, JumpStart]),
+			_ => (x * 40, vec![]),
+		})
+		.collect()
+}
+
+struct Client {
+	game: Game,
+	transport: SimulatedTransport<LoopbackTransport>,
+	accumulator: TickAccumulator,
+	frames: u64,
+	/// The first frame the client lost track of the server on, and how.
+	lost_track: Option<(u64, ConnectionState)>,
+}
+
+impl Client {
+	/// Runs a frame, predicting however many ticks are due.
+	fn frame(&mut self) {
+		self.frames += 1;
+		NOW_US.set(self.frames * 1_000_000 / FPS);
+		let due_ticks = self.accumulator.advance(self.game.tick_speed() / FPS as f32, self.game.tick_rate().delta());
+		let game = &mut self.game;
+		self.transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(now());
+		let requests = [game.time_request(now()), game.snapshot_request(now())];
+		for request in requests.into_iter().flatten() {
+			send(&mut self.transport, request);
+		}
+		for _ in 0..due_ticks {
+			let index = game.latest_tick().map_or(0, |x| x.index() + 1);
+			if let Some(packet) = game.run_tick(|_| intents(index)) {
+				send(&mut self.transport, packet);
+				game.tick_sent(now());
+			}
+		}
+		let state = game.connection_state();
+		if matches!(state, ConnectionState::Disconnected | ConnectionState::Resyncing) {
+			self.lost_track.get_or_insert((self.frames, state));
+		}
+	}
+}
+
+/// Plays until the client's predicted `TICKS` ticks.
+fn play() -> Client {
+	NOW_US.set(0);
+	let conditions = SimulatedConditions { latency_ms: 40, jitter_ms: 10, loss_percent: 10, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, schedule())
+		.with_redundancy(REDUNDANCY);
+	let mut client = Client {
+		game: Game::new(LOCAL),
+		transport: SimulatedTransport::new(server, conditions, 11).with_clock(now),
+		accumulator: TickAccumulator::default(),
+		frames: 0,
+		lost_track: None,
+	};
+	client.game.sync_clock(2);
+	while client.game.latest_tick().is_none_or(|x| x.index() < TICKS) {
+		client.frame();
+		assert!(client.frames < 2 * TICKS * FPS / 20, "only got to tick {:?}", client.game.latest_tick().map(|x| x.index()));
+	}
+	client
+}
+
+#[test]
+fn stays_connected_at_ten_percent_loss() {
+	let client = play();
+	assert_eq!(client.lost_track, None);
+}
+
+#[test]
+fn converges_at_ten_percent_loss() {
+	let mut client = play();
+	// The server's world as of its latest tick, which the client has yet
+	// to hear the last of.
+	let snapshot = client.transport.inner().snapshot();
+	let mut server = Game::new(LOCAL);
+	let mut writer = ByteWriter::new();
+	Packet::<PlayerIntent>::Snapshot(snapshot).to_bytes(&mut writer).unwrap();
+	server.dispatch_packet(writer.as_slice());
+	server.receive(now());
+	let index = server.accepted_head();
+	assert!(index + 20 >= TICKS, "the server only got to tick {}", index);
+
+	let waited = client.frames;
+	while client.game.accepted_head() < index {
+		client.frame();
+		assert!(client.frames - waited < 5 * FPS, "stuck at tick {} of {}", client.game.accepted_head(), index);
+	}
+	assert_eq!(client.game.desync(), None, "the client desynced");
+	assert_eq!(client.game.state_hash_at(index), Some(server.state_hash()), "the client ended up elsewhere than the server on tick {}", index);
+}
+
+#[test]
+fn the_remote_player_gets_mispredicted_along_the_way() {
+	let client = play();
+	assert!(client.game.prediction_stats().corrected > 0, "nothing got mispredicted");
+}
@@ -0,0 +1,222 @@
+//! Plays against an in-process server gone rogue. Along with everything
+//! it's meant to send, the host floods the client with more packets than
+//! the inbox holds: ticks from far ahead, long ago, and from players that
+//! don't exist, joins and names for thousands of clients, and chat. With
+//! the transport turning sends down for a bit too, and a replay being
+//! recorded, every cap in a tight memory budget has to hold, frame after
+//! frame, and frames have to stay quick. There's not much playing to be
+//! done meanwhile, with the server's ticks crowded out, but once the flood
+//! is over, the client gets back in step, and plays on.
+
+mod common;
+
+use std::cell::Cell;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use command_networking_rs::{drain_inbox, push_packet, ByteWriter, NetType};
+use command_networking_rs::game::{ConnectionState, Game, MemoryBudget, TickAccumulator};
+use command_networking_rs::log;
+use command_networking_rs::overlay::DebugStats;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerInfo, PlayerIntent, Tick};
+use command_networking_rs::replay::Replay;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const FPS: u64 = 60;
+/// Packets the host floods the inbox with every frame, on top of the
+/// server's own.
+const FLOOD: u64 = 300;
+/// The slowest a frame may get, flood and all.
+const MAX_FRAME: Duration = Duration::from_millis(50);
+
+const BUDGET: MemoryBudget = MemoryBudget {
+	history_ticks: 64,
+	inbox_packets: 128,
+	buffered_ticks: 40,
+	pending_ticks: 8,
+	pending_corrections: 16,
+	resend_packets: 4,
+	replay_bytes: 1024,
+	players: 16,
+	sim_events: 64,
+};
+
+/// The inbox is the whole process's, so the tests flooding it take turns.
+static INBOX: Mutex<()> = Mutex::new(());
+
+thread_local! {
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+}
+
+type Client = SimulatedTransport<LoopbackTransport>;
+
+fn encode(packet: Packet<PlayerIntent>) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn send(game: &mut Game, transport: &mut Client, packet: Packet<PlayerIntent>) {
+	game.send(transport, &encode(packet), now());
+}
+
+/// The `number`th packet of the flood, somewhere around `head`.
+fn hostile_packet(number: u64, head: u64) -> Packet<PlayerIntent> {
+	let stranger = 100 + (number % 5000) as u16;
+	let frames = vec![CommandFrame::new(stranger, vec![PlayerIntent::JumpStart])];
+	match number % 6 {
+		// Far enough ahead of anything we'd predict.
+		0 => Packet::AuthoritativeTick(Tick::new(head + 10_000 + number, frames)),
+		1 => Packet::AuthoritativeTick(Tick::new(head.saturating_sub(number % 50), frames)),
+		2 => Packet::Join(stranger),
+		3 => Packet::PlayerInfo(PlayerInfo::new(stranger, "flooder")),
+		4 => Packet::Chat { sender: stranger, text: "spam".to_string() },
+		_ => Packet::Received { index: head + number },
+	}
+}
+
+/// What went on over a stretch of frames.
+#[derive(Default)]
+struct Run {
+	predicted: u32,
+	slowest: Duration,
+	states: Vec<ConnectionState>,
+}
+
+/// Runs `seconds` worth of frames the way the client does, auditing the
+/// budget every frame. The host floods the inbox while `flooding`; the
+/// transport turns sends down over the frames `rejecting` says.
+fn play(game: &mut Game, transport: &mut Client, accumulator: &mut TickAccumulator, seconds: u64, flooding: bool, rejecting: impl Fn(u64) -> bool) -> Run {
+	let mut run = Run::default();
+	let mut flooded = 0;
+	for frame in 0..seconds * FPS {
+		let number = FRAMES.get() + 1;
+		FRAMES.set(number);
+		NOW_US.set(number * 1_000_000 / FPS);
+		transport.simulated_conditions().unwrap().rejecting = rejecting(frame);
+		let started = Instant::now();
+		let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+
+		transport.poll(&mut |packet| push_packet(packet.to_vec()));
+		if flooding {
+			for _ in 0..FLOOD {
+				push_packet(encode(hostile_packet(flooded, game.accepted_head())));
+				flooded += 1;
+			}
+		}
+		// The inbox is at its fullest right before it's drained.
+		game.assert_within_budget();
+		drain_inbox(game, |_| {});
+		game.receive(now());
+		game.resend(transport, now());
+		if let Some(request) = game.time_request(now()) {
+			send(game, transport, request);
+		}
+		if let Some(request) = game.snapshot_request(now()) {
+			send(game, transport, request);
+		}
+		for _ in 0..due_ticks {
+			if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+				send(game, transport, packet);
+				game.tick_sent(now());
+				run.predicted += 1;
+			}
+		}
+		game.flush(transport, now());
+		game.assert_within_budget();
+
+		run.slowest = run.slowest.max(started.elapsed());
+		if run.states.last() != Some(&game.connection_state()) {
+			run.states.push(game.connection_state());
+		}
+	}
+	run
+}
+
+/// A client that got going against the rogue server, then played through
+/// five seconds of its flood, and what went on over the flood.
+struct Flooded {
+	game: Game,
+	transport: Client,
+	accumulator: TickAccumulator,
+	flood: Run,
+	_inbox: MutexGuard<'static, ()>,
+}
+
+fn flooded() -> Flooded {
+	let inbox = INBOX.lock().unwrap_or_else(PoisonError::into_inner);
+	let conditions = SimulatedConditions { latency_ms: 40, jitter_ms: 5, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL).with_clock(now).with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])]);
+	let mut transport = SimulatedTransport::new(server, conditions, 11).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.set_memory_budget(BUDGET);
+	assert_eq!(*game.memory_budget(), BUDGET);
+	game.sync_clock(2);
+	game.start_recording();
+	let mut accumulator = TickAccumulator::default();
+
+	let calm = play(&mut game, &mut transport, &mut accumulator, 2, false, |_| false);
+	assert_eq!(calm.states.last(), Some(&ConnectionState::Connected));
+	assert!(game.accepted_head() > 20, "never got going");
+
+	// The flood, with the transport turning sends down for a quarter of a
+	// second in every second of it; not long enough to give up over.
+	log::set_level(None);
+	let flood = play(&mut game, &mut transport, &mut accumulator, 5, true, |x| x % FPS < FPS / 4);
+	log::set_level(Some(log::LogLevel::Info));
+	Flooded { game, transport, accumulator, flood, _inbox: inbox }
+}
+
+#[test]
+fn every_cap_holds_through_a_flood() {
+	let Flooded { game, flood, .. } = flooded();
+	assert!(flood.slowest < MAX_FRAME, "a frame of the flood took {:?}", flood.slowest);
+	let usage = game.memory_usage();
+	for name in ["buffered_ticks", "pending_ticks", "players"] {
+		let usage = usage.iter().find(|x| x.subsystem == name).unwrap();
+		assert_eq!(usage.used, usage.cap, "the flood didn't fill {}", name);
+	}
+	let mut stats = DebugStats::default();
+	game.update_debug_stats(&mut stats, now());
+	assert!(stats.stale_ticks > 0, "none of the flood's ticks got thrown away");
+	assert_eq!(stats.memory, usage);
+}
+
+#[test]
+fn the_client_gets_back_in_step_once_the_flood_is_over() {
+	let Flooded { mut game, mut transport, mut accumulator, .. } = flooded();
+	// Once it's over, the server's ticks get through again.
+	let head = game.accepted_head();
+	let after = play(&mut game, &mut transport, &mut accumulator, 8, false, |_| false);
+	assert_eq!(game.connection_state(), ConnectionState::Connected, "went through {:?}", after.states);
+	assert!(after.predicted > 100 && game.accepted_head() > head + 100, "never got going again, at {}", game.accepted_head());
+	assert!(after.slowest < MAX_FRAME);
+}
+
+#[test]
+fn a_full_replay_stops_recording_and_still_plays_back() {
+	let Flooded { game, .. } = flooded();
+	let recorded = game.memory_usage().into_iter().find(|x| x.subsystem == "replay_bytes").unwrap();
+	assert!(recorded.used > recorded.cap / 2, "only recorded {} bytes", recorded.used);
+	let replay = game.export_replay().unwrap();
+	let replay = Replay::parse(&replay).expect("the replay got cut off mid-entry");
+	assert!(replay.tick_range().is_some(), "nothing got recorded");
+}
+
+#[test]
+fn the_audit_goes_off_once_anything_is_over_its_cap() {
+	let strict = MemoryBudget { history_ticks: 1, ..BUDGET };
+	let mut tight = Game::new(LOCAL);
+	for _ in 0..4 {
+		let tick = tight.predict_tick(vec![]);
+		tight.advance(tick);
+	}
+	tight.set_memory_budget(strict);
+	let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tight.assert_within_budget()));
+	let message = caught.expect_err("a history over its cap went unnoticed");
+	let message = message.downcast_ref::<String>().unwrap();
+	assert!(message.starts_with("history_ticks over its memory budget"), "{}", message);
+}
@@ -0,0 +1,158 @@
+//! Batches packets up the way the host transport does over a frame, to
+//! check that they split back into the same packets, that whatever doesn't
+//! fit into one batch spills over into the next, that a flush turned down
+//! halfway goes on from there the next time, and that malformed batches
+//! are rejected whole. The loopback server gets a batch too, and has to
+//! answer every packet in it.
+
+use command_networking_rs::{drain_inbox, push_packet, ByteWriter, DecodeError, NetType};
+use command_networking_rs::batch::{batch_size, encode_batch, is_batch, split_batch, Coalescer, MAX_BATCH_PACKETS, MAX_BATCH_SIZE};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{Packet, PlayerIntent, TimeRequest, PROTOCOL_VERSION};
+use command_networking_rs::transport::{LoopbackTransport, Transport};
+
+fn encode(packet: &Packet<PlayerIntent>) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn chat(text: &str) -> Vec<u8> {
+	encode(&Packet::Chat { sender: 0, text: text.into() })
+}
+
+/// Pushes `packets` into `coalescer`, and flushes them into messages.
+fn flush(coalescer: &mut Coalescer, packets: &[Vec<u8>]) -> Vec<Vec<u8>> {
+	for packet in packets {
+		coalescer.push(packet);
+	}
+	let mut messages = vec![];
+	let count = coalescer.flush(|x| messages.push(x.to_vec()));
+	assert_eq!(count, messages.len());
+	assert!(coalescer.is_empty(), "packets were left over after flushing");
+	messages
+}
+
+/// Every packet in `messages`, in order.
+fn split_all(messages: &[Vec<u8>]) -> Vec<Vec<u8>> {
+	messages.iter()
+		.flat_map(|x| split_batch(x).expect("a flushed message didn't split"))
+		.map(<[u8]>::to_vec)
+		.collect()
+}
+
+#[test]
+fn packets_make_a_single_batch_and_split_back_up() {
+	let mut coalescer = Coalescer::default();
+	let packets = vec![chat("one"), chat("two"), chat("three")];
+	let messages = flush(&mut coalescer, &packets);
+	assert_eq!(messages.len(), 1, "a frame's worth of chat took {} messages", messages.len());
+	assert!(is_batch(&messages[0]));
+	assert_eq!(messages[0].len(), batch_size(&packets));
+	assert_eq!(split_all(&messages), packets);
+
+	let mut game = Game::new(0);
+	push_packet(messages[0].clone());
+	assert_eq!(drain_inbox(&mut game, |_| {}), 1);
+	game.receive(0.0);
+	let log: Vec<String> = game.chat().messages().map(|x| x.text.clone()).collect();
+	assert_eq!(log, ["one", "two", "three"], "the batch didn't reach the game packet by packet");
+}
+
+#[test]
+fn a_lone_packet_goes_out_as_it_is() {
+	let mut coalescer = Coalescer::default();
+	let messages = flush(&mut coalescer, &[chat("alone")]);
+	assert_eq!(messages, [chat("alone")], "a lone packet got batched");
+	assert_eq!(flush(&mut coalescer, &[]).len(), 0, "nothing to send still sent something");
+}
+
+#[test]
+fn packets_spill_over_by_size_and_by_count() {
+	// Room for two of them, but not three.
+	let packets = vec![chat("one"), chat("two"), chat("three")];
+	let mut coalescer = Coalescer::new(batch_size(&packets[..2]));
+	let messages = flush(&mut coalescer, &packets);
+	assert_eq!(messages.len(), 2, "overflowing packets took {} messages", messages.len());
+	assert!(is_batch(&messages[0]) && !is_batch(&messages[1]));
+	assert_eq!(split_all(&messages), packets);
+
+	let big = chat(&"x".repeat(100));
+	let mut coalescer = Coalescer::new(big.len());
+	let mixed = vec![chat("before"), big.clone(), chat("after")];
+	let messages = flush(&mut coalescer, &mixed);
+	assert_eq!(messages.len(), 3);
+	assert_eq!(messages[1], big, "a packet too big to batch wasn't sent as it is");
+	assert_eq!(split_all(&messages), mixed);
+
+	let many: Vec<Vec<u8>> = (0..MAX_BATCH_PACKETS + 10).map(|x| chat(&x.to_string())).collect();
+	let messages = flush(&mut Coalescer::default(), &many);
+	assert_eq!(messages.len(), 2, "{} packets took {} messages", many.len(), messages.len());
+	assert_eq!(split_batch(&messages[0]).unwrap().len(), MAX_BATCH_PACKETS);
+	assert_eq!(split_all(&messages), many);
+}
+
+#[test]
+fn truncated_outdated_and_overlong_batches_are_rejected_whole() {
+	let mut writer = ByteWriter::new();
+	encode_batch(&[chat("one"), chat("two"), chat("three")], &mut writer);
+	let batch = writer.as_slice().to_vec();
+	let truncated = &batch[..batch.len() - 1];
+	assert!(matches!(split_batch(truncated), Err(DecodeError::LengthOutOfBounds { .. })));
+	let mut trailing = batch.clone();
+	trailing.push(0);
+	assert_eq!(split_batch(&trailing), Err(DecodeError::TrailingBytes { count: 1 }));
+	let mut outdated = batch.clone();
+	outdated[2] = PROTOCOL_VERSION - 1;
+	assert_eq!(split_batch(&outdated), Err(DecodeError::UnsupportedVersion { got: PROTOCOL_VERSION - 1, expected: PROTOCOL_VERSION }));
+	let mut overlong = ByteWriter::new();
+	encode_batch(&[vec![0; MAX_BATCH_SIZE / 2], vec![0; MAX_BATCH_SIZE / 2]], &mut overlong);
+	assert!(matches!(split_batch(overlong.as_slice()), Err(DecodeError::PacketTooLarge { .. })));
+
+	// Not even the packets before the damage make it through.
+	let mut game = Game::new(0);
+	game.dispatch_packet(truncated);
+	game.receive(0.0);
+	assert_eq!(game.chat().messages().count(), 0, "a malformed batch got dispatched");
+}
+
+#[test]
+fn a_flush_turned_down_halfway_picks_up_where_it_left_off() {
+	// Turned down halfway, whatever didn't go out is kept for the next
+	// flush, in the same order.
+	let packets = vec![chat("one"), chat("two"), chat("three"), chat("four")];
+	let mut coalescer = Coalescer::new(batch_size(&packets[..2]));
+	for packet in &packets {
+		coalescer.push(packet);
+	}
+	let mut messages = vec![];
+	let sent = coalescer.try_flush(|x| {
+		if !messages.is_empty() {
+			return Err("full");
+		}
+		messages.push(x.to_vec());
+		Ok(())
+	});
+	assert_eq!(sent, Err("full"));
+	assert_eq!(coalescer.len(), packets.len() - split_all(&messages).len());
+	coalescer.push(&chat("five"));
+	let sent = coalescer.try_flush(|x| {
+		messages.push(x.to_vec());
+		Ok::<(), ()>(())
+	});
+	assert!(sent.is_ok_and(|x| x > 0));
+	assert!(coalescer.is_empty());
+	assert_eq!(split_all(&messages), [packets, vec![chat("five")]].concat());
+}
+
+#[test]
+fn the_loopback_server_answers_every_packet_in_a_batch() {
+	let mut transport = LoopbackTransport::new(0);
+	let request = encode(&Packet::TimeRequest(TimeRequest { sender: 0, client_time: 1.5 }));
+	let messages = flush(&mut Coalescer::default(), &[request, chat("hello")]);
+	transport.send(&messages[0]).unwrap();
+	let mut answers = vec![];
+	transport.poll(&mut |bytes| answers.push(Packet::<PlayerIntent>::decode(bytes).unwrap()));
+	assert!(answers.iter().any(|x| matches!(x, Packet::TimeResponse(response) if response.client_time == 1.5)), "the time request in the batch went unanswered");
+	assert!(answers.iter().any(|x| matches!(x, Packet::Chat { text, .. } if text == "hello")), "the chat in the batch wasn't relayed");
+}
@@ -0,0 +1,94 @@
+//! Pushes crafted packets into the inbox, from another thread and from
+//! within a drain, to check that they all come out in order and reach the
+//! game, and that a full inbox drops the oldest packets first. Then checks
+//! that two games in the same process only take in their own packets.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use command_networking_rs::{drain_inbox, push_packet, ByteWriter, NetType, INBOX_CAPACITY};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{Packet, PlayerIntent};
+
+/// The inbox is the whole process's, so the tests pushing to it take turns.
+static INBOX: Mutex<()> = Mutex::new(());
+
+fn inbox() -> MutexGuard<'static, ()> {
+	INBOX.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn chat(text: &str) -> Vec<u8> {
+	let packet = Packet::<PlayerIntent>::Chat { sender: 1, text: text.into() };
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+/// The chat as the game has it, oldest first.
+fn chat_log(game: &Game) -> Vec<String> {
+	game.chat().messages().map(|x| x.text.clone()).collect()
+}
+
+#[test]
+fn packets_from_another_thread_come_out_in_order() {
+	let _inbox = inbox();
+	let mut game = Game::new(0);
+	push_packet(chat("first"));
+	std::thread::spawn(|| push_packet(chat("from a thread"))).join().unwrap();
+	assert_eq!(drain_inbox(&mut game, |_| {}), 2);
+	game.receive(0.0);
+	assert_eq!(chat_log(&game), ["first", "from a thread"]);
+}
+
+#[test]
+fn a_packet_pushed_while_draining_waits_for_the_next_drain() {
+	let _inbox = inbox();
+	let mut game = Game::new(0);
+	push_packet(chat("first"));
+	let mut pushed_again = false;
+	let drained = drain_inbox(&mut game, |_| {
+		// Waits for the next drain, rather than deadlocking this one.
+		if !std::mem::replace(&mut pushed_again, true) {
+			push_packet(chat("while draining"));
+		}
+	});
+	assert_eq!(drained, 1, "drained {} packets, rather than the 1 pushed before", drained);
+	assert_eq!(drain_inbox(&mut game, |_| {}), 1, "the packet pushed while draining got lost");
+	game.receive(0.0);
+	assert_eq!(chat_log(&game), ["first", "while draining"]);
+	assert_eq!(drain_inbox(&mut game, |_| {}), 0, "the inbox didn't empty");
+}
+
+#[test]
+fn a_full_inbox_drops_the_oldest_packets_first() {
+	let _inbox = inbox();
+	let mut game = Game::new(0);
+	let overflow = 3;
+	for number in 0..INBOX_CAPACITY + overflow {
+		push_packet(chat(&number.to_string()));
+	}
+	let mut first = None;
+	let drained = drain_inbox(&mut game, |packet| {
+		first.get_or_insert(packet.to_vec());
+	});
+	assert_eq!(drained, INBOX_CAPACITY, "the inbox held on to {} packets", drained);
+	assert_eq!(first, Some(chat(&overflow.to_string())), "the oldest packets weren't the ones dropped");
+	game.receive(0.0);
+	let newest = (INBOX_CAPACITY + overflow - 1).to_string();
+	assert_eq!(chat_log(&game).last(), Some(&newest));
+}
+
+#[test]
+fn two_games_side_by_side_only_take_in_their_own_packets() {
+	// Each game decodes into an inbox of its own, so a packet handed to
+	// one never shows up in the other, nor do its decode errors.
+	let mut game = Game::new(0);
+	let mut other = Game::new(0);
+	game.dispatch_packet(&chat("only for this one"));
+	other.dispatch_packet(&chat("only for the other"));
+	other.dispatch_packet(&[0xff]);
+	game.receive(0.0);
+	other.receive(0.0);
+	assert_eq!(chat_log(&game), ["only for this one"], "the other game's packet reached this one");
+	assert_eq!(chat_log(&other), ["only for the other"]);
+	assert!(game.last_decode_error().is_none() && other.last_decode_error().is_some());
+}
@@ -0,0 +1,152 @@
+//! Plays 1000-tick input scripts through two games built independently of
+//! each other, checking after every tick that their players are the same
+//! down to the last bit, and that the physics each script is about did get
+//! exercised along the way.
+//!
+//! With the `fixed-point` feature, where every platform has to come out the
+//! same, what each script ends up at is pinned as well; a change there is a
+//! change to the simulation, and breaks compatibility with older clients.
+//! Floats are only ever compared between two games on the same platform.
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::{Game, Player, SimConfig};
+use command_networking_rs::protocol::{ClientId, CommandFrame, PlayerIntent, Tick};
+use command_networking_rs::scalar::Scalar;
+use PlayerIntent::*;
+
+const TICKS: u64 = 1000;
+
+/// FNV-1a over every player's serialized state, in ClientId order. Unlike
+/// the state hash, it's the same whichever hasher the build uses.
+fn fingerprint(game: &Game) -> u64 {
+	let mut writer = ByteWriter::new();
+	for (client_id, player) in game.players() {
+		client_id.to_bytes(&mut writer).unwrap();
+		player.to_bytes(&mut writer).unwrap();
+	}
+	writer.as_slice().iter().fold(0xcbf2_9ce4_8422_2325, |hash, x| (hash ^ *x as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Plays `intents` for `players` on two games, one of which learned about
+/// them in reverse, handing every tick's players to `observe`. Returns the
+/// fingerprint they both ended up with.
+fn run(players: ClientId, intents: fn(u64, ClientId) -> Vec<PlayerIntent>, mut observe: impl FnMut(u64, &Game)) -> u64 {
+	let mut first = Game::new(0);
+	let mut second = Game::new(players - 1);
+	for player in 0..players {
+		first.add_local_player(player);
+		second.add_local_player(players - 1 - player);
+	}
+	for index in 0..TICKS {
+		let frames = (0..players).map(|x| CommandFrame::new(x, intents(index, x))).collect();
+		let tick = Tick::new(index, frames);
+		first.advance(tick.clone());
+		second.advance(tick);
+		assert_eq!(fingerprint(&first), fingerprint(&second), "the games diverged on tick {}", index);
+		observe(index, &first);
+	}
+	fingerprint(&first)
+}
+
+/// Checks that a script ended up where it always has, in fixed point.
+fn check_pinned(name: &str, fingerprint: u64, fixed_point: u64) {
+	if cfg!(feature = "fixed-point") {
+		assert_eq!(fingerprint, fixed_point, "{} ended up elsewhere than it used to, at {:016x}", name, fingerprint);
+	}
+}
+
+fn speed(player: &Player) -> Scalar {
+	player.velocity().0.abs()
+}
+
+/// Runs right for a second, lets go, then runs back left with a jump in
+/// the middle of it, over and over.
+fn accelerate(tick: u64, _: ClientId) -> Vec<PlayerIntent> {
+	match tick % 100 {
+		0..=19 => vec![MoveRight],
+		20..=49 => vec![],
+		50 => vec![MoveLeft, JumpStart],
+		51..=69 => vec![MoveLeft],
+		_ => vec![],
+	}
+}
+
+#[test]
+fn accelerating_and_braking() {
+	let mut speeds = vec![];
+	let fingerprint = run(2, accelerate, |index, game| {
+		if index < 50 {
+			speeds.push(speed(&game.players()[&0]));
+		}
+	});
+	// Speeding up over several ticks rather than all at once, to a steady
+	// speed that friction keeps under the top one.
+	let top = Game::new(0).sim_config().move_speed;
+	assert!(speeds[0] > Scalar::default() && speeds[0] < top, "got to {:?} on the first tick", speeds[0]);
+	assert!(speeds[0] < speeds[1] && speeds[1] < speeds[2], "didn't keep speeding up: {:?}", &speeds[..3]);
+	assert!(speeds[10..20].iter().all(|x| *x == speeds[19]) && speeds[19] <= top, "never settled: {:?}", &speeds[..20]);
+	// Then friction brings it to a halt once the key's let go.
+	assert!(speeds[21] < speeds[20] && speeds[20] < speeds[19], "didn't slow down: {:?}", &speeds[19..22]);
+	assert_eq!(speeds[49], Scalar::default(), "never came to a halt");
+	check_pinned("accelerating and braking", fingerprint, 0xa55d1b2006830dd2);
+}
+
+/// Both players head for the left wall, the second one pushing the first
+/// into it until it jumps on top of it. The first one then walks out from
+/// under it, and back.
+fn collide(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	match (player, tick % 120) {
+		(0, 80..=99) => vec![MoveRight],
+		(0, _) => vec![MoveLeft],
+		(_, 40) => vec![MoveLeft, JumpStart],
+		(_, 41..=45) => vec![MoveLeft, JumpHold],
+		(_, _) => vec![MoveLeft],
+	}
+}
+
+#[test]
+fn walking_into_each_other() {
+	let config = SimConfig::default();
+	let (width, height) = (config.player_width, config.player_height);
+	let (mut touching, mut stacked) = (0, 0);
+	let fingerprint = run(2, collide, |index, game| {
+		let (a, b) = (&game.players()[&0], &game.players()[&1]);
+		let ((ax, ay), (bx, by)) = (a.position(), b.position());
+		let apart = (ax - bx).abs() >= width || (ay - by).abs() >= height;
+		assert!(apart, "the players overlapped on tick {}, at {:?} and {:?}", index, a.position(), b.position());
+		touching += ((ax - bx).abs() == width && ay == by) as u32;
+		stacked += ((a.is_grounded() && ay == by - height) || (b.is_grounded() && by == ay - height)) as u32;
+	});
+	assert!(touching > 100, "the players only pushed against each other for {} ticks", touching);
+	assert!(stacked > 0, "neither player ever stood on the other one");
+	check_pinned("walking into each other", fingerprint, 0x5fcf0dba7a8e0632);
+}
+
+/// Anything goes, from a fixed seed.
+fn noise(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	let mut x = tick.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (player as u64 + 1).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	x ^= x >> 29;
+	x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+	let horizontal = match x % 5 {
+		0 => Some(MoveLeft),
+		1 => Some(MoveRight),
+		2 => Some(MoveHorizontal((x >> 8) as i8)),
+		_ => None,
+	};
+	let jump = match (x >> 16) % 6 {
+		0 => Some(JumpStart),
+		1 | 2 => Some(JumpHold),
+		_ => None,
+	};
+	horizontal.into_iter().chain(jump).collect()
+}
+
+#[test]
+fn random_input() {
+	let mut airborne = 0;
+	let fingerprint = run(6, noise, |_, game| {
+		airborne += game.players().values().filter(|x| !x.is_grounded()).count();
+	});
+	assert!(airborne > 1000, "hardly anyone jumped");
+	check_pinned("random input", fingerprint, 0x631a7d692688ff7e);
+}
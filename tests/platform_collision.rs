@@ -0,0 +1,121 @@
+//! Scripts a player around a few platforms, to check that they can be
+//! landed on, bumped into from below and run into from the side, and that
+//! walking off one leaves the player airborne until they're back down.
+//!
+//! Like `scripted_intents`, every tick is predicted and simulated right
+//! away, without a server.
+
+use command_networking_rs::game::{Game, Platform, SimConfig};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::scalar::Scalar;
+
+#[cfg(not(feature = "fixed-point"))]
+fn to_f32(value: Scalar) -> f32 {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+fn to_f32(value: Scalar) -> f32 {
+	value.to_f32()
+}
+
+/// A game on `platforms` alone, with the player standing at its spawn.
+fn game_on(platforms: Vec<Platform>) -> Game {
+	let mut game = Game::new(0);
+	game.set_platforms(platforms);
+	game
+}
+
+/// How high up the player stands on the floor, at its spawn.
+fn floor() -> Scalar {
+	Game::new(0).players()[&0].position().1
+}
+
+/// Runs for `ticks` with the same intents on every tick, and returns
+/// whether the player was grounded after each of them.
+fn run(game: &mut Game, intents: &[PlayerIntent], ticks: u32) -> Vec<bool> {
+	(0..ticks)
+		.map(|_| {
+			let tick = game.predict_tick(intents.to_vec());
+			game.advance(tick);
+			game.players()[&0].is_grounded()
+		})
+		.collect()
+}
+
+#[test]
+fn blocks_stop_players_walking_into_them() {
+	// A block on the ground, right in the way.
+	let wall = Platform::new(100.0, 196.0, 40.0, 60.0);
+	let mut game = game_on(vec![wall]);
+	run(&mut game, &[PlayerIntent::MoveRight], 60);
+	let player = &game.players()[&0];
+	assert!(player.position().0 < wall.x, "the player walked into a block");
+	assert!(to_f32(player.velocity().0) == 0.0, "the player kept pushing into a block");
+}
+
+/// A low step, jumped up onto from the floor, with the game as it is once
+/// the player has landed on it.
+fn on_step() -> (Game, Platform) {
+	let step = Platform::new(100.0, 236.0, 160.0, 20.0);
+	let mut game = game_on(vec![step]);
+	run(&mut game, &[PlayerIntent::MoveRight], 20);
+	run(&mut game, &[PlayerIntent::MoveRight, PlayerIntent::JumpStart], 1);
+	run(&mut game, &[PlayerIntent::MoveRight], 4);
+	let landed = run(&mut game, &[], 40);
+	assert!(landed.last() == Some(&true), "the player never landed on the step");
+	(game, step)
+}
+
+#[test]
+fn players_land_on_top_of_platforms() {
+	let floor = floor();
+	let (game, step) = on_step();
+	let player = &game.players()[&0];
+	assert!(player.position().1 < floor, "the player landed on the floor rather than the step");
+	assert!(player.position().1 + SimConfig::PLAYER_SIZE == step.y, "the player isn't standing on top of the step");
+}
+
+#[test]
+fn walking_off_a_platform_drops_players_back_down() {
+	let floor = floor();
+	let (mut game, _) = on_step();
+	let walked_off = run(&mut game, &[PlayerIntent::MoveRight], 40);
+	assert!(walked_off.contains(&false), "walking off the step never left the player airborne");
+	assert!(walked_off.last() == Some(&true), "the player never landed after walking off the step");
+	assert!(game.players()[&0].position().1 == floor, "the player didn't drop back to the floor");
+}
+
+#[test]
+fn ceilings_stop_jumps() {
+	let floor = floor();
+	// A ceiling just above the player's head.
+	let ceiling = Platform::new(0.0, 200.0, 100.0, 10.0);
+	let mut game = game_on(vec![ceiling]);
+	let mut highest = floor;
+	for _ in 0..20 {
+		let tick = game.predict_tick(vec![PlayerIntent::JumpStart, PlayerIntent::JumpHold]);
+		game.advance(tick);
+		highest = highest.min(game.players()[&0].position().1);
+	}
+	assert!(highest < floor, "the player never left the floor");
+	assert!(highest == ceiling.y + ceiling.h, "the player jumped through a ceiling");
+}
+
+#[test]
+fn runs_over_the_demo_level_are_deterministic() {
+	// The same run over the demo level twice, reaching the same state.
+	let parkour = || {
+		let mut game = Game::new(0);
+		for tick_index in 0..300u32 {
+			let mut intents = vec![PlayerIntent::MoveRight];
+			if tick_index % 25 == 0 {
+				intents.push(PlayerIntent::JumpStart);
+			}
+			let tick = game.predict_tick(intents);
+			game.advance(tick);
+		}
+		game.state_hash()
+	};
+	assert!(parkour() == parkour(), "the same run over the demo level ended up in different states");
+}
@@ -0,0 +1,50 @@
+//! Checks that the palette players are drawn with tells apart any 16
+//! consecutive ClientIds at a glance, and that none of them blends into the
+//! background.
+
+use command_networking_rs::game::color_for_client;
+use command_networking_rs::protocol::ClientId;
+use macroquad::color::Color;
+
+/// How many players have to be told apart at once.
+const PLAYERS: u16 = 16;
+/// The least distance between two colors, in RGB, still told apart.
+const MIN_DISTANCE: f32 = 0.1;
+/// What the game clears the screen with.
+const BACKGROUND: Color = Color::new(0.168, 0.149, 0.152, 1.0);
+
+fn distance(a: Color, b: Color) -> f32 {
+	((a.r - b.r).powi(2) + (a.g - b.g).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+/// Every run of `PLAYERS` consecutive ClientIds checked, including the
+/// last one there is.
+fn runs() -> impl Iterator<Item = Vec<ClientId>> {
+	[0, 1, 7, 1000, ClientId::MAX - PLAYERS + 1].into_iter().map(|first| (first..=first + (PLAYERS - 1)).collect())
+}
+
+#[test]
+fn a_client_always_gets_the_same_color() {
+	for id in runs().flatten() {
+		assert_eq!(color_for_client(id), color_for_client(id), "ClientId {} changed color", id);
+	}
+}
+
+#[test]
+fn no_client_blends_into_the_background() {
+	for id in runs().flatten() {
+		assert!(distance(color_for_client(id), BACKGROUND) > 0.4, "ClientId {} blends into the background", id);
+	}
+}
+
+#[test]
+fn consecutive_clients_are_told_apart() {
+	for ids in runs() {
+		for (i, a) in ids.iter().enumerate() {
+			for b in &ids[i + 1..] {
+				let apart = distance(color_for_client(*a), color_for_client(*b));
+				assert!(apart >= MIN_DISTANCE, "ClientIds {} and {} are only {:.3} apart", a, b, apart);
+			}
+		}
+	}
+}
@@ -0,0 +1,108 @@
+//! Has a remote player join a client's game, play for a while and leave,
+//! with the server's ticks lagging behind. The player spawns in the same
+//! place for everyone, stays around for the ticks it was in so that
+//! corrections to them still apply, and is gone after. Frames the server
+//! sends for it once it left don't bring it back.
+
+mod common;
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+/// When the remote player joins, and when it leaves.
+const JOIN: u64 = 10;
+const LEAVE: u64 = 40;
+
+/// The server's tick `index`, in which the remote player keeps jumping
+/// while it's in the game, and sends one more frame after it left.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	let mut frames = vec![CommandFrame::new(LOCAL, vec![])];
+	if (JOIN..=LEAVE + 5).contains(&index) {
+		frames.push(CommandFrame::new(REMOTE, vec![MoveLeft, JumpStart]));
+	}
+	Tick::new(index, frames)
+}
+
+/// Runs tick `index` on `game`, hearing of the remote player's joining
+/// and leaving as it happens, and of the server's ticks `LAG` late.
+fn frame(game: &mut Game, index: u64) {
+	match index {
+		JOIN => deliver(game, Packet::Join(REMOTE)),
+		LEAVE => deliver(game, Packet::Leave(REMOTE)),
+		_ => {},
+	}
+	if index >= LAG {
+		deliver(game, Packet::AuthoritativeTick(authoritative(index - LAG)));
+	}
+	game.receive(index as f64 * TICK_DELTA as f64);
+	game.run_tick(|_| vec![]);
+}
+
+/// Runs ticks `range` on `game`.
+fn play(game: &mut Game, range: std::ops::Range<u64>) {
+	for index in range {
+		frame(game, index);
+	}
+}
+
+#[test]
+fn the_player_spawns_in_the_same_place_for_everyone() {
+	let (mut first, mut second) = (Game::new(LOCAL), Game::new(LOCAL));
+	play(&mut first, 0..JOIN);
+	play(&mut second, 0..JOIN);
+	assert!(!first.players().contains_key(&REMOTE));
+
+	frame(&mut first, JOIN);
+	frame(&mut second, JOIN);
+	let spawn = first.players()[&REMOTE].position();
+	assert_ne!(spawn, Default::default(), "spawned at the origin");
+	assert_eq!(second.players()[&REMOTE].position(), spawn, "spawned somewhere else on another client");
+}
+
+#[test]
+fn the_servers_frames_for_the_player_apply_until_it_leaves() {
+	// Until it leaves, the server's corrections keep the player jumping.
+	let mut game = Game::new(LOCAL);
+	play(&mut game, 0..JOIN + 1);
+	let mut jumped = false;
+	for index in JOIN + 1..LEAVE {
+		frame(&mut game, index);
+		jumped |= !game.players()[&REMOTE].is_grounded();
+	}
+	assert!(jumped, "the server's frames for the player never got applied");
+	assert_eq!(game.rejected_frames(), 0, "frames from while it was in the game got rejected");
+}
+
+#[test]
+fn the_player_is_gone_as_soon_as_it_leaves() {
+	// It's in no tick predicted after it left, while the server's ticks
+	// from before that still come in and get taken.
+	let mut game = Game::new(LOCAL);
+	play(&mut game, 0..LEAVE + 1);
+	assert!(!game.players().contains_key(&REMOTE), "still around after leaving");
+	for index in LEAVE + 1..LEAVE + LAG {
+		frame(&mut game, index);
+		assert!(!game.players().contains_key(&REMOTE), "came back on tick {}", index);
+	}
+	assert_eq!(game.rejected_frames(), 0, "frames from before it left got rejected");
+	assert!(game.accepted_head() >= LEAVE - 2, "only got to tick {}", game.accepted_head());
+}
+
+#[test]
+fn frames_sent_for_the_player_after_it_left_are_rejected() {
+	// Once the ticks it left on are settled, the frames the server still
+	// sends for it are from a client that's gone.
+	let mut game = Game::new(LOCAL);
+	play(&mut game, 0..LEAVE + LAG);
+	for index in LEAVE + LAG..LEAVE + 20 {
+		frame(&mut game, index);
+		assert!(!game.players().contains_key(&REMOTE), "came back on tick {}", index);
+	}
+	assert!(game.rejected_frames() > 0, "frames for a client that left got through");
+	assert!(game.rejected_frames() <= 5, "rejected frames from before it left");
+}
@@ -0,0 +1,120 @@
+//! Sends display names around, to check that strings round-trip, that
+//! malformed and oversized ones are rejected while decoding, that names
+//! too long get cut short at a char boundary, and that a late joiner
+//! learns everyone's names from its snapshot.
+
+mod common;
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{Packet, PlayerInfo, MAX_NAME_LEN};
+use common::deliver;
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn decode<T: NetType>(bytes: &[u8]) -> Result<T, DecodeError> {
+	T::from_bytes(&mut ByteReader::new(bytes))
+}
+
+/// The infos in the introductions a game has to send.
+fn introductions(game: &mut Game) -> Vec<PlayerInfo> {
+	game.take_introductions()
+		.into_iter()
+		.map(|x| match x {
+			Packet::PlayerInfo(info) => info,
+			_ => panic!("introductions are only ever player infos"),
+		})
+		.collect()
+}
+
+/// A game that named its own player, heard of a remote one's name and of
+/// someone pretending to be its own player, and played a few ticks.
+fn host() -> Game {
+	let mut host = Game::new(0);
+	host.set_name(0, "Ada");
+	introductions(&mut host);
+	deliver(&mut host, Packet::PlayerInfo(PlayerInfo::new(3, "Cy")));
+	deliver(&mut host, Packet::PlayerInfo(PlayerInfo::new(0, "Impostor")));
+	host.receive(0.0);
+	for _ in 0..10 {
+		let tick = host.predict_tick(vec![]);
+		host.advance(tick);
+	}
+	host
+}
+
+#[test]
+fn strings_are_their_length_then_utf8() {
+	let name = String::from("Zoë");
+	assert_eq!(encode(&name), [4, b'Z', b'o', 0xc3, 0xab]);
+	assert_eq!(decode::<String>(&encode(&name)), Ok(name));
+	assert_eq!(decode::<String>(&[2, 0xc3, 0x28]), Err(DecodeError::InvalidUtf8 { what: "string" }));
+	assert!(matches!(decode::<String>(&[5, b'a']), Err(DecodeError::LengthOutOfBounds { len: 5, .. })));
+}
+
+#[test]
+fn names_too_long_get_cut_short_at_a_char_boundary() {
+	// Rather than rejected.
+	let long = "ü".repeat(MAX_NAME_LEN);
+	let info = PlayerInfo::new(2, &long);
+	assert_eq!(info.name, "ü".repeat(MAX_NAME_LEN / 2));
+	let info = PlayerInfo::new(2, &format!("a{}", long));
+	assert_eq!(info.name.len(), MAX_NAME_LEN - 1);
+	assert_eq!(PlayerInfo::new(2, "").name, "");
+	assert_eq!(decode::<PlayerInfo>(&encode(&info)), Ok(info));
+}
+
+#[test]
+fn only_names_cut_short_go_over_the_wire() {
+	let mut writer = ByteWriter::new();
+	2u16.to_bytes(&mut writer).unwrap();
+	"x".repeat(MAX_NAME_LEN + 1).to_bytes(&mut writer).unwrap();
+	assert_eq!(
+		decode::<PlayerInfo>(writer.as_slice()),
+		Err(DecodeError::LimitExceeded { what: "name", len: MAX_NAME_LEN + 1, max: MAX_NAME_LEN })
+	);
+}
+
+#[test]
+fn a_name_that_isnt_utf8_is_rejected() {
+	let mut writer = ByteWriter::new();
+	2u16.to_bytes(&mut writer).unwrap();
+	writer.write_varint(1);
+	writer.write_u8(0xff);
+	assert_eq!(decode::<PlayerInfo>(writer.as_slice()), Err(DecodeError::InvalidUtf8 { what: "name" }));
+}
+
+#[test]
+fn introductions_only_go_out_for_our_own_new_names() {
+	let mut host = Game::new(0);
+	assert!(introductions(&mut host).is_empty());
+	host.set_name(0, "Ada");
+	host.set_name(7, "Not ours");
+	assert_eq!(introductions(&mut host), [PlayerInfo::new(0, "Ada")]);
+	assert!(introductions(&mut host).is_empty());
+	assert_eq!(host.name(7), None);
+}
+
+#[test]
+fn names_relayed_from_others_are_taken_on_except_for_our_own_players() {
+	let host = host();
+	assert_eq!(host.name(3), Some("Cy"));
+	assert_eq!(host.name(0), Some("Ada"));
+}
+
+#[test]
+fn a_late_joiner_learns_everyones_names_from_the_snapshot() {
+	// And keeps its own.
+	let mut late = Game::new(1);
+	late.set_name(1, "Bea");
+	deliver(&mut late, Packet::Snapshot(host().snapshot()));
+	late.receive(0.0);
+	assert_eq!(late.name(0), Some("Ada"));
+	assert_eq!(late.name(3), Some("Cy"));
+	assert_eq!(late.name(1), Some("Bea"));
+	assert_eq!(introductions(&mut late), [PlayerInfo::new(1, "Bea")]);
+}
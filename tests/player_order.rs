@@ -0,0 +1,105 @@
+//! Has three games learn about the same players in different orders, some
+//! through Join packets and some as local players, then plays the same
+//! ticks on all of them. Players walk into each other and stack up, which
+//! comes out differently depending on who gets resolved first, so every
+//! game has to go through them in ascending ClientId order to stay the same.
+
+mod common;
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{ClientId, CommandFrame, Packet, PlayerIntent, Tick};
+use PlayerIntent::*;
+use common::deliver;
+
+const PLAYERS: ClientId = 6;
+const TICKS: u64 = 600;
+
+/// A game playing as `client_id`, told of everyone else by one Join packet
+/// each, in `order`.
+fn joined(client_id: ClientId, order: &[ClientId]) -> Game {
+	let mut game = Game::new(client_id);
+	for player in order.iter().filter(|x| **x != client_id) {
+		deliver(&mut game, Packet::Join(*player));
+	}
+	game.receive(0.0);
+	game
+}
+
+/// Everyone's serialized state, in the order `players` hands them out.
+fn dump(game: &Game) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	for (client_id, player) in game.players() {
+		client_id.to_bytes(&mut writer).unwrap();
+		player.to_bytes(&mut writer).unwrap();
+	}
+	writer.as_slice().to_vec()
+}
+
+/// The even players run right and the odd ones left, jumping at different
+/// times, so they keep meeting in the middle and landing on each other.
+fn intents(tick: u64, player: ClientId) -> Vec<PlayerIntent> {
+	let towards = if player.is_multiple_of(2) { MoveRight } else { MoveLeft };
+	match (tick + player as u64 * 5) % 40 {
+		0 => vec![towards, JumpStart],
+		1..=4 => vec![towards, JumpHold],
+		_ => vec![towards],
+	}
+}
+
+/// Plays the same ticks on three games that learned about the players in
+/// different orders, handing them to `observe` after every tick.
+fn play(mut observe: impl FnMut(u64, &[Game])) {
+	let mut games = [
+		joined(0, &[3, 1, 5, 2, 4]),
+		joined(5, &[4, 0, 2, 3, 1]),
+		{
+			let mut game = Game::new(2);
+			for player in [1, 5, 0, 4, 3] {
+				game.add_local_player(player);
+			}
+			game
+		},
+	];
+	for index in 0..TICKS {
+		let frames = (0..PLAYERS).map(|x| CommandFrame::new(x, intents(index, x))).collect();
+		let tick = Tick::new(index, frames);
+		for game in &mut games {
+			game.advance(tick.clone());
+		}
+		observe(index, &games);
+	}
+}
+
+#[test]
+fn every_game_goes_through_players_in_client_id_order() {
+	play(|index, games| {
+		for (which, game) in games.iter().enumerate() {
+			let ids: Vec<ClientId> = game.players().keys().copied().collect();
+			assert_eq!(ids, (0..PLAYERS).collect::<Vec<_>>(), "game {} on tick {}", which, index);
+		}
+	});
+}
+
+#[test]
+fn games_agree_whichever_order_they_learned_about_players_in() {
+	play(|index, games| {
+		let expected = dump(&games[0]);
+		for (which, game) in games.iter().enumerate().skip(1) {
+			assert!(dump(game) == expected, "game {} diverged from game 0 on tick {}", which, index);
+			assert_eq!(game.state_hash(), games[0].state_hash(), "game {} on tick {}", which, index);
+		}
+	});
+}
+
+#[test]
+fn players_land_on_each_other() {
+	// Or the order wouldn't have mattered.
+	let height = Game::new(0).sim_config().player_height;
+	let mut stacked = 0;
+	play(|_, games| {
+		let players = games[0].players();
+		stacked += players.values().any(|a| a.is_grounded() && players.values().any(|b| a.position().1 == b.position().1 - height)) as u32;
+	});
+	assert!(stacked > 0, "nobody ever stood on anyone else");
+}
@@ -0,0 +1,76 @@
+//! Plays against the in-process server, once with a remote player standing
+//! still and once with one reversing direction every few ticks. Repeating
+//! the last frame should never be wrong about the former, and keep being
+//! wrong about the latter. Then has a server leave out our own frames, as
+//! if they got lost, which the stats have to blame for the corrections.
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::prediction::PredictionStats;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::transport::{LoopbackTransport, Transport};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const TICKS: u64 = 300;
+
+fn play(mut transport: impl Transport) -> PredictionStats {
+	let mut game = Game::new(LOCAL);
+	let mut buffer = ByteWriter::new();
+	for index in 0..TICKS {
+		let now = index as f64 / 20.0;
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(now);
+		if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+			buffer.clear();
+			packet.to_bytes(&mut buffer).unwrap();
+			transport.send(buffer.as_slice()).unwrap();
+		}
+	}
+
+	let stats = game.prediction_stats().clone();
+	assert!(stats.confirmed + stats.corrected <= stats.predicted, "a tick got settled twice: {:?}", stats);
+	assert_eq!(stats.remote_intents + stats.local_frames_lost, stats.corrected);
+	stats
+}
+
+#[test]
+fn a_player_standing_still_never_gets_mispredicted() {
+	let stationary = play(LoopbackTransport::new(LOCAL).with_remote(REMOTE, vec![(0, vec![])]));
+	// Apart from the very first tick, which has no last frame to repeat.
+	assert!(stationary.corrected <= 1, "a player standing still got mispredicted: {:?}", stationary);
+	assert_eq!(stationary.rolling_misprediction_rate(), Some(0.0));
+}
+
+#[test]
+fn a_player_reversing_keeps_getting_mispredicted() {
+	let schedule = (0..TICKS)
+		.step_by(3)
+		.map(|x| match x % 2 == 0 {
+			true => (x, vec![PlayerIntent::MoveLeft]),
+			false => (x, vec![PlayerIntent::MoveRight]),
+		})
+		.collect();
+	let reversing = play(LoopbackTransport::new(LOCAL).with_remote(REMOTE, schedule));
+	let rate = reversing.rolling_misprediction_rate().unwrap();
+	assert!(rate > 25.0, "direction changes every 3 ticks only got {:.0}% mispredicted", rate);
+	assert_eq!(reversing.local_frames_lost, 0, "nothing got lost, yet {:?}", reversing);
+}
+
+#[test]
+fn lost_frames_of_ours_get_the_blame() {
+	// The loopback server waits for every frame of ours, so this one's
+	// scripted; it never got anything from us.
+	let mut game = Game::new(LOCAL);
+	for index in 0..TICKS {
+		game.run_tick(|_| vec![PlayerIntent::MoveRight]);
+		let mut writer = ByteWriter::new();
+		let tick: Tick<PlayerIntent> = Tick::new(index, vec![CommandFrame::new(LOCAL, vec![])]);
+		Packet::AuthoritativeTick(tick).to_bytes(&mut writer).unwrap();
+		game.dispatch_packet(writer.as_slice());
+		game.receive(index as f64 / 20.0);
+	}
+	let lossy = game.prediction_stats();
+	assert!(lossy.local_frames_lost > 0, "lost frames weren't blamed: {:?}", lossy);
+	assert_eq!(lossy.remote_intents, 0, "nobody else is playing, yet {:?}", lossy);
+}
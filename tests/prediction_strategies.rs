@@ -0,0 +1,93 @@
+//! Pins what each of the built-in prediction strategies guesses from a
+//! small scripted history: two remote players, one of whom only shows up
+//! in the earlier ticks, alongside two local ones whose frames are never
+//! to be made up.
+
+use command_networking_rs::prediction::{
+	builtin, EmptyFrames, Extrapolate, PredictionContext, PredictionStrategy, RemoteMotion, RepeatLast,
+	EXTRAPOLATION_TICKS
+};
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Tick};
+use PlayerIntent::*;
+
+const LOCAL: [u16; 2] = [0, 3];
+
+/// Ticks 7 to 9. Player 1 starts running left and jumps, player 2 runs
+/// right until they stop sending in tick 9.
+fn history() -> Vec<Tick<PlayerIntent>> {
+	vec![
+		Tick::new(7, vec![
+			CommandFrame::new(0, vec![MoveRight]),
+			CommandFrame::new(1, vec![]),
+			CommandFrame::new(2, vec![MoveRight]),
+		]),
+		Tick::new(8, vec![
+			CommandFrame::new(0, vec![MoveRight]),
+			CommandFrame::new(1, vec![MoveLeft]),
+			CommandFrame::new(2, vec![MoveRight]),
+			CommandFrame::new(3, vec![JumpStart]),
+		]),
+		Tick::new(9, vec![
+			CommandFrame::new(0, vec![]),
+			CommandFrame::new(1, vec![MoveLeft, JumpStart]),
+			CommandFrame::new(3, vec![JumpHold]),
+		]),
+	]
+}
+
+fn predict(strategy: &dyn PredictionStrategy<PlayerIntent>, context: &PredictionContext) -> Vec<CommandFrame<PlayerIntent>> {
+	let history = history();
+	let history: Vec<&Tick<PlayerIntent>> = history.iter().collect();
+	strategy.predict_with_context(&history, &LOCAL, context)
+}
+
+#[test]
+fn repeat_last_repeats_the_remote_frames_of_the_last_tick() {
+	let frames = predict(&RepeatLast, &PredictionContext::default());
+	assert_eq!(frames, [CommandFrame::new(1, vec![MoveLeft, JumpStart])]);
+}
+
+#[test]
+fn empty_frames_has_every_remote_player_stand_still() {
+	let frames = predict(&EmptyFrames, &PredictionContext::default());
+	assert_eq!(frames, [CommandFrame::new(1, vec![])]);
+}
+
+#[test]
+fn extrapolate_decays_the_confirmed_velocity() {
+	// Player 1 was last confirmed on tick 8, running left at full speed.
+	let mut context = PredictionContext::default();
+	context.motion.insert(1, RemoteMotion { velocity: -1.0, last_seen: 8 });
+	let frames = predict(&Extrapolate, &context);
+	// Two ticks on, at 0.75 squared of the top speed.
+	let magnitude = -(0.5625 * PlayerIntent::MAX_MAGNITUDE as f32).round() as i8;
+	assert_eq!(frames, [CommandFrame::new(1, vec![MoveHorizontal(magnitude)])]);
+	assert!(Extrapolate.is_extrapolating(1, 10, &context));
+	assert!(!Extrapolate.is_extrapolating(1, 8, &context));
+	assert!(!Extrapolate.is_extrapolating(2, 10, &context), "extrapolated a player nothing's known about");
+
+	// Past `EXTRAPOLATION_TICKS`, they stand still.
+	context.motion.insert(1, RemoteMotion { velocity: -1.0, last_seen: 9 - EXTRAPOLATION_TICKS });
+	assert_eq!(predict(&Extrapolate, &context), [CommandFrame::new(1, vec![])]);
+	assert!(!Extrapolate.is_extrapolating(1, 10, &context));
+}
+
+#[test]
+fn extrapolate_repeats_the_last_frame_without_any_motion() {
+	let frames = predict(&Extrapolate, &PredictionContext::default());
+	assert_eq!(frames, predict(&RepeatLast, &PredictionContext::default()));
+}
+
+#[test]
+fn nothing_is_predicted_without_a_history() {
+	for strategy in builtin() {
+		let frames = strategy.predict_with_context(&[], &LOCAL, &PredictionContext::default());
+		assert!(frames.is_empty(), "{} made up frames out of nothing", strategy.name());
+	}
+}
+
+#[test]
+fn builtin_strategies_are_told_apart_by_name() {
+	let names: Vec<&str> = builtin().iter().map(|x| x.name()).collect();
+	assert_eq!(names, ["repeat-last", "empty", "extrapolate"]);
+}
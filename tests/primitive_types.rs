@@ -0,0 +1,112 @@
+//! The numbers and booleans everything else is made of: each has to come
+//! back exactly as it was written, little-endian, and a buffer cut short
+//! has to be reported as such rather than read as something else.
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn decode<T: NetType>(bytes: &[u8]) -> Result<T, DecodeError> {
+	let mut reader = ByteReader::new(bytes);
+	let value = T::from_bytes(&mut reader)?;
+	assert_eq!(reader.remaining(), 0, "decoding stopped short of the end");
+	Ok(value)
+}
+
+/// Checks that every strict prefix of `bytes` runs out, saying how many
+/// bytes it was short.
+fn check_truncated<T: NetType + std::fmt::Debug>(bytes: &[u8]) {
+	for len in 0..bytes.len() {
+		match T::from_bytes(&mut ByteReader::new(&bytes[..len])) {
+			Err(DecodeError::UnexpectedEof { needed, remaining }) => {
+				assert_eq!((needed, remaining), (bytes.len(), len), "{} of {} bytes", len, bytes.len());
+			},
+			other => panic!("{} of {} bytes read as {:?}", len, bytes.len(), other),
+		}
+	}
+}
+
+macro_rules! check_integers {
+	($($ty:ty),*) => {$(
+		for value in [<$ty>::MIN, <$ty>::MAX, 0, 1, <$ty>::MAX / 3] {
+			let bytes = encode(&value);
+			assert_eq!(bytes, value.to_le_bytes(), "{}", stringify!($ty));
+			assert_eq!(decode::<$ty>(&bytes).unwrap(), value, "{}", stringify!($ty));
+			check_truncated::<$ty>(&bytes);
+		}
+	)*};
+}
+
+#[test]
+fn integers_round_trip() {
+	check_integers!(u8, u16, u32, u64, i8, i16, i32, i64);
+	assert_eq!(encode(&0x0102_0304u32), [0x04, 0x03, 0x02, 0x01]);
+}
+
+macro_rules! check_floats {
+	($($ty:ident),*) => {$(
+		let values = [
+			0.0, -0.0, 1.5, -273.15,
+			std::$ty::consts::PI,
+			$ty::MIN, $ty::MAX, $ty::MIN_POSITIVE, $ty::EPSILON,
+			// The smallest subnormal.
+			$ty::from_bits(1),
+			$ty::INFINITY, $ty::NEG_INFINITY,
+			$ty::NAN, -$ty::NAN,
+			// A NaN with a payload, which mustn't get canonicalized.
+			$ty::from_bits($ty::NAN.to_bits() | 0b101),
+		];
+		for value in values {
+			let bytes = encode(&value);
+			assert_eq!(bytes, value.to_le_bytes(), "{} {}", stringify!($ty), value);
+			// Compared bit for bit, as NaN isn't equal to itself, and
+			// zero is equal to negative zero.
+			let decoded = decode::<$ty>(&bytes).unwrap();
+			assert_eq!(decoded.to_bits(), value.to_bits(), "{} {}", stringify!($ty), value);
+			check_truncated::<$ty>(&bytes);
+		}
+	)*};
+}
+
+#[test]
+fn floats_round_trip_bit_for_bit() {
+	check_floats!(f32, f64);
+	assert!(decode::<f32>(&encode(&f32::NAN)).unwrap().is_nan());
+	assert_eq!(decode::<f64>(&encode(&f64::NEG_INFINITY)).unwrap(), f64::NEG_INFINITY);
+}
+
+#[test]
+fn booleans_round_trip() {
+	assert_eq!(encode(&false), [0]);
+	assert_eq!(encode(&true), [1]);
+	assert!(!decode::<bool>(&[0]).unwrap());
+	assert!(decode::<bool>(&[1]).unwrap());
+	check_truncated::<bool>(&[1]);
+	for tag in 2..=u8::MAX {
+		assert!(matches!(decode::<bool>(&[tag]), Err(DecodeError::InvalidTag { ty: "bool", tag: x }) if x == tag));
+	}
+}
+
+#[test]
+fn values_are_read_one_after_another() {
+	let mut writer = ByteWriter::new();
+	7u16.to_bytes(&mut writer).unwrap();
+	f32::INFINITY.to_bytes(&mut writer).unwrap();
+	true.to_bytes(&mut writer).unwrap();
+	(-2i64).to_bytes(&mut writer).unwrap();
+	let bytes = writer.as_slice().to_vec();
+	assert_eq!(bytes.len(), 2 + 4 + 1 + 8);
+
+	let mut reader = ByteReader::new(&bytes);
+	assert_eq!(u16::from_bytes(&mut reader).unwrap(), 7);
+	assert_eq!(f32::from_bytes(&mut reader).unwrap(), f32::INFINITY);
+	assert!(bool::from_bytes(&mut reader).unwrap());
+	assert_eq!(i64::from_bytes(&mut reader).unwrap(), -2);
+	assert_eq!(reader.remaining(), 0);
+	// Nothing's left for another one.
+	assert!(matches!(u8::from_bytes(&mut reader), Err(DecodeError::UnexpectedEof { needed: 1, remaining: 0 })));
+}
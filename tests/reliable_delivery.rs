@@ -0,0 +1,172 @@
+//! Chats with the in-process server over a network losing a fifth of the
+//! packets either way, and duplicating and reordering some more, to check
+//! that every message makes it there and back exactly once, in the order
+//! it was sent. The server relays each one back, so the chat coming in
+//! went through the reliable layer on both ends. Then sends a burst too
+//! big for the window, which has to hold the rest back until there's room.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::protocol::{Packet, PlayerIntent, TimeRequest};
+use command_networking_rs::reliable::ReliableConfig;
+use command_networking_rs::transport::{LoopbackTransport, ReliableTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const MESSAGES: usize = 200;
+/// Messages sent a frame, while not congested.
+const PER_FRAME: usize = 2;
+/// How many packets get lost either way.
+const LOSS_PERCENT: u8 = 20;
+
+thread_local! {
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// What came in over a frame.
+#[derive(Default)]
+struct Received {
+	chat: Vec<String>,
+	joins: usize,
+	time_responses: usize,
+}
+
+/// Moves time on by a frame, at 60 frames per second, and takes in
+/// whatever arrived meanwhile.
+fn frame(transport: &mut impl Transport) -> Received {
+	let number = FRAMES.get() + 1;
+	FRAMES.set(number);
+	NOW_US.set(number * 1_000_000 / 60);
+	let mut received = Received::default();
+	transport.poll(&mut |bytes| match Packet::<PlayerIntent>::decode(bytes) {
+		Ok(Packet::Chat { text, .. }) => received.chat.push(text),
+		Ok(Packet::Join(_)) => received.joins += 1,
+		Ok(Packet::TimeResponse(_)) => received.time_responses += 1,
+		Ok(_) => {},
+		Err(error) => panic!("received something that isn't a packet: {}", error),
+	});
+	transport.flush().unwrap();
+	received
+}
+
+fn connect(config: ReliableConfig, conditions: SimulatedConditions) -> ReliableTransport<SimulatedTransport<LoopbackTransport>> {
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![])])
+		.with_reliability(config);
+	ReliableTransport::new(SimulatedTransport::new(server, conditions, 11).with_clock(now), config).with_clock(now)
+}
+
+fn text(index: usize) -> String {
+	format!("message {}", index)
+}
+
+/// The chat that came back over the lossy network, and what else did.
+struct Chatted {
+	transport: ReliableTransport<SimulatedTransport<LoopbackTransport>>,
+	chat: Vec<String>,
+	joins: usize,
+	time_requests: usize,
+	time_responses: usize,
+}
+
+/// Sends `MESSAGES` messages, along with a time request every frame, until
+/// they've all come back, then waits a while for anything duplicated.
+fn chatted() -> Chatted {
+	let conditions = SimulatedConditions {
+		latency_ms: 40,
+		jitter_ms: 10,
+		loss_percent: LOSS_PERCENT,
+		duplicate_percent: 5,
+		reorder_percent: 10,
+		..Default::default()
+	};
+	let mut transport = connect(ReliableConfig::default(), conditions);
+	let mut sent = 0;
+	let mut chat = vec![];
+	let mut joins = 0;
+	let mut time_requests = 0;
+	let mut time_responses = 0;
+	let mut frames = 0;
+	while chat.len() < MESSAGES {
+		for _ in 0..PER_FRAME {
+			if sent < MESSAGES && !transport.is_congested() {
+				send(&mut transport, Packet::Chat { sender: LOCAL, text: text(sent) });
+				sent += 1;
+			}
+		}
+		// Nothing sees to it that these make it.
+		send(&mut transport, Packet::TimeRequest(TimeRequest { sender: LOCAL, client_time: now() }));
+		time_requests += 1;
+
+		let received = frame(&mut transport);
+		chat.extend(received.chat);
+		joins += received.joins;
+		time_responses += received.time_responses;
+		frames += 1;
+		assert!(frames < 60 * 60, "only {} of {} messages made it after a minute", chat.len(), MESSAGES);
+	}
+	// Anything duplicated would be showing up by now.
+	for _ in 0..120 {
+		let received = frame(&mut transport);
+		chat.extend(received.chat);
+		joins += received.joins;
+	}
+	Chatted { transport, chat, joins, time_requests, time_responses }
+}
+
+#[test]
+fn every_message_comes_back_once_and_in_order() {
+	let Chatted { transport, chat, .. } = chatted();
+	let expected: Vec<String> = (0..MESSAGES).map(text).collect();
+	assert_eq!(chat, expected, "the chat came back with messages missing, duplicated or out of order");
+	assert_eq!(transport.endpoint().in_flight(), 0, "messages went unanswered");
+}
+
+#[test]
+fn the_scripted_client_joins_once() {
+	let joins = chatted().joins;
+	assert_eq!(joins, 1, "the scripted client joined {} times", joins);
+}
+
+#[test]
+fn lost_messages_get_sent_again() {
+	let retransmissions = chatted().transport.endpoint().retransmissions();
+	assert!(retransmissions > 0, "nothing got sent again, at {}% loss", LOSS_PERCENT);
+}
+
+#[test]
+fn unreliable_packets_stay_lost() {
+	let Chatted { time_requests, time_responses, .. } = chatted();
+	let share = time_responses as f64 / time_requests as f64;
+	assert!(share < 0.8, "{:.0}% of the unreliable time requests got answered, at {}% loss either way", share * 100.0, LOSS_PERCENT);
+}
+
+#[test]
+fn a_burst_past_the_window_waits_its_turn() {
+	// Over a clean network.
+	let window = 8;
+	let config = ReliableConfig { window, ..Default::default() };
+	let mut transport = connect(config, SimulatedConditions { latency_ms: 40, ..Default::default() });
+	for index in 0..window * 3 {
+		send(&mut transport, Packet::Chat { sender: LOCAL, text: text(index) });
+	}
+	assert!(transport.is_congested(), "a burst three windows long didn't congest");
+	assert_eq!(transport.endpoint().in_flight(), window);
+	assert_eq!(transport.endpoint().waiting(), window * 2);
+	let mut chat = vec![];
+	let mut frames = 0;
+	while transport.is_congested() || transport.endpoint().in_flight() > 0 {
+		assert!(transport.endpoint().in_flight() <= window);
+		chat.extend(frame(&mut transport).chat);
+		frames += 1;
+		assert!(frames < 600, "the burst never got through");
+	}
+	for _ in 0..30 {
+		chat.extend(frame(&mut transport).chat);
+	}
+	assert_eq!(chat, (0..window * 3).map(text).collect::<Vec<_>>());
+}
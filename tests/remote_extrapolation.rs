@@ -0,0 +1,138 @@
+//! Has a remote player run right until the server's ticks stop coming in,
+//! then checks that `Extrapolate` keeps them going for a few ticks with a
+//! decaying velocity, and holds them still after that, where repeating the
+//! last frame has them run on at full speed. Once the server's ticks come
+//! back, the player glides over to where they actually went, and the world
+//! ends up the same whichever strategy predicted it.
+
+mod common;
+
+use command_networking_rs::game::{Game, MAX_TICKS_AHEAD};
+use command_networking_rs::overlay::DebugStats;
+use command_networking_rs::prediction::{Extrapolate, PredictionStrategy, RepeatLast, EXTRAPOLATION_TICKS};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::scalar::Scalar;
+use common::deliver;
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+/// The server's ticks stop coming in after this many.
+const STOP: u64 = 30;
+/// How many frames go by without any, before they resume.
+const OUTAGE: u64 = 12;
+const TICKS: u64 = 80;
+
+/// The server's version of a tick: the remote player runs right all along,
+/// and the local player stands still, the same as predicted.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	Tick::new(index, vec![
+		CommandFrame::new(REMOTE, vec![PlayerIntent::MoveRight]),
+		CommandFrame::new(LOCAL, vec![]),
+	])
+}
+
+/// How the remote player was predicted by the end of the outage.
+struct Outage {
+	/// How many ticks past the last confirmed one prediction got.
+	ahead: u64,
+	/// Whether they were predicted to stand still, on the latest tick.
+	held: bool,
+	x: Scalar,
+	/// Whether they got predicted to go on past where they were confirmed.
+	moved_on: bool,
+	/// In pixels per second.
+	velocity: f32,
+	/// How many ticks past the last confirmed one the overlay showed them
+	/// extrapolating at.
+	extrapolated: Vec<u64>,
+}
+
+fn play(strategy: Box<dyn PredictionStrategy<PlayerIntent>>) -> (Game, Outage) {
+	let mut game = Game::new(LOCAL);
+	game.set_prediction_strategy(strategy);
+	deliver(&mut game, Packet::Join(REMOTE));
+
+	let mut outage = None;
+	let mut extrapolated = vec![];
+	let mut sent = 0;
+	for frame in 0..TICKS + OUTAGE {
+		let now = frame as f64 / 20.0;
+		let frozen = (STOP..STOP + OUTAGE).contains(&frame);
+		if !frozen {
+			deliver(&mut game, Packet::AuthoritativeTick(authoritative(sent)));
+			sent += 1;
+		}
+		game.receive(now);
+		game.run_tick(|_| vec![]);
+
+		if frozen {
+			let mut stats = DebugStats::default();
+			game.update_debug_stats(&mut stats, now);
+			let remote = stats.players.iter().find(|x| x.client_id == REMOTE).unwrap();
+			let latest = game.latest_tick().unwrap();
+			let ahead = latest.index() - game.accepted_head();
+			if remote.extrapolating {
+				extrapolated.push(ahead);
+			}
+
+			if frame == STOP + OUTAGE - 1 {
+				let confirmed = game.player_state_at(REMOTE, game.accepted_head()).unwrap();
+				let x = game.players()[&REMOTE].position().0;
+				outage = Some(Outage {
+					ahead,
+					held: latest.frame_of(REMOTE).unwrap().intents().is_empty(),
+					x,
+					moved_on: x > confirmed.x,
+					velocity: remote.velocity.0,
+					extrapolated: std::mem::take(&mut extrapolated)
+				});
+			}
+		}
+		if frame == STOP + OUTAGE {
+			let error = game.players()[&REMOTE].error();
+			assert!(error > 0.0, "the remote player snapped back, rather than gliding");
+		}
+	}
+	(game, outage.unwrap())
+}
+
+#[test]
+fn prediction_runs_as_far_ahead_as_it_may() {
+	let (_, outage) = play(Box::new(Extrapolate));
+	assert_eq!(outage.ahead, MAX_TICKS_AHEAD, "prediction didn't run as far ahead as it may");
+}
+
+#[test]
+fn extrapolating_holds_the_remote_player_still_after_a_few_ticks() {
+	let (_, outage) = play(Box::new(Extrapolate));
+	let (_, repeated) = play(Box::new(RepeatLast));
+	assert!(outage.held, "the remote player wasn't held still past {} ticks", EXTRAPOLATION_TICKS);
+	assert!(!repeated.held, "repeating the last frame held the remote player still");
+}
+
+#[test]
+fn extrapolating_slows_the_remote_player_down() {
+	let (_, outage) = play(Box::new(Extrapolate));
+	let (_, repeated) = play(Box::new(RepeatLast));
+	assert!(outage.velocity < repeated.velocity / 2.0, "extrapolating at {:.0} px/s, repeating at {:.0}", outage.velocity, repeated.velocity);
+	assert!(outage.x < repeated.x, "extrapolating got further than repeating the last frame");
+	assert!(outage.moved_on, "the remote player stopped dead at the last confirmed tick");
+}
+
+#[test]
+fn the_overlay_shows_extrapolating_while_theres_something_left_to_extrapolate() {
+	// Only ever the ticks with something left to extrapolate.
+	let (_, outage) = play(Box::new(Extrapolate));
+	let (_, repeated) = play(Box::new(RepeatLast));
+	assert!(!outage.extrapolated.is_empty(), "the overlay never showed the remote player extrapolating");
+	assert!(outage.extrapolated.iter().all(|x| (1..=EXTRAPOLATION_TICKS).contains(x)), "shown extrapolating at {:?} ticks ahead", outage.extrapolated);
+	assert!(repeated.extrapolated.is_empty(), "repeating the last frame showed as extrapolating");
+}
+
+#[test]
+fn both_strategies_end_up_in_the_same_world() {
+	let (extrapolated, _) = play(Box::new(Extrapolate));
+	let (repeated, _) = play(Box::new(RepeatLast));
+	let head = extrapolated.accepted_head().min(repeated.accepted_head());
+	assert_eq!(extrapolated.state_hash_at(head), repeated.state_hash_at(head), "the strategies ended up in different worlds");
+}
@@ -0,0 +1,126 @@
+//! Plays against an in-process server over a jittery network, with a remote
+//! player changing direction every few ticks, drawing remote players more
+//! and more ticks behind. The further behind, the more of the server's
+//! corrections come in before the tick they're for gets drawn, so fewer of
+//! them show up as a visual error. Then checks that a delay longer than the
+//! history goes back draws the oldest tick there is.
+
+mod common;
+
+use command_networking_rs::game::{Game, TickAccumulator, MAX_RENDER_DELAY};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const FPS: u64 = 60;
+const DURATION: u64 = 30;
+/// Visual errors which moved by less than this, in pixels, don't count.
+const VISIBLE: f32 = 0.5;
+
+/// How the remote player's corrections showed at some render delay.
+struct Run {
+	/// Frames on which a correction moved where the remote player is drawn.
+	visible: u32,
+	/// How far those corrections moved it, added up, in pixels.
+	total_error: f32,
+	corrected: u64,
+}
+
+fn run(render_delay: u8) -> (Game, Run) {
+	NOW_US.set(0);
+	let conditions = SimulatedConditions { latency_ms: 60, jitter_ms: 30, ..Default::default() };
+	let schedule = (0..DURATION * 20)
+		.step_by(4)
+		.map(|x| match x % 8 == 0 {
+			true => (x, vec![PlayerIntent::MoveLeft]),
+			false => (x, vec![PlayerIntent::MoveRight]),
+		})
+		.collect();
+	let server = LoopbackTransport::new(LOCAL).with_clock(now).with_remote(REMOTE, schedule);
+	let mut transport = SimulatedTransport::new(server, conditions, 5).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(2);
+	game.set_render_delay(render_delay);
+	let mut accumulator = TickAccumulator::default();
+
+	let mut run = Run { visible: 0, total_error: 0.0, corrected: 0 };
+	for number in 1..=DURATION * FPS {
+		NOW_US.set(number * 1_000_000 / FPS);
+		let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		let before = game.players().get(&REMOTE).map_or(0.0, |x| x.error());
+		game.receive(now());
+		if let Some(request) = game.time_request(now()) {
+			send(&mut transport, request);
+		}
+		for _ in 0..due_ticks {
+			if let Some(packet) = game.run_tick(|_| vec![]) {
+				send(&mut transport, packet);
+				game.tick_sent(now());
+			}
+		}
+		// Nothing decays the error without a window, so it only ever
+		// changes when a correction moves where the remote player's drawn.
+		let after = game.players().get(&REMOTE).map_or(0.0, |x| x.error());
+		if (after - before).abs() > VISIBLE {
+			run.visible += 1;
+			run.total_error += (after - before).abs();
+		}
+	}
+	assert_eq!(game.desync(), None);
+	run.corrected = game.prediction_stats().corrected;
+	(game, run)
+}
+
+/// The delays tried, shortest first.
+const DELAYS: [u8; 4] = [0, 2, 4, 8];
+
+#[test]
+fn remote_players_get_drawn_as_many_ticks_behind_as_asked() {
+	for delay in DELAYS {
+		let (game, run) = run(delay);
+		assert!(run.corrected > 50, "only {} ticks got corrected, at a delay of {}", run.corrected, delay);
+		let latest = game.latest_tick().unwrap().index();
+		assert_eq!(game.render_tick(), Some(latest - delay as u64));
+	}
+}
+
+#[test]
+fn further_behind_less_of_the_corrections_shows() {
+	// A couple of ticks may not hide many more corrections, as most take
+	// longer than that to come in, but what's left of the ones that do
+	// show is smaller every time.
+	let runs: Vec<Run> = DELAYS.iter().map(|x| run(*x).1).collect();
+	for (delays, runs) in DELAYS.windows(2).zip(runs.windows(2)) {
+		assert!(
+			runs[1].total_error < runs[0].total_error,
+			"{} ticks behind showed {} corrections, for {:.0} px, {} ticks behind {}, for {:.0} px",
+			delays[1], runs[1].visible, runs[1].total_error, delays[0], runs[0].visible, runs[0].total_error
+		);
+	}
+}
+
+#[test]
+fn most_corrections_never_get_drawn_far_enough_behind() {
+	// The same corrections still came in; they just never got drawn.
+	let (_, nearest) = run(DELAYS[0]);
+	let (_, furthest) = run(DELAYS[DELAYS.len() - 1]);
+	assert!((furthest.visible as u64) * 4 < furthest.corrected, "{} of {} corrections showed anyway", furthest.visible, furthest.corrected);
+	assert!(furthest.visible < nearest.visible, "no fewer corrections showed than without a delay");
+}
+
+#[test]
+fn a_delay_past_the_history_draws_the_oldest_tick() {
+	// Far more than the history holds, which gets clamped to its start.
+	let (mut game, _) = run(0);
+	game.set_render_delay(u8::MAX);
+	assert_eq!(game.render_delay(), MAX_RENDER_DELAY);
+	let latest = game.latest_tick().unwrap().index();
+	let oldest = (0..=latest).find(|x| game.player_state_at(REMOTE, *x).is_some()).unwrap();
+	let tick = game.render_tick().unwrap();
+	assert!(tick < latest - 8 && tick + 1 >= oldest, "drawn as of tick {}, with {} to {} stored", tick, oldest, latest);
+	assert!(game.remote_position(REMOTE, 0.5).is_some(), "nothing to draw as of the oldest tick");
+}
@@ -0,0 +1,69 @@
+//! Feeds frame times through the tick accumulator, hitches included, to
+//! check that every due tick gets run and that what's left for rendering to
+//! interpolate by is a genuine fraction of a tick, so that a long frame
+//! never has players drawn past where they are.
+
+use command_networking_rs::game::{Game, TickAccumulator};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::TICK_DELTA;
+
+#[test]
+fn a_long_frame_runs_every_tick_it_owes() {
+	for (ticks, fraction) in [(3, 0.0), (3, 0.5), (3, 0.9), (1, 0.25), (10, 0.0)] {
+		let mut accumulator = TickAccumulator::default();
+		let frame_time = (ticks as f32 + fraction) * TICK_DELTA;
+		let due = accumulator.advance(frame_time, TICK_DELTA);
+		let smoothing = accumulator.smoothing(TICK_DELTA);
+		// A frame of exactly so many ticks may come out a hair short in
+		// floating point, leaving the last tick for the next frame.
+		let (due, smoothing) = match (due + 1 == ticks, fraction == 0.0) {
+			(true, true) => (due + 1, smoothing - 1.0),
+			_ => (due, smoothing),
+		};
+		assert_eq!(due, ticks, "{} ticks' worth of frame time", ticks as f32 + fraction);
+		assert!((smoothing - fraction).abs() < 1e-3, "{} left over from {} ticks' worth", smoothing, ticks as f32 + fraction);
+	}
+}
+
+#[test]
+fn smoothing_never_reaches_past_a_tick() {
+	let mut accumulator = TickAccumulator::default();
+	let mut total = 0;
+	// Steady frames at 60 fps, with a hitch of three ticks every second.
+	for frame in 0..600 {
+		let frame_time = match frame % 60 {
+			59 => 3.0 * TICK_DELTA,
+			_ => 1.0 / 60.0,
+		};
+		total += accumulator.advance(frame_time, TICK_DELTA);
+		let smoothing = accumulator.smoothing(TICK_DELTA);
+		assert!((0.0..=1.0).contains(&smoothing), "smoothing of {} on frame {}", smoothing, frame);
+	}
+	// Ten seconds of steady frames, and thirty ticks of hitches.
+	let expected = 10.0 * 59.0 / 60.0 / TICK_DELTA + 30.0;
+	assert!((total as f32 - expected).abs() <= 1.0, "ran {} ticks rather than {}", total, expected);
+
+	accumulator.reset();
+	assert_eq!(accumulator.smoothing(TICK_DELTA), 0.0);
+}
+
+#[test]
+fn players_are_never_drawn_past_where_they_are() {
+	let mut game = Game::new(0);
+	let mut accumulator = TickAccumulator::default();
+	for _ in 0..accumulator.advance(3.0 * TICK_DELTA + TICK_DELTA / 2.0, TICK_DELTA) {
+		game.run_tick(|_| vec![PlayerIntent::MoveRight]);
+	}
+	let player = &game.players()[&0];
+	let x = player.position().0;
+	#[cfg(feature = "fixed-point")]
+	let x = x.to_f32();
+
+	let smoothing = accumulator.smoothing(TICK_DELTA);
+	let (drawn, _) = player.interpolated_position(smoothing);
+	assert!(drawn < x, "drawn at {} with the player at {}, {} of the way", drawn, x, smoothing);
+	// However far off a smoothing factor is handed in, it's kept to the
+	// player's actual position.
+	assert_eq!(player.interpolated_position(3.0).0, x);
+	assert_eq!(player.interpolated_position(-1.0), player.interpolated_position(0.0));
+}
@@ -0,0 +1,126 @@
+//! Records a session against a scripted server, with a remote player who
+//! keeps getting mispredicted and leaves halfway through, then plays the
+//! replay back. Every confirmed tick has to come out with the state hash
+//! it had live, in order as well as after seeking around, and a replay
+//! from another protocol version has to be turned down.
+
+mod common;
+
+use std::collections::BTreeMap;
+use command_networking_rs::game::Game;
+use command_networking_rs::hash::Hash;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick, PROTOCOL_VERSION};
+use command_networking_rs::replay::{Playback, Replay, ReplayError};
+use PlayerIntent::*;
+use common::deliver;
+
+const LOCAL: u16 = 1;
+const REMOTE: u16 = 2;
+const TICKS: u64 = 120;
+/// The remote player leaves right before this tick.
+const LEAVES_AT: u64 = 70;
+
+fn local_intents(index: u64) -> Vec<PlayerIntent> {
+	match index % 40 {
+		0..=19 => vec![MoveRight],
+		20 => vec![JumpStart],
+		21..=25 => vec![MoveLeft, JumpHold],
+		_ => vec![MoveLeft],
+	}
+}
+
+/// Changes every so often, so that repeating the last frame guesses wrong.
+fn remote_intents(index: u64) -> Vec<PlayerIntent> {
+	match index / 15 % 3 {
+		0 => vec![MoveLeft],
+		1 => vec![MoveRight, JumpStart],
+		_ => vec![],
+	}
+}
+
+/// Every confirmed tick's state hash, as the live game saw it.
+fn record_session() -> (Vec<u8>, BTreeMap<u64, Hash>) {
+	let mut game = Game::new(LOCAL);
+	game.start_recording();
+	deliver(&mut game, Packet::Join(REMOTE));
+	game.receive(0.0);
+
+	let mut hashes = BTreeMap::new();
+	for index in 0..TICKS {
+		let now = index as f64 / 20.0;
+		if index == LEAVES_AT {
+			deliver(&mut game, Packet::Leave(REMOTE));
+			game.receive(now);
+		}
+		game.run_tick(|_| local_intents(index));
+
+		let mut frames = vec![CommandFrame::new(LOCAL, local_intents(index))];
+		if index < LEAVES_AT {
+			frames.push(CommandFrame::new(REMOTE, remote_intents(index)));
+		}
+		deliver(&mut game, Packet::AuthoritativeTick(Tick::new(index, frames)));
+		game.receive(now);
+
+		let head = game.accepted_head();
+		if let Some(hash) = game.state_hash_at(head) {
+			hashes.insert(head, hash);
+		}
+	}
+
+	assert!(game.mispredictions("repeat-last") > 0, "the remote player never got mispredicted");
+	(game.export_replay().expect("the game was recording"), hashes)
+}
+
+#[test]
+fn the_header_says_who_recorded_which_ticks() {
+	let (bytes, hashes) = record_session();
+	let last = *hashes.keys().last().expect("nothing got confirmed");
+	let replay = Replay::parse(&bytes).unwrap_or_else(|error| panic!("couldn't parse the replay: {}", error));
+	assert_eq!(replay.header().client_id, LOCAL);
+	assert_eq!(replay.header().tick_rate, 20);
+	assert_eq!(replay.tick_range(), Some((0, last)));
+}
+
+#[test]
+fn every_tick_plays_back_the_way_it_went_live() {
+	let (bytes, hashes) = record_session();
+	let last = *hashes.keys().last().unwrap();
+	let mut playback = Playback::new(Replay::parse(&bytes).unwrap());
+	while playback.step() {
+		let index = playback.tick_index().unwrap();
+		if let Some(hash) = hashes.get(&index) {
+			assert_eq!(&playback.game().state_hash(), hash, "tick {} played back differently", index);
+		}
+	}
+	assert!(playback.is_finished());
+	assert_eq!(playback.tick_index(), Some(last));
+	assert!(!playback.game().players().contains_key(&REMOTE), "the remote player never left");
+}
+
+#[test]
+fn seeking_around_lands_on_the_ticks_as_they_went_live() {
+	let (bytes, hashes) = record_session();
+	let last = *hashes.keys().last().unwrap();
+	let mut playback = Playback::new(Replay::parse(&bytes).unwrap());
+	for index in [10, LEAVES_AT - 1, LEAVES_AT, 3, last] {
+		playback.seek(index);
+		assert_eq!(playback.tick_index(), Some(index));
+		assert_eq!(&playback.game().state_hash(), &hashes[&index], "seeking to tick {} went wrong", index);
+	}
+}
+
+#[test]
+fn a_replay_from_another_protocol_version_is_turned_down() {
+	let (mut bytes, _) = record_session();
+	bytes[4] = PROTOCOL_VERSION.wrapping_add(1);
+	assert_eq!(
+		Replay::parse(&bytes).err(),
+		Some(ReplayError::UnsupportedVersion { got: PROTOCOL_VERSION.wrapping_add(1), expected: PROTOCOL_VERSION })
+	);
+}
+
+#[test]
+fn something_too_short_isnt_a_replay() {
+	let (bytes, _) = record_session();
+	assert_eq!(Replay::parse(&bytes[..3]).err(), Some(ReplayError::NotAReplay));
+}
@@ -0,0 +1,126 @@
+//! Round trips randomly generated intents, command frames and ticks through
+//! the wire format: each has to decode back into exactly what was written,
+//! with the same hash, and every strict prefix of its bytes has to fail to
+//! decode rather than produce some other value.
+//!
+//! The cases come from proptest, which shrinks a failing one down as far as
+//! it will go, and saves it under `proptest-regressions/` to be tried first
+//! from then on. The boundaries, the empty tick and the longest frames, are
+//! checked on every run regardless.
+
+use proptest::prelude::*;
+use proptest::collection::vec;
+use proptest::sample::subsequence;
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, EncodeError, NetType};
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Tick, MAX_FRAMES_PER_TICK, MAX_INTENTS_PER_FRAME};
+use PlayerIntent::*;
+
+const CASES: u32 = 500;
+/// Beyond this many bytes, only some of the prefixes are tried.
+const EXHAUSTIVE_PREFIXES: usize = 512;
+
+fn intent() -> impl Strategy<Value = PlayerIntent> {
+	prop_oneof![
+		Just(MoveLeft),
+		Just(MoveRight),
+		Just(JumpStart),
+		Just(JumpHold),
+		any::<i8>().prop_map(MoveHorizontal),
+	]
+}
+
+/// Half the time, a frame which gets packed into a single byte.
+fn frame() -> impl Strategy<Value = CommandFrame<PlayerIntent>> {
+	let packed = subsequence(vec![MoveLeft, MoveRight, JumpStart, JumpHold], 0..=4);
+	let listed = vec(intent(), 0..=MAX_INTENTS_PER_FRAME);
+	(any::<u16>(), prop_oneof![packed, listed])
+		.prop_map(|(owner, intents)| CommandFrame::new(owner, intents))
+}
+
+/// Indices of every size, so varints of every length turn up.
+fn tick() -> impl Strategy<Value = Tick<PlayerIntent>> {
+	let index = (any::<u64>(), 0..64u32).prop_map(|(x, shift)| x >> shift);
+	let frames = prop_oneof![
+		9 => vec(frame(), 0..=8),
+		1 => vec(frame(), 0..=MAX_FRAMES_PER_TICK),
+	];
+	(index, frames).prop_map(|(index, frames)| Tick::new(index, frames))
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn decode<T: NetType>(bytes: &[u8]) -> Result<T, DecodeError> {
+	let mut reader = ByteReader::new(bytes);
+	let value = T::from_bytes(&mut reader)?;
+	assert_eq!(reader.remaining(), 0, "decoding stopped short of the end");
+	Ok(value)
+}
+
+/// Checks both properties for `value`, naming `case` if either fails.
+fn check<T: NetType + PartialEq + std::fmt::Debug>(case: &str, value: &T) {
+	let bytes = encode(value);
+	match decode::<T>(&bytes) {
+		Ok(decoded) => assert_eq!(&decoded, value, "{} came back different", case),
+		Err(error) => panic!("{} didn't decode: {}\n{:?}", case, error, value),
+	}
+
+	let step = bytes.len().div_ceil(EXHAUSTIVE_PREFIXES).max(1);
+	for len in (0..bytes.len()).step_by(step).chain([bytes.len() - 1]) {
+		if let Ok(decoded) = T::from_bytes(&mut ByteReader::new(&bytes[..len])) {
+			panic!("{} decoded from only {} of its {} bytes, as {:?}", case, len, bytes.len(), decoded);
+		}
+	}
+}
+
+fn check_tick(case: &str, tick: &Tick<PlayerIntent>) {
+	check(case, tick);
+	let decoded: Tick<PlayerIntent> = decode(&encode(tick)).unwrap();
+	assert_eq!(decoded.hash(), tick.hash(), "{} hashed differently once decoded", case);
+}
+
+#[test]
+fn the_boundaries_round_trip() {
+	check_tick("the empty tick", &Tick::new(0, vec![]));
+	check_tick("the last tick", &Tick::new(u64::MAX, vec![]));
+
+	let longest = CommandFrame::new(u16::MAX, vec![MoveHorizontal(i8::MIN); MAX_INTENTS_PER_FRAME]);
+	check("the longest frame", &longest);
+	check("an empty frame", &CommandFrame::<PlayerIntent>::new(0, vec![]));
+
+	let frames = (0..MAX_FRAMES_PER_TICK).map(|x| CommandFrame::new(x as u16, longest.intents().to_vec())).collect();
+	check_tick("the largest tick", &Tick::new(u64::MAX, frames));
+}
+
+#[test]
+fn one_past_the_longest_frame_isnt_written() {
+	// See `encode_limits`.
+	let too_long = CommandFrame::new(0, vec![MoveHorizontal(1); MAX_INTENTS_PER_FRAME + 1]);
+	assert!(matches!(
+		too_long.to_bytes(&mut ByteWriter::new()),
+		Err(EncodeError::TooManyIntents { .. })
+	));
+}
+
+proptest! {
+	#![proptest_config(ProptestConfig::with_cases(CASES))]
+
+	#[test]
+	fn intents_round_trip(intent in intent()) {
+		check("the intent", &intent);
+	}
+
+	#[test]
+	fn frames_round_trip(frame in frame()) {
+		check("the frame", &frame);
+	}
+
+	#[test]
+	fn ticks_round_trip(tick in tick()) {
+		check_tick("the tick", &tick);
+	}
+}
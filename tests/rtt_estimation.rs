@@ -0,0 +1,91 @@
+//! Feeds the round trip time estimator a scripted sequence of fake ack
+//! delays, first directly and then through a game answered by a scripted
+//! server, and checks both come up with what the smoothing constants say
+//! they should. Corrections count as answers too; answering the same tick
+//! twice doesn't.
+
+mod common;
+
+use command_networking_rs::game::{Game, RttEstimator};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use common::deliver;
+
+const LOCAL: u16 = 0;
+const TICK_DELTA: f64 = 0.05;
+
+/// How long the server takes to answer each tick, in seconds. Always less
+/// than a tick, so the answers arrive in order.
+fn delay(index: u64) -> f64 {
+	match index {
+		0..=19 => 0.020,
+		20..=29 => 0.045,
+		_ => 0.020 + (index % 3) as f64 * 0.010,
+	}
+}
+
+fn close(a: f64, b: f64) -> bool {
+	(a - b).abs() < 1e-9
+}
+
+/// An estimator that took two samples of 100 ms.
+fn steady() -> RttEstimator {
+	let mut estimator = RttEstimator::default();
+	estimator.update(0.100);
+	estimator.update(0.100);
+	estimator
+}
+
+#[test]
+fn the_first_sample_is_taken_as_is() {
+	// With half of it as the jitter.
+	let mut estimator = RttEstimator::default();
+	assert_eq!(estimator.smoothed(), None);
+	estimator.update(0.100);
+	assert!(close(estimator.smoothed().unwrap(), 0.100) && close(estimator.jitter(), 0.050));
+}
+
+#[test]
+fn the_jitter_shrinks_by_a_quarter_while_nothing_changes() {
+	let estimator = steady();
+	assert!(close(estimator.smoothed().unwrap(), 0.100) && close(estimator.jitter(), 0.0375));
+}
+
+#[test]
+fn a_spike_moves_the_average_by_an_eighth_of_the_difference() {
+	// After the jitter is updated against the average from before.
+	let mut estimator = steady();
+	estimator.update(0.300);
+	assert!(close(estimator.smoothed().unwrap(), 0.100 + RttEstimator::RTT_GAIN * 0.200));
+	assert!(close(estimator.jitter(), 0.0375 + RttEstimator::JITTER_GAIN * (0.200 - 0.0375)));
+}
+
+#[test]
+fn a_game_samples_every_tick_answered_once() {
+	// Whether by an ack or a correction.
+	let mut expected = RttEstimator::default();
+	let mut game = Game::new(LOCAL);
+	for index in 0..60 {
+		let now = index as f64 * TICK_DELTA;
+		game.run_tick(|_| vec![PlayerIntent::MoveRight]).expect("the game stalled");
+		game.tick_sent(now);
+
+		let answered = now + delay(index);
+		let hash = *game.latest_tick().unwrap().hash();
+		if index % 10 == 5 {
+			// The server never got our frame, and corrects the tick.
+			deliver(&mut game, Packet::AuthoritativeTick(Tick::new(index, vec![CommandFrame::new(LOCAL, vec![])])));
+		} else {
+			deliver(&mut game, Packet::Ack { index, hash });
+		}
+		game.receive(answered);
+		expected.update(delay(index));
+
+		// Answering the same tick again doesn't make for another sample.
+		deliver(&mut game, Packet::Ack { index, hash });
+		game.receive(answered + 0.001);
+	}
+
+	let rtt = game.rtt();
+	assert!(close(rtt.smoothed().unwrap(), expected.smoothed().unwrap()), "{:?} rather than {:?}", rtt, expected);
+	assert!(close(rtt.jitter(), expected.jitter()), "{:?} rather than {:?}", rtt, expected);
+}
@@ -0,0 +1,115 @@
+//! Saves a client's state halfway through a game and restores it into
+//! another, to check that the world and the tick history come back the
+//! same, that both carry on the same from there, and that states from a
+//! different protocol version are turned away.
+
+mod common;
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick, PROTOCOL_VERSION};
+use command_networking_rs::saved_state::{SavedState, StateError, STATE_MAGIC};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+/// Tick `index` as the server has it: player 0 runs right and jumps every
+/// so often, while player 1 runs left.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	Tick::new(index, vec![CommandFrame::new(0, local_intents(index)), CommandFrame::new(1, vec![MoveLeft])])
+}
+
+fn local_intents(index: u64) -> Vec<PlayerIntent> {
+	match index % 15 {
+		0 => vec![MoveRight, JumpStart],
+		_ => vec![MoveRight],
+	}
+}
+
+/// Runs ticks `range`, with the server answering `LAG` ticks late.
+fn play(game: &mut Game, range: std::ops::Range<u64>) {
+	for index in range {
+		if index >= LAG {
+			deliver(game, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		game.receive(index as f64 * TICK_DELTA as f64);
+		game.run_tick(|_| local_intents(index));
+	}
+}
+
+/// A game 60 ticks in, and its state saved.
+fn saved() -> (Game, Vec<u8>) {
+	let mut game = Game::new(0);
+	deliver(&mut game, Packet::Join(1));
+	game.receive(0.0);
+	play(&mut game, 0..60);
+	let saved = game.export_state().unwrap();
+	(game, saved)
+}
+
+fn restored(saved: &[u8]) -> Game {
+	let mut restored = Game::new(0);
+	restored.import_state(saved).expect("the state didn't restore");
+	restored
+}
+
+#[test]
+fn the_world_and_history_come_back_the_same() {
+	let (game, saved) = saved();
+	assert!(saved.starts_with(&STATE_MAGIC));
+	let restored = restored(&saved);
+	assert_eq!(restored.state_hash(), game.state_hash(), "the world came back different");
+	assert_eq!(restored.accepted_head(), game.accepted_head());
+	let head = game.accepted_head();
+	assert!(head > 0, "nothing got confirmed before saving");
+	for index in head.saturating_sub(10)..60 {
+		assert_eq!(restored.state_hash_at(index), game.state_hash_at(index), "tick {} came back different", index);
+	}
+	assert_eq!(restored.export_state().unwrap(), saved, "saving the restored state again came out different");
+}
+
+#[test]
+fn a_restored_game_holds_off_predicting_until_the_snapshot() {
+	// The original's world stands in for the server's snapshot.
+	let (game, saved) = saved();
+	let mut restored = restored(&saved);
+	assert!(restored.is_resync_requested(), "the restored game trusts its predictions");
+	assert!(restored.run_tick(|_| local_intents(60)).is_none(), "the restored game predicted before the snapshot");
+	deliver(&mut restored, Packet::Snapshot(game.snapshot()));
+	restored.receive(60.0 * TICK_DELTA as f64);
+	assert!(!restored.is_resync_requested());
+}
+
+#[test]
+fn both_carry_on_the_same() {
+	// The restored game starts playing the server's ticks back a little
+	// later, so only what the server confirmed has to agree.
+	let (mut game, saved) = saved();
+	let mut restored = restored(&saved);
+	deliver(&mut restored, Packet::Snapshot(game.snapshot()));
+	restored.receive(60.0 * TICK_DELTA as f64);
+	play(&mut game, 60..90);
+	play(&mut restored, 60..90);
+	let head = game.accepted_head();
+	assert!(head >= 90 - LAG - 2, "the server only got to tick {}", head);
+	assert_eq!(restored.accepted_head(), head, "the restored game caught up with the server differently");
+	assert_eq!(restored.state_hash_at(head), game.state_hash_at(head), "the restored game carried on differently");
+}
+
+#[test]
+fn a_state_from_another_protocol_version_is_turned_away() {
+	// The version comes right after the magic.
+	let (mut game, saved) = saved();
+	let mut outdated = saved.clone();
+	outdated[STATE_MAGIC.len()] = PROTOCOL_VERSION - 1;
+	let before = game.state_hash();
+	let error = game.import_state(&outdated).unwrap_err();
+	assert_eq!(error, StateError::UnsupportedVersion { got: PROTOCOL_VERSION - 1, expected: PROTOCOL_VERSION });
+	assert_eq!(game.state_hash(), before, "a rejected state changed the game");
+}
+
+#[test]
+fn anything_but_a_whole_state_is_turned_away() {
+	let (_, saved) = saved();
+	assert_eq!(SavedState::parse(b"nope").err(), Some(StateError::NotAState));
+	assert!(matches!(SavedState::parse(&saved[..saved.len() - 1]), Err(StateError::Malformed(_))));
+}
@@ -0,0 +1,55 @@
+//! Drives the local player from code rather than the keyboard: moves right
+//! for 10 ticks, jumps, then moves left for 5 ticks.
+//!
+//! No server is involved; every tick is predicted and simulated right
+//! away, the same way the headless binary does it.
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::scalar::Scalar;
+
+/// Queues `intents` for the local player for `ticks` ticks, and returns
+/// where it got to.
+fn run(game: &mut Game, intents: Vec<PlayerIntent>, ticks: u32) -> (Scalar, Scalar) {
+	for _ in 0..ticks {
+		game.queue_intents(0, intents.clone());
+		let tick = game.predict_tick(vec![]);
+		game.advance(tick);
+	}
+	game.players()[&0].position()
+}
+
+#[test]
+fn moving_right_moves_the_player_right() {
+	let mut game = Game::new(0);
+	let spawn = game.players()[&0].position();
+	let after_right = run(&mut game, vec![PlayerIntent::MoveRight], 10);
+	assert!(after_right.0 > spawn.0, "moving right didn't move the player right");
+}
+
+#[test]
+fn jumping_leaves_the_floor() {
+	let mut game = Game::new(0);
+	let spawn = game.players()[&0].position();
+	run(&mut game, vec![PlayerIntent::MoveRight], 10);
+	let after_jump = run(&mut game, vec![PlayerIntent::JumpStart], 1);
+	assert!(after_jump.1 != spawn.1, "jumping didn't leave the floor");
+}
+
+#[test]
+fn moving_left_then_moves_the_player_left() {
+	let mut game = Game::new(0);
+	run(&mut game, vec![PlayerIntent::MoveRight], 10);
+	let after_jump = run(&mut game, vec![PlayerIntent::JumpStart], 1);
+	let after_left = run(&mut game, vec![PlayerIntent::MoveLeft], 5);
+	assert!(after_left.0 < after_jump.0, "moving left didn't move the player left");
+}
+
+#[test]
+fn intents_for_someone_other_than_the_local_player_are_ignored() {
+	let mut game = Game::new(0);
+	game.queue_intents(1, vec![PlayerIntent::MoveRight]);
+	let tick = game.predict_tick(vec![]);
+	assert!(tick.frame_of(1).is_none());
+	assert!(tick.frame_of(0).is_some_and(|x| x.intents().is_empty()));
+}
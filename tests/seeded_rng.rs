@@ -0,0 +1,123 @@
+//! Checks that the simulation's random numbers are the same wherever and
+//! however often a tick gets simulated: on two separate games, and on a
+//! client that mispredicted and had to roll back and simulate again.
+//!
+//! Players past the last spawn point spawn at random, so they're what
+//! consumes random numbers here.
+
+mod common;
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::{ClientId, CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::rng::Rng;
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+const SEED: u64 = 0x5eed_cafe;
+const TICKS: u64 = 60;
+/// Remote players, all of them past the last spawn point.
+const REMOTES: [ClientId; 3] = [7, 8, 9];
+/// Tick `index` as the server has it, with everyone moving about.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	let mut frames = vec![CommandFrame::new(0, vec![MoveRight])];
+	for owner in REMOTES {
+		let intents = match (index + owner as u64) % 12 {
+			0 => vec![JumpStart],
+			1..=6 => vec![MoveLeft],
+			_ => vec![MoveRight],
+		};
+		frames.push(CommandFrame::new(owner, intents));
+	}
+	Tick::new(index, frames)
+}
+
+/// A game with the remote players in it from the start.
+fn seeded_game(seed: u64) -> Game {
+	let mut game = Game::new(0);
+	game.set_seed(seed);
+	for owner in REMOTES {
+		deliver(&mut game, Packet::Join(owner));
+	}
+	game.receive(0.0);
+	game
+}
+
+/// Plays the server's ticks as they are, without predicting anything.
+fn lockstep(seed: u64) -> Game {
+	let mut game = seeded_game(seed);
+	for index in 0..TICKS {
+		game.advance(authoritative(index));
+	}
+	game
+}
+
+fn draws(seed: u64, tick: u64) -> Vec<u32> {
+	let mut rng = Rng::for_tick(seed, tick);
+	(0..8).map(|_| rng.next_u32()).collect()
+}
+
+#[test]
+fn the_same_tick_draws_the_same_numbers() {
+	assert_eq!(draws(SEED, 5), draws(SEED, 5), "the same tick drew different numbers");
+}
+
+#[test]
+fn neighbouring_ticks_and_seeds_draw_different_numbers() {
+	assert_ne!(draws(SEED, 5), draws(SEED, 6), "neighbouring ticks drew the same numbers");
+	assert_ne!(draws(SEED, 5), draws(SEED + 1, 5), "neighbouring seeds drew the same numbers");
+}
+
+#[test]
+fn a_split_stream_leaves_its_parent_alone() {
+	let rng = Rng::for_tick(SEED, 0);
+	let untouched = rng.clone();
+	let mut split = rng.split(7);
+	assert_ne!(split.next_u32(), rng.clone().next_u32(), "a split stream drew the same as its parent");
+	assert_eq!(rng, untouched, "drawing from a split stream moved its parent along");
+}
+
+#[test]
+fn draws_stay_in_bounds() {
+	let mut rng = Rng::for_tick(SEED, 0);
+	assert!((0..1000).all(|_| rng.below(10) < 10), "a draw fell out of its bounds");
+	assert!((0..1000).all(|_| (-5..5).contains(&rng.range(-5, 5))), "a draw fell out of its range");
+}
+
+#[test]
+fn games_with_the_same_seed_stay_identical() {
+	assert_eq!(lockstep(SEED).state_hash(), lockstep(SEED).state_hash(), "two games with the same seed diverged");
+	assert_ne!(lockstep(SEED).state_hash(), lockstep(SEED + 1).state_hash(), "the seed made no difference");
+}
+
+#[test]
+fn random_spawns_land_apart() {
+	let game = lockstep(SEED);
+	let spawns: Vec<_> = REMOTES.iter().map(|x| game.world_after(0).unwrap()[x].position()).collect();
+	assert!(spawns.windows(2).all(|x| x[0] != x[1]), "random spawns landed on top of each other");
+}
+
+#[test]
+fn rolling_back_draws_the_same_numbers_again() {
+	// A client predicting ahead, with the server's ticks arriving late. It
+	// knows nothing of the remote players' intents, so their spawns get
+	// simulated again on every rollback.
+	let lockstep = lockstep(SEED);
+	let mut client = seeded_game(SEED);
+	for index in 0..TICKS + LAG {
+		if index >= LAG {
+			deliver(&mut client, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		client.receive(index as f64 * TICK_DELTA as f64);
+		client.run_tick(|_| vec![MoveRight]);
+	}
+	let strategy = client.prediction_strategy().name();
+	assert!(client.mispredictions(strategy) > 0, "nothing got mispredicted, so nothing was rolled back");
+	assert!(client.accepted_head() >= TICKS - LAG, "the client never caught up with the server");
+	// Whatever the client still holds on to, that is; settled ticks get pruned.
+	let compared = (0..=client.accepted_head())
+		.filter_map(|index| client.state_hash_at(index).map(|hash| (index, hash)))
+		.inspect(|(index, hash)| assert_eq!(Some(*hash), lockstep.state_hash_at(*index), "tick {} came out differently after a rollback", index))
+		.count();
+	assert!(compared > 0, "the client didn't hold on to any confirmed ticks");
+}
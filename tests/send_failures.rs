@@ -0,0 +1,161 @@
+//! Plays against an in-process server while the transport turns every
+//! send down for a bit. Half a second of that, the client rides out: what
+//! got turned down goes out later, in order, and the server's corrections
+//! catch it up. Two seconds, it gives up on the connection rather than
+//! predicting into a black hole, and doesn't try getting back in until
+//! sends go through again; then it resyncs off the server's snapshot, and
+//! plays on without desyncing.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::{ConnectionState, Game, TickAccumulator, MAX_RESEND_PACKETS, SEND_FAILURE_TIMEOUT};
+use command_networking_rs::protocol::{Packet, PlayerIntent};
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{deliver, now, NOW_US};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const FPS: u64 = 60;
+
+thread_local! {
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+}
+
+type Client = SimulatedTransport<LoopbackTransport>;
+
+fn send(game: &mut Game, transport: &mut Client, packet: Packet<PlayerIntent>) {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	game.send(transport, writer.as_slice(), now());
+}
+
+/// What went on over a stretch of frames.
+#[derive(Default)]
+struct Run {
+	/// Ticks predicted and handed to the transport.
+	predicted: u32,
+	/// The most packets waiting to go again at once.
+	deepest_queue: usize,
+	states: Vec<ConnectionState>,
+}
+
+struct Session {
+	game: Game,
+	transport: Client,
+	accumulator: TickAccumulator,
+}
+
+impl Session {
+	/// Runs `seconds` worth of frames the way the client does, rejecting
+	/// every send if told to.
+	fn play(&mut self, seconds: f64, rejecting: bool) -> Run {
+		let Session { game, transport, accumulator } = self;
+		transport.simulated_conditions().unwrap().rejecting = rejecting;
+		let mut run = Run::default();
+		for _ in 0..(seconds * FPS as f64) as u64 {
+			let number = FRAMES.get() + 1;
+			FRAMES.set(number);
+			NOW_US.set(number * 1_000_000 / FPS);
+			let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+
+			transport.poll(&mut |packet| game.dispatch_packet(packet));
+			game.receive(now());
+			game.resend(transport, now());
+			if let Some(request) = game.time_request(now()) {
+				send(game, transport, request);
+			}
+			for _ in 0..due_ticks {
+				if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+					send(game, transport, packet);
+					game.tick_sent(now());
+					run.predicted += 1;
+				}
+			}
+			game.flush(transport, now());
+
+			run.deepest_queue = run.deepest_queue.max(game.resend_queue_len());
+			if run.states.last() != Some(&game.connection_state()) {
+				run.states.push(game.connection_state());
+			}
+		}
+		run
+	}
+}
+
+/// A session that's been playing for three seconds, with every send
+/// going through.
+fn connected() -> Session {
+	let conditions = SimulatedConditions { latency_ms: 40, jitter_ms: 5, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL).with_clock(now).with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])]);
+	let mut session = Session {
+		game: Game::new(LOCAL),
+		transport: SimulatedTransport::new(server, conditions, 7).with_clock(now),
+		accumulator: TickAccumulator::default(),
+	};
+	session.game.sync_clock(2);
+	session.play(3.0, false);
+	assert_eq!(session.game.connection_state(), ConnectionState::Connected);
+	assert_eq!(session.game.send_failures(), 0);
+	session
+}
+
+#[test]
+fn half_a_second_of_failed_sends_gets_ridden_out() {
+	// Not long enough to give up over.
+	let mut session = connected();
+	let blip = session.play(SEND_FAILURE_TIMEOUT / 2.0, true);
+	assert_eq!(blip.states, [ConnectionState::Connected], "half a second of failed sends lost the connection");
+	assert!(blip.deepest_queue > 0 && session.game.send_failures() > 0);
+}
+
+#[test]
+fn what_got_turned_down_goes_out_later() {
+	let mut session = connected();
+	let head = session.game.accepted_head();
+	session.play(SEND_FAILURE_TIMEOUT / 2.0, true);
+	let failures = session.game.send_failures();
+	let after = session.play(2.0, false);
+	let game = &session.game;
+	assert_eq!(after.states, [ConnectionState::Connected]);
+	assert_eq!(game.resend_queue_len(), 0, "the packets turned down never went out");
+	assert_eq!(game.send_failures(), failures, "sends kept failing once the transport took them again");
+	assert!(game.accepted_head() > head + 40, "the server stopped confirming ticks, at {}", game.accepted_head());
+	assert_eq!(game.desync(), None);
+}
+
+#[test]
+fn two_seconds_of_failed_sends_loses_the_connection() {
+	// Long enough to be sure nothing's getting through.
+	let mut session = connected();
+	let outage = session.play(2.0, true);
+	let game = &session.game;
+	assert_eq!(outage.states, [ConnectionState::Connected, ConnectionState::Disconnected], "the server's ticks got us back in, with every send still failing");
+	assert!(outage.deepest_queue <= MAX_RESEND_PACKETS, "{} packets held, past the limit", outage.deepest_queue);
+	assert!(game.is_stalled());
+	// Only the ticks predicted before giving up, and ahead of the server
+	// by no more than usual.
+	let most = (SEND_FAILURE_TIMEOUT * game.tick_rate().hz() as f64) as u32 + 8;
+	assert!(outage.predicted <= most, "predicted {} ticks into failed sends", outage.predicted);
+}
+
+#[test]
+fn once_a_send_gets_through_the_client_resyncs_and_plays_on() {
+	// Back in, but the history's too stale.
+	let mut session = connected();
+	session.play(2.0, true);
+	let back = session.play(1.0, false);
+	let game = &mut session.game;
+	assert_eq!(back.states[..2], [ConnectionState::Disconnected, ConnectionState::Resyncing]);
+	assert!(game.is_resync_requested() && !game.is_syncing_clock(), "never got back in step");
+	assert_eq!(game.resend_queue_len(), 0);
+	deliver(game, Packet::Snapshot(session.transport.inner().snapshot()));
+	let head = game.accepted_head();
+	let resynced = session.play(3.0, false);
+	let game = &session.game;
+	assert_eq!(resynced.states, [ConnectionState::Connected]);
+	assert!(resynced.predicted > 40 && game.accepted_head() > head + 40, "never got going again after the snapshot");
+	assert_eq!(game.desync(), None, "desynced after resyncing");
+}
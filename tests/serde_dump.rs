@@ -0,0 +1,80 @@
+//! Dumps ticks, players and a snapshot to JSON, to check that they read
+//! back into the same values, that a tick's hash is checked on the way
+//! back in, and that `Game::debug_dump_tick` dumps what got predicted.
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::{Game, Player};
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Snapshot, Tick};
+use PlayerIntent::*;
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn tick() -> Tick<PlayerIntent> {
+	Tick::new(7, vec![
+		CommandFrame::new(0, vec![MoveRight, JumpStart]).with_offset(64),
+		CommandFrame::new(1, vec![MoveHorizontal(100)]),
+	])
+}
+
+/// A game that predicted three ticks, and the ticks it predicted.
+fn predicted() -> (Game, Vec<Tick<PlayerIntent>>) {
+	let mut game = Game::new(0);
+	let mut predicted = vec![];
+	for intents in [vec![MoveRight], vec![MoveRight, JumpStart], vec![JumpHold]] {
+		let tick = game.predict_tick(intents);
+		predicted.push(tick.clone());
+		game.advance(tick);
+	}
+	(game, predicted)
+}
+
+#[test]
+fn intents_round_trip() {
+	let intents = vec![MoveLeft, MoveRight, JumpStart, JumpHold, MoveHorizontal(-64)];
+	let json = serde_json::to_string(&intents).unwrap();
+	assert_eq!(serde_json::from_str::<Vec<PlayerIntent>>(&json).unwrap(), intents);
+}
+
+#[test]
+fn ticks_round_trip_with_their_hash_in_hex() {
+	let tick = tick();
+	let json = serde_json::to_string(&tick).unwrap();
+	let read: Tick<PlayerIntent> = serde_json::from_str(&json).unwrap();
+	assert_eq!(read, tick);
+	assert_eq!(read.hash(), tick.hash());
+	let hex: String = tick.hash().iter().map(|x| format!("{:02x}", x)).collect();
+	assert!(json.contains(&format!("\"hash\":\"{}\"", hex)), "the hash isn't written as hex: {}", json);
+}
+
+#[test]
+fn a_tick_read_back_is_checked_against_its_hash() {
+	// Same tick, with a different intent than it was hashed with.
+	let tampered = serde_json::to_string(&tick()).unwrap().replace("JumpStart", "JumpHold");
+	assert!(serde_json::from_str::<Tick<PlayerIntent>>(&tampered).is_err(), "a tampered tick read back fine");
+}
+
+#[test]
+fn debug_dump_tick_dumps_what_got_predicted() {
+	let (game, predicted) = predicted();
+	for tick in &predicted {
+		let dump = game.debug_dump_tick(tick.index()).expect("the tick wasn't dumped");
+		assert_eq!(&serde_json::from_str::<Tick<PlayerIntent>>(&dump).unwrap(), tick);
+	}
+	assert_eq!(game.debug_dump_tick(predicted.len() as u64), None, "dumped a tick that doesn't exist yet");
+}
+
+#[test]
+fn players_and_snapshots_round_trip() {
+	// Neither compares, so they're compared by how they go over the wire.
+	let (game, _) = predicted();
+	let player = &game.players()[&0];
+	let read: Player = serde_json::from_str(&serde_json::to_string(player).unwrap()).unwrap();
+	assert_eq!(encode(&read), encode(player));
+	let snapshot = game.snapshot();
+	let read: Snapshot = serde_json::from_str(&serde_json::to_string_pretty(&snapshot).unwrap()).unwrap();
+	assert_eq!(encode(&read), encode(&snapshot));
+}
@@ -0,0 +1,110 @@
+//! Has the in-process server simulate a world unlike the default one, to
+//! check that a client takes it on from the welcome before predicting
+//! anything, and plays along without ever desyncing. A client that never
+//! heard of it desyncs instead, as the config is part of the state hash.
+//! Connecting gets the config across the same way, and one that only shows
+//! up once ticks got predicted stalls the game, like a tick rate would.
+
+mod common;
+
+use command_networking_rs::game::{Game, SimConfig};
+use command_networking_rs::protocol::{ConnectRequest, Packet, PlayerIntent};
+use command_networking_rs::scalar::Scalar;
+use command_networking_rs::transport::{LoopbackTransport, Transport};
+use common::send;
+
+const LOCAL: u16 = 0;
+const TICKS: u64 = 120;
+
+#[cfg(not(feature = "fixed-point"))]
+fn scalar(value: f32) -> Scalar {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+fn scalar(value: f32) -> Scalar {
+	Scalar::from_f32(value)
+}
+
+/// Narrow, with big and fast players.
+fn world() -> SimConfig {
+	SimConfig {
+		move_speed: scalar(360.0),
+		player_width: scalar(48.0),
+		player_height: scalar(40.0),
+		world_width: scalar(400.0),
+		..Default::default()
+	}
+}
+
+/// Runs right for `TICKS` ticks against `transport`, handing the client
+/// only the packets `deliver` lets through.
+fn play(game: &mut Game, transport: &mut LoopbackTransport, deliver: impl Fn(&Packet<PlayerIntent>) -> bool) {
+	for index in 0..TICKS {
+		transport.poll(&mut |bytes| {
+			if Packet::<PlayerIntent>::decode(bytes).is_ok_and(|x| deliver(&x)) {
+				game.dispatch_packet(bytes);
+			}
+		});
+		game.receive(index as f64 / 20.0);
+		if let Some(request) = game.connect_request(index as f64 / 20.0) {
+			send(transport, request);
+		}
+		if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+			send(transport, packet);
+		}
+	}
+}
+
+#[test]
+fn the_client_takes_on_the_servers_world_from_the_welcome() {
+	let mut transport = LoopbackTransport::new(LOCAL).with_sim_config(world());
+	let mut game = Game::new(LOCAL);
+	play(&mut game, &mut transport, |_| true);
+	assert_eq!(game.sim_config(), &world());
+	assert_eq!(game.sim_config_mismatch(), None);
+	assert!(game.accepted_head() > TICKS / 2, "the server stopped confirming ticks, at {}", game.accepted_head());
+	assert_eq!(game.desync(), None, "desynced while playing in the server's world");
+	// Right up against the nearer wall, standing on the higher floor.
+	let player = &game.players()[&LOCAL];
+	assert_eq!(player.position(), (world().max_x(), world().floor_y()));
+}
+
+#[test]
+fn missing_the_welcome_desyncs() {
+	let mut transport = LoopbackTransport::new(LOCAL).with_sim_config(world());
+	let mut stubborn = Game::new(LOCAL);
+	play(&mut stubborn, &mut transport, |x| !matches!(x, Packet::Welcome { .. }));
+	assert_eq!(stubborn.sim_config(), &SimConfig::default());
+	assert!(stubborn.desync().is_some(), "simulating a different world went unnoticed");
+}
+
+#[test]
+fn getting_let_in_brings_the_world_along_too() {
+	let mut transport = LoopbackTransport::new(LOCAL).with_sim_config(world());
+	let request = ConnectRequest { requested_id: None, name: None };
+	let mut connected = Game::connecting(request, false);
+	play(&mut connected, &mut transport, |x| !matches!(x, Packet::Welcome { .. }));
+	assert!(!connected.is_connecting());
+	assert_eq!(connected.sim_config(), &world());
+	assert_eq!(connected.desync(), None);
+}
+
+#[test]
+fn a_different_world_mid_game_stalls_it() {
+	// Too late to switch worlds once ticks got predicted in this one.
+	let mut game = Game::new(LOCAL);
+	game.run_tick(|_| vec![]);
+	game.set_sim_config(world());
+	assert_eq!(game.sim_config_mismatch(), Some(&world()));
+	assert_eq!(game.sim_config(), &SimConfig::default());
+	assert!(game.run_tick(|_| vec![PlayerIntent::MoveRight]).is_none(), "a game in the wrong world kept going");
+}
+
+#[test]
+fn a_world_players_dont_fit_into_is_turned_down() {
+	// Whenever it comes.
+	let mut game = Game::new(LOCAL);
+	game.set_sim_config(SimConfig { world_width: scalar(20.0), ..Default::default() });
+	assert!(game.sim_config_mismatch().is_some() && game.run_tick(|_| vec![]).is_none());
+}
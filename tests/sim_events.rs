@@ -0,0 +1,140 @@
+//! Scripts a jump and a landing for two players, and a run into the wall
+//! for one of them, and checks the exact events the simulation comes up
+//! with. Then plays the same ticks as a client the server answers late
+//! enough for rollbacks to happen, and checks that taking the events once
+//! a frame brings every one of them up exactly once, the remote player's
+//! jump included, which is only found out about by a correction.
+
+mod common;
+
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::game::{Game, SimEvent, TickEvent, WallSide};
+use command_networking_rs::protocol::{ClientId, CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::{deliver, LAG};
+
+/// Away from the wall, and from each other.
+const LOCAL: ClientId = 2;
+const REMOTE: ClientId = 4;
+const TICKS: u64 = 100;
+
+fn local_intents(index: u64) -> Vec<PlayerIntent> {
+	match index {
+		0 => vec![JumpStart],
+		40..=80 => vec![MoveLeft],
+		_ => vec![],
+	}
+}
+
+fn remote_intents(index: u64) -> Vec<PlayerIntent> {
+	match index {
+		10 => vec![JumpStart],
+		_ => vec![],
+	}
+}
+
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	Tick::new(index, vec![CommandFrame::new(LOCAL, local_intents(index)), CommandFrame::new(REMOTE, remote_intents(index))])
+}
+
+/// A game with both players in, as of tick 0.
+fn joined(client_id: ClientId) -> Game {
+	let mut game = Game::new(client_id);
+	deliver(&mut game, Packet::Join(REMOTE));
+	game.receive(0.0);
+	game
+}
+
+/// The server's ticks played as they are, and the events they brought up.
+fn lockstep() -> (Game, Vec<TickEvent>) {
+	let mut game = joined(LOCAL);
+	let mut events = vec![];
+	for index in 0..TICKS {
+		game.advance(authoritative(index));
+		events.extend(game.take_sim_events());
+	}
+	(game, events)
+}
+
+#[test]
+fn the_simulation_brings_up_the_scripted_events() {
+	let (_, events) = lockstep();
+	let kinds: Vec<(u64, SimEvent)> = events
+		.iter()
+		.map(|x| match x.event {
+			// Checked on its own.
+			SimEvent::Landed { id, .. } => (x.tick, SimEvent::Landed { id, impact_velocity: 0.0 }),
+			event => (x.tick, event),
+		})
+		.collect();
+	let landing = |id| SimEvent::Landed { id, impact_velocity: 0.0 };
+	assert_eq!(
+		kinds,
+		[
+			(0, SimEvent::Jumped { id: LOCAL }),
+			(10, SimEvent::Jumped { id: REMOTE }),
+			(21, landing(LOCAL)),
+			(31, landing(REMOTE)),
+			(50, SimEvent::HitWall { id: LOCAL, side: WallSide::Left }),
+		]
+	);
+}
+
+#[test]
+fn players_jumping_the_same_way_land_as_fast() {
+	// Both jumped off the floor the same way, and came down on it as fast.
+	let (_, events) = lockstep();
+	let impacts: Vec<f32> = events
+		.iter()
+		.filter_map(|x| match x.event {
+			SimEvent::Landed { impact_velocity, .. } => Some(impact_velocity),
+			_ => None,
+		})
+		.collect();
+	assert!(impacts[0] > 0.0, "landed going up, at {}", impacts[0]);
+	assert_eq!(impacts[0], impacts[1]);
+}
+
+#[test]
+fn taking_the_events_makes_no_difference_to_the_simulation() {
+	let (game, _) = lockstep();
+	let mut untaken = joined(LOCAL);
+	for index in 0..TICKS {
+		untaken.advance(authoritative(index));
+	}
+	assert_eq!(untaken.state_hash(), game.state_hash());
+}
+
+#[test]
+fn events_round_trip() {
+	let (_, events) = lockstep();
+	let mut writer = ByteWriter::new();
+	events.to_bytes(&mut writer).unwrap();
+	let decoded = Vec::<TickEvent>::from_bytes(&mut ByteReader::new(writer.as_slice())).unwrap();
+	assert_eq!(decoded, events);
+}
+
+#[test]
+fn a_client_rolling_back_brings_up_every_event_once() {
+	// The client only finds out about the remote player's jump once the
+	// server's tick for it comes in, and replays the ticks after it; those
+	// replays don't bring up the local player's events again.
+	let (_, events) = lockstep();
+	let mut client = joined(LOCAL);
+	let mut presented: Vec<TickEvent> = vec![];
+	let mut rolled_back = 0;
+	for index in 0..TICKS + LAG {
+		if index >= LAG {
+			deliver(&mut client, Packet::AuthoritativeTick(authoritative(index - LAG)));
+		}
+		let before = client.mispredictions(client.prediction_strategy().name());
+		client.receive(index as f64 * TICK_DELTA as f64);
+		client.run_tick(|_| local_intents(index));
+		rolled_back += (client.mispredictions(client.prediction_strategy().name()) > before) as u32;
+		presented.extend(client.take_sim_events());
+	}
+	assert!(rolled_back > 0, "nothing got rolled back");
+	presented.sort_by_key(|x| (x.tick, x.event.id()));
+	assert_eq!(presented, events, "the client brought up other events than the simulation did");
+}
@@ -0,0 +1,187 @@
+//! Checks that `NetType::from_slice` decodes every type on the wire the
+//! same as reading it through a `ByteReader`, reports how many bytes it
+//! took, and that whole packets with anything trailing them get rejected.
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+use command_networking_rs::game::SimConfig;
+use command_networking_rs::protocol::{CommandFrame, EncodedTick, Packet, PlayerInfo, PlayerIntent, Snapshot, StateHash, Tick, TimeRequest, TimeResponse};
+use command_networking_rs::scalar::Scalar;
+use PlayerIntent::*;
+
+/// Bytes after a value, which neither way of decoding should touch.
+const TRAILER: [u8; 3] = [0xaa, 0xbb, 0xcc];
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+/// Decodes `bytes` both ways, with `TRAILER` after them, and checks that
+/// both come back out as `bytes` when written again.
+fn check<T: NetType>(name: &str, bytes: &[u8]) {
+	let mut padded = bytes.to_vec();
+	padded.extend_from_slice(&TRAILER);
+
+	let mut reader = ByteReader::new(&padded);
+	let read = T::from_bytes(&mut reader).unwrap_or_else(|x| panic!("{} didn't decode: {}", name, x));
+	let (sliced, len) = T::from_slice(&padded).unwrap_or_else(|x| panic!("{} didn't decode: {}", name, x));
+
+	assert_eq!(len, bytes.len(), "{} consumed the wrong number of bytes", name);
+	assert_eq!(reader.position(), len, "{} got read to a different length", name);
+	assert_eq!(encode(&read), bytes, "{} changed through a reader", name);
+	assert_eq!(encode(&sliced), bytes, "{} decoded differently from a slice", name);
+}
+
+fn round_trip<T: NetType>(name: &str, value: &T) {
+	check::<T>(name, &encode(value));
+}
+
+/// A frame that packs into a bitmask, and one that has to go as a list.
+fn frames() -> [CommandFrame<PlayerIntent>; 2] {
+	[
+		CommandFrame::new(2, vec![MoveRight, JumpStart]),
+		CommandFrame::new(3, vec![MoveHorizontal(12), JumpHold, MoveHorizontal(12)]),
+	]
+}
+
+fn tick() -> Tick<PlayerIntent> {
+	Tick::new(300, frames().to_vec())
+}
+
+fn state_hash() -> StateHash {
+	StateHash { index: 290, hash: *tick().hash() }
+}
+
+/// One player on one platform, as raw bytes, as the player's fields aren't
+/// public; with the position in full, or quantized.
+fn snapshot_bytes(quantized: bool) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	writer.write_u64_le(12);
+	writer.write_u64_le(0x5eed);
+	SimConfig::default().to_bytes(&mut writer).unwrap();
+	quantized.to_bytes(&mut writer).unwrap();
+	writer.write_varint(1);
+	4u16.to_bytes(&mut writer).unwrap();
+	match quantized {
+		true => writer.write_bytes(&[0x34, 0x12, 0xff, 0x9f]),
+		false => writer.write_bytes(&[1, 2, 3, 4, 1, 2, 3, 4]),
+	}
+	for _ in 0..2 {
+		writer.write_bytes(&[1, 2, 3, 4]);
+	}
+	writer.write_u8(1);
+	writer.write_bytes(&[0; 3]);
+	writer.write_varint(1);
+	for _ in 0..4 {
+		writer.write_bytes(&[0, 0, 2, 0]);
+	}
+	// No coins, but a score.
+	writer.write_varint(0);
+	writer.write_varint(1);
+	4u16.to_bytes(&mut writer).unwrap();
+	writer.write_bytes(&[7, 0, 0, 0]);
+	// And a name.
+	writer.write_varint(1);
+	4u16.to_bytes(&mut writer).unwrap();
+	writer.write_varint(2);
+	writer.write_bytes(b"Jo");
+	writer.as_slice().to_vec()
+}
+
+/// One of every packet.
+fn packets() -> Vec<Packet<PlayerIntent>> {
+	let tick = tick();
+	let hash = *tick.hash();
+	vec![
+		Packet::PredictedTick {
+			sender: 2,
+			tick: EncodedTick::Keyframe(tick.clone()),
+			past_frames: vec![],
+			state_hash: Some(state_hash()),
+		},
+		Packet::AuthoritativeTick(tick),
+		Packet::Received { index: 300 },
+		Packet::Ack { index: 300, hash },
+		Packet::Snapshot(Snapshot::from_bytes(&mut ByteReader::new(&snapshot_bytes(false))).unwrap()),
+		Packet::Join(5),
+		Packet::Leave(5),
+		Packet::Welcome { tick_rate: 60, seed: u64::MAX, sim_config: SimConfig::default() },
+		Packet::StateHash(state_hash()),
+		Packet::PlayerInfo(PlayerInfo::new(5, "Player 5")),
+		Packet::Chat { sender: 5, text: "hello there".into() },
+		Packet::TimeRequest(TimeRequest { sender: 5, client_time: 12.5 }),
+		Packet::TimeResponse(TimeResponse { client_time: 12.5, tick: 300, progress: 0.5 }),
+	]
+}
+
+#[test]
+fn primitives_decode_the_same_from_a_slice() {
+	round_trip("u8", &0xfeu8);
+	round_trip("u16", &0xbeefu16);
+	round_trip("u32", &0xdead_beefu32);
+	round_trip("u64", &u64::MAX);
+	round_trip("i8", &-5i8);
+	round_trip("i16", &-1234i16);
+	round_trip("i32", &i32::MIN);
+	round_trip("i64", &-1i64);
+	round_trip("f32", &-0.5f32);
+	round_trip("f64", &1e300f64);
+	round_trip("bool", &true);
+	round_trip("Vec", &vec![1u16, 2, 3]);
+	round_trip("Option", &Some(7u32));
+	round_trip("None", &None::<u32>);
+	round_trip("array", &[9u8; 4]);
+	check::<Scalar>("Scalar", &[0x00, 0x80, 0xd6, 0xff]);
+}
+
+#[test]
+fn intents_and_frames_decode_the_same_from_a_slice() {
+	for intent in [MoveLeft, MoveRight, JumpStart, JumpHold, MoveHorizontal(-64)] {
+		round_trip("PlayerIntent", &intent);
+	}
+	for frame in frames() {
+		round_trip("CommandFrame", &frame);
+	}
+}
+
+#[test]
+fn ticks_and_state_hashes_decode_the_same_from_a_slice() {
+	round_trip("Tick", &tick());
+	round_trip("StateHash", &state_hash());
+}
+
+#[test]
+fn snapshots_decode_the_same_from_a_slice() {
+	check::<Snapshot>("Snapshot", &snapshot_bytes(false));
+	check::<Snapshot>("quantized Snapshot", &snapshot_bytes(true));
+}
+
+#[test]
+fn packets_decode_the_same_from_a_slice() {
+	for packet in &packets() {
+		let bytes = encode(packet);
+		check::<Packet<PlayerIntent>>("Packet", &bytes);
+		assert_eq!(encode(&Packet::<PlayerIntent>::decode(&bytes).unwrap()), bytes);
+	}
+}
+
+#[test]
+fn a_packet_with_anything_after_it_is_rejected() {
+	for packet in &packets() {
+		let bytes = encode(packet);
+		// Two packets handed over as one, or a packet with junk after it.
+		let mut doubled = bytes.clone();
+		doubled.extend_from_slice(&bytes);
+		assert_eq!(
+			Packet::<PlayerIntent>::decode(&doubled).err(),
+			Some(DecodeError::TrailingBytes { count: bytes.len() })
+		);
+		let mut padded = bytes.clone();
+		padded.extend_from_slice(&TRAILER);
+		assert_eq!(
+			Packet::<PlayerIntent>::decode(&padded).err(),
+			Some(DecodeError::TrailingBytes { count: TRAILER.len() })
+		);
+	}
+}
@@ -0,0 +1,187 @@
+//! Plays against an in-process server, then has the client lose track of
+//! it for three different reasons at once. Only one snapshot request goes
+//! out for all of them, and while the server's snapshots keep getting lost,
+//! it's sent again less and less often, rather than every frame. Once one
+//! makes it, the client picks up from it, as connected as before.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::game::{ConnectionState, Game, TickAccumulator, MAX_SNAPSHOT_RETRY_INTERVAL, SNAPSHOT_RETRY_INTERVAL};
+use command_networking_rs::overlay::DebugStats;
+use command_networking_rs::protocol::{Packet, PlayerIntent, SnapshotReason};
+use command_networking_rs::reliable::ReliableConfig;
+use command_networking_rs::transport::{LoopbackTransport, ReliableTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const FPS: u64 = 60;
+
+thread_local! {
+	static FRAMES: Cell<u64> = const { Cell::new(0) };
+	/// Whether the server's snapshots get lost on the way.
+	static LOSING_SNAPSHOTS: Cell<bool> = const { Cell::new(false) };
+}
+
+type Client = ReliableTransport<LoopbackTransport>;
+
+/// Runs a frame the way the client does. Returns whether a snapshot
+/// request went out, and whether a tick got predicted.
+fn frame(game: &mut Game, transport: &mut Client, accumulator: &mut TickAccumulator) -> (bool, bool) {
+	let number = FRAMES.get() + 1;
+	FRAMES.set(number);
+	NOW_US.set(number * 1_000_000 / FPS);
+	let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+
+	transport.poll(&mut |bytes| {
+		let snapshot = matches!(Packet::<PlayerIntent>::decode(bytes), Ok(Packet::Snapshot(_)));
+		if !snapshot || !LOSING_SNAPSHOTS.get() {
+			game.dispatch_packet(bytes);
+		}
+	});
+	game.receive(now());
+	if let Some(request) = game.time_request(now()) {
+		send(transport, request);
+	}
+	let requested = match game.snapshot_request(now()) {
+		Some(request) => {
+			send(transport, request);
+			true
+		},
+		None => false,
+	};
+	let mut predicted = false;
+	for _ in 0..due_ticks {
+		if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+			send(transport, packet);
+			game.tick_sent(now());
+			predicted = true;
+		}
+	}
+	transport.flush().unwrap();
+	(requested, predicted)
+}
+
+struct Session {
+	game: Game,
+	transport: Client,
+	accumulator: TickAccumulator,
+}
+
+impl Session {
+	fn frame(&mut self) -> (bool, bool) {
+		frame(&mut self.game, &mut self.transport, &mut self.accumulator)
+	}
+}
+
+/// A session that's been playing for two seconds.
+fn connected() -> Session {
+	let config = ReliableConfig::default();
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])])
+		.with_reliability(config);
+	let mut session = Session {
+		game: Game::new(LOCAL),
+		transport: ReliableTransport::new(server, config).with_clock(now),
+		accumulator: TickAccumulator::default(),
+	};
+	session.game.sync_clock(2);
+	for _ in 0..2 * FPS {
+		let (requested, _) = session.frame();
+		assert!(!requested, "asked for a snapshot, in step with the server");
+	}
+	assert_eq!(session.game.connection_state(), ConnectionState::Connected);
+	assert!(session.game.accepted_head() > 0, "never got going");
+	session
+}
+
+/// Has three things go wrong at once, before the frame's requests are
+/// due, with the server's snapshots getting lost from then on.
+fn lose_track(game: &mut Game) {
+	LOSING_SNAPSHOTS.set(true);
+	game.request_snapshot(SnapshotReason::Pruned);
+	game.request_snapshot(SnapshotReason::Gap);
+	game.request_snapshot(SnapshotReason::Restored);
+}
+
+/// A session that lost track of the server and kept asking for 20 seconds,
+/// asking again itself every second. Returns when every request went out.
+fn backed_off() -> (Session, Vec<f64>) {
+	let mut session = connected();
+	lose_track(&mut session.game);
+	session.frame();
+	let mut sent_at = vec![now()];
+	for number in 0..20 * FPS {
+		if number % FPS == 0 {
+			session.game.request_snapshot(SnapshotReason::FarBehind);
+		}
+		let (requested, predicted) = session.frame();
+		assert!(!predicted, "predicted while waiting on a snapshot");
+		if requested {
+			sent_at.push(now());
+		}
+	}
+	assert!(session.game.is_resync_requested() && session.game.is_stalled());
+	(session, sent_at)
+}
+
+#[test]
+fn three_reasons_at_once_make_for_one_request() {
+	let mut session = connected();
+	lose_track(&mut session.game);
+	assert_eq!(session.game.connection_state(), ConnectionState::Resyncing);
+	let (requested, predicted) = session.frame();
+	assert!(requested && !predicted);
+	assert!(session.game.snapshot_request(now()).is_none(), "a second request went out alongside the first");
+}
+
+#[test]
+fn the_first_reason_is_the_one_kept() {
+	let mut session = connected();
+	lose_track(&mut session.game);
+	assert_eq!(session.game.snapshot_reason(), Some(SnapshotReason::Pruned), "a later reason took over from the first");
+}
+
+#[test]
+fn requests_back_off_while_the_snapshots_get_lost() {
+	// Asking again meanwhile doesn't send any sooner.
+	let (_, sent_at) = backed_off();
+	let gaps: Vec<f64> = sent_at.windows(2).map(|x| x[1] - x[0]).collect();
+	assert!(gaps.len() >= 3, "only sent {} times in 20 seconds", sent_at.len());
+	assert!((gaps[0] - SNAPSHOT_RETRY_INTERVAL).abs() < 0.1, "first sent again after {:.2}s", gaps[0]);
+	for pair in gaps.windows(2) {
+		let expected = (pair[0] * 2.0).min(MAX_SNAPSHOT_RETRY_INTERVAL);
+		assert!((pair[1] - expected).abs() < 0.1, "sent again after {:.2}s, then {:.2}s", pair[0], pair[1]);
+	}
+}
+
+#[test]
+fn the_overlay_shows_the_request() {
+	let (session, sent_at) = backed_off();
+	let mut stats = DebugStats::default();
+	session.game.update_debug_stats(&mut stats, now());
+	let (reason, age) = stats.last_snapshot_request.expect("the overlay never heard of the request");
+	assert_eq!(reason, SnapshotReason::Pruned);
+	assert!((age - (now() - sent_at[0])).abs() < 1e-6);
+	assert!(stats.resync_requested && stats.snapshot_attempts as usize == sent_at.len());
+}
+
+#[test]
+fn the_client_picks_up_from_the_snapshot_that_makes_it() {
+	let (mut session, _) = backed_off();
+	let head = session.game.accepted_head();
+	LOSING_SNAPSHOTS.set(false);
+	let mut ticks = 0;
+	for _ in 0..(MAX_SNAPSHOT_RETRY_INTERVAL as u64 + 3) * FPS {
+		ticks += session.frame().1 as u32;
+	}
+	let game = &mut session.game;
+	assert!(!game.is_resync_requested(), "the snapshot never came");
+	assert_eq!(game.connection_state(), ConnectionState::Connected);
+	assert!(ticks > 0 && game.accepted_head() > head + 100, "never got going again, at {}", game.accepted_head());
+	assert_eq!(game.desync(), None);
+	assert!(game.snapshot_request(now()).is_none());
+}
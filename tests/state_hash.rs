@@ -0,0 +1,113 @@
+//! Pins the state hash of a short scripted run, so its format can't change
+//! without anyone noticing, and checks that both zeros hash the same, and
+//! that the sim config counts too.
+
+use command_networking_rs::game::{Game, SimConfig};
+use command_networking_rs::protocol::PlayerIntent;
+use PlayerIntent::*;
+
+/// Ticks alongside the intents the player switches to from then on.
+const SCRIPT: &[(u64, &[PlayerIntent])] = &[
+	(0, &[MoveRight]),
+	(10, &[MoveRight, JumpStart]),
+	(11, &[MoveRight, JumpHold]),
+	(20, &[MoveLeft]),
+	(30, &[]),
+];
+const TICKS: u64 = 40;
+
+/// The state hash after playing `SCRIPT`, which only ever changes along
+/// with the hash format, or the simulation itself.
+#[cfg(all(not(feature = "fixed-point"), not(feature = "crc32")))]
+const PINNED: &str = "eb81617346f9d0a45b89e0367bfd33c8ccbb5fc5cd2c14891bb1c469c0959b1f";
+#[cfg(all(feature = "fixed-point", not(feature = "crc32")))]
+const PINNED: &str = "e9c134f4ea285e03dcfdca80c755e24fdc4b4eff212e769f322a468f9549c1a5";
+#[cfg(all(not(feature = "fixed-point"), feature = "crc32"))]
+const PINNED: &str = "6ea1a774";
+#[cfg(all(feature = "fixed-point", feature = "crc32"))]
+const PINNED: &str = "f42b6a18";
+
+fn hex(hash: &[u8]) -> String {
+	hash.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// The world after a single player at rest got loaded from a snapshot,
+/// with `velocity` as its horizontal velocity.
+#[cfg(not(feature = "fixed-point"))]
+fn loaded(velocity: f32) -> Game {
+	use command_networking_rs::net::{ByteReader, ByteWriter, NetType};
+	use command_networking_rs::protocol::{Packet, Snapshot};
+
+	let mut writer = ByteWriter::new();
+	0u64.to_bytes(&mut writer).unwrap();
+	// The seed.
+	0u64.to_bytes(&mut writer).unwrap();
+	SimConfig::default().to_bytes(&mut writer).unwrap();
+	// Positions in full.
+	false.to_bytes(&mut writer).unwrap();
+	writer.write_varint(1);
+	0u16.to_bytes(&mut writer).unwrap();
+	for value in [0.0f32, 226.0, velocity, 0.0] {
+		value.to_bytes(&mut writer).unwrap();
+	}
+	true.to_bytes(&mut writer).unwrap();
+	[0u8; 3].to_bytes(&mut writer).unwrap();
+	// No platforms, coins, scores or names.
+	writer.write_varint(0);
+	writer.write_varint(0);
+	writer.write_varint(0);
+	writer.write_varint(0);
+	let snapshot = Snapshot::from_bytes(&mut ByteReader::new(writer.as_slice())).unwrap();
+
+	let mut writer = ByteWriter::new();
+	Packet::<PlayerIntent>::Snapshot(snapshot).to_bytes(&mut writer).unwrap();
+	let mut game = Game::new(0);
+	game.dispatch_packet(writer.as_slice());
+	game.receive(0.0);
+	game
+}
+
+/// Plays `SCRIPT` with a second player always moving left.
+fn play(config: SimConfig) -> Game {
+	let mut game = Game::new(0);
+	game.set_sim_config(config);
+	game.add_local_player(1);
+	for index in 0..TICKS {
+		let (_, intents) = SCRIPT.iter().rev().find(|(start, _)| *start <= index).unwrap();
+		game.queue_intents(1, vec![MoveLeft]);
+		let tick = game.predict_tick(intents.to_vec());
+		game.advance(tick);
+		assert_eq!(game.state_hash_at(index), Some(game.state_hash()));
+	}
+	game
+}
+
+#[test]
+fn the_state_hash_format_is_pinned() {
+	let hash = hex(play(SimConfig::default()).state_hash().as_ref());
+	assert_eq!(hash, PINNED, "the state hash format changed");
+}
+
+#[test]
+fn the_sim_config_is_part_of_the_state_hash() {
+	// Nobody falls anywhere near the default terminal velocity, so doubling
+	// it leaves the world exactly as it was, but a client simulating with
+	// it still has to stand out.
+	let game = play(SimConfig::default());
+	let config = SimConfig { terminal_velocity: SimConfig::TERMINAL_VELOCITY + SimConfig::TERMINAL_VELOCITY, ..Default::default() };
+	let other = play(config);
+	assert_eq!(other.players()[&0].position(), game.players()[&0].position());
+	assert_ne!(other.state_hash(), game.state_hash(), "the sim config isn't part of the state hash");
+}
+
+#[cfg(not(feature = "fixed-point"))]
+#[test]
+fn both_zeros_hash_the_same() {
+	assert_eq!(loaded(0.0).state_hash(), loaded(-0.0).state_hash());
+}
+
+#[cfg(not(feature = "fixed-point"))]
+#[test]
+fn another_velocity_hashes_differently() {
+	assert_ne!(loaded(0.0).state_hash(), loaded(1.0).state_hash());
+}
@@ -0,0 +1,120 @@
+//! Plays against an in-process server over a jittery network, with a remote
+//! player changing direction all the time, and takes a state summary in
+//! between frames whenever one's due, the way the host gets them. Checks
+//! that they come every so many confirmed ticks, and only ever hold
+//! confirmed state, which the rollbacks going on meanwhile never change.
+
+mod common;
+
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::game::{Game, StateSummary, TickAccumulator};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::scalar::Scalar;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const FPS: u64 = 60;
+const DURATION: u64 = 20;
+const INTERVAL: u32 = 5;
+
+#[cfg(not(feature = "fixed-point"))]
+fn to_f32(value: Scalar) -> f32 {
+	value
+}
+
+#[cfg(feature = "fixed-point")]
+fn to_f32(value: Scalar) -> f32 {
+	value.to_f32()
+}
+
+/// Plays for `DURATION`, asking for summaries after the first second, and
+/// checks each one as it comes: that it reads back the same, is of the
+/// latest confirmed tick, far enough along from the last one, and only
+/// gets handed out once. Returns the game and the summaries.
+fn observed() -> (Game, Vec<StateSummary>) {
+	NOW_US.set(0);
+	let conditions = SimulatedConditions { latency_ms: 60, jitter_ms: 60, ..Default::default() };
+	let schedule = (0..DURATION * 20)
+		.step_by(3)
+		.map(|x| match x % 2 == 0 {
+			true => (x, vec![PlayerIntent::MoveLeft]),
+			false => (x, vec![PlayerIntent::MoveRight]),
+		})
+		.collect();
+	let server = LoopbackTransport::new(LOCAL).with_clock(now).with_remote(REMOTE, schedule);
+	let mut transport = SimulatedTransport::new(server, conditions, 3).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(2);
+	let mut accumulator = TickAccumulator::default();
+
+	let mut summaries: Vec<StateSummary> = vec![];
+	for number in 1..=DURATION * FPS {
+		NOW_US.set(number * 1_000_000 / FPS);
+		let due_ticks = accumulator.advance(game.tick_speed() / FPS as f32, game.tick_rate().delta());
+		// Nothing's due until asked for.
+		if number == FPS {
+			assert!(summaries.is_empty() && game.take_state_summary().is_none());
+			game.set_observer_interval(INTERVAL);
+		}
+
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(now());
+		if let Some(request) = game.time_request(now()) {
+			send(&mut transport, request);
+		}
+		for _ in 0..due_ticks {
+			if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+				send(&mut transport, packet);
+				game.tick_sent(now());
+			}
+		}
+
+		let Some(summary) = game.take_state_summary() else { continue };
+		// Handed over as bytes, which read back the same.
+		let mut writer = ByteWriter::new();
+		summary.to_bytes(&mut writer).unwrap();
+		assert_eq!(StateSummary::from_bytes(&mut ByteReader::new(writer.as_slice())).unwrap(), summary);
+		assert_eq!(summary.tick, game.accepted_head());
+		if let Some(previous) = summaries.last() {
+			assert!(summary.tick >= previous.tick + INTERVAL as u64, "tick {} came only {} after the last", summary.tick, summary.tick - previous.tick);
+		}
+		assert_eq!(summary.players.iter().map(|x| x.owner).collect::<Vec<_>>(), [LOCAL, REMOTE]);
+		assert!(game.take_state_summary().is_none(), "the same summary got handed out twice");
+		summaries.push(summary);
+	}
+	assert_eq!(game.desync(), None);
+	(game, summaries)
+}
+
+#[test]
+fn summaries_come_every_so_many_confirmed_ticks() {
+	let (_, summaries) = observed();
+	assert!(summaries.len() as u64 > DURATION * 20 / INTERVAL as u64 / 2, "only {} summaries came", summaries.len());
+}
+
+#[test]
+fn summaries_only_hold_confirmed_state() {
+	// The confirmed ticks they were made of never changed since, however
+	// many of their successors got rolled back.
+	let (game, summaries) = observed();
+	let corrected = game.prediction_stats().corrected;
+	assert!(corrected > 50, "only {} ticks got corrected, for the summaries to hold out against", corrected);
+	let mut checked = 0;
+	for summary in &summaries {
+		for player in &summary.players {
+			let Some(state) = game.player_state_at(player.owner, summary.tick) else { continue };
+			assert_eq!((to_f32(state.x), to_f32(state.y)), (player.x, player.y), "player {} moved on tick {}", player.owner, summary.tick);
+			checked += 1;
+		}
+	}
+	assert!(checked > 0, "none of the summarized ticks are left to check against");
+}
+
+#[test]
+fn an_interval_of_zero_stops_the_summaries() {
+	let (mut game, _) = observed();
+	game.set_observer_interval(0);
+	assert!(game.take_state_summary().is_none());
+}
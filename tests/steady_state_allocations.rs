@@ -0,0 +1,108 @@
+//! Sends and receives packets through one reused scratch buffer, counting
+//! allocations, and checks that once the buffer has grown to fit, neither
+//! writing a packet nor decoding one from a borrowed slice allocates.
+//!
+//! Building the ticks themselves still allocates, since they own their
+//! frames, so this only covers the trip through the serialization layer.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::hash::Hash;
+use command_networking_rs::protocol::{CommandFrame, EncodedTick, Packet, PlayerIntent, StateHash, Tick};
+use PlayerIntent::*;
+
+/// Counts every allocation made, by the thread making it, so that the
+/// test harness's own threads don't get counted.
+struct CountingAllocator;
+
+thread_local! {
+	static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		// Gone already while the thread's being torn down.
+		let _ = ALLOCATIONS.try_with(|x| x.set(x.get() + 1));
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ROUNDS: usize = 1000;
+
+/// How many allocations `f` makes over `ROUNDS` calls.
+fn allocations(mut f: impl FnMut()) -> usize {
+	let before = ALLOCATIONS.with(Cell::get);
+	for _ in 0..ROUNDS {
+		f();
+	}
+	ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Decodes a packet straight from the bytes it arrived in.
+fn receive(bytes: &[u8]) -> Packet<PlayerIntent> {
+	Packet::from_bytes(&mut ByteReader::new(bytes)).unwrap()
+}
+
+fn tick() -> Tick<PlayerIntent> {
+	let command_frames = (0..4)
+		.map(|owner| CommandFrame::new(owner, vec![MoveLeft, JumpStart, JumpHold]))
+		.collect();
+	Tick::new(40, command_frames)
+}
+
+fn predicted() -> Packet<PlayerIntent> {
+	let tick = tick();
+	let hash = *tick.hash();
+	Packet::PredictedTick {
+		sender: 0,
+		tick: EncodedTick::Keyframe(tick),
+		past_frames: vec![],
+		state_hash: Some(StateHash { index: 30, hash }),
+	}
+}
+
+#[test]
+fn sending_a_predicted_tick_doesnt_allocate() {
+	// The first send grows the buffer to fit, every later one reuses it.
+	let predicted = predicted();
+	let mut scratch = ByteWriter::new();
+	predicted.to_bytes(&mut scratch).unwrap();
+
+	let sent = allocations(|| {
+		scratch.clear();
+		predicted.to_bytes(&mut scratch).unwrap();
+		std::hint::black_box(scratch.as_slice());
+	});
+	assert_eq!(sent, 0, "sending a predicted tick allocated");
+}
+
+#[test]
+fn a_round_trip_through_the_scratch_buffer_doesnt_allocate() {
+	let hash: Hash = *tick().hash();
+	let small: [Packet<PlayerIntent>; 4] = [
+		Packet::Received { index: 40 },
+		Packet::Ack { index: 40, hash },
+		Packet::StateHash(StateHash { index: 30, hash }),
+		Packet::Join(3),
+	];
+	let mut scratch = ByteWriter::new();
+	predicted().to_bytes(&mut scratch).unwrap();
+
+	let received = allocations(|| {
+		for packet in &small {
+			scratch.clear();
+			packet.to_bytes(&mut scratch).unwrap();
+			std::hint::black_box(receive(scratch.as_slice()));
+		}
+	});
+	assert_eq!(received, 0, "a round trip through the scratch buffer allocated");
+}
@@ -0,0 +1,101 @@
+//! Rolls back 200 ticks at once, in a game with no tick budget, and in one
+//! whose budget only leaves room for a few ticks a frame. The second one
+//! takes its time getting through the replay, drawing the world from before
+//! the rollback meanwhile, but ends up with the very same state as the first.
+
+mod common;
+
+use command_networking_rs::game::{Game, TickBudget};
+use command_networking_rs::hash::Hash;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use PlayerIntent::*;
+use common::{deliver, NOW_US};
+
+const TICKS: u64 = 200;
+const BUDGET_MS: u32 = 10;
+
+/// A clock on which every tick replayed takes a millisecond, that being
+/// about how often the budget gets looked at.
+fn clock() -> f64 {
+	NOW_US.replace(NOW_US.get() + 1000) as f64 / 1e6
+}
+
+fn predicted(index: u64) -> Tick<PlayerIntent> {
+	let local = match index % 30 {
+		0 => vec![MoveRight, JumpStart],
+		_ => vec![MoveRight],
+	};
+	Tick::new(index, vec![CommandFrame::new(0, local), CommandFrame::new(1, vec![MoveRight])])
+}
+
+/// What the server says actually happened: player 1 went the other way.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	let remote = vec![MoveLeft, JumpStart];
+	Tick::new(index, vec![predicted(index).command_frames()[0].clone(), CommandFrame::new(1, remote)])
+}
+
+/// A game 200 ticks ahead of the server, which is about to find out it
+/// got the first of them wrong, and guess the rest again from there. Returns how many frames it took to get
+/// through the rollback, and the state hash after every tick.
+fn roll_back(budget: Option<TickBudget>) -> (Game, u32, Vec<Hash>) {
+	let mut game = Game::new(0);
+	deliver(&mut game, Packet::Join(1));
+	game.receive(0.0);
+	game.set_tick_budget(budget);
+	for index in 0..TICKS {
+		game.advance(predicted(index));
+	}
+	let before = game.state_hash();
+
+	// Enough for the jitter buffer to start playing back.
+	deliver(&mut game, Packet::AuthoritativeTick(authoritative(0)));
+	deliver(&mut game, Packet::AuthoritativeTick(authoritative(1)));
+	let mut frames = 0;
+	loop {
+		frames += 1;
+		game.start_frame();
+		game.receive(0.0);
+		assert!(game.run_tick(|_| vec![]).is_none(), "predicted a tick 200 ticks ahead of the server");
+		if frames > 1 && !game.is_replaying() {
+			break;
+		}
+		// The world from before the rollback is the one that gets drawn
+		// until the replay's through.
+		if game.is_replaying() {
+			assert_eq!(game.state_hash(), before, "the world changed halfway through the replay");
+		}
+		assert!(frames < 1000, "the replay never got through");
+	}
+	let hashes = (0..TICKS).map(|x| game.state_hash_at(x).unwrap()).collect();
+	(game, frames, hashes)
+}
+
+fn budgeted() -> (Game, u32, Vec<Hash>) {
+	roll_back(Some(TickBudget::from_millis(BUDGET_MS, clock)))
+}
+
+#[test]
+fn without_a_budget_the_rollback_goes_in_one_go() {
+	let (unbudgeted, _, _) = roll_back(None);
+	assert_eq!(unbudgeted.budget_overruns(), 0);
+	assert_eq!(unbudgeted.split_replays(), 0);
+	assert!(unbudgeted.mispredictions(unbudgeted.prediction_strategy().name()) >= 1, "nothing got rolled back");
+}
+
+#[test]
+fn a_budget_splits_the_replay_over_frames() {
+	let (budgeted, frames, _) = budgeted();
+	assert!(frames > 10, "a replay of {} ticks got through in {} frames", TICKS, frames);
+	assert!(budgeted.split_replays() >= 1, "no replay got split");
+	assert!(budgeted.budget_overruns() >= frames - 2, "only {} of {} frames went over budget", budgeted.budget_overruns(), frames);
+}
+
+#[test]
+fn a_split_replay_ends_up_where_one_in_one_go_does() {
+	let (unbudgeted, _, expected) = roll_back(None);
+	let (budgeted, _, hashes) = budgeted();
+	assert_eq!(budgeted.state_hash(), unbudgeted.state_hash(), "the split replay ended up somewhere else");
+	for (index, (got, expected)) in hashes.iter().zip(&expected).enumerate() {
+		assert_eq!(got, expected, "tick {} hashes differently after the split replay", index);
+	}
+}
@@ -0,0 +1,63 @@
+//! Flips every bit of a serialized tick, one at a time, checking that the
+//! transmitted hash catches it: no flip may decode into a tick other than
+//! the one that was sent.
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use PlayerIntent::*;
+
+fn tick() -> Tick<PlayerIntent> {
+	Tick::new(300, vec![
+		CommandFrame::new(1, vec![MoveRight, JumpStart]),
+		CommandFrame::new(2, vec![MoveHorizontal(-40), JumpHold]),
+		CommandFrame::new(7, vec![]),
+	])
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+/// Whether `decoded` is `sent` after all, which a flip of a bit the
+/// encoding doesn't use may well come back as.
+fn same(decoded: &Tick<PlayerIntent>, sent: &Tick<PlayerIntent>) -> bool {
+	decoded.index() == sent.index() && decoded.command_frames() == sent.command_frames() && decoded.hash() == sent.hash()
+}
+
+#[test]
+fn a_flipped_bit_never_passes_for_another_tick() {
+	let sent = tick();
+	let bytes = encode(&sent);
+	let mut mismatches = 0;
+	for position in 0..bytes.len() {
+		for bit in 0..8 {
+			let mut flipped = bytes.clone();
+			flipped[position] ^= 1 << bit;
+			match Tick::<PlayerIntent>::from_bytes(&mut ByteReader::new(&flipped)) {
+				Ok(decoded) => assert!(same(&decoded, &sent), "bit {} of byte {} decoded as {:?}", bit, position, decoded),
+				Err(DecodeError::HashMismatch { .. }) => mismatches += 1,
+				Err(_) => {},
+			}
+		}
+	}
+	// Most flips leave something well formed, that only the hash gives away.
+	assert!(mismatches > bytes.len() * 8 / 2, "only {} flips were caught by the hash", mismatches);
+}
+
+#[test]
+fn a_flipped_byte_in_a_tick_packet_is_rejected() {
+	let sent = tick();
+	let bytes = encode(&Packet::AuthoritativeTick(sent.clone()));
+	// Past the header and tag, each byte belongs to the tick.
+	for position in 5..bytes.len() {
+		let mut flipped = bytes.clone();
+		flipped[position] ^= 0xff;
+		if let Ok(Packet::AuthoritativeTick(decoded)) = Packet::<PlayerIntent>::decode(&flipped) {
+			assert!(same(&decoded, &sent), "byte {} flipped decoded as {:?}", position, decoded);
+		}
+	}
+	let Ok(Packet::AuthoritativeTick(decoded)) = Packet::<PlayerIntent>::decode(&bytes) else { panic!("the tick didn't decode") };
+	assert!(same(&decoded, &sent));
+}
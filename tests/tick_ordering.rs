@@ -0,0 +1,182 @@
+//! Hands a client its authoritative ticks shuffled, with some of them
+//! arriving twice, and checks that it ends up confirming everything it
+//! would have if they had come in order, the duplicates passing without
+//! notice. Ticks older than the accepted head are counted as stale, and
+//! ticks from too far ahead only wait in the pending map up to its cap.
+
+mod common;
+
+use command_networking_rs::game::{Game, MemoryBudget};
+use command_networking_rs::overlay::DebugStats;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick};
+use command_networking_rs::TICK_DELTA;
+use PlayerIntent::*;
+use common::deliver;
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const TICKS: u64 = 40;
+
+/// A game, and how many frames it has run.
+struct Client {
+	game: Game,
+	frames: u64,
+}
+
+impl Client {
+	/// A client which has predicted `TICKS` ticks of running right, with
+	/// the remote player in the game from the start.
+	fn new() -> Self {
+		let mut game = Game::new(LOCAL);
+		deliver(&mut game, Packet::Join(REMOTE));
+		game.receive(0.0);
+		for _ in 0..TICKS {
+			game.run_tick(|_| vec![MoveRight]);
+		}
+		Self { game, frames: 0 }
+	}
+
+	fn frame(&mut self) {
+		self.frames += 1;
+		self.game.receive(self.frames as f64 * TICK_DELTA as f64);
+		self.game.run_tick(|_| vec![MoveRight]);
+	}
+
+	/// Delivers `indices` a few at a time, a frame apart.
+	fn send(&mut self, indices: &[u64]) {
+		for batch in indices.chunks(4) {
+			for index in batch {
+				deliver(&mut self.game, Packet::AuthoritativeTick(authoritative(*index)));
+			}
+			self.frame();
+		}
+	}
+
+	/// Delivers `indices`, then runs frames until the jitter buffer has
+	/// played back everything. That's done well before the connection
+	/// would time out for the accepted head not moving.
+	fn play(&mut self, indices: &[u64]) {
+		self.send(indices);
+		for _ in 0..TICKS {
+			self.frame();
+		}
+	}
+
+	fn stale_ticks(&self) -> u32 {
+		let mut stats = DebugStats::default();
+		self.game.update_debug_stats(&mut stats, 0.0);
+		stats.stale_ticks
+	}
+}
+
+/// What the server says happened: we ran right as predicted, and the
+/// remote player ran left, jumping now and then, which we didn't know.
+fn authoritative(index: u64) -> Tick<PlayerIntent> {
+	let remote = match index % 10 {
+		0 => vec![MoveLeft, JumpStart],
+		_ => vec![MoveLeft],
+	};
+	Tick::new(index, vec![CommandFrame::new(LOCAL, vec![MoveRight]), CommandFrame::new(REMOTE, remote)])
+}
+
+/// `0..TICKS` shuffled from a fixed seed, each tick arriving up to seven
+/// places from where it should, and every fifth one coming again a few
+/// places later.
+fn shuffled() -> Vec<u64> {
+	let mut indices: Vec<u64> = (0..TICKS).collect();
+	let mut seed = 0x2545_f491_4f6c_dd1du64;
+	for window in indices.chunks_mut(8) {
+		for position in (1..window.len()).rev() {
+			seed ^= seed << 13;
+			seed ^= seed >> 7;
+			seed ^= seed << 17;
+			window.swap(position, (seed % (position as u64 + 1)) as usize);
+		}
+	}
+	let mut delivered = vec![];
+	for (position, index) in indices.iter().enumerate() {
+		delivered.push(*index);
+		if position >= 3 && position % 5 == 0 {
+			delivered.push(indices[position - 3]);
+		}
+	}
+	delivered
+}
+
+#[test]
+fn shuffled_and_duplicated_ticks_end_up_in_order() {
+	let mut in_order = Client::new();
+	in_order.play(&(0..TICKS).collect::<Vec<_>>());
+	assert_eq!(in_order.game.accepted_head(), TICKS - 1);
+
+	let indices = shuffled();
+	assert_ne!(indices[..TICKS as usize], (0..TICKS).collect::<Vec<_>>()[..]);
+	let mut shuffled = Client::new();
+	shuffled.play(&indices);
+	let (game, expected) = (&shuffled.game, &in_order.game);
+	assert_eq!(game.accepted_head(), TICKS - 1, "confirmed up to {} only", game.accepted_head());
+	for index in 0..TICKS {
+		assert_eq!(game.state_hash_at(index), expected.state_hash_at(index), "tick {}", index);
+	}
+	assert_eq!(game.players()[&REMOTE].position(), expected.players()[&REMOTE].position());
+	assert_eq!(shuffled.stale_ticks(), 0, "duplicates were counted as stale");
+}
+
+#[test]
+fn ticks_older_than_the_accepted_head_are_counted_and_ignored() {
+	let (mut client, mut expected) = (Client::new(), Client::new());
+	for client in [&mut client, &mut expected] {
+		client.play(&(0..TICKS / 2).collect::<Vec<_>>());
+		assert_eq!(client.game.accepted_head(), TICKS / 2 - 1);
+	}
+
+	// Tick 5 again, but not as it was confirmed, ahead of the rest.
+	let forged = Tick::new(5, vec![CommandFrame::new(LOCAL, vec![]), CommandFrame::new(REMOTE, vec![JumpStart])]);
+	deliver(&mut client.game, Packet::AuthoritativeTick(forged));
+	for client in [&mut client, &mut expected] {
+		client.play(&(TICKS / 2..TICKS).collect::<Vec<_>>());
+		assert_eq!(client.game.accepted_head(), TICKS - 1);
+	}
+	assert_eq!(client.stale_ticks(), 1);
+	assert_eq!(client.game.state_hash(), expected.game.state_hash(), "a tick behind the accepted head got applied");
+}
+
+#[test]
+fn the_pending_map_is_capped() {
+	const CAP: usize = 8;
+	let mut client = Client::new();
+	client.game.set_memory_budget(MemoryBudget { pending_ticks: CAP, ..Default::default() });
+
+	// Everything but tick 0, which holds the rest up.
+	client.send(&(1..TICKS).collect::<Vec<_>>());
+	for _ in 0..TICKS - 10 {
+		client.frame();
+	}
+	assert_eq!(client.game.accepted_head(), 0, "got ahead without tick 0");
+	assert_eq!(client.stale_ticks() as u64, TICKS - 1 - CAP as u64, "held on to more than {} ticks", CAP);
+
+	// Tick 0 at last, and only what got dropped comes again, so the rest
+	// must have been held on to.
+	let resent: Vec<u64> = [0].into_iter().chain(CAP as u64 + 1..TICKS).collect();
+	client.play(&resent);
+	assert_eq!(client.game.accepted_head(), TICKS - 1, "the ticks held on to weren't confirmed");
+	assert_eq!(client.stale_ticks() as u64, TICKS - 1 - CAP as u64);
+}
+
+#[test]
+fn ticks_sent_again_hold_nothing_up() {
+	// Every tick coming again with the two after it, the way a server
+	// sends its outcomes redundantly, keeps pace with each coming once.
+	let (mut client, mut expected) = (Client::new(), Client::new());
+	for index in 0..TICKS {
+		let again: Vec<u64> = (index.saturating_sub(2)..=index).collect();
+		client.send(&again);
+		expected.send(&[index]);
+		assert_eq!(client.game.accepted_head(), expected.game.accepted_head(), "fell behind on tick {}", index);
+		let mut stats = DebugStats::default();
+		client.game.update_debug_stats(&mut stats, 0.0);
+		assert!(stats.jitter_depth <= stats.jitter_target + 1, "{} ticks buffered on tick {}", stats.jitter_depth, index);
+	}
+	assert_eq!(client.game.accepted_head(), TICKS - 2);
+	assert_eq!(client.stale_ticks(), 0, "duplicates were counted as stale");
+}
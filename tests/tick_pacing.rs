@@ -0,0 +1,156 @@
+//! Runs the client against an in-process server keeping time of its own,
+//! with the client's clock running a little fast or slow, to check that
+//! pacing the ticks keeps the client about as far ahead of the server as
+//! the latency and margin call for, where it would drift off otherwise.
+
+mod common;
+
+use std::cell::Cell;
+
+use command_networking_rs::game::{Game, TickAccumulator, MAX_TICK_SPEED_ADJUSTMENT};
+use command_networking_rs::protocol::PlayerIntent;
+use command_networking_rs::transport::{LoopbackTransport, SimulatedConditions, SimulatedTransport, Transport};
+use common::{now, NOW_US, send};
+
+const LOCAL: u16 = 0;
+const REMOTE: u16 = 1;
+const LATENCY_MS: u32 = 100;
+const MARGIN: u8 = 2;
+const FPS: u64 = 60;
+/// How long each run goes on for, and how much of the end of it is
+/// checked, in seconds; pacing gets some time to settle before that.
+const DURATION: u64 = 90;
+const CHECKED: u64 = 30;
+/// How far off the client's clock is, in parts per million, either way.
+const SKEW: i64 = 15_000;
+
+thread_local! {
+	/// How much faster the client's clock runs, in parts per million.
+	static SKEW_PPM: Cell<i64> = const { Cell::new(0) };
+}
+
+fn skew() -> f64 {
+	SKEW_PPM.get() as f64 / 1e6
+}
+
+/// The time as the client tells it.
+fn client_now() -> f64 {
+	now() * (1.0 + skew())
+}
+
+struct Run {
+	/// Ticks ahead, averaged over the checked part of the run.
+	ticks_ahead: f64,
+	target: u64,
+	/// The tick speed, averaged over the checked part of the run.
+	speed: f64,
+	/// Frames in the checked part of the run which were throttled.
+	throttled: u64,
+	/// Ticks in the checked part of the run which the server corrected;
+	/// with intents this steady, only ever for our frames coming too late.
+	corrected: u64,
+}
+
+/// Plays a game for `DURATION` seconds with the client's clock off by
+/// `skew_ppm`, pacing the ticks unless told otherwise.
+fn run(skew_ppm: i64, paced: bool) -> Run {
+	NOW_US.set(0);
+	SKEW_PPM.set(skew_ppm);
+	let conditions = SimulatedConditions { latency_ms: LATENCY_MS, jitter_ms: 10, ..Default::default() };
+	let server = LoopbackTransport::new(LOCAL)
+		.with_clock(now)
+		.with_remote(REMOTE, vec![(0, vec![PlayerIntent::MoveLeft])]);
+	let mut transport = SimulatedTransport::new(server, conditions, 11).with_clock(now);
+	let mut game = Game::new(LOCAL);
+	game.sync_clock(MARGIN);
+	let mut accumulator = TickAccumulator::default();
+
+	let frames = DURATION * FPS;
+	let checked_from = frames - CHECKED * FPS;
+	let (mut ticks_ahead, mut speed, mut throttled) = (0.0, 0.0, 0);
+	let mut corrected_before = 0;
+	for number in 1..=frames {
+		NOW_US.set(number * 1_000_000 / FPS);
+		let frame_time = (1.0 + skew()) as f32 / FPS as f32;
+		let speed_factor = if paced { game.tick_speed() } else { 1.0 };
+		let due_ticks = accumulator.advance(frame_time * speed_factor, game.tick_rate().delta());
+
+		transport.poll(&mut |packet| game.dispatch_packet(packet));
+		game.receive(client_now());
+		if let Some(request) = game.time_request(client_now()) {
+			send(&mut transport, request);
+		}
+		for _ in 0..due_ticks {
+			if let Some(packet) = game.run_tick(|_| vec![PlayerIntent::MoveRight]) {
+				send(&mut transport, packet);
+				game.tick_sent(client_now());
+			}
+		}
+
+		if number == checked_from {
+			corrected_before = game.prediction_stats().corrected;
+		}
+		if number > checked_from {
+			ticks_ahead += game.ticks_ahead() as f64;
+			speed += game.tick_speed() as f64;
+			throttled += game.is_stalled() as u64;
+		}
+	}
+	assert_eq!(game.desync(), None);
+	let checked = (CHECKED * FPS) as f64;
+	Run {
+		ticks_ahead: ticks_ahead / checked,
+		target: game.target_ticks_ahead().expect("no round trip measured"),
+		speed: speed / checked,
+		throttled,
+		corrected: game.prediction_stats().corrected - corrected_before
+	}
+}
+
+/// Checks that a paced client with its clock off by `skew_ppm` stays
+/// on target, neither throttled nor late.
+fn check_paced(skew_ppm: i64) {
+	let paced = run(skew_ppm, true);
+	assert!(
+		(paced.ticks_ahead - paced.target as f64).abs() <= 1.0,
+		"{:+} ppm: {:.2} ticks ahead on average, aiming for {}",
+		skew_ppm,
+		paced.ticks_ahead,
+		paced.target
+	);
+	assert_eq!(paced.throttled, 0, "{:+} ppm: got throttled", skew_ppm);
+	assert_eq!(paced.corrected, 0, "{:+} ppm: frames came too late", skew_ppm);
+	// Running fast, the ticks have to slow down to make up for it, and
+	// the other way around.
+	let expected = 1.0 / (1.0 + skew_ppm as f64 / 1e6);
+	assert!((paced.speed - expected).abs() < 0.003, "{:+} ppm: running at {:.4}, rather than {:.4}", skew_ppm, paced.speed, expected);
+}
+
+#[test]
+fn the_skew_is_one_pacing_can_make_up_for() {
+	// Outside of the limit, there'd be no keeping up; more than a few
+	// ticks' worth of drift over a run, and there'd be nothing to correct.
+	assert!(SKEW as f32 / 1e6 < MAX_TICK_SPEED_ADJUSTMENT);
+}
+
+#[test]
+fn pacing_keeps_a_fast_clock_on_target() {
+	check_paced(SKEW);
+}
+
+#[test]
+fn pacing_keeps_a_slow_clock_on_target() {
+	check_paced(-SKEW);
+}
+
+#[test]
+fn a_fast_clock_runs_into_the_throttle_unpaced() {
+	let unpaced = run(SKEW, false);
+	assert!(unpaced.throttled + unpaced.corrected > 0, "kept up without pacing");
+}
+
+#[test]
+fn a_slow_clock_has_its_frames_miss_their_ticks_unpaced() {
+	let unpaced = run(-SKEW, false);
+	assert!(unpaced.throttled + unpaced.corrected > 0, "kept up without pacing");
+}
@@ -0,0 +1,77 @@
+//! Runs the same input script, given in seconds rather than ticks, at 20
+//! and at 30 ticks per second, and checks that the player ends up in about
+//! the same spot either way.
+
+use command_networking_rs::game::Game;
+use command_networking_rs::protocol::PlayerIntent;
+use PlayerIntent::*;
+
+/// Seconds into the run alongside the intents held from then on.
+const SCRIPT: &[(f32, &[PlayerIntent])] = &[
+	(0.0, &[MoveRight]),
+	(1.0, &[MoveRight, JumpStart]),
+	(1.05, &[MoveRight, JumpHold]),
+	(1.5, &[MoveLeft]),
+	(2.0, &[]),
+];
+const DURATION: f32 = 3.0;
+
+/// How far the player moved sideways in total, and how high it jumped, at
+/// `hz` ticks per second.
+fn run(hz: u8) -> (f32, f32) {
+	let mut game = Game::new(0);
+	game.set_tick_rate(hz);
+	let (_, floor) = game.players()[&0].rendered_position();
+	let mut distance = 0.0;
+	let mut peak = floor;
+
+	let ticks = (DURATION * hz as f32).round() as u32;
+	let mut jumped = false;
+	for index in 0..ticks {
+		let time = index as f32 / hz as f32;
+		let (_, intents) = SCRIPT.iter().rev().find(|(start, _)| *start <= time + 1e-4).unwrap();
+		// Jumps only start once, however many ticks the first step spans.
+		let intents: Vec<PlayerIntent> = intents.iter()
+			.copied()
+			.filter(|x| *x != JumpStart || !jumped)
+			.collect();
+		jumped |= intents.contains(&JumpStart);
+
+		// Without any rollbacks, that's exactly the simulated position.
+		let (x, _) = game.players()[&0].rendered_position();
+		let tick = game.predict_tick(intents);
+		game.advance(tick);
+		let (next_x, y) = game.players()[&0].rendered_position();
+		distance += (next_x - x).abs();
+		peak = peak.min(y);
+	}
+
+	(distance, floor - peak)
+}
+
+// Each tick advances by a slightly different step, so the two only agree
+// up to how finely they sample the same motion.
+
+#[test]
+fn the_player_moves_as_far_at_either_rate() {
+	let (distance_20, _) = run(20);
+	let (distance_30, _) = run(30);
+	assert!((distance_20 - distance_30).abs() < distance_20 * 0.02, "moved {:.2}px at 20 Hz, {:.2}px at 30 Hz", distance_20, distance_30);
+}
+
+#[test]
+fn the_player_jumps_as_high_at_either_rate() {
+	let (_, height_20) = run(20);
+	let (_, height_30) = run(30);
+	assert!((height_20 - height_30).abs() < height_20 * 0.05, "jumped {:.2}px at 20 Hz, {:.2}px at 30 Hz", height_20, height_30);
+}
+
+#[test]
+fn switching_rates_mid_game_stalls_it() {
+	// Once ticks have been predicted, it's too late to switch.
+	let mut game = Game::new(0);
+	game.run_tick(|_| vec![]);
+	game.set_tick_rate(30);
+	assert_eq!(game.tick_rate_mismatch(), Some(30));
+	assert!(game.run_tick(|_| vec![MoveRight]).is_none(), "a game at the wrong tick rate kept going");
+}
@@ -0,0 +1,107 @@
+//! Sends datagrams between a `UdpTransport` and a relay on the loopback
+//! interface, right at `MAX_DATAGRAM_SIZE` and one byte past it. Packets
+//! are never fragmented: what fits goes through whole, what doesn't is
+//! turned down for good on the way out and dropped on the way in. Then
+//! checks that what a client really sends is well within the limit.
+
+use std::net::UdpSocket;
+use std::thread::sleep;
+use std::time::Duration;
+
+use command_networking_rs::{ByteWriter, NetType};
+use command_networking_rs::game::Game;
+use command_networking_rs::overlay::DebugStats;
+use command_networking_rs::protocol::{CommandFrame, Packet, PlayerIntent, Tick, MAX_PAST_FRAMES};
+use command_networking_rs::transport::{SendError, Transport, UdpTransport, MAX_DATAGRAM_SIZE};
+use PlayerIntent::*;
+
+/// A relay, and a transport sending to it.
+fn connect() -> (UdpSocket, UdpTransport) {
+	let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+	relay.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+	let transport = UdpTransport::bind("127.0.0.1:0", relay.local_addr().unwrap()).unwrap();
+	(relay, transport)
+}
+
+/// Everything the transport has received, after giving it a moment to.
+fn poll(transport: &mut UdpTransport) -> Vec<Vec<u8>> {
+	sleep(Duration::from_millis(50));
+	let mut received = vec![];
+	transport.poll(&mut |x| received.push(x.to_vec()));
+	received
+}
+
+fn encode(packet: Packet<PlayerIntent>) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	packet.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+#[test]
+fn oversized_datagrams_are_turned_down() {
+	let (relay, mut transport) = connect();
+	let mut buffer = vec![0; 2 * MAX_DATAGRAM_SIZE];
+
+	let error = transport.send(&vec![1; MAX_DATAGRAM_SIZE + 1]).unwrap_err();
+	assert_eq!(error, SendError::TooLarge);
+	assert!(!error.is_retryable(), "an oversized packet would be tried again");
+	transport.send(&vec![2; MAX_DATAGRAM_SIZE]).unwrap();
+	// Only the one that fit made it, in one piece.
+	let (size, _) = relay.recv_from(&mut buffer).unwrap();
+	assert_eq!(size, MAX_DATAGRAM_SIZE);
+	assert!(buffer[..size].iter().all(|x| *x == 2));
+	assert!(relay.recv_from(&mut buffer).is_err(), "something else arrived");
+
+	// Nor does the game hold on to it for sending again.
+	let mut game = Game::new(0);
+	game.send(&mut transport, &vec![3; MAX_DATAGRAM_SIZE + 1], 0.0);
+	let mut stats = DebugStats::default();
+	game.update_debug_stats(&mut stats, 0.0);
+	assert_eq!((stats.resend_queue, stats.send_failures), (0, 0));
+}
+
+#[test]
+fn oversized_datagrams_are_dropped_on_arrival() {
+	let (relay, mut transport) = connect();
+	transport.send(b"hello").unwrap();
+	let mut buffer = [0; 16];
+	let (_, address) = relay.recv_from(&mut buffer).unwrap();
+
+	relay.send_to(&vec![1; MAX_DATAGRAM_SIZE], address).unwrap();
+	relay.send_to(&vec![2; MAX_DATAGRAM_SIZE + 1], address).unwrap();
+	relay.send_to(&[3; 8], address).unwrap();
+	// Anyone other than the relay isn't listened to.
+	let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+	stranger.send_to(&[4; 8], address).unwrap();
+
+	let received = poll(&mut transport);
+	let sizes: Vec<(usize, u8)> = received.iter().map(|x| (x.len(), x[0])).collect();
+	assert_eq!(sizes, [(MAX_DATAGRAM_SIZE, 1), (8, 3)]);
+}
+
+#[test]
+fn what_a_client_sends_fits() {
+	// Four players sharing a screen, all of them moving and jumping on
+	// every tick, with as many past frames as can be repeated.
+	let mut game = Game::new(0);
+	for player in 1..4 {
+		game.add_local_player(player);
+	}
+	game.set_redundant_frames(MAX_PAST_FRAMES as u8);
+	// Without a server answering, that's only as far as it gets ahead.
+	let largest = (0..20)
+		.map_while(|_| game.run_tick(|_| vec![MoveHorizontal(-100), JumpHold]))
+		.map(|x| encode(x).len())
+		.max()
+		.unwrap();
+	assert!(largest * 4 < MAX_DATAGRAM_SIZE, "a tick took {} bytes", largest);
+}
+
+#[test]
+fn a_busy_servers_tick_fits() {
+	// The server's ticks carry every player's frames, so they grow with
+	// the game. A busy one of 64 players still fits.
+	let frames = (0..64).map(|x| CommandFrame::new(x, vec![MoveHorizontal(-100), JumpHold])).collect();
+	let tick = encode(Packet::AuthoritativeTick(Tick::new(u32::MAX as u64, frames))).len();
+	assert!(tick < MAX_DATAGRAM_SIZE, "64 players' tick took {} bytes", tick);
+}
@@ -0,0 +1,99 @@
+//! Pins how long varints get at each boundary, round trips them there, and
+//! measures what writing tick indices and counts as varints saves over the
+//! fixed width fields they replaced.
+
+use command_networking_rs::{ByteReader, ByteWriter, DecodeError, NetType};
+use command_networking_rs::protocol::{CommandFrame, PlayerIntent, Tick};
+use PlayerIntent::*;
+
+fn varint(value: u64) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	writer.write_varint(value);
+	writer.as_slice().to_vec()
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+#[test]
+fn boundaries_round_trip() {
+	// Every seven bits take another byte.
+	let boundaries: Vec<(u64, usize)> = (1..10u32)
+		.flat_map(|bytes| [((1u64 << (7 * bytes)) - 1, bytes as usize), (1u64 << (7 * bytes), bytes as usize + 1)])
+		.chain([(0, 1), (u32::MAX as u64, 5), (u64::MAX, 10)])
+		.collect();
+	for (value, len) in boundaries {
+		let bytes = varint(value);
+		assert_eq!(bytes.len(), len, "{} took {} bytes", value, bytes.len());
+		assert_eq!(ByteWriter::varint_len(value), len, "{}", value);
+
+		let mut reader = ByteReader::new(&bytes);
+		assert_eq!(reader.read_varint().unwrap(), value);
+		assert_eq!(reader.remaining(), 0);
+		for cut in 0..bytes.len() {
+			assert!(
+				matches!(ByteReader::new(&bytes[..cut]).read_varint(), Err(DecodeError::UnexpectedEof { .. })),
+				"{} read from {} of its {} bytes", value, cut, len
+			);
+		}
+	}
+}
+
+#[test]
+fn boundary_bytes() {
+	assert_eq!(varint(127), [0x7f]);
+	assert_eq!(varint(128), [0x80, 0x01]);
+	assert_eq!(varint(300), [0xac, 0x02]);
+	assert_eq!(varint(16383), [0xff, 0x7f]);
+	assert_eq!(varint(16384), [0x80, 0x80, 0x01]);
+}
+
+#[test]
+fn tick_indices_grow_a_byte_at_each_boundary() {
+	let frames = || vec![CommandFrame::new(1, vec![MoveRight])];
+	for (below, above) in [(127, 128), (16383, 16384)] {
+		let (short, long) = (Tick::new(below, frames()), Tick::new(above, frames()));
+		assert_eq!(encode(&long).len(), encode(&short).len() + 1, "{} to {}", below, above);
+		for tick in [short, long] {
+			let decoded = Tick::<PlayerIntent>::from_bytes(&mut ByteReader::new(&encode(&tick))).unwrap();
+			assert_eq!(decoded.index(), tick.index());
+			assert_eq!(decoded.hash(), tick.hash());
+		}
+	}
+}
+
+/// What `tick` took up with its index as a u64, and its frame and intent
+/// counts as single bytes, the way ticks went out before varints. Intents
+/// and the hash are written as they are now.
+fn fixed_width_len(tick: &Tick<PlayerIntent>) -> usize {
+	let mut writer = ByteWriter::new();
+	writer.write_u64_le(tick.index());
+	writer.write_u8(tick.command_frames().len() as u8);
+	for frame in tick.command_frames() {
+		frame.owner().to_bytes(&mut writer).unwrap();
+		writer.write_u8(frame.intents().len() as u8);
+		for intent in frame.intents() {
+			intent.to_bytes(&mut writer).unwrap();
+		}
+		frame.offset().to_bytes(&mut writer).unwrap();
+	}
+	tick.hash().to_bytes(&mut writer).unwrap();
+	writer.len()
+}
+
+#[test]
+fn a_typical_tick_gets_smaller() {
+	// Two players, a minute into the game at 20 ticks a second.
+	let tick = Tick::new(1200, vec![
+		CommandFrame::new(1, vec![MoveRight]),
+		CommandFrame::new(2, vec![MoveLeft, JumpHold]),
+	]);
+	let varint_len = encode(&tick).len();
+	let fixed_len = fixed_width_len(&tick);
+	// The index alone takes two bytes rather than eight.
+	assert_eq!(ByteWriter::varint_len(tick.index()), 2);
+	assert!(varint_len + 6 <= fixed_len, "{} bytes against {}", varint_len, fixed_len);
+}
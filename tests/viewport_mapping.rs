@@ -0,0 +1,88 @@
+//! Fits the playfield into a few window sizes, and checks it stays square
+//! and centered, and that mapping back from the screen lands on the same
+//! logical pixel. Then follows a player across the world, checking the view
+//! never goes past its edges. None of this needs a window.
+
+use command_networking_rs::viewport::{view_origin, Viewport};
+use command_networking_rs::{SCREEN_SIZE, WORLD_HEIGHT, WORLD_WIDTH};
+
+fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+	(a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+}
+
+const SIZE: f32 = SCREEN_SIZE as f32;
+
+#[test]
+fn exactly_the_playfields_size_leaves_no_bars() {
+	// Or a multiple of it.
+	let viewport = Viewport::fit(SIZE, SIZE);
+	assert_eq!(viewport.scale(), 1.0);
+	assert!(close(viewport.to_screen((10.0, 20.0)), (10.0, 20.0)));
+	let viewport = Viewport::fit(SIZE * 4.0, SIZE * 4.0);
+	assert!(close(viewport.to_screen((SIZE, SIZE)), (SIZE * 4.0, SIZE * 4.0)));
+}
+
+#[test]
+fn a_wide_window_gets_bars_left_and_right() {
+	let viewport = Viewport::fit(1920.0, 1080.0);
+	assert_eq!(viewport.scale(), 1080.0 / SIZE);
+	assert!(close(viewport.to_screen((0.0, 0.0)), ((1920.0 - 1080.0) / 2.0, 0.0)));
+	assert!(close(viewport.to_screen((SIZE, SIZE)), ((1920.0 + 1080.0) / 2.0, 1080.0)));
+	assert!(close(viewport.to_screen((SIZE / 2.0, SIZE / 2.0)), (960.0, 540.0)));
+}
+
+#[test]
+fn a_tall_window_gets_bars_above_and_below() {
+	let viewport = Viewport::fit(300.0, 900.0);
+	assert!(close(viewport.to_screen((0.0, 0.0)), (0.0, 300.0)));
+	assert!(close(viewport.to_screen((SIZE, SIZE)), (300.0, 600.0)));
+}
+
+#[test]
+fn mapping_back_from_the_screen_lands_on_the_same_pixel() {
+	for (width, height) in [(256.0, 256.0), (1920.0, 1080.0), (300.0, 900.0), (3840.0, 2160.0), (123.0, 457.0)] {
+		let viewport = Viewport::fit(width, height);
+		for point in [(0.0, 0.0), (17.5, 200.25), (SIZE, SIZE), (-10.0, SIZE + 10.0)] {
+			let back = viewport.to_logical(viewport.to_screen(point));
+			assert!(close(back, point), "{:?} came back as {:?} at {}x{}", point, back, width, height);
+		}
+	}
+}
+
+#[test]
+fn the_bars_are_outside_the_playfield() {
+	let (x, _) = Viewport::fit(1920.0, 1080.0).to_logical((10.0, 540.0));
+	assert!(x < 0.0);
+}
+
+#[test]
+fn a_minimized_window_doesnt_divide_by_zero() {
+	let viewport = Viewport::fit(0.0, 0.0);
+	assert!(viewport.to_logical((0.0, 0.0)).0.is_finite());
+}
+
+#[test]
+fn the_view_follows_the_player_until_it_runs_into_a_wall() {
+	let world = (WORLD_WIDTH as f32, WORLD_HEIGHT as f32);
+	let max_x = (WORLD_WIDTH - SCREEN_SIZE) as f32;
+	assert_eq!(view_origin((500.0, 100.0), world).0, 500.0 - SIZE / 2.0);
+	assert_eq!(view_origin((10.0, 100.0), world), (0.0, 0.0));
+	assert_eq!(view_origin((WORLD_WIDTH as f32 - 10.0, 100.0), world).0, max_x);
+}
+
+#[test]
+fn the_view_never_goes_past_the_worlds_edges() {
+	let world = (WORLD_WIDTH as f32, WORLD_HEIGHT as f32);
+	for x in (0..=WORLD_WIDTH).step_by(7) {
+		for y in [-50.0, 0.0, WORLD_HEIGHT as f32, 1000.0] {
+			let (left, top) = view_origin((x as f32, y), world);
+			assert!(left >= 0.0 && left + SIZE <= WORLD_WIDTH as f32);
+			assert!(top >= 0.0 && top + SIZE <= WORLD_HEIGHT.max(SCREEN_SIZE) as f32);
+		}
+	}
+}
+
+#[test]
+fn a_world_no_larger_than_the_screen_never_moves() {
+	assert_eq!(view_origin((300.0, 300.0), (SIZE / 2.0, SIZE)), (0.0, 0.0));
+}
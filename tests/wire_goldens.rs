@@ -0,0 +1,193 @@
+//! Pins the exact bytes a few hand-built values go over the wire as. The JS
+//! host parses these on its own, so any change here has to be deliberate,
+//! and come with a bump of `PROTOCOL_VERSION`.
+//!
+//! A golden that no longer matches fails with its current encoding, ready
+//! to be copied back in below.
+
+use command_networking_rs::{ByteReader, ByteWriter, NetType};
+use command_networking_rs::game::{PlayerSummary, SimConfig, StateSummary};
+use command_networking_rs::protocol::{CommandFrame, ConnectAccept, Packet, PlayerIntent, SnapshotReason, SnapshotRequest, Tick};
+use PlayerIntent::*;
+
+/// A value, and the bytes it's written as.
+struct Golden<T> {
+	name: &'static str,
+	value: T,
+	bytes: &'static [u8],
+}
+
+fn encode(value: &impl NetType) -> Vec<u8> {
+	let mut writer = ByteWriter::new();
+	value.to_bytes(&mut writer).unwrap();
+	writer.as_slice().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// `bytes` as a slice literal, the way the goldens below are written.
+fn literal(bytes: &[u8]) -> String {
+	let bytes: Vec<String> = bytes.iter().map(|x| format!("0x{:02x}", x)).collect();
+	format!("&[{}]", bytes.join(", "))
+}
+
+/// Checks `golden` both ways.
+fn check<T: NetType + PartialEq + std::fmt::Debug>(golden: Golden<T>) {
+	let bytes = encode(&golden.value);
+	assert_eq!(bytes, golden.bytes, "{} encodes differently, as {}", golden.name, literal(&bytes));
+	let decoded = T::from_bytes(&mut ByteReader::new(golden.bytes)).expect(golden.name);
+	assert_eq!(decoded, golden.value, "{} decodes differently", golden.name);
+}
+
+fn empty_tick() -> Tick<PlayerIntent> {
+	Tick::new(0, vec![])
+}
+
+/// One frame packs into a single byte, the other has to go as a list, and
+/// came in a quarter of the way into the tick.
+fn two_player_tick() -> Tick<PlayerIntent> {
+	Tick::new(300, vec![
+		CommandFrame::new(1, vec![MoveRight, JumpStart]),
+		CommandFrame::new(2, vec![MoveHorizontal(-64), JumpHold]).with_offset(64),
+	])
+}
+
+/// Ticks carry their hash, so their bytes depend on the hash algorithm.
+#[cfg(not(feature = "crc32"))]
+mod hashed {
+	pub const EMPTY_TICK: &[u8] = &[
+		0x00, 0x00, 0x37, 0x47, 0x08, 0xff, 0xf7, 0x71, 0x9d, 0xd5, 0x97, 0x9e,
+		0xc8, 0x75, 0xd5, 0x6c, 0xd2, 0x28, 0x6f, 0x6d, 0x3c, 0xf7, 0xec, 0x31,
+		0x7a, 0x3b, 0x25, 0x63, 0x2a, 0xab, 0x28, 0xec, 0x37, 0xbb,
+	];
+	pub const TWO_PLAYER_TICK: &[u8] = &[
+		0xac, 0x02, 0x02, 0x01, 0x00, 0x06, 0x00, 0x02, 0x00, 0x80, 0x02, 0x03,
+		0xc0, 0x04, 0x40, 0x1f, 0xec, 0x71, 0xe6, 0x78, 0x0f, 0x57, 0x6a, 0x5c,
+		0x3d, 0x76, 0x85, 0x9d, 0xec, 0x00, 0xc9, 0xef, 0xc4, 0x50, 0xcd, 0x0a,
+		0xd5, 0xe6, 0x44, 0xe8, 0x6d, 0x52, 0xd6, 0x4e, 0xb9, 0x08, 0x38,
+	];
+	pub const EMPTY_TICK_HASH: &str = "374708fff7719dd5979ec875d56cd2286f6d3cf7ec317a3b25632aab28ec37bb";
+	pub const TWO_PLAYER_TICK_HASH: &str = "1fec71e6780f576a5c3d76859dec00c9efc450cd0ad5e644e86d52d64eb90838";
+}
+
+#[cfg(feature = "crc32")]
+mod hashed {
+	pub const EMPTY_TICK: &[u8] = &[0x00, 0x00, 0x55, 0x4b, 0xbb, 0xec];
+	pub const TWO_PLAYER_TICK: &[u8] = &[
+		0xac, 0x02, 0x02, 0x01, 0x00, 0x06, 0x00, 0x02, 0x00, 0x80, 0x02, 0x03,
+		0xc0, 0x04, 0x40, 0xa1, 0xa0, 0x9f, 0x62,
+	];
+	pub const EMPTY_TICK_HASH: &str = "554bbbec";
+	pub const TWO_PLAYER_TICK_HASH: &str = "a1a09f62";
+}
+
+/// Sim configs are made of scalars, so their bytes depend on whether those
+/// are fixed point.
+#[cfg(not(feature = "fixed-point"))]
+mod scalars {
+	pub const CONNECT_ACCEPT: &[u8] = &[
+		0x02, 0x01, 0x14, 0x2c, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x40, 0x75, 0x44, 0x00, 0x40, 0x09, 0xc4, 0x00, 0x00, 0x7a, 0x44, 0x00,
+		0x00, 0x48, 0x43, 0x00, 0x00, 0xc8, 0x44, 0x00, 0x00, 0xf0, 0x41, 0x00,
+		0x00, 0xf0, 0x41, 0x00, 0x00, 0x80, 0x44, 0x00, 0x00, 0x80, 0x43,
+	];
+}
+
+#[cfg(feature = "fixed-point")]
+mod scalars {
+	pub const CONNECT_ACCEPT: &[u8] = &[
+		0x02, 0x01, 0x14, 0x2c, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x00, 0xd5, 0x03, 0x00, 0x00, 0xdb, 0xfd, 0x00, 0x00, 0xe8, 0x03, 0x00,
+		0x00, 0xc8, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x1e, 0x00, 0x00,
+		0x00, 0x1e, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01,
+	];
+}
+
+/// The packet header and a message needing no tick, which pins the version
+/// and hash algorithm along with the layout.
+#[cfg(not(feature = "crc32"))]
+const RECEIVED_PACKET: &[u8] = &[0x43, 0x4e, 0x1c, 0x00, 0x02, 0xac, 0x02];
+#[cfg(feature = "crc32")]
+const RECEIVED_PACKET: &[u8] = &[0x43, 0x4e, 0x1c, 0x01, 0x02, 0xac, 0x02];
+
+const EMPTY_FRAME: &[u8] = &[0x07, 0x00, 0x00, 0x00];
+/// The reason is a single byte, see `SnapshotReason`.
+const SNAPSHOT_REQUEST: &[u8] = &[0x02, 0x01, 0x02];
+/// Handed to the host through `on_state_changed`, which parses it itself.
+const STATE_SUMMARY: &[u8] = &[
+	0x2c, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00,
+	0x00, 0x48, 0x41, 0x00, 0x00, 0x62, 0x43, 0x03, 0x00, 0x00, 0x00, 0x02,
+	0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0xc1, 0x00, 0x00, 0x00,
+	0x00,
+];
+
+#[test]
+fn an_empty_frame_matches() {
+	check(Golden {
+		name: "EMPTY_FRAME",
+		value: CommandFrame::<PlayerIntent>::new(7, vec![]),
+		bytes: EMPTY_FRAME,
+	});
+}
+
+#[test]
+fn a_connect_accept_matches() {
+	// ClientIds are two bytes, little endian, the same as any other u16.
+	check(Golden {
+		name: "CONNECT_ACCEPT",
+		value: ConnectAccept { client_id: 0x0102, tick_rate: 20, starting_tick: 300, sim_config: SimConfig::default() },
+		bytes: scalars::CONNECT_ACCEPT,
+	});
+}
+
+#[test]
+fn a_snapshot_request_matches() {
+	check(Golden {
+		name: "SNAPSHOT_REQUEST",
+		value: SnapshotRequest { sender: 0x0102, reason: SnapshotReason::FarBehind },
+		bytes: SNAPSHOT_REQUEST,
+	});
+}
+
+#[test]
+fn a_state_summary_matches() {
+	check(Golden {
+		name: "STATE_SUMMARY",
+		value: StateSummary {
+			tick: 300,
+			players: vec![
+				PlayerSummary { owner: 1, x: 12.5, y: 226.0, score: 3 },
+				PlayerSummary { owner: 0x0102, x: 0.0, y: -8.25, score: 0 },
+			],
+		},
+		bytes: STATE_SUMMARY,
+	});
+}
+
+#[test]
+fn ticks_match() {
+	check(Golden { name: "EMPTY_TICK", value: empty_tick(), bytes: hashed::EMPTY_TICK });
+	check(Golden { name: "TWO_PLAYER_TICK", value: two_player_tick(), bytes: hashed::TWO_PLAYER_TICK });
+}
+
+#[test]
+fn tick_hashes_match() {
+	let ticks = [
+		("EMPTY_TICK_HASH", empty_tick(), hashed::EMPTY_TICK_HASH),
+		("TWO_PLAYER_TICK_HASH", two_player_tick(), hashed::TWO_PLAYER_TICK_HASH),
+	];
+	for (name, tick, hash) in ticks {
+		assert_eq!(hex(tick.hash()), hash, "{} changed", name);
+	}
+}
+
+#[test]
+fn the_packet_header_matches() {
+	// Packets don't implement PartialEq, so this one is compared as bytes.
+	let bytes = encode(&Packet::<PlayerIntent>::Received { index: 300 });
+	assert_eq!(bytes, RECEIVED_PACKET, "the packet header changed, as {}", literal(&bytes));
+	let decoded = Packet::<PlayerIntent>::decode(RECEIVED_PACKET).expect("RECEIVED_PACKET");
+	assert!(matches!(decoded, Packet::Received { index: 300 }));
+}
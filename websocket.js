@@ -0,0 +1,96 @@
+// JS glue for the `transport-websocket` feature. Every packet travels as a
+// single binary message; anything else coming from the server is ignored.
+// bytes the browser may hold on to for the socket before sends get turned down
+const MAX_BUFFERED_BYTES = 64 * 1024;
+
+let register_websocket = function(guest) {
+	let socket = null;
+
+	let copy_into_guest = function(bytes) {
+		let exports = guest.wasm_exports;
+		let ptr = exports.net_alloc(bytes.length);
+		// too large to be a packet
+		if(ptr == 0) {
+			return null;
+		}
+		new Uint8Array(exports.memory.buffer, ptr, bytes.length).set(bytes);
+		return ptr;
+	}
+
+	guest.env.ws_connect = function(
+		url_ptr,	// *const u8
+		url_len,	// usize
+	) {
+		let url = new TextDecoder().decode(new Uint8Array(
+			guest.wasm_exports.memory.buffer,
+			url_ptr,
+			url_len
+		));
+
+		if(socket != null) {
+			socket.onclose = null;
+			socket.close();
+		}
+
+		socket = new WebSocket(url);
+		socket.binaryType = "arraybuffer";
+		socket.onopen = () => guest.wasm_exports.ws_on_open();
+		// also fires after a failed connection attempt
+		socket.onclose = () => guest.wasm_exports.ws_on_close();
+		socket.onmessage = (event) => {
+			if(!(event.data instanceof ArrayBuffer)) {
+				return;
+			}
+
+			// the buffer is consumed by the call, no need to free it
+			let bytes = new Uint8Array(event.data);
+			let ptr = copy_into_guest(bytes);
+			if(ptr != null) {
+				guest.wasm_exports.ws_on_message(ptr, bytes.length);
+			}
+		};
+	}
+
+	// returns 0 once the socket took the message; anything else has the
+	// guest send it again later
+	guest.env.ws_send = function(
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		if(socket == null || socket.readyState != WebSocket.OPEN) {
+			return 2;
+		}
+		// the browser buffers whatever the network can't take yet, without
+		// limit; past a point, that's better left to the guest
+		if(socket.bufferedAmount > MAX_BUFFERED_BYTES) {
+			return 1;
+		}
+
+		// the memory is only lent to us, so it has to be copied right away
+		let bytes = new Uint8Array(
+			guest.wasm_exports.memory.buffer,
+			data_ptr,
+			data_len
+		).slice();
+		socket.send(bytes);
+		return 0;
+	}
+
+	guest.env.host_log = function(
+		level,		// u8, 1 = error ... 5 = trace
+		data_ptr,	// *const u8
+		data_len,	// usize
+	) {
+		let message = new TextDecoder().decode(new Uint8Array(
+			guest.wasm_exports.memory.buffer,
+			data_ptr,
+			data_len
+		));
+		let log = [console.error, console.warn, console.info, console.debug, console.debug][level - 1];
+		(log || console.log)(message);
+	}
+}
+
+miniquad_add_plugin({
+	register_plugin: register_websocket
+});
\ No newline at end of file